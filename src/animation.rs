@@ -0,0 +1,248 @@
+// Tweens of scene object transforms and light parameters, described in the scene file and played
+// back by `Animator` on the simulation clock.
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::color::Color;
+use crate::math::easing::{lerp, Easing};
+use crate::scene::{LightKind, Scene};
+use std::iter;
+
+/// What a track animates, by the name of the scene object or light it belongs to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Property {
+    Position(String),
+    RotationDegrees(String),
+    Scale(String),
+    LightColor(String),
+    /// Point and spot lights only
+    LightRange(String),
+    /// Spot lights only
+    LightCutoffDegrees(String),
+}
+
+/// A value a property takes on. Positions and scales are `Vec3`, colors `Color`, the rest
+/// `Float`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum TweenValue {
+    Float(f32),
+    Vec3([f32; 3]),
+    Color(Color),
+}
+
+/// Moves a property to `to` over `duration` seconds, from wherever the previous tween left it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Tween {
+    pub to: TweenValue,
+    pub duration: f32,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+/// What a track does once its last tween ends.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Looping {
+    /// Holds the last value
+    #[default]
+    Once,
+    /// Jumps back to `from` and plays again
+    Repeat,
+    /// Plays the tweens backwards to `from`, then forwards again
+    PingPong,
+}
+
+/// Tweens chained one after another on a single property.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Track {
+    pub property: Property,
+    pub from: TweenValue,
+    pub tweens: Vec<Tween>,
+    #[serde(default)]
+    pub looping: Looping,
+    /// Seconds before the first tween starts, for staggering tracks
+    #[serde(default)]
+    pub delay: f32,
+}
+
+/// Plays a scene's tracks, writing their values into the scene every simulation step.
+pub struct Animator {
+    /// Tracks with the index of the object or light they animate
+    tracks: Vec<(Track, usize)>,
+    elapsed: f32,
+}
+
+impl Property {
+    /// The scene object whose transform this animates, if any.
+    pub fn object_name(&self) -> Option<&str> {
+        match self {
+            Property::Position(name) | Property::RotationDegrees(name) | Property::Scale(name) => {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    fn light_name(&self) -> Option<&str> {
+        match self {
+            Property::LightColor(name)
+            | Property::LightRange(name)
+            | Property::LightCutoffDegrees(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Whether `value` is of the kind this property takes.
+    fn accepts(&self, value: &TweenValue) -> bool {
+        matches!(
+            (self, value),
+            (Property::Position(_), TweenValue::Vec3(_))
+                | (Property::Scale(_), TweenValue::Vec3(_))
+                | (Property::RotationDegrees(_), TweenValue::Float(_))
+                | (Property::LightRange(_), TweenValue::Float(_))
+                | (Property::LightCutoffDegrees(_), TweenValue::Float(_))
+                | (Property::LightColor(_), TweenValue::Color(_))
+        )
+    }
+}
+
+impl TweenValue {
+    /// Blends towards `other`, which has to be of the same kind. Angles are blended as plain
+    /// numbers, so a tween from 0 to 360 degrees makes a full turn.
+    fn lerp(&self, other: &TweenValue, t: f32) -> TweenValue {
+        match (self, other) {
+            (TweenValue::Float(a), TweenValue::Float(b)) => TweenValue::Float(lerp(*a, *b, t)),
+            (TweenValue::Vec3(a), TweenValue::Vec3(b)) => TweenValue::Vec3([
+                lerp(a[0], b[0], t),
+                lerp(a[1], b[1], t),
+                lerp(a[2], b[2], t),
+            ]),
+            (TweenValue::Color(a), TweenValue::Color(b)) => TweenValue::Color(a.lerp(b, t)),
+            _ => *other,
+        }
+    }
+}
+
+impl Track {
+    /// Seconds from the first tween's start to the last one's end.
+    fn duration(&self) -> f32 {
+        self.tweens
+            .iter()
+            .map(|tween| tween.duration.max(0.0_f32))
+            .sum()
+    }
+
+    /// The property's value `elapsed` seconds after the animation started.
+    fn value_at(&self, elapsed: f32) -> TweenValue {
+        let duration = self.duration();
+        let t = (elapsed - self.delay).max(0.0_f32);
+        let mut t = match self.looping {
+            _ if duration <= 0.0_f32 => duration,
+            Looping::Once => t.min(duration),
+            Looping::Repeat => t % duration,
+            Looping::PingPong => {
+                let t = t % (2.0_f32 * duration);
+                if t > duration {
+                    2.0_f32 * duration - t
+                } else {
+                    t
+                }
+            }
+        };
+        let mut start = self.from;
+        for (tween_i, tween) in self.tweens.iter().enumerate() {
+            let tween_duration = tween.duration.max(0.0_f32);
+            if t < tween_duration || tween_i + 1 == self.tweens.len() {
+                let progress = if tween_duration > 0.0_f32 {
+                    t / tween_duration
+                } else {
+                    1.0_f32
+                };
+                return start.lerp(&tween.to, tween.easing.apply(progress));
+            }
+            t -= tween_duration;
+            start = tween.to;
+        }
+        start
+    }
+}
+
+impl Animator {
+    /// Picks up `scene`'s tracks, leaving out those naming a missing object or light, or with
+    /// values of the wrong kind for their property.
+    pub fn new(scene: &Scene) -> Animator {
+        let mut tracks = vec![];
+        for track in scene.animations.iter() {
+            let target_i = match (track.property.object_name(), track.property.light_name()) {
+                (Some(name), _) => scene.objects.iter().position(|object| object.name == name),
+                (_, Some(name)) => scene.lights.iter().position(|light| light.name == name),
+                _ => None,
+            };
+            let target_i = match target_i {
+                Some(target_i) => target_i,
+                None => {
+                    eprintln!(
+                        "Skipping animation of {:?}: nothing by that name",
+                        track.property
+                    );
+                    continue;
+                }
+            };
+            let mut values =
+                iter::once(&track.from).chain(track.tweens.iter().map(|tween| &tween.to));
+            if !values.all(|value| track.property.accepts(value)) {
+                eprintln!(
+                    "Skipping animation of {:?}: values of the wrong kind",
+                    track.property
+                );
+                continue;
+            }
+            tracks.push((track.clone(), target_i));
+        }
+        Animator {
+            tracks,
+            elapsed: 0.0_f32,
+        }
+    }
+
+    /// Advances by `dt` seconds and sets every animated property in `scene`.
+    pub fn update(&mut self, scene: &mut Scene, dt: f32) {
+        self.elapsed += dt;
+        for (track, target_i) in self.tracks.iter() {
+            let value = track.value_at(self.elapsed);
+            match (&track.property, value) {
+                (Property::Position(_), TweenValue::Vec3(position)) => {
+                    scene.objects[*target_i].transform.position = position;
+                }
+                (Property::RotationDegrees(_), TweenValue::Float(degrees)) => {
+                    let transform = &mut scene.objects[*target_i].transform;
+                    transform.set_rotation(transform.rotation_axis(), degrees);
+                }
+                (Property::Scale(_), TweenValue::Vec3(scale)) => {
+                    scene.objects[*target_i].transform.scale = scale;
+                }
+                (Property::LightColor(_), TweenValue::Color(color)) => {
+                    scene.lights[*target_i].color = color;
+                }
+                (Property::LightRange(_), TweenValue::Float(value)) => {
+                    match &mut scene.lights[*target_i].kind {
+                        LightKind::Point { range, .. } | LightKind::Spot { range, .. } => {
+                            *range = value.max(0.0_f32);
+                        }
+                        LightKind::Directional { .. } => {}
+                    }
+                }
+                (Property::LightCutoffDegrees(_), TweenValue::Float(value)) => {
+                    if let LightKind::Spot {
+                        outer_cutoff_degrees,
+                        ..
+                    } = &mut scene.lights[*target_i].kind
+                    {
+                        *outer_cutoff_degrees = value.clamp(0.0_f32, 90.0_f32);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
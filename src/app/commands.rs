@@ -0,0 +1,309 @@
+use crate::app::input::InputState;
+use crate::app::setup_coordinate_systems;
+use crate::config::Settings;
+use crate::console::{on_off, Command};
+use crate::ogl::debug_hud::DebugHud;
+use crate::ogl::light_clusters::LightClusters;
+use crate::ogl::renderer::Renderer;
+use crate::ogl::texture_inspector::TextureInspector;
+use crate::platform::{DefaultBackend, WindowBackend};
+use crate::scene::SCENE_DIRECTORY;
+use crate::simulation::Time;
+use std::path::Path;
+
+/// Carries out a console command, returning what to print back.
+#[allow(clippy::too_many_arguments)]
+pub fn run_command(
+    command: Command,
+    settings: &mut Settings,
+    renderer: &mut Renderer,
+    framebuffer_size: (i32, i32),
+    input_state: &mut InputState,
+    debug_hud: &mut DebugHud,
+    texture_inspector: &mut TextureInspector,
+    light_clusters: &mut LightClusters,
+    time: &mut Time,
+) -> Result<String, String> {
+    match command {
+        Command::Set(name, value) => match name.as_str() {
+            "fov" => {
+                settings.renderer.fov = value.clamp(1.0_f32, 179.0_f32);
+                renderer.set_projection(setup_coordinate_systems(settings, framebuffer_size));
+                Ok(format!("fov = {}", settings.renderer.fov))
+            }
+            "move_speed" => {
+                input_state.move_speed = value;
+                Ok(format!("move_speed = {}", value))
+            }
+            "mouse_sensitivity" => {
+                input_state.mouse_sensitivity = value;
+                Ok(format!("mouse_sensitivity = {}", value))
+            }
+            "time_scale" => {
+                time.time_scale = value.clamp(Time::MIN_TIME_SCALE, Time::MAX_TIME_SCALE);
+                Ok(format!("time_scale = {}", time.time_scale))
+            }
+            "god_ray_density" => {
+                settings.post_process.god_rays.density = value.clamp(0.0_f32, 1.0_f32);
+                Ok(format!(
+                    "god_ray_density = {}",
+                    settings.post_process.god_rays.density
+                ))
+            }
+            "god_ray_decay" => {
+                settings.post_process.god_rays.decay = value.clamp(0.0_f32, 1.0_f32);
+                Ok(format!(
+                    "god_ray_decay = {}",
+                    settings.post_process.god_rays.decay
+                ))
+            }
+            "normal_length" => {
+                settings.renderer.normal_length = value.max(0.0_f32);
+                renderer.normal_length = settings.renderer.normal_length;
+                Ok(format!("normal_length = {}", renderer.normal_length))
+            }
+            "wireframe_width" => {
+                settings.renderer.wireframe_width = value.max(0.0_f32);
+                renderer.wireframe_width = settings.renderer.wireframe_width;
+                Ok(format!("wireframe_width = {}", renderer.wireframe_width))
+            }
+            "motion_blur_shutter" => {
+                settings.post_process.motion_blur.shutter_scale = value.max(0.0_f32);
+                Ok(format!(
+                    "motion_blur_shutter = {}",
+                    settings.post_process.motion_blur.shutter_scale
+                ))
+            }
+            "motion_blur_samples" => {
+                settings.post_process.motion_blur.samples = value.clamp(1.0_f32, 64.0_f32) as u32;
+                Ok(format!(
+                    "motion_blur_samples = {}",
+                    settings.post_process.motion_blur.samples
+                ))
+            }
+            "exposure" => {
+                settings.post_process.tone_mapping.exposure = value.max(0.0_f32);
+                Ok(format!(
+                    "exposure = {}",
+                    settings.post_process.tone_mapping.exposure
+                ))
+            }
+            "white_point" => {
+                settings.post_process.tone_mapping.white_point = value.max(1.0_f32);
+                Ok(format!(
+                    "white_point = {}",
+                    settings.post_process.tone_mapping.white_point
+                ))
+            }
+            "tone_map_split" => {
+                settings.post_process.tone_mapping.split = value.clamp(0.0_f32, 1.0_f32);
+                Ok(format!(
+                    "tone_map_split = {}",
+                    settings.post_process.tone_mapping.split
+                ))
+            }
+            "god_ray_exposure" => {
+                settings.post_process.god_rays.exposure = value.max(0.0_f32);
+                Ok(format!(
+                    "god_ray_exposure = {}",
+                    settings.post_process.god_rays.exposure
+                ))
+            }
+            "msaa_samples" => {
+                request_window_recreation(input_state)?;
+                settings.window.msaa_samples = value.clamp(0.0_f32, 32.0_f32) as u32;
+                Ok(format!("msaa_samples = {}", settings.window.msaa_samples))
+            }
+            "gl_version" => {
+                // 4.1 is major 4, minor 1
+                let version = [
+                    value.trunc() as u32,
+                    (value.fract() * 10.0_f32).round() as u32,
+                ];
+                if !(3..=4).contains(&version[0]) {
+                    return Err(format!("Not an OpenGL version: {}", value));
+                }
+                request_window_recreation(input_state)?;
+                settings.window.gl_version = version;
+                Ok(format!("gl_version = {}.{}", version[0], version[1]))
+            }
+            _ => Err(format!("Unknown setting: {}", name)),
+        },
+        Command::Toggle(name) => match name.as_str() {
+            "wireframe" => {
+                renderer.set_wireframe(!renderer.wireframe())?;
+                Ok(format!("wireframe {}", on_off(renderer.wireframe())))
+            }
+            "normals" => {
+                renderer.set_show_normals(!renderer.show_normals())?;
+                Ok(format!("normals {}", on_off(renderer.show_normals())))
+            }
+            "wireframe_overlay" => {
+                renderer.set_show_wireframe_overlay(!renderer.show_wireframe_overlay())?;
+                Ok(format!(
+                    "wireframe_overlay {}",
+                    on_off(renderer.show_wireframe_overlay())
+                ))
+            }
+            "lights" => {
+                settings.renderer.show_lights = !settings.renderer.show_lights;
+                Ok(format!("lights {}", on_off(settings.renderer.show_lights)))
+            }
+            "light_ranges" => {
+                settings.renderer.show_light_ranges = !settings.renderer.show_light_ranges;
+                Ok(format!(
+                    "light_ranges {}",
+                    on_off(settings.renderer.show_light_ranges)
+                ))
+            }
+            "light_heatmap" => {
+                light_clusters.show_heatmap = !light_clusters.show_heatmap;
+                let (light_count, max_cluster_lights) = light_clusters.counts();
+                Ok(format!(
+                    "light_heatmap {} ({} lights, at most {} in a cluster)",
+                    on_off(light_clusters.show_heatmap),
+                    light_count,
+                    max_cluster_lights
+                ))
+            }
+            "labels" => {
+                settings.renderer.show_labels = !settings.renderer.show_labels;
+                Ok(format!("labels {}", on_off(settings.renderer.show_labels)))
+            }
+            "debug_draw" => {
+                settings.renderer.debug_draw = !settings.renderer.debug_draw;
+                Ok(format!(
+                    "debug_draw {}",
+                    on_off(settings.renderer.debug_draw)
+                ))
+            }
+            "hud" => {
+                debug_hud.visible = !debug_hud.visible;
+                Ok(format!("hud {}", on_off(debug_hud.visible)))
+            }
+            "textures" => {
+                texture_inspector.visible = !texture_inspector.visible;
+                Ok(format!("textures {}", on_off(texture_inspector.visible)))
+            }
+            "pause" => {
+                time.toggle_pause();
+                Ok(format!("pause {}", on_off(time.paused)))
+            }
+            "culling" => {
+                renderer.frustum_culling = !renderer.frustum_culling;
+                Ok(format!("culling {}", on_off(renderer.frustum_culling)))
+            }
+            "instancing" => {
+                renderer.instancing = !renderer.instancing;
+                Ok(format!("instancing {}", on_off(renderer.instancing)))
+            }
+            "multi_draw" => {
+                renderer.multi_draw_indirect = !renderer.multi_draw_indirect;
+                Ok(format!(
+                    "multi_draw {}",
+                    on_off(renderer.multi_draw_indirect)
+                ))
+            }
+            "occlusion" => {
+                renderer.occlusion_culling = !renderer.occlusion_culling;
+                Ok(format!("occlusion {}", on_off(renderer.occlusion_culling)))
+            }
+            "freeze_culling" => {
+                renderer.set_culling_frozen(!renderer.culling_frozen());
+                Ok(format!(
+                    "freeze_culling {}",
+                    on_off(renderer.culling_frozen())
+                ))
+            }
+            "fullscreen" => {
+                input_state.fullscreen_toggle_requested = true;
+                Ok("Toggling fullscreen".to_string())
+            }
+            "god_rays" => {
+                let god_rays = &mut settings.post_process.god_rays;
+                god_rays.enabled = !god_rays.enabled;
+                Ok(format!("god_rays {}", on_off(god_rays.enabled)))
+            }
+            "motion_blur" => {
+                let motion_blur = &mut settings.post_process.motion_blur;
+                motion_blur.enabled = !motion_blur.enabled;
+                Ok(format!("motion_blur {}", on_off(motion_blur.enabled)))
+            }
+            _ => Err(format!("Unknown toggle: {}", name)),
+        },
+        Command::LoadScene(file_path) => {
+            // Bare names are looked up next to the default scene
+            let file_path = if Path::new(&file_path).exists() {
+                file_path
+            } else {
+                Path::new(SCENE_DIRECTORY)
+                    .join(&file_path)
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            input_state.scene_load_request = Some(file_path.clone());
+            Ok(format!("Loading scene {}", file_path))
+        }
+        Command::ToneMap(operator) => {
+            settings.post_process.tone_mapping.operator = operator;
+            Ok(format!("Tone mapping with {}", operator.name()))
+        }
+        Command::CompareToneMap(Some(operator)) => {
+            let tone_mapping = &mut settings.post_process.tone_mapping;
+            tone_mapping.compare = true;
+            tone_mapping.compare_operator = operator;
+            Ok(format!(
+                "Comparing {} with {}",
+                tone_mapping.operator.name(),
+                operator.name()
+            ))
+        }
+        Command::CompareToneMap(None) => {
+            settings.post_process.tone_mapping.compare = false;
+            Ok("Comparison off".to_string())
+        }
+        Command::ReloadShaders => {
+            input_state.shader_reload_requested = true;
+            Ok("Rebuilding scene shaders and materials".to_string())
+        }
+        Command::BakeProbes => {
+            input_state.probe_bake_requested = true;
+            Ok("Baking the light probes".to_string())
+        }
+        Command::CaptureFrame => {
+            input_state.renderdoc_capture_requested = true;
+            Ok("Capturing the next frame".to_string())
+        }
+        Command::RecreateWindow => {
+            request_window_recreation(input_state)?;
+            Ok("Recreating the window".to_string())
+        }
+        Command::InspectTexture(texture_i) => {
+            texture_inspector.visible = true;
+            texture_inspector.selected = texture_i;
+            Ok(match texture_i {
+                Some(texture_i) => format!("Inspecting texture {}", texture_i),
+                None => "Inspecting all textures".to_string(),
+            })
+        }
+        Command::InspectChannel(channel) => {
+            texture_inspector.visible = true;
+            texture_inspector.channel = channel;
+            Ok(format!("Inspecting channel {}", channel.name()))
+        }
+        Command::InspectMip(level) => {
+            texture_inspector.visible = true;
+            texture_inspector.mip_level = level;
+            Ok(format!("Inspecting mip level {}", level))
+        }
+    }
+}
+
+/// Ends the frame loop so the window and its context are created again from the settings.
+fn request_window_recreation(input_state: &mut InputState) -> Result<(), String> {
+    if !DefaultBackend::can_recreate() {
+        return Err("This window backend cannot recreate its window; restart instead".to_string());
+    }
+    input_state.window_recreation_requested = true;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::debug;
+use crate::math::bounds::BoundingSphere;
+use crate::math::color::Color;
+use crate::math::glm;
+use crate::ogl::font_renderer::WorldTextSize;
+use crate::ogl::line_renderer::DepthMode;
+use crate::ogl::renderer::Renderer;
+use crate::picking::mesh_bounds;
+use crate::scene::{Light, LightKind, Scene, WorldMatrices};
+
+// Size of light icons, and length of the arrows and spot light cones drawn without ranges
+pub const LIGHT_ICON_SIZE: f32 = 0.3_f32;
+const LIGHT_SHAPE_LENGTH: f32 = 1.0_f32;
+// Colors of the names `toggle labels` shows; lights are named in their own color
+const OBJECT_LABEL_COLOR: Color = Color::WHITE;
+const PROBE_LABEL_COLOR: Color = Color::rgb(0.4_f32, 1.0_f32, 0.6_f32);
+const REFLECTION_PROBE_LABEL_COLOR: Color = Color::rgb(0.4_f32, 0.8_f32, 1.0_f32);
+
+/// Queues what `toggle debug_draw` shows: the bounds objects are culled with, the culling
+/// frustum while it is frozen, a ground grid, and the selected object's axes and bounding sphere.
+pub fn draw_debug_shapes(
+    scene: &Scene,
+    world_matrices: &WorldMatrices,
+    renderer: &Renderer,
+    selected_object: Option<usize>,
+) {
+    debug::draw_grid(
+        &glm::vec3(0.0_f32, 0.0_f32, 0.0_f32),
+        1.0_f32,
+        20,
+        Color::rgb(0.3_f32, 0.3_f32, 0.3_f32),
+        DepthMode::Tested,
+    );
+    for (object_i, object) in scene.objects.iter().enumerate() {
+        if let Some(bounds) = mesh_bounds(&object.mesh) {
+            debug::draw_aabb(
+                &bounds.transformed(world_matrices.get(object_i)),
+                Color::rgb(0.1_f32, 0.6_f32, 1.0_f32),
+                DepthMode::Tested,
+            );
+        }
+    }
+    if renderer.culling_frozen() {
+        debug::draw_frustum(
+            renderer.culling_frustum(),
+            Color::rgb(1.0_f32, 0.2_f32, 0.8_f32),
+            DepthMode::Tested,
+        );
+    }
+    if let Some(object_i) = selected_object {
+        let world_from_object = world_matrices.get(object_i);
+        debug::draw_axes(world_from_object, 1.0_f32, DepthMode::Overlay);
+        if let Some(bounds) = mesh_bounds(&scene.objects[object_i].mesh) {
+            debug::draw_sphere(
+                &BoundingSphere::from_aabb(&bounds).transformed(world_from_object),
+                Color::rgb(1.0_f32, 0.8_f32, 0.1_f32),
+                DepthMode::Tested,
+            );
+        }
+    }
+}
+
+/// Queues what `toggle lights` shows besides the light icons: an arrow along each directional
+/// light and the cone of each spot light, reaching out to their range with `ranges`, which also
+/// shows the sphere each point light reaches.
+pub fn draw_light_shapes(lights: &[Light], ranges: bool) {
+    for light in lights {
+        match light.kind {
+            LightKind::Directional {
+                position,
+                direction,
+            } => {
+                let start = glm::make_vec3(&position);
+                let direction = glm::normalize(&glm::make_vec3(&direction));
+                debug::draw_arrow(
+                    &start,
+                    &(start + direction * LIGHT_SHAPE_LENGTH),
+                    light.color,
+                    DepthMode::Overlay,
+                );
+            }
+            LightKind::Point { position, range } => {
+                if ranges {
+                    debug::draw_sphere(
+                        &BoundingSphere {
+                            center: glm::make_vec3(&position),
+                            radius: range,
+                        },
+                        light.color,
+                        DepthMode::Tested,
+                    );
+                }
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                range,
+                outer_cutoff_degrees,
+            } => {
+                debug::draw_cone(
+                    &glm::make_vec3(&position),
+                    &glm::make_vec3(&direction),
+                    if ranges { range } else { LIGHT_SHAPE_LENGTH },
+                    outer_cutoff_degrees,
+                    light.color,
+                    DepthMode::Tested,
+                );
+            }
+        }
+    }
+}
+
+/// Queues what `toggle labels` shows: the names of the scene's objects over their bounds, and of
+/// its lights and probes over their positions. Lights show through the scene like their icons;
+/// the rest hide behind it.
+pub fn draw_scene_labels(scene: &Scene, world_matrices: &WorldMatrices, size: WorldTextSize) {
+    for (object_i, object) in scene.objects.iter().enumerate() {
+        let world_from_object = world_matrices.get(object_i);
+        // Over the top of the bounds, so the object does not hide its own name
+        let position = match mesh_bounds(&object.mesh) {
+            Some(bounds) => {
+                let bounds = bounds.transformed(world_from_object);
+                let center = bounds.center();
+                glm::vec3(center.x, bounds.max.y, center.z)
+            }
+            None => glm::xyz(&glm::column(world_from_object, 3)),
+        };
+        debug::draw_label(
+            &position,
+            &object.name,
+            size,
+            OBJECT_LABEL_COLOR,
+            DepthMode::Tested,
+        );
+    }
+    for light in scene.lights.iter() {
+        let above_icon = glm::vec3(0.0_f32, LIGHT_ICON_SIZE * 0.5_f32, 0.0_f32);
+        debug::draw_label(
+            &(light.kind.position() + above_icon),
+            &light.name,
+            size,
+            light.color,
+            DepthMode::Overlay,
+        );
+    }
+    for probe in scene.probes.iter() {
+        debug::draw_label(
+            &glm::make_vec3(&probe.position),
+            &probe.name,
+            size,
+            PROBE_LABEL_COLOR,
+            DepthMode::Tested,
+        );
+    }
+    for probe in scene.reflection_probes.iter() {
+        debug::draw_label(
+            &glm::make_vec3(&probe.position),
+            &probe.name,
+            size,
+            REFLECTION_PROBE_LABEL_COLOR,
+            DepthMode::Tested,
+        );
+    }
+}
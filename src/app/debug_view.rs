@@ -0,0 +1,64 @@
+use crate::config::Settings;
+use crate::ogl::graphics::Camera;
+use crate::ogl::render_target::{RenderTarget, SharedTargetPresenter};
+use crate::platform::{DefaultBackend, WindowBackend};
+
+/// Second window showing the scene from a fixed camera. The view is rendered in the main context
+/// into `target`, which the window's own context only presents.
+pub struct DebugView {
+    pub window: DefaultBackend,
+    pub target: RenderTarget,
+    pub presenter: SharedTargetPresenter,
+    pub camera: Camera,
+}
+
+/// Opens the debug view window next to `window`, whose context is current again on return.
+pub fn open_debug_view(window: &mut DefaultBackend, settings: &Settings) -> Option<DebugView> {
+    let mut debug_window = match window.create_shared(
+        "Learn OpenGL - debug view",
+        settings.debug_view.width,
+        settings.debug_view.height,
+    ) {
+        Ok(debug_window) => debug_window,
+        Err(e) => {
+            eprintln!("Could not open the debug view: {}", e);
+            window.make_current();
+            return None;
+        }
+    };
+
+    window.make_current();
+    let (width, height) = debug_window.framebuffer_size();
+    let target = RenderTarget::new(&window.gl_context(), width, height)
+        .expect("Render target setup failure");
+    target.set_label(&window.gl_context(), "debug view");
+    debug_window.make_current();
+    let presenter = SharedTargetPresenter::new(&debug_window.gl_context(), &target);
+    window.make_current();
+    match presenter {
+        Ok(presenter) => Some(DebugView {
+            window: debug_window,
+            target,
+            presenter,
+            camera: settings.debug_view.camera.to_camera(),
+        }),
+        Err(e) => {
+            eprintln!("Could not open the debug view: {}", e);
+            None
+        }
+    }
+}
+
+/// Frees the debug view's GL objects in the contexts owning them and closes its window.
+pub fn close_debug_view(view: DebugView, window: &mut DefaultBackend) {
+    let DebugView {
+        window: mut debug_window,
+        target,
+        presenter,
+        ..
+    } = view;
+    debug_window.make_current();
+    presenter.delete();
+    window.make_current();
+    drop(target);
+}
@@ -0,0 +1,312 @@
+use crate::gizmo::GizmoMode;
+use crate::input_map::{apply_dead_zone, InputAction, InputMap, LookFilter, Trigger};
+use crate::math::glm;
+use crate::math::ray::Ray;
+use crate::ogl::debug_hud::DebugHud;
+use crate::ogl::graphics::Camera;
+use crate::ogl::renderer::Renderer;
+use crate::platform::{Action, Event, WindowBackend};
+use crate::simulation::Time;
+use std::mem;
+
+pub struct MouseInputState {
+    pub x: f32,
+    pub y: f32,
+}
+
+pub struct InputState {
+    pub mouse: Option<MouseInputState>,
+    pub move_speed: f32,
+    pub mouse_sensitivity: f32,
+    pub gamepad_dead_zone: f32,
+    pub gamepad_look_speed: f32,
+    /// Window coordinates of a pick click not yet handled by the frame loop
+    pub pick_request: Option<(f32, f32)>,
+    /// Pointer position in window coordinates while the pick button is held
+    pub drag_cursor: Option<(f32, f32)>,
+    /// Set while a gizmo drag owns the mouse, suspending mouse look
+    pub look_locked: bool,
+    /// Mouse look motion received since the last frame, applied through `look_filter`
+    pub look_delta: (f32, f32),
+    pub look_filter: LookFilter,
+    pub gizmo_mode_request: Option<GizmoMode>,
+    pub capture_toggle_requested: bool,
+    pub fullscreen_toggle_requested: bool,
+    pub vsync_cycle_requested: bool,
+    pub scene_reload_requested: bool,
+    pub scene_save_requested: bool,
+    /// Scene file to switch to, from the console
+    pub scene_load_request: Option<String>,
+    pub shader_reload_requested: bool,
+    pub probe_bake_requested: bool,
+    pub renderdoc_capture_requested: bool,
+    pub visualization_cycle_requested: bool,
+    pub texture_inspector_toggle_requested: bool,
+    pub launch_requested: bool,
+    /// Set by settings fixed at context creation; the frame loop ends and a new window opens
+    pub window_recreation_requested: bool,
+}
+
+/// How the player steers a lesson that takes the movement keys: -1 for full speed left to 1 for
+/// full speed right, from the movement keys and the gamepad's left stick.
+pub fn steer_input(
+    window: &impl WindowBackend,
+    input_map: &InputMap,
+    input_state: &InputState,
+) -> f32 {
+    let mut direction = 0.0_f32;
+    if input_map.is_down(window, InputAction::MoveLeft) {
+        direction -= 1.0_f32;
+    }
+    if input_map.is_down(window, InputAction::MoveRight) {
+        direction += 1.0_f32;
+    }
+    if let Some(gamepad) = window.gamepad() {
+        let (stick_x, _) = apply_dead_zone(gamepad.left_stick, input_state.gamepad_dead_zone);
+        direction += stick_x;
+    }
+    direction.clamp(-1.0_f32, 1.0_f32)
+}
+
+/// World-space ray through `cursor`, given in window coordinates.
+pub fn cursor_ray(
+    window: &impl WindowBackend,
+    cursor: (f32, f32),
+    camera: &Camera,
+    renderer: &Renderer,
+) -> Ray {
+    let (width, height) = window.size();
+    Ray::from_screen(
+        cursor,
+        (width as f32, height as f32),
+        &camera.view_matrix(),
+        renderer.projection_from_view(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_events(
+    window: &mut impl WindowBackend,
+    events: Vec<Event>,
+    input_map: &InputMap,
+    input_state: &mut InputState,
+    debug_hud: &mut DebugHud,
+    time: &mut Time,
+) {
+    for event in events {
+        let triggered = match event {
+            Event::Key(key, action) => Some((Trigger::Key(key), action)),
+            Event::MouseButton(button, action) => Some((Trigger::Mouse(button), action)),
+            _ => None,
+        };
+        if let Some((trigger, action)) = triggered {
+            for input_action in input_map.actions(trigger, window.modifiers()) {
+                process_action(window, input_action, action, input_state, debug_hud, time);
+            }
+        }
+
+        match event {
+            Event::FramebufferSize(width, height) => {
+                window.gl_context().set_viewport(width, height);
+            }
+
+            Event::ContentScale(scale) => {
+                println!("Content scale {}", scale);
+            }
+
+            Event::CursorPos(mouse_x, mouse_y) => {
+                let mouse_x = mouse_x as f32;
+                let mouse_y = mouse_y as f32;
+                if input_state.mouse.is_none() {
+                    input_state.mouse = Some(MouseInputState {
+                        x: mouse_x,
+                        y: mouse_y,
+                    });
+                }
+                let last_mouse = input_state.mouse.as_ref().unwrap();
+                let (x_offset, y_offset) = (mouse_x - last_mouse.x, last_mouse.y - mouse_y);
+                // The drag pointer follows relative motion so it also works with a captured cursor
+                if let Some(drag_cursor) = input_state.drag_cursor.as_mut() {
+                    drag_cursor.0 += x_offset;
+                    drag_cursor.1 -= y_offset;
+                }
+                // A free cursor is for pointing at things, not looking around
+                if !input_state.look_locked && window.is_cursor_captured() {
+                    input_state.look_delta.0 += x_offset;
+                    input_state.look_delta.1 += y_offset;
+                }
+
+                input_state.mouse = Some(MouseInputState {
+                    x: mouse_x,
+                    y: mouse_y,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_action(
+    window: &mut impl WindowBackend,
+    input_action: InputAction,
+    action: Action,
+    input_state: &mut InputState,
+    debug_hud: &mut DebugHud,
+    time: &mut Time,
+) {
+    match (input_action, action) {
+        (InputAction::Quit, Action::Press) => {
+            window.set_should_close(true);
+        }
+
+        (InputAction::CycleVsync, Action::Press) => {
+            input_state.vsync_cycle_requested = true;
+        }
+
+        (InputAction::ToggleStats, Action::Press) => {
+            debug_hud.visible = !debug_hud.visible;
+        }
+
+        (InputAction::Pick, Action::Press) => {
+            // With the cursor captured for mouse look, pick through the screen center
+            let cursor = if window.is_cursor_captured() {
+                let (width, height) = window.size();
+                (width as f32 * 0.5_f32, height as f32 * 0.5_f32)
+            } else {
+                let (cursor_x, cursor_y) = window.cursor_pos();
+                (cursor_x as f32, cursor_y as f32)
+            };
+            input_state.pick_request = Some(cursor);
+            input_state.drag_cursor = Some(cursor);
+        }
+
+        (InputAction::Pick, Action::Release) => {
+            input_state.drag_cursor = None;
+        }
+
+        (InputAction::GizmoTranslate, Action::Press) => {
+            input_state.gizmo_mode_request = Some(GizmoMode::Translate);
+        }
+
+        (InputAction::GizmoRotate, Action::Press) => {
+            input_state.gizmo_mode_request = Some(GizmoMode::Rotate);
+        }
+
+        (InputAction::GizmoScale, Action::Press) => {
+            input_state.gizmo_mode_request = Some(GizmoMode::Scale);
+        }
+
+        (InputAction::TogglePause, Action::Press) => {
+            time.toggle_pause();
+            println!(
+                "Simulation {}",
+                if time.paused { "paused" } else { "resumed" }
+            );
+        }
+
+        (InputAction::StepSimulation, Action::Press) => {
+            time.request_step();
+        }
+
+        (InputAction::SlowDown, Action::Press) => {
+            time.scale_by(0.5_f32);
+            println!("Time scale {}", time.time_scale);
+        }
+
+        (InputAction::SpeedUp, Action::Press) => {
+            time.scale_by(2.0_f32);
+            println!("Time scale {}", time.time_scale);
+        }
+
+        (InputAction::ToggleRecording, Action::Press) => {
+            input_state.capture_toggle_requested = true;
+        }
+
+        (InputAction::ToggleCursorCapture, Action::Press) => {
+            window.set_cursor_captured(!window.is_cursor_captured());
+            // The cursor jumps when captured or released; measure motion from wherever it lands
+            input_state.mouse = None;
+        }
+
+        (InputAction::ToggleFullscreen, Action::Press) => {
+            input_state.fullscreen_toggle_requested = true;
+        }
+
+        (InputAction::ReloadScene, Action::Press) => {
+            input_state.scene_reload_requested = true;
+        }
+
+        (InputAction::SaveScene, Action::Press) => {
+            input_state.scene_save_requested = true;
+        }
+
+        (InputAction::RenderDocCapture, Action::Press) => {
+            input_state.renderdoc_capture_requested = true;
+        }
+
+        (InputAction::CycleVisualization, Action::Press) => {
+            input_state.visualization_cycle_requested = true;
+        }
+
+        (InputAction::ToggleTextureInspector, Action::Press) => {
+            input_state.texture_inspector_toggle_requested = true;
+        }
+        (InputAction::Launch, Action::Press) => {
+            input_state.launch_requested = true;
+        }
+        _ => {}
+    }
+}
+
+pub fn process_inputs(
+    window: &impl WindowBackend,
+    input_map: &InputMap,
+    camera: &mut Camera,
+    input_state: &mut InputState,
+    delta_time: f32,
+) {
+    let look_delta = mem::replace(&mut input_state.look_delta, (0.0, 0.0));
+    let (look_x, look_y) = input_state.look_filter.filter(look_delta, delta_time);
+    if look_x != 0.0 || look_y != 0.0 {
+        camera.yaw += look_x * input_state.mouse_sensitivity;
+        camera.pitch =
+            (camera.pitch + look_y * input_state.mouse_sensitivity).clamp(-89.0_f32, 89.0_f32);
+        camera.update_front();
+    }
+
+    let camera_speed = delta_time * input_state.move_speed;
+    if input_map.is_down(window, InputAction::MoveForward) {
+        camera.position += camera_speed * camera.front;
+    }
+    if input_map.is_down(window, InputAction::MoveBackward) {
+        camera.position -= camera_speed * camera.front;
+    }
+    if input_map.is_down(window, InputAction::MoveLeft) {
+        camera.position -= camera_speed * glm::normalize(&glm::cross(&camera.front, &camera.up));
+    }
+    if input_map.is_down(window, InputAction::MoveRight) {
+        camera.position += camera_speed * glm::normalize(&glm::cross(&camera.front, &camera.up));
+    }
+
+    if let Some(gamepad) = window.gamepad() {
+        let (move_x, move_y) = apply_dead_zone(gamepad.left_stick, input_state.gamepad_dead_zone);
+        // Full right trigger moves 4x as fast, full left trigger a quarter as fast
+        let gamepad_speed = camera_speed
+            * (1.0_f32 + 3.0_f32 * gamepad.right_trigger)
+            * (1.0_f32 - 0.75_f32 * gamepad.left_trigger);
+        // Stick +y points down, i.e. backwards
+        camera.position -= gamepad_speed * move_y * camera.front;
+        camera.position +=
+            gamepad_speed * move_x * glm::normalize(&glm::cross(&camera.front, &camera.up));
+
+        if !input_state.look_locked {
+            let (look_x, look_y) =
+                apply_dead_zone(gamepad.right_stick, input_state.gamepad_dead_zone);
+            let look_speed = input_state.gamepad_look_speed * delta_time;
+            camera.yaw += look_x * look_speed;
+            camera.pitch = (camera.pitch - look_y * look_speed).clamp(-89.0_f32, 89.0_f32);
+            camera.update_front();
+        }
+    }
+}
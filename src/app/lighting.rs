@@ -0,0 +1,259 @@
+use crate::app::scene_setup::SceneRenderables;
+use crate::math::glm;
+use crate::ogl::context::GlContext;
+use crate::ogl::environment_capture::{cubemap_face_projection, CaptureMode, EnvironmentCapture};
+use crate::ogl::graphics::Camera;
+use crate::ogl::light_clusters::LightClusters;
+use crate::ogl::light_probes::{self, LightProbes, ProbeIrradiance, PROBE_CAPTURE_SIZE};
+use crate::ogl::reflection_probes::{ReflectionProbes, REFLECTION_CAPTURE_SIZE};
+use crate::ogl::render_target::RenderTarget;
+use crate::ogl::renderer::Renderer;
+use crate::scene::{Scene, Shading, WorldMatrices};
+use std::mem;
+
+/// Lights and baked probes shared by the lit programs of whichever scene is loaded, and the
+/// environment mirrors reflect.
+pub struct SceneLighting {
+    pub clusters: LightClusters,
+    pub light_probes: LightProbes,
+    pub reflection_probes: ReflectionProbes,
+    /// Set up once a scene has a mirror
+    pub environment: Option<EnvironmentCapture>,
+}
+
+impl SceneLighting {
+    pub fn new(gl_context: &GlContext) -> SceneLighting {
+        SceneLighting {
+            clusters: LightClusters::new(gl_context),
+            light_probes: LightProbes::new(gl_context),
+            reflection_probes: ReflectionProbes::new(gl_context),
+            environment: None,
+        }
+    }
+
+    /// Sorts the scene's lights into clusters for the view of `camera` and binds them and the
+    /// probes to the lit programs. Mirror reflections reuse the camera's clusters, so their lit
+    /// objects are shaded as if seen from the camera.
+    pub fn update(
+        &mut self,
+        scene_renderables: &SceneRenderables,
+        scene: &Scene,
+        camera: &Camera,
+        renderer: &Renderer,
+        viewport_size: (i32, i32),
+    ) {
+        self.clusters.update(
+            &scene.lights,
+            &camera.view_matrix(),
+            renderer.projection_from_view(),
+            viewport_size,
+        );
+        for lit_program in scene_renderables.lit_programs.iter() {
+            let program = &lit_program.program;
+            self.clusters.bind(program, &lit_program.cluster_uniforms);
+            self.light_probes
+                .bind(program, &lit_program.light_probe_uniforms);
+            self.reflection_probes.bind(
+                program,
+                &lit_program.reflection_probe_uniforms,
+                &camera.position,
+            );
+        }
+        if let Some(environment) = &self.environment {
+            let mirror_program = &scene_renderables.mirror_program;
+            environment.bind(
+                &mirror_program.program,
+                &mirror_program.environment_uniforms,
+                &camera.position,
+            );
+        }
+    }
+
+    /// Captures the static scene around each light probe and then each reflection probe, and
+    /// uploads the results. The captures see lit objects with the probes as they were before,
+    /// and reflection captures reflect nothing.
+    pub fn bake(
+        &mut self,
+        scene_renderables: &mut SceneRenderables,
+        scene: &Scene,
+        world_matrices: &WorldMatrices,
+        renderer: &mut Renderer,
+        gl_context: &GlContext,
+    ) {
+        scene_renderables.probes_baked = true;
+        // The probes of the scene loaded before go, even when this one has none
+        if scene.probes.is_empty() && scene.reflection_probes.is_empty() {
+            self.light_probes.upload(&[], &[]);
+            self.reflection_probes.clear();
+            return;
+        }
+        let projection_from_view = *renderer.projection_from_view();
+        renderer.set_projection(cubemap_face_projection(0.05_f32, 100.0_f32));
+        // Every face sees a different part of the scene, and none is the camera's view
+        let frustum_culling = mem::replace(&mut renderer.frustum_culling, false);
+        let occlusion_culling = mem::replace(&mut renderer.occlusion_culling, false);
+
+        let mut irradiance: Vec<ProbeIrradiance> = vec![];
+        if !scene.probes.is_empty() {
+            let target = RenderTarget::new(gl_context, PROBE_CAPTURE_SIZE, PROBE_CAPTURE_SIZE)
+                .expect("Render target setup failure");
+            target.set_label(gl_context, "light probe capture");
+            irradiance = scene
+                .probes
+                .iter()
+                .map(|probe| {
+                    light_probes::capture(
+                        gl_context,
+                        &glm::make_vec3(&probe.position),
+                        &target,
+                        |camera| {
+                            self.draw_probe_face(
+                                camera,
+                                PROBE_CAPTURE_SIZE,
+                                scene_renderables,
+                                scene,
+                                world_matrices,
+                                renderer,
+                            )
+                        },
+                    )
+                })
+                .collect();
+        }
+        self.light_probes.upload(&scene.probes, &irradiance);
+        if scene.reflection_probes.is_empty() {
+            self.reflection_probes.clear();
+        } else {
+            let target =
+                RenderTarget::new(gl_context, REFLECTION_CAPTURE_SIZE, REFLECTION_CAPTURE_SIZE)
+                    .expect("Render target setup failure");
+            target.set_label(gl_context, "reflection probe capture");
+            let probes = self
+                .reflection_probes
+                .begin_bake(&scene.reflection_probes, REFLECTION_CAPTURE_SIZE);
+            for (probe_i, probe) in probes.iter().enumerate() {
+                let cameras = light_probes::cube_face_cameras(&glm::make_vec3(&probe.position));
+                for (face, camera) in cameras.iter().enumerate() {
+                    target.bind(gl_context);
+                    self.draw_probe_face(
+                        camera,
+                        REFLECTION_CAPTURE_SIZE,
+                        scene_renderables,
+                        scene,
+                        world_matrices,
+                        renderer,
+                    );
+                    self.reflection_probes
+                        .capture_face(gl_context, probe_i, face, &target);
+                }
+            }
+            self.reflection_probes.end_bake();
+        }
+
+        renderer.frustum_culling = frustum_culling;
+        renderer.occlusion_culling = occlusion_culling;
+        renderer.set_projection(projection_from_view);
+        println!(
+            "Baked {} light probes and {} reflection probes",
+            self.light_probes.probe_count(),
+            self.reflection_probes.probe_count()
+        );
+    }
+
+    /// Draws the static objects of the scene for a probe's `camera` into the bound target, `size`
+    /// pixels across.
+    fn draw_probe_face(
+        &mut self,
+        camera: &Camera,
+        size: i32,
+        scene_renderables: &SceneRenderables,
+        scene: &Scene,
+        world_matrices: &WorldMatrices,
+        renderer: &mut Renderer,
+    ) {
+        renderer.begin_view(camera);
+        if scene_renderables.has_lit_objects {
+            self.update(scene_renderables, scene, camera, renderer, (size, size));
+        }
+        for (mesh, material, _) in scene_renderables.static_batches.iter() {
+            renderer.submit(mesh, material, &glm::identity());
+        }
+        let static_objects = scene.static_objects();
+        for (object_i, mesh, material) in scene_renderables.objects.iter() {
+            if static_objects[*object_i] {
+                renderer.submit(mesh, material, world_matrices.get(*object_i));
+            }
+        }
+        renderer.end_view("probe capture");
+    }
+
+    /// Renders the scene around the first mirror object into the environment cubemap, as much of
+    /// it as `mode` asks for, setting the capture up the first time and whenever `size` changes.
+    /// Leaves the capture's framebuffer bound and the renderer on its last face's view.
+    #[allow(clippy::too_many_arguments)]
+    pub fn capture_environment(
+        &mut self,
+        scene_renderables: &SceneRenderables,
+        scene: &Scene,
+        world_matrices: &WorldMatrices,
+        renderer: &mut Renderer,
+        mode: CaptureMode,
+        size: i32,
+        gl_context: &GlContext,
+    ) {
+        let mirror_i = match scene_renderables.mirror_object {
+            Some(mirror_i) => mirror_i,
+            None => return,
+        };
+        let mut environment = match self.environment.take() {
+            Some(environment) if environment.size() == size => environment,
+            _ => EnvironmentCapture::new(gl_context, size)
+                .expect("Environment capture setup failure"),
+        };
+        let position = glm::xyz(&glm::column(world_matrices.get(mirror_i), 3));
+        let projection_from_view = *renderer.projection_from_view();
+        renderer.set_projection(cubemap_face_projection(0.05_f32, 100.0_f32));
+        // Occlusion queries answer for the camera's view; each face still culls to its frustum
+        let occlusion_culling = mem::replace(&mut renderer.occlusion_culling, false);
+        environment.capture(&position, mode, |camera| {
+            self.draw_environment_face(
+                camera,
+                size,
+                scene_renderables,
+                scene,
+                world_matrices,
+                renderer,
+            )
+        });
+        renderer.occlusion_culling = occlusion_culling;
+        renderer.set_projection(projection_from_view);
+        self.environment = Some(environment);
+    }
+
+    /// Draws the scene but its mirrors, which would sample the faces being rendered, for an
+    /// environment capture's `camera` into the bound target, `size` pixels across.
+    fn draw_environment_face(
+        &mut self,
+        camera: &Camera,
+        size: i32,
+        scene_renderables: &SceneRenderables,
+        scene: &Scene,
+        world_matrices: &WorldMatrices,
+        renderer: &mut Renderer,
+    ) {
+        renderer.begin_view(camera);
+        if scene_renderables.has_lit_objects {
+            self.update(scene_renderables, scene, camera, renderer, (size, size));
+        }
+        // Mirrors are never batched
+        for (mesh, material, _) in scene_renderables.static_batches.iter() {
+            renderer.submit(mesh, material, &glm::identity());
+        }
+        for (object_i, mesh, material) in scene_renderables.objects.iter() {
+            if scene.objects[*object_i].shading != Shading::Mirror {
+                renderer.submit(mesh, material, world_matrices.get(*object_i));
+            }
+        }
+        renderer.end_view("environment capture");
+    }
+}
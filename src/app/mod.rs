@@ -0,0 +1,1331 @@
+// Everything a window draws and the frame loop that drives it: the scene with its GPU resources,
+// the lesson's runner, the debug tools, and each frame's steps from input through simulation and
+// rendering to presenting. `main` opens the window, picks the lesson and runs `App` frame by
+// frame until it closes.
+
+mod commands;
+mod debug_draw;
+mod debug_view;
+mod input;
+mod lighting;
+mod scene_setup;
+
+use crate::animation::Animator;
+use crate::app::commands::run_command;
+use crate::app::debug_draw::{
+    draw_debug_shapes, draw_light_shapes, draw_scene_labels, LIGHT_ICON_SIZE,
+};
+use crate::app::debug_view::{close_debug_view, open_debug_view, DebugView};
+use crate::app::input::{cursor_ray, process_events, process_inputs, steer_input, InputState};
+use crate::app::lighting::SceneLighting;
+use crate::app::scene_setup::{setup_scene, submit_scene, SceneRenderables};
+use crate::cli::CliArgs;
+use crate::config::{PickingMode, Settings, TextSettings};
+use crate::console::Console;
+use crate::debug;
+use crate::flipbook::UvRect;
+use crate::gizmo::Gizmo;
+use crate::input_map::{InputAction, InputMap, LookFilter, Trigger};
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner};
+use crate::math::color::Color;
+use crate::math::glm::{self, Mat4};
+use crate::ogl::billboard_renderer::{create_marker_texture, Billboard, BillboardRenderer};
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_hud::DebugHud;
+use crate::ogl::font_renderer::{
+    FontRenderer, GlyphRendering, WorldTextSize, SIGNED_DISTANCE_PIXEL_HEIGHT,
+};
+use crate::ogl::frame_capture::{write_rgba_png, FrameCapture};
+use crate::ogl::graphics::{Camera, Mesh};
+use crate::ogl::id_buffer::IdBuffer;
+use crate::ogl::line_renderer::LineRenderer;
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::post_process::PostProcess;
+#[cfg(feature = "ui")]
+use crate::ogl::post_process::ToneMapOperator;
+use crate::ogl::render_target::RenderTarget;
+use crate::ogl::renderer::Renderer;
+use crate::ogl::skybox::SkyboxRenderer;
+use crate::ogl::text_renderer::DEFAULT_FONT_ATLAS;
+#[cfg(feature = "ui")]
+use crate::ogl::texture_inspector::InspectorChannel;
+use crate::ogl::texture_inspector::TextureInspector;
+use crate::ogl::upload_worker::UploadWorker;
+use crate::particles::BlendMode;
+use crate::picking::pick_scene_object;
+use crate::platform::{Action, DefaultBackend, Event, WindowBackend};
+use crate::presentation::Presenter;
+use crate::renderdoc::RenderDoc;
+use crate::scene::{CameraPose, Scene, Transform, WorldMatrices};
+#[cfg(feature = "scripting")]
+use crate::scripting::ScriptRunner;
+use crate::simulation::{FixedTimestep, Time};
+#[cfg(feature = "ui")]
+use crate::ui::{uniform_panel, Ui};
+use crate::window_title::WindowTitle;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADLESS_OUTPUT_DIRECTORY: &str = "headless_output";
+
+const SIMULATION_STEPS_PER_SECOND: f32 = 60.0;
+
+/// What a window's resources are built from, carried over when the window is recreated.
+pub struct WindowSetup {
+    pub settings: Settings,
+    /// The scene and the file it saves to; the lesson's own when empty
+    pub scene: Option<(Scene, String)>,
+}
+
+/// The offscreen target headless runs render into, and where its frames are written.
+struct HeadlessOutput {
+    target: RenderTarget,
+    directory: PathBuf,
+    frame_count: u32,
+    frames_rendered: u32,
+}
+
+/// A window, its scene and lesson, and everything else the frame loop keeps between frames.
+pub struct App {
+    settings: Settings,
+    window: DefaultBackend,
+    gl_context: GlContext,
+    window_title: WindowTitle,
+    presenter: Presenter,
+    /// The worker's hidden window has to stay open for as long as the worker runs
+    _upload_window: Option<DefaultBackend>,
+    upload_worker: Option<UploadWorker>,
+    scene: Scene,
+    scene_file: String,
+    animator: Animator,
+    #[cfg(feature = "scripting")]
+    scripts: ScriptRunner,
+    lesson_runner: Option<Box<dyn LessonRunner>>,
+    scene_renderables: SceneRenderables,
+    scene_lighting: SceneLighting,
+    skybox: Option<SkyboxRenderer>,
+    timestep: FixedTimestep,
+    time: Time,
+    last_frame: f32,
+    /// Transforms as of the previous simulation step, which frames interpolate from
+    previous_transforms: Vec<Transform>,
+    world_matrices: WorldMatrices,
+    framebuffer_size: (i32, i32),
+    renderer: Renderer,
+    camera: Camera,
+    input_map: InputMap,
+    input_state: InputState,
+    selected_object: Option<usize>,
+    id_buffer: IdBuffer,
+    gizmo: Gizmo,
+    line_renderer: LineRenderer,
+    billboard_renderer: BillboardRenderer,
+    marker_texture: u32,
+    marker_billboards: Vec<Billboard>,
+    post_process: PostProcess,
+    frame_capture: Option<FrameCapture>,
+    headless: Option<HeadlessOutput>,
+    debug_view: Option<DebugView>,
+    debug_hud: DebugHud,
+    label_font: Option<(FontRenderer, f32)>,
+    console: Console,
+    texture_inspector: TextureInspector,
+    #[cfg(feature = "ui")]
+    ui_overlay: Ui,
+}
+
+impl App {
+    /// Sets up everything `window` draws: `scene`, which saves to `scene_file`, the lesson's
+    /// runner if it has one, and the debug tools. Headless runs render offscreen instead.
+    pub fn new(
+        cli_args: &CliArgs,
+        mut window: DefaultBackend,
+        settings: Settings,
+        (scene, scene_file): (Scene, String),
+        lesson_runner: Option<Box<dyn LessonRunner>>,
+    ) -> App {
+        let window_title = WindowTitle::new(&settings.window.title);
+        window.set_title(&window_title.initial(window.framebuffer_size()));
+        let gl_context = window.gl_context();
+        let presenter = Presenter::new(&mut window, &settings.window);
+        let (upload_window, mut upload_worker) = match window.create_worker_context() {
+            Ok((upload_window, context)) => {
+                (Some(upload_window), Some(UploadWorker::start(context)))
+            }
+            Err(e) => {
+                eprintln!("Uploading on the render thread: {}", e);
+                (None, None)
+            }
+        };
+
+        let animator = Animator::new(&scene);
+        #[cfg(feature = "scripting")]
+        let mut scene = scene;
+        #[cfg(feature = "scripting")]
+        let scripts = ScriptRunner::new(&mut scene);
+        #[cfg(not(feature = "scripting"))]
+        report_ignored_scripts(&scene);
+        let skybox = load_skybox(&gl_context, &settings);
+        let scene_renderables =
+            setup_scene(&settings, &scene, None, upload_worker.as_mut(), &gl_context);
+        let previous_transforms: Vec<Transform> = scene
+            .objects
+            .iter()
+            .map(|object| object.transform.clone())
+            .collect();
+        let world_matrices = WorldMatrices::new(&scene);
+        let framebuffer_size = window.framebuffer_size();
+        let projection_from_view = setup_coordinate_systems(&settings, framebuffer_size);
+        let mut renderer = Renderer::new(
+            &gl_context,
+            Color::from_srgb(0.2, 0.3, 0.3, 1.0),
+            projection_from_view,
+        )
+        .expect("Renderer setup failure");
+        configure_renderer(&mut renderer, &settings);
+
+        let camera = scene.camera.to_camera();
+        let input_map = InputMap::new(&settings.input.bindings);
+        let input_state = InputState {
+            mouse: None,
+            move_speed: settings.input.move_speed,
+            mouse_sensitivity: settings.input.mouse_sensitivity,
+            gamepad_dead_zone: settings.input.gamepad_dead_zone,
+            gamepad_look_speed: settings.input.gamepad_look_speed,
+            pick_request: None,
+            drag_cursor: None,
+            look_locked: false,
+            look_delta: (0.0, 0.0),
+            look_filter: LookFilter::new(
+                settings.input.look_smoothing,
+                settings.input.look_acceleration,
+            ),
+            gizmo_mode_request: None,
+            capture_toggle_requested: false,
+            fullscreen_toggle_requested: false,
+            vsync_cycle_requested: false,
+            scene_reload_requested: false,
+            scene_save_requested: false,
+            scene_load_request: None,
+            shader_reload_requested: false,
+            probe_bake_requested: false,
+            renderdoc_capture_requested: false,
+            visualization_cycle_requested: false,
+            texture_inspector_toggle_requested: false,
+            launch_requested: false,
+            window_recreation_requested: false,
+        };
+
+        // Headless runs render a fixed number of frames into an offscreen target at a fixed rate
+        let headless = cli_args.headless_frames.map(|frame_count| {
+            let directory = PathBuf::from(
+                cli_args
+                    .output
+                    .as_deref()
+                    .unwrap_or(HEADLESS_OUTPUT_DIRECTORY),
+            );
+            let (width, height) = window.framebuffer_size();
+            fs::create_dir_all(&directory).expect("Failed creating headless output directory");
+            let target =
+                RenderTarget::new(&gl_context, width, height).expect("Render target setup failure");
+            target.set_label(&gl_context, "headless output");
+            HeadlessOutput {
+                target,
+                directory,
+                frame_count,
+                frames_rendered: 0,
+            }
+        });
+
+        let debug_view = if settings.debug_view.enabled && headless.is_none() {
+            open_debug_view(&mut window, &settings)
+        } else {
+            None
+        };
+
+        let mut debug_hud =
+            DebugHud::new(&gl_context, DEFAULT_FONT_ATLAS).expect("Debug HUD setup failure");
+        // Frame times would make headless output differ between runs
+        debug_hud.visible = headless.is_none();
+        let label_font = load_label_font(&gl_context, &settings.text, window.content_scale());
+        let console = Console::new(&gl_context, DEFAULT_FONT_ATLAS).expect("Console setup failure");
+        let texture_inspector = TextureInspector::new(&gl_context, DEFAULT_FONT_ATLAS)
+            .expect("Texture inspector setup failure");
+
+        App {
+            billboard_renderer: BillboardRenderer::new(&gl_context)
+                .expect("Billboard renderer setup failure"),
+            marker_texture: create_marker_texture(&gl_context),
+            marker_billboards: vec![],
+            scene_lighting: SceneLighting::new(&gl_context),
+            id_buffer: IdBuffer::new(&gl_context).expect("ID buffer setup failure"),
+            gizmo: Gizmo::new(),
+            line_renderer: LineRenderer::new(&gl_context).expect("Line renderer setup failure"),
+            post_process: PostProcess::new(&gl_context).expect("Post-processing setup failure"),
+            #[cfg(feature = "ui")]
+            ui_overlay: Ui::new(&gl_context).expect("UI setup failure"),
+            settings,
+            window,
+            gl_context,
+            window_title,
+            presenter,
+            _upload_window: upload_window,
+            upload_worker,
+            scene,
+            scene_file,
+            animator,
+            #[cfg(feature = "scripting")]
+            scripts,
+            lesson_runner,
+            scene_renderables,
+            skybox,
+            timestep: FixedTimestep::new(SIMULATION_STEPS_PER_SECOND),
+            time: Time::new(),
+            last_frame: 0.0_f32,
+            previous_transforms,
+            world_matrices,
+            framebuffer_size,
+            renderer,
+            camera,
+            input_map,
+            input_state,
+            selected_object: None,
+            frame_capture: None,
+            headless,
+            debug_view,
+            debug_hud,
+            label_font,
+            console,
+            texture_inspector,
+        }
+    }
+
+    /// Runs one frame, from handling its events to presenting it. Returns false once there are
+    /// no more to run: the window closed or asked to be recreated, or the headless frames are
+    /// all rendered.
+    pub fn run_frame(&mut self, renderdoc: &mut Option<RenderDoc>) -> bool {
+        if self.window.should_close() {
+            return false;
+        }
+        let delta_time = self.begin_frame();
+
+        // Process Events
+        self.handle_events();
+        // Nothing to render into; keep handling events at a low rate until restored
+        if self.window.is_minimized() {
+            self.presenter.idle();
+            return true;
+        }
+        // Typing into the console should not also fly the camera around, and lessons such as
+        // breakout steer with the movement keys instead
+        let lesson_takes_movement_keys = self
+            .lesson_runner
+            .as_ref()
+            .is_some_and(|lesson_runner| lesson_runner.takes_movement_keys());
+        if !self.console.open && !lesson_takes_movement_keys {
+            process_inputs(
+                &self.window,
+                &self.input_map,
+                &mut self.camera,
+                &mut self.input_state,
+                delta_time,
+            );
+        }
+        self.run_commands();
+        if self.input_state.window_recreation_requested {
+            return false;
+        }
+        self.handle_requests();
+        self.update_debug_view();
+        self.update_selection();
+
+        // Simulate
+        let alpha = self.simulate(delta_time);
+
+        // Render
+        self.render(delta_time, alpha, renderdoc);
+        self.render_debug_view();
+        self.finish_frame(renderdoc)
+    }
+
+    /// Deletes the window's GL objects and closes it. Returns the setup for a new window when one
+    /// was requested instead.
+    pub fn close(self) -> Option<WindowSetup> {
+        let App {
+            mut settings,
+            mut window,
+            gl_context,
+            upload_worker,
+            mut scene,
+            scene_file,
+            lesson_runner,
+            scene_renderables,
+            scene_lighting,
+            skybox,
+            renderer,
+            camera,
+            input_state,
+            id_buffer,
+            line_renderer,
+            billboard_renderer,
+            marker_texture,
+            post_process,
+            frame_capture,
+            headless,
+            debug_view,
+            debug_hud,
+            label_font,
+            console,
+            texture_inspector,
+            #[cfg(feature = "ui")]
+            ui_overlay,
+            ..
+        } = self;
+        if let Some(capture) = frame_capture {
+            capture.finish();
+        }
+        if let Some(view) = debug_view {
+            close_debug_view(view, &mut window);
+        }
+        if let Some(worker) = upload_worker {
+            worker.stop();
+        }
+        if let Some(headless) = &headless {
+            println!(
+                "Rendered {} frames to {}",
+                headless.frames_rendered,
+                headless.directory.display()
+            );
+        }
+
+        // GL objects go while their context is still current: first through their owners, then
+        // whatever those leaked, listed in debug builds
+        drop(scene_renderables);
+        drop(billboard_renderer);
+        drop(lesson_runner);
+        drop(skybox);
+        drop(renderer);
+        drop(id_buffer);
+        drop(line_renderer);
+        drop(post_process);
+        drop(scene_lighting);
+        drop(headless);
+        drop(debug_hud);
+        drop(label_font);
+        drop(console);
+        drop(texture_inspector);
+        #[cfg(feature = "ui")]
+        drop(ui_overlay);
+        live_objects::release(&gl_context, GlObject::Texture, marker_texture);
+        live_objects::teardown(&gl_context, cfg!(debug_assertions));
+
+        if !input_state.window_recreation_requested {
+            return None;
+        }
+        // Console changes the settings do not record yet
+        scene.camera = CameraPose::from_camera(&camera);
+        settings.input.move_speed = input_state.move_speed;
+        settings.input.mouse_sensitivity = input_state.mouse_sensitivity;
+        Some(WindowSetup {
+            settings,
+            scene: Some((scene, scene_file)),
+        })
+    }
+
+    /// Times the frame, fixed in headless runs, and returns its length in seconds.
+    fn begin_frame(&mut self) -> f32 {
+        let current_frame = self.window.time() as f32;
+        let delta_time = if self.headless.is_some() {
+            self.timestep.step
+        } else {
+            current_frame - self.last_frame
+        };
+        self.last_frame = current_frame;
+        self.renderer.stats.record_frame_time(delta_time);
+        if let Some(title) = self
+            .window_title
+            .update(delta_time, self.window.framebuffer_size())
+        {
+            self.window.set_title(&title);
+        }
+        delta_time
+    }
+
+    /// Hands the window's events to the console and the UI first, then to the key bindings.
+    fn handle_events(&mut self) {
+        let events = self.window.poll_events();
+        let events = self
+            .console
+            .handle_events(events, &self.input_map, self.window.modifiers());
+        #[cfg(feature = "ui")]
+        let events = self.ui_overlay.handle_events(&self.window, events);
+        process_events(
+            &mut self.window,
+            events,
+            &self.input_map,
+            &mut self.input_state,
+            &mut self.debug_hud,
+            &mut self.time,
+        );
+    }
+
+    fn run_commands(&mut self) {
+        for command in self.console.take_commands() {
+            // The lesson gets the first look, for commands of its own
+            let handled = self
+                .lesson_runner
+                .as_mut()
+                .and_then(|lesson_runner| lesson_runner.handle_command(&command));
+            let result = match handled {
+                Some(result) => result,
+                None => run_command(
+                    command,
+                    &mut self.settings,
+                    &mut self.renderer,
+                    self.framebuffer_size,
+                    &mut self.input_state,
+                    &mut self.debug_hud,
+                    &mut self.texture_inspector,
+                    &mut self.scene_lighting.clusters,
+                    &mut self.time,
+                ),
+            };
+            match result {
+                Ok(message) => self.console.print(&message),
+                Err(e) => self.console.print_error(&e),
+            }
+        }
+    }
+
+    /// Carries out what the key bindings and console commands requested this frame, and follows
+    /// the window's size.
+    fn handle_requests(&mut self) {
+        let input_state = &mut self.input_state;
+        if input_state.vsync_cycle_requested {
+            input_state.vsync_cycle_requested = false;
+            self.presenter.cycle_vsync(&mut self.window);
+        }
+        if input_state.texture_inspector_toggle_requested {
+            input_state.texture_inspector_toggle_requested = false;
+            self.texture_inspector.visible = !self.texture_inspector.visible;
+        }
+        if input_state.visualization_cycle_requested {
+            input_state.visualization_cycle_requested = false;
+            let renderer = &mut self.renderer;
+            match renderer.set_visualization(renderer.visualization().next()) {
+                Ok(()) => {
+                    let message = format!("Visualization {:?}", renderer.visualization());
+                    println!("{}", message);
+                    self.console.print(&message);
+                }
+                Err(e) => self.console.print_error(&e),
+            }
+        }
+        if input_state.fullscreen_toggle_requested {
+            input_state.fullscreen_toggle_requested = false;
+            let fullscreen = !self.window.is_fullscreen();
+            self.window
+                .set_fullscreen(fullscreen, &self.settings.window);
+        }
+        if input_state.scene_reload_requested {
+            input_state.scene_reload_requested = false;
+            input_state.scene_load_request = Some(self.scene_file.clone());
+        }
+        if let Some(file_path) = input_state.scene_load_request.take() {
+            self.load_scene(file_path);
+        }
+        if self.input_state.scene_save_requested {
+            self.input_state.scene_save_requested = false;
+            self.scene.camera = CameraPose::from_camera(&self.camera);
+            match self.scene.save(&self.scene_file) {
+                Ok(_) => println!("Saved scene to {}", self.scene_file),
+                Err(e) => eprintln!("Failed saving scene {}: {}", self.scene_file, e),
+            }
+        }
+        #[cfg(feature = "scripting")]
+        self.scripts.reload_changed();
+        if self.input_state.shader_reload_requested {
+            self.input_state.shader_reload_requested = false;
+            self.setup_scene(None);
+        }
+        if self.input_state.probe_bake_requested {
+            self.input_state.probe_bake_requested = false;
+            self.scene_renderables.probes_baked = false;
+        }
+        // Mode changes and resizes; a minimized window reports a zero size
+        let framebuffer_size = self.window.framebuffer_size();
+        if framebuffer_size != self.framebuffer_size
+            && framebuffer_size.0 > 0
+            && framebuffer_size.1 > 0
+        {
+            self.framebuffer_size = framebuffer_size;
+            self.renderer
+                .set_projection(setup_coordinate_systems(&self.settings, framebuffer_size));
+        }
+    }
+
+    /// Switches to the scene in `file_path`, keeping the current one if it fails to load.
+    fn load_scene(&mut self, file_path: String) {
+        match Scene::load(&file_path) {
+            Ok(loaded) => {
+                self.scene = loaded;
+                self.animator = Animator::new(&self.scene);
+                #[cfg(feature = "scripting")]
+                {
+                    self.scripts = ScriptRunner::new(&mut self.scene);
+                }
+                #[cfg(not(feature = "scripting"))]
+                report_ignored_scripts(&self.scene);
+                self.camera = self.scene.camera.to_camera();
+                self.selected_object = None;
+                self.previous_transforms = self
+                    .scene
+                    .objects
+                    .iter()
+                    .map(|object| object.transform.clone())
+                    .collect();
+                self.world_matrices = WorldMatrices::new(&self.scene);
+                self.setup_scene(None);
+                let message = format!("Loaded scene from {}", file_path);
+                println!("{}", message);
+                self.console.print(&message);
+                self.scene_file = file_path;
+            }
+            Err(e) => {
+                let message = format!("Failed loading scene {}: {}", file_path, e);
+                eprintln!("{}", message);
+                self.console.print_error(&message);
+            }
+        }
+    }
+
+    /// Sets up the scene's GPU resources again, leaving `unbatched_object` out of the batches.
+    fn setup_scene(&mut self, unbatched_object: Option<usize>) {
+        self.scene_renderables = setup_scene(
+            &self.settings,
+            &self.scene,
+            unbatched_object,
+            self.upload_worker.as_mut(),
+            &self.gl_context,
+        );
+        self.renderer.forget_deleted_objects();
+    }
+
+    /// Handles the debug view window's events, closing it when asked to.
+    fn update_debug_view(&mut self) {
+        let input_map = &self.input_map;
+        let debug_view_closed = match self.debug_view.as_mut() {
+            Some(view) => {
+                for event in view.window.poll_events() {
+                    if let Event::Key(key, Action::Press) = event {
+                        if input_map
+                            .actions(Trigger::Key(key), view.window.modifiers())
+                            .contains(&InputAction::Quit)
+                        {
+                            view.window.set_should_close(true);
+                        }
+                    }
+                }
+                view.window.should_close()
+            }
+            None => false,
+        };
+        if debug_view_closed {
+            close_debug_view(self.debug_view.take().unwrap(), &mut self.window);
+        }
+    }
+
+    /// Picks objects and moves the selected one with the gizmo.
+    fn update_selection(&mut self) {
+        if let Some(mode) = self.input_state.gizmo_mode_request.take() {
+            if !self.gizmo.is_dragging() {
+                self.gizmo.mode = mode;
+            }
+        }
+
+        let grabbed_object = match (self.input_state.pick_request, self.selected_object) {
+            (Some(cursor), Some(object_i)) => {
+                let ray = cursor_ray(&self.window, cursor, &self.camera, &self.renderer);
+                if self.gizmo.begin_drag(
+                    &ray,
+                    &self.scene.objects[object_i].transform,
+                    &self.camera.position,
+                ) {
+                    self.input_state.pick_request = None;
+                    Some(object_i)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(object_i) = grabbed_object {
+            let is_batched = self
+                .scene_renderables
+                .objects
+                .iter()
+                .all(|(drawn_i, _, _)| *drawn_i != object_i);
+            if is_batched {
+                self.setup_scene(Some(object_i));
+            }
+        }
+
+        if let Some(cursor) = self.input_state.pick_request.take() {
+            self.selected_object = self.pick(cursor);
+            match self.selected_object {
+                Some(object_i) => println!("Selected {}", self.scene.objects[object_i].name),
+                None => println!("Selection cleared"),
+            }
+        }
+
+        if self.gizmo.is_dragging() {
+            match (self.input_state.drag_cursor, self.selected_object) {
+                (Some(cursor), Some(object_i)) => {
+                    let ray = cursor_ray(&self.window, cursor, &self.camera, &self.renderer);
+                    let transform = &mut self.scene.objects[object_i].transform;
+                    self.gizmo
+                        .update_drag(&ray, transform, &self.camera.position);
+                    // Edits apply immediately instead of easing in over the next step
+                    self.previous_transforms[object_i] = transform.clone();
+                    self.world_matrices.mark_dirty(object_i);
+                }
+                _ => self.gizmo.end_drag(),
+            }
+        }
+        self.input_state.look_locked = self.gizmo.is_dragging();
+    }
+
+    /// The object under `cursor`, in window coordinates, if there is one.
+    fn pick(&mut self, cursor: (f32, f32)) -> Option<usize> {
+        let (width, height) = self.window.size();
+        match self.settings.input.picking {
+            PickingMode::Ray => {
+                let ray = cursor_ray(&self.window, cursor, &self.camera, &self.renderer);
+                pick_scene_object(&ray, &self.scene, &self.world_matrices)
+            }
+            PickingMode::IdBuffer => {
+                let (framebuffer_width, framebuffer_height) = self.window.framebuffer_size();
+                let pixel = (
+                    (cursor.0 * framebuffer_width as f32 / width as f32) as i32,
+                    (cursor.1 * framebuffer_height as f32 / height as f32) as i32,
+                );
+                let world_matrices = &self.world_matrices;
+                let pickables: Vec<(usize, &Mesh, Mat4)> = self
+                    .scene_renderables
+                    .object_meshes
+                    .iter()
+                    .enumerate()
+                    .map(|(object_i, mesh)| {
+                        (object_i, mesh.as_ref(), *world_matrices.get(object_i))
+                    })
+                    .collect();
+                self.id_buffer.pick(
+                    &pickables,
+                    &self.camera.view_matrix(),
+                    self.renderer.projection_from_view(),
+                    (framebuffer_width, framebuffer_height),
+                    pixel,
+                )
+            }
+        }
+    }
+
+    /// Advances the scene and the lesson by the fixed steps that fit in `delta_time`, then
+    /// interpolates the world matrices between the last two. Returns how far between they are.
+    fn simulate(&mut self, delta_time: f32) -> f32 {
+        let mut lesson_input = LessonInput {
+            steer: if self.console.open {
+                0.0_f32
+            } else {
+                steer_input(&self.window, &self.input_map, &self.input_state)
+            },
+            launch: mem::replace(&mut self.input_state.launch_requested, false),
+        };
+        let step = self.timestep.step;
+        let simulation_delta = self.time.simulation_delta(delta_time, step);
+        for _ in 0..self.timestep.advance(simulation_delta) {
+            for (previous, object) in self
+                .previous_transforms
+                .iter_mut()
+                .zip(self.scene.objects.iter())
+            {
+                *previous = object.transform.clone();
+            }
+            self.scene.update(step);
+            self.animator.update(&mut self.scene, step);
+            #[cfg(feature = "scripting")]
+            self.scripts.update(&mut self.scene, step);
+            if let Some(lesson_runner) = self.lesson_runner.as_mut() {
+                lesson_runner.update(step, &mut lesson_input);
+            }
+        }
+        let alpha = self.timestep.alpha();
+        self.world_matrices
+            .update(&self.scene, &self.previous_transforms, alpha);
+        alpha
+    }
+
+    /// Renders the frame into the window, or the headless target: the scene with the lesson's
+    /// passes in between, then the debug drawing, post-processing and the overlays.
+    fn render(&mut self, delta_time: f32, alpha: f32, renderdoc: &mut Option<RenderDoc>) {
+        if !self.scene_renderables.probes_baked {
+            self.scene_lighting.bake(
+                &mut self.scene_renderables,
+                &self.scene,
+                &self.world_matrices,
+                &mut self.renderer,
+                &self.gl_context,
+            );
+        }
+        if self.input_state.renderdoc_capture_requested {
+            self.input_state.renderdoc_capture_requested = false;
+            match renderdoc.as_mut() {
+                Some(renderdoc) => renderdoc.start_frame_capture(),
+                None => self
+                    .console
+                    .print_error("RenderDoc is not attached; run with --renderdoc"),
+            }
+        }
+        let output_size = match &self.headless {
+            Some(headless) => (headless.target.width, headless.target.height),
+            None => self.window.framebuffer_size(),
+        };
+        let post_processing = self.post_process.is_active(&self.settings.post_process)
+            && self.post_process.begin(output_size);
+        if !post_processing {
+            self.bind_output(output_size);
+        }
+        self.renderer.begin_frame(&self.camera);
+        if self.lesson_runner.is_some() {
+            self.render_lesson(LessonPass::Offscreen, output_size);
+            self.bind_scene_output(post_processing, output_size);
+        }
+        if self.scene_renderables.mirror_object.is_some() {
+            self.scene_lighting.capture_environment(
+                &self.scene_renderables,
+                &self.scene,
+                &self.world_matrices,
+                &mut self.renderer,
+                self.settings.renderer.environment_capture,
+                self.settings.renderer.environment_capture_size,
+                &self.gl_context,
+            );
+            self.bind_scene_output(post_processing, output_size);
+            // Back to the camera's view, which the faces replaced
+            self.renderer.begin_view(&self.camera);
+        }
+        if self.scene_renderables.has_lit_objects {
+            self.scene_lighting.update(
+                &self.scene_renderables,
+                &self.scene,
+                &self.camera,
+                &self.renderer,
+                output_size,
+            );
+        }
+        submit_scene(
+            &mut self.renderer,
+            &self.scene_renderables,
+            &self.scene,
+            &self.world_matrices,
+        );
+        if let Some(object_i) = self.selected_object {
+            self.renderer.submit_outline(
+                &self.scene_renderables.object_meshes[object_i],
+                self.world_matrices.get(object_i),
+            );
+        }
+        self.renderer.end_frame();
+        self.render_lesson(LessonPass::Opaque, output_size);
+        // After the opaque geometry, so only the pixels left uncovered are shaded
+        if let Some(skybox) = &self.skybox {
+            self.renderer.gpu_timer.begin_pass("skybox");
+            skybox.draw(&self.camera, &mut self.renderer);
+            self.renderer.gpu_timer.end_pass();
+        }
+        self.render_lesson(LessonPass::Transparent, output_size);
+        self.draw_debug(output_size);
+        self.render_lesson(LessonPass::Sprites, output_size);
+        if post_processing {
+            self.renderer.gpu_timer.begin_pass("post process");
+            self.bind_output(output_size);
+            self.post_process.apply(
+                &self.settings.post_process,
+                &self.camera,
+                &mut self.renderer,
+            );
+            self.renderer.gpu_timer.end_pass();
+        }
+        self.draw_overlay(delta_time, alpha, output_size);
+    }
+
+    /// Binds the framebuffer frames end up in: the headless target, or else the window's.
+    fn bind_output(&self, (width, height): (i32, i32)) {
+        match &self.headless {
+            Some(headless) => headless.target.bind(&self.gl_context),
+            None => self.gl_context.bind_window_framebuffer(width, height),
+        }
+    }
+
+    /// Binds the framebuffer the scene is drawn into, which post-processing reads from.
+    fn bind_scene_output(&self, post_processing: bool, output_size: (i32, i32)) {
+        if post_processing {
+            self.post_process.scene_target().bind(&self.gl_context);
+        } else {
+            self.bind_output(output_size);
+        }
+    }
+
+    /// Runs the lesson's part of `pass`, if the lesson has a runner.
+    fn render_lesson(&mut self, pass: LessonPass, output_size: (i32, i32)) {
+        let App {
+            ref mut lesson_runner,
+            ref mut renderer,
+            ref camera,
+            ref scene,
+            ref scene_renderables,
+            ref world_matrices,
+            ref debug_hud,
+            ref window,
+            ..
+        } = *self;
+        let lesson_runner = match lesson_runner.as_mut() {
+            Some(lesson_runner) => lesson_runner,
+            None => return,
+        };
+        let submit_lesson_scene = |renderer: &mut Renderer| {
+            submit_scene(renderer, scene_renderables, scene, world_matrices)
+        };
+        let lesson_frame = LessonFrame {
+            camera,
+            submit_scene: &submit_lesson_scene,
+            // Emitter origins and such are marked along with the HUD
+            show_markers: debug_hud.visible,
+            output_size,
+            framebuffer_size: window.framebuffer_size(),
+            content_scale: window.content_scale(),
+        };
+        lesson_runner.render(pass, renderer, &lesson_frame);
+    }
+
+    /// Draws the debug shapes, labels and light icons the settings ask for.
+    fn draw_debug(&mut self, output_size: (i32, i32)) {
+        let settings = &self.settings;
+        let scene = &self.scene;
+        let renderer = &mut self.renderer;
+        if settings.renderer.debug_draw {
+            draw_debug_shapes(scene, &self.world_matrices, renderer, self.selected_object);
+        }
+        if let (true, Some((_, label_scale))) = (settings.renderer.show_labels, &self.label_font) {
+            let label_size = if settings.text.world_label_height > 0.0_f32 {
+                WorldTextSize::World(settings.text.world_label_height)
+            } else {
+                WorldTextSize::Screen(*label_scale)
+            };
+            draw_scene_labels(scene, &self.world_matrices, label_size);
+        }
+        if settings.renderer.show_lights && !scene.lights.is_empty() {
+            draw_light_shapes(&scene.lights, settings.renderer.show_light_ranges);
+            renderer.gpu_timer.begin_pass("lights");
+            self.marker_billboards.clear();
+            self.marker_billboards
+                .extend(scene.lights.iter().map(|light| Billboard {
+                    position: light.kind.position(),
+                    size: LIGHT_ICON_SIZE,
+                    rotation_radians: 0.0_f32,
+                    color: light.color,
+                    uv_rect: UvRect::FULL,
+                }));
+            let projection_from_view = *renderer.projection_from_view();
+            self.billboard_renderer.draw(
+                &self.marker_billboards,
+                Some(self.marker_texture),
+                BlendMode::Alpha,
+                &self.camera,
+                &projection_from_view,
+                &mut renderer.stats,
+            );
+            renderer.gpu_timer.end_pass();
+        }
+        renderer.gpu_timer.begin_pass("debug draw");
+        debug::flush(
+            &mut self.line_renderer,
+            self.label_font
+                .as_mut()
+                .map(|(font_renderer, _)| font_renderer),
+        );
+        let projection_from_view = *renderer.projection_from_view();
+        self.line_renderer
+            .flush(&self.camera, &projection_from_view, &mut renderer.stats);
+        if let Some((font_renderer, _)) = self.label_font.as_mut() {
+            font_renderer.draw_world(&self.camera, &projection_from_view, output_size);
+        }
+        renderer.gpu_timer.end_pass();
+    }
+
+    /// Draws the gizmo, the HUD, the texture inspector, the lesson's overlay, the selected
+    /// object's name, the UI and the console over the finished frame.
+    fn draw_overlay(&mut self, delta_time: f32, alpha: f32, output_size: (i32, i32)) {
+        self.renderer.gpu_timer.begin_pass("overlay");
+        if let Some(object_i) = self.selected_object {
+            let transform = self.previous_transforms[object_i]
+                .lerp(&self.scene.objects[object_i].transform, alpha);
+            self.gizmo
+                .push_lines(&transform, &self.camera.position, &mut self.line_renderer);
+        }
+        let projection_from_view = *self.renderer.projection_from_view();
+        self.line_renderer.flush(
+            &self.camera,
+            &projection_from_view,
+            &mut self.renderer.stats,
+        );
+        self.debug_hud.draw(
+            &self.renderer.stats,
+            &self.camera,
+            self.window.framebuffer_size(),
+            self.window.content_scale(),
+        );
+        self.texture_inspector
+            .draw(self.window.framebuffer_size(), self.window.content_scale());
+        self.render_lesson(LessonPass::Overlay, output_size);
+        if let (Some((font_renderer, label_scale)), Some(object_i)) =
+            (self.label_font.as_mut(), self.selected_object)
+        {
+            let name = &self.scene.objects[object_i].name;
+            let (framebuffer_width, _) = self.window.framebuffer_size();
+            let (width, _) = font_renderer.measure(name, *label_scale);
+            let margin = font_renderer.line_height(*label_scale) * 0.5_f32;
+            font_renderer.add_text(
+                name,
+                (framebuffer_width as f32 - width - margin, margin),
+                *label_scale,
+                [1.0_f32, 0.9_f32, 0.3_f32, 1.0_f32],
+            );
+            font_renderer.draw(self.window.framebuffer_size());
+        }
+        #[cfg(feature = "ui")]
+        if self.headless.is_none() {
+            self.run_ui(delta_time);
+        }
+        #[cfg(not(feature = "ui"))]
+        let _ = delta_time;
+        self.console
+            .draw(self.window.framebuffer_size(), self.window.content_scale());
+        self.renderer.gpu_timer.end_pass();
+    }
+
+    /// The debug window, with the frame time and the settings worth changing while running.
+    #[cfg(feature = "ui")]
+    fn run_ui(&mut self, delta_time: f32) {
+        let App {
+            ref mut ui_overlay,
+            ref window,
+            ref scene_renderables,
+            selected_object,
+            ref mut debug_hud,
+            ref mut settings,
+            ref mut renderer,
+            ref mut time,
+            ref mut texture_inspector,
+            ..
+        } = *self;
+        let active_material = scene_renderables.active_material(selected_object);
+        ui_overlay.run(window, |ctx| {
+            egui::Window::new("Debug").show(ctx, |ui| {
+                ui.label(format!("Frame time {:.2} ms", delta_time * 1000.0_f32));
+                ui.checkbox(&mut debug_hud.visible, "Debug HUD");
+                ui.checkbox(&mut settings.renderer.show_lights, "Lights");
+                ui.checkbox(&mut settings.renderer.show_light_ranges, "Light ranges");
+                ui.checkbox(&mut settings.renderer.show_labels, "Labels");
+                ui.checkbox(&mut renderer.frustum_culling, "Frustum culling");
+                ui.checkbox(&mut renderer.occlusion_culling, "Occlusion culling");
+                ui.checkbox(&mut renderer.instancing, "Instancing");
+                ui.checkbox(&mut renderer.multi_draw_indirect, "Multi-draw indirect");
+                ui.checkbox(&mut time.paused, "Simulation paused");
+                ui.add(
+                    egui::Slider::new(
+                        &mut time.time_scale,
+                        Time::MIN_TIME_SCALE..=Time::MAX_TIME_SCALE,
+                    )
+                    .text("Time scale"),
+                );
+                egui::CollapsingHeader::new("Tone mapping").show(ui, |ui| {
+                    let tone_mapping = &mut settings.post_process.tone_mapping;
+                    for operator in ToneMapOperator::ALL.iter() {
+                        ui.radio_value(&mut tone_mapping.operator, *operator, operator.name());
+                    }
+                    ui.add(
+                        egui::Slider::new(&mut tone_mapping.exposure, 0.0_f32..=8.0_f32)
+                            .text("Exposure"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut tone_mapping.white_point, 1.0_f32..=16.0_f32)
+                            .text("White point"),
+                    );
+                    ui.checkbox(&mut tone_mapping.compare, "Split screen");
+                    if tone_mapping.compare {
+                        ui.horizontal(|ui| {
+                            for operator in ToneMapOperator::ALL.iter() {
+                                ui.radio_value(
+                                    &mut tone_mapping.compare_operator,
+                                    *operator,
+                                    operator.name(),
+                                );
+                            }
+                        });
+                        ui.add(
+                            egui::Slider::new(&mut tone_mapping.split, 0.0_f32..=1.0_f32)
+                                .text("Split"),
+                        );
+                    }
+                });
+                egui::CollapsingHeader::new("Texture inspector").show(ui, |ui| {
+                    ui.checkbox(&mut texture_inspector.visible, "Visible");
+                    ui.horizontal(|ui| {
+                        for channel in [
+                            InspectorChannel::Rgba,
+                            InspectorChannel::Red,
+                            InspectorChannel::Green,
+                            InspectorChannel::Blue,
+                            InspectorChannel::Alpha,
+                        ]
+                        .iter()
+                        {
+                            ui.radio_value(
+                                &mut texture_inspector.channel,
+                                *channel,
+                                channel.name(),
+                            );
+                        }
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut texture_inspector.mip_level, 0..=12)
+                            .text("Mip level"),
+                    );
+                    if texture_inspector.selected.is_some() && ui.button("Show all").clicked() {
+                        texture_inspector.selected = None;
+                    }
+                });
+                if let Some(material) = active_material {
+                    egui::CollapsingHeader::new("Shader uniforms")
+                        .show(ui, |ui| uniform_panel(ui, &material.shader_program));
+                }
+            });
+        });
+    }
+
+    /// Renders the scene from the debug camera into the debug view window.
+    fn render_debug_view(&mut self) {
+        let view = match self.debug_view.as_mut() {
+            Some(view) => view,
+            None => return,
+        };
+        let gl_context = &self.gl_context;
+        let renderer = &mut self.renderer;
+        view.target.bind(gl_context);
+        renderer.begin_observer_view(&view.camera);
+        if self.scene_renderables.has_lit_objects {
+            self.scene_lighting.update(
+                &self.scene_renderables,
+                &self.scene,
+                &view.camera,
+                renderer,
+                (view.target.width, view.target.height),
+            );
+        }
+        submit_scene(
+            renderer,
+            &self.scene_renderables,
+            &self.scene,
+            &self.world_matrices,
+        );
+        renderer.end_view("debug view");
+        view.target.unbind(gl_context);
+        let (width, height) = self.window.framebuffer_size();
+        gl_context.set_viewport(width, height);
+        // The debug window's context reads the target next
+        gl_context.flush();
+        view.window.make_current();
+        let (width, height) = view.window.framebuffer_size();
+        view.presenter
+            .present(&view.window.gl_context(), width, height);
+        view.window.swap_buffers();
+        self.window.make_current();
+    }
+
+    /// Writes the headless frame, records the capture and presents. Returns false once the last
+    /// headless frame is written.
+    fn finish_frame(&mut self, renderdoc: &mut Option<RenderDoc>) -> bool {
+        if let Some(headless) = self.headless.as_mut() {
+            let target = &headless.target;
+            target.unbind(&self.gl_context);
+            let file_path = headless
+                .directory
+                .join(format!("frame_{:05}.png", headless.frames_rendered));
+            if let Err(e) = write_rgba_png(
+                &file_path,
+                target.width,
+                target.height,
+                &target.read_pixels(&self.gl_context),
+            ) {
+                eprintln!("Failed writing {}: {}", file_path.display(), e);
+            }
+            headless.frames_rendered += 1;
+            if headless.frames_rendered >= headless.frame_count {
+                return false;
+            }
+        }
+
+        if self.input_state.capture_toggle_requested {
+            self.input_state.capture_toggle_requested = false;
+            self.frame_capture = match self.frame_capture.take() {
+                Some(capture) => {
+                    capture.finish();
+                    None
+                }
+                None => start_capture(&self.gl_context, &self.settings),
+            };
+        }
+        if let Some(capture) = self.frame_capture.as_mut() {
+            let (width, height) = self.window.framebuffer_size();
+            capture.capture(width, height);
+        }
+
+        self.presenter.present(&mut self.window);
+        if let Some(renderdoc) = renderdoc
+            .as_mut()
+            .filter(|renderdoc| renderdoc.is_capturing())
+        {
+            match renderdoc.end_frame_capture() {
+                Ok(file_path) => {
+                    let message = format!("Saved RenderDoc capture {}", file_path);
+                    println!("{}", message);
+                    self.console.print(&message);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    self.console.print_error(&e);
+                }
+            }
+        }
+        true
+    }
+}
+
+pub fn setup_coordinate_systems(settings: &Settings, (width, height): (i32, i32)) -> Mat4 {
+    let aspect_ratio = (width as f32) / (height as f32);
+    let angle = settings.renderer.fov;
+    let projection_from_view =
+        glm::perspective(aspect_ratio, angle.to_radians(), 0.1_f32, 100.0_f32);
+
+    projection_from_view
+}
+
+/// Applies the renderer settings the settings file starts with.
+fn configure_renderer(renderer: &mut Renderer, settings: &Settings) {
+    renderer.frustum_culling = settings.renderer.frustum_culling;
+    renderer.occlusion_culling = settings.renderer.occlusion_culling;
+    renderer.instancing = settings.renderer.instancing;
+    renderer.multi_draw_indirect = settings.renderer.multi_draw_indirect;
+    renderer.normal_length = settings.renderer.normal_length;
+    renderer.normal_color = settings.renderer.normal_color;
+    if let Err(e) = renderer.set_show_normals(settings.renderer.show_normals) {
+        eprintln!("{}", e);
+    }
+    renderer.wireframe_width = settings.renderer.wireframe_width;
+    renderer.wireframe_color = settings.renderer.wireframe_color;
+    if let Err(e) = renderer.set_show_wireframe_overlay(settings.renderer.wireframe_overlay) {
+        eprintln!("{}", e);
+    }
+    if let Err(e) = renderer.set_visualization(settings.renderer.visualization) {
+        eprintln!("{}", e);
+    }
+}
+
+/// Loads the skybox the settings name, if any; a skybox that fails to load is left out.
+fn load_skybox(gl_context: &GlContext, settings: &Settings) -> Option<SkyboxRenderer> {
+    if settings.renderer.skybox.is_empty() {
+        return None;
+    }
+    let cache_directory = Some(Path::new(&settings.renderer.cubemap_cache))
+        .filter(|directory| !directory.as_os_str().is_empty());
+    let skybox = SkyboxRenderer::new(
+        gl_context,
+        Path::new(&settings.renderer.skybox),
+        settings.renderer.skybox_size,
+        cache_directory,
+    );
+    match skybox {
+        Ok(skybox) => Some(skybox),
+        Err(err) => {
+            eprintln!("Skipping skybox {}: {}", settings.renderer.skybox, err);
+            None
+        }
+    }
+}
+
+/// Scene scripts only run with the `scripting` feature, so this says when some are left out.
+#[cfg(not(feature = "scripting"))]
+fn report_ignored_scripts(scene: &Scene) {
+    if scene.objects.iter().any(|object| object.script.is_some())
+        || scene.lights.iter().any(|light| light.script.is_some())
+    {
+        eprintln!("Ignoring the scene's scripts; build with --features scripting to run them");
+    }
+}
+
+/// Bakes the label font for the window's content scale, returning it with the scale to draw at.
+/// Signed distance glyphs are baked once at a large size and scaled down.
+fn load_label_font(
+    gl_context: &GlContext,
+    settings: &TextSettings,
+    content_scale: f32,
+) -> Option<(FontRenderer, f32)> {
+    if settings.font.is_empty() {
+        return None;
+    }
+    let pixel_height = settings.font_size * content_scale;
+    let (rendering, baked_pixel_height) = if settings.signed_distance_field {
+        (GlyphRendering::SignedDistance, SIGNED_DISTANCE_PIXEL_HEIGHT)
+    } else {
+        (GlyphRendering::Bitmap, pixel_height)
+    };
+    match FontRenderer::new(gl_context, &settings.font, baked_pixel_height, rendering) {
+        Ok(font_renderer) => Some((font_renderer, pixel_height / baked_pixel_height)),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// Starts recording into a new timestamped subdirectory of the capture directory.
+fn start_capture(gl_context: &GlContext, settings: &Settings) -> Option<FrameCapture> {
+    // Web builds have no file system to write frames to, nor a clock to name them by
+    if cfg!(target_arch = "wasm32") {
+        eprintln!("Recording is not supported in web builds");
+        return None;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let directory = Path::new(&settings.capture.directory).join(format!("capture_{}", timestamp));
+    match FrameCapture::start(
+        gl_context,
+        directory.clone(),
+        settings.capture.framerate,
+        settings.capture.max_queued_frames,
+    ) {
+        Ok(capture) => {
+            println!("Recording to {}", directory.display());
+            Some(capture)
+        }
+        Err(e) => {
+            eprintln!("Failed starting capture in {}: {}", directory.display(), e);
+            None
+        }
+    }
+}
@@ -0,0 +1,755 @@
+use crate::config::Settings;
+use crate::math::bounds::Aabb;
+use crate::math::color::Color;
+use crate::math::glm;
+use crate::ogl::batching::StaticBatcher;
+use crate::ogl::context::GlContext;
+use crate::ogl::environment_capture::EnvironmentUniforms;
+use crate::ogl::fallback;
+use crate::ogl::graphics::{
+    HullOutline, Material, Mesh, MeshData, ShaderProgram, Texture, VertexAttribute,
+};
+use crate::ogl::light_clusters::{self, LightClusterUniforms};
+use crate::ogl::light_probes::{LightProbeUniforms, LIGHT_PROBES_SOURCE};
+use crate::ogl::mesh_pool::MeshPool;
+use crate::ogl::reflection_probes::{ReflectionProbeUniforms, REFLECTION_PROBES_SOURCE};
+use crate::ogl::renderer::Renderer;
+use crate::ogl::texture_table::{self, TextureTable, TextureTableKind, TEXTURE_ARRAY_UNIT};
+use crate::ogl::upload_worker::{Upload, UploadId, UploadWorker};
+use crate::picking::mesh_bounds;
+use crate::scene::{LightKind, Scene, SceneObject, Shading, WorldMatrices};
+use std::rc::Rc;
+
+// Occlusion query keys of static batches start here, above those of scene objects (their indices)
+const BATCH_OCCLUSION_KEYS: u64 = 1 << 32;
+// Vertex attribute of mesh normals, which the normal display expects there too
+const NORMAL_LOCATION: u32 = 2;
+// Diffuse bands of the cel-shaded material
+const TOON_BANDS: f32 = 3.0_f32;
+// Toward the light that cel-shaded objects are lit by, in world space, when the scene has no
+// directional light
+const TOON_LIGHT_DIRECTION: [f32; 3] = [0.4_f32, 0.8_f32, 0.45_f32];
+
+const VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+// One matrix and texture set per instance, see renderer::INSTANCE_MATRIX_LOCATION
+layout (location = 3) in mat4 world_from_object;
+layout (location = 7) in uint a_texture_set;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+out vec2 o_tex_coords;
+flat out uint o_texture_set;
+
+void main() {
+    mat4 projection_from_object = projection_from_view * view_from_world * world_from_object;
+    gl_Position = projection_from_object * vec4(a_pos, 1.0f);
+    o_tex_coords = a_tex_coords;
+    o_texture_set = a_texture_set;
+}
+"#;
+
+const FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+// Textures come from a texture table, see texture_table::prepare_shader
+#ifdef BINDLESS_TEXTURES
+layout (std430, binding = 0) readonly buffer TextureHandles {
+    uvec2 texture_handles[];
+};
+#else
+uniform sampler2DArray texture_array;
+#endif
+uniform float mix_amount;
+uniform vec4 tint;
+
+in vec2 o_tex_coords;
+flat in uint o_texture_set;
+
+out vec4 frag_color;
+
+vec4 sample_texture(uint texture_i) {
+    uint table_i = o_texture_set * uint(TEXTURES_PER_SET) + texture_i;
+#ifdef BINDLESS_TEXTURES
+    return texture(sampler2D(texture_handles[table_i]), o_tex_coords);
+#else
+    return texture(texture_array, vec3(o_tex_coords, float(table_i)));
+#endif
+}
+
+void main() {
+    frag_color = tint * mix(sample_texture(0u), sample_texture(1u), mix_amount);
+}
+"#;
+
+const TOON_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+layout (location = 2) in vec3 a_normal;
+layout (location = 3) in mat4 world_from_object;
+layout (location = 7) in uint a_texture_set;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+// Toward the light, in world space
+uniform vec3 light_direction;
+
+out vec2 o_tex_coords;
+flat out uint o_texture_set;
+// Lighting happens in view space, where the camera sits at the origin
+out vec3 o_normal;
+out vec3 o_view_position;
+out vec3 o_light_direction;
+
+void main() {
+    mat4 view_from_object = view_from_world * world_from_object;
+    vec4 view_position = view_from_object * vec4(a_pos, 1.0f);
+    gl_Position = projection_from_view * view_position;
+    o_tex_coords = a_tex_coords;
+    o_texture_set = a_texture_set;
+    o_normal = mat3(transpose(inverse(view_from_object))) * a_normal;
+    o_view_position = view_position.xyz;
+    o_light_direction = mat3(view_from_world) * light_direction;
+}
+"#;
+
+const TOON_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+#ifdef BINDLESS_TEXTURES
+layout (std430, binding = 0) readonly buffer TextureHandles {
+    uvec2 texture_handles[];
+};
+#else
+uniform sampler2DArray texture_array;
+#endif
+uniform float mix_amount;
+uniform vec4 tint;
+uniform float bands;
+
+in vec2 o_tex_coords;
+flat in uint o_texture_set;
+in vec3 o_normal;
+in vec3 o_view_position;
+in vec3 o_light_direction;
+
+out vec4 frag_color;
+
+const float AMBIENT = 0.35f;
+const float RIM_STRENGTH = 0.4f;
+
+vec4 sample_texture(uint texture_i) {
+    uint table_i = o_texture_set * uint(TEXTURES_PER_SET) + texture_i;
+#ifdef BINDLESS_TEXTURES
+    return texture(sampler2D(texture_handles[table_i]), o_tex_coords);
+#else
+    return texture(texture_array, vec3(o_tex_coords, float(table_i)));
+#endif
+}
+
+void main() {
+    vec4 albedo = tint * mix(sample_texture(0u), sample_texture(1u), mix_amount);
+    vec3 normal = normalize(o_normal);
+    float diffuse = max(dot(normal, normalize(o_light_direction)), 0.0f);
+    // Flat bands instead of a smooth falloff, the lit side rounded up into the brightest
+    float banded = min(ceil(diffuse * bands) / bands, 1.0f);
+    // A hard-edged rim where the surface turns away from the camera, on the lit side
+    float facing = max(dot(normal, normalize(-o_view_position)), 0.0f);
+    float rim = smoothstep(0.3f, 0.35f, 1.0f - facing) * step(0.0f, diffuse - 0.01f);
+    vec3 color = albedo.rgb * (AMBIENT + (1.0f - AMBIENT) * banded) + RIM_STRENGTH * rim;
+    frag_color = vec4(color, albedo.a);
+}
+"#;
+
+const LIT_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+layout (location = 2) in vec3 a_normal;
+layout (location = 3) in mat4 world_from_object;
+layout (location = 7) in uint a_texture_set;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+out vec2 o_tex_coords;
+flat out uint o_texture_set;
+// Lights are uploaded in view space
+out vec3 o_normal;
+out vec3 o_view_position;
+// Light from the probes around, and how fully they cover the object
+out vec4 o_probe_light;
+// Reflection probes are placed in world space
+out vec3 o_world_position;
+out vec3 o_world_normal;
+
+#include light_probes
+
+void main() {
+    mat4 view_from_object = view_from_world * world_from_object;
+    vec4 view_position = view_from_object * vec4(a_pos, 1.0f);
+    gl_Position = projection_from_view * view_position;
+    o_tex_coords = a_tex_coords;
+    o_texture_set = a_texture_set;
+    o_normal = mat3(transpose(inverse(view_from_object))) * a_normal;
+    o_view_position = view_position.xyz;
+    vec3 world_normal = normalize(mat3(transpose(inverse(world_from_object))) * a_normal);
+    o_world_position = (world_from_object * vec4(a_pos, 1.0f)).xyz;
+    o_world_normal = world_normal;
+#ifdef PROBES_PER_VERTEX
+    // Static batches are merged into world space, so their vertices each blend for themselves
+    vec3 probe_point = o_world_position;
+#else
+    // Every vertex of an object blends the same probes, those around its origin
+    vec3 probe_point = world_from_object[3].xyz;
+#endif
+    o_probe_light = probe_irradiance(probe_point, world_normal);
+}
+"#;
+
+// `#include clustered_lighting` is replaced by light_clusters::clustered_lighting_source, and
+// `#include reflection_probes` by REFLECTION_PROBES_SOURCE
+const LIT_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+#ifdef BINDLESS_TEXTURES
+layout (std430, binding = 0) readonly buffer TextureHandles {
+    uvec2 texture_handles[];
+};
+#else
+uniform sampler2DArray texture_array;
+#endif
+uniform float mix_amount;
+uniform vec4 tint;
+
+in vec2 o_tex_coords;
+flat in uint o_texture_set;
+in vec3 o_normal;
+in vec3 o_view_position;
+in vec4 o_probe_light;
+in vec3 o_world_position;
+in vec3 o_world_normal;
+
+out vec4 frag_color;
+
+// Indirect light where no probe reaches
+const float AMBIENT = 0.05f;
+// Share of the light glossy surfaces reflect when seen head on, rising toward grazing angles
+const float REFLECTANCE = 0.15f;
+
+#include clustered_lighting
+#include reflection_probes
+
+vec4 sample_texture(uint texture_i) {
+    uint table_i = o_texture_set * uint(TEXTURES_PER_SET) + texture_i;
+#ifdef BINDLESS_TEXTURES
+    return texture(sampler2D(texture_handles[table_i]), o_tex_coords);
+#else
+    return texture(texture_array, vec3(o_tex_coords, float(table_i)));
+#endif
+}
+
+void main() {
+    vec4 albedo = tint * mix(sample_texture(0u), sample_texture(1u), mix_amount);
+    vec3 normal = normalize(o_normal);
+    vec3 indirect = mix(vec3(AMBIENT), o_probe_light.rgb, o_probe_light.a);
+    vec3 color = indirect * albedo.rgb + clustered_lighting(o_view_position, normal, albedo.rgb);
+#ifdef REFLECTIONS
+    vec3 world_normal = normalize(o_world_normal);
+    vec3 view_direction = normalize(o_world_position - camera_position);
+    vec4 reflection = probe_reflection(o_world_position, reflect(view_direction, world_normal));
+    float facing = max(dot(-view_direction, world_normal), 0.0f);
+    float fresnel = REFLECTANCE + (1.0f - REFLECTANCE) * pow(1.0f - facing, 5.0f);
+    color = mix(color, reflection.rgb, fresnel * reflection.a);
+#endif
+    frag_color = vec4(color, albedo.a);
+}
+"#;
+
+const MIRROR_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 2) in vec3 a_normal;
+layout (location = 3) in mat4 world_from_object;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+// The environment cubemap is looked up by world space direction
+out vec3 o_world_position;
+out vec3 o_world_normal;
+
+void main() {
+    vec4 world_position = world_from_object * vec4(a_pos, 1.0f);
+    gl_Position = projection_from_view * view_from_world * world_position;
+    o_world_position = world_position.xyz;
+    o_world_normal = mat3(transpose(inverse(world_from_object))) * a_normal;
+}
+"#;
+
+const MIRROR_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform samplerCube environment;
+uniform vec3 camera_position;
+uniform vec4 tint;
+
+in vec3 o_world_position;
+in vec3 o_world_normal;
+
+out vec4 frag_color;
+
+void main() {
+    vec3 view_direction = normalize(o_world_position - camera_position);
+    vec3 reflected = reflect(view_direction, normalize(o_world_normal));
+    frag_color = tint * vec4(texture(environment, reflected).rgb, 1.0f);
+}
+"#;
+
+/// GPU resources for a `Scene`. Static objects are merged into batches up front, everything else
+/// is drawn per object from its index into `Scene::objects`.
+pub struct SceneRenderables {
+    pub static_batches: Vec<(Rc<Mesh>, Rc<Material>, Option<Aabb>)>,
+    pub objects: Vec<(usize, Rc<Mesh>, Rc<Material>)>,
+    pub object_meshes: Vec<Rc<Mesh>>,
+    /// Programs of `Shading::Lit` and `Shading::Glossy`, each for objects and for static batches,
+    /// which read the light clusters and probes, and whether anything uses them or the mirror
+    /// program
+    pub lit_programs: Vec<LitProgram>,
+    pub has_lit_objects: bool,
+    /// Program of `Shading::Mirror`, which reads the environment capture
+    pub mirror_program: MirrorProgram,
+    /// The first mirror object, where the environment is captured from for every mirror
+    pub mirror_object: Option<usize>,
+    /// Whether `SceneLighting::bake` has captured the scene's probes since it was set up
+    pub probes_baked: bool,
+    /// Owns the textures the materials refer to by name
+    #[allow(dead_code)]
+    pub textures: Vec<Texture>,
+}
+
+impl SceneRenderables {
+    /// The material of the selected object, or else any material the scene is drawn with.
+    #[cfg(feature = "ui")]
+    pub fn active_material(&self, selected_object: Option<usize>) -> Option<&Rc<Material>> {
+        let selected = selected_object.and_then(|object_i| {
+            self.objects
+                .iter()
+                .find(|(drawn_i, _, _)| *drawn_i == object_i)
+        });
+        selected
+            .or_else(|| self.objects.first())
+            .map(|(_, _, material)| material)
+            .or_else(|| self.static_batches.first().map(|(_, material, _)| material))
+    }
+}
+
+pub struct LitProgram {
+    pub program: Rc<ShaderProgram>,
+    pub cluster_uniforms: LightClusterUniforms,
+    pub light_probe_uniforms: LightProbeUniforms,
+    pub reflection_probe_uniforms: ReflectionProbeUniforms,
+}
+
+pub struct MirrorProgram {
+    pub program: Rc<ShaderProgram>,
+    pub environment_uniforms: EnvironmentUniforms,
+}
+
+fn setup_program(
+    gl_context: &GlContext,
+    vertex_shader_source: &str,
+    fragment_shader_source: &str,
+    table_kind: TextureTableKind,
+    textures_per_set: usize,
+) -> ShaderProgram {
+    let program = ShaderProgram::with_shaders(
+        gl_context,
+        &texture_table::prepare_shader(vertex_shader_source, table_kind, textures_per_set),
+        &texture_table::prepare_shader(fragment_shader_source, table_kind, textures_per_set),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!(
+            "Failed building a scene shader, drawing flat magenta instead: {}",
+            e
+        );
+        fallback::flat_color_program(gl_context).expect("Fallback program setup failure")
+    });
+    program.use_program();
+    program
+        .uniform("texture_array")
+        .set_int(TEXTURE_ARRAY_UNIT as i32);
+    program.uniform("mix_amount").set_float(0.2_f32);
+    program.uniform("tint").set_color(&Color::WHITE);
+    program
+}
+
+/// Builds GPU resources for `scene`. The `unbatched` object is kept out of the static batches
+/// even when static, so it can be moved while editing.
+pub fn setup_scene(
+    settings: &Settings,
+    scene: &Scene,
+    unbatched: Option<usize>,
+    upload_worker: Option<&mut UploadWorker>,
+    gl_context: &GlContext,
+) -> SceneRenderables {
+    let texture_files = [
+        "resources/images/container.jpg",
+        "resources/images/awesomeface.png",
+    ];
+    // With a worker, the images decode and upload while the shaders compile
+    let texture_uploads: Option<(&mut UploadWorker, Vec<UploadId>)> = upload_worker.map(|worker| {
+        let uploads = texture_files
+            .iter()
+            .map(|file_path| worker.upload_texture(file_path, false))
+            .collect();
+        (worker, uploads)
+    });
+
+    let table_kind = TextureTableKind::detect(settings.renderer.bindless_textures);
+    let shader_program = setup_program(
+        gl_context,
+        VERTEX_SHADER_SOURCE,
+        FRAGMENT_SHADER_SOURCE,
+        table_kind,
+        texture_files.len(),
+    );
+    let toon_program = setup_program(
+        gl_context,
+        TOON_VERTEX_SHADER_SOURCE,
+        TOON_FRAGMENT_SHADER_SOURCE,
+        table_kind,
+        texture_files.len(),
+    );
+    let lit_vertex_shader_source =
+        LIT_VERTEX_SHADER_SOURCE.replace("#include light_probes", LIGHT_PROBES_SOURCE);
+    let lit_fragment_shader_source = LIT_FRAGMENT_SHADER_SOURCE
+        .replace(
+            "#include clustered_lighting",
+            &light_clusters::clustered_lighting_source(),
+        )
+        .replace("#include reflection_probes", REFLECTION_PROBES_SOURCE);
+    let lit_batches_vertex_shader_source = lit_vertex_shader_source.replacen(
+        "#version 330 core",
+        "#version 330 core\n#define PROBES_PER_VERTEX",
+        1,
+    );
+    let glossy_fragment_shader_source = lit_fragment_shader_source.replacen(
+        "#version 330 core",
+        "#version 330 core\n#define REFLECTIONS",
+        1,
+    );
+    // Indexed by whether they are glossy, then whether they draw batches
+    let lit_programs: Vec<LitProgram> = [
+        (
+            "scene lit",
+            &lit_vertex_shader_source,
+            &lit_fragment_shader_source,
+        ),
+        (
+            "scene lit batches",
+            &lit_batches_vertex_shader_source,
+            &lit_fragment_shader_source,
+        ),
+        (
+            "scene glossy",
+            &lit_vertex_shader_source,
+            &glossy_fragment_shader_source,
+        ),
+        (
+            "scene glossy batches",
+            &lit_batches_vertex_shader_source,
+            &glossy_fragment_shader_source,
+        ),
+    ]
+    .iter()
+    .map(|(label, vertex_shader_source, fragment_shader_source)| {
+        let program = setup_program(
+            gl_context,
+            vertex_shader_source,
+            fragment_shader_source,
+            table_kind,
+            texture_files.len(),
+        );
+        program.set_label(label);
+        LitProgram {
+            cluster_uniforms: LightClusterUniforms::new(&program),
+            light_probe_uniforms: LightProbeUniforms::new(&program),
+            reflection_probe_uniforms: ReflectionProbeUniforms::new(&program),
+            program: Rc::new(program),
+        }
+    })
+    .collect();
+    let mirror_program = setup_program(
+        gl_context,
+        MIRROR_VERTEX_SHADER_SOURCE,
+        MIRROR_FRAGMENT_SHADER_SOURCE,
+        table_kind,
+        texture_files.len(),
+    );
+    mirror_program.set_label("scene mirror");
+    let mirror_program = MirrorProgram {
+        environment_uniforms: EnvironmentUniforms::new(&mirror_program),
+        program: Rc::new(mirror_program),
+    };
+    shader_program.set_label("scene textured");
+    toon_program.set_label("scene toon");
+    // Cel-shaded objects are lit by the scene's first directional light
+    let toon_light_direction = scene
+        .lights
+        .iter()
+        .find_map(|light| match light.kind {
+            LightKind::Directional { direction, .. } => {
+                let toward_light = -glm::normalize(&glm::make_vec3(&direction));
+                Some([toward_light.x, toward_light.y, toward_light.z])
+            }
+            _ => None,
+        })
+        .unwrap_or(TOON_LIGHT_DIRECTION);
+    toon_program
+        .uniform("light_direction")
+        .set_vec3f(toon_light_direction);
+    toon_program.uniform("bands").set_float(TOON_BANDS);
+
+    #[rustfmt::skip]
+        let scene_vertices = [
+            //    X         Y         Z        S        T       NX       NY       NZ
+           -0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  0.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,
+            0.5_f32, -0.5_f32, -0.5_f32,  1.0_f32,  0.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,
+            0.5_f32,  0.5_f32, -0.5_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,
+            0.5_f32,  0.5_f32, -0.5_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,
+           -0.5_f32,  0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,
+           -0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  0.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,
+
+           -0.5_f32, -0.5_f32,  0.5_f32,  0.0_f32,  0.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,
+            0.5_f32, -0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,
+            0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,
+            0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,
+           -0.5_f32,  0.5_f32,  0.5_f32,  0.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,
+           -0.5_f32, -0.5_f32,  0.5_f32,  0.0_f32,  0.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,
+
+           -0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,  0.0_f32,
+           -0.5_f32,  0.5_f32, -0.5_f32,  1.0_f32,  1.0_f32, -1.0_f32,  0.0_f32,  0.0_f32,
+           -0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32, -1.0_f32,  0.0_f32,  0.0_f32,
+           -0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32, -1.0_f32,  0.0_f32,  0.0_f32,
+           -0.5_f32, -0.5_f32,  0.5_f32,  0.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,  0.0_f32,
+           -0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,  0.0_f32,
+
+            0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,
+            0.5_f32,  0.5_f32, -0.5_f32,  1.0_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,
+            0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,
+            0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,
+            0.5_f32, -0.5_f32,  0.5_f32,  0.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,
+            0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,  0.0_f32,
+
+           -0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,
+            0.5_f32, -0.5_f32, -0.5_f32,  1.0_f32,  1.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,
+            0.5_f32, -0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,
+            0.5_f32, -0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,
+           -0.5_f32, -0.5_f32,  0.5_f32,  0.0_f32,  0.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,
+           -0.5_f32, -0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  0.0_f32, -1.0_f32,  0.0_f32,
+
+           -0.5_f32,  0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,
+            0.5_f32,  0.5_f32, -0.5_f32,  1.0_f32,  1.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,
+            0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,
+            0.5_f32,  0.5_f32,  0.5_f32,  1.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,
+           -0.5_f32,  0.5_f32,  0.5_f32,  0.0_f32,  0.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,
+           -0.5_f32,  0.5_f32, -0.5_f32,  0.0_f32,  1.0_f32,  0.0_f32,  1.0_f32,  0.0_f32,
+        ];
+
+    let cube_data = MeshData {
+        vertices: scene_vertices.to_vec(),
+        indices: None,
+        attributes: vec![
+            // a_pos attribute
+            VertexAttribute {
+                location: 0,
+                components: 3,
+            },
+            // a_tex_coords attribute
+            VertexAttribute {
+                location: 1,
+                components: 2,
+            },
+            // a_normal attribute
+            VertexAttribute {
+                location: NORMAL_LOCATION,
+                components: 3,
+            },
+        ],
+    };
+
+    // Missing images are replaced by a checker texture
+    let textures: Vec<Texture> = match texture_uploads {
+        Some((worker, uploads)) => uploads
+            .into_iter()
+            .zip(texture_files.iter())
+            .map(|(upload_id, file_path)| match worker.wait(upload_id) {
+                Ok(Upload::Texture(texture)) => Texture::from_shared(gl_context, texture),
+                Ok(Upload::Mesh(_)) => panic!("Texture upload returned a mesh"),
+                Err(e) => {
+                    eprintln!("Failed loading texture file {}: {}", file_path, e);
+                    fallback::checker_texture(gl_context)
+                }
+            })
+            .collect(),
+        None => texture_files
+            .iter()
+            .map(
+                |file_path| match Texture::from_file(gl_context, file_path, false) {
+                    Ok(mut texture) => {
+                        texture.load(gl_context);
+                        texture
+                    }
+                    Err(e) => {
+                        eprintln!("Failed loading texture file {}: {}", file_path, e);
+                        fallback::checker_texture(gl_context)
+                    }
+                },
+            )
+            .collect(),
+    };
+    let texture_table = Rc::new(
+        TextureTable::build(gl_context, table_kind, &[textures.iter().collect()])
+            .expect("Texture table setup failure"),
+    );
+
+    let texture_ids: Vec<u32> = textures.iter().map(|texture| texture.id).collect();
+    let cube_material = Rc::new(
+        Material::new(Rc::new(shader_program), texture_ids.clone())
+            .with_texture_set(&texture_table, 0),
+    );
+    let toon_material = Rc::new(
+        Material::new(Rc::new(toon_program), texture_ids.clone())
+            .with_texture_set(&texture_table, 0)
+            .with_outline(HullOutline {
+                width: 0.03_f32,
+                color: Color::BLACK,
+            }),
+    );
+    let lit_materials: Vec<Rc<Material>> = lit_programs
+        .iter()
+        .map(|lit_program| {
+            Rc::new(
+                Material::new(Rc::clone(&lit_program.program), texture_ids.clone())
+                    .with_texture_set(&texture_table, 0),
+            )
+        })
+        .collect();
+    let mirror_material = Rc::new(
+        Material::new(Rc::clone(&mirror_program.program), texture_ids.clone())
+            .with_texture_set(&texture_table, 0),
+    );
+    let material_for = |object: &SceneObject, batched: bool| match object.shading {
+        Shading::Textured => &cube_material,
+        Shading::Toon => &toon_material,
+        Shading::Lit => &lit_materials[batched as usize],
+        Shading::Glossy => &lit_materials[2 + batched as usize],
+        Shading::Mirror => &mirror_material,
+    };
+
+    // The cube and the static batches share buffers, so where multi-draw is supported one
+    // indirect call draws all of them
+    let mut mesh_pool = MeshPool::new(&cube_data.attributes);
+    let cube_mesh_i = mesh_pool.add(&cube_data).expect("Mesh pooling failure");
+    let mut batcher = StaticBatcher::new().with_normal_location(NORMAL_LOCATION);
+    let mut drawn_objects = vec![];
+    let static_objects = scene.static_objects();
+    for (object_i, object) in scene.objects.iter().enumerate() {
+        // The cube is the only mesh, and stands in for any other
+        if object.mesh != "cube" {
+            eprintln!(
+                "Scene object {} has unknown mesh {}, drawing a unit cube instead",
+                object.name, object.mesh
+            );
+        }
+        // Mirrors stay apart so environment captures can leave them out
+        if settings.renderer.batch_static_geometry
+            && static_objects[object_i]
+            && unbatched != Some(object_i)
+            && object.shading != Shading::Mirror
+        {
+            batcher
+                .add(
+                    &cube_data,
+                    material_for(object, true),
+                    &object.transform.world_from_object(),
+                )
+                .expect("Static batching failure");
+        } else {
+            drawn_objects.push(object_i);
+        }
+    }
+    let batches = batcher
+        .build_into(&mut mesh_pool)
+        .expect("Static batching failure");
+    let meshes = mesh_pool.build(gl_context).expect("Mesh pooling failure");
+    let cube_mesh = &meshes[cube_mesh_i];
+
+    SceneRenderables {
+        static_batches: batches
+            .into_iter()
+            .map(|(mesh_i, material, bounds)| (Rc::clone(&meshes[mesh_i]), material, bounds))
+            .collect(),
+        objects: drawn_objects
+            .into_iter()
+            .map(|object_i| {
+                (
+                    object_i,
+                    Rc::clone(cube_mesh),
+                    Rc::clone(material_for(&scene.objects[object_i], false)),
+                )
+            })
+            .collect(),
+        object_meshes: scene.objects.iter().map(|_| Rc::clone(cube_mesh)).collect(),
+        lit_programs,
+        has_lit_objects: scene.objects.iter().any(|object| {
+            matches!(
+                object.shading,
+                Shading::Lit | Shading::Glossy | Shading::Mirror
+            )
+        }),
+        mirror_program,
+        mirror_object: scene
+            .objects
+            .iter()
+            .position(|object| object.shading == Shading::Mirror),
+        probes_baked: false,
+        textures,
+    }
+}
+
+/// Queues every object of the scene, blending moving objects between the last two simulation
+/// steps. Objects and batches with known bounds are frustum culled.
+pub fn submit_scene(
+    renderer: &mut Renderer,
+    scene_renderables: &SceneRenderables,
+    scene: &Scene,
+    world_matrices: &WorldMatrices,
+) {
+    for (batch_i, (mesh, material, bounds)) in scene_renderables.static_batches.iter().enumerate() {
+        match bounds {
+            Some(bounds) => renderer.submit_occlusion_culled(
+                BATCH_OCCLUSION_KEYS + batch_i as u64,
+                mesh,
+                material,
+                &glm::identity(),
+                bounds,
+            ),
+            None => renderer.submit(mesh, material, &glm::identity()),
+        }
+    }
+    for (object_i, mesh, material) in scene_renderables.objects.iter() {
+        let world_from_object = world_matrices.get(*object_i);
+        match mesh_bounds(&scene.objects[*object_i].mesh) {
+            Some(bounds) => renderer.submit_occlusion_culled(
+                *object_i as u64,
+                mesh,
+                material,
+                world_from_object,
+                &bounds.transformed(world_from_object),
+            ),
+            None => renderer.submit(mesh, material, world_from_object),
+        }
+    }
+}
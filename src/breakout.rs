@@ -0,0 +1,375 @@
+// A Breakout game drawn with the sprite batch, after the one LearnOpenGL builds in its "In
+// Practice" chapters: a paddle, a ball and levels of bricks in a field of world pixels.
+
+use gl::types::*;
+
+use crate::flipbook::Flipbook;
+use crate::math::color::Color;
+use crate::math::glm::{self, Vec2};
+use crate::ogl::billboard_renderer::{PUFF_SHEET_COLUMNS, PUFF_SHEET_ROWS};
+use crate::ogl::sprite_batch::{Sprite, SpriteBatch};
+
+/// Width and height of the playing field, in world pixels.
+pub const FIELD_SIZE: [f32; 2] = [800.0_f32, 600.0_f32];
+const PADDLE_SIZE: [f32; 2] = [100.0_f32, 20.0_f32];
+// Pixels per second
+const PADDLE_SPEED: f32 = 500.0_f32;
+const BALL_RADIUS: f32 = 12.5_f32;
+const BALL_SERVE_VELOCITY: [f32; 2] = [100.0_f32, -350.0_f32];
+// How much hitting the paddle off center steers the ball sideways
+const PADDLE_STEERING: f32 = 2.0_f32;
+const LIVES: u32 = 3;
+const BRICK_POINTS: u32 = 10;
+// Puffs the ball leaves behind it, one per step, and how long they last in seconds
+const TRAIL_LIFETIME: f32 = 0.4_f32;
+const TRAIL_SIZE: f32 = 30.0_f32;
+// Pixels across a life in the HUD
+const LIFE_ICON_SIZE: f32 = 24.0_f32;
+
+// Rows of bricks filling the top half of the field: 0 leaves a gap, 1 is a solid brick the ball
+// bounces off forever, 2 to 5 are colors
+const LEVELS: [&str; 3] = [
+    "5 5 5 5 5 5 5 5 5 5 5 5 5 5 5
+     5 5 5 5 5 5 5 5 5 5 5 5 5 5 5
+     4 4 4 4 4 0 0 0 0 0 4 4 4 4 4
+     4 1 4 1 4 0 0 1 0 0 4 1 4 1 4
+     3 3 3 3 3 0 0 0 0 0 3 3 3 3 3
+     3 3 1 3 3 3 3 3 3 3 3 3 1 3 3
+     2 2 2 2 2 2 2 2 2 2 2 2 2 2 2
+     2 2 2 2 2 2 2 2 2 2 2 2 2 2 2",
+    "1 1 1 1 1 1 1 1 1 1 1 1 1
+     1 0 5 5 0 5 5 0 5 5 0 5 1
+     1 5 5 5 5 5 5 5 5 5 5 5 1
+     1 0 3 3 0 3 3 0 3 3 0 3 1
+     1 3 3 3 3 3 3 3 3 3 3 3 1
+     1 0 2 2 0 2 2 0 2 2 0 2 1
+     1 2 2 2 2 2 2 2 2 2 2 2 1
+     1 0 5 5 0 5 5 0 5 5 0 5 1
+     1 1 1 1 1 1 1 1 1 1 1 1 1",
+    "0 0 0 0 0 0 0 0 0 0 0 0 0
+     0 0 2 0 0 0 0 0 0 0 2 0 0
+     0 0 0 2 0 0 0 0 0 2 0 0 0
+     0 0 0 5 5 5 5 5 5 5 0 0 0
+     0 0 5 5 0 5 5 5 0 5 5 0 0
+     0 5 5 5 5 5 5 5 5 5 5 5 0
+     0 3 0 1 1 1 1 1 1 1 0 3 0
+     0 3 0 3 0 0 0 0 0 3 0 3 0
+     0 0 0 0 4 4 0 4 4 0 0 0 0",
+];
+
+/// Textures the game draws with, all loaded flipped vertically.
+pub struct BreakoutTextures {
+    pub ball: GLuint,
+    pub solid_brick: GLuint,
+    /// Sheet of `PUFF_SHEET_COLUMNS` by `PUFF_SHEET_ROWS` frames for the ball's trail
+    pub puff_sheet: GLuint,
+}
+
+struct Brick {
+    position: Vec2,
+    size: Vec2,
+    solid: bool,
+    color: Color,
+    destroyed: bool,
+}
+
+struct Ball {
+    center: Vec2,
+    velocity: Vec2,
+    /// Rides on the paddle until served
+    stuck: bool,
+}
+
+/// A puff of the ball's trail.
+struct TrailPuff {
+    center: Vec2,
+    age: f32,
+}
+
+/// The game's state, stepped with the simulation and added to a sprite batch to draw.
+pub struct Breakout {
+    level: usize,
+    bricks: Vec<Brick>,
+    /// Left edge of the paddle, which sits on the bottom of the field
+    paddle_x: f32,
+    ball: Ball,
+    trail: Vec<TrailPuff>,
+    trail_flipbook: Flipbook,
+    pub lives: u32,
+    pub score: u32,
+}
+
+impl Breakout {
+    /// The first level, with the ball on the paddle.
+    pub fn new() -> Breakout {
+        let mut breakout = Breakout {
+            level: 0,
+            bricks: vec![],
+            paddle_x: (FIELD_SIZE[0] - PADDLE_SIZE[0]) * 0.5_f32,
+            ball: Ball {
+                center: glm::vec2(0.0_f32, 0.0_f32),
+                velocity: glm::vec2(0.0_f32, 0.0_f32),
+                stuck: true,
+            },
+            trail: vec![],
+            trail_flipbook: Flipbook::new(
+                PUFF_SHEET_COLUMNS,
+                PUFF_SHEET_ROWS,
+                (PUFF_SHEET_COLUMNS * PUFF_SHEET_ROWS) as f32 / TRAIL_LIFETIME,
+            )
+            .once(),
+            lives: LIVES,
+            score: 0,
+        };
+        breakout.load_level(0);
+        breakout
+    }
+
+    /// Levels count from 1 here, as shown to the player.
+    pub fn level(&self) -> usize {
+        self.level + 1
+    }
+
+    /// Whether the ball is waiting on the paddle to be served.
+    pub fn serving(&self) -> bool {
+        self.ball.stuck
+    }
+
+    /// Advances the game by `dt` seconds, moving the paddle by `paddle_direction`, -1 for full
+    /// speed left to 1 for full speed right, and serving the ball when `launch` is set.
+    pub fn update(&mut self, dt: f32, paddle_direction: f32, launch: bool) {
+        self.paddle_x = (self.paddle_x
+            + paddle_direction.clamp(-1.0_f32, 1.0_f32) * PADDLE_SPEED * dt)
+            .clamp(0.0_f32, FIELD_SIZE[0] - PADDLE_SIZE[0]);
+        for puff in self.trail.iter_mut() {
+            puff.age += dt;
+        }
+        self.trail.retain(|puff| puff.age < TRAIL_LIFETIME);
+
+        if self.ball.stuck {
+            self.ball.center = self.paddle_top_center();
+            if launch {
+                self.ball.stuck = false;
+                self.ball.velocity = glm::vec2(BALL_SERVE_VELOCITY[0], BALL_SERVE_VELOCITY[1]);
+            }
+            return;
+        }
+        let ball = &mut self.ball;
+        ball.center += ball.velocity * dt;
+        // Walls on the left, right and top
+        if ball.center.x < BALL_RADIUS {
+            ball.center.x = BALL_RADIUS;
+            ball.velocity.x = ball.velocity.x.abs();
+        } else if ball.center.x > FIELD_SIZE[0] - BALL_RADIUS {
+            ball.center.x = FIELD_SIZE[0] - BALL_RADIUS;
+            ball.velocity.x = -ball.velocity.x.abs();
+        }
+        if ball.center.y < BALL_RADIUS {
+            ball.center.y = BALL_RADIUS;
+            ball.velocity.y = ball.velocity.y.abs();
+        }
+        self.trail.push(TrailPuff {
+            center: ball.center,
+            age: 0.0_f32,
+        });
+
+        for brick in self.bricks.iter_mut().filter(|brick| !brick.destroyed) {
+            if let Some(push) = push_out(
+                &ball.center,
+                BALL_RADIUS,
+                &brick.position,
+                &(brick.position + brick.size),
+            ) {
+                bounce(ball, &push);
+                if !brick.solid {
+                    brick.destroyed = true;
+                    self.score += BRICK_POINTS;
+                }
+            }
+        }
+
+        let paddle_position = glm::vec2(self.paddle_x, FIELD_SIZE[1] - PADDLE_SIZE[1]);
+        let paddle_size = glm::vec2(PADDLE_SIZE[0], PADDLE_SIZE[1]);
+        if ball.velocity.y > 0.0_f32
+            && push_out(
+                &ball.center,
+                BALL_RADIUS,
+                &paddle_position,
+                &(paddle_position + paddle_size),
+            )
+            .is_some()
+        {
+            // Off center hits steer the ball that way, at the speed it came in
+            let half_width = paddle_size.x * 0.5_f32;
+            let off_center = (ball.center.x - (paddle_position.x + half_width)) / half_width;
+            let speed = ball.velocity.x.hypot(ball.velocity.y);
+            let velocity = glm::vec2(
+                BALL_SERVE_VELOCITY[0] * off_center * PADDLE_STEERING,
+                -ball.velocity.y.abs(),
+            );
+            ball.velocity = velocity * (speed / velocity.x.hypot(velocity.y));
+            ball.center.y = paddle_position.y - BALL_RADIUS;
+        }
+
+        if ball.center.y > FIELD_SIZE[1] + BALL_RADIUS {
+            self.lives -= 1;
+            if self.lives == 0 {
+                self.lives = LIVES;
+                self.score = 0;
+                self.load_level(self.level);
+            }
+            self.ball.stuck = true;
+        } else if self
+            .bricks
+            .iter()
+            .all(|brick| brick.solid || brick.destroyed)
+        {
+            self.load_level((self.level + 1) % LEVELS.len());
+            self.ball.stuck = true;
+        }
+    }
+
+    /// Adds the field, the bricks, the paddle and the ball to `batch`, in world pixels with
+    /// the field from the origin to `FIELD_SIZE`.
+    pub fn add_sprites(&self, textures: &BreakoutTextures, batch: &mut SpriteBatch) {
+        batch.add(
+            Sprite::new(
+                glm::vec2(0.0_f32, 0.0_f32),
+                glm::vec2(FIELD_SIZE[0], FIELD_SIZE[1]),
+            )
+            .with_color(Color::from_srgb(0.06, 0.07, 0.14, 1.0)),
+        );
+        for puff in self.trail.iter() {
+            let fade = 1.0_f32 - puff.age / TRAIL_LIFETIME;
+            let half_size = glm::vec2(TRAIL_SIZE, TRAIL_SIZE) * 0.5_f32;
+            batch.add(
+                Sprite::new(puff.center - half_size, half_size * 2.0_f32)
+                    .with_texture(textures.puff_sheet)
+                    .with_uv_rect(self.trail_flipbook.uv_rect_at(puff.age))
+                    .with_color(Color::from_srgb(1.0, 0.7, 0.3, 0.5 * fade))
+                    .with_layer(1),
+            );
+        }
+        for brick in self.bricks.iter().filter(|brick| !brick.destroyed) {
+            let sprite = Sprite::new(brick.position, brick.size)
+                .with_color(brick.color)
+                .with_layer(2);
+            batch.add(if brick.solid {
+                sprite.with_texture(textures.solid_brick)
+            } else {
+                sprite
+            });
+        }
+        batch.add(
+            Sprite::new(
+                glm::vec2(self.paddle_x, FIELD_SIZE[1] - PADDLE_SIZE[1]),
+                glm::vec2(PADDLE_SIZE[0], PADDLE_SIZE[1]),
+            )
+            .with_color(Color::from_srgb(0.85, 0.85, 0.9, 1.0))
+            .with_layer(2),
+        );
+        let ball_size = glm::vec2(BALL_RADIUS, BALL_RADIUS) * 2.0_f32;
+        batch.add(
+            Sprite::new(self.ball.center - ball_size * 0.5_f32, ball_size)
+                .with_texture(textures.ball)
+                .with_layer(3),
+        );
+    }
+
+    /// Adds a ball per life left to `batch`, in a row from `top_left`, for a HUD drawn in
+    /// screen pixels.
+    pub fn add_hud_sprites(
+        &self,
+        textures: &BreakoutTextures,
+        top_left: Vec2,
+        batch: &mut SpriteBatch,
+    ) {
+        let size = glm::vec2(LIFE_ICON_SIZE, LIFE_ICON_SIZE);
+        for life in 0..self.lives {
+            let offset = glm::vec2(life as f32 * LIFE_ICON_SIZE * 1.25_f32, 0.0_f32);
+            batch.add(Sprite::new(top_left + offset, size).with_texture(textures.ball));
+        }
+    }
+
+    /// Lays out level `level` of `LEVELS` in rows of equally sized bricks over the top half of
+    /// the field.
+    fn load_level(&mut self, level: usize) {
+        self.level = level;
+        self.bricks.clear();
+        let rows: Vec<Vec<u32>> = LEVELS[level]
+            .lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .filter_map(|brick| brick.parse().ok())
+                    .collect()
+            })
+            .filter(|row: &Vec<u32>| !row.is_empty())
+            .collect();
+        let colors = [
+            Color::from_srgb(0.2, 0.6, 1.0, 1.0),
+            Color::from_srgb(0.0, 0.7, 0.0, 1.0),
+            Color::from_srgb(0.8, 0.8, 0.4, 1.0),
+            Color::from_srgb(1.0, 0.5, 0.0, 1.0),
+        ];
+        let height = FIELD_SIZE[1] * 0.5_f32 / rows.len() as f32;
+        for (row_i, row) in rows.iter().enumerate() {
+            let width = FIELD_SIZE[0] / row.len() as f32;
+            for (column_i, &brick) in row.iter().enumerate() {
+                if brick == 0 {
+                    continue;
+                }
+                self.bricks.push(Brick {
+                    position: glm::vec2(column_i as f32 * width, row_i as f32 * height),
+                    size: glm::vec2(width, height),
+                    solid: brick == 1,
+                    color: if brick == 1 {
+                        Color::from_srgb(0.8, 0.8, 0.7, 1.0)
+                    } else {
+                        colors[(brick as usize - 2).min(colors.len() - 1)]
+                    },
+                    destroyed: false,
+                });
+            }
+        }
+    }
+
+    fn paddle_top_center(&self) -> Vec2 {
+        glm::vec2(
+            self.paddle_x + PADDLE_SIZE[0] * 0.5_f32,
+            FIELD_SIZE[1] - PADDLE_SIZE[1] - BALL_RADIUS,
+        )
+    }
+}
+
+/// How far a circle overlapping a box has to move along one axis to leave it, or `None` when they
+/// do not overlap. The axis is the one the circle's center is farthest outside the box along.
+fn push_out(center: &Vec2, radius: f32, box_min: &Vec2, box_max: &Vec2) -> Option<Vec2> {
+    let closest = glm::vec2(
+        center.x.clamp(box_min.x, box_max.x),
+        center.y.clamp(box_min.y, box_max.y),
+    );
+    let offset = center - closest;
+    if offset.x * offset.x + offset.y * offset.y >= radius * radius {
+        return None;
+    }
+    // A center inside the box leaves through the bottom, like a ball coming up from the paddle
+    if offset.x == 0.0_f32 && offset.y == 0.0_f32 {
+        return Some(glm::vec2(0.0_f32, box_max.y - center.y + radius));
+    }
+    Some(if offset.x.abs() > offset.y.abs() {
+        glm::vec2(offset.x.signum() * radius - offset.x, 0.0_f32)
+    } else {
+        glm::vec2(0.0_f32, offset.y.signum() * radius - offset.y)
+    })
+}
+
+/// Moves `ball` out by `push` and turns it away along that axis. Turning away rather than
+/// reversing keeps two bricks hit in one step from cancelling each other out.
+fn bounce(ball: &mut Ball, push: &Vec2) {
+    ball.center += push;
+    if push.x != 0.0_f32 {
+        ball.velocity.x = ball.velocity.x.abs() * push.x.signum();
+    } else {
+        ball.velocity.y = ball.velocity.y.abs() * push.y.signum();
+    }
+}
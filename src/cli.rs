@@ -0,0 +1,240 @@
+use crate::config::Settings;
+use crate::ogl::capabilities::GlApi;
+
+pub const USAGE: &str = "\
+Usage: learn-opengl-rs [OPTIONS]
+
+Options override the values read from settings.toml.
+    --width <PIXELS>        Window width
+    --height <PIXELS>       Window height
+    --fullscreen            Open fullscreen (toggle at runtime with Alt+Enter)
+    --monitor <INDEX>       Monitor to go fullscreen on, as listed at startup
+    --no-vsync              Disable vertical sync (cycle off/on/adaptive at runtime with V)
+    --max-fps <FPS>         Cap the frame rate, 0 for no cap
+    --gl-version <MAJ.MIN>  Newest OpenGL context version to request, e.g. 3.3
+    --gles                  Request an OpenGL ES 3.0 context instead
+    --lesson <NAME>         Lesson to run: cubes, stress, particles, gpu_particles,
+                            gpu_culling, terrain, water, grass, mirror, toon, lights,
+                            reflections, breakout or hdr
+    --headless <FRAMES>     Render FRAMES frames offscreen in a hidden window, save them and exit
+    --output <DIR>          Directory for --headless images [default: headless_output]
+    --skybox <FILE>         Equirectangular .hdr panorama to draw behind the scene
+    --debug-view            Open a second window showing the scene from the debug camera
+    --renderdoc             Load RenderDoc's library to capture frames with F12, unless already
+                            launched through RenderDoc
+    --gl-debug              Request a debug context and abort with a backtrace on the first
+                            high-severity driver message
+    -h, --help              Print this message";
+
+/// The demo scenes `--lesson` picks between.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lesson {
+    Cubes,
+    /// 100,000 spinning cubes, for measuring the renderer
+    Stress,
+    /// Fountain, smoke and fire particle emitters
+    Particles,
+    /// A million particles simulated on the GPU
+    GpuParticles,
+    /// A quarter of a million shapes frustum culled by a compute shader
+    GpuCulling,
+    /// Heightmap terrain tessellated by distance from the camera
+    Terrain,
+    /// The terrain around a lake reflecting and refracting it
+    Water,
+    /// The terrain covered in grass swaying in the wind
+    Grass,
+    /// The cubes over a floor mirror drawn with the stencil buffer
+    Mirror,
+    /// The cubes, every other one cel shaded with an outline
+    Toon,
+    /// Cubes on a slab lit by hundreds of point lights through clustered light lists
+    Lights,
+    /// Two rooms with glossy floors reflecting them through box-projected reflection probes
+    Reflections,
+    /// A Breakout game drawn in 2D with the sprite batch
+    Breakout,
+    /// A tunnel with a very bright light at its end, tone mapped two ways side by side
+    Hdr,
+}
+
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    pub monitor: Option<usize>,
+    pub no_vsync: bool,
+    pub max_fps: Option<u32>,
+    pub gl_version: Option<[u32; 2]>,
+    pub gles: bool,
+    pub lesson: Option<Lesson>,
+    pub headless_frames: Option<u32>,
+    pub output: Option<String>,
+    pub skybox: Option<String>,
+    pub debug_view: bool,
+    pub renderdoc: bool,
+    pub gl_debug: bool,
+    pub help: bool,
+}
+
+impl CliArgs {
+    /// Parses the arguments following the program name. Values may be given either as
+    /// `--flag value` or `--flag=value`.
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<CliArgs, String> {
+        let mut cli_args = CliArgs::default();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.find('=') {
+                Some(split_i) if arg.starts_with("--") => (
+                    arg[..split_i].to_string(),
+                    Some(arg[split_i + 1..].to_string()),
+                ),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("Missing value for {}", flag))
+            };
+            // Switches are only ever turned on, so `--fullscreen=false` is a mistake
+            let no_value = || match &inline_value {
+                Some(_) => Err(format!("{} takes no value", flag)),
+                None => Ok(true),
+            };
+            match flag.as_str() {
+                "--width" => cli_args.width = Some(parse_dimension(&flag, &value()?)?),
+                "--height" => cli_args.height = Some(parse_dimension(&flag, &value()?)?),
+                "--fullscreen" => cli_args.fullscreen = no_value()?,
+                "--monitor" => cli_args.monitor = Some(parse_number(&flag, &value()?)? as usize),
+                "--no-vsync" => cli_args.no_vsync = no_value()?,
+                "--max-fps" => cli_args.max_fps = Some(parse_number(&flag, &value()?)?),
+                "--gl-version" => cli_args.gl_version = Some(parse_gl_version(&value()?)?),
+                "--gles" => cli_args.gles = no_value()?,
+                "--lesson" => cli_args.lesson = Some(Lesson::from_name(&value()?)?),
+                "--headless" => cli_args.headless_frames = Some(parse_number(&flag, &value()?)?),
+                "--output" => cli_args.output = Some(value()?),
+                "--skybox" => cli_args.skybox = Some(value()?),
+                "--debug-view" => cli_args.debug_view = no_value()?,
+                "--renderdoc" => cli_args.renderdoc = no_value()?,
+                "--gl-debug" => cli_args.gl_debug = no_value()?,
+                "-h" | "--help" => cli_args.help = no_value()?,
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+        Ok(cli_args)
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(width) = self.width {
+            settings.window.width = width;
+        }
+        if let Some(height) = self.height {
+            settings.window.height = height;
+        }
+        if self.fullscreen {
+            settings.window.fullscreen = true;
+        }
+        if let Some(monitor) = self.monitor {
+            settings.window.monitor = monitor;
+        }
+        if self.no_vsync {
+            settings.window.vsync = false;
+        }
+        if let Some(max_fps) = self.max_fps {
+            settings.window.max_fps = max_fps;
+        }
+        if self.headless_frames.is_some() {
+            // The hidden window never has focus, which would otherwise throttle it
+            settings.window.background_fps = 0;
+        }
+        if let Some(gl_version) = self.gl_version {
+            settings.window.gl_version = gl_version;
+        }
+        if self.gles {
+            settings.window.api = GlApi::OpenGlEs;
+        }
+        if let Some(skybox) = &self.skybox {
+            settings.renderer.skybox = skybox.clone();
+        }
+        if self.debug_view {
+            settings.debug_view.enabled = true;
+        }
+        if self.gl_debug {
+            settings.window.gl_debug = true;
+        }
+    }
+}
+
+impl Lesson {
+    /// The name `from_name` takes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lesson::Cubes => "cubes",
+            Lesson::Stress => "stress",
+            Lesson::Particles => "particles",
+            Lesson::GpuParticles => "gpu_particles",
+            Lesson::GpuCulling => "gpu_culling",
+            Lesson::Terrain => "terrain",
+            Lesson::Water => "water",
+            Lesson::Grass => "grass",
+            Lesson::Mirror => "mirror",
+            Lesson::Toon => "toon",
+            Lesson::Lights => "lights",
+            Lesson::Reflections => "reflections",
+            Lesson::Breakout => "breakout",
+            Lesson::Hdr => "hdr",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Lesson, String> {
+        match name {
+            "cubes" => Ok(Lesson::Cubes),
+            "stress" => Ok(Lesson::Stress),
+            "particles" => Ok(Lesson::Particles),
+            "gpu_particles" => Ok(Lesson::GpuParticles),
+            "gpu_culling" => Ok(Lesson::GpuCulling),
+            "terrain" => Ok(Lesson::Terrain),
+            "water" => Ok(Lesson::Water),
+            "grass" => Ok(Lesson::Grass),
+            "mirror" => Ok(Lesson::Mirror),
+            "toon" => Ok(Lesson::Toon),
+            "lights" => Ok(Lesson::Lights),
+            "reflections" => Ok(Lesson::Reflections),
+            "breakout" => Ok(Lesson::Breakout),
+            "hdr" => Ok(Lesson::Hdr),
+            _ => Err(format!(
+                "Unknown lesson {}, available lessons: cubes, stress, particles, gpu_particles, gpu_culling, terrain, water, grass, mirror, toon, lights, reflections, breakout, hdr",
+                name
+            )),
+        }
+    }
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value {} for {}", value, flag))
+}
+
+/// A window size, which must be at least one pixel.
+fn parse_dimension(flag: &str, value: &str) -> Result<u32, String> {
+    match parse_number(flag, value)? {
+        0 => Err(format!("{} must be at least 1", flag)),
+        pixels => Ok(pixels),
+    }
+}
+
+fn parse_gl_version(value: &str) -> Result<[u32; 2], String> {
+    let mut parts = value.splitn(2, '.');
+    match (
+        parts.next().and_then(|major| major.parse().ok()),
+        parts.next().and_then(|minor| minor.parse().ok()),
+    ) {
+        (Some(major), Some(minor)) => Ok([major, minor]),
+        _ => Err(format!(
+            "Invalid value {} for --gl-version, expected MAJOR.MINOR",
+            value
+        )),
+    }
+}
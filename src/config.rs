@@ -0,0 +1,292 @@
+use crate::input_map::InputBindings;
+use crate::math::color::Color;
+use crate::ogl::capabilities::GlApi;
+use crate::ogl::environment_capture::CaptureMode;
+use crate::ogl::post_process::PostProcessSettings;
+use crate::ogl::renderer::Visualization;
+use crate::scene::CameraPose;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub renderer: RendererSettings,
+    pub input: InputSettings,
+    pub capture: CaptureSettings,
+    pub text: TextSettings,
+    pub debug_view: DebugViewSettings,
+    pub post_process: PostProcessSettings,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    /// `{fps}`, `{frame_time}` and `{resolution}` are filled in once per second
+    pub title: String,
+    /// Image file for the window icon, empty for the platform default
+    pub icon: String,
+    pub width: u32,
+    pub height: u32,
+    pub vsync: bool,
+    /// Tear instead of waiting a whole interval when a frame misses vsync, where supported
+    pub adaptive_vsync: bool,
+    pub double_buffer: bool,
+    /// CPU-side frame rate cap, 0 for none
+    pub max_fps: u32,
+    /// Frame rate cap while the window is not focused, 0 for none
+    pub background_fps: u32,
+    pub fullscreen: bool,
+    pub fullscreen_mode: FullscreenMode,
+    /// Index into the connected monitors listed at startup; 0 is the primary monitor
+    pub monitor: usize,
+    pub msaa_samples: u32,
+    pub gl_version: [u32; 2],
+    pub api: GlApi,
+    /// Request a debug context and abort on the first high-severity driver message
+    pub gl_debug: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RendererSettings {
+    pub fov: f32,
+    pub batch_static_geometry: bool,
+    /// Skip objects outside the camera's view
+    pub frustum_culling: bool,
+    /// Skip large objects hidden behind others, a frame late
+    pub occlusion_culling: bool,
+    /// Draw repeated meshes with one instanced draw call
+    pub instancing: bool,
+    /// Draw meshes sharing buffers with one indirect call where OpenGL 4.3 allows
+    pub multi_draw_indirect: bool,
+    /// Sample textures through resident bindless handles where ARB_bindless_texture allows
+    pub bindless_textures: bool,
+    /// Draw lines along mesh normals with a geometry shader, for debugging
+    pub show_normals: bool,
+    pub normal_length: f32,
+    pub normal_color: Color,
+    /// Draw the edges of every triangle over the shaded scene, for inspecting meshes
+    pub wireframe_overlay: bool,
+    pub wireframe_width: f32,
+    pub wireframe_color: Color,
+    /// Show depth, normals or overdraw in place of the materials
+    pub visualization: Visualization,
+    /// Draw object bounds, the frozen culling frustum, a ground grid and the selected object's
+    /// axes as lines
+    pub debug_draw: bool,
+    /// Mark the scene's lights with icons, arrows and cones
+    pub show_lights: bool,
+    /// Also outline how far each point and spot light reaches
+    pub show_light_ranges: bool,
+    /// Name the scene's objects, lights and probes where they are, with the label font
+    pub show_labels: bool,
+    /// How many faces of the mirror objects' environment cubemap are rendered each frame
+    pub environment_capture: CaptureMode,
+    /// Pixels across each face of the environment cubemap
+    pub environment_capture_size: i32,
+    /// Equirectangular `.hdr` panorama drawn behind the scene, empty for none
+    pub skybox: String,
+    /// Pixels across each face of the cubemap the skybox is converted to
+    pub skybox_size: i32,
+    /// Directory converted cubemaps are saved to and loaded from, empty to convert every run
+    pub cubemap_cache: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub mouse_sensitivity: f32,
+    /// Seconds over which mouse look motion is smoothed, 0 to disable
+    pub look_smoothing: f32,
+    /// Exponent applied to mouse speed: above 1 fast flicks turn further, 1 keeps look linear
+    pub look_acceleration: f32,
+    pub move_speed: f32,
+    pub picking: PickingMode,
+    /// Stick deflection, out of 1, ignored as noise
+    pub gamepad_dead_zone: f32,
+    /// Degrees per second of camera turn at full right stick deflection
+    pub gamepad_look_speed: f32,
+    pub bindings: InputBindings,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CaptureSettings {
+    pub directory: String,
+    pub framerate: u32,
+    pub max_queued_frames: usize,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FullscreenMode {
+    /// Switch the monitor to the window's size
+    Exclusive,
+    /// Cover the monitor at its current resolution
+    Borderless,
+}
+
+/// TrueType text drawn over the scene, such as the selected object's name.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TextSettings {
+    /// TrueType font file, empty to disable the text
+    pub font: String,
+    /// Text height in pixels before content scaling
+    pub font_size: f32,
+    /// Bake glyphs as signed distance fields, which stay sharp at any size
+    pub signed_distance_field: bool,
+    /// Height in world units of the labels placed in the scene, which then shrink with
+    /// distance; 0 keeps them `font_size` pixels tall
+    pub world_label_height: f32,
+}
+
+/// Second window showing the scene from a fixed camera, next to the main view.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DebugViewSettings {
+    pub enabled: bool,
+    pub width: u32,
+    pub height: u32,
+    pub camera: CameraPose,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PickingMode {
+    /// Intersect a cursor ray with each object's analytic bounds
+    Ray,
+    /// Render object ids offscreen and read back the pixel under the cursor
+    IdBuffer,
+}
+
+impl Default for WindowSettings {
+    fn default() -> WindowSettings {
+        WindowSettings {
+            title: "Learn OpenGL - {fps} FPS".to_string(),
+            icon: "resources/images/awesomeface.png".to_string(),
+            width: 800,
+            height: 600,
+            vsync: true,
+            adaptive_vsync: false,
+            double_buffer: true,
+            max_fps: 0,
+            background_fps: 15,
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::Exclusive,
+            monitor: 0,
+            msaa_samples: 0,
+            gl_version: [4, 5],
+            api: GlApi::OpenGl,
+            gl_debug: false,
+        }
+    }
+}
+
+impl Default for RendererSettings {
+    fn default() -> RendererSettings {
+        RendererSettings {
+            fov: 45.0_f32,
+            batch_static_geometry: true,
+            frustum_culling: true,
+            occlusion_culling: false,
+            instancing: true,
+            multi_draw_indirect: true,
+            bindless_textures: true,
+            show_normals: false,
+            normal_length: 0.1_f32,
+            normal_color: Color::from_srgb(1.0_f32, 1.0_f32, 0.0_f32, 1.0_f32),
+            wireframe_overlay: false,
+            wireframe_width: 1.0_f32,
+            wireframe_color: Color::from_srgb(0.0_f32, 0.0_f32, 0.0_f32, 0.8_f32),
+            visualization: Visualization::default(),
+            debug_draw: false,
+            show_lights: false,
+            show_light_ranges: false,
+            show_labels: false,
+            environment_capture: CaptureMode::default(),
+            environment_capture_size: 256,
+            skybox: String::new(),
+            skybox_size: 512,
+            cubemap_cache: "cache".to_string(),
+        }
+    }
+}
+
+impl Default for InputSettings {
+    fn default() -> InputSettings {
+        InputSettings {
+            mouse_sensitivity: 0.1_f32,
+            look_smoothing: 0.0_f32,
+            look_acceleration: 1.0_f32,
+            move_speed: 2.5_f32,
+            picking: PickingMode::Ray,
+            gamepad_dead_zone: 0.2_f32,
+            gamepad_look_speed: 120.0_f32,
+            bindings: InputBindings::default(),
+        }
+    }
+}
+
+impl Default for CaptureSettings {
+    fn default() -> CaptureSettings {
+        CaptureSettings {
+            directory: "captures".to_string(),
+            framerate: 60,
+            max_queued_frames: 8,
+        }
+    }
+}
+
+impl Default for TextSettings {
+    fn default() -> TextSettings {
+        TextSettings {
+            font: "resources/fonts/DejaVuSans.ttf".to_string(),
+            font_size: 20.0_f32,
+            signed_distance_field: true,
+            world_label_height: 0.0_f32,
+        }
+    }
+}
+
+impl Default for DebugViewSettings {
+    fn default() -> DebugViewSettings {
+        DebugViewSettings {
+            enabled: false,
+            width: 400,
+            height: 300,
+            // Looking down on the default cubes from above and behind the main camera
+            camera: CameraPose {
+                position: [0.0_f32, 6.0_f32, 8.0_f32],
+                yaw: -90.0_f32,
+                pitch: -35.0_f32,
+            },
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from `file_path`, falling back to defaults for a missing file and for any
+    /// key the file leaves out. A malformed file is reported and ignored.
+    pub fn load(file_path: &str) -> Settings {
+        match fs::read_to_string(file_path) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    eprintln!("Ignoring invalid settings file {}: {}", file_path, err);
+                    Settings::default()
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => Settings::default(),
+            Err(err) => {
+                eprintln!("Could not read settings file {}: {}", file_path, err);
+                Settings::default()
+            }
+        }
+    }
+}
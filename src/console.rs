@@ -0,0 +1,270 @@
+use crate::input_map::{InputAction, InputMap, Trigger};
+use crate::ogl::context::GlContext;
+use crate::ogl::post_process::ToneMapOperator;
+use crate::ogl::text_renderer::TextRenderer;
+use crate::ogl::texture_inspector::InspectorChannel;
+use crate::platform::{Action, Event, Key, Modifiers};
+use std::collections::VecDeque;
+use std::mem;
+
+// Lines kept for scrolling back through output; only those that fit are drawn
+const MAX_OUTPUT_LINES: usize = 200;
+// Share of the framebuffer height the console covers when open
+const HEIGHT_FRACTION: f32 = 0.4;
+const BACKGROUND_COLOR: [f32; 4] = [0.05_f32, 0.05_f32, 0.08_f32, 1.0_f32];
+const TEXT_COLOR: [f32; 4] = [0.85_f32, 0.85_f32, 0.85_f32, 1.0_f32];
+const INPUT_COLOR: [f32; 4] = [1.0_f32, 1.0_f32, 1.0_f32, 1.0_f32];
+const ERROR_COLOR: [f32; 4] = [1.0_f32, 0.4_f32, 0.4_f32, 1.0_f32];
+
+const HELP: [&str; 12] = [
+    "set fov|move_speed|mouse_sensitivity|time_scale|god_ray_density|god_ray_decay|god_ray_exposure|motion_blur_shutter|motion_blur_samples|exposure|white_point|tone_map_split|normal_length|wireframe_width|msaa_samples|gl_version <value>",
+    "toggle wireframe|wireframe_overlay|normals|debug_draw|lights|light_ranges|labels|light_heatmap|hud|textures|pause|culling|occlusion|freeze_culling|instancing|multi_draw|fullscreen|grass_blending|god_rays|motion_blur",
+    "tonemap none|reinhard|reinhard_extended|uncharted2|aces",
+    "compare <operator>|off  (split screen against the tonemap operator)",
+    "load scene <file>  (also looked up in resources/scenes)",
+    "reload shaders",
+    "bake probes  (captures the light and reflection probes again)",
+    "capture frame  (needs RenderDoc, see --renderdoc)",
+    "recreate window  (rebuilds the window, context and GL resources)",
+    "inspect <index>|all|rgba|r|g|b|a|mip <level>  (texture inspector, F6)",
+    "clear",
+    "help",
+];
+
+/// Something typed into the console for the app to carry out.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Set(String, f32),
+    Toggle(String),
+    ToneMap(ToneMapOperator),
+    /// Splits the screen between the tone map operator and this one, or with `None` stops
+    CompareToneMap(Option<ToneMapOperator>),
+    LoadScene(String),
+    ReloadShaders,
+    BakeProbes,
+    CaptureFrame,
+    RecreateWindow,
+    /// Fills the texture inspector with one texture, or with `None` all of them again
+    InspectTexture(Option<usize>),
+    InspectChannel(InspectorChannel),
+    InspectMip(u32),
+}
+
+/// How a toggle's new state reads in the console.
+pub fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["set", name, value] => value
+                .parse::<f32>()
+                .map(|value| Command::Set(name.to_string(), value))
+                .map_err(|_| format!("Not a number: {}", value)),
+            ["toggle", name] => Ok(Command::Toggle(name.to_string())),
+            ["tonemap", name] => ToneMapOperator::from_name(name)
+                .map(Command::ToneMap)
+                .ok_or_else(|| format!("Not a tone map operator: {}", name)),
+            ["compare", "off"] => Ok(Command::CompareToneMap(None)),
+            ["compare", name] => ToneMapOperator::from_name(name)
+                .map(|operator| Command::CompareToneMap(Some(operator)))
+                .ok_or_else(|| format!("Not a tone map operator: {}", name)),
+            ["load", "scene", file_path] => Ok(Command::LoadScene(file_path.to_string())),
+            ["reload", "shaders"] => Ok(Command::ReloadShaders),
+            ["bake", "probes"] => Ok(Command::BakeProbes),
+            ["capture", "frame"] => Ok(Command::CaptureFrame),
+            ["recreate", "window"] => Ok(Command::RecreateWindow),
+            ["inspect", "all"] => Ok(Command::InspectTexture(None)),
+            ["inspect", "mip", level] => level
+                .parse::<u32>()
+                .map(Command::InspectMip)
+                .map_err(|_| format!("Not a mip level: {}", level)),
+            ["inspect", target] => match InspectorChannel::from_name(target) {
+                Some(channel) => Ok(Command::InspectChannel(channel)),
+                None => target
+                    .parse::<usize>()
+                    .map(|texture_i| Command::InspectTexture(Some(texture_i)))
+                    .map_err(|_| format!("Not a texture index or channel: {}", target)),
+            },
+            _ => Err(format!("Unknown command: {}; type help for a list", line)),
+        }
+    }
+}
+
+/// Drop-down command line over the top of the window. While open it takes keyboard input from
+/// the app, and submitted lines are parsed into commands collected with `take_commands`.
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    /// Index into `history` while stepping through it with the arrow keys
+    history_position: Option<usize>,
+    output: VecDeque<(String, [f32; 4])>,
+    commands: Vec<Command>,
+    text_renderer: TextRenderer,
+    context: GlContext,
+}
+
+impl Console {
+    pub fn new(context: &GlContext, font_atlas_file: &str) -> Result<Console, String> {
+        Ok(Console {
+            open: false,
+            input: String::new(),
+            history: vec![],
+            history_position: None,
+            output: VecDeque::new(),
+            commands: vec![],
+            text_renderer: TextRenderer::new(context, font_atlas_file)?,
+            context: *context,
+        })
+    }
+
+    /// Opens and closes the console on `InputAction::ToggleConsole`, and while it is open takes
+    /// key presses and typed text. Returns the events it did not take.
+    pub fn handle_events(
+        &mut self,
+        events: Vec<Event>,
+        input_map: &InputMap,
+        modifiers: Modifiers,
+    ) -> Vec<Event> {
+        // The toggle key types a character too, which arrives in the same batch of events
+        let mut skip_char = false;
+        let mut unused = vec![];
+        for event in events {
+            match event {
+                Event::Key(key, Action::Press)
+                    if input_map
+                        .actions(Trigger::Key(key), modifiers)
+                        .contains(&InputAction::ToggleConsole) =>
+                {
+                    self.open = !self.open;
+                    skip_char = true;
+                }
+                // Releases still reach the app so nothing it tracks stays held down
+                Event::Key(key, Action::Press) | Event::Key(key, Action::Repeat) if self.open => {
+                    self.edit(key)
+                }
+                Event::Char(_) if skip_char => skip_char = false,
+                Event::Char(character) if self.open => {
+                    if !character.is_control() {
+                        self.input.push(character);
+                    }
+                }
+                _ => unused.push(event),
+            }
+        }
+        unused
+    }
+
+    /// Commands submitted since the last call, oldest first.
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        mem::take(&mut self.commands)
+    }
+
+    pub fn print(&mut self, line: &str) {
+        self.push_output(line, TEXT_COLOR);
+    }
+
+    pub fn print_error(&mut self, line: &str) {
+        self.push_output(line, ERROR_COLOR);
+    }
+
+    pub fn draw(&mut self, framebuffer_size: (i32, i32), content_scale: f32) {
+        if !self.open {
+            return;
+        }
+        let (width, height) = framebuffer_size;
+        let console_height = (height as f32 * HEIGHT_FRACTION) as i32;
+        self.context.clear_color_rect(
+            (0, height - console_height),
+            (width, console_height),
+            BACKGROUND_COLOR,
+        );
+
+        // The input line sits at the bottom with as much recent output above it as fits
+        let scale = 0.5_f32 * content_scale;
+        let line_height = self.text_renderer.line_height(scale);
+        let margin = line_height * 0.25_f32;
+        let mut y = console_height as f32 - margin - line_height;
+        self.text_renderer.add_text(
+            &format!("> {}_", self.input),
+            (margin, y),
+            scale,
+            INPUT_COLOR,
+        );
+        for (line, color) in self.output.iter().rev() {
+            y -= line_height;
+            if y < margin {
+                break;
+            }
+            self.text_renderer
+                .add_text(line, (margin, y), scale, *color);
+        }
+        self.text_renderer.draw(framebuffer_size);
+    }
+
+    fn edit(&mut self, key: Key) {
+        match key {
+            Key::Enter => self.submit(),
+            Key::Escape => self.open = false,
+            Key::Backspace => {
+                self.input.pop();
+            }
+            Key::Up if !self.history.is_empty() => {
+                let position = match self.history_position {
+                    Some(position) => position.saturating_sub(1),
+                    None => self.history.len() - 1,
+                };
+                self.history_position = Some(position);
+                self.input = self.history[position].clone();
+            }
+            Key::Down => {
+                self.history_position = match self.history_position {
+                    Some(position) if position + 1 < self.history.len() => Some(position + 1),
+                    _ => None,
+                };
+                self.input = match self.history_position {
+                    Some(position) => self.history[position].clone(),
+                    None => String::new(),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn submit(&mut self) {
+        let input = mem::take(&mut self.input);
+        let line = input.trim();
+        self.history_position = None;
+        if line.is_empty() {
+            return;
+        }
+        self.print(&format!("> {}", line));
+        self.history.push(line.to_string());
+        match line {
+            "help" => {
+                for help_line in HELP.iter() {
+                    self.print(help_line);
+                }
+            }
+            "clear" => self.output.clear(),
+            _ => match Command::parse(line) {
+                Ok(command) => self.commands.push(command),
+                Err(e) => self.print_error(&e),
+            },
+        }
+    }
+
+    fn push_output(&mut self, line: &str, color: [f32; 4]) {
+        if self.output.len() == MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back((line.to_string(), color));
+    }
+}
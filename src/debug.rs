@@ -0,0 +1,254 @@
+// Immediate-mode debug drawing: any code can queue wireframe shapes and text labels during the
+// frame, and `flush` hands them all to a `LineRenderer` and a `FontRenderer` once, after the
+// scene is drawn.
+
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::color::Color;
+use crate::math::frustum::Frustum;
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::font_renderer::{FontRenderer, WorldTextSize};
+use crate::ogl::line_renderer::{DepthMode, LineRenderer};
+use std::sync::Mutex;
+
+// Line width in pixels
+const LINE_WIDTH: f32 = 1.5;
+// Segments per circle of a sphere
+const CIRCLE_SEGMENTS: usize = 32;
+// Lines from a cone's tip to its base circle
+const CONE_SIDES: usize = 8;
+// Share of an arrow's length taken by its head
+const ARROWHEAD_FRACTION: f32 = 0.2;
+// Corner pairs joined by the edges of a box, indexing corners ordered like `Aabb::corners`
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+const AXIS_COLORS: [Color; 3] = [
+    Color::rgb(0.8_f32, 0.03_f32, 0.03_f32),
+    Color::rgb(0.03_f32, 0.8_f32, 0.03_f32),
+    Color::rgb(0.03_f32, 0.13_f32, 1.0_f32),
+];
+
+struct DebugLine {
+    start: Vec3,
+    end: Vec3,
+    color: Color,
+    depth: DepthMode,
+}
+
+struct DebugLabel {
+    text: String,
+    position: Vec3,
+    size: WorldTextSize,
+    color: Color,
+    depth: DepthMode,
+}
+
+// Lines queued since the last flush. Behind a lock so simulation code on other threads can draw
+// too.
+static QUEUE: Mutex<Vec<DebugLine>> = Mutex::new(Vec::new());
+static LABELS: Mutex<Vec<DebugLabel>> = Mutex::new(Vec::new());
+
+pub fn draw_line(start: &Vec3, end: &Vec3, color: Color, depth: DepthMode) {
+    QUEUE.lock().unwrap().push(DebugLine {
+        start: *start,
+        end: *end,
+        color,
+        depth,
+    });
+}
+
+pub fn draw_aabb(aabb: &Aabb, color: Color, depth: DepthMode) {
+    draw_box(&aabb.corners(), color, depth);
+}
+
+/// Three circles around the sphere, one in each axis plane.
+pub fn draw_sphere(sphere: &BoundingSphere, color: Color, depth: DepthMode) {
+    let axes = [
+        glm::vec3(1.0_f32, 0.0_f32, 0.0_f32),
+        glm::vec3(0.0_f32, 1.0_f32, 0.0_f32),
+        glm::vec3(0.0_f32, 0.0_f32, 1.0_f32),
+    ];
+    for axis_i in 0..3 {
+        draw_circle(
+            &sphere.center,
+            &axes[axis_i],
+            &axes[(axis_i + 1) % 3],
+            sphere.radius,
+            color,
+            depth,
+        );
+    }
+}
+
+/// A line from `start` to `end` with an arrowhead at `end`.
+pub fn draw_arrow(start: &Vec3, end: &Vec3, color: Color, depth: DepthMode) {
+    draw_line(start, end, color, depth);
+    let length = glm::distance(start, end);
+    if length <= 0.0_f32 {
+        return;
+    }
+    let direction = (end - start) / length;
+    let (u, v) = perpendicular_axes(&direction);
+    let head_length = length * ARROWHEAD_FRACTION;
+    let head_base = end - direction * head_length;
+    for axis in [u, -u, v, -v].iter() {
+        draw_line(
+            end,
+            &(head_base + axis * (head_length * 0.5_f32)),
+            color,
+            depth,
+        );
+    }
+}
+
+/// The outline of a cone with its tip at `apex`, opening along `direction` to a base circle
+/// `length` away, its sides `half_angle_degrees` off the axis.
+pub fn draw_cone(
+    apex: &Vec3,
+    direction: &Vec3,
+    length: f32,
+    half_angle_degrees: f32,
+    color: Color,
+    depth: DepthMode,
+) {
+    let direction = glm::normalize(direction);
+    let (u, v) = perpendicular_axes(&direction);
+    let center = apex + direction * length;
+    let radius = length * half_angle_degrees.to_radians().tan();
+    draw_circle(&center, &u, &v, radius, color, depth);
+    for side_i in 0..CONE_SIDES {
+        let angle = side_i as f32 / CONE_SIDES as f32 * std::f32::consts::TAU;
+        draw_line(
+            apex,
+            &(center + (u * angle.cos() + v * angle.sin()) * radius),
+            color,
+            depth,
+        );
+    }
+}
+
+/// The edges of `frustum`, e.g. a frozen culling frustum seen from outside.
+pub fn draw_frustum(frustum: &Frustum, color: Color, depth: DepthMode) {
+    draw_box(&frustum.corners, color, depth);
+}
+
+/// The x, y and z axes of `world_from_object` in red, green and blue, `size` object units long,
+/// so rotation and scale show too.
+pub fn draw_axes(world_from_object: &Mat4, size: f32, depth: DepthMode) {
+    let origin = glm::xyz(&(world_from_object * glm::vec4(0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32)));
+    for (axis_i, color) in AXIS_COLORS.iter().enumerate() {
+        let mut tip = glm::vec4(0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32);
+        tip[axis_i] = size;
+        draw_line(
+            &origin,
+            &glm::xyz(&(world_from_object * tip)),
+            *color,
+            depth,
+        );
+    }
+}
+
+/// Square grid in the horizontal plane through `center`, `cells` per side of `cell_size` each.
+pub fn draw_grid(center: &Vec3, cell_size: f32, cells: usize, color: Color, depth: DepthMode) {
+    let half_size = cell_size * cells as f32 * 0.5_f32;
+    for line_i in 0..=cells {
+        let offset = line_i as f32 * cell_size - half_size;
+        draw_line(
+            &(center + glm::vec3(offset, 0.0_f32, -half_size)),
+            &(center + glm::vec3(offset, 0.0_f32, half_size)),
+            color,
+            depth,
+        );
+        draw_line(
+            &(center + glm::vec3(-half_size, 0.0_f32, offset)),
+            &(center + glm::vec3(half_size, 0.0_f32, offset)),
+            color,
+            depth,
+        );
+    }
+}
+
+/// `text` centered just above `position`, facing the camera.
+pub fn draw_label(
+    position: &Vec3,
+    text: &str,
+    size: WorldTextSize,
+    color: Color,
+    depth: DepthMode,
+) {
+    LABELS.lock().unwrap().push(DebugLabel {
+        text: text.to_string(),
+        position: *position,
+        size,
+        color,
+        depth,
+    });
+}
+
+/// Moves the lines queued this frame into `lines` and the labels into `labels`, to draw at their
+/// next flush. Labels are dropped without a font.
+pub fn flush(lines: &mut LineRenderer, labels: Option<&mut FontRenderer>) {
+    for line in QUEUE.lock().unwrap().drain(..) {
+        lines.line(&line.start, &line.end, line.color, LINE_WIDTH, line.depth);
+    }
+    let mut queued_labels = LABELS.lock().unwrap();
+    match labels {
+        Some(labels) => {
+            for label in queued_labels.drain(..) {
+                labels.add_world_text(
+                    &label.text,
+                    &label.position,
+                    label.size,
+                    label.color.to_srgb(),
+                    label.depth,
+                );
+            }
+        }
+        None => queued_labels.clear(),
+    }
+}
+
+fn draw_box(corners: &[Vec3; 8], color: Color, depth: DepthMode) {
+    for &(a, b) in BOX_EDGES.iter() {
+        draw_line(&corners[a], &corners[b], color, depth);
+    }
+}
+
+/// Circle around `center` in the plane of the unit axes `u` and `v`.
+fn draw_circle(center: &Vec3, u: &Vec3, v: &Vec3, radius: f32, color: Color, depth: DepthMode) {
+    let circle_point = |segment_i: usize| {
+        let angle = segment_i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+        center + (u * angle.cos() + v * angle.sin()) * radius
+    };
+    for segment_i in 0..CIRCLE_SEGMENTS {
+        draw_line(
+            &circle_point(segment_i),
+            &circle_point(segment_i + 1),
+            color,
+            depth,
+        );
+    }
+}
+
+/// Two unit axes perpendicular to the unit vector `direction` and to each other.
+fn perpendicular_axes(direction: &Vec3) -> (Vec3, Vec3) {
+    // Any direction not along `direction` gives the axes
+    let helper = if direction.y.abs() < 0.99_f32 {
+        glm::vec3(0.0_f32, 1.0_f32, 0.0_f32)
+    } else {
+        glm::vec3(0.0_f32, 0.0_f32, 1.0_f32)
+    };
+    let u = glm::normalize(&glm::cross(&helper, direction));
+    (u, glm::cross(direction, &u))
+}
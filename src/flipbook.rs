@@ -0,0 +1,88 @@
+// Sprite-sheet animation: frames laid out in a grid on one texture, picked by time and drawn by
+// showing only their part of the texture.
+
+/// Part of a texture, in texture coordinates from its bottom left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub offset: [f32; 2],
+    pub size: [f32; 2],
+}
+
+/// Frames of a sprite sheet, `columns` by `rows`, read left to right and top to bottom as the
+/// image is drawn, for sheets loaded flipped vertically like the scene's textures. Plays at `fps`
+/// frames per second, wrapping around when `looping` and holding the last frame otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flipbook {
+    pub columns: u32,
+    pub rows: u32,
+    /// Frames used, for sheets whose last row is not full
+    pub frame_count: u32,
+    pub fps: f32,
+    pub looping: bool,
+}
+
+impl UvRect {
+    /// The whole texture.
+    pub const FULL: UvRect = UvRect {
+        offset: [0.0_f32, 0.0_f32],
+        size: [1.0_f32, 1.0_f32],
+    };
+}
+
+impl Flipbook {
+    /// Every frame of a full `columns` by `rows` sheet, looping.
+    pub fn new(columns: u32, rows: u32, fps: f32) -> Flipbook {
+        Flipbook {
+            columns,
+            rows,
+            frame_count: columns * rows,
+            fps,
+            looping: true,
+        }
+    }
+
+    /// The same frames played once, stopping on the last.
+    pub fn once(self) -> Flipbook {
+        Flipbook {
+            looping: false,
+            ..self
+        }
+    }
+
+    /// Seconds one pass through the frames takes.
+    #[allow(dead_code)]
+    pub fn duration(&self) -> f32 {
+        self.frame_count as f32 / self.fps
+    }
+
+    /// The frame showing `seconds` after the animation started.
+    pub fn frame_at(&self, seconds: f32) -> u32 {
+        let frame_count = self.frame_count.clamp(1, (self.columns * self.rows).max(1));
+        let frame = (seconds.max(0.0_f32) * self.fps) as u32;
+        if self.looping {
+            frame % frame_count
+        } else {
+            frame.min(frame_count - 1)
+        }
+    }
+
+    /// Where `frame` is on the sheet.
+    pub fn uv_rect(&self, frame: u32) -> UvRect {
+        let (columns, rows) = (self.columns.max(1), self.rows.max(1));
+        let size = [1.0_f32 / columns as f32, 1.0_f32 / rows as f32];
+        let (column, row) = (frame % columns, (frame / columns) % rows);
+        // The image's top row ends up at the top of the texture
+        UvRect {
+            offset: [
+                column as f32 * size[0],
+                1.0_f32 - (row + 1) as f32 * size[1],
+            ],
+            size,
+        }
+    }
+
+    /// Where the frame showing `seconds` after the animation started is on the sheet.
+    pub fn uv_rect_at(&self, seconds: f32) -> UvRect {
+        self.uv_rect(self.frame_at(seconds))
+    }
+}
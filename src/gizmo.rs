@@ -0,0 +1,241 @@
+use crate::math::color::Color;
+use crate::math::glm::{self, Vec3};
+use crate::math::plane::Plane;
+use crate::math::ray::Ray;
+use crate::ogl::line_renderer::{DepthMode, LineRenderer};
+use crate::scene::Transform;
+
+// Handle length as a fraction of the camera distance, so the gizmo keeps its on-screen size
+const GIZMO_SCREEN_SCALE: f32 = 0.15;
+// Pick tolerance around a handle, relative to the handle length
+const HANDLE_PICK_TOLERANCE: f32 = 0.08;
+const RING_SEGMENTS: usize = 48;
+const MIN_SCALE: f32 = 0.01;
+// Handle line width in pixels
+const LINE_WIDTH: f32 = 2.0;
+const AXIS_COLORS: [[f32; 3]; 3] = [
+    [0.9_f32, 0.2_f32, 0.2_f32],
+    [0.2_f32, 0.9_f32, 0.2_f32],
+    [0.2_f32, 0.4_f32, 1.0_f32],
+];
+const ACTIVE_COLOR: [f32; 3] = [1.0_f32, 0.9_f32, 0.1_f32];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+struct GizmoDrag {
+    axis: usize,
+    start_transform: Transform,
+    // Translate and scale: position along the axis line; rotate: direction of the hit on the ring
+    start_offset: f32,
+    start_direction: Vec3,
+}
+
+/// Translate/rotate/scale handles around the selected object. Translation and rotation work
+/// along the world axes, scaling along the object's own axes.
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    drag: Option<GizmoDrag>,
+}
+
+impl Gizmo {
+    pub fn new() -> Gizmo {
+        Gizmo {
+            mode: GizmoMode::Translate,
+            drag: None,
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Starts a drag if `ray` hits one of the handles, returning whether it did.
+    pub fn begin_drag(&mut self, ray: &Ray, transform: &Transform, camera_position: &Vec3) -> bool {
+        let center = glm::make_vec3(&transform.position);
+        let size = gizmo_size(&center, camera_position);
+        let mut closest: Option<(usize, f32)> = None;
+        for axis in 0..3 {
+            let axis_dir = self.axis_direction(transform, axis);
+            let hit = match self.mode {
+                GizmoMode::Translate | GizmoMode::Scale => closest_to_axis(ray, &center, &axis_dir)
+                    .and_then(|(offset, t, distance)| {
+                        if offset >= 0.0_f32
+                            && offset <= size
+                            && distance <= size * HANDLE_PICK_TOLERANCE
+                        {
+                            Some(t)
+                        } else {
+                            None
+                        }
+                    }),
+                GizmoMode::Rotate => ray
+                    .intersect_plane(&Plane::from_point_normal(&center, &axis_dir))
+                    .and_then(|hit| {
+                        let radius = glm::length(&(hit.point - center));
+                        if (radius - size).abs() <= size * HANDLE_PICK_TOLERANCE {
+                            Some(hit.distance)
+                        } else {
+                            None
+                        }
+                    }),
+            };
+            if let Some(t) = hit {
+                match closest {
+                    Some((_, closest_t)) if closest_t <= t => {}
+                    _ => closest = Some((axis, t)),
+                }
+            }
+        }
+
+        let axis = match closest {
+            Some((axis, _)) => axis,
+            None => return false,
+        };
+        let axis_dir = self.axis_direction(transform, axis);
+        let (start_offset, start_direction) = match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => (
+                closest_to_axis(ray, &center, &axis_dir).map_or(0.0_f32, |(offset, _, _)| offset),
+                Vec3::default(),
+            ),
+            GizmoMode::Rotate => (
+                0.0_f32,
+                ray.intersect_plane(&Plane::from_point_normal(&center, &axis_dir))
+                    .map_or(Vec3::default(), |hit| hit.point - center),
+            ),
+        };
+        self.drag = Some(GizmoDrag {
+            axis,
+            start_transform: transform.clone(),
+            start_offset,
+            start_direction,
+        });
+        true
+    }
+
+    /// Applies the current drag to `transform`, relative to the transform the drag started from.
+    pub fn update_drag(&self, ray: &Ray, transform: &mut Transform, camera_position: &Vec3) {
+        let drag = match &self.drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        let start = &drag.start_transform;
+        let center = glm::make_vec3(&start.position);
+        let axis_dir = self.axis_direction(start, drag.axis);
+        match self.mode {
+            GizmoMode::Translate => {
+                if let Some((offset, _, _)) = closest_to_axis(ray, &center, &axis_dir) {
+                    let position = center + axis_dir * (offset - drag.start_offset);
+                    transform.position = [position.x, position.y, position.z];
+                }
+            }
+            GizmoMode::Scale => {
+                if let Some((offset, _, _)) = closest_to_axis(ray, &center, &axis_dir) {
+                    let size = gizmo_size(&center, camera_position);
+                    let factor = 1.0_f32 + (offset - drag.start_offset) / size;
+                    transform.scale[drag.axis] = (start.scale[drag.axis] * factor).max(MIN_SCALE);
+                }
+            }
+            GizmoMode::Rotate => {
+                if let Some(hit) =
+                    ray.intersect_plane(&Plane::from_point_normal(&center, &axis_dir))
+                {
+                    let direction = hit.point - center;
+                    let angle = signed_angle(&drag.start_direction, &direction, &axis_dir);
+                    let rotation = glm::quat_angle_axis(angle, &axis_dir) * start.rotation();
+                    transform.set_rotation_quat(&rotation);
+                }
+            }
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    /// Queues the handle line segments, drawn over the scene.
+    pub fn push_lines(
+        &self,
+        transform: &Transform,
+        camera_position: &Vec3,
+        lines: &mut LineRenderer,
+    ) {
+        let center = glm::make_vec3(&transform.position);
+        let size = gizmo_size(&center, camera_position);
+        for (axis, axis_color) in AXIS_COLORS.iter().enumerate() {
+            let axis_dir = self.axis_direction(transform, axis);
+            let color = match &self.drag {
+                Some(drag) if drag.axis == axis => ACTIVE_COLOR,
+                _ => *axis_color,
+            };
+            match self.mode {
+                GizmoMode::Translate | GizmoMode::Scale => {
+                    push_line(lines, &center, &(center + axis_dir * size), color);
+                }
+                GizmoMode::Rotate => {
+                    // Any two directions perpendicular to the axis span the ring plane
+                    let tangent = self.axis_direction(transform, (axis + 1) % 3);
+                    let bitangent = glm::cross(&axis_dir, &tangent);
+                    let ring_point = |segment_i: usize| {
+                        let angle = segment_i as f32 / RING_SEGMENTS as f32
+                            * std::f32::consts::PI
+                            * 2.0_f32;
+                        center + (tangent * angle.cos() + bitangent * angle.sin()) * size
+                    };
+                    for segment_i in 0..RING_SEGMENTS {
+                        push_line(
+                            lines,
+                            &ring_point(segment_i),
+                            &ring_point(segment_i + 1),
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn axis_direction(&self, transform: &Transform, axis: usize) -> Vec3 {
+        let mut direction = Vec3::default();
+        direction[axis] = 1.0_f32;
+        if self.mode == GizmoMode::Scale {
+            glm::quat_rotate_vec3(&transform.rotation(), &direction)
+        } else {
+            direction
+        }
+    }
+}
+
+fn gizmo_size(center: &Vec3, camera_position: &Vec3) -> f32 {
+    glm::length(&(center - camera_position)) * GIZMO_SCREEN_SCALE
+}
+
+/// Closest approach between `ray` and the line through `origin` along unit `axis`. Returns the
+/// position along the axis, the distance along the ray and the gap between them, or `None` when
+/// the ray is parallel to the axis.
+fn closest_to_axis(ray: &Ray, origin: &Vec3, axis: &Vec3) -> Option<(f32, f32, f32)> {
+    let to_axis = origin - ray.origin;
+    let cos_angle = glm::dot(axis, &ray.direction);
+    let denominator = 1.0_f32 - cos_angle * cos_angle;
+    if denominator < 1e-6_f32 {
+        return None;
+    }
+    let offset =
+        (cos_angle * glm::dot(&ray.direction, &to_axis) - glm::dot(axis, &to_axis)) / denominator;
+    let t = glm::dot(&ray.direction, &to_axis) + cos_angle * offset;
+    let gap = glm::length(&(to_axis + axis * offset - ray.direction * t));
+    Some((offset, t, gap))
+}
+
+fn signed_angle(from: &Vec3, to: &Vec3, axis: &Vec3) -> f32 {
+    glm::dot(&glm::cross(from, to), axis).atan2(glm::dot(from, to))
+}
+
+fn push_line(lines: &mut LineRenderer, from: &Vec3, to: &Vec3, color: [f32; 3]) {
+    let color = Color::from_srgb(color[0], color[1], color[2], 1.0_f32);
+    lines.line(from, to, color, LINE_WIDTH, DepthMode::Overlay);
+}
@@ -0,0 +1,383 @@
+use crate::math::easing::damp_factor;
+use crate::platform::{Key, Modifiers, MouseButton, WindowBackend};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Names bindings are written with in the settings file.
+const KEY_NAMES: [(Key, &str); 35] = [
+    (Key::W, "W"),
+    (Key::A, "A"),
+    (Key::S, "S"),
+    (Key::D, "D"),
+    (Key::P, "P"),
+    (Key::Num1, "1"),
+    (Key::Num2, "2"),
+    (Key::Num3, "3"),
+    (Key::Period, "Period"),
+    (Key::LeftBracket, "LeftBracket"),
+    (Key::RightBracket, "RightBracket"),
+    (Key::Escape, "Escape"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F9, "F9"),
+    (Key::F12, "F12"),
+    (Key::V, "V"),
+    (Key::Enter, "Enter"),
+    (Key::LeftAlt, "LeftAlt"),
+    (Key::RightAlt, "RightAlt"),
+    (Key::Tab, "Tab"),
+    (Key::R, "R"),
+    (Key::LeftShift, "LeftShift"),
+    (Key::RightShift, "RightShift"),
+    (Key::LeftControl, "LeftControl"),
+    (Key::RightControl, "RightControl"),
+    (Key::GraveAccent, "GraveAccent"),
+    (Key::Backspace, "Backspace"),
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+    (Key::Space, "Space"),
+];
+
+const MOUSE_BUTTON_NAMES: [(MouseButton, &str); 3] = [
+    (MouseButton::Left, "MouseLeft"),
+    (MouseButton::Right, "MouseRight"),
+    (MouseButton::Middle, "MouseMiddle"),
+];
+
+/// Something the user does that the app reacts to, independent of the key or button bound to it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Quit,
+    Pick,
+    GizmoTranslate,
+    GizmoRotate,
+    GizmoScale,
+    TogglePause,
+    StepSimulation,
+    SlowDown,
+    SpeedUp,
+    ToggleStats,
+    CycleVsync,
+    ToggleRecording,
+    SaveScene,
+    ToggleCursorCapture,
+    ToggleFullscreen,
+    ReloadScene,
+    ToggleConsole,
+    RenderDocCapture,
+    CycleVisualization,
+    ToggleTextureInspector,
+    Launch,
+}
+
+/// A key or mouse button.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Trigger {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+/// A trigger pressed while exactly `modifiers` are held. Written as the trigger's name, a key
+/// ("W", "F3", "LeftBracket") or "MouseLeft", "MouseRight" or "MouseMiddle", after any of
+/// "Ctrl+", "Shift+" and "Alt+", e.g. "Ctrl+S".
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Binding {
+    pub modifiers: Modifiers,
+    pub trigger: Trigger,
+}
+
+impl Binding {
+    fn key(key: Key) -> Binding {
+        Binding {
+            modifiers: Modifiers::default(),
+            trigger: Trigger::Key(key),
+        }
+    }
+
+    fn button(button: MouseButton) -> Binding {
+        Binding {
+            modifiers: Modifiers::default(),
+            trigger: Trigger::Mouse(button),
+        }
+    }
+
+    fn with_control(mut self) -> Binding {
+        self.modifiers.control = true;
+        self
+    }
+
+    fn with_alt(mut self) -> Binding {
+        self.modifiers.alt = true;
+        self
+    }
+
+    /// A modifier key bound on its own still matches, although pressing it sets its modifier.
+    fn matches(&self, trigger: Trigger, mut modifiers: Modifiers) -> bool {
+        match trigger {
+            Trigger::Key(Key::LeftShift) | Trigger::Key(Key::RightShift) => modifiers.shift = false,
+            Trigger::Key(Key::LeftControl) | Trigger::Key(Key::RightControl) => {
+                modifiers.control = false
+            }
+            Trigger::Key(Key::LeftAlt) | Trigger::Key(Key::RightAlt) => modifiers.alt = false,
+            _ => {}
+        }
+        self.trigger == trigger && self.modifiers == modifiers
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Binding, String> {
+        let mut parts: Vec<&str> = name.split('+').collect();
+        let trigger_name = parts.pop().unwrap_or_default();
+        let mut modifiers = Modifiers::default();
+        for modifier in parts {
+            match modifier {
+                "Ctrl" => modifiers.control = true,
+                "Shift" => modifiers.shift = true,
+                "Alt" => modifiers.alt = true,
+                _ => return Err(format!("Unknown modifier {} in {}", modifier, name)),
+            }
+        }
+        let key = KEY_NAMES
+            .iter()
+            .find(|(_, key_name)| *key_name == trigger_name)
+            .map(|(key, _)| Trigger::Key(*key));
+        let button = MOUSE_BUTTON_NAMES
+            .iter()
+            .find(|(_, button_name)| *button_name == trigger_name)
+            .map(|(button, _)| Trigger::Mouse(*button));
+        let trigger = key
+            .or(button)
+            .ok_or_else(|| format!("Unknown key or mouse button {}", trigger_name))?;
+        Ok(Binding { modifiers, trigger })
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> String {
+        let trigger_name = match binding.trigger {
+            Trigger::Key(key) => KEY_NAMES.iter().find(|(k, _)| *k == key).map(|(_, n)| n),
+            Trigger::Mouse(button) => MOUSE_BUTTON_NAMES
+                .iter()
+                .find(|(b, _)| *b == button)
+                .map(|(_, n)| n),
+        };
+        let mut name = String::new();
+        if binding.modifiers.control {
+            name.push_str("Ctrl+");
+        }
+        if binding.modifiers.shift {
+            name.push_str("Shift+");
+        }
+        if binding.modifiers.alt {
+            name.push_str("Alt+");
+        }
+        name.push_str(trigger_name.expect("Every key and mouse button is named"));
+        name
+    }
+}
+
+/// Keys and buttons per action, as read from `[input.bindings]`. An action can have several
+/// bindings, or none to disable it; actions left out keep their default bindings.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub move_forward: Vec<Binding>,
+    pub move_backward: Vec<Binding>,
+    pub move_left: Vec<Binding>,
+    pub move_right: Vec<Binding>,
+    pub quit: Vec<Binding>,
+    pub pick: Vec<Binding>,
+    pub gizmo_translate: Vec<Binding>,
+    pub gizmo_rotate: Vec<Binding>,
+    pub gizmo_scale: Vec<Binding>,
+    pub toggle_pause: Vec<Binding>,
+    pub step_simulation: Vec<Binding>,
+    pub slow_down: Vec<Binding>,
+    pub speed_up: Vec<Binding>,
+    pub toggle_stats: Vec<Binding>,
+    pub cycle_vsync: Vec<Binding>,
+    pub toggle_recording: Vec<Binding>,
+    pub save_scene: Vec<Binding>,
+    pub toggle_cursor_capture: Vec<Binding>,
+    pub toggle_fullscreen: Vec<Binding>,
+    pub reload_scene: Vec<Binding>,
+    pub toggle_console: Vec<Binding>,
+    pub renderdoc_capture: Vec<Binding>,
+    pub cycle_visualization: Vec<Binding>,
+    pub toggle_texture_inspector: Vec<Binding>,
+    pub launch: Vec<Binding>,
+}
+
+impl Default for InputBindings {
+    fn default() -> InputBindings {
+        InputBindings {
+            move_forward: vec![Binding::key(Key::W)],
+            move_backward: vec![Binding::key(Key::S)],
+            move_left: vec![Binding::key(Key::A), Binding::key(Key::Left)],
+            move_right: vec![Binding::key(Key::D), Binding::key(Key::Right)],
+            quit: vec![Binding::key(Key::Escape)],
+            pick: vec![Binding::button(MouseButton::Left)],
+            gizmo_translate: vec![Binding::key(Key::Num1)],
+            gizmo_rotate: vec![Binding::key(Key::Num2)],
+            gizmo_scale: vec![Binding::key(Key::Num3)],
+            toggle_pause: vec![Binding::key(Key::P)],
+            step_simulation: vec![Binding::key(Key::Period)],
+            slow_down: vec![Binding::key(Key::LeftBracket)],
+            speed_up: vec![Binding::key(Key::RightBracket)],
+            toggle_stats: vec![Binding::key(Key::F3)],
+            cycle_vsync: vec![Binding::key(Key::V)],
+            toggle_recording: vec![Binding::key(Key::F9)],
+            save_scene: vec![Binding::key(Key::F5), Binding::key(Key::S).with_control()],
+            toggle_cursor_capture: vec![Binding::key(Key::Tab)],
+            toggle_fullscreen: vec![Binding::key(Key::Enter).with_alt()],
+            reload_scene: vec![Binding::key(Key::R).with_control()],
+            toggle_console: vec![Binding::key(Key::GraveAccent)],
+            renderdoc_capture: vec![Binding::key(Key::F12)],
+            cycle_visualization: vec![Binding::key(Key::F4)],
+            toggle_texture_inspector: vec![Binding::key(Key::F6)],
+            launch: vec![Binding::key(Key::Space)],
+        }
+    }
+}
+
+/// Looks up the actions bound to keys and buttons. Events are translated with `actions`, held
+/// keys are polled with `is_down`.
+pub struct InputMap {
+    bindings: Vec<(Binding, InputAction)>,
+}
+
+impl InputMap {
+    pub fn new(bindings: &InputBindings) -> InputMap {
+        let actions = [
+            (InputAction::MoveForward, &bindings.move_forward),
+            (InputAction::MoveBackward, &bindings.move_backward),
+            (InputAction::MoveLeft, &bindings.move_left),
+            (InputAction::MoveRight, &bindings.move_right),
+            (InputAction::Quit, &bindings.quit),
+            (InputAction::Pick, &bindings.pick),
+            (InputAction::GizmoTranslate, &bindings.gizmo_translate),
+            (InputAction::GizmoRotate, &bindings.gizmo_rotate),
+            (InputAction::GizmoScale, &bindings.gizmo_scale),
+            (InputAction::TogglePause, &bindings.toggle_pause),
+            (InputAction::StepSimulation, &bindings.step_simulation),
+            (InputAction::SlowDown, &bindings.slow_down),
+            (InputAction::SpeedUp, &bindings.speed_up),
+            (InputAction::ToggleStats, &bindings.toggle_stats),
+            (InputAction::CycleVsync, &bindings.cycle_vsync),
+            (InputAction::ToggleRecording, &bindings.toggle_recording),
+            (InputAction::SaveScene, &bindings.save_scene),
+            (
+                InputAction::ToggleCursorCapture,
+                &bindings.toggle_cursor_capture,
+            ),
+            (InputAction::ToggleFullscreen, &bindings.toggle_fullscreen),
+            (InputAction::ReloadScene, &bindings.reload_scene),
+            (InputAction::ToggleConsole, &bindings.toggle_console),
+            (InputAction::RenderDocCapture, &bindings.renderdoc_capture),
+            (
+                InputAction::CycleVisualization,
+                &bindings.cycle_visualization,
+            ),
+            (
+                InputAction::ToggleTextureInspector,
+                &bindings.toggle_texture_inspector,
+            ),
+            (InputAction::Launch, &bindings.launch),
+        ];
+        let bindings = actions
+            .iter()
+            .flat_map(|(action, bindings)| bindings.iter().map(move |binding| (*binding, *action)))
+            .collect();
+        InputMap { bindings }
+    }
+
+    /// Actions bound to `trigger` with exactly `modifiers` held, in the order they are declared.
+    /// Ctrl+S thus saves without also moving backwards.
+    pub fn actions(&self, trigger: Trigger, modifiers: Modifiers) -> Vec<InputAction> {
+        self.bindings
+            .iter()
+            .filter(|(binding, _)| binding.matches(trigger, modifiers))
+            .map(|(_, action)| *action)
+            .collect()
+    }
+
+    /// Whether a key bound to `action` is held, along with its modifiers. Mouse button bindings
+    /// are only seen as events.
+    pub fn is_down(&self, window: &impl WindowBackend, action: InputAction) -> bool {
+        let modifiers = window.modifiers();
+        self.bindings.iter().any(|(binding, bound_action)| {
+            *bound_action == action
+                && match binding.trigger {
+                    Trigger::Key(key) => {
+                        window.is_key_down(key) && binding.matches(binding.trigger, modifiers)
+                    }
+                    Trigger::Mouse(_) => false,
+                }
+        })
+    }
+}
+
+/// Zeroes stick deflections shorter than `dead_zone` and rescales the rest to start from zero,
+/// so worn sticks do not drift and motion still ramps up smoothly past the dead zone.
+pub fn apply_dead_zone((x, y): (f32, f32), dead_zone: f32) -> (f32, f32) {
+    let length = (x * x + y * y).sqrt();
+    if length <= dead_zone {
+        return (0.0, 0.0);
+    }
+    let scale = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0) / length;
+    (x * scale, y * scale)
+}
+
+// Mouse speed, in counts per second, that the acceleration curve leaves unchanged
+const LOOK_ACCELERATION_REFERENCE_SPEED: f32 = 500.0;
+
+/// Shapes mouse look motion: an acceleration curve scales fast motion up (or down) relative to
+/// slow motion, then exponential smoothing evens out the jitter of high-DPI mice. Works on
+/// speeds rather than per-frame deltas, so it behaves the same at any frame rate.
+pub struct LookFilter {
+    /// Smoothing time constant in seconds, 0 to disable
+    smoothing: f32,
+    /// Exponent of the acceleration curve, 1 for none
+    acceleration: f32,
+    velocity: (f32, f32),
+}
+
+impl LookFilter {
+    pub fn new(smoothing: f32, acceleration: f32) -> LookFilter {
+        LookFilter {
+            smoothing,
+            acceleration,
+            velocity: (0.0, 0.0),
+        }
+    }
+
+    /// Turns the mouse motion accumulated over a frame into the motion to look by.
+    pub fn filter(&mut self, (x, y): (f32, f32), delta_time: f32) -> (f32, f32) {
+        if delta_time <= 0.0 {
+            return (x, y);
+        }
+        let (mut velocity_x, mut velocity_y) = (x / delta_time, y / delta_time);
+        let speed = (velocity_x * velocity_x + velocity_y * velocity_y).sqrt();
+        if self.acceleration != 1.0 && speed > 0.0 {
+            let gain = (speed / LOOK_ACCELERATION_REFERENCE_SPEED).powf(self.acceleration - 1.0);
+            velocity_x *= gain;
+            velocity_y *= gain;
+        }
+        let blend = damp_factor(self.smoothing, delta_time);
+        self.velocity.0 += (velocity_x - self.velocity.0) * blend;
+        self.velocity.1 += (velocity_y - self.velocity.1) * blend;
+        (self.velocity.0 * delta_time, self.velocity.1 * delta_time)
+    }
+}
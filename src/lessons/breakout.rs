@@ -0,0 +1,127 @@
+use gl::types::*;
+
+use crate::breakout::{Breakout, BreakoutTextures, FIELD_SIZE};
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner, LessonSetup};
+use crate::math::glm;
+use crate::ogl::billboard_renderer::create_puff_sheet_texture;
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::Texture;
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::renderer::Renderer;
+use crate::ogl::sprite_batch::{Camera2d, SpriteBatch};
+use crate::ogl::text_renderer::{TextRenderer, DEFAULT_FONT_ATLAS};
+use std::mem;
+
+// Images the `--lesson breakout` ball and solid bricks are drawn with
+const BREAKOUT_BALL_TEXTURE: &str = "resources/images/awesomeface.png";
+const BREAKOUT_SOLID_BRICK_TEXTURE: &str = "resources/images/container.jpg";
+
+/// The Breakout game and what it draws with.
+pub struct BreakoutLesson {
+    game: Breakout,
+    textures: BreakoutTextures,
+    /// Owns the textures `textures` refers to, but for the puff sheet
+    #[allow(dead_code)]
+    owned_textures: Vec<Texture>,
+    sprite_batch: SpriteBatch,
+    text: TextRenderer,
+    context: GlContext,
+}
+
+impl LessonRunner for BreakoutLesson {
+    /// A new game, drawing its ball's trail from a sheet of smoke puffs. Sprites whose image
+    /// fails to load are drawn flat instead.
+    fn init(setup: &LessonSetup) -> Result<BreakoutLesson, String> {
+        let context = setup.gl_context;
+        let load = |file_path: &str| match Texture::from_file(context, file_path, true) {
+            Ok(mut texture) => {
+                texture.load(context);
+                Some(texture)
+            }
+            Err(e) => {
+                eprintln!("Failed loading texture file {}: {}", file_path, e);
+                None
+            }
+        };
+        let ball = load(BREAKOUT_BALL_TEXTURE);
+        let solid_brick = load(BREAKOUT_SOLID_BRICK_TEXTURE);
+        let id = |texture: &Option<Texture>| texture.as_ref().map_or(0, |texture| texture.id);
+        Ok(BreakoutLesson {
+            game: Breakout::new(),
+            textures: BreakoutTextures {
+                ball: id(&ball),
+                solid_brick: id(&solid_brick),
+                puff_sheet: create_puff_sheet_texture(context),
+            },
+            owned_textures: ball.into_iter().chain(solid_brick).collect(),
+            sprite_batch: SpriteBatch::new(context)?,
+            text: TextRenderer::new(context, DEFAULT_FONT_ATLAS)?,
+            context: *context,
+        })
+    }
+
+    fn update(&mut self, dt: f32, input: &mut LessonInput) {
+        let launch = mem::replace(&mut input.launch, false);
+        self.game.update(dt, input.steer, launch);
+    }
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame) {
+        match pass {
+            LessonPass::Sprites => {
+                renderer.gpu_timer.begin_pass("sprites");
+                self.game
+                    .add_sprites(&self.textures, &mut self.sprite_batch);
+                let field_camera =
+                    Camera2d::fitting(glm::vec2(FIELD_SIZE[0], FIELD_SIZE[1]), frame.output_size);
+                self.sprite_batch
+                    .draw(&field_camera, frame.output_size, &mut renderer.stats);
+                renderer.gpu_timer.end_pass();
+            }
+            LessonPass::Overlay => self.draw_hud(renderer, frame),
+            LessonPass::Offscreen | LessonPass::Opaque | LessonPass::Transparent => {}
+        }
+    }
+
+    fn takes_movement_keys(&self) -> bool {
+        true
+    }
+}
+
+impl BreakoutLesson {
+    /// Lives as balls in the top left corner, with the score and level under them, in pixels
+    /// scaled by the content scale.
+    fn draw_hud(&mut self, renderer: &mut Renderer, frame: &LessonFrame) {
+        let content_scale = frame.content_scale;
+        let hud_camera = Camera2d {
+            zoom: content_scale,
+            ..Camera2d::new()
+        };
+        let margin = 10.0_f32;
+        self.game.add_hud_sprites(
+            &self.textures,
+            glm::vec2(margin, margin),
+            &mut self.sprite_batch,
+        );
+        self.sprite_batch
+            .draw(&hud_camera, frame.framebuffer_size, &mut renderer.stats);
+        let text_scale = 0.75_f32 * content_scale;
+        let mut text = format!("Score {}  Level {}", self.game.score, self.game.level());
+        if self.game.serving() {
+            text.push_str("\nSpace serves the ball");
+        }
+        self.text.add_text(
+            &text,
+            (margin * content_scale, 4.0_f32 * margin * content_scale),
+            text_scale,
+            [1.0_f32, 1.0_f32, 1.0_f32, 0.9_f32],
+        );
+        self.text.draw(frame.framebuffer_size);
+    }
+}
+
+impl Drop for BreakoutLesson {
+    fn drop(&mut self) {
+        let puff_sheet: GLuint = self.textures.puff_sheet;
+        live_objects::release(&self.context, GlObject::Texture, puff_sheet);
+    }
+}
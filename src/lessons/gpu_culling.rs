@@ -0,0 +1,49 @@
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner, LessonSetup};
+use crate::ogl::gpu_culling::{self, GpuCuller};
+use crate::ogl::renderer::Renderer;
+
+// Shapes in the `--lesson gpu_culling` field, the radius of the ball they fill, and their seed
+const GPU_CULLING_INSTANCE_COUNT: usize = 250_000;
+const GPU_CULLING_RADIUS: f32 = 120.0_f32;
+const GPU_CULLING_SEED: u64 = 464;
+
+/// A field of shapes frustum culled by a compute shader and drawn with one indirect call.
+pub struct GpuCullingLesson {
+    culler: GpuCuller,
+}
+
+impl LessonRunner for GpuCullingLesson {
+    fn init(setup: &LessonSetup) -> Result<GpuCullingLesson, String> {
+        let capabilities = setup.capabilities;
+        if !(capabilities.compute_shaders
+            && capabilities.shader_storage_buffers
+            && capabilities.multi_draw_indirect)
+        {
+            return Err(
+                "GPU culling needs compute shaders and multi-draw indirect (OpenGL 4.3)"
+                    .to_string(),
+            );
+        }
+        let shapes = gpu_culling::shapes();
+        let instances = gpu_culling::scatter_instances(
+            GPU_CULLING_INSTANCE_COUNT,
+            shapes.len(),
+            GPU_CULLING_RADIUS,
+            GPU_CULLING_SEED,
+        );
+        let culler = GpuCuller::new(setup.gl_context, &shapes, &instances)?;
+        println!("Culling {} instances on the GPU", culler.instance_count());
+        Ok(GpuCullingLesson { culler })
+    }
+
+    fn update(&mut self, _dt: f32, _input: &mut LessonInput) {}
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame) {
+        if pass != LessonPass::Opaque {
+            return;
+        }
+        renderer.gpu_timer.begin_pass("gpu culling");
+        self.culler.draw(frame.camera, renderer);
+        renderer.gpu_timer.end_pass();
+    }
+}
@@ -0,0 +1,40 @@
+use crate::lessons::particles::PARTICLES_SEED;
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner, LessonSetup};
+use crate::ogl::gpu_particles::{GpuParticleBackend, GpuParticleSettings, GpuParticleSystem};
+use crate::ogl::renderer::Renderer;
+
+const GPU_PARTICLE_COUNT: usize = 1_000_000;
+
+/// A million particles swirling around attractors, simulated on the GPU.
+pub struct GpuParticlesLesson {
+    system: GpuParticleSystem,
+}
+
+impl LessonRunner for GpuParticlesLesson {
+    fn init(setup: &LessonSetup) -> Result<GpuParticlesLesson, String> {
+        let backend = GpuParticleBackend::detect();
+        println!("Simulating GPU particles with {:?}", backend);
+        let settings = GpuParticleSettings::attractor_cloud(GPU_PARTICLE_COUNT);
+        Ok(GpuParticlesLesson {
+            system: GpuParticleSystem::new(setup.gl_context, backend, settings, PARTICLES_SEED)?,
+        })
+    }
+
+    fn update(&mut self, dt: f32, _input: &mut LessonInput) {
+        self.system.update(dt);
+    }
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame) {
+        if pass != LessonPass::Transparent {
+            return;
+        }
+        renderer.gpu_timer.begin_pass("gpu particles");
+        let projection_from_view = *renderer.projection_from_view();
+        self.system.draw(
+            &frame.camera.view_matrix(),
+            &projection_from_view,
+            &mut renderer.stats,
+        );
+        renderer.gpu_timer.end_pass();
+    }
+}
@@ -0,0 +1,41 @@
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner, LessonSetup};
+use crate::math::glm;
+use crate::ogl::mirror::Mirror;
+use crate::ogl::renderer::Renderer;
+use std::mem;
+
+// Floor mirror under the cubes, facing up
+const MIRROR_CENTER: [f32; 3] = [0.0_f32, -3.5_f32, -6.0_f32];
+const MIRROR_SIZE: [f32; 2] = [14.0_f32, 20.0_f32];
+
+/// The cubes reflected in a floor mirror, drawn through the stencil buffer.
+pub struct MirrorLesson {
+    mirror: Mirror,
+}
+
+impl LessonRunner for MirrorLesson {
+    fn init(setup: &LessonSetup) -> Result<MirrorLesson, String> {
+        Ok(MirrorLesson {
+            mirror: Mirror::new(
+                setup.gl_context,
+                glm::make_vec3(&MIRROR_CENTER),
+                glm::vec3(0.0_f32, 1.0_f32, 0.0_f32),
+                MIRROR_SIZE[0],
+                MIRROR_SIZE[1],
+            )?,
+        })
+    }
+
+    fn update(&mut self, _dt: f32, _input: &mut LessonInput) {}
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame) {
+        if pass != LessonPass::Opaque || !self.mirror.begin_reflection(frame.camera, renderer) {
+            return;
+        }
+        // Occlusion queries answer for the camera's view, not the reflected one
+        let occlusion_culling = mem::replace(&mut renderer.occlusion_culling, false);
+        (frame.submit_scene)(renderer);
+        renderer.occlusion_culling = occlusion_culling;
+        self.mirror.end_reflection(frame.camera, renderer);
+    }
+}
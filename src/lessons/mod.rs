@@ -0,0 +1,184 @@
+// What each `--lesson` adds to the frame beyond its scene: the particle systems, the terrain,
+// the mirror, Breakout and so on each live in a module of their own behind `LessonRunner`, which
+// `App` calls into at fixed points of the frame. Lessons that are only a scene, such as the cubes
+// or the lights, have no runner.
+
+mod breakout;
+#[cfg(feature = "gl45")]
+mod gpu_culling;
+mod gpu_particles;
+mod mirror;
+mod particles;
+mod terrain;
+
+use crate::cli::Lesson;
+use crate::console::Command;
+use crate::ogl::capabilities::GlCapabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::Camera;
+use crate::ogl::renderer::Renderer;
+use crate::scene::{
+    Scene, BREAKOUT_SCENE_FILE, DEFAULT_SCENE_FILE, GPU_CULLING_SCENE_FILE, HDR_SCENE_FILE,
+    LIGHTS_SCENE_FILE, PARTICLES_SCENE_FILE, REFLECTIONS_SCENE_FILE, STRESS_SCENE_FILE,
+    TERRAIN_SCENE_FILE, TOON_SCENE_FILE,
+};
+use std::path::Path;
+
+// Objects in the `--lesson stress` scene, and the seed scattering them
+const STRESS_OBJECT_COUNT: usize = 100_000;
+const STRESS_SEED: u64 = 418;
+// Point lights over the `--lesson lights` slab, and the seed scattering them
+const LIGHTS_COUNT: usize = 256;
+const LIGHTS_SEED: u64 = 465;
+
+/// What a lesson is set up with, once the window and its GL context exist.
+pub struct LessonSetup<'a> {
+    pub lesson: Lesson,
+    pub gl_context: &'a GlContext,
+    #[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+    pub capabilities: &'a GlCapabilities,
+    pub framebuffer_size: (i32, i32),
+}
+
+/// What the player does to a lesson's simulation this frame.
+pub struct LessonInput {
+    /// -1 for full speed left to 1 for full speed right, from the movement keys and left stick
+    pub steer: f32,
+    /// Taken by the first simulation step that acts on it
+    pub launch: bool,
+}
+
+/// Points of the frame a lesson draws at, in the order they come.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LessonPass {
+    /// Into targets of its own before the scene; the frame's output is bound again afterwards
+    Offscreen,
+    /// Along with the scene's opaque geometry, before the skybox
+    Opaque,
+    /// Over the scene and skybox, blended or testing against their depth
+    Transparent,
+    /// 2D sprites, after the debug drawing and before post-processing
+    Sprites,
+    /// On top of the finished frame, with the debug HUD
+    Overlay,
+}
+
+/// The frame a lesson draws into.
+pub struct LessonFrame<'a> {
+    pub camera: &'a Camera,
+    /// Submits the scene once more, for passes that draw it from another view
+    pub submit_scene: &'a dyn Fn(&mut Renderer),
+    /// Debug markers, such as emitter origins, show along with the debug HUD
+    pub show_markers: bool,
+    pub output_size: (i32, i32),
+    pub framebuffer_size: (i32, i32),
+    pub content_scale: f32,
+}
+
+pub trait LessonRunner {
+    fn init(setup: &LessonSetup) -> Result<Self, String>
+    where
+        Self: Sized;
+
+    /// Advances the lesson by one simulation step of `dt` seconds.
+    fn update(&mut self, dt: f32, input: &mut LessonInput);
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame);
+
+    /// Whether the movement keys steer the lesson instead of flying the camera.
+    fn takes_movement_keys(&self) -> bool {
+        false
+    }
+
+    /// Carries out a console command meant for the lesson, or returns `None` to leave it to the
+    /// app.
+    fn handle_command(&mut self, _command: &Command) -> Option<Result<String, String>> {
+        None
+    }
+}
+
+/// The runner of `setup.lesson`, or `None` for lessons that are only a scene or that cannot run
+/// on this context.
+pub fn init(setup: &LessonSetup) -> Option<Box<dyn LessonRunner>> {
+    fn boxed<T: LessonRunner + 'static>(runner: T) -> Box<dyn LessonRunner> {
+        Box::new(runner)
+    }
+    let runner = match setup.lesson {
+        Lesson::Particles => particles::ParticlesLesson::init(setup).map(boxed),
+        Lesson::GpuParticles => gpu_particles::GpuParticlesLesson::init(setup).map(boxed),
+        #[cfg(feature = "gl45")]
+        Lesson::GpuCulling => gpu_culling::GpuCullingLesson::init(setup).map(boxed),
+        #[cfg(not(feature = "gl45"))]
+        Lesson::GpuCulling => Err("GPU culling needs the gl45 feature".to_string()),
+        Lesson::Terrain | Lesson::Water | Lesson::Grass => {
+            terrain::TerrainLesson::init(setup).map(boxed)
+        }
+        Lesson::Mirror => mirror::MirrorLesson::init(setup).map(boxed),
+        Lesson::Breakout => breakout::BreakoutLesson::init(setup).map(boxed),
+        Lesson::Cubes
+        | Lesson::Stress
+        | Lesson::Toon
+        | Lesson::Lights
+        | Lesson::Reflections
+        | Lesson::Hdr => return None,
+    };
+    runner
+        .map_err(|e| eprintln!("Failed setting up the {:?} lesson: {}", setup.lesson, e))
+        .ok()
+}
+
+/// The scene `lesson` starts with, and the file it saves to and reloads from.
+pub fn scene(lesson: Lesson) -> (Scene, String) {
+    let scene = match lesson {
+        Lesson::Stress => Scene::stress(STRESS_OBJECT_COUNT, STRESS_SEED),
+        Lesson::Particles | Lesson::GpuParticles => Scene::particles(),
+        Lesson::GpuCulling => Scene::gpu_culling(),
+        Lesson::Terrain | Lesson::Water | Lesson::Grass => Scene::terrain(),
+        Lesson::Cubes | Lesson::Mirror if Path::new(DEFAULT_SCENE_FILE).exists() => {
+            Scene::load(DEFAULT_SCENE_FILE).unwrap_or_else(|e| {
+                eprintln!("Failed loading scene {}: {}", DEFAULT_SCENE_FILE, e);
+                Scene::default_cubes()
+            })
+        }
+        Lesson::Cubes | Lesson::Mirror => Scene::default_cubes(),
+        Lesson::Toon if Path::new(TOON_SCENE_FILE).exists() => Scene::load(TOON_SCENE_FILE)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed loading scene {}: {}", TOON_SCENE_FILE, e);
+                Scene::toon_cubes()
+            }),
+        Lesson::Toon => Scene::toon_cubes(),
+        Lesson::Lights if Path::new(LIGHTS_SCENE_FILE).exists() => Scene::load(LIGHTS_SCENE_FILE)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed loading scene {}: {}", LIGHTS_SCENE_FILE, e);
+                Scene::many_lights(LIGHTS_COUNT, LIGHTS_SEED)
+            }),
+        Lesson::Lights => Scene::many_lights(LIGHTS_COUNT, LIGHTS_SEED),
+        Lesson::Reflections if Path::new(REFLECTIONS_SCENE_FILE).exists() => {
+            Scene::load(REFLECTIONS_SCENE_FILE).unwrap_or_else(|e| {
+                eprintln!("Failed loading scene {}: {}", REFLECTIONS_SCENE_FILE, e);
+                Scene::reflection_rooms()
+            })
+        }
+        Lesson::Reflections => Scene::reflection_rooms(),
+        Lesson::Breakout => Scene::empty(),
+        Lesson::Hdr if Path::new(HDR_SCENE_FILE).exists() => Scene::load(HDR_SCENE_FILE)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed loading scene {}: {}", HDR_SCENE_FILE, e);
+                Scene::hdr_tunnel()
+            }),
+        Lesson::Hdr => Scene::hdr_tunnel(),
+    };
+    let scene_file = match lesson {
+        Lesson::Cubes | Lesson::Mirror => DEFAULT_SCENE_FILE,
+        Lesson::Toon => TOON_SCENE_FILE,
+        Lesson::Lights => LIGHTS_SCENE_FILE,
+        Lesson::Reflections => REFLECTIONS_SCENE_FILE,
+        Lesson::Breakout => BREAKOUT_SCENE_FILE,
+        Lesson::Hdr => HDR_SCENE_FILE,
+        Lesson::Stress => STRESS_SCENE_FILE,
+        Lesson::Particles | Lesson::GpuParticles => PARTICLES_SCENE_FILE,
+        Lesson::GpuCulling => GPU_CULLING_SCENE_FILE,
+        Lesson::Terrain | Lesson::Water | Lesson::Grass => TERRAIN_SCENE_FILE,
+    };
+    (scene, scene_file.to_string())
+}
@@ -0,0 +1,90 @@
+use gl::types::*;
+
+use crate::flipbook::UvRect;
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner, LessonSetup};
+use crate::math::color::Color;
+use crate::ogl::billboard_renderer::{
+    create_marker_texture, create_puff_sheet_texture, Billboard, BillboardRenderer,
+};
+use crate::ogl::context::GlContext;
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::particle_renderer::ParticleRenderer;
+use crate::ogl::renderer::Renderer;
+use crate::particles::{BlendMode, ParticleSystem};
+
+/// Seeds the emitters of `--lesson particles` and `--lesson gpu_particles`.
+pub const PARTICLES_SEED: u64 = 426;
+
+/// The fountain, smoke and fire emitters, simulated on the CPU and drawn as billboards.
+pub struct ParticlesLesson {
+    system: ParticleSystem,
+    particle_renderer: ParticleRenderer,
+    billboard_renderer: BillboardRenderer,
+    /// Emitter origins, drawn with the debug HUD
+    markers: Vec<Billboard>,
+    marker_texture: GLuint,
+    puff_sheet_texture: GLuint,
+    context: GlContext,
+}
+
+impl LessonRunner for ParticlesLesson {
+    fn init(setup: &LessonSetup) -> Result<ParticlesLesson, String> {
+        let context = setup.gl_context;
+        Ok(ParticlesLesson {
+            system: ParticleSystem::presets(PARTICLES_SEED),
+            particle_renderer: ParticleRenderer::new(),
+            billboard_renderer: BillboardRenderer::new(context)?,
+            markers: vec![],
+            marker_texture: create_marker_texture(context),
+            puff_sheet_texture: create_puff_sheet_texture(context),
+            context: *context,
+        })
+    }
+
+    fn update(&mut self, dt: f32, _input: &mut LessonInput) {
+        self.system.update(dt);
+    }
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame) {
+        if pass != LessonPass::Transparent {
+            return;
+        }
+        renderer.gpu_timer.begin_pass("particles");
+        let projection_from_view = *renderer.projection_from_view();
+        self.particle_renderer.draw(
+            &self.system,
+            self.puff_sheet_texture,
+            &mut self.billboard_renderer,
+            frame.camera,
+            &projection_from_view,
+            &mut renderer.stats,
+        );
+        if frame.show_markers {
+            self.markers.clear();
+            self.markers
+                .extend(self.system.emitters.iter().map(|emitter| Billboard {
+                    position: emitter.position,
+                    size: 0.3_f32,
+                    rotation_radians: 0.0_f32,
+                    color: Color::from_srgb(1.0, 0.85, 0.2, 0.9),
+                    uv_rect: UvRect::FULL,
+                }));
+            self.billboard_renderer.draw(
+                &self.markers,
+                Some(self.marker_texture),
+                BlendMode::Alpha,
+                frame.camera,
+                &projection_from_view,
+                &mut renderer.stats,
+            );
+        }
+        renderer.gpu_timer.end_pass();
+    }
+}
+
+impl Drop for ParticlesLesson {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Texture, self.marker_texture);
+        live_objects::release(&self.context, GlObject::Texture, self.puff_sheet_texture);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::cli::Lesson;
+use crate::console::{on_off, Command};
+use crate::lessons::{LessonFrame, LessonInput, LessonPass, LessonRunner, LessonSetup};
+use crate::ogl::grass_renderer::GrassRenderer;
+use crate::ogl::renderer::Renderer;
+use crate::ogl::terrain_renderer::{TerrainMode, TerrainRenderer};
+use crate::ogl::water_renderer::WaterRenderer;
+use crate::terrain::{scatter_grass, Heightmap, Terrain};
+
+const TERRAIN_SEED: u64 = 428;
+// One more sample than a power of two per side, so the patches split the grid evenly
+const TERRAIN_SAMPLES: usize = 129;
+const TERRAIN_SIZE: f32 = 96.0_f32;
+const TERRAIN_HEIGHT: f32 = 18.0_f32;
+const TERRAIN_PATCHES_PER_SIDE: usize = 8;
+// Floods the lowest fifth of the terrain's height range
+const WATER_HEIGHT: f32 = 0.2_f32 * TERRAIN_HEIGHT;
+const GRASS_SEED: u64 = 430;
+// Closest two tufts stand to each other
+const GRASS_SPACING: f32 = 0.5_f32;
+// Grass grows on the lower ground, below where the terrain turns to rock
+const GRASS_MAX_HEIGHT: f32 = 0.28_f32 * TERRAIN_HEIGHT;
+
+/// The heightmap terrain of `--lesson terrain`, with the lake of `--lesson water` or the grass
+/// of `--lesson grass`.
+pub struct TerrainLesson {
+    terrain: Terrain,
+    terrain_renderer: TerrainRenderer,
+    water: Option<WaterRenderer>,
+    grass: Option<GrassRenderer>,
+}
+
+impl LessonRunner for TerrainLesson {
+    fn init(setup: &LessonSetup) -> Result<TerrainLesson, String> {
+        let context = setup.gl_context;
+        let heightmap =
+            Heightmap::generate(TERRAIN_SAMPLES, TERRAIN_SIZE, TERRAIN_HEIGHT, TERRAIN_SEED);
+        let terrain = Terrain::new(heightmap, TERRAIN_PATCHES_PER_SIDE);
+        let mode = TerrainMode::detect();
+        println!("Drawing terrain patches with {:?}", mode);
+        let terrain_renderer = TerrainRenderer::new(context, mode, &terrain)?;
+        let water = match setup.lesson {
+            Lesson::Water => {
+                let (width, height) = setup.framebuffer_size;
+                Some(WaterRenderer::new(
+                    context,
+                    WATER_HEIGHT,
+                    TERRAIN_SIZE,
+                    (width / 2, height / 2),
+                    TERRAIN_SEED,
+                )?)
+            }
+            _ => None,
+        };
+        let grass = match setup.lesson {
+            Lesson::Grass => {
+                let tufts = scatter_grass(
+                    &terrain.heightmap,
+                    GRASS_SPACING,
+                    GRASS_MAX_HEIGHT,
+                    GRASS_SEED,
+                );
+                let grass_renderer = GrassRenderer::new(context, tufts, GRASS_SEED)?;
+                println!("Drawing {} grass tufts", grass_renderer.tuft_count());
+                Some(grass_renderer)
+            }
+            _ => None,
+        };
+        Ok(TerrainLesson {
+            terrain,
+            terrain_renderer,
+            water,
+            grass,
+        })
+    }
+
+    fn update(&mut self, dt: f32, _input: &mut LessonInput) {
+        if let Some(water) = self.water.as_mut() {
+            water.update(dt);
+        }
+        if let Some(grass) = self.grass.as_mut() {
+            grass.update(dt);
+        }
+    }
+
+    fn render(&mut self, pass: LessonPass, renderer: &mut Renderer, frame: &LessonFrame) {
+        let camera = frame.camera;
+        match pass {
+            LessonPass::Offscreen => {
+                if let Some(water) = &self.water {
+                    renderer.gpu_timer.begin_pass("water passes");
+                    water.begin_reflection(renderer);
+                    let reflection_camera = water.reflection_camera(camera);
+                    self.terrain_renderer.draw(
+                        &self.terrain,
+                        &reflection_camera,
+                        Some(water.reflection_clip_plane()),
+                        renderer,
+                    );
+                    water.begin_refraction(renderer);
+                    self.terrain_renderer.draw(
+                        &self.terrain,
+                        camera,
+                        Some(water.refraction_clip_plane()),
+                        renderer,
+                    );
+                    renderer.gpu_timer.end_pass();
+                }
+            }
+            LessonPass::Opaque => {
+                renderer.gpu_timer.begin_pass("terrain");
+                self.terrain_renderer
+                    .draw(&self.terrain, camera, None, renderer);
+                renderer.gpu_timer.end_pass();
+            }
+            LessonPass::Transparent => {
+                if let Some(grass) = self.grass.as_mut() {
+                    renderer.gpu_timer.begin_pass("grass");
+                    grass.draw(camera, renderer);
+                    renderer.gpu_timer.end_pass();
+                }
+                if let Some(water) = &self.water {
+                    renderer.gpu_timer.begin_pass("water");
+                    water.draw(camera, renderer);
+                    renderer.gpu_timer.end_pass();
+                }
+            }
+            LessonPass::Sprites | LessonPass::Overlay => {}
+        }
+    }
+
+    /// `toggle grass_blending` switches grass between alpha blending and alpha testing.
+    fn handle_command(&mut self, command: &Command) -> Option<Result<String, String>> {
+        match (command, self.grass.as_mut()) {
+            (Command::Toggle(name), Some(grass)) if name == "grass_blending" => {
+                grass.alpha_blend = !grass.alpha_blend;
+                Some(Ok(format!("grass_blending {}", on_off(grass.alpha_blend))))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::math::glm::{self, Mat4, Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+/// Sphere enclosing some geometry, cheaper to test and transform than a box but looser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+#[allow(dead_code)]
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// Smallest box around `points`, or `None` for no points.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(*first, *first), |aabb, point| {
+            aabb.merged_point(point)
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5_f32
+    }
+
+    /// Half the size along each axis.
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5_f32
+    }
+
+    /// The eight corners, ordered by x, then y, then z being at the max side.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            glm::vec3(min.x, min.y, min.z),
+            glm::vec3(max.x, min.y, min.z),
+            glm::vec3(min.x, max.y, min.z),
+            glm::vec3(max.x, max.y, min.z),
+            glm::vec3(min.x, min.y, max.z),
+            glm::vec3(max.x, min.y, max.z),
+            glm::vec3(min.x, max.y, max.z),
+            glm::vec3(max.x, max.y, max.z),
+        ]
+    }
+
+    pub fn merged(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn merged_point(&self, point: &Vec3) -> Aabb {
+        Aabb {
+            min: glm::min2(&self.min, point),
+            max: glm::max2(&self.max, point),
+        }
+    }
+
+    /// Box around this one after transforming it by `to_from_from`, which grows under rotation.
+    pub fn transformed(&self, to_from_from: &Mat4) -> Aabb {
+        // Each matrix column stretches the box along one output axis by its absolute extent
+        let center = to_from_from * glm::vec4(0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32);
+        let mut min = glm::xyz(&center);
+        let mut max = glm::xyz(&center);
+        for column in 0..3 {
+            let axis = glm::xyz(&glm::column(to_from_from, column));
+            let a = axis * self.min[column];
+            let b = axis * self.max[column];
+            min += glm::min2(&a, &b);
+            max += glm::max2(&a, &b);
+        }
+        Aabb { min, max }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && self.max[axis] >= other.min[axis])
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        let closest = glm::clamp_vec(&sphere.center, &self.min, &self.max);
+        glm::distance2(&closest, &sphere.center) <= sphere.radius * sphere.radius
+    }
+}
+
+#[allow(dead_code)]
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    /// Sphere through the corners of `aabb`.
+    pub fn from_aabb(aabb: &Aabb) -> BoundingSphere {
+        BoundingSphere {
+            center: aabb.center(),
+            radius: glm::length(&aabb.extents()),
+        }
+    }
+
+    /// Smallest sphere enclosing both.
+    pub fn merged(&self, other: &BoundingSphere) -> BoundingSphere {
+        let offset = other.center - self.center;
+        let distance = glm::length(&offset);
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5_f32;
+        BoundingSphere {
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    /// Sphere around this one after transforming it by `to_from_from`, scaled by the largest
+    /// axis scale so it stays enclosing under non-uniform scale.
+    pub fn transformed(&self, to_from_from: &Mat4) -> BoundingSphere {
+        let center = to_from_from * glm::vec4(self.center.x, self.center.y, self.center.z, 1.0_f32);
+        let max_scale = (0..3)
+            .map(|column| glm::length(&glm::xyz(&glm::column(to_from_from, column))))
+            .fold(0.0_f32, f32::max);
+        BoundingSphere {
+            center: glm::xyz(&center),
+            radius: self.radius * max_scale,
+        }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        glm::distance2(&self.center, point) <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &BoundingSphere) -> bool {
+        let radii = self.radius + other.radius;
+        glm::distance2(&self.center, &other.center) <= radii * radii
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        aabb.intersects_sphere(self)
+    }
+}
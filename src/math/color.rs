@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::easing::lerp;
+
+/// RGBA color with linear components, so blending and lighting math comes out right. Nothing
+/// here renders into sRGB framebuffers yet, so colors headed for GL go through `to_srgb`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// sRGB-encoded component to linear, both 0..1.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045_f32 {
+        value / 12.92_f32
+    } else {
+        ((value + 0.055_f32) / 1.055_f32).powf(2.4_f32)
+    }
+}
+
+/// Linear component to sRGB-encoded, both 0..1.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8_f32 {
+        value * 12.92_f32
+    } else {
+        1.055_f32 * value.powf(1.0_f32 / 2.4_f32) - 0.055_f32
+    }
+}
+
+#[allow(dead_code)]
+impl Color {
+    pub const BLACK: Color = Color::rgb(0.0_f32, 0.0_f32, 0.0_f32);
+    pub const WHITE: Color = Color::rgb(1.0_f32, 1.0_f32, 1.0_f32);
+    pub const TRANSPARENT: Color = Color::new(0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Color {
+        Color::new(r, g, b, 1.0_f32)
+    }
+
+    /// From sRGB-encoded components in 0..1, as color pickers and most tools give them.
+    pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::from_srgba8(r, g, b, 255)
+    }
+
+    /// Alpha is linear already, so it is only rescaled.
+    pub fn from_srgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::from_srgb(
+            r as f32 / 255.0_f32,
+            g as f32 / 255.0_f32,
+            b as f32 / 255.0_f32,
+            a as f32 / 255.0_f32,
+        )
+    }
+
+    /// Parses sRGB `RRGGBB` or `RRGGBBAA` hex digits, with or without a leading `#`.
+    pub fn from_hex(hex: &str) -> Result<Color, String> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+            return Err(format!(
+                "Expected a color like #RRGGBB or #RRGGBBAA: {}",
+                hex
+            ));
+        }
+        let byte = |i: usize| {
+            u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("Invalid hex digits in color: {}", hex))
+        };
+        let alpha = if digits.len() == 8 { byte(3)? } else { 255 };
+        Ok(Color::from_srgba8(byte(0)?, byte(1)?, byte(2)?, alpha))
+    }
+
+    /// From hue in degrees, saturation and value in 0..1. HSV describes sRGB-encoded colors, so
+    /// the result is converted from sRGB.
+    pub fn from_hsv(hue_degrees: f32, saturation: f32, value: f32) -> Color {
+        let hue = hue_degrees.rem_euclid(360.0_f32) / 60.0_f32;
+        let chroma = value * saturation;
+        let x = chroma * (1.0_f32 - (hue % 2.0_f32 - 1.0_f32).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.0_f32),
+            1 => (x, chroma, 0.0_f32),
+            2 => (0.0_f32, chroma, x),
+            3 => (0.0_f32, x, chroma),
+            4 => (x, 0.0_f32, chroma),
+            _ => (chroma, 0.0_f32, x),
+        };
+        let m = value - chroma;
+        Color::from_srgb(r + m, g + m, b + m, 1.0_f32)
+    }
+
+    /// Hue in degrees, saturation and value of the sRGB-encoded color.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let [r, g, b, _] = self.to_srgb();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        let hue = if chroma == 0.0_f32 {
+            0.0_f32
+        } else if max == r {
+            60.0_f32 * ((g - b) / chroma).rem_euclid(6.0_f32)
+        } else if max == g {
+            60.0_f32 * ((b - r) / chroma + 2.0_f32)
+        } else {
+            60.0_f32 * ((r - g) / chroma + 4.0_f32)
+        };
+        let saturation = if max == 0.0_f32 {
+            0.0_f32
+        } else {
+            chroma / max
+        };
+        (hue, saturation, max)
+    }
+
+    /// sRGB-encoded components, each clamped to 0..1, with alpha unchanged.
+    pub fn to_srgb(self) -> [f32; 4] {
+        let encode = |value: f32| linear_to_srgb(value.clamp(0.0_f32, 1.0_f32));
+        [encode(self.r), encode(self.g), encode(self.b), self.a]
+    }
+
+    pub fn to_srgba8(self) -> [u8; 4] {
+        let [r, g, b, a] = self.to_srgb();
+        let byte = |value: f32| (value.clamp(0.0_f32, 1.0_f32) * 255.0_f32).round() as u8;
+        [byte(r), byte(g), byte(b), byte(a)]
+    }
+
+    /// `#RRGGBBAA` of the sRGB-encoded color, the inverse of `from_hex`.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_srgba8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+
+    /// The linear components.
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn with_alpha(self, a: f32) -> Color {
+        Color { a, ..self }
+    }
+
+    /// Blends in linear space.
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        Color::new(
+            lerp(self.r, other.r, t),
+            lerp(self.g, other.g, t),
+            lerp(self.b, other.b, t),
+            lerp(self.a, other.a, t),
+        )
+    }
+}
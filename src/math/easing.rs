@@ -0,0 +1,141 @@
+use crate::math::glm::{self, Quat};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Easing curves by name, for picking one from settings or scene files.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    Smoothstep,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    QuintInOut,
+    BounceOut,
+    ElasticOut,
+}
+
+#[allow(dead_code)]
+impl Easing {
+    /// Maps progress `t` in 0..1 to eased progress, which starts at 0 and ends at 1 but may
+    /// overshoot in between.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0_f32, 1.0_f32);
+        match self {
+            Easing::Linear => linear(t),
+            Easing::Smoothstep => smoothstep(t),
+            Easing::CubicIn => cubic_in(t),
+            Easing::CubicOut => cubic_out(t),
+            Easing::CubicInOut => cubic_in_out(t),
+            Easing::QuintInOut => quint_in_out(t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::ElasticOut => elastic_out(t),
+        }
+    }
+}
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+pub fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0_f32 - 2.0_f32 * t)
+}
+
+pub fn cubic_in(t: f32) -> f32 {
+    t * t * t
+}
+
+pub fn cubic_out(t: f32) -> f32 {
+    let u = 1.0_f32 - t;
+    1.0_f32 - u * u * u
+}
+
+pub fn cubic_in_out(t: f32) -> f32 {
+    if t < 0.5_f32 {
+        4.0_f32 * t * t * t
+    } else {
+        let u = -2.0_f32 * t + 2.0_f32;
+        1.0_f32 - u * u * u * 0.5_f32
+    }
+}
+
+pub fn quint_in_out(t: f32) -> f32 {
+    if t < 0.5_f32 {
+        16.0_f32 * t.powi(5)
+    } else {
+        1.0_f32 - (-2.0_f32 * t + 2.0_f32).powi(5) * 0.5_f32
+    }
+}
+
+/// Lands like a dropped ball, with three shrinking bounces.
+pub fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0_f32 / D {
+        N * t * t
+    } else if t < 2.0_f32 / D {
+        let t = t - 1.5_f32 / D;
+        N * t * t + 0.75_f32
+    } else if t < 2.5_f32 / D {
+        let t = t - 2.25_f32 / D;
+        N * t * t + 0.9375_f32
+    } else {
+        let t = t - 2.625_f32 / D;
+        N * t * t + 0.984_375_f32
+    }
+}
+
+/// Overshoots and springs back, settling at 1.
+pub fn elastic_out(t: f32) -> f32 {
+    if t <= 0.0_f32 || t >= 1.0_f32 {
+        return t;
+    }
+    2.0_f32.powf(-10.0_f32 * t) * ((t * 10.0_f32 - 0.75_f32) * (2.0_f32 * PI / 3.0_f32)).sin()
+        + 1.0_f32
+}
+
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Blends between angles in degrees, taking the short way around.
+#[allow(dead_code)]
+pub fn lerp_degrees(a: f32, b: f32, t: f32) -> f32 {
+    let mut delta = (b - a) % 360.0_f32;
+    if delta > 180.0_f32 {
+        delta -= 360.0_f32;
+    } else if delta < -180.0_f32 {
+        delta += 360.0_f32;
+    }
+    a + delta * t
+}
+
+/// Spherical blend between rotations, taking the short way around.
+pub fn slerp(a: &Quat, b: &Quat, t: f32) -> Quat {
+    // q and -q are the same rotation; pick the one on a's side of the hypersphere
+    let b = if glm::quat_dot(a, b) < 0.0_f32 {
+        -b
+    } else {
+        *b
+    };
+    glm::quat_slerp(a, &b, t)
+}
+
+/// Share of the remaining distance exponential smoothing covers in `delta_time`, given the
+/// time constant `smoothing` in seconds. A smoothing of 0 snaps straight to the target.
+pub fn damp_factor(smoothing: f32, delta_time: f32) -> f32 {
+    if smoothing > 0.0_f32 {
+        1.0_f32 - (-delta_time / smoothing).exp()
+    } else {
+        1.0_f32
+    }
+}
+
+/// Moves `current` towards `target` the same amount per second at any frame rate.
+#[allow(dead_code)]
+pub fn damp(current: f32, target: f32, smoothing: f32, delta_time: f32) -> f32 {
+    lerp(current, target, damp_factor(smoothing, delta_time))
+}
@@ -0,0 +1,106 @@
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::math::plane::Plane;
+
+/// How a volume relates to a frustum.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Containment {
+    Inside,
+    Intersecting,
+    Outside,
+}
+
+/// The volume a camera sees, bounded by six planes with unit normals pointing inwards.
+#[derive(Clone, Debug)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far
+    pub planes: [Plane; 6],
+    /// Near plane corners followed by far plane corners, each ordered like `Aabb::corners`
+    #[allow(dead_code)]
+    pub corners: [Vec3; 8],
+}
+
+#[allow(dead_code)]
+impl Frustum {
+    /// Extracts the planes of `projection_from_world`, e.g. `projection_from_view *
+    /// view_from_world`, whose clip space has OpenGL's -w..w depth range. A projection matrix
+    /// alone gives the frustum in view space.
+    pub fn from_matrix(projection_from_world: &Mat4) -> Frustum {
+        let row = |i: usize| glm::row(projection_from_world, i);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        // A point is inside when -w <= x, y, z <= w, so each plane is w plus or minus a row
+        let plane = |coefficients: glm::Vec4| {
+            Plane::new(glm::xyz(&coefficients), coefficients.w).normalized()
+        };
+        let planes = [
+            plane(w + x),
+            plane(w - x),
+            plane(w + y),
+            plane(w - y),
+            plane(w + z),
+            plane(w - z),
+        ];
+
+        let world_from_projection = glm::inverse(projection_from_world);
+        let mut corners = [Vec3::default(); 8];
+        for (corner_i, corner) in corners.iter_mut().enumerate() {
+            let ndc = glm::vec4(
+                if corner_i & 1 == 0 { -1.0_f32 } else { 1.0_f32 },
+                if corner_i & 2 == 0 { -1.0_f32 } else { 1.0_f32 },
+                if corner_i & 4 == 0 { -1.0_f32 } else { 1.0_f32 },
+                1.0_f32,
+            );
+            let world = world_from_projection * ndc;
+            *corner = glm::xyz(&world) / world.w;
+        }
+        Frustum { planes, corners }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0_f32)
+    }
+
+    pub fn classify_sphere(&self, sphere: &BoundingSphere) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in self.planes.iter() {
+            let distance = plane.signed_distance(&sphere.center);
+            if distance < -sphere.radius {
+                return Containment::Outside;
+            }
+            if distance < sphere.radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+
+    /// Conservative near the frustum's edges: a box outside no single plane but still clear of
+    /// the frustum counts as intersecting.
+    pub fn classify_aabb(&self, aabb: &Aabb) -> Containment {
+        let center = aabb.center();
+        let extents = aabb.extents();
+        let mut containment = Containment::Inside;
+        for plane in self.planes.iter() {
+            let distance = plane.signed_distance(&center);
+            // Projected half extent of the box onto the plane normal
+            let radius = glm::dot(&glm::abs(&plane.normal), &extents);
+            if distance < -radius {
+                return Containment::Outside;
+            }
+            if distance < radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+
+    pub fn is_sphere_visible(&self, sphere: &BoundingSphere) -> bool {
+        self.classify_sphere(sphere) != Containment::Outside
+    }
+
+    pub fn is_aabb_visible(&self, aabb: &Aabb) -> bool {
+        self.classify_aabb(aabb) != Containment::Outside
+    }
+}
@@ -0,0 +1,200 @@
+// The vector, matrix and quaternion math the crate uses, named after GLM. This is the only
+// module that talks to the math library, currently nalgebra-glm; another library can stand in
+// by implementing these types and functions, with no changes to the renderer. Elsewhere, stick
+// to these functions, field access (`v.x`), indexing (`v[0]`) and arithmetic operators.
+
+use nalgebra_glm as backend;
+
+pub type Vec2 = backend::Vec2;
+pub type Vec3 = backend::Vec3;
+pub type Vec4 = backend::Vec4;
+pub type Mat3 = backend::Mat3;
+pub type Mat4 = backend::Mat4;
+pub type Quat = backend::Quat;
+
+#[allow(dead_code)]
+pub fn vec2(x: f32, y: f32) -> Vec2 {
+    backend::vec2(x, y)
+}
+
+pub fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+    backend::vec3(x, y, z)
+}
+
+pub fn vec4(x: f32, y: f32, z: f32, w: f32) -> Vec4 {
+    backend::vec4(x, y, z, w)
+}
+
+/// Vector from the first three values of `values`.
+pub fn make_vec3(values: &[f32]) -> Vec3 {
+    backend::make_vec3(values)
+}
+
+/// The x, y and z components, dropping w.
+pub fn xyz(v: &Vec4) -> Vec3 {
+    v.xyz()
+}
+
+pub fn dot(a: &Vec3, b: &Vec3) -> f32 {
+    a.dot(b)
+}
+
+pub fn cross(a: &Vec3, b: &Vec3) -> Vec3 {
+    a.cross(b)
+}
+
+pub fn length(v: &Vec3) -> f32 {
+    v.norm()
+}
+
+pub fn length2(v: &Vec3) -> f32 {
+    v.norm_squared()
+}
+
+pub fn normalize(v: &Vec3) -> Vec3 {
+    v.normalize()
+}
+
+pub fn distance(a: &Vec3, b: &Vec3) -> f32 {
+    backend::distance(a, b)
+}
+
+pub fn distance2(a: &Vec3, b: &Vec3) -> f32 {
+    backend::distance2(a, b)
+}
+
+/// Componentwise absolute value.
+pub fn abs(v: &Vec3) -> Vec3 {
+    backend::abs(v)
+}
+
+/// Componentwise minimum.
+pub fn min2(a: &Vec3, b: &Vec3) -> Vec3 {
+    backend::min2(a, b)
+}
+
+/// Componentwise maximum.
+pub fn max2(a: &Vec3, b: &Vec3) -> Vec3 {
+    backend::max2(a, b)
+}
+
+/// Componentwise clamp.
+pub fn clamp_vec(v: &Vec3, min: &Vec3, max: &Vec3) -> Vec3 {
+    backend::clamp_vec(v, min, max)
+}
+
+pub fn lerp(a: &Vec3, b: &Vec3, t: f32) -> Vec3 {
+    backend::lerp(a, b, t)
+}
+
+pub fn identity() -> Mat4 {
+    Mat4::identity()
+}
+
+pub fn column(m: &Mat4, i: usize) -> Vec4 {
+    m.column(i).into_owned()
+}
+
+pub fn row(m: &Mat4, i: usize) -> Vec4 {
+    m.row(i).transpose()
+}
+
+pub fn set_row(m: &mut Mat4, i: usize, row: &Vec4) {
+    m.set_row(i, &row.transpose());
+}
+
+pub fn dot4(a: &Vec4, b: &Vec4) -> f32 {
+    a.dot(b)
+}
+
+pub fn inverse(m: &Mat4) -> Mat4 {
+    backend::inverse(m)
+}
+
+/// The upper left 3x3 part, holding rotation and scale.
+pub fn mat4_to_mat3(m: &Mat4) -> Mat3 {
+    backend::mat4_to_mat3(m)
+}
+
+pub fn inverse_transpose(m: Mat3) -> Mat3 {
+    backend::inverse_transpose(m)
+}
+
+/// The 16 values in column-major order, as OpenGL expects them.
+pub fn value_ptr(m: &Mat4) -> &[f32] {
+    backend::value_ptr(m)
+}
+
+/// `m` followed by a translation, so the translation applies first.
+pub fn translate(m: &Mat4, v: &Vec3) -> Mat4 {
+    backend::translate(m, v)
+}
+
+/// `m` followed by a rotation of `angle` radians about `axis`.
+#[allow(dead_code)]
+pub fn rotate(m: &Mat4, angle: f32, axis: &Vec3) -> Mat4 {
+    backend::rotate(m, angle, axis)
+}
+
+/// `m` followed by a scale along each axis.
+pub fn scale(m: &Mat4, v: &Vec3) -> Mat4 {
+    backend::scale(m, v)
+}
+
+/// Right-handed view matrix with OpenGL conventions.
+pub fn look_at(eye: &Vec3, center: &Vec3, up: &Vec3) -> Mat4 {
+    backend::look_at(eye, center, up)
+}
+
+/// Right-handed projection onto OpenGL's -1..1 depth range; `fovy` is in radians.
+pub fn perspective(aspect: f32, fovy: f32, near: f32, far: f32) -> Mat4 {
+    backend::perspective(aspect, fovy, near, far)
+}
+
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    backend::ortho(left, right, bottom, top, near, far)
+}
+
+/// Quaternion from its components, `w` being the real part.
+#[allow(dead_code)]
+pub fn quat(x: f32, y: f32, z: f32, w: f32) -> Quat {
+    backend::quat(x, y, z, w)
+}
+
+pub fn quat_identity() -> Quat {
+    backend::quat_identity()
+}
+
+/// Rotation of `angle` radians about the unit vector `axis`.
+pub fn quat_angle_axis(angle: f32, axis: &Vec3) -> Quat {
+    backend::quat_angle_axis(angle, axis)
+}
+
+pub fn quat_axis(q: &Quat) -> Vec3 {
+    backend::quat_axis(q)
+}
+
+/// Rotation angle in radians.
+pub fn quat_angle(q: &Quat) -> f32 {
+    backend::quat_angle(q)
+}
+
+pub fn quat_rotate_vec3(q: &Quat, v: &Vec3) -> Vec3 {
+    backend::quat_rotate_vec3(q, v)
+}
+
+pub fn quat_dot(a: &Quat, b: &Quat) -> f32 {
+    backend::quat_dot(a, b)
+}
+
+pub fn quat_slerp(a: &Quat, b: &Quat, t: f32) -> Quat {
+    backend::quat_slerp(a, b, t)
+}
+
+pub fn quat_to_mat4(q: &Quat) -> Mat4 {
+    backend::quat_to_mat4(q)
+}
+
+pub fn quat_normalize(q: &Quat) -> Quat {
+    backend::quat_normalize(q)
+}
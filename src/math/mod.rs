@@ -1 +1,11 @@
+pub mod bounds;
+pub mod color;
+pub mod easing;
+pub mod frustum;
+pub mod glm;
+pub mod noise;
+pub mod plane;
+pub mod random;
+pub mod ray;
+pub mod spline;
 pub mod utils;
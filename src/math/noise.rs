@@ -0,0 +1,184 @@
+// Gradient noise after Ken Perlin's improved noise, with a permutation table shuffled from a
+// seed so different seeds give unrelated patterns.
+
+use crate::math::easing::lerp;
+use crate::math::random::Rng;
+
+/// Seeded Perlin gradient noise in one to three dimensions. Values lie roughly in -1..1 and are
+/// 0 at integer coordinates; the pattern repeats every 256 units.
+#[derive(Clone)]
+pub struct Noise {
+    /// Permutation of 0..256, stored twice so lookups can skip wrapping
+    permutation: [u8; 512],
+}
+
+/// Fractal Brownian motion: octaves of noise, each at a higher frequency and lower amplitude
+/// than the last, summed for detail at several scales.
+#[derive(Clone, Copy, Debug)]
+pub struct Fbm {
+    pub octaves: u32,
+    /// Frequency multiplier from one octave to the next
+    pub lacunarity: f32,
+    /// Amplitude multiplier from one octave to the next
+    pub gain: f32,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Fbm {
+            octaves: 5,
+            lacunarity: 2.0_f32,
+            gain: 0.5_f32,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Noise {
+    pub fn new(seed: u64) -> Noise {
+        let mut table = [0_u8; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        Rng::new(seed).shuffle(&mut table);
+        let mut permutation = [0_u8; 512];
+        for (i, entry) in permutation.iter_mut().enumerate() {
+            *entry = table[i & 255];
+        }
+        Noise { permutation }
+    }
+
+    pub fn noise1(&self, x: f32) -> f32 {
+        let (xi, xf) = split(x);
+        let u = fade(xf);
+        let a = grad1(self.hash(xi), xf);
+        let b = grad1(self.hash(xi + 1), xf - 1.0_f32);
+        // Peaks at 0.5 for a unit gradient, so scale up to fill -1..1
+        lerp(a, b, u) * 2.0_f32
+    }
+
+    pub fn noise2(&self, x: f32, y: f32) -> f32 {
+        let (xi, xf) = split(x);
+        let (yi, yf) = split(y);
+        let (u, v) = (fade(xf), fade(yf));
+        let corner = |dx: usize, dy: usize| {
+            let hash = self.hash(self.hash(xi + dx) as usize + yi + dy);
+            grad2(hash, xf - dx as f32, yf - dy as f32)
+        };
+        let bottom = lerp(corner(0, 0), corner(1, 0), u);
+        let top = lerp(corner(0, 1), corner(1, 1), u);
+        lerp(bottom, top, v)
+    }
+
+    pub fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (xi, xf) = split(x);
+        let (yi, yf) = split(y);
+        let (zi, zf) = split(z);
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let hash =
+                self.hash(self.hash(self.hash(xi + dx) as usize + yi + dy) as usize + zi + dz);
+            grad3(hash, xf - dx as f32, yf - dy as f32, zf - dz as f32)
+        };
+        let y0 = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        );
+        let y1 = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        );
+        lerp(y0, y1, w)
+    }
+
+    /// Octaves of `noise1` normalized back to roughly -1..1.
+    pub fn fbm1(&self, x: f32, fbm: &Fbm) -> f32 {
+        fbm.sum(|frequency| self.noise1(x * frequency))
+    }
+
+    pub fn fbm2(&self, x: f32, y: f32, fbm: &Fbm) -> f32 {
+        fbm.sum(|frequency| self.noise2(x * frequency, y * frequency))
+    }
+
+    pub fn fbm3(&self, x: f32, y: f32, z: f32, fbm: &Fbm) -> f32 {
+        fbm.sum(|frequency| self.noise3(x * frequency, y * frequency, z * frequency))
+    }
+
+    fn hash(&self, i: usize) -> u8 {
+        self.permutation[i & 511]
+    }
+}
+
+impl Fbm {
+    fn sum(&self, mut octave: impl FnMut(f32) -> f32) -> f32 {
+        let mut total = 0.0_f32;
+        let mut amplitude = 1.0_f32;
+        let mut frequency = 1.0_f32;
+        let mut total_amplitude = 0.0_f32;
+        for _ in 0..self.octaves {
+            total += octave(frequency) * amplitude;
+            total_amplitude += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+        if total_amplitude > 0.0_f32 {
+            total / total_amplitude
+        } else {
+            0.0_f32
+        }
+    }
+}
+
+/// Integer lattice cell, wrapped to the table size, and the position within it.
+fn split(x: f32) -> (usize, f32) {
+    let floor = x.floor();
+    ((floor as i32 & 255) as usize, x - floor)
+}
+
+/// 6t^5 - 15t^4 + 10t^3, flat at both ends so cells join without creases.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0_f32 - 15.0_f32) + 10.0_f32)
+}
+
+fn grad1(hash: u8, x: f32) -> f32 {
+    // Slopes of a quarter to one either way, never zero
+    let gradient = ((hash & 3) + 1) as f32 * 0.25_f32;
+    if hash & 4 == 0 {
+        gradient * x
+    } else {
+        -gradient * x
+    }
+}
+
+fn grad2(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn grad3(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    // The twelve cube edge directions, with four repeated to fill sixteen
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
@@ -0,0 +1,87 @@
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::glm::{self, Mat4, Vec3, Vec4};
+
+/// Points `p` with `normal.dot(p) + distance == 0`. With a unit normal, `distance` is the
+/// plane's signed distance from the origin, negated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+#[allow(dead_code)]
+impl Plane {
+    pub fn new(normal: Vec3, distance: f32) -> Plane {
+        Plane { normal, distance }
+    }
+
+    pub fn from_point_normal(point: &Vec3, normal: &Vec3) -> Plane {
+        Plane {
+            normal: *normal,
+            distance: -glm::dot(normal, point),
+        }
+    }
+
+    /// Plane through three points, facing the side they wind counterclockwise around.
+    pub fn from_points(a: &Vec3, b: &Vec3, c: &Vec3) -> Plane {
+        let normal = glm::normalize(&glm::cross(&(b - a), &(c - a)));
+        Plane::from_point_normal(a, &normal)
+    }
+
+    /// Same plane with a unit normal, so `signed_distance` gives true distances.
+    pub fn normalized(&self) -> Plane {
+        let length = glm::length(&self.normal);
+        Plane {
+            normal: self.normal / length,
+            distance: self.distance / length,
+        }
+    }
+
+    /// Positive on the side the normal points to, scaled by the normal's length.
+    pub fn signed_distance(&self, point: &Vec3) -> f32 {
+        glm::dot(&self.normal, point) + self.distance
+    }
+
+    pub fn project_point(&self, point: &Vec3) -> Vec3 {
+        point - self.normal * (self.signed_distance(point) / glm::length2(&self.normal))
+    }
+
+    /// Mirrors points across the plane; the normal must be unit length.
+    pub fn reflection(&self) -> Mat4 {
+        let n = self.normal;
+        let mut reflection = glm::identity();
+        // p - 2 (n.p + distance) n, a row at a time
+        for i in 0..3 {
+            let mut row = glm::vec4(n.x, n.y, n.z, self.distance) * (-2.0_f32 * n[i]);
+            row[i] += 1.0_f32;
+            glm::set_row(&mut reflection, i, &row);
+        }
+        reflection
+    }
+
+    /// The plane's `normal` and `distance` in the space `to_from_from` maps to, packed for
+    /// shaders and projection matrices.
+    pub fn coefficients_in(&self, to_from_from: &Mat4) -> Vec4 {
+        // Planes map by the inverse transpose, as normals do
+        let from_from_to = glm::inverse(to_from_from);
+        let coefficients = glm::vec4(self.normal.x, self.normal.y, self.normal.z, self.distance);
+        glm::vec4(
+            glm::dot4(&coefficients, &glm::column(&from_from_to, 0)),
+            glm::dot4(&coefficients, &glm::column(&from_from_to, 1)),
+            glm::dot4(&coefficients, &glm::column(&from_from_to, 2)),
+            glm::dot4(&coefficients, &glm::column(&from_from_to, 3)),
+        )
+    }
+
+    /// Whether the sphere touches the plane; the normal must be unit length.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.signed_distance(&sphere.center).abs() <= sphere.radius
+    }
+
+    /// Whether the box straddles the plane.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        // Projected half extent of the box onto the normal
+        let radius = glm::dot(&glm::abs(&self.normal), &aabb.extents());
+        self.signed_distance(&aabb.center()).abs() <= radius
+    }
+}
@@ -0,0 +1,161 @@
+use crate::math::glm::{self, Quat, Vec2, Vec3};
+use std::f32::consts::PI;
+
+// Tries at placing a new point around an existing one before Poisson-disk sampling gives up on it
+const POISSON_DISK_ATTEMPTS: usize = 30;
+
+/// Small seedable random number generator (splitmix64), so generated content comes out the same
+/// on every run and platform for a given seed. Not for anything security related.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+#[allow(dead_code)]
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in 0..1, excluding 1.
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fill an f32 mantissa exactly
+        (self.next_u64() >> 40) as f32 / (1_u64 << 24) as f32
+    }
+
+    /// Uniform in `min..max`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniform in `0..count`, which must not be 0.
+    pub fn index(&mut self, count: usize) -> usize {
+        (self.next_u64() % count as u64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.index(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Uniform over the surface of the unit sphere.
+#[allow(dead_code)]
+pub fn random_unit_vector(rng: &mut Rng) -> Vec3 {
+    let z = rng.range(-1.0_f32, 1.0_f32);
+    let angle = rng.range(0.0_f32, 2.0_f32 * PI);
+    let ring_radius = (1.0_f32 - z * z).sqrt();
+    glm::vec3(ring_radius * angle.cos(), ring_radius * angle.sin(), z)
+}
+
+/// Uniform over the volume of a sphere around the origin.
+#[allow(dead_code)]
+pub fn random_point_in_sphere(rng: &mut Rng, radius: f32) -> Vec3 {
+    // Volume grows with the cube of the radius, so the cube root spreads points evenly
+    random_unit_vector(rng) * (radius * rng.next_f32().cbrt())
+}
+
+/// Uniform over a ring in the XZ plane around the origin, e.g. for an asteroid belt.
+#[allow(dead_code)]
+pub fn random_point_in_ring(rng: &mut Rng, inner_radius: f32, outer_radius: f32) -> Vec3 {
+    let (inner2, outer2) = (inner_radius * inner_radius, outer_radius * outer_radius);
+    let radius = rng.range(inner2, outer2).sqrt();
+    let angle = rng.range(0.0_f32, 2.0_f32 * PI);
+    glm::vec3(radius * angle.cos(), 0.0_f32, radius * angle.sin())
+}
+
+/// Uniformly distributed rotation, after Shoemake's method from three uniform numbers.
+#[allow(dead_code)]
+pub fn random_rotation(rng: &mut Rng) -> Quat {
+    let u = rng.next_f32();
+    let (theta1, theta2) = (
+        rng.range(0.0_f32, 2.0_f32 * PI),
+        rng.range(0.0_f32, 2.0_f32 * PI),
+    );
+    let (r1, r2) = ((1.0_f32 - u).sqrt(), u.sqrt());
+    glm::quat(
+        r1 * theta1.sin(),
+        r1 * theta1.cos(),
+        r2 * theta2.sin(),
+        r2 * theta2.cos(),
+    )
+}
+
+/// Points in the rectangle from the origin to `size` no closer than `min_distance` to each
+/// other, scattered evenly without a grid look (Bridson's algorithm). Good for placing grass
+/// tufts or trees on a plane; map x and y to the plane's axes.
+pub fn poisson_disk_samples(rng: &mut Rng, size: Vec2, min_distance: f32) -> Vec<Vec2> {
+    // Cells small enough to hold at most one point each
+    let cell_size = min_distance / 2.0_f32.sqrt();
+    let columns = (size.x / cell_size).ceil().max(1.0_f32) as usize;
+    let rows = (size.y / cell_size).ceil().max(1.0_f32) as usize;
+    let mut grid: Vec<Option<usize>> = vec![None; columns * rows];
+    let cell_of = |point: &Vec2| {
+        let column = ((point.x / cell_size) as usize).min(columns - 1);
+        let row = ((point.y / cell_size) as usize).min(rows - 1);
+        (column, row)
+    };
+
+    let mut points = vec![];
+    let mut active = vec![];
+    let first = glm::vec2(rng.range(0.0_f32, size.x), rng.range(0.0_f32, size.y));
+    let (column, row) = cell_of(&first);
+    grid[row * columns + column] = Some(points.len());
+    active.push(points.len());
+    points.push(first);
+
+    while !active.is_empty() {
+        let active_i = rng.index(active.len());
+        let center = points[active[active_i]];
+        let mut placed = false;
+        for _ in 0..POISSON_DISK_ATTEMPTS {
+            // Somewhere in the annulus between one and two minimum distances out
+            let angle = rng.range(0.0_f32, 2.0_f32 * PI);
+            let distance = rng.range(min_distance, 2.0_f32 * min_distance);
+            let candidate = center + glm::vec2(angle.cos(), angle.sin()) * distance;
+            if candidate.x < 0.0_f32
+                || candidate.y < 0.0_f32
+                || candidate.x >= size.x
+                || candidate.y >= size.y
+            {
+                continue;
+            }
+            let (column, row) = cell_of(&candidate);
+            // Any point closer than the minimum distance lies within two cells
+            let too_close = (row.saturating_sub(2)..(row + 3).min(rows)).any(|near_row| {
+                (column.saturating_sub(2)..(column + 3).min(columns)).any(|near_column| {
+                    grid[near_row * columns + near_column].is_some_and(|point_i| {
+                        let offset: Vec2 = points[point_i] - candidate;
+                        offset.x * offset.x + offset.y * offset.y < min_distance * min_distance
+                    })
+                })
+            });
+            if !too_close {
+                grid[row * columns + column] = Some(points.len());
+                active.push(points.len());
+                points.push(candidate);
+                placed = true;
+                break;
+            }
+        }
+        if !placed {
+            active.swap_remove(active_i);
+        }
+    }
+    points
+}
@@ -0,0 +1,158 @@
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::math::plane::Plane;
+
+// Below this, a ray counts as parallel to a plane or triangle
+const PARALLEL_EPSILON: f32 = 1e-6;
+
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+/// Where a ray meets a surface. `distance` is in units of the ray's direction, so it is only a
+/// world distance when the direction is normalized.
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+impl Ray {
+    /// Builds the world-space ray through a cursor position given in window coordinates, with
+    /// the origin on the near plane.
+    pub fn from_screen(
+        cursor: (f32, f32),
+        viewport_size: (f32, f32),
+        view_from_world: &Mat4,
+        projection_from_view: &Mat4,
+    ) -> Ray {
+        let ndc_x = 2.0_f32 * cursor.0 / viewport_size.0 - 1.0_f32;
+        let ndc_y = 1.0_f32 - 2.0_f32 * cursor.1 / viewport_size.1;
+        let world_from_projection = glm::inverse(&(projection_from_view * view_from_world));
+        let unproject = |ndc_z: f32| {
+            let world = world_from_projection * glm::vec4(ndc_x, ndc_y, ndc_z, 1.0_f32);
+            glm::xyz(&world) / world.w
+        };
+        let near = unproject(-1.0_f32);
+        let far = unproject(1.0_f32);
+        Ray {
+            origin: near,
+            direction: glm::normalize(&(far - near)),
+        }
+    }
+
+    pub fn transformed(&self, to_from_from: &Mat4) -> Ray {
+        let origin = to_from_from * glm::vec4(self.origin.x, self.origin.y, self.origin.z, 1.0_f32);
+        let direction = to_from_from
+            * glm::vec4(
+                self.direction.x,
+                self.direction.y,
+                self.direction.z,
+                0.0_f32,
+            );
+        Ray {
+            origin: glm::xyz(&origin),
+            direction: glm::xyz(&direction),
+        }
+    }
+
+    pub fn point_at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Slab test; hits at or after the origin, which counts as a hit when inside the box.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<RayHit> {
+        let (min, max) = (&aabb.min, &aabb.max);
+        let mut t_near = f32::NEG_INFINITY;
+        let mut t_far = f32::INFINITY;
+        for axis in 0..3 {
+            if self.direction[axis].abs() < f32::EPSILON {
+                if self.origin[axis] < min[axis] || self.origin[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (min[axis] - self.origin[axis]) / self.direction[axis];
+            let t2 = (max[axis] - self.origin[axis]) / self.direction[axis];
+            t_near = t_near.max(t1.min(t2));
+            t_far = t_far.min(t1.max(t2));
+        }
+        if t_near > t_far || t_far < 0.0_f32 {
+            None
+        } else {
+            Some(self.hit_at(t_near.max(0.0_f32)))
+        }
+    }
+
+    /// Hits at or after the origin, which counts as a hit when inside the sphere.
+    pub fn intersect_sphere(&self, sphere: &BoundingSphere) -> Option<RayHit> {
+        let (center, radius) = (&sphere.center, sphere.radius);
+        let to_origin = self.origin - center;
+        let a = glm::length2(&self.direction);
+        let b = 2.0_f32 * glm::dot(&to_origin, &self.direction);
+        let c = glm::length2(&to_origin) - radius * radius;
+        let discriminant = b * b - 4.0_f32 * a * c;
+        if discriminant < 0.0_f32 {
+            return None;
+        }
+        let sqrt_discriminant = discriminant.sqrt();
+        let t1 = (-b - sqrt_discriminant) / (2.0_f32 * a);
+        let t2 = (-b + sqrt_discriminant) / (2.0_f32 * a);
+        if t2 < 0.0_f32 {
+            None
+        } else {
+            Some(self.hit_at(t1.max(0.0_f32)))
+        }
+    }
+
+    /// Hits the plane from either side.
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<RayHit> {
+        let denominator = glm::dot(&plane.normal, &self.direction);
+        if denominator.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let t = -plane.signed_distance(&self.origin) / denominator;
+        if t < 0.0_f32 {
+            None
+        } else {
+            Some(self.hit_at(t))
+        }
+    }
+
+    /// Möller-Trumbore test against the triangle `a`, `b`, `c`, hit from either side.
+    #[allow(dead_code)]
+    pub fn intersect_triangle(&self, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<RayHit> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = glm::cross(&self.direction, &edge2);
+        let determinant = glm::dot(&edge1, &p);
+        if determinant.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0_f32 / determinant;
+        let to_origin = self.origin - a;
+        let u = glm::dot(&to_origin, &p) * inverse_determinant;
+        if !(0.0_f32..=1.0_f32).contains(&u) {
+            return None;
+        }
+        let q = glm::cross(&to_origin, &edge1);
+        let v = glm::dot(&self.direction, &q) * inverse_determinant;
+        if v < 0.0_f32 || u + v > 1.0_f32 {
+            return None;
+        }
+        let t = glm::dot(&edge2, &q) * inverse_determinant;
+        if t < 0.0_f32 {
+            None
+        } else {
+            Some(self.hit_at(t))
+        }
+    }
+
+    fn hit_at(&self, distance: f32) -> RayHit {
+        RayHit {
+            distance,
+            point: self.point_at(distance),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::math::glm::{self, Vec3};
+
+// Straight pieces per curve segment in the arc-length table
+const ARC_LENGTH_SAMPLES_PER_SEGMENT: usize = 32;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SplineKind {
+    /// Passes through every control point
+    CatmullRom,
+    /// Cubic segments sharing end points, with two handles between each pair of them
+    Bezier,
+}
+
+/// Piecewise cubic curve through `Vec3` control points. Besides the raw parameter `t`, which
+/// moves at uneven speed along the curve, points can be looked up by distance travelled.
+#[derive(Clone, Debug)]
+pub struct Spline {
+    kind: SplineKind,
+    points: Vec<Vec3>,
+    /// Curve length from the start at each of `ARC_LENGTH_SAMPLES_PER_SEGMENT` steps of `t`
+    /// per segment, starting with 0
+    arc_lengths: Vec<f32>,
+}
+
+/// Uniform Catmull-Rom segment from `p1` to `p2`, shaped by their neighbors `p0` and `p3`.
+pub fn catmull_rom(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0_f32
+        + (p2 - p0) * t
+        + (p0 * 2.0_f32 - p1 * 5.0_f32 + p2 * 4.0_f32 - p3) * t2
+        + (-p0 + p1 * 3.0_f32 - p2 * 3.0_f32 + p3) * t3)
+        * 0.5_f32
+}
+
+pub fn catmull_rom_tangent(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    ((p2 - p0)
+        + (p0 * 2.0_f32 - p1 * 5.0_f32 + p2 * 4.0_f32 - p3) * (2.0_f32 * t)
+        + (-p0 + p1 * 3.0_f32 - p2 * 3.0_f32 + p3) * (3.0_f32 * t * t))
+        * 0.5_f32
+}
+
+/// Cubic Bézier from `p0` to `p3` with handles `p1` and `p2`.
+pub fn cubic_bezier(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    let u = 1.0_f32 - t;
+    p0 * (u * u * u) + p1 * (3.0_f32 * u * u * t) + p2 * (3.0_f32 * u * t * t) + p3 * (t * t * t)
+}
+
+pub fn cubic_bezier_tangent(p0: &Vec3, p1: &Vec3, p2: &Vec3, p3: &Vec3, t: f32) -> Vec3 {
+    let u = 1.0_f32 - t;
+    (p1 - p0) * (3.0_f32 * u * u) + (p2 - p1) * (6.0_f32 * u * t) + (p3 - p2) * (3.0_f32 * t * t)
+}
+
+#[allow(dead_code)]
+impl Spline {
+    /// Curve through `points`, of which there must be at least two. The end segments treat
+    /// the missing outer neighbor as the end point itself.
+    pub fn catmull_rom(points: Vec<Vec3>) -> Result<Spline, String> {
+        if points.len() < 2 {
+            return Err(format!(
+                "A Catmull-Rom spline needs at least 2 points, got {}",
+                points.len()
+            ));
+        }
+        Ok(Spline::new(SplineKind::CatmullRom, points))
+    }
+
+    /// Curve from `points` laid out as start, handle, handle, end, handle, handle, end, ..., so
+    /// there must be 3n + 1 of them for n segments.
+    pub fn bezier(points: Vec<Vec3>) -> Result<Spline, String> {
+        if points.len() < 4 || !(points.len() - 1).is_multiple_of(3) {
+            return Err(format!(
+                "A Bézier spline needs 3n + 1 points with n at least 1, got {}",
+                points.len()
+            ));
+        }
+        Ok(Spline::new(SplineKind::Bezier, points))
+    }
+
+    fn new(kind: SplineKind, points: Vec<Vec3>) -> Spline {
+        let mut spline = Spline {
+            kind,
+            points,
+            arc_lengths: vec![],
+        };
+        let sample_count = spline.segment_count() * ARC_LENGTH_SAMPLES_PER_SEGMENT;
+        let mut length = 0.0_f32;
+        let mut previous = spline.point(0.0_f32);
+        spline.arc_lengths.push(length);
+        for sample_i in 1..=sample_count {
+            let point = spline.point(sample_i as f32 / sample_count as f32);
+            length += glm::distance(&previous, &point);
+            spline.arc_lengths.push(length);
+            previous = point;
+        }
+        spline
+    }
+
+    pub fn kind(&self) -> SplineKind {
+        self.kind
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn segment_count(&self) -> usize {
+        match self.kind {
+            SplineKind::CatmullRom => self.points.len() - 1,
+            SplineKind::Bezier => (self.points.len() - 1) / 3,
+        }
+    }
+
+    /// Point at `t` from 0 at the start to 1 at the end, with each segment taking an equal
+    /// share of `t` however long it is.
+    pub fn point(&self, t: f32) -> Vec3 {
+        let (segment_i, local_t) = self.segment_at(t);
+        let [p0, p1, p2, p3] = self.segment_points(segment_i);
+        match self.kind {
+            SplineKind::CatmullRom => catmull_rom(&p0, &p1, &p2, &p3, local_t),
+            SplineKind::Bezier => cubic_bezier(&p0, &p1, &p2, &p3, local_t),
+        }
+    }
+
+    /// Derivative of `point` with respect to `t`, not normalized.
+    pub fn tangent(&self, t: f32) -> Vec3 {
+        let (segment_i, local_t) = self.segment_at(t);
+        let [p0, p1, p2, p3] = self.segment_points(segment_i);
+        let tangent = match self.kind {
+            SplineKind::CatmullRom => catmull_rom_tangent(&p0, &p1, &p2, &p3, local_t),
+            SplineKind::Bezier => cubic_bezier_tangent(&p0, &p1, &p2, &p3, local_t),
+        };
+        tangent * self.segment_count() as f32
+    }
+
+    /// Approximate length of the whole curve.
+    pub fn length(&self) -> f32 {
+        *self.arc_lengths.last().unwrap()
+    }
+
+    /// The `t` reached after travelling `distance` along the curve, clamped to its ends.
+    pub fn t_at_distance(&self, distance: f32) -> f32 {
+        let sample_count = self.arc_lengths.len() - 1;
+        let distance = distance.clamp(0.0_f32, self.length());
+        // First sample at or past the distance, then interpolate within the piece before it
+        let after_i = self
+            .arc_lengths
+            .partition_point(|&length| length < distance)
+            .clamp(1, sample_count);
+        let before = self.arc_lengths[after_i - 1];
+        let piece_length = self.arc_lengths[after_i] - before;
+        let piece_t = if piece_length > 0.0_f32 {
+            (distance - before) / piece_length
+        } else {
+            0.0_f32
+        };
+        (after_i as f32 - 1.0_f32 + piece_t) / sample_count as f32
+    }
+
+    /// Point `distance` along the curve, so equal steps in distance give even motion.
+    pub fn point_at_distance(&self, distance: f32) -> Vec3 {
+        self.point(self.t_at_distance(distance))
+    }
+
+    /// `count` points evenly spaced by distance from the start of the curve to its end, for
+    /// laying objects out along it.
+    pub fn evenly_spaced_points(&self, count: usize) -> Vec<Vec3> {
+        match count {
+            0 => vec![],
+            1 => vec![self.point(0.0_f32)],
+            _ => {
+                let step = self.length() / (count - 1) as f32;
+                (0..count)
+                    .map(|point_i| self.point_at_distance(point_i as f32 * step))
+                    .collect()
+            }
+        }
+    }
+
+    /// Which segment `t` falls in and how far along it.
+    fn segment_at(&self, t: f32) -> (usize, f32) {
+        let segment_count = self.segment_count();
+        let scaled = t.clamp(0.0_f32, 1.0_f32) * segment_count as f32;
+        let segment_i = (scaled as usize).min(segment_count - 1);
+        (segment_i, scaled - segment_i as f32)
+    }
+
+    fn segment_points(&self, segment_i: usize) -> [Vec3; 4] {
+        match self.kind {
+            SplineKind::CatmullRom => {
+                let last_i = self.points.len() - 1;
+                let point = |i: isize| self.points[(i.max(0) as usize).min(last_i)];
+                let i = segment_i as isize;
+                [point(i - 1), point(i), point(i + 1), point(i + 2)]
+            }
+            SplineKind::Bezier => {
+                let first_i = segment_i * 3;
+                [
+                    self.points[first_i],
+                    self.points[first_i + 1],
+                    self.points[first_i + 2],
+                    self.points[first_i + 3],
+                ]
+            }
+        }
+    }
+}
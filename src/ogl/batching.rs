@@ -0,0 +1,162 @@
+use gl::types::*;
+
+use crate::math::bounds::Aabb;
+use crate::math::glm::{self, Mat3, Mat4};
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{Material, Mesh, MeshData};
+use crate::ogl::mesh_pool::MeshPool;
+use std::rc::Rc;
+
+/// Index of a batch's mesh in a `MeshPool`, its material and world-space bounds.
+pub type PooledBatch = (usize, Rc<Material>, Option<Aabb>);
+
+/// Merges static meshes that share a material into a single indexed mesh at scene build time,
+/// baking each object's transform into its vertices.
+pub struct StaticBatcher {
+    position_location: GLuint,
+    normal_location: Option<GLuint>,
+    batches: Vec<Batch>,
+}
+
+struct Batch {
+    material: Rc<Material>,
+    data: MeshData,
+    bounds: Option<Aabb>,
+}
+
+impl StaticBatcher {
+    pub fn new() -> StaticBatcher {
+        StaticBatcher {
+            position_location: 0,
+            normal_location: None,
+            batches: vec![],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_position_location(mut self, location: GLuint) -> StaticBatcher {
+        self.position_location = location;
+        self
+    }
+
+    pub fn with_normal_location(mut self, location: GLuint) -> StaticBatcher {
+        self.normal_location = Some(location);
+        self
+    }
+
+    pub fn add(
+        &mut self,
+        geometry: &MeshData,
+        material: &Rc<Material>,
+        world_from_object: &Mat4,
+    ) -> Result<(), String> {
+        let position_offset = geometry
+            .attribute_offset(self.position_location)
+            .ok_or_else(|| {
+                format!(
+                    "Batched geometry has no position attribute at location {}",
+                    self.position_location
+                )
+            })?;
+        let normal_offset = self
+            .normal_location
+            .and_then(|location| geometry.attribute_offset(location));
+
+        let batch_i = match self
+            .batches
+            .iter()
+            .position(|batch| batch.material.id == material.id)
+        {
+            Some(batch_i) => batch_i,
+            None => {
+                self.batches.push(Batch {
+                    material: Rc::clone(material),
+                    data: MeshData {
+                        vertices: vec![],
+                        indices: Some(vec![]),
+                        attributes: geometry.attributes.clone(),
+                    },
+                    bounds: None,
+                });
+                self.batches.len() - 1
+            }
+        };
+        let batch = &mut self.batches[batch_i];
+        let batch_data = &mut batch.data;
+        if batch_data.attributes != geometry.attributes {
+            return Err(format!(
+                "Batched geometry layout {:?} does not match batch layout {:?}",
+                geometry.attributes, batch_data.attributes
+            ));
+        }
+
+        let floats_per_vertex = geometry.floats_per_vertex();
+        let base_vertex = batch_data.vertex_count() as u32;
+        let normal_from_object: Mat3 = glm::inverse_transpose(glm::mat4_to_mat3(world_from_object));
+        for vertex in geometry.vertices.chunks(floats_per_vertex) {
+            let mut vertex = vertex.to_vec();
+            let position = world_from_object
+                * glm::vec4(
+                    vertex[position_offset],
+                    vertex[position_offset + 1],
+                    vertex[position_offset + 2],
+                    1.0_f32,
+                );
+            let position = glm::xyz(&position);
+            vertex[position_offset..position_offset + 3]
+                .copy_from_slice(&[position.x, position.y, position.z]);
+            batch.bounds = Some(match &batch.bounds {
+                Some(bounds) => bounds.merged_point(&position),
+                None => Aabb::new(position, position),
+            });
+            if let Some(normal_offset) = normal_offset {
+                let normal = glm::normalize(
+                    &(normal_from_object
+                        * glm::vec3(
+                            vertex[normal_offset],
+                            vertex[normal_offset + 1],
+                            vertex[normal_offset + 2],
+                        )),
+                );
+                vertex[normal_offset..normal_offset + 3]
+                    .copy_from_slice(&[normal.x, normal.y, normal.z]);
+            }
+            batch_data.vertices.extend_from_slice(&vertex);
+        }
+
+        let batch_indices = batch_data.indices.get_or_insert_with(Vec::new);
+        match &geometry.indices {
+            Some(indices) => {
+                batch_indices.extend(indices.iter().map(|index| base_vertex + index));
+            }
+            None => {
+                batch_indices
+                    .extend((0..geometry.vertex_count() as u32).map(|index| base_vertex + index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `build`, but adds the batch geometry to `pool` instead of uploading it, giving the
+    /// index of each batch's mesh in the pool.
+    pub fn build_into(self, pool: &mut MeshPool) -> Result<Vec<PooledBatch>, String> {
+        self.batches
+            .into_iter()
+            .map(|batch| Ok((pool.add(&batch.data)?, batch.material, batch.bounds)))
+            .collect()
+    }
+
+    /// One mesh per material, with the world-space bounds of its vertices when it has any.
+    #[allow(dead_code)]
+    pub fn build(self, context: &GlContext) -> Vec<(Rc<Mesh>, Rc<Material>, Option<Aabb>)> {
+        self.batches
+            .into_iter()
+            .enumerate()
+            .map(|(batch_i, batch)| {
+                let mesh = Mesh::from_data(context, &batch.data);
+                mesh.set_label(&format!("static batch {}", batch_i));
+                (Rc::new(mesh), batch.material, batch.bounds)
+            })
+            .collect()
+    }
+}
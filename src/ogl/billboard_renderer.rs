@@ -0,0 +1,393 @@
+use gl::types::*;
+
+use crate::flipbook::UvRect;
+use crate::math::color::Color;
+use crate::math::easing::smoothstep;
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::buffer::Buffer;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::stats::FrameStats;
+use crate::particles::BlendMode;
+use std::ffi::c_void;
+use std::mem;
+
+// Floats per billboard: center, size, rotation in radians, sRGB color with alpha, then the
+// texture coordinates' offset and size
+const BILLBOARD_FLOATS: usize = 13;
+// Width and height of the marker texture
+const MARKER_TEXTURE_SIZE: usize = 64;
+// Frames across and down the puff sheet, and pixels across each
+pub const PUFF_SHEET_COLUMNS: u32 = 4;
+pub const PUFF_SHEET_ROWS: u32 = 4;
+const PUFF_FRAME_SIZE: usize = 64;
+
+const BILLBOARD_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_center;
+layout (location = 1) in float a_size;
+layout (location = 2) in float a_rotation;
+layout (location = 3) in vec4 a_color;
+layout (location = 4) in vec4 a_uv_rect;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+out vec2 o_corner;
+out vec2 o_tex_coords;
+out vec4 o_color;
+
+void main() {
+    // Four vertices per billboard, drawn as a strip: (-1, -1), (1, -1), (-1, 1), (1, 1)
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1)) * 2.0f - 1.0f;
+    float c = cos(a_rotation);
+    float s = sin(a_rotation);
+    vec2 offset = vec2(c * corner.x - s * corner.y, s * corner.x + c * corner.y) * a_size * 0.5f;
+    // Offsetting the corners in view space keeps the quad facing the camera
+    vec4 view_center = view_from_world * vec4(a_center, 1.0f);
+    gl_Position = projection_from_view * (view_center + vec4(offset, 0.0f, 0.0f));
+    o_corner = corner;
+    o_tex_coords = a_uv_rect.xy + (corner * 0.5f + 0.5f) * a_uv_rect.zw;
+    o_color = a_color;
+}
+"#;
+
+const BILLBOARD_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_corner;
+in vec2 o_tex_coords;
+in vec4 o_color;
+
+uniform sampler2D sprite;
+uniform bool textured;
+
+out vec4 frag_color;
+
+void main() {
+    if (textured) {
+        frag_color = texture(sprite, o_tex_coords) * o_color;
+    } else {
+        // A soft disc, so particles need no texture
+        float coverage = 1.0f - smoothstep(0.0f, 1.0f, length(o_corner));
+        frag_color = vec4(o_color.rgb, o_color.a * coverage);
+    }
+}
+"#;
+
+/// A camera-facing quad, `size` units across, turned `rotation_radians` in the view plane.
+#[derive(Clone, Copy, Debug)]
+pub struct Billboard {
+    pub position: Vec3,
+    pub size: f32,
+    pub rotation_radians: f32,
+    /// Tints the texture, or colors the soft disc drawn without one
+    pub color: Color,
+    /// Part of the texture shown, such as a flipbook frame
+    pub uv_rect: UvRect,
+}
+
+/// Draws billboards as instanced quads, one instance each, streamed into a buffer every draw.
+/// Billboards test against the scene's depth without writing it, so draw them after the opaque
+/// scene.
+pub struct BillboardRenderer {
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    instance_buffer: Buffer,
+    instance_data: Vec<f32>,
+    /// Billboard indices, sorted back to front for alpha blending
+    draw_order: Vec<(f32, usize)>,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    textured_uniform: Uniform,
+    context: GlContext,
+}
+
+impl BillboardRenderer {
+    pub fn new(context: &GlContext) -> Result<BillboardRenderer, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                BILLBOARD_VERTEX_SHADER_SOURCE,
+                BILLBOARD_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("billboards");
+            let instance_buffer = Buffer::new(context, gl::STREAM_DRAW);
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, instance_buffer.id));
+            let stride = (BILLBOARD_FLOATS * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_center, a_size, a_rotation, a_color and a_uv_rect attributes
+            let mut offset = 0;
+            for (location, components) in [(0, 3), (1, 1), (2, 1), (3, 4), (4, 4)].iter() {
+                gl_check!(gl::VertexAttribPointer(
+                    *location,
+                    *components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (offset * mem::size_of::<GLfloat>()) as *const c_void,
+                ));
+                gl_check!(gl::EnableVertexAttribArray(*location));
+                gl_check!(gl::VertexAttribDivisor(*location, 1));
+                offset += *components as usize;
+            }
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "billboards");
+            instance_buffer.set_label(context, "billboard instances");
+
+            shader_program.use_program();
+            shader_program.uniform("sprite").set_int(0);
+            let view_from_world_uniform = shader_program.uniform("view_from_world");
+            let projection_from_view_uniform = shader_program.uniform("projection_from_view");
+            let textured_uniform = shader_program.uniform("textured");
+            Ok(BillboardRenderer {
+                shader_program,
+                vertex_array_obj,
+                instance_buffer,
+                instance_data: vec![],
+                draw_order: vec![],
+                view_from_world_uniform,
+                projection_from_view_uniform,
+                textured_uniform,
+                context: *context,
+            })
+        }
+    }
+
+    /// Draws `billboards` seen from `camera` with one instanced draw call, textured with
+    /// `texture` or as soft discs without one. Alpha blended billboards are drawn farthest first.
+    pub fn draw(
+        &mut self,
+        billboards: &[Billboard],
+        texture: Option<GLuint>,
+        blend: BlendMode,
+        camera: &Camera,
+        projection_from_view: &Mat4,
+        stats: &mut FrameStats,
+    ) {
+        if billboards.is_empty() {
+            return;
+        }
+        self.fill_instance_data(billboards, blend, &camera.position);
+        self.shader_program.use_program();
+        self.view_from_world_uniform
+            .set_mat4f(&camera.view_matrix());
+        self.projection_from_view_uniform
+            .set_mat4f(projection_from_view);
+        self.textured_uniform.set_bool(texture.is_some());
+        unsafe {
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::DepthMask(gl::FALSE));
+            match blend {
+                BlendMode::Additive => gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE)),
+                BlendMode::Alpha => {
+                    gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA))
+                }
+            }
+            if let Some(texture) = texture {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+            }
+            self.instance_buffer
+                .set_data(&self.context, &self.instance_data);
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::DrawArraysInstanced(
+                gl::TRIANGLE_STRIP,
+                0,
+                4,
+                billboards.len() as GLsizei
+            ));
+            gl_check!(gl::BindVertexArray(0));
+            if texture.is_some() {
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            }
+            gl_check!(gl::DepthMask(gl::TRUE));
+            gl_check!(gl::Disable(gl::BLEND));
+        }
+        stats.record_draw(2 * billboards.len() as u64);
+    }
+
+    /// Fills `instance_data` with `billboards`, farthest first when they are alpha blended.
+    fn fill_instance_data(
+        &mut self,
+        billboards: &[Billboard],
+        blend: BlendMode,
+        camera_position: &Vec3,
+    ) {
+        self.draw_order.clear();
+        self.draw_order.extend(
+            billboards
+                .iter()
+                .enumerate()
+                .map(|(billboard_i, billboard)| {
+                    (
+                        glm::distance2(&billboard.position, camera_position),
+                        billboard_i,
+                    )
+                }),
+        );
+        if blend == BlendMode::Alpha {
+            self.draw_order
+                .sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+        }
+        self.instance_data.clear();
+        for &(_, billboard_i) in self.draw_order.iter() {
+            let billboard = &billboards[billboard_i];
+            let [red, green, blue, alpha] = billboard.color.to_srgb();
+            let uv_rect = &billboard.uv_rect;
+            self.instance_data.extend_from_slice(&[
+                billboard.position.x,
+                billboard.position.y,
+                billboard.position.z,
+                billboard.size,
+                billboard.rotation_radians,
+                red,
+                green,
+                blue,
+                alpha,
+                uv_rect.offset[0],
+                uv_rect.offset[1],
+                uv_rect.size[0],
+                uv_rect.size[1],
+            ]);
+        }
+    }
+}
+
+impl Drop for BillboardRenderer {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::VertexArray, self.vertex_array_obj);
+    }
+}
+
+/// White ring around a dot, for marking points of interest such as emitters and lights in the
+/// scene; tint it through the billboard color.
+pub fn create_marker_texture(_context: &GlContext) -> GLuint {
+    unsafe {
+        let size = MARKER_TEXTURE_SIZE as f32;
+        let texels: Vec<[u8; 4]> = (0..MARKER_TEXTURE_SIZE * MARKER_TEXTURE_SIZE)
+            .map(|i| {
+                let x = ((i % MARKER_TEXTURE_SIZE) as f32 + 0.5_f32) / size * 2.0_f32 - 1.0_f32;
+                let y = ((i / MARKER_TEXTURE_SIZE) as f32 + 0.5_f32) / size * 2.0_f32 - 1.0_f32;
+                let radius = (x * x + y * y).sqrt();
+                // Edges a texel wide, so the marker stays smooth when filtered
+                let edge = 2.0_f32 / size;
+                let coverage = |distance: f32| (0.5_f32 - distance / edge).clamp(0.0_f32, 1.0_f32);
+                let ring = coverage((radius - 0.8_f32).abs() - 0.1_f32);
+                let dot = coverage(radius - 0.25_f32);
+                [255, 255, 255, (ring.max(dot) * 255.0_f32) as u8]
+            })
+            .collect();
+        upload_texture(
+            &texels,
+            MARKER_TEXTURE_SIZE,
+            MARKER_TEXTURE_SIZE,
+            true,
+            "billboard marker",
+        )
+    }
+}
+
+/// A puff of smoke billowing out and thinning away over the frames of a
+/// `PUFF_SHEET_COLUMNS` by `PUFF_SHEET_ROWS` sprite sheet, white so the billboard color tints it.
+/// Frames are not mipmapped, so filtering never bleeds one into the next.
+pub fn create_puff_sheet_texture(_context: &GlContext) -> GLuint {
+    unsafe {
+        let columns = PUFF_SHEET_COLUMNS as usize;
+        let rows = PUFF_SHEET_ROWS as usize;
+        let (width, height) = (columns * PUFF_FRAME_SIZE, rows * PUFF_FRAME_SIZE);
+        let size = PUFF_FRAME_SIZE as f32;
+        let texels: Vec<[u8; 4]> = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                // Texture rows go up, while frames are read from the top row of the sheet down
+                let frame = (rows - 1 - y / PUFF_FRAME_SIZE) * columns + x / PUFF_FRAME_SIZE;
+                let t = frame as f32 / (columns * rows - 1) as f32;
+                let u = ((x % PUFF_FRAME_SIZE) as f32 + 0.5_f32) / size * 2.0_f32 - 1.0_f32;
+                let v = ((y % PUFF_FRAME_SIZE) as f32 + 0.5_f32) / size * 2.0_f32 - 1.0_f32;
+                // A lumpy outline whose lumps roll around as it grows
+                let angle = v.atan2(u);
+                let lumps = 1.0_f32
+                    + 0.12_f32 * (5.0_f32 * angle + 4.0_f32 * t).sin()
+                    + 0.08_f32 * (9.0_f32 * angle - 3.0_f32 * t + 1.0_f32).sin();
+                let radius = (0.4_f32 + 0.45_f32 * t) * lumps;
+                let distance = (u * u + v * v).sqrt() / radius;
+                let edge = 1.0_f32
+                    - smoothstep(((distance - 0.45_f32) / 0.55_f32).clamp(0.0_f32, 1.0_f32));
+                // Thins from the middle out as it spreads
+                let thinning = 1.0_f32 - 0.6_f32 * t * (1.0_f32 - distance).max(0.0_f32);
+                let density = edge * thinning * (1.0_f32 - 0.5_f32 * t);
+                [
+                    255,
+                    255,
+                    255,
+                    (density.clamp(0.0_f32, 1.0_f32) * 255.0_f32) as u8,
+                ]
+            })
+            .collect();
+        upload_texture(&texels, width, height, false, "billboard puff sheet")
+    }
+}
+
+// Uploads `texels`, rows bottom first, as a clamped, linearly filtered RGBA texture
+unsafe fn upload_texture(
+    texels: &[[u8; 4]],
+    width: usize,
+    height: usize,
+    mipmapped: bool,
+    label: &str,
+) -> GLuint {
+    let mut texture = 0;
+    gl_check!(gl::GenTextures(1, &mut texture));
+    live_objects::created(GlObject::Texture, texture);
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+    gl_check!(gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA8 as GLint,
+        width as GLsizei,
+        height as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        texels.as_ptr() as *const c_void,
+    ));
+    for (parameter, value) in [
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+        (
+            gl::TEXTURE_MIN_FILTER,
+            if mipmapped {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
+            },
+        ),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    if mipmapped {
+        gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D));
+    }
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        texture,
+        gpu_memory::texture_bytes(width as u32, height as u32, 1, 4, mipmapped),
+    );
+    debug_label::set(gl::TEXTURE, texture, label);
+    texture
+}
@@ -0,0 +1,40 @@
+use gl::types::*;
+
+use std::ffi::c_void;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// ARB_bindless_texture entry points. The gl crate only generates core profile functions, so
+// these are looked up separately and stay null where the driver lacks the extension.
+static GET_TEXTURE_HANDLE: AtomicUsize = AtomicUsize::new(0);
+static MAKE_TEXTURE_HANDLE_RESIDENT: AtomicUsize = AtomicUsize::new(0);
+
+type GetTextureHandleFn = unsafe extern "system" fn(GLuint) -> GLuint64;
+type MakeTextureHandleResidentFn = unsafe extern "system" fn(GLuint64);
+
+/// Looks up the extension functions, like `gl::load_with` does for the core ones.
+pub fn load_with<F: FnMut(&'static str) -> *const c_void>(mut loader: F) {
+    GET_TEXTURE_HANDLE.store(loader("glGetTextureHandleARB") as usize, Ordering::Relaxed);
+    MAKE_TEXTURE_HANDLE_RESIDENT.store(
+        loader("glMakeTextureHandleResidentARB") as usize,
+        Ordering::Relaxed,
+    );
+}
+
+pub fn is_loaded() -> bool {
+    GET_TEXTURE_HANDLE.load(Ordering::Relaxed) != 0
+        && MAKE_TEXTURE_HANDLE_RESIDENT.load(Ordering::Relaxed) != 0
+}
+
+/// Bindless handle of `texture`, which freezes the texture's storage and sampling state.
+pub unsafe fn texture_handle(texture: GLuint) -> GLuint64 {
+    let function: GetTextureHandleFn = mem::transmute(GET_TEXTURE_HANDLE.load(Ordering::Relaxed));
+    gl_check!(function(texture))
+}
+
+/// Lets shaders sample through `handle`; it stays resident for the rest of the run.
+pub unsafe fn make_resident(handle: GLuint64) {
+    let function: MakeTextureHandleResidentFn =
+        mem::transmute(MAKE_TEXTURE_HANDLE_RESIDENT.load(Ordering::Relaxed));
+    gl_check!(function(handle))
+}
@@ -0,0 +1,256 @@
+use gl::types::*;
+
+#[cfg(feature = "gl45")]
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::{mem, ptr};
+
+/// GL buffer whose contents can be replaced, updated in ranges, or written through a mapping, so
+/// data that changes in places is not uploaded whole. Edits go through the `COPY_WRITE_BUFFER`
+/// target, which any buffer can be bound to without disturbing vertex array state.
+pub struct Buffer {
+    pub id: GLuint,
+    size: usize,
+    /// `STATIC_DRAW`, `DYNAMIC_DRAW` or `STREAM_DRAW`, passed on every respecification
+    usage: GLenum,
+    context: GlContext,
+}
+
+/// Range of a `Buffer` mapped for writing with explicit flushes. Writes reach the buffer once
+/// flushed; `unmap` ends the mapping.
+pub struct MappedRange<'a> {
+    buffer_id: GLuint,
+    mapped: *mut u8,
+    length: usize,
+    buffer: PhantomData<&'a mut Buffer>,
+}
+
+impl Buffer {
+    pub fn new(context: &GlContext, usage: GLenum) -> Buffer {
+        unsafe {
+            let mut id = 0;
+            gl_check!(gl::GenBuffers(1, &mut id));
+            live_objects::created(GlObject::Buffer, id);
+            Buffer {
+                id,
+                size: 0,
+                usage,
+                context: *context,
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Names the buffer for debuggers, see `debug_label::set`.
+    pub fn set_label(&self, _context: &GlContext, label: &str) {
+        unsafe {
+            // A generated name is only a buffer once bound
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+            debug_label::set(gl::BUFFER, self.id, label);
+        }
+    }
+
+    /// Replaces the whole contents, resizing the buffer to fit `data`. Respecifying storage
+    /// orphans the old contents, so draws still reading them do not hold this up.
+    pub fn set_data<T: Copy>(&mut self, _context: &GlContext, data: &[T]) {
+        unsafe {
+            self.size = mem::size_of_val(data);
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id));
+            gl_check!(gl::BufferData(
+                gl::COPY_WRITE_BUFFER,
+                self.size as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                self.usage,
+            ));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+            gpu_memory::track(GpuResource::Buffer, self.id, self.size);
+        }
+    }
+
+    /// Overwrites the bytes from `offset` on with `data`, leaving the rest of the buffer as it is.
+    pub fn update<T: Copy>(
+        &mut self,
+        _context: &GlContext,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), String> {
+        unsafe {
+            let length = mem::size_of_val(data);
+            self.check_range(offset, length)?;
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id));
+            gl_check!(gl::BufferSubData(
+                gl::COPY_WRITE_BUFFER,
+                offset as GLintptr,
+                length as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+            ));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+            Ok(())
+        }
+    }
+
+    /// Hints that the contents are no longer needed, so the next writes need not wait for draws
+    /// still reading them. Uses `glInvalidateBufferData` where OpenGL 4.3 allows, and orphans
+    /// the storage otherwise.
+    #[allow(dead_code)]
+    pub fn invalidate(&mut self, _context: &GlContext) {
+        unsafe {
+            #[cfg(feature = "gl45")]
+            {
+                if capabilities::invalidate_subdata() {
+                    gl_check!(gl::InvalidateBufferData(self.id));
+                    return;
+                }
+            }
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id));
+            gl_check!(gl::BufferData(
+                gl::COPY_WRITE_BUFFER,
+                self.size as GLsizeiptr,
+                ptr::null(),
+                self.usage,
+            ));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+        }
+    }
+
+    /// Hints that `length` bytes from `offset` on are about to be rewritten. Without OpenGL 4.3
+    /// there is nothing to tell the driver; `map_range` with `invalidate` covers that case.
+    #[allow(dead_code)]
+    #[cfg_attr(not(feature = "gl45"), allow(unused_variables))]
+    pub fn invalidate_range(
+        &mut self,
+        _context: &GlContext,
+        offset: usize,
+        length: usize,
+    ) -> Result<(), String> {
+        self.check_range(offset, length)?;
+        #[cfg(feature = "gl45")]
+        {
+            if capabilities::invalidate_subdata() {
+                unsafe {
+                    gl_check!(gl::InvalidateBufferSubData(
+                        self.id,
+                        offset as GLintptr,
+                        length as GLsizeiptr
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps `length` bytes from `offset` on for writing. With `invalidate` the range's old
+    /// contents are discarded instead of read back or waited on. Nothing but the buffer's own
+    /// edits may touch it while the range is mapped.
+    #[allow(dead_code)]
+    pub fn map_range(
+        &mut self,
+        _context: &GlContext,
+        offset: usize,
+        length: usize,
+        invalidate: bool,
+    ) -> Result<MappedRange<'_>, String> {
+        unsafe {
+            self.check_range(offset, length)?;
+            let mut access = gl::MAP_WRITE_BIT | gl::MAP_FLUSH_EXPLICIT_BIT;
+            if invalidate {
+                access |= gl::MAP_INVALIDATE_RANGE_BIT;
+            }
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id));
+            let mapped = gl_check!(gl::MapBufferRange(
+                gl::COPY_WRITE_BUFFER,
+                offset as GLintptr,
+                length as GLsizeiptr,
+                access
+            ));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+            if mapped.is_null() {
+                return Err(format!(
+                    "Failed mapping {} bytes at {} of buffer {}",
+                    length, offset, self.id
+                ));
+            }
+            Ok(MappedRange {
+                buffer_id: self.id,
+                mapped: mapped as *mut u8,
+                length,
+                buffer: PhantomData,
+            })
+        }
+    }
+
+    fn check_range(&self, offset: usize, length: usize) -> Result<(), String> {
+        if offset + length > self.size {
+            return Err(format!(
+                "Range of {} bytes at {} is past the end of a {} byte buffer",
+                length, offset, self.size
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Buffer, self.id);
+    }
+}
+
+#[allow(dead_code)]
+impl MappedRange<'_> {
+    /// Copies `data` to `offset` bytes into the range. It reaches the buffer once flushed.
+    pub fn write<T: Copy>(&mut self, offset: usize, data: &[T]) -> Result<(), String> {
+        unsafe {
+            let length = mem::size_of_val(data);
+            if offset + length > self.length {
+                return Err(format!(
+                    "Write of {} bytes at {} is past the end of a {} byte mapping",
+                    length, offset, self.length
+                ));
+            }
+            ptr::copy_nonoverlapping(data.as_ptr() as *const u8, self.mapped.add(offset), length);
+            Ok(())
+        }
+    }
+
+    /// Makes `length` written bytes from `offset` on, relative to the range, visible to GL.
+    pub fn flush(&self, _context: &GlContext, offset: usize, length: usize) {
+        unsafe {
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.buffer_id));
+            gl_check!(gl::FlushMappedBufferRange(
+                gl::COPY_WRITE_BUFFER,
+                offset as GLintptr,
+                length as GLsizeiptr
+            ));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+        }
+    }
+
+    /// Ends the mapping. Fails when the contents were lost meanwhile, e.g. to a display mode
+    /// change, and have to be written again.
+    pub fn unmap(self, _context: &GlContext) -> Result<(), String> {
+        unsafe {
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.buffer_id));
+            let intact = gl_check!(gl::UnmapBuffer(gl::COPY_WRITE_BUFFER));
+            gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+            if intact == gl::TRUE {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Buffer {} lost its contents while mapped",
+                    self.buffer_id
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use gl::types::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gl45")]
+use crate::ogl::bindless;
+use crate::ogl::context::GlContext;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+// EXT_texture_filter_anisotropic's limit, core only from GL 4.6, past these bindings
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+// Extensions the crate has a code path for, listed in the startup report when present
+const RELEVANT_EXTENSIONS: [&str; 14] = [
+    "GL_ARB_direct_state_access",
+    "GL_ARB_compute_shader",
+    "GL_ARB_shader_storage_buffer_object",
+    "GL_ARB_tessellation_shader",
+    "GL_ARB_buffer_storage",
+    "GL_ARB_multi_draw_indirect",
+    "GL_ARB_bindless_texture",
+    "GL_ARB_invalidate_subdata",
+    "GL_EXT_color_buffer_float",
+    "GL_EXT_color_buffer_half_float",
+    "GL_EXT_texture_filter_anisotropic",
+    "GL_NVX_gpu_memory_info",
+    "GL_ATI_meminfo",
+    "GL_KHR_debug",
+];
+
+// Read by the buffer/texture/VAO wrappers, which have no access to the detected capabilities
+static DIRECT_STATE_ACCESS: AtomicBool = AtomicBool::new(false);
+static OPENGL_ES: AtomicBool = AtomicBool::new(false);
+static BUFFER_STORAGE: AtomicBool = AtomicBool::new(false);
+static MULTI_DRAW_INDIRECT: AtomicBool = AtomicBool::new(false);
+static COMPUTE_SHADERS: AtomicBool = AtomicBool::new(false);
+static TESSELLATION_SHADERS: AtomicBool = AtomicBool::new(false);
+static BINDLESS_TEXTURES: AtomicBool = AtomicBool::new(false);
+static INVALIDATE_SUBDATA: AtomicBool = AtomicBool::new(false);
+static FLOAT_RENDER_TARGETS: AtomicBool = AtomicBool::new(false);
+static NVX_GPU_MEMORY_INFO: AtomicBool = AtomicBool::new(false);
+static ATI_MEMINFO: AtomicBool = AtomicBool::new(false);
+static DEBUG_LABELS: AtomicBool = AtomicBool::new(false);
+// Zero until detected, which the checks take as no limit
+static MAX_TEXTURE_SIZE: AtomicI32 = AtomicI32::new(0);
+static MAX_ARRAY_TEXTURE_LAYERS: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlApi {
+    OpenGl,
+    /// OpenGL ES 3.0, for ARM boards and ANGLE
+    OpenGlEs,
+}
+
+/// Context versions the renderer knows how to drive. 4.1 is the newest core profile macOS
+/// offers; 4.5 adds direct state access on top of the compute shaders and SSBOs of 4.3.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlProfile {
+    Gl33,
+    Gl41,
+    Gl45,
+    Gles30,
+}
+
+impl GlProfile {
+    pub fn version(self) -> [u32; 2] {
+        match self {
+            GlProfile::Gl33 => [3, 3],
+            GlProfile::Gl41 => [4, 1],
+            GlProfile::Gl45 => [4, 5],
+            GlProfile::Gles30 => [3, 0],
+        }
+    }
+
+    /// Newer profiles are opted into with the `gl41` and `gl45` cargo features.
+    pub fn is_compiled(self) -> bool {
+        match self {
+            GlProfile::Gl33 | GlProfile::Gles30 => true,
+            GlProfile::Gl41 => cfg!(feature = "gl41"),
+            GlProfile::Gl45 => cfg!(feature = "gl45"),
+        }
+    }
+
+    /// Desktop profiles built into this binary, newest first.
+    pub fn compiled() -> Vec<GlProfile> {
+        [GlProfile::Gl45, GlProfile::Gl41, GlProfile::Gl33]
+            .iter()
+            .copied()
+            .filter(|profile| profile.is_compiled())
+            .collect()
+    }
+
+    /// Compiled profiles no newer than `max_version`, in the order context creation should try
+    /// them. Never empty: 3.3 is the floor the shaders are written against. ES always uses 3.0.
+    pub fn candidates(api: GlApi, max_version: [u32; 2]) -> Vec<GlProfile> {
+        if api == GlApi::OpenGlEs {
+            return vec![GlProfile::Gles30];
+        }
+        let candidates: Vec<GlProfile> = GlProfile::compiled()
+            .into_iter()
+            .filter(|profile| profile.version() <= max_version)
+            .collect();
+        if candidates.is_empty() {
+            vec![GlProfile::Gl33]
+        } else {
+            candidates
+        }
+    }
+}
+
+/// Implementation limits of the current context that the crate runs into.
+#[derive(Clone, Copy, Debug)]
+pub struct GlLimits {
+    /// Widest and tallest 2D texture or render target
+    pub max_texture_size: i32,
+    pub max_array_texture_layers: i32,
+    /// Texture units usable by all shader stages together
+    pub max_texture_units: i32,
+    /// Bytes in one uniform block
+    pub max_uniform_block_size: i32,
+    /// Most samples of a multisampled framebuffer
+    pub max_samples: i32,
+    /// Highest anisotropic filtering ratio; 0 without EXT_texture_filter_anisotropic
+    pub max_anisotropy: f32,
+}
+
+/// What the current context actually supports, queried once after it is created. Optional code
+/// paths check these flags, which are only ever set when their profile is compiled in.
+#[derive(Clone, Debug)]
+pub struct GlCapabilities {
+    pub version: [u32; 2],
+    pub opengl_es: bool,
+    pub vendor: String,
+    pub renderer: String,
+    /// The driver's full version string, usually with its own version number in it
+    pub version_string: String,
+    pub glsl_version: String,
+    pub limits: GlLimits,
+    /// Extensions from `RELEVANT_EXTENSIONS` the context has
+    pub extensions: Vec<String>,
+    pub direct_state_access: bool,
+    pub compute_shaders: bool,
+    /// Tessellation control and evaluation stages from GL 4.0; never on ES 3.0
+    pub tessellation_shaders: bool,
+    pub shader_storage_buffers: bool,
+    /// Immutable, persistently mapped buffers from GL 4.4
+    pub buffer_storage: bool,
+    /// `glMultiDrawElementsIndirect` with per-draw base instances
+    pub multi_draw_indirect: bool,
+    /// ARB_bindless_texture, with its entry points loaded and SSBOs to keep handles in
+    pub bindless_textures: bool,
+    /// `glInvalidateBufferData` and friends, to drop buffer contents without orphaning
+    pub invalidate_subdata: bool,
+    /// Rendering into half float color textures; core on desktop, an extension on ES 3.0
+    pub float_render_targets: bool,
+    /// Vendor extensions reporting free video memory, NVIDIA's and AMD's
+    pub nvx_gpu_memory_info: bool,
+    pub ati_meminfo: bool,
+    /// `glObjectLabel` from KHR_debug, core in GL 4.3, naming objects for debuggers
+    pub debug_labels: bool,
+}
+
+impl GlCapabilities {
+    /// Queries the current context and enables the optional wrapper code paths it supports.
+    pub fn detect(_context: &GlContext) -> GlCapabilities {
+        unsafe {
+            let (mut major, mut minor) = (0, 0);
+            gl_check!(gl::GetIntegerv(gl::MAJOR_VERSION, &mut major));
+            gl_check!(gl::GetIntegerv(gl::MINOR_VERSION, &mut minor));
+            let version = [major as u32, minor as u32];
+            let opengl_es = gl_string(gl::VERSION).starts_with("OpenGL ES");
+            let extensions = extensions();
+            let has = |required: [u32; 2], extension: &str| {
+                version >= required || extensions.iter().any(|name| name == extension)
+            };
+
+            #[cfg(feature = "gl45")]
+            let bindless_loaded = bindless::is_loaded();
+            #[cfg(not(feature = "gl45"))]
+            let bindless_loaded = false;
+
+            let integer = |name: GLenum| {
+                let mut value = 0;
+                gl_check!(gl::GetIntegerv(name, &mut value));
+                value
+            };
+            let mut max_anisotropy = 0.0_f32;
+            if extensions
+                .iter()
+                .any(|name| name == "GL_EXT_texture_filter_anisotropic")
+            {
+                gl_check!(gl::GetFloatv(
+                    MAX_TEXTURE_MAX_ANISOTROPY,
+                    &mut max_anisotropy
+                ));
+            }
+            let limits = GlLimits {
+                max_texture_size: integer(gl::MAX_TEXTURE_SIZE),
+                max_array_texture_layers: integer(gl::MAX_ARRAY_TEXTURE_LAYERS),
+                max_texture_units: integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+                max_uniform_block_size: integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+                max_samples: integer(gl::MAX_SAMPLES),
+                max_anisotropy,
+            };
+
+            let capabilities = GlCapabilities {
+                version,
+                opengl_es,
+                vendor: gl_string(gl::VENDOR),
+                renderer: gl_string(gl::RENDERER),
+                version_string: gl_string(gl::VERSION),
+                glsl_version: gl_string(gl::SHADING_LANGUAGE_VERSION),
+                limits,
+                extensions: RELEVANT_EXTENSIONS
+                    .iter()
+                    .filter(|relevant| extensions.iter().any(|name| name == *relevant))
+                    .map(|relevant| relevant.to_string())
+                    .collect(),
+                direct_state_access: cfg!(feature = "gl45")
+                    && has([4, 5], "GL_ARB_direct_state_access"),
+                compute_shaders: cfg!(feature = "gl45") && has([4, 3], "GL_ARB_compute_shader"),
+                tessellation_shaders: cfg!(feature = "gl41")
+                    && !opengl_es
+                    && has([4, 0], "GL_ARB_tessellation_shader"),
+                shader_storage_buffers: cfg!(feature = "gl45")
+                    && has([4, 3], "GL_ARB_shader_storage_buffer_object"),
+                buffer_storage: cfg!(feature = "gl45") && has([4, 4], "GL_ARB_buffer_storage"),
+                multi_draw_indirect: cfg!(feature = "gl45")
+                    && has([4, 3], "GL_ARB_multi_draw_indirect")
+                    && has([4, 2], "GL_ARB_base_instance"),
+                // Never part of core, so only the extension counts
+                bindless_textures: cfg!(feature = "gl45")
+                    && bindless_loaded
+                    && has([4, 3], "GL_ARB_shader_storage_buffer_object")
+                    && extensions
+                        .iter()
+                        .any(|name| name == "GL_ARB_bindless_texture"),
+                invalidate_subdata: cfg!(feature = "gl45")
+                    && has([4, 3], "GL_ARB_invalidate_subdata"),
+                float_render_targets: !opengl_es
+                    || extensions.iter().any(|name| {
+                        name == "GL_EXT_color_buffer_float"
+                            || name == "GL_EXT_color_buffer_half_float"
+                    }),
+                nvx_gpu_memory_info: extensions
+                    .iter()
+                    .any(|name| name == "GL_NVX_gpu_memory_info"),
+                ati_meminfo: extensions.iter().any(|name| name == "GL_ATI_meminfo"),
+                // ES names the extension's entry points with a KHR suffix, which are not loaded
+                debug_labels: !opengl_es
+                    && has([4, 3], "GL_KHR_debug")
+                    && gl::ObjectLabel::is_loaded(),
+            };
+            DIRECT_STATE_ACCESS.store(capabilities.direct_state_access, Ordering::Relaxed);
+            OPENGL_ES.store(capabilities.opengl_es, Ordering::Relaxed);
+            BUFFER_STORAGE.store(capabilities.buffer_storage, Ordering::Relaxed);
+            MULTI_DRAW_INDIRECT.store(capabilities.multi_draw_indirect, Ordering::Relaxed);
+            COMPUTE_SHADERS.store(
+                capabilities.compute_shaders && capabilities.shader_storage_buffers,
+                Ordering::Relaxed,
+            );
+            TESSELLATION_SHADERS.store(capabilities.tessellation_shaders, Ordering::Relaxed);
+            BINDLESS_TEXTURES.store(capabilities.bindless_textures, Ordering::Relaxed);
+            INVALIDATE_SUBDATA.store(capabilities.invalidate_subdata, Ordering::Relaxed);
+            FLOAT_RENDER_TARGETS.store(capabilities.float_render_targets, Ordering::Relaxed);
+            NVX_GPU_MEMORY_INFO.store(capabilities.nvx_gpu_memory_info, Ordering::Relaxed);
+            ATI_MEMINFO.store(capabilities.ati_meminfo, Ordering::Relaxed);
+            DEBUG_LABELS.store(capabilities.debug_labels, Ordering::Relaxed);
+            MAX_TEXTURE_SIZE.store(limits.max_texture_size, Ordering::Relaxed);
+            MAX_ARRAY_TEXTURE_LAYERS.store(limits.max_array_texture_layers, Ordering::Relaxed);
+            capabilities
+        }
+    }
+
+    /// The capabilities line, then the driver, limits and relevant extensions, one per line, for
+    /// the startup log.
+    pub fn report(&self) -> String {
+        format!(
+            "{}\n  Driver: {}, {}, GLSL {}\n  Limits: {}\n  Extensions: {}",
+            self,
+            self.vendor,
+            self.version_string,
+            self.glsl_version,
+            self.limits,
+            if self.extensions.is_empty() {
+                "none".to_string()
+            } else {
+                self.extensions.join(" ")
+            }
+        )
+    }
+}
+
+/// Fails for a `width` by `height` texture or render target larger than the context allows,
+/// which GL would otherwise only flag with an error once it is allocated.
+pub fn check_texture_size(width: i32, height: i32) -> Result<(), String> {
+    let max_size = MAX_TEXTURE_SIZE.load(Ordering::Relaxed);
+    if max_size > 0 && (width > max_size || height > max_size) {
+        return Err(format!(
+            "{}x{} exceeds the largest texture size, {}",
+            width, height, max_size
+        ));
+    }
+    Ok(())
+}
+
+/// Fails for more layers than a 2D array texture can hold.
+pub fn check_array_texture_layers(layers: usize) -> Result<(), String> {
+    let max_layers = MAX_ARRAY_TEXTURE_LAYERS.load(Ordering::Relaxed);
+    if max_layers > 0 && layers > max_layers as usize {
+        return Err(format!(
+            "{} layers exceed the most an array texture holds, {}",
+            layers, max_layers
+        ));
+    }
+    Ok(())
+}
+
+/// Whether objects are created and edited through GL 4.5 direct state access instead of being
+/// bound first.
+#[cfg(feature = "gl45")]
+pub fn direct_state_access() -> bool {
+    DIRECT_STATE_ACCESS.load(Ordering::Relaxed)
+}
+
+/// Whether per-frame data is streamed through persistently mapped buffers instead of being
+/// respecified every frame.
+#[cfg(feature = "gl45")]
+pub fn buffer_storage() -> bool {
+    BUFFER_STORAGE.load(Ordering::Relaxed)
+}
+
+/// Whether meshes sharing buffers can be drawn with one indirect multi-draw call.
+#[cfg(feature = "gl45")]
+pub fn multi_draw_indirect() -> bool {
+    MULTI_DRAW_INDIRECT.load(Ordering::Relaxed)
+}
+
+/// Whether compute shaders can run, with shader storage buffers to read and write.
+#[cfg(feature = "gl45")]
+pub fn compute_shaders() -> bool {
+    COMPUTE_SHADERS.load(Ordering::Relaxed)
+}
+
+/// Whether patches can be subdivided on the GPU by tessellation shaders.
+#[cfg(feature = "gl41")]
+pub fn tessellation_shaders() -> bool {
+    TESSELLATION_SHADERS.load(Ordering::Relaxed)
+}
+
+/// Whether textures can be sampled through resident handles instead of bound units.
+#[cfg(feature = "gl45")]
+pub fn bindless_textures() -> bool {
+    BINDLESS_TEXTURES.load(Ordering::Relaxed)
+}
+
+/// Whether buffer contents can be invalidated directly instead of by orphaning the storage.
+#[cfg(feature = "gl45")]
+pub fn invalidate_subdata() -> bool {
+    INVALIDATE_SUBDATA.load(Ordering::Relaxed)
+}
+
+/// Whether offscreen targets can hold colors brighter than white in half float textures.
+pub fn float_render_targets() -> bool {
+    FLOAT_RENDER_TARGETS.load(Ordering::Relaxed)
+}
+
+/// Whether free video memory can be queried the NVIDIA way.
+pub fn nvx_gpu_memory_info() -> bool {
+    NVX_GPU_MEMORY_INFO.load(Ordering::Relaxed)
+}
+
+/// Whether free video memory can be queried the AMD way.
+pub fn ati_meminfo() -> bool {
+    ATI_MEMINFO.load(Ordering::Relaxed)
+}
+
+/// Whether GL objects can be given names that debuggers show.
+pub fn debug_labels() -> bool {
+    DEBUG_LABELS.load(Ordering::Relaxed)
+}
+
+/// Whether the context is OpenGL ES, where shaders are rewritten to GLSL ES and desktop-only
+/// features are skipped.
+pub fn opengl_es() -> bool {
+    OPENGL_ES.load(Ordering::Relaxed)
+}
+
+impl fmt::Display for GlCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        write!(
+            f,
+            "{} {}.{} on {} (DSA: {}, compute: {}, SSBO: {}, tessellation: {}, buffer storage: {}, \
+             MDI: {}, bindless: {}, float targets: {}, memory info: {}, labels: {})",
+            if self.opengl_es {
+                "OpenGL ES"
+            } else {
+                "OpenGL"
+            },
+            self.version[0],
+            self.version[1],
+            self.renderer,
+            yes_no(self.direct_state_access),
+            yes_no(self.compute_shaders),
+            yes_no(self.shader_storage_buffers),
+            yes_no(self.tessellation_shaders),
+            yes_no(self.buffer_storage),
+            yes_no(self.multi_draw_indirect),
+            yes_no(self.bindless_textures),
+            yes_no(self.float_render_targets),
+            yes_no(self.nvx_gpu_memory_info || self.ati_meminfo),
+            yes_no(self.debug_labels)
+        )
+    }
+}
+
+impl fmt::Display for GlLimits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "texture size {}, array layers {}, texture units {}, uniform block {} bytes, \
+             samples {}, anisotropy {}",
+            self.max_texture_size,
+            self.max_array_texture_layers,
+            self.max_texture_units,
+            self.max_uniform_block_size,
+            self.max_samples,
+            if self.max_anisotropy > 0.0_f32 {
+                format!("{}x", self.max_anisotropy)
+            } else {
+                "none".to_string()
+            }
+        )
+    }
+}
+
+unsafe fn extensions() -> Vec<String> {
+    let mut count = 0;
+    gl_check!(gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count));
+    (0..count as GLuint)
+        .filter_map(|i| {
+            let name = gl_check!(gl::GetStringi(gl::EXTENSIONS, i));
+            if name.is_null() {
+                None
+            } else {
+                Some(
+                    CStr::from_ptr(name as *const c_char)
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            }
+        })
+        .collect()
+}
+
+unsafe fn gl_string(name: GLenum) -> String {
+    let value = gl_check!(gl::GetString(name));
+    if value.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(value as *const c_char)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
@@ -0,0 +1,72 @@
+// Proof of a usable GL context, taken by the wrappers that create GL objects or draw, so doing
+// that is safe: the one promise left to the caller is made once, where the token is created.
+// The token cannot leave the thread it was made on, so GL work reached from another thread, such
+// as the upload worker's, needs a token of that thread's own context.
+
+use std::marker::PhantomData;
+
+/// Token showing that the `gl` entry points are loaded and a context is current on this thread.
+/// Window backends hand one out once `create` has loaded GL, see `WindowBackend::gl_context`.
+/// Neither `Send` nor `Sync`, so passing it, or a renderer holding it, to another thread does
+/// not compile.
+#[derive(Clone, Copy, Debug)]
+pub struct GlContext {
+    // Raw pointers are neither `Send` nor `Sync`
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl GlContext {
+    /// The caller vouches that `gl::load_with` has run and a context is current on this thread,
+    /// e.g. on a worker thread right after making its shared context current.
+    pub unsafe fn assume_current() -> GlContext {
+        GlContext {
+            _thread_bound: PhantomData,
+        }
+    }
+
+    /// Binds the window's own framebuffer, `width` by `height`, and draws over all of it.
+    pub fn bind_window_framebuffer(&self, width: i32, height: i32) {
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+        }
+        self.set_viewport(width, height);
+    }
+
+    pub fn set_viewport(&self, width: i32, height: i32) {
+        unsafe {
+            gl_check!(gl::Viewport(0, 0, width, height));
+        }
+    }
+
+    /// Hands the commands issued so far to the driver, for another context to see their results.
+    pub fn flush(&self) {
+        unsafe {
+            gl_check!(gl::Flush());
+        }
+    }
+
+    /// Clears the `width` by `height` rectangle at `x`, `y` of the bound framebuffer's color to
+    /// `color`, leaving the rest alone.
+    pub fn clear_color_rect(
+        &self,
+        (x, y): (i32, i32),
+        (width, height): (i32, i32),
+        color: [f32; 4],
+    ) {
+        let [red, green, blue, alpha] = color;
+        unsafe {
+            gl_check!(gl::Enable(gl::SCISSOR_TEST));
+            gl_check!(gl::Scissor(x, y, width, height));
+            gl_check!(gl::ClearColor(red, green, blue, alpha));
+            gl_check!(gl::Clear(gl::COLOR_BUFFER_BIT));
+            gl_check!(gl::Disable(gl::SCISSOR_TEST));
+        }
+    }
+
+    /// Multisampling of multisampled framebuffers, on unless turned off.
+    pub fn enable_multisample(&self) {
+        unsafe {
+            gl_check!(gl::Enable(gl::MULTISAMPLE));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::ogl::context::GlContext;
+use crate::ogl::gpu_memory;
+use crate::ogl::graphics::Camera;
+use crate::ogl::overlay::{StatsOverlay, GPU_PASS_COLORS};
+use crate::ogl::stats::FrameStats;
+use crate::ogl::text_renderer::TextRenderer;
+
+const TEXT_COLOR: [f32; 4] = [1.0_f32, 1.0_f32, 1.0_f32, 1.0_f32];
+const BYTES_PER_MIB: f32 = 1024.0_f32 * 1024.0_f32;
+const KIB_PER_MIB: f32 = 1024.0_f32;
+
+/// Frame time graphs along the bottom left and a text readout in the top left of frame times,
+/// draw counters, GPU memory use, the camera pose and the GPU time of each timed pass.
+pub struct DebugHud {
+    pub visible: bool,
+    graphs: StatsOverlay,
+    text_renderer: TextRenderer,
+}
+
+impl DebugHud {
+    pub fn new(context: &GlContext, font_atlas_file: &str) -> Result<DebugHud, String> {
+        Ok(DebugHud {
+            visible: true,
+            graphs: StatsOverlay::new(context)?,
+            text_renderer: TextRenderer::new(context, font_atlas_file)?,
+        })
+    }
+
+    pub fn draw(
+        &mut self,
+        stats: &FrameStats,
+        camera: &Camera,
+        framebuffer_size: (i32, i32),
+        content_scale: f32,
+    ) {
+        if !self.visible {
+            return;
+        }
+        self.graphs.draw(stats);
+
+        let summary = stats.summary();
+        let counters = stats.counters();
+        let memory = gpu_memory::usage();
+        let driver_memory = match unsafe { gpu_memory::driver_memory() } {
+            Some(driver_memory) => match driver_memory.total {
+                Some(total) => format!(
+                    "\nvideo memory {:.0} of {:.0} MiB free",
+                    driver_memory.available as f32 / KIB_PER_MIB,
+                    total as f32 / KIB_PER_MIB
+                ),
+                None => format!(
+                    "\nvideo memory {:.0} MiB free",
+                    driver_memory.available as f32 / KIB_PER_MIB
+                ),
+            },
+            None => String::new(),
+        };
+        let text = format!(
+            "{:.0} FPS  {:.2} ms\nmin {:.2}  p99 {:.2}  max {:.2} ms\n\
+             {} draw calls  {} triangles  {} state changes\n\
+             {} objects visible  {} culled  {} occluded\n\
+             buffers {:.1} MiB  textures {:.1} MiB  renderbuffers {:.1} MiB{}\n\
+             camera ({:.2}, {:.2}, {:.2})  yaw {:.1}  pitch {:.1}",
+            1.0_f32 / summary.avg.max(f32::EPSILON),
+            summary.avg * 1000.0_f32,
+            summary.min * 1000.0_f32,
+            summary.p99 * 1000.0_f32,
+            summary.max * 1000.0_f32,
+            counters.draw_calls,
+            counters.triangles,
+            counters.state_changes,
+            counters.visible_objects,
+            counters.culled_objects,
+            counters.occluded_objects,
+            memory.buffers as f32 / BYTES_PER_MIB,
+            memory.textures as f32 / BYTES_PER_MIB,
+            memory.renderbuffers as f32 / BYTES_PER_MIB,
+            driver_memory,
+            camera.position.x,
+            camera.position.y,
+            camera.position.z,
+            camera.yaw,
+            camera.pitch,
+        );
+        // Half the atlas cell size keeps text readable without covering the scene
+        let scale = 0.5_f32 * content_scale;
+        let line_height = self.text_renderer.line_height(scale);
+        let margin = line_height * 0.5_f32;
+        self.text_renderer
+            .add_text(&text, (margin, margin), scale, TEXT_COLOR);
+
+        // One line per GPU pass, in the color of its bars in the graph
+        let mut y = margin + line_height * (text.lines().count() as f32 + 0.5_f32);
+        for (pass_i, (name, time)) in stats.last_gpu_pass_times().into_iter().enumerate() {
+            let [red, green, blue] = GPU_PASS_COLORS[pass_i % GPU_PASS_COLORS.len()];
+            self.text_renderer.add_text(
+                &format!("GPU {} {:.2} ms", name, time * 1000.0_f32),
+                (margin, y),
+                scale,
+                [red, green, blue, 1.0_f32],
+            );
+            y += line_height;
+        }
+        self.text_renderer.draw(framebuffer_size);
+    }
+}
@@ -0,0 +1,54 @@
+use gl::types::*;
+
+use crate::ogl::capabilities;
+use crate::ogl::live_objects;
+use std::os::raw::c_char;
+
+// Longest label every implementation takes: GL_MAX_LABEL_LENGTH is at least 256, counting the
+// terminating null, which is not passed here
+const MAX_LABEL_LENGTH: usize = 255;
+
+/// Names GL object `id` of kind `identifier` (`gl::BUFFER`, `gl::TEXTURE`, `gl::PROGRAM`,
+/// `gl::VERTEX_ARRAY`, `gl::FRAMEBUFFER`, ...) for debuggers such as RenderDoc and apitrace,
+/// which show it instead of the bare number. Does nothing without KHR_debug. Names from `glGen*`
+/// only become objects once first bound, so label them after that. The leak report at exit
+/// names objects by their labels too, with or without KHR_debug.
+pub unsafe fn set(identifier: GLenum, id: GLuint, label: &str) {
+    live_objects::set_label(identifier, id, label);
+    if !capabilities::debug_labels() || id == 0 {
+        return;
+    }
+    let mut length = label.len().min(MAX_LABEL_LENGTH);
+    while !label.is_char_boundary(length) {
+        length -= 1;
+    }
+    gl_check!(gl::ObjectLabel(
+        identifier,
+        id,
+        length as GLsizei,
+        label.as_ptr() as *const c_char
+    ));
+}
+
+/// The name `set` gave GL object `id`, if any.
+#[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+pub unsafe fn get(identifier: GLenum, id: GLuint) -> Option<String> {
+    if !capabilities::debug_labels() || id == 0 {
+        return None;
+    }
+    let mut label = vec![0_u8; MAX_LABEL_LENGTH + 1];
+    let mut length = 0;
+    gl_check!(gl::GetObjectLabel(
+        identifier,
+        id,
+        label.len() as GLsizei,
+        &mut length,
+        label.as_mut_ptr() as *mut c_char
+    ));
+    label.truncate(length.max(0) as usize);
+    if label.is_empty() {
+        None
+    } else {
+        Some(String::from_utf8_lossy(&label).into_owned())
+    }
+}
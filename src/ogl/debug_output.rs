@@ -0,0 +1,103 @@
+// KHR_debug message output for contexts created with `--gl-debug`. Messages are delivered
+// synchronously, on the thread and inside the GL call that caused them, so a backtrace taken in
+// the callback points at the wrapper call at fault.
+
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use std::backtrace::Backtrace;
+use std::ffi::{c_void, CStr};
+use std::process;
+use std::ptr;
+
+/// Turns on synchronous debug output for the current context, which has to be a debug context.
+/// Messages are printed as they come; the first high-severity one is printed with a backtrace,
+/// then the process aborts, which stops an attached debugger at the offending call.
+pub fn enable(_context: &GlContext) -> Result<(), String> {
+    unsafe {
+        if !gl::DebugMessageCallback::is_loaded() || !gl::DebugMessageControl::is_loaded() {
+            return Err("the context has no KHR_debug".to_string());
+        }
+        let mut flags = 0;
+        gl_check!(gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags));
+        if flags as GLuint & gl::CONTEXT_FLAG_DEBUG_BIT == 0 {
+            return Err("the driver did not create a debug context".to_string());
+        }
+        gl_check!(gl::Enable(gl::DEBUG_OUTPUT));
+        gl_check!(gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS));
+        gl_check!(gl::DebugMessageCallback(Some(on_message), ptr::null()));
+        // Notifications report things like buffer placement on every upload
+        gl_check!(gl::DebugMessageControl(
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            gl::DEBUG_SEVERITY_NOTIFICATION,
+            0,
+            ptr::null(),
+            gl::FALSE
+        ));
+        Ok(())
+    }
+}
+
+extern "system" fn on_message(
+    source: GLenum,
+    message_type: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    _length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let message = if message.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    eprintln!(
+        "GL {} {} from {} ({}): {}",
+        severity_name(severity),
+        type_name(message_type),
+        source_name(source),
+        id,
+        message.trim_end()
+    );
+    if severity == gl::DEBUG_SEVERITY_HIGH {
+        // Unwinding cannot cross the driver's frames, so this stops the process instead
+        eprintln!("{}", Backtrace::force_capture());
+        process::abort();
+    }
+}
+
+fn severity_name(severity: GLenum) -> &'static str {
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => "high severity",
+        gl::DEBUG_SEVERITY_MEDIUM => "medium severity",
+        gl::DEBUG_SEVERITY_LOW => "low severity",
+        _ => "notification",
+    }
+}
+
+fn type_name(message_type: GLenum) -> &'static str {
+    match message_type {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability issue",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance issue",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "message",
+    }
+}
+
+fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "the API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "the window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "the shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "a third party",
+        gl::DEBUG_SOURCE_APPLICATION => "the application",
+        _ => "elsewhere",
+    }
+}
@@ -0,0 +1,265 @@
+use gl::types::*;
+use serde::{Deserialize, Serialize};
+
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ptr;
+
+// Texture unit the captured cubemap is bound to, after those of reflection_probes
+const ENVIRONMENT_UNIT: GLuint = 14;
+
+/// How much of the cubemap `EnvironmentCapture::capture` renders each time it is called.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    /// All six faces, so reflections keep up with everything that moves
+    #[default]
+    EveryFace,
+    /// One face in turn, a sixth of the cost, with each face up to six captures old
+    OneFace,
+}
+
+/// The uniforms of a program sampling the captured cubemap as `environment`, looked up once.
+pub struct EnvironmentUniforms {
+    camera_position: Uniform,
+}
+
+/// A cubemap the scene around a point is rendered into at runtime, for reflective materials to
+/// sample, unlike the baked reflection probes.
+pub struct EnvironmentCapture {
+    texture: GLuint,
+    framebuffer: GLuint,
+    depth_renderbuffer: GLuint,
+    size: i32,
+    /// Face the next one face capture renders
+    next_face: usize,
+    /// Whether every face has been rendered since the capture was created
+    complete: bool,
+    context: GlContext,
+}
+
+/// Cameras at `position` looking along +X, -X, +Y, -Y, +Z and -Z, in the order of the cubemap
+/// faces from `TEXTURE_CUBE_MAP_POSITIVE_X`. Their up vectors follow the cubemap convention, so
+/// a face rendered with one is the right way up when sampled by direction.
+pub fn cubemap_face_cameras(position: &Vec3) -> [Camera; 6] {
+    let camera = |front: Vec3, up: Vec3| Camera {
+        position: *position,
+        front,
+        up,
+        yaw: 0.0_f32,
+        pitch: 0.0_f32,
+    };
+    let (x, y, z) = (
+        glm::vec3(1.0_f32, 0.0_f32, 0.0_f32),
+        glm::vec3(0.0_f32, 1.0_f32, 0.0_f32),
+        glm::vec3(0.0_f32, 0.0_f32, 1.0_f32),
+    );
+    [
+        camera(x, -y),
+        camera(-x, -y),
+        camera(y, z),
+        camera(-y, -z),
+        camera(z, -y),
+        camera(-z, -y),
+    ]
+}
+
+/// The 90 degree square projection every cubemap face is rendered with.
+pub fn cubemap_face_projection(near: f32, far: f32) -> Mat4 {
+    glm::perspective(1.0_f32, 90.0_f32.to_radians(), near, far)
+}
+
+impl EnvironmentUniforms {
+    /// Looks up the uniforms of `program` and points its sampler at the captured cubemap.
+    pub fn new(program: &ShaderProgram) -> EnvironmentUniforms {
+        program.use_program();
+        program
+            .uniform("environment")
+            .set_int(ENVIRONMENT_UNIT as i32);
+        EnvironmentUniforms {
+            camera_position: program.uniform("camera_position"),
+        }
+    }
+}
+
+impl EnvironmentCapture {
+    /// An RGBA8 cubemap `size` pixels across each face, with a depth buffer to render it with.
+    /// Faces are black until captured.
+    pub fn new(context: &GlContext, size: i32) -> Result<EnvironmentCapture, String> {
+        unsafe {
+            capabilities::check_texture_size(size, size)?;
+            let mut texture = 0_u32;
+            gl_check!(gl::GenTextures(1, &mut texture));
+            live_objects::created(GlObject::Texture, texture);
+            gl_check!(gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture));
+            for face in 0..6 {
+                gl_check!(gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    gl::RGBA8 as GLint,
+                    size,
+                    size,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    ptr::null(),
+                ));
+            }
+            for (parameter, value) in [
+                (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+                (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+            ]
+            .iter()
+            {
+                gl_check!(gl::TexParameteri(
+                    gl::TEXTURE_CUBE_MAP,
+                    *parameter,
+                    *value as GLint
+                ));
+            }
+            gl_check!(gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0));
+            gpu_memory::track(
+                GpuResource::Texture,
+                texture,
+                gpu_memory::texture_bytes(size as u32, size as u32, 6, 4, false),
+            );
+            debug_label::set(gl::TEXTURE, texture, "environment capture");
+
+            let mut depth_renderbuffer = 0_u32;
+            gl_check!(gl::GenRenderbuffers(1, &mut depth_renderbuffer));
+            live_objects::created(GlObject::Renderbuffer, depth_renderbuffer);
+            gl_check!(gl::BindRenderbuffer(gl::RENDERBUFFER, depth_renderbuffer));
+            gl_check!(gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                size,
+                size
+            ));
+            gl_check!(gl::BindRenderbuffer(gl::RENDERBUFFER, 0));
+            gpu_memory::track(
+                GpuResource::Renderbuffer,
+                depth_renderbuffer,
+                gpu_memory::texture_bytes(size as u32, size as u32, 1, 4, false),
+            );
+            debug_label::set(
+                gl::RENDERBUFFER,
+                depth_renderbuffer,
+                "environment capture depth/stencil",
+            );
+
+            let mut framebuffer = 0_u32;
+            gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+            live_objects::created(GlObject::Framebuffer, framebuffer);
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer));
+            gl_check!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+                texture,
+                0
+            ));
+            gl_check!(gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_renderbuffer
+            ));
+            let status = gl_check!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER));
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+            debug_label::set(gl::FRAMEBUFFER, framebuffer, "environment capture");
+            let capture = EnvironmentCapture {
+                texture,
+                framebuffer,
+                depth_renderbuffer,
+                size,
+                next_face: 0,
+                complete: false,
+                context: *context,
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Environment capture incomplete (0x{:X})", status));
+            }
+            Ok(capture)
+        }
+    }
+
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    /// Renders the faces `mode` asks for around `position` through `draw_face`, which draws the
+    /// scene for the camera it is given with `cubemap_face_projection` into the bound
+    /// framebuffer, already cleared by the caller's view. Every face is rendered the first time,
+    /// whatever the mode. Leaves the capture's framebuffer bound.
+    pub fn capture(
+        &mut self,
+        position: &Vec3,
+        mode: CaptureMode,
+        mut draw_face: impl FnMut(&Camera),
+    ) {
+        let cameras = cubemap_face_cameras(position);
+        let faces = if mode == CaptureMode::EveryFace || !self.complete {
+            0..6
+        } else {
+            self.next_face..self.next_face + 1
+        };
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer));
+            gl_check!(gl::Viewport(0, 0, self.size, self.size));
+        }
+        for face in faces {
+            unsafe {
+                gl_check!(gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum,
+                    self.texture,
+                    0
+                ));
+            }
+            draw_face(&cameras[face]);
+        }
+        self.next_face = (self.next_face + 1) % 6;
+        self.complete = true;
+    }
+
+    /// Binds the cubemap and sets the camera position of `program`, whose `uniforms` these are.
+    pub fn bind(
+        &self,
+        program: &ShaderProgram,
+        uniforms: &EnvironmentUniforms,
+        camera_position: &Vec3,
+    ) {
+        program.use_program();
+        uniforms.camera_position.set_vec3f([
+            camera_position.x,
+            camera_position.y,
+            camera_position.z,
+        ]);
+        unsafe {
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0 + ENVIRONMENT_UNIT));
+            gl_check!(gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.texture));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        }
+    }
+}
+
+impl Drop for EnvironmentCapture {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Framebuffer, self.framebuffer);
+        live_objects::release(
+            &self.context,
+            GlObject::Renderbuffer,
+            self.depth_renderbuffer,
+        );
+        live_objects::release(&self.context, GlObject::Texture, self.texture);
+    }
+}
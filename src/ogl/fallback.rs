@@ -0,0 +1,67 @@
+// Stand-ins for assets that fail to load, so a partial asset set still runs. They are meant to
+// be noticed: magenta and black checks, or flat magenta.
+
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{ShaderProgram, Texture, TextureFormat};
+
+// Width and height of the checker texture, and of each of its squares, in texels
+const CHECKER_SIZE: u32 = 64;
+const CHECKER_SQUARE_SIZE: u32 = 8;
+const CHECKER_COLORS: [[u8; 4]; 2] = [[255, 0, 255, 255], [0, 0, 0, 255]];
+
+const FLAT_COLOR_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+// One matrix per instance, see renderer::INSTANCE_MATRIX_LOCATION
+layout (location = 3) in mat4 world_from_object;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+void main() {
+    gl_Position = projection_from_view * view_from_world * world_from_object * vec4(a_pos, 1.0f);
+}
+"#;
+
+const FLAT_COLOR_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(1.0f, 0.0f, 1.0f, 1.0f);
+}
+"#;
+
+/// A loaded magenta and black checker texture, to sample in place of a missing image.
+pub fn checker_texture(context: &GlContext) -> Texture {
+    let mut data = Vec::with_capacity((CHECKER_SIZE * CHECKER_SIZE * 4) as usize);
+    for y in 0..CHECKER_SIZE {
+        for x in 0..CHECKER_SIZE {
+            let square = (x / CHECKER_SQUARE_SIZE + y / CHECKER_SQUARE_SIZE) % 2;
+            data.extend_from_slice(&CHECKER_COLORS[square as usize]);
+        }
+    }
+    let mut texture = Texture::from_pixels(
+        context,
+        CHECKER_SIZE,
+        CHECKER_SIZE,
+        TextureFormat::for_channels(4, false),
+        data,
+        "missing texture",
+    )
+    .expect("The checker texture fits any context");
+    texture.load(context);
+    texture
+}
+
+/// A program drawing scene meshes, instanced like every scene shader, in flat magenta; for
+/// materials whose own shader fails to build.
+pub fn flat_color_program(context: &GlContext) -> Result<ShaderProgram, String> {
+    let shader_program = ShaderProgram::with_shaders(
+        context,
+        FLAT_COLOR_VERTEX_SHADER_SOURCE,
+        FLAT_COLOR_FRAGMENT_SHADER_SOURCE,
+    )?;
+    shader_program.set_label("missing shader");
+    Ok(shader_program)
+}
@@ -0,0 +1,677 @@
+use gl::types::*;
+use rusttype::{point, Font, GlyphId, Scale};
+
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::line_renderer::DepthMode;
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+use std::{fs, mem, ptr};
+
+const FONT_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+// Pixels from the top left, and the depth buffer value world-space text is tested at
+layout (location = 0) in vec3 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+layout (location = 2) in vec4 a_color;
+
+uniform mat4 projection_from_screen;
+
+out vec2 o_tex_coords;
+out vec4 o_color;
+
+void main() {
+    gl_Position = projection_from_screen * vec4(a_pos.xy, 0.0f, 1.0f);
+    gl_Position.z = a_pos.z;
+    o_tex_coords = a_tex_coords;
+    o_color = a_color;
+}
+"#;
+
+const FONT_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform sampler2D glyph_atlas;
+uniform bool signed_distance;
+
+in vec2 o_tex_coords;
+in vec4 o_color;
+
+out vec4 frag_color;
+
+void main() {
+    float value = texture(glyph_atlas, o_tex_coords).r;
+    float coverage = value;
+    if (signed_distance) {
+        // The outline sits at 0.5; fwidth keeps the antialiased edge about a pixel wide at any scale
+        float edge_width = 0.7f * fwidth(value);
+        coverage = smoothstep(0.5f - edge_width, 0.5f + edge_width, value);
+    }
+    frag_color = vec4(o_color.rgb, o_color.a * coverage);
+}
+"#;
+
+/// Size signed distance glyphs are usually baked at; they are scaled down from it when drawn.
+pub const SIGNED_DISTANCE_PIXEL_HEIGHT: f32 = 48.0;
+
+// Glyphs baked into the atlas; anything else is drawn as `FALLBACK_CHAR`
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+const FALLBACK_CHAR: char = '?';
+const ATLAS_WIDTH: usize = 512;
+// Empty pixels around each bitmap glyph so linear filtering does not pick up its neighbors
+const GLYPH_PADDING: usize = 1;
+// Distance in atlas pixels covered by the signed distance ramp on either side of an outline
+const SDF_SPREAD: usize = 6;
+// Floats per vertex: position and depth, texture coordinates and color
+const VERTEX_SIZE: usize = 9;
+// World-space text shrunk to lines shorter than this many pixels is left out as unreadable
+const MIN_WORLD_LINE_HEIGHT: f32 = 4.0;
+// Pixels between world-space text and the point it is anchored to
+const WORLD_TEXT_GAP: f32 = 4.0;
+
+/// How glyphs are stored in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlyphRendering {
+    /// Antialiased coverage, sharpest at the size the atlas was baked at
+    Bitmap,
+    /// Distance to the glyph outline, which stays crisp when text is scaled up
+    SignedDistance,
+}
+
+/// How large world-space text is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldTextSize {
+    /// The same size at any distance, this many times the size the glyphs were baked at, like
+    /// the `scale` of `add_text`
+    Screen(f32),
+    /// Lines this many world units tall, shrinking with distance like the scene around them
+    World(f32),
+}
+
+/// Text queued by `add_world_text`, laid out once the camera is known.
+struct WorldText {
+    text: String,
+    anchor: Vec3,
+    size: WorldTextSize,
+    color: [f32; 4],
+    depth: DepthMode,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BakedGlyph {
+    id: GlyphId,
+    advance: f32,
+    /// Top left corner of the quad relative to the pen position on the baseline, in baked pixels
+    offset: (f32, f32),
+    size: (f32, f32),
+    tex_coords_min: (f32, f32),
+    tex_coords_max: (f32, f32),
+}
+
+/// Text in a TrueType font, baked into a glyph atlas for printable ASCII when created and laid
+/// out with the font's advances and kerning. Text is collected with `add_text` and drawn in one
+/// call by `draw`, over whatever is in the framebuffer. Text anchored in the world is collected
+/// with `add_world_text` and drawn by `draw_world`, optionally hidden behind the scene.
+pub struct FontRenderer {
+    font: Font<'static>,
+    font_scale: Scale,
+    ascent: f32,
+    line_height: f32,
+    glyphs: Vec<BakedGlyph>,
+    rendering: GlyphRendering,
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    vertex_buffer_obj: GLuint,
+    atlas_texture: GLuint,
+    projection_from_screen_uniform: Uniform,
+    signed_distance_uniform: Uniform,
+    vertices: Vec<f32>,
+    world_text: Vec<WorldText>,
+}
+
+impl FontRenderer {
+    /// Loads a TrueType font and bakes its glyphs `pixel_height` pixels tall. Signed distance
+    /// atlases scale well from a fairly large size, such as `SIGNED_DISTANCE_PIXEL_HEIGHT`.
+    pub fn new(
+        context: &GlContext,
+        font_file: &str,
+        pixel_height: f32,
+        rendering: GlyphRendering,
+    ) -> Result<FontRenderer, String> {
+        unsafe {
+            let font_data = fs::read(font_file)
+                .map_err(|e| format!("Failed reading font {}: {}", font_file, e))?;
+            let font = Font::try_from_vec(font_data)
+                .ok_or_else(|| format!("Failed parsing font {}", font_file))?;
+            let font_scale = Scale::uniform(pixel_height);
+            let v_metrics = font.v_metrics(font_scale);
+
+            let (glyphs, atlas_height, atlas_pixels) = bake_glyphs(&font, font_scale, rendering);
+
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                FONT_VERTEX_SHADER_SOURCE,
+                FONT_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label(font_file);
+            let (mut vertex_array_obj, mut vertex_buffer_obj) = (0_u32, 0_u32);
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::GenBuffers(1, &mut vertex_buffer_obj));
+            live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_obj));
+            let stride = (VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_pos attribute
+            gl_check!(gl::VertexAttribPointer(
+                0,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                ptr::null()
+            ));
+            gl_check!(gl::EnableVertexAttribArray(0));
+            // a_tex_coords attribute
+            gl_check!(gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (3 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(1));
+            // a_color attribute
+            gl_check!(gl::VertexAttribPointer(
+                2,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (5 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(2));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, font_file);
+            debug_label::set(
+                gl::BUFFER,
+                vertex_buffer_obj,
+                &format!("{} vertices", font_file),
+            );
+
+            let mut atlas_texture = 0_u32;
+            gl_check!(gl::GenTextures(1, &mut atlas_texture));
+            live_objects::created(GlObject::Texture, atlas_texture);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, atlas_texture));
+            gl_check!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R8 as GLint,
+                ATLAS_WIDTH as GLsizei,
+                atlas_height as GLsizei,
+                0,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                atlas_pixels.as_ptr() as *const c_void,
+            ));
+            gpu_memory::track(GpuResource::Texture, atlas_texture, atlas_pixels.len());
+            debug_label::set(gl::TEXTURE, atlas_texture, &format!("{} atlas", font_file));
+            gl_check!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+
+            let projection_from_screen_uniform = shader_program.uniform("projection_from_screen");
+            let signed_distance_uniform = shader_program.uniform("signed_distance");
+            Ok(FontRenderer {
+                font,
+                font_scale,
+                ascent: v_metrics.ascent,
+                line_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+                glyphs,
+                rendering,
+                shader_program,
+                vertex_array_obj,
+                vertex_buffer_obj,
+                atlas_texture,
+                projection_from_screen_uniform,
+                signed_distance_uniform,
+                vertices: vec![],
+                world_text: vec![],
+            })
+        }
+    }
+
+    /// Distance in pixels between the baselines of consecutive lines at `scale`.
+    pub fn line_height(&self, scale: f32) -> f32 {
+        self.line_height * scale
+    }
+
+    /// Width and height in pixels `text` takes up at `scale`.
+    pub fn measure(&self, text: &str, scale: f32) -> (f32, f32) {
+        self.layout(text, (0.0_f32, 0.0_f32), scale, |_, _, _| {})
+    }
+
+    /// Queues `text` with its top left corner at `position`, in framebuffer pixels from the top
+    /// left. `scale` multiplies the size the glyphs were baked at. Lines break at `\n`.
+    pub fn add_text(&mut self, text: &str, position: (f32, f32), scale: f32, color: [f32; 4]) {
+        let mut vertices = mem::take(&mut self.vertices);
+        self.push_text(&mut vertices, text, position, 0.0_f32, scale, color);
+        self.vertices = vertices;
+    }
+
+    /// Queues `text` centered just above `anchor`, a point in the world, for the next
+    /// `draw_world`. Lines break at `\n`.
+    pub fn add_world_text(
+        &mut self,
+        text: &str,
+        anchor: &Vec3,
+        size: WorldTextSize,
+        color: [f32; 4],
+        depth: DepthMode,
+    ) {
+        self.world_text.push(WorldText {
+            text: text.to_string(),
+            anchor: *anchor,
+            size,
+            color,
+            depth,
+        });
+    }
+
+    /// Draws and clears the queued text.
+    pub fn draw(&mut self, framebuffer_size: (i32, i32)) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        self.use_program(framebuffer_size);
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            self.draw_vertices(&self.vertices);
+            gl_check!(gl::Disable(gl::BLEND));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+        self.vertices.clear();
+    }
+
+    /// Lays out the text queued with `add_world_text` as seen by `camera` and draws it into the
+    /// bound viewport, `framebuffer_size` pixels, depth tested text first, then clears it. Text
+    /// behind the camera, or too far away to read, is left out.
+    pub fn draw_world(
+        &mut self,
+        camera: &Camera,
+        projection_from_view: &Mat4,
+        framebuffer_size: (i32, i32),
+    ) {
+        if self.world_text.is_empty() {
+            return;
+        }
+        let (width, height) = (framebuffer_size.0 as f32, framebuffer_size.1 as f32);
+        let projection_from_world = projection_from_view * camera.view_matrix();
+        // Pixels from the top left and depth buffer value of a world point, if in front
+        let to_screen = |point: &Vec3| {
+            let clip = projection_from_world * glm::vec4(point.x, point.y, point.z, 1.0_f32);
+            if clip.w <= 0.0_f32 || clip.z > clip.w {
+                return None;
+            }
+            Some((
+                (clip.x / clip.w * 0.5_f32 + 0.5_f32) * width,
+                (0.5_f32 - clip.y / clip.w * 0.5_f32) * height,
+                clip.z / clip.w,
+            ))
+        };
+        let right = glm::normalize(&glm::cross(&camera.front, &camera.up));
+        let screen_up = glm::cross(&right, &camera.front);
+        let (mut tested_vertices, mut overlay_vertices) = (vec![], vec![]);
+        for world_text in self.world_text.iter() {
+            let (x, y, depth) = match to_screen(&world_text.anchor) {
+                Some(screen_position) => screen_position,
+                None => continue,
+            };
+            let scale = match world_text.size {
+                WorldTextSize::Screen(scale) => scale,
+                WorldTextSize::World(line_height) => {
+                    match to_screen(&(world_text.anchor + screen_up * line_height)) {
+                        Some((_, top_y, _)) => (y - top_y) / self.line_height,
+                        None => continue,
+                    }
+                }
+            };
+            if self.line_height(scale) < MIN_WORLD_LINE_HEIGHT {
+                continue;
+            }
+            let (text_width, text_height) = self.measure(&world_text.text, scale);
+            let vertices = match world_text.depth {
+                DepthMode::Tested => &mut tested_vertices,
+                DepthMode::Overlay => &mut overlay_vertices,
+            };
+            self.push_text(
+                vertices,
+                &world_text.text,
+                (x - text_width * 0.5_f32, y - text_height - WORLD_TEXT_GAP),
+                depth,
+                scale,
+                world_text.color,
+            );
+        }
+        self.world_text.clear();
+        if tested_vertices.is_empty() && overlay_vertices.is_empty() {
+            return;
+        }
+        self.use_program(framebuffer_size);
+        unsafe {
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            // Glyph quads are mostly empty, and would hide what lies behind them if they wrote depth
+            gl_check!(gl::DepthMask(gl::FALSE));
+            self.draw_vertices(&tested_vertices);
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            self.draw_vertices(&overlay_vertices);
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+            gl_check!(gl::DepthMask(gl::TRUE));
+            gl_check!(gl::Disable(gl::BLEND));
+        }
+    }
+
+    /// Appends the quads of `text`, laid out from `position` at `depth`, to `vertices`.
+    fn push_text(
+        &self,
+        vertices: &mut Vec<f32>,
+        text: &str,
+        position: (f32, f32),
+        depth: f32,
+        scale: f32,
+        color: [f32; 4],
+    ) {
+        self.layout(text, position, scale, |glyph, x, y| {
+            let (x0, y0) = (x + glyph.offset.0 * scale, y + glyph.offset.1 * scale);
+            let (x1, y1) = (x0 + glyph.size.0 * scale, y0 + glyph.size.1 * scale);
+            let (u0, v0) = glyph.tex_coords_min;
+            let (u1, v1) = glyph.tex_coords_max;
+            for (vertex_x, vertex_y, u, v) in [
+                (x0, y0, u0, v0),
+                (x0, y1, u0, v1),
+                (x1, y1, u1, v1),
+                (x1, y1, u1, v1),
+                (x1, y0, u1, v0),
+                (x0, y0, u0, v0),
+            ]
+            .iter()
+            {
+                vertices.extend_from_slice(&[*vertex_x, *vertex_y, depth, *u, *v]);
+                vertices.extend_from_slice(&color);
+            }
+        });
+    }
+
+    /// Sets up the program to lay text out in pixels of a `framebuffer_size` framebuffer.
+    fn use_program(&self, framebuffer_size: (i32, i32)) {
+        let (width, height) = framebuffer_size;
+        // Pixel coordinates with y pointing down, matching how text is laid out
+        let projection_from_screen = glm::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        self.shader_program.use_program();
+        self.projection_from_screen_uniform
+            .set_mat4f(&projection_from_screen);
+        self.signed_distance_uniform
+            .set_bool(self.rendering == GlyphRendering::SignedDistance);
+    }
+
+    /// Streams `vertices` into the vertex buffer and draws them with the glyph atlas, leaving
+    /// depth and blending to the caller.
+    unsafe fn draw_vertices(&self, vertices: &[f32]) {
+        if vertices.is_empty() {
+            return;
+        }
+        gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.atlas_texture));
+        gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+        gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer_obj));
+        gl_check!(gl::BufferData(
+            gl::ARRAY_BUFFER,
+            mem::size_of_val(vertices) as GLsizeiptr,
+            vertices.as_ptr() as *const c_void,
+            gl::STREAM_DRAW,
+        ));
+        gpu_memory::track(
+            GpuResource::Buffer,
+            self.vertex_buffer_obj,
+            mem::size_of_val(vertices),
+        );
+        gl_check!(gl::DrawArrays(
+            gl::TRIANGLES,
+            0,
+            (vertices.len() / VERTEX_SIZE) as GLsizei
+        ));
+        gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+        gl_check!(gl::BindVertexArray(0));
+        gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    }
+
+    /// Walks the glyphs of `text`, calling `emit` with each visible glyph and its pen position on
+    /// the baseline. Returns the size of the laid out text.
+    fn layout(
+        &self,
+        text: &str,
+        position: (f32, f32),
+        scale: f32,
+        mut emit: impl FnMut(&BakedGlyph, f32, f32),
+    ) -> (f32, f32) {
+        let line_height = self.line_height * scale;
+        let (mut x, mut baseline) = (position.0, position.1 + self.ascent * scale);
+        let mut width = 0.0_f32;
+        let mut lines = 1;
+        let mut previous: Option<GlyphId> = None;
+        for character in text.chars() {
+            if character == '\n' {
+                width = width.max(x - position.0);
+                x = position.0;
+                baseline += line_height;
+                lines += 1;
+                previous = None;
+                continue;
+            }
+            let glyph = &self.glyphs[glyph_index(character)];
+            if let Some(previous) = previous {
+                x += self.font.pair_kerning(self.font_scale, previous, glyph.id) * scale;
+            }
+            if glyph.size.0 > 0.0_f32 {
+                emit(glyph, x, baseline);
+            }
+            x += glyph.advance * scale;
+            previous = Some(glyph.id);
+        }
+        width = width.max(x - position.0);
+        (width, lines as f32 * line_height)
+    }
+}
+
+impl Drop for FontRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete(GlObject::Buffer, self.vertex_buffer_obj);
+            live_objects::delete(GlObject::Texture, self.atlas_texture);
+        }
+    }
+}
+
+fn glyph_index(character: char) -> usize {
+    let character = if (FIRST_CHAR..=LAST_CHAR).contains(&character) {
+        character
+    } else {
+        FALLBACK_CHAR
+    };
+    character as usize - FIRST_CHAR as usize
+}
+
+/// Rasterizes every glyph from `FIRST_CHAR` to `LAST_CHAR` and packs them into rows of a single
+/// channel atlas `ATLAS_WIDTH` pixels wide. Returns the glyphs, indexed by `glyph_index`, with the
+/// atlas height and pixels.
+fn bake_glyphs(
+    font: &Font,
+    font_scale: Scale,
+    rendering: GlyphRendering,
+) -> (Vec<BakedGlyph>, usize, Vec<u8>) {
+    let padding = match rendering {
+        GlyphRendering::Bitmap => GLYPH_PADDING,
+        GlyphRendering::SignedDistance => SDF_SPREAD,
+    };
+
+    struct GlyphBitmap {
+        id: GlyphId,
+        advance: f32,
+        origin: (i32, i32),
+        width: usize,
+        height: usize,
+        coverage: Vec<f32>,
+        atlas_position: (usize, usize),
+    }
+
+    // Each glyph bitmap with room for its padding, placed on shelves left to right
+    let mut bitmaps = vec![];
+    let (mut shelf_x, mut shelf_y, mut shelf_height) = (0_usize, 0_usize, 0_usize);
+    for character in FIRST_CHAR..=LAST_CHAR {
+        let glyph = font.glyph(character).scaled(font_scale);
+        let advance = glyph.h_metrics().advance_width;
+        let id = glyph.id();
+        let glyph = glyph.positioned(point(0.0_f32, 0.0_f32));
+        let bounding_box = match glyph.pixel_bounding_box() {
+            Some(bounding_box) => bounding_box,
+            None => {
+                bitmaps.push(GlyphBitmap {
+                    id,
+                    advance,
+                    origin: (0, 0),
+                    width: 0,
+                    height: 0,
+                    coverage: vec![],
+                    atlas_position: (0, 0),
+                });
+                continue;
+            }
+        };
+        let width = bounding_box.width() as usize + 2 * padding;
+        let height = bounding_box.height() as usize + 2 * padding;
+        let mut coverage = vec![0.0_f32; width * height];
+        glyph.draw(|x, y, value| {
+            coverage[(y as usize + padding) * width + x as usize + padding] = value;
+        });
+
+        if shelf_x + width > ATLAS_WIDTH {
+            shelf_x = 0;
+            shelf_y += shelf_height;
+            shelf_height = 0;
+        }
+        bitmaps.push(GlyphBitmap {
+            id,
+            advance,
+            origin: (
+                bounding_box.min.x - padding as i32,
+                bounding_box.min.y - padding as i32,
+            ),
+            width,
+            height,
+            coverage,
+            atlas_position: (shelf_x, shelf_y),
+        });
+        shelf_x += width;
+        shelf_height = shelf_height.max(height);
+    }
+    let atlas_height = (shelf_y + shelf_height).max(1);
+
+    let mut pixels = vec![0_u8; ATLAS_WIDTH * atlas_height];
+    let mut glyphs = Vec::with_capacity(bitmaps.len());
+    for bitmap in bitmaps {
+        let (width, height) = (bitmap.width, bitmap.height);
+        let (atlas_x, atlas_y) = bitmap.atlas_position;
+        let values = match rendering {
+            GlyphRendering::Bitmap => bitmap.coverage,
+            GlyphRendering::SignedDistance => {
+                signed_distance_field(&bitmap.coverage, width, height)
+            }
+        };
+        for y in 0..height {
+            for x in 0..width {
+                pixels[(atlas_y + y) * ATLAS_WIDTH + atlas_x + x] =
+                    (values[y * width + x] * 255.0_f32).round() as u8;
+            }
+        }
+        glyphs.push(BakedGlyph {
+            id: bitmap.id,
+            advance: bitmap.advance,
+            offset: (bitmap.origin.0 as f32, bitmap.origin.1 as f32),
+            size: (width as f32, height as f32),
+            tex_coords_min: (
+                atlas_x as f32 / ATLAS_WIDTH as f32,
+                atlas_y as f32 / atlas_height as f32,
+            ),
+            tex_coords_max: (
+                (atlas_x + width) as f32 / ATLAS_WIDTH as f32,
+                (atlas_y + height) as f32 / atlas_height as f32,
+            ),
+        });
+    }
+    (glyphs, atlas_height, pixels)
+}
+
+/// Converts a coverage bitmap into distances to the nearest pixel on the other side of the
+/// outline, mapped so 0.5 is the outline and 0 and 1 are `SDF_SPREAD` pixels outside and inside.
+fn signed_distance_field(coverage: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let spread = SDF_SPREAD as i32;
+    let is_inside = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < height
+            && coverage[y as usize * width + x as usize] >= 0.5_f32
+    };
+    let mut field = Vec::with_capacity(width * height);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let inside = is_inside(x, y);
+            let mut nearest_squared = (spread * spread) as f32;
+            for dy in -spread..=spread {
+                for dx in -spread..=spread {
+                    let distance_squared = (dx * dx + dy * dy) as f32;
+                    if distance_squared < nearest_squared && is_inside(x + dx, y + dy) != inside {
+                        nearest_squared = distance_squared;
+                    }
+                }
+            }
+            // The outline lies about halfway between a pixel and its nearest opposite neighbor
+            let distance = nearest_squared.sqrt() - 0.5_f32;
+            let signed_distance = if inside { distance } else { -distance };
+            field.push(
+                (0.5_f32 + 0.5_f32 * signed_distance / spread as f32).clamp(0.0_f32, 1.0_f32),
+            );
+        }
+    }
+    field
+}
@@ -0,0 +1,237 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::live_objects::{self, GlObject};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::{ptr, slice};
+
+// Frames a readback may stay in flight before it is mapped, so mapping never waits on the GPU
+const PBO_RING_SIZE: usize = 3;
+
+struct CapturedFrame {
+    index: usize,
+    width: i32,
+    height: i32,
+    pixels: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+struct PendingReadback {
+    width: i32,
+    height: i32,
+}
+
+/// Records the framebuffer as a numbered PNG sequence. Pixels are read into a ring of pixel
+/// buffer objects and only mapped a few frames later, then handed to a writer thread through a
+/// bounded queue; frames arriving while the queue is full are dropped rather than stalling.
+pub struct FrameCapture {
+    directory: PathBuf,
+    framerate: u32,
+    pixel_buffers: [GLuint; PBO_RING_SIZE],
+    pending: [Option<PendingReadback>; PBO_RING_SIZE],
+    next_slot: usize,
+    // Frames are numbered as they are queued so the sequence has no gaps when frames are dropped
+    queued_frames: usize,
+    dropped_frames: usize,
+    sender: Option<SyncSender<CapturedFrame>>,
+    writer: Option<JoinHandle<()>>,
+    context: GlContext,
+}
+
+impl FrameCapture {
+    pub fn start(
+        context: &GlContext,
+        directory: PathBuf,
+        framerate: u32,
+        max_queued_frames: usize,
+    ) -> Result<FrameCapture, String> {
+        fs::create_dir_all(&directory).map_err(|err| err.to_string())?;
+        let (sender, receiver) = mpsc::sync_channel::<CapturedFrame>(max_queued_frames);
+        let writer_directory = directory.clone();
+        let writer = thread::spawn(move || {
+            for frame in receiver {
+                let file_path = writer_directory.join(format!("frame_{:05}.png", frame.index));
+                if let Err(err) =
+                    write_rgba_png(&file_path, frame.width, frame.height, &frame.pixels)
+                {
+                    eprintln!("Failed writing {}: {}", file_path.display(), err);
+                }
+            }
+        });
+
+        let mut pixel_buffers = [0; PBO_RING_SIZE];
+        unsafe {
+            gl_check!(gl::GenBuffers(
+                PBO_RING_SIZE as GLsizei,
+                pixel_buffers.as_mut_ptr()
+            ));
+            live_objects::created_all(GlObject::Buffer, &pixel_buffers);
+            for (slot, pixel_buffer) in pixel_buffers.iter().enumerate() {
+                gl_check!(gl::BindBuffer(gl::PIXEL_PACK_BUFFER, *pixel_buffer));
+                debug_label::set(
+                    gl::BUFFER,
+                    *pixel_buffer,
+                    &format!("frame capture {}", slot),
+                );
+            }
+            gl_check!(gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0));
+        }
+        Ok(FrameCapture {
+            directory,
+            framerate,
+            pixel_buffers,
+            pending: [None; PBO_RING_SIZE],
+            next_slot: 0,
+            queued_frames: 0,
+            dropped_frames: 0,
+            sender: Some(sender),
+            writer: Some(writer),
+            context: *context,
+        })
+    }
+
+    /// Queues a readback of the current read framebuffer. Call after rendering, before swapping.
+    pub fn capture(&mut self, width: i32, height: i32) {
+        let slot = self.next_slot;
+        // The slot about to be reused holds the oldest readback, which has had time to finish
+        self.finish_readback(slot);
+
+        unsafe {
+            gl_check!(gl::BindBuffer(
+                gl::PIXEL_PACK_BUFFER,
+                self.pixel_buffers[slot]
+            ));
+            gl_check!(gl::BufferData(
+                gl::PIXEL_PACK_BUFFER,
+                (width * height * 4) as GLsizeiptr,
+                ptr::null(),
+                gl::STREAM_READ,
+            ));
+            gpu_memory::track(
+                GpuResource::Buffer,
+                self.pixel_buffers[slot],
+                (width * height * 4) as usize,
+            );
+            gl_check!(gl::ReadPixels(
+                0,
+                0,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ptr::null_mut()
+            ));
+            gl_check!(gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0));
+        }
+        self.pending[slot] = Some(PendingReadback { width, height });
+        self.next_slot = (slot + 1) % PBO_RING_SIZE;
+    }
+
+    /// Flushes the readbacks still in flight, waits for the writer to drain its queue and
+    /// prints an ffmpeg command that encodes the sequence.
+    pub fn finish(mut self) {
+        for offset in 0..PBO_RING_SIZE {
+            self.finish_readback((self.next_slot + offset) % PBO_RING_SIZE);
+        }
+        self.delete_pixel_buffers();
+        // Closing the channel ends the writer loop
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                eprintln!("Frame capture writer thread panicked");
+            }
+        }
+
+        println!(
+            "Captured {} frames to {} ({} dropped)",
+            self.queued_frames,
+            self.directory.display(),
+            self.dropped_frames
+        );
+        println!(
+            "Encode with: ffmpeg -framerate {} -i {} -pix_fmt yuv420p {}",
+            self.framerate,
+            self.directory.join("frame_%05d.png").display(),
+            self.directory.join("capture.mp4").display()
+        );
+    }
+
+    fn delete_pixel_buffers(&mut self) {
+        for pixel_buffer in self.pixel_buffers.iter() {
+            live_objects::release(&self.context, GlObject::Buffer, *pixel_buffer);
+        }
+        self.pixel_buffers = [0; PBO_RING_SIZE];
+    }
+
+    fn finish_readback(&mut self, slot: usize) {
+        let readback = match self.pending[slot].take() {
+            Some(readback) => readback,
+            None => return,
+        };
+        let byte_count = (readback.width * readback.height * 4) as usize;
+        let mut pixels = Vec::with_capacity(byte_count);
+        unsafe {
+            gl_check!(gl::BindBuffer(
+                gl::PIXEL_PACK_BUFFER,
+                self.pixel_buffers[slot]
+            ));
+            let mapped = gl_check!(gl::MapBufferRange(
+                gl::PIXEL_PACK_BUFFER,
+                0,
+                byte_count as GLsizeiptr,
+                gl::MAP_READ_BIT
+            )) as *const u8;
+            if !mapped.is_null() {
+                pixels.extend_from_slice(slice::from_raw_parts(mapped, byte_count));
+                gl_check!(gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER));
+            }
+            gl_check!(gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0));
+        }
+        if pixels.is_empty() {
+            self.dropped_frames += 1;
+            return;
+        }
+
+        let frame = CapturedFrame {
+            index: self.queued_frames,
+            width: readback.width,
+            height: readback.height,
+            pixels,
+        };
+        match &self.sender {
+            Some(sender) if sender.try_send(frame).is_ok() => self.queued_frames += 1,
+            _ => self.dropped_frames += 1,
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        // Only when dropped without `finish`
+        self.delete_pixel_buffers();
+    }
+}
+
+/// Writes RGBA8 pixels read back from GL, whose rows start at the bottom of the image.
+pub fn write_rgba_png(
+    file_path: &Path,
+    width: i32,
+    height: i32,
+    pixels: &[u8],
+) -> Result<(), String> {
+    let row_size = (width * 4) as usize;
+    let flipped: Vec<u8> = pixels.chunks(row_size).rev().flatten().copied().collect();
+    image::save_buffer(
+        file_path,
+        &flipped,
+        width as u32,
+        height as u32,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|err| err.to_string())
+}
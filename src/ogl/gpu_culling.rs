@@ -0,0 +1,483 @@
+use gl::types::*;
+
+use crate::math::frustum::Frustum;
+use crate::math::glm::{self, Mat4};
+use crate::math::random::{random_point_in_sphere, random_unit_vector, Rng};
+use crate::ogl::buffer::Buffer;
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{Camera, Mesh, MeshData, ShaderProgram, Uniform, VertexAttribute};
+use crate::ogl::indirect::{DrawElementsIndirectCommand, IndirectBuffer};
+use crate::ogl::mesh_pool::MeshPool;
+use crate::ogl::renderer::Renderer;
+use std::ptr;
+use std::rc::Rc;
+
+/// Shader storage bindings of the instances, the draw commands and the visible instance list.
+pub const INSTANCES_BINDING: GLuint = 2;
+pub const COMMANDS_BINDING: GLuint = 3;
+pub const VISIBLE_BINDING: GLuint = 4;
+const COMPUTE_GROUP_SIZE: usize = 256;
+// Per-instance attribute of the visible instance list in the meshes' vertex array
+const INSTANCE_INDEX_LOCATION: GLuint = 2;
+
+const CULL_COMPUTE_SHADER_SOURCE: &str = r#"
+#version 430 core
+layout (local_size_x = COMPUTE_GROUP_SIZE) in;
+
+struct Instance {
+    mat4 world_from_object;
+    // World space center and radius
+    vec4 bounds;
+    // Index of the mesh, in x
+    uvec4 mesh;
+};
+
+// Laid out like DrawElementsIndirectCommand
+struct Command {
+    uint count;
+    uint instance_count;
+    uint first_index;
+    int base_vertex;
+    uint base_instance;
+};
+
+layout (std430, binding = 2) readonly buffer Instances {
+    Instance instances[];
+};
+
+layout (std430, binding = 3) buffer Commands {
+    Command commands[];
+};
+
+layout (std430, binding = 4) writeonly buffer Visible {
+    uint visible[];
+};
+
+uniform int instance_count;
+// Left, right, bottom, top, near and far, facing inwards
+uniform vec4 frustum_planes[6];
+uniform bool culling;
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= uint(instance_count)) {
+        return;
+    }
+    vec4 bounds = instances[index].bounds;
+    if (culling) {
+        for (int i = 0; i < 6; ++i) {
+            if (dot(frustum_planes[i].xyz, bounds.xyz) + frustum_planes[i].w < -bounds.w) {
+                return;
+            }
+        }
+    }
+    // Each mesh's survivors are packed from its command's base instance on
+    uint mesh = instances[index].mesh.x;
+    uint slot = atomicAdd(commands[mesh].instance_count, 1u);
+    visible[commands[mesh].base_instance + slot] = index;
+}
+"#;
+
+const DRAW_VERTEX_SHADER_SOURCE: &str = r#"
+#version 430 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 1) in vec3 a_normal;
+// Read from the visible list from the draw's base instance on
+layout (location = 2) in uint a_instance_index;
+
+struct Instance {
+    mat4 world_from_object;
+    vec4 bounds;
+    uvec4 mesh;
+};
+
+layout (std430, binding = 2) readonly buffer Instances {
+    Instance instances[];
+};
+
+uniform mat4 projection_from_world;
+
+out vec3 o_normal;
+out vec3 o_color;
+
+void main() {
+    mat4 world_from_object = instances[a_instance_index].world_from_object;
+    gl_Position = projection_from_world * world_from_object * vec4(a_pos, 1.0f);
+    o_normal = mat3(world_from_object) * a_normal;
+    // A hue per instance, so neighbours stand apart
+    float hue = fract(float(a_instance_index) * 0.618034f);
+    o_color = 0.55f + 0.45f * cos(6.283185f * (hue + vec3(0.0f, 0.33f, 0.67f)));
+}
+"#;
+
+const DRAW_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 430 core
+in vec3 o_normal;
+in vec3 o_color;
+
+uniform vec3 light_direction;
+
+out vec4 frag_color;
+
+void main() {
+    float diffuse = max(dot(normalize(o_normal), light_direction), 0.0f);
+    frag_color = vec4(o_color * (0.25f + 0.75f * diffuse), 1.0f);
+}
+"#;
+
+/// A mesh placed in the world, to be culled on the GPU.
+#[derive(Clone, Debug)]
+pub struct CulledInstance {
+    /// Index into the meshes the culler was built with
+    pub mesh: usize,
+    pub world_from_object: Mat4,
+}
+
+/// An instance as the shaders read it from the `Instances` block.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuInstance {
+    world_from_object: [f32; 16],
+    bounds: [f32; 4],
+    mesh: [u32; 4],
+}
+
+/// Instances frustum culled by a compute shader every frame and drawn with a single
+/// `glMultiDrawElementsIndirect` call whose instance counts that shader writes, so the CPU
+/// never touches them after setup. Every instance's bounding sphere is tested against the
+/// frustum, and those that pass are appended to their mesh's stretch of the visible list, which
+/// the vertex shader reads the instances through. Needs OpenGL 4.3.
+pub struct GpuCuller {
+    meshes: Vec<Rc<Mesh>>,
+    instance_count: usize,
+    /// Instances and their world space bounds, read by both passes
+    instances: Buffer,
+    /// Indices of the instances that passed, in stretches starting at each command's base
+    /// instance
+    visible: Buffer,
+    /// One command per mesh, reset to no instances before every cull
+    commands: Vec<DrawElementsIndirectCommand>,
+    indirect_buffer: IndirectBuffer,
+    cull_program: ShaderProgram,
+    instance_count_uniform: Uniform,
+    frustum_plane_uniforms: Vec<Uniform>,
+    culling_uniform: Uniform,
+    draw_program: ShaderProgram,
+    projection_from_world_uniform: Uniform,
+    light_direction_uniform: Uniform,
+}
+
+impl GpuCuller {
+    /// Uploads `instances` of `meshes`, which all need positions at location 0 and normals at
+    /// location 1, and builds both programs.
+    pub fn new(
+        context: &GlContext,
+        meshes: &[MeshData],
+        instances: &[CulledInstance],
+    ) -> Result<GpuCuller, String> {
+        unsafe {
+            let cull_program = ShaderProgram::with_compute_shader(
+                context,
+                &CULL_COMPUTE_SHADER_SOURCE
+                    .replace("COMPUTE_GROUP_SIZE", &format!("{}", COMPUTE_GROUP_SIZE)),
+            )?;
+            let draw_program = ShaderProgram::with_shaders(
+                context,
+                DRAW_VERTEX_SHADER_SOURCE,
+                DRAW_FRAGMENT_SHADER_SOURCE,
+            )?;
+            cull_program.set_label("gpu culling");
+            draw_program.set_label("gpu culled instances");
+
+            let attributes = meshes
+                .first()
+                .map(|mesh| mesh.attributes.clone())
+                .unwrap_or_default();
+            let mut mesh_pool = MeshPool::new(&attributes);
+            for mesh in meshes.iter() {
+                mesh_pool.add(mesh)?;
+            }
+            // Meshes are bounded by a sphere around their origin, which scales with them
+            let mesh_radii: Vec<f32> = meshes.iter().map(bounding_radius).collect();
+            let gpu_instances = instances
+                .iter()
+                .map(|instance| {
+                    if instance.mesh >= meshes.len() {
+                        return Err(format!(
+                            "Culled instance of mesh {} out of {}",
+                            instance.mesh,
+                            meshes.len()
+                        ));
+                    }
+                    let m = &instance.world_from_object;
+                    let scale = (0..3)
+                        .map(|i| glm::length(&glm::xyz(&glm::column(m, i))))
+                        .fold(0.0_f32, f32::max);
+                    let center = glm::column(m, 3);
+                    let mut world_from_object = [0.0_f32; 16];
+                    world_from_object.copy_from_slice(glm::value_ptr(m));
+                    Ok(GpuInstance {
+                        world_from_object,
+                        bounds: [
+                            center.x,
+                            center.y,
+                            center.z,
+                            mesh_radii[instance.mesh] * scale,
+                        ],
+                        mesh: [instance.mesh as u32, 0, 0, 0],
+                    })
+                })
+                .collect::<Result<Vec<GpuInstance>, String>>()?;
+            let pooled_meshes = mesh_pool.build(context)?;
+
+            // Each mesh gets room in the visible list for all of its instances
+            let mut commands = Vec::with_capacity(pooled_meshes.len());
+            let mut base_instance = 0;
+            for (mesh_i, mesh) in pooled_meshes.iter().enumerate() {
+                commands.push(DrawElementsIndirectCommand {
+                    count: mesh.index_count() as GLuint,
+                    instance_count: 0,
+                    first_index: mesh.first_index() as GLuint,
+                    base_vertex: 0,
+                    base_instance,
+                });
+                base_instance += instances
+                    .iter()
+                    .filter(|instance| instance.mesh == mesh_i)
+                    .count() as GLuint;
+            }
+
+            let mut instance_buffer = Buffer::new(context, gl::STATIC_DRAW);
+            instance_buffer.set_data(context, &gpu_instances);
+            instance_buffer.set_label(context, "gpu culled instances");
+            let mut visible = Buffer::new(context, gl::DYNAMIC_COPY);
+            visible.set_data(context, &vec![0_u32; instances.len().max(1)]);
+            visible.set_label(context, "gpu culled visible instances");
+            // The pooled meshes share one vertex array, which the visible list is added to
+            if let Some(mesh) = pooled_meshes.first() {
+                gl_check!(gl::BindVertexArray(mesh.vertex_array_obj()));
+                gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, visible.id));
+                gl_check!(gl::EnableVertexAttribArray(INSTANCE_INDEX_LOCATION));
+                gl_check!(gl::VertexAttribIPointer(
+                    INSTANCE_INDEX_LOCATION,
+                    1,
+                    gl::UNSIGNED_INT,
+                    0,
+                    ptr::null(),
+                ));
+                gl_check!(gl::VertexAttribDivisor(INSTANCE_INDEX_LOCATION, 1));
+                gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+                gl_check!(gl::BindVertexArray(0));
+                mesh.set_label("gpu culled meshes");
+            }
+
+            Ok(GpuCuller {
+                meshes: pooled_meshes,
+                instance_count: instances.len(),
+                instances: instance_buffer,
+                visible,
+                commands,
+                indirect_buffer: IndirectBuffer::new(context),
+                instance_count_uniform: cull_program.uniform("instance_count"),
+                frustum_plane_uniforms: (0..6)
+                    .map(|i| cull_program.uniform(&format!("frustum_planes[{}]", i)))
+                    .collect(),
+                culling_uniform: cull_program.uniform("culling"),
+                cull_program,
+                projection_from_world_uniform: draw_program.uniform("projection_from_world"),
+                light_direction_uniform: draw_program.uniform("light_direction"),
+                draw_program,
+            })
+        }
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instance_count
+    }
+
+    /// Culls the instances against `camera`'s frustum (or the frozen one), unless the renderer's
+    /// frustum culling is off, then draws those that passed.
+    pub fn draw(&mut self, camera: &Camera, renderer: &mut Renderer) {
+        if self.instance_count == 0 {
+            return;
+        }
+        let projection_from_world = renderer.projection_from_view() * camera.view_matrix();
+        let frustum = if renderer.culling_frozen() {
+            renderer.culling_frustum().clone()
+        } else {
+            Frustum::from_matrix(&projection_from_world)
+        };
+        unsafe {
+            self.cull(&frustum, renderer.frustum_culling);
+
+            self.draw_program.use_program();
+            self.projection_from_world_uniform
+                .set_mat4f(&projection_from_world);
+            let light_direction = glm::normalize(&glm::vec3(0.4_f32, 0.8_f32, 0.45_f32));
+            self.light_direction_uniform.set_vec3f([
+                light_direction.x,
+                light_direction.y,
+                light_direction.z,
+            ]);
+            let wireframe = renderer.wireframe();
+            if wireframe {
+                gl_check!(gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE));
+            }
+            gl_check!(gl::BindVertexArray(self.meshes[0].vertex_array_obj()));
+            self.indirect_buffer.multi_draw(0, self.commands.len());
+            gl_check!(gl::BindVertexArray(0));
+            if wireframe {
+                gl_check!(gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL));
+            }
+        }
+        // The instances drawn are only counted on the GPU
+        renderer.stats.record_draw(0);
+    }
+
+    /// Resets the commands to no instances and runs the compute pass filling them in.
+    unsafe fn cull(&mut self, frustum: &Frustum, culling: bool) {
+        self.indirect_buffer.clear();
+        for command in self.commands.iter() {
+            self.indirect_buffer.push(*command);
+        }
+        self.indirect_buffer.upload();
+
+        self.cull_program.use_program();
+        self.instance_count_uniform
+            .set_int(self.instance_count as GLint);
+        for (uniform, plane) in self
+            .frustum_plane_uniforms
+            .iter()
+            .zip(frustum.planes.iter())
+        {
+            let normal = &plane.normal;
+            uniform.set_vec4f([normal.x, normal.y, normal.z, plane.distance]);
+        }
+        self.culling_uniform.set_bool(culling);
+        for (binding, buffer) in [
+            (INSTANCES_BINDING, self.instances.id),
+            (COMMANDS_BINDING, self.indirect_buffer.id),
+            (VISIBLE_BINDING, self.visible.id),
+        ]
+        .iter()
+        {
+            gl_check!(gl::BindBufferBase(
+                gl::SHADER_STORAGE_BUFFER,
+                *binding,
+                *buffer
+            ));
+        }
+        let groups = self.instance_count.div_ceil(COMPUTE_GROUP_SIZE);
+        gl_check!(gl::DispatchCompute(groups as GLuint, 1, 1));
+        // Drawing reads the counts as commands and the visible list as an attribute
+        gl_check!(gl::MemoryBarrier(
+            gl::COMMAND_BARRIER_BIT | gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT
+        ));
+    }
+}
+
+/// `count` instances of `mesh_count` meshes scattered through a ball of `radius` around the
+/// origin, turned every which way and between half and one and a half times their size.
+pub fn scatter_instances(
+    count: usize,
+    mesh_count: usize,
+    radius: f32,
+    seed: u64,
+) -> Vec<CulledInstance> {
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .map(|_| {
+            let position = random_point_in_sphere(&mut rng, radius);
+            let axis = random_unit_vector(&mut rng);
+            let angle = rng.range(0.0_f32, 360.0_f32).to_radians();
+            let scale = rng.range(0.5_f32, 1.5_f32);
+            let world_from_object = glm::scale(
+                &glm::rotate(&glm::translate(&glm::identity(), &position), angle, &axis),
+                &glm::vec3(scale, scale, scale),
+            );
+            CulledInstance {
+                mesh: rng.index(mesh_count.max(1)),
+                world_from_object,
+            }
+        })
+        .collect()
+}
+
+/// A unit cube and an octahedron of the same size, flat shaded, with the layout `GpuCuller`
+/// draws: positions at location 0 and normals at location 1.
+pub fn shapes() -> Vec<MeshData> {
+    let attributes = vec![
+        VertexAttribute {
+            location: 0,
+            components: 3,
+        },
+        VertexAttribute {
+            location: 1,
+            components: 3,
+        },
+    ];
+    let mut cube = MeshData {
+        vertices: vec![],
+        indices: Some(vec![]),
+        attributes: attributes.clone(),
+    };
+    for axis in 0..3 {
+        for &sign in [-1.0_f32, 1.0_f32].iter() {
+            let mut normal = [0.0_f32; 3];
+            normal[axis] = sign;
+            // Two axes across the face, ordered so its corners wind counterclockwise
+            let (u, v) = if sign > 0.0_f32 {
+                ((axis + 1) % 3, (axis + 2) % 3)
+            } else {
+                ((axis + 2) % 3, (axis + 1) % 3)
+            };
+            let first = (cube.vertices.len() / 6) as u32;
+            for &(a, b) in [(-1.0_f32, -1.0_f32), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+                let mut position = [0.0_f32; 3];
+                position[axis] = 0.5_f32 * sign;
+                position[u] = 0.5_f32 * a;
+                position[v] = 0.5_f32 * b;
+                cube.vertices.extend_from_slice(&position);
+                cube.vertices.extend_from_slice(&normal);
+            }
+            if let Some(indices) = cube.indices.as_mut() {
+                indices.extend([0, 1, 2, 0, 2, 3].iter().map(|corner| first + corner));
+            }
+        }
+    }
+    let mut octahedron = MeshData {
+        vertices: vec![],
+        indices: None,
+        attributes,
+    };
+    for octant in 0..8 {
+        let signs = [
+            if octant & 1 == 0 { -1.0_f32 } else { 1.0_f32 },
+            if octant & 2 == 0 { -1.0_f32 } else { 1.0_f32 },
+            if octant & 4 == 0 { -1.0_f32 } else { 1.0_f32 },
+        ];
+        let normal = glm::normalize(&glm::make_vec3(&signs));
+        // Tips on the three axes, flipped where an odd number of signs keeps it facing out
+        let mut tips = [0, 1, 2];
+        if signs[0] * signs[1] * signs[2] < 0.0_f32 {
+            tips.swap(1, 2);
+        }
+        for &axis in tips.iter() {
+            let mut position = [0.0_f32; 3];
+            position[axis] = 0.7_f32 * signs[axis];
+            octahedron.vertices.extend_from_slice(&position);
+            octahedron
+                .vertices
+                .extend_from_slice(&[normal.x, normal.y, normal.z]);
+        }
+    }
+    vec![cube, octahedron]
+}
+
+/// Distance from the origin to the farthest vertex of `mesh`, whose positions come first.
+fn bounding_radius(mesh: &MeshData) -> f32 {
+    mesh.vertices
+        .chunks(mesh.floats_per_vertex().max(1))
+        .map(|vertex| glm::length(&glm::make_vec3(&vertex[..3])))
+        .fold(0.0_f32, f32::max)
+}
@@ -0,0 +1,118 @@
+use gl::types::*;
+
+use crate::ogl::capabilities;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// NVX_gpu_memory_info and ATI_meminfo queries, which the core profile bindings leave out
+const GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX: GLenum = 0x9048;
+const GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX: GLenum = 0x9049;
+const TEXTURE_FREE_MEMORY_ATI: GLenum = 0x87FC;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GpuResource {
+    Buffer,
+    Texture,
+    Renderbuffer,
+}
+
+// Storage size of every live object allocated through the wrappers. Behind a lock because the
+// upload worker allocates from its own thread.
+static ALLOCATIONS: Mutex<BTreeMap<(GpuResource, GLuint), usize>> = Mutex::new(BTreeMap::new());
+
+/// Bytes allocated through the wrappers, by resource kind.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuMemoryUsage {
+    pub buffers: usize,
+    pub textures: usize,
+    pub renderbuffers: usize,
+}
+
+/// Video memory as the driver reports it, in KiB.
+#[derive(Clone, Copy, Debug)]
+pub struct DriverMemory {
+    pub available: i64,
+    /// Only NVX_gpu_memory_info reports a total
+    pub total: Option<i64>,
+}
+
+/// Records `bytes` of storage for object `id`, replacing what was recorded for it before, as
+/// when a buffer is respecified with a new size.
+pub fn track(resource: GpuResource, id: GLuint, bytes: usize) {
+    ALLOCATIONS.lock().unwrap().insert((resource, id), bytes);
+}
+
+/// Forgets object `id`, once it is deleted.
+pub fn untrack(resource: GpuResource, id: GLuint) {
+    ALLOCATIONS.lock().unwrap().remove(&(resource, id));
+}
+
+/// Live objects of `resource` allocated through the wrappers, by ascending name.
+#[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+pub fn ids(resource: GpuResource) -> Vec<GLuint> {
+    ALLOCATIONS
+        .lock()
+        .unwrap()
+        .keys()
+        .filter(|(tracked, _)| *tracked == resource)
+        .map(|&(_, id)| id)
+        .collect()
+}
+
+pub fn usage() -> GpuMemoryUsage {
+    let mut usage = GpuMemoryUsage::default();
+    for (&(resource, _), &bytes) in ALLOCATIONS.lock().unwrap().iter() {
+        match resource {
+            GpuResource::Buffer => usage.buffers += bytes,
+            GpuResource::Texture => usage.textures += bytes,
+            GpuResource::Renderbuffer => usage.renderbuffers += bytes,
+        }
+    }
+    usage
+}
+
+/// Bytes of a `width` by `height` texture with `layers` layers of `bytes_per_pixel`, plus its
+/// mip chain (a third more) when `mipmapped`.
+pub fn texture_bytes(
+    width: u32,
+    height: u32,
+    layers: u32,
+    bytes_per_pixel: usize,
+    mipmapped: bool,
+) -> usize {
+    let base = width as usize * height as usize * layers as usize * bytes_per_pixel;
+    if mipmapped {
+        base + base / 3
+    } else {
+        base
+    }
+}
+
+/// What the driver reports through NVX_gpu_memory_info or ATI_meminfo, where either is there.
+pub unsafe fn driver_memory() -> Option<DriverMemory> {
+    if capabilities::nvx_gpu_memory_info() {
+        let (mut available, mut total) = (0, 0);
+        gl_check!(gl::GetIntegerv(
+            GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX,
+            &mut available
+        ));
+        gl_check!(gl::GetIntegerv(
+            GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX,
+            &mut total
+        ));
+        Some(DriverMemory {
+            available: available as i64,
+            total: Some(total as i64),
+        })
+    } else if capabilities::ati_meminfo() {
+        // Free memory in the pool, largest free block, then the same for auxiliary memory
+        let mut free = [0; 4];
+        gl_check!(gl::GetIntegerv(TEXTURE_FREE_MEMORY_ATI, free.as_mut_ptr()));
+        Some(DriverMemory {
+            available: free[0] as i64,
+            total: None,
+        })
+    } else {
+        None
+    }
+}
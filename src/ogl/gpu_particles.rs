@@ -0,0 +1,520 @@
+use gl::types::*;
+
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::math::random::{random_point_in_sphere, Rng};
+use crate::ogl::buffer::Buffer;
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::stats::FrameStats;
+use std::ffi::c_void;
+use std::mem;
+
+/// Shader storage binding the compute backend reads and writes particles through.
+#[cfg(feature = "gl45")]
+pub const PARTICLES_BINDING: GLuint = 1;
+/// Attractors the simulation shaders have room for.
+pub const MAX_ATTRACTORS: usize = 4;
+// Floats per particle: position and age, then velocity and lifetime
+const PARTICLE_FLOATS: usize = 8;
+#[cfg(feature = "gl45")]
+const COMPUTE_GROUP_SIZE: usize = 256;
+
+// Shared by both backends, pasted in after their `#version` and declarations
+const SIMULATION_SOURCE: &str = r#"
+uniform float dt;
+uniform float time;
+uniform vec3 emitter_position;
+uniform float emitter_radius;
+uniform float lifetime;
+uniform float drag;
+uniform float turbulence;
+uniform float turbulence_scale;
+// Position, then strength; negative strengths repel
+uniform vec4 attractors[MAX_ATTRACTORS];
+uniform int attractor_count;
+
+float hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return float(x) / 4294967295.0f;
+}
+
+// Every component depends only on the other two coordinates, so the field has no divergence and
+// swirls particles around without bunching them up
+vec3 turbulence_at(vec3 p) {
+    p *= turbulence_scale;
+    return vec3(
+        sin(p.y * 1.7f + time) + sin(p.z * 2.3f - time * 0.7f),
+        sin(p.z * 1.3f + time * 0.9f) + sin(p.x * 2.1f - time * 0.4f),
+        sin(p.x * 1.9f - time * 0.8f) + sin(p.y * 2.7f + time * 0.5f));
+}
+
+void simulate(uint index, inout vec4 position_age, inout vec4 velocity_lifetime) {
+    vec3 position = position_age.xyz;
+    vec3 velocity = velocity_lifetime.xyz;
+    float age = position_age.w + dt;
+    float particle_lifetime = velocity_lifetime.w;
+    if (age >= particle_lifetime) {
+        uint seed = index * 4u + uint(time * 60.0f) * 2654435761u;
+        vec3 direction = normalize(vec3(hash(seed), hash(seed + 1u), hash(seed + 2u)) - 0.5f);
+        position = emitter_position + direction * emitter_radius * hash(seed + 3u);
+        velocity = direction * 0.5f;
+        age = 0.0f;
+        particle_lifetime = lifetime * (0.5f + hash(seed ^ 0x5bd1e995u));
+    }
+    vec3 acceleration = turbulence * turbulence_at(position);
+    for (int i = 0; i < attractor_count; ++i) {
+        vec3 to_attractor = attractors[i].xyz - position;
+        // Softened so particles passing through an attractor are not flung away
+        float distance_squared = dot(to_attractor, to_attractor) + 0.25f;
+        acceleration += attractors[i].w * to_attractor * inversesqrt(distance_squared) / distance_squared;
+    }
+    velocity = (velocity + acceleration * dt) * max(1.0f - drag * dt, 0.0f);
+    position += velocity * dt;
+    position_age = vec4(position, age);
+    velocity_lifetime = vec4(velocity, particle_lifetime);
+}
+"#;
+
+const FEEDBACK_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec4 a_position_age;
+layout (location = 1) in vec4 a_velocity_lifetime;
+
+out vec4 o_position_age;
+out vec4 o_velocity_lifetime;
+"#;
+
+const FEEDBACK_VERTEX_SHADER_MAIN: &str = r#"
+void main() {
+    o_position_age = a_position_age;
+    o_velocity_lifetime = a_velocity_lifetime;
+    simulate(uint(gl_VertexID), o_position_age, o_velocity_lifetime);
+}
+"#;
+
+// Never runs, with rasterization discarded, but ES will not link a program without one
+const FEEDBACK_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(0.0f);
+}
+"#;
+
+#[cfg(feature = "gl45")]
+const COMPUTE_SHADER_SOURCE: &str = r#"
+#version 430 core
+layout (local_size_x = COMPUTE_GROUP_SIZE) in;
+
+struct Particle {
+    vec4 position_age;
+    vec4 velocity_lifetime;
+};
+
+layout (std430, binding = 1) buffer Particles {
+    Particle particles[];
+};
+
+uniform int particle_count;
+"#;
+
+#[cfg(feature = "gl45")]
+const COMPUTE_SHADER_MAIN: &str = r#"
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= uint(particle_count)) {
+        return;
+    }
+    simulate(index, particles[index].position_age, particles[index].velocity_lifetime);
+}
+"#;
+
+const POINT_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec4 a_position_age;
+layout (location = 1) in vec4 a_velocity_lifetime;
+
+uniform mat4 projection_from_world;
+
+out vec4 o_color;
+
+void main() {
+    gl_Position = projection_from_world * vec4(a_position_age.xyz, 1.0f);
+    gl_PointSize = 2.0f;
+    // Slow particles are a cool blue, fast ones a hot orange, and all fade out as they age
+    float speed = length(a_velocity_lifetime.xyz);
+    vec3 color = mix(vec3(0.1f, 0.3f, 1.0f), vec3(1.0f, 0.5f, 0.1f), clamp(speed / 6.0f, 0.0f, 1.0f));
+    float fade = 1.0f - a_position_age.w / a_velocity_lifetime.w;
+    o_color = vec4(color, 0.15f * fade);
+}
+"#;
+
+const POINT_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec4 o_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = o_color;
+}
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GpuParticleBackend {
+    /// A vertex shader streams every particle from one buffer into another, swapping each step
+    TransformFeedback,
+    /// A compute shader updates the particles in place in a shader storage buffer
+    #[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+    Compute,
+}
+
+/// A point pulling particles in by `strength` over distance squared, pushing them away when
+/// negative.
+#[derive(Clone, Copy, Debug)]
+pub struct Attractor {
+    pub position: Vec3,
+    pub strength: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct GpuParticleSettings {
+    pub count: usize,
+    /// Particles respawn within `emitter_radius` of `emitter_position`
+    pub emitter_position: Vec3,
+    pub emitter_radius: f32,
+    /// Average seconds a particle lives; each lives between half and one and a half times that
+    pub lifetime: f32,
+    /// Fraction of velocity lost per second
+    pub drag: f32,
+    /// Strength and spatial frequency of the swirling turbulence field
+    pub turbulence: f32,
+    pub turbulence_scale: f32,
+    /// Up to `MAX_ATTRACTORS`; the rest are ignored
+    pub attractors: Vec<Attractor>,
+}
+
+/// Uniforms of the simulation code both backends share.
+struct SimulationUniforms {
+    dt: Uniform,
+    time: Uniform,
+    emitter_position: Uniform,
+    emitter_radius: Uniform,
+    lifetime: Uniform,
+    drag: Uniform,
+    turbulence: Uniform,
+    turbulence_scale: Uniform,
+    attractors: Vec<Uniform>,
+    attractor_count: Uniform,
+}
+
+/// Particles simulated and drawn entirely on the GPU, so a million of them cost no CPU time or
+/// uploads after the initial state. Drawn as additive points.
+pub struct GpuParticleSystem {
+    pub backend: GpuParticleBackend,
+    pub settings: GpuParticleSettings,
+    /// Transform feedback ping-pongs between both; compute only uses the first
+    buffers: [Buffer; 2],
+    /// Reads the buffer of the same index as particle attributes, for simulating and drawing
+    vertex_arrays: [GLuint; 2],
+    /// Buffer holding the latest state
+    current: usize,
+    time: f32,
+    simulation_program: ShaderProgram,
+    simulation_uniforms: SimulationUniforms,
+    #[cfg(feature = "gl45")]
+    particle_count_uniform: Uniform,
+    point_program: ShaderProgram,
+    projection_from_world_uniform: Uniform,
+}
+
+impl GpuParticleBackend {
+    /// Compute shaders where OpenGL 4.3 allows, transform feedback otherwise.
+    pub fn detect() -> GpuParticleBackend {
+        #[cfg(feature = "gl45")]
+        {
+            if capabilities::compute_shaders() {
+                return GpuParticleBackend::Compute;
+            }
+        }
+        GpuParticleBackend::TransformFeedback
+    }
+}
+
+impl GpuParticleSettings {
+    /// A cloud swirling between two attractors, one to each side of the emitter.
+    pub fn attractor_cloud(count: usize) -> GpuParticleSettings {
+        GpuParticleSettings {
+            count,
+            emitter_position: glm::vec3(0.0_f32, 3.0_f32, 0.0_f32),
+            emitter_radius: 0.5_f32,
+            lifetime: 8.0_f32,
+            drag: 0.2_f32,
+            turbulence: 1.5_f32,
+            turbulence_scale: 0.6_f32,
+            attractors: vec![
+                Attractor {
+                    position: glm::vec3(-3.0_f32, 3.0_f32, 0.0_f32),
+                    strength: 6.0_f32,
+                },
+                Attractor {
+                    position: glm::vec3(3.0_f32, 3.0_f32, 0.0_f32),
+                    strength: 6.0_f32,
+                },
+            ],
+        }
+    }
+}
+
+impl SimulationUniforms {
+    fn new(program: &ShaderProgram) -> SimulationUniforms {
+        SimulationUniforms {
+            dt: program.uniform("dt"),
+            time: program.uniform("time"),
+            emitter_position: program.uniform("emitter_position"),
+            emitter_radius: program.uniform("emitter_radius"),
+            lifetime: program.uniform("lifetime"),
+            drag: program.uniform("drag"),
+            turbulence: program.uniform("turbulence"),
+            turbulence_scale: program.uniform("turbulence_scale"),
+            attractors: (0..MAX_ATTRACTORS)
+                .map(|i| program.uniform(&format!("attractors[{}]", i)))
+                .collect(),
+            attractor_count: program.uniform("attractor_count"),
+        }
+    }
+
+    fn set(&self, settings: &GpuParticleSettings, dt: f32, time: f32) {
+        self.dt.set_float(dt);
+        self.time.set_float(time);
+        let emitter = &settings.emitter_position;
+        self.emitter_position
+            .set_vec3f([emitter.x, emitter.y, emitter.z]);
+        self.emitter_radius.set_float(settings.emitter_radius);
+        self.lifetime.set_float(settings.lifetime);
+        self.drag.set_float(settings.drag);
+        self.turbulence.set_float(settings.turbulence);
+        self.turbulence_scale.set_float(settings.turbulence_scale);
+        let attractors = &settings.attractors[..settings.attractors.len().min(MAX_ATTRACTORS)];
+        for (uniform, attractor) in self.attractors.iter().zip(attractors.iter()) {
+            let position = &attractor.position;
+            uniform.set_vec4f([position.x, position.y, position.z, attractor.strength]);
+        }
+        self.attractor_count.set_int(attractors.len() as i32);
+    }
+}
+
+impl GpuParticleSystem {
+    /// Seeds `settings.count` particles around the emitter, at staggered ages so they do not all
+    /// respawn together, and builds the programs `backend` runs.
+    pub fn new(
+        context: &GlContext,
+        backend: GpuParticleBackend,
+        settings: GpuParticleSettings,
+        seed: u64,
+    ) -> Result<GpuParticleSystem, String> {
+        unsafe {
+            let simulation_source =
+                SIMULATION_SOURCE.replace("MAX_ATTRACTORS", &format!("{}", MAX_ATTRACTORS));
+            let simulation_program = match backend {
+                GpuParticleBackend::TransformFeedback => ShaderProgram::with_transform_feedback(
+                    context,
+                    &format!(
+                        "{}{}{}",
+                        FEEDBACK_VERTEX_SHADER_SOURCE,
+                        simulation_source,
+                        FEEDBACK_VERTEX_SHADER_MAIN
+                    ),
+                    FEEDBACK_FRAGMENT_SHADER_SOURCE,
+                    &["o_position_age", "o_velocity_lifetime"],
+                )?,
+                #[cfg(feature = "gl45")]
+                GpuParticleBackend::Compute => ShaderProgram::with_compute_shader(
+                    context,
+                    &format!(
+                        "{}{}{}",
+                        COMPUTE_SHADER_SOURCE
+                            .replace("COMPUTE_GROUP_SIZE", &format!("{}", COMPUTE_GROUP_SIZE)),
+                        simulation_source,
+                        COMPUTE_SHADER_MAIN
+                    ),
+                )?,
+                #[cfg(not(feature = "gl45"))]
+                GpuParticleBackend::Compute => {
+                    return Err("Compute particles need the gl45 feature".to_string())
+                }
+            };
+            let point_program = ShaderProgram::with_shaders(
+                context,
+                POINT_VERTEX_SHADER_SOURCE,
+                POINT_FRAGMENT_SHADER_SOURCE,
+            )?;
+            simulation_program.set_label("gpu particle simulation");
+            point_program.set_label("gpu particle points");
+
+            let mut rng = Rng::new(seed);
+            let mut particles = Vec::with_capacity(settings.count * PARTICLE_FLOATS);
+            for _ in 0..settings.count {
+                let position = settings.emitter_position
+                    + random_point_in_sphere(&mut rng, settings.emitter_radius);
+                let velocity = random_point_in_sphere(&mut rng, 0.5_f32);
+                let lifetime = settings.lifetime * rng.range(0.5_f32, 1.5_f32);
+                let age = rng.range(0.0_f32, lifetime);
+                particles.extend_from_slice(&[
+                    position.x, position.y, position.z, age, velocity.x, velocity.y, velocity.z,
+                    lifetime,
+                ]);
+            }
+            let mut buffers = [
+                Buffer::new(context, gl::DYNAMIC_COPY),
+                Buffer::new(context, gl::DYNAMIC_COPY),
+            ];
+            buffers[0].set_data(context, &particles);
+            if backend == GpuParticleBackend::TransformFeedback {
+                buffers[1].set_data(context, &particles);
+            }
+
+            let mut vertex_arrays = [0; 2];
+            gl_check!(gl::GenVertexArrays(2, vertex_arrays.as_mut_ptr()));
+            live_objects::created_all(GlObject::VertexArray, &vertex_arrays);
+            let stride = (PARTICLE_FLOATS * mem::size_of::<GLfloat>()) as GLsizei;
+            for (vertex_array, buffer) in vertex_arrays.iter().zip(buffers.iter()) {
+                gl_check!(gl::BindVertexArray(*vertex_array));
+                gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, buffer.id));
+                // a_position_age and a_velocity_lifetime attributes
+                for location in 0..2 {
+                    gl_check!(gl::VertexAttribPointer(
+                        location,
+                        4,
+                        gl::FLOAT,
+                        gl::FALSE,
+                        stride,
+                        (location as usize * 4 * mem::size_of::<GLfloat>()) as *const c_void,
+                    ));
+                    gl_check!(gl::EnableVertexAttribArray(location));
+                }
+            }
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            for (buffer_i, (vertex_array, buffer)) in
+                vertex_arrays.iter().zip(buffers.iter()).enumerate()
+            {
+                let label = format!("gpu particles {}", buffer_i);
+                debug_label::set(gl::VERTEX_ARRAY, *vertex_array, &label);
+                buffer.set_label(context, &label);
+            }
+
+            let simulation_uniforms = SimulationUniforms::new(&simulation_program);
+            #[cfg(feature = "gl45")]
+            let particle_count_uniform = simulation_program.uniform("particle_count");
+            let projection_from_world_uniform = point_program.uniform("projection_from_world");
+            Ok(GpuParticleSystem {
+                backend,
+                settings,
+                buffers,
+                vertex_arrays,
+                current: 0,
+                time: 0.0_f32,
+                simulation_program,
+                simulation_uniforms,
+                #[cfg(feature = "gl45")]
+                particle_count_uniform,
+                point_program,
+                projection_from_world_uniform,
+            })
+        }
+    }
+
+    /// Advances every particle by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.simulation_program.use_program();
+        self.simulation_uniforms.set(&self.settings, dt, self.time);
+        let count = self.settings.count as GLsizei;
+        unsafe {
+            match self.backend {
+                GpuParticleBackend::TransformFeedback => {
+                    let next = 1 - self.current;
+                    gl_check!(gl::Enable(gl::RASTERIZER_DISCARD));
+                    gl_check!(gl::BindVertexArray(self.vertex_arrays[self.current]));
+                    gl_check!(gl::BindBufferBase(
+                        gl::TRANSFORM_FEEDBACK_BUFFER,
+                        0,
+                        self.buffers[next].id
+                    ));
+                    gl_check!(gl::BeginTransformFeedback(gl::POINTS));
+                    gl_check!(gl::DrawArrays(gl::POINTS, 0, count));
+                    gl_check!(gl::EndTransformFeedback());
+                    gl_check!(gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0));
+                    gl_check!(gl::BindVertexArray(0));
+                    gl_check!(gl::Disable(gl::RASTERIZER_DISCARD));
+                    self.current = next;
+                }
+                #[cfg(feature = "gl45")]
+                GpuParticleBackend::Compute => {
+                    self.particle_count_uniform.set_int(count);
+                    gl_check!(gl::BindBufferBase(
+                        gl::SHADER_STORAGE_BUFFER,
+                        PARTICLES_BINDING,
+                        self.buffers[0].id
+                    ));
+                    let groups = self.settings.count.div_ceil(COMPUTE_GROUP_SIZE);
+                    gl_check!(gl::DispatchCompute(groups as GLuint, 1, 1));
+                    // Drawing reads the particles back as vertex attributes
+                    gl_check!(gl::MemoryBarrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT));
+                }
+                #[cfg(not(feature = "gl45"))]
+                GpuParticleBackend::Compute => {}
+            }
+        }
+    }
+
+    /// Draws the particles as additive points over the scene, testing against its depth without
+    /// writing it.
+    pub fn draw(
+        &self,
+        view_from_world: &Mat4,
+        projection_from_view: &Mat4,
+        stats: &mut FrameStats,
+    ) {
+        self.point_program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(&(projection_from_view * view_from_world));
+        unsafe {
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE));
+            gl_check!(gl::DepthMask(gl::FALSE));
+            // ES always takes the point size from the shader
+            if !capabilities::opengl_es() {
+                gl_check!(gl::Enable(gl::PROGRAM_POINT_SIZE));
+            }
+            gl_check!(gl::BindVertexArray(self.vertex_arrays[self.current]));
+            gl_check!(gl::DrawArrays(
+                gl::POINTS,
+                0,
+                self.settings.count as GLsizei
+            ));
+            gl_check!(gl::BindVertexArray(0));
+            if !capabilities::opengl_es() {
+                gl_check!(gl::Disable(gl::PROGRAM_POINT_SIZE));
+            }
+            gl_check!(gl::DepthMask(gl::TRUE));
+            gl_check!(gl::Disable(gl::BLEND));
+        }
+        stats.record_draw(0);
+    }
+}
+
+impl Drop for GpuParticleSystem {
+    fn drop(&mut self) {
+        unsafe { live_objects::delete_all(GlObject::VertexArray, &self.vertex_arrays) };
+    }
+}
@@ -0,0 +1,132 @@
+use gl::types::*;
+
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::stats::FrameStats;
+
+// Frames a query may stay in flight before its slot is reused, so reading results never stalls
+const QUERY_RING_SIZE: usize = 4;
+
+/// `GL_TIME_ELAPSED` queries per named render pass. Passes cannot nest, since only one elapsed
+/// time query may be active at a time.
+pub struct GpuTimer {
+    passes: Vec<PassQueries>,
+    active_pass: Option<usize>,
+    frame: usize,
+    context: GlContext,
+}
+
+struct PassQueries {
+    name: String,
+    queries: [GLuint; QUERY_RING_SIZE],
+    pending: [bool; QUERY_RING_SIZE],
+    /// Recorded again while a result is not available yet, rather than a false zero
+    last_secs: f32,
+}
+
+impl GpuTimer {
+    pub fn new(context: &GlContext) -> GpuTimer {
+        GpuTimer {
+            passes: vec![],
+            active_pass: None,
+            frame: 0,
+            context: *context,
+        }
+    }
+
+    /// Timer queries are desktop-only, so passes are not timed on OpenGL ES.
+    pub fn begin_pass(&mut self, name: &str) {
+        if capabilities::opengl_es() {
+            return;
+        }
+        if let Some(active_pass) = self.active_pass {
+            eprintln!(
+                "GPU timer pass `{}` started while `{}` is active",
+                name, self.passes[active_pass].name
+            );
+            self.end_pass();
+        }
+        let pass_i = match self.passes.iter().position(|pass| pass.name == name) {
+            Some(pass_i) => pass_i,
+            None => {
+                let mut queries = [0; QUERY_RING_SIZE];
+                unsafe {
+                    gl_check!(gl::GenQueries(
+                        QUERY_RING_SIZE as GLsizei,
+                        queries.as_mut_ptr()
+                    ));
+                    live_objects::created_all(GlObject::Query, &queries);
+                }
+                self.passes.push(PassQueries {
+                    name: name.to_string(),
+                    queries,
+                    pending: [false; QUERY_RING_SIZE],
+                    last_secs: 0.0_f32,
+                });
+                self.passes.len() - 1
+            }
+        };
+        let slot = self.frame % QUERY_RING_SIZE;
+        let pass = &mut self.passes[pass_i];
+        unsafe {
+            gl_check!(gl::BeginQuery(gl::TIME_ELAPSED, pass.queries[slot]));
+        }
+        pass.pending[slot] = true;
+        self.active_pass = Some(pass_i);
+    }
+
+    pub fn end_pass(&mut self) {
+        if self.active_pass.take().is_some() {
+            unsafe {
+                gl_check!(gl::EndQuery(gl::TIME_ELAPSED));
+            }
+        }
+    }
+
+    /// Advances the ring and records, for every pass, the oldest result that is available. A
+    /// result still outstanding after `QUERY_RING_SIZE` frames is dropped rather than waited on,
+    /// and the pass's previous time recorded in its place.
+    pub fn collect(&mut self, stats: &mut FrameStats) {
+        self.end_pass();
+        self.frame += 1;
+        let slot = self.frame % QUERY_RING_SIZE;
+        let mut pass_times = Vec::with_capacity(self.passes.len());
+        for pass in self.passes.iter_mut() {
+            let mut elapsed_secs = 0.0_f32;
+            if pass.pending[slot] {
+                let mut available = gl::FALSE as GLint;
+                unsafe {
+                    gl_check!(gl::GetQueryObjectiv(
+                        pass.queries[slot],
+                        gl::QUERY_RESULT_AVAILABLE,
+                        &mut available
+                    ));
+                    if available == gl::TRUE as GLint {
+                        let mut elapsed_ns: GLuint64 = 0;
+                        gl_check!(gl::GetQueryObjectui64v(
+                            pass.queries[slot],
+                            gl::QUERY_RESULT,
+                            &mut elapsed_ns
+                        ));
+                        pass.last_secs = elapsed_ns as f32 * 1e-9_f32;
+                    }
+                    elapsed_secs = pass.last_secs;
+                }
+                pass.pending[slot] = false;
+            }
+            pass_times.push((pass.name.clone(), elapsed_secs));
+        }
+        stats.record_gpu_pass_times(pass_times);
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        for pass in self.passes.iter() {
+            for query in pass.queries.iter() {
+                live_objects::release(&self.context, GlObject::Query, *query);
+            }
+        }
+    }
+}
@@ -1,21 +1,178 @@
 use gl::types::*;
-use glm::{Mat4, Vec3};
-use nalgebra_glm as glm;
 
-use crate::ogl::utils::{build_program, build_shader, clean_shader};
-use image::GenericImageView;
-use std::ffi::{c_void, CStr};
+use crate::math::color::Color;
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::render_queue::RenderPass;
+use crate::ogl::texture_table::TextureTable;
+use crate::ogl::utils::{build_program, build_shader, clean_shader, link_program};
+use image::{DynamicImage, GenericImageView};
+use std::ffi::{c_void, CStr, CString};
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::{mem, ptr};
+
+// Texture parameters swizzling red, green, blue and alpha, in that order
+const SWIZZLE_PARAMETERS: [GLenum; 4] = [
+    gl::TEXTURE_SWIZZLE_R,
+    gl::TEXTURE_SWIZZLE_G,
+    gl::TEXTURE_SWIZZLE_B,
+    gl::TEXTURE_SWIZZLE_A,
+];
+// GL_UNPACK_ALIGNMENT's initial value, which every other upload assumes
+const DEFAULT_UNPACK_ALIGNMENT: GLint = 4;
 
 pub struct ShaderProgram {
     pub id: GLuint,
+    context: GlContext,
+}
+
+/// A uniform of one program, looked up once with `ShaderProgram::uniform` so setting it needs
+/// neither a name string nor a location query. Setting applies to the program in use.
+#[derive(Clone, Copy, Debug)]
+pub struct Uniform {
+    location: GLint,
+}
+
+/// Value of a float or float vector uniform.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+}
+
+/// A non-array float or float vector uniform used by a linked program, found by introspection.
+/// Matrices, samplers and uniforms the compiler optimized away are not reported.
+#[allow(dead_code)]
+pub struct ActiveUniform {
+    pub name: String,
+    pub location: GLint,
+    pub value: UniformValue,
+}
+
+/// A vertex shader input of a linked program, found by introspection. Built-in inputs such as
+/// `gl_VertexID` are not reported.
+pub struct ActiveAttribute {
+    pub name: String,
+    pub location: GLuint,
+    /// GLSL type, e.g. `FLOAT_VEC3` or `UNSIGNED_INT`
+    pub attribute_type: GLenum,
+    /// Array length, 1 for non-arrays
+    pub size: GLint,
 }
 
 pub struct Texture {
     pub id: GLuint,
     pub width: u32,
     pub height: u32,
-    data: Vec<[u8; 3]>,
+    /// Chosen for the source image, so gray maps stay one channel and 16-bit ones keep their
+    /// precision
+    pub format: TextureFormat,
+    /// Tightly packed rows, until uploaded by `load`
+    data: Vec<u8>,
+    /// File the texture was read from, or another name for debuggers
+    file_path: String,
+    context: GlContext,
+}
+
+/// A texture loaded in another context sharing objects with this one, as plain data that can
+/// cross threads. `Texture::from_shared` takes it over in the context that draws.
+#[derive(Debug)]
+pub struct SharedTexture {
+    id: GLuint,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    file_path: String,
+}
+
+/// How a texture is stored on the GPU, and how its pixel data is laid out for uploading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureFormat {
+    pub internal_format: GLenum,
+    /// Format and type of the uploaded pixel data
+    pub format: GLenum,
+    pub pixel_type: GLenum,
+    /// Channels sampling reads for red, green, blue and alpha, where they differ from the stored
+    /// ones
+    pub swizzle: Option<[GLenum; 4]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VertexAttribute {
+    pub location: GLuint,
+    pub components: GLint,
+}
+
+/// CPU-side geometry, interleaved in `attributes` order.
+#[derive(Clone)]
+pub struct MeshData {
+    pub vertices: Vec<f32>,
+    pub indices: Option<Vec<u32>>,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+pub struct Mesh {
+    vertex_array_obj: GLuint,
+    /// Shared with sub-meshes, which draw from the same vertex array and buffers
+    objects: Rc<MeshObjects>,
+    // Counts and offset stay private: draws trust them to lie within the buffers
+    vertex_count: GLsizei,
+    index_count: GLsizei,
+    /// Index draws start at, for meshes sharing their buffers with others
+    first_index: GLsizei,
+    /// Layout of the vertex buffer, checked against the shaders the mesh is drawn with
+    pub attributes: Vec<VertexAttribute>,
+}
+
+/// What a mesh and its sub-meshes draw from, deleted once the last of them is dropped.
+struct MeshObjects {
+    vertex_array_obj: GLuint,
+    vertex_buffer_obj: GLuint,
+    element_buffer_obj: Option<GLuint>,
+}
+
+/// Vertex and index buffers filled ahead of time, e.g. by the upload worker in a shared context.
+/// Buffers are shared between contexts, vertex arrays are not, so `Mesh::from_buffers` builds the
+/// vertex array in the context that draws.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct MeshBuffers {
+    vertex_buffer_obj: GLuint,
+    element_buffer_obj: Option<GLuint>,
+    vertex_count: GLsizei,
+    index_count: GLsizei,
+    attributes: Vec<VertexAttribute>,
+}
+
+pub struct Material {
+    pub id: u32,
+    pub shader_program: Rc<ShaderProgram>,
+    pub textures: Vec<GLuint>,
+    pub pass: RenderPass,
+    /// Table `textures` are sampled from instead of being bound, see `with_texture_set`
+    pub texture_table: Option<Rc<TextureTable>>,
+    pub texture_set: u32,
+    /// Drawn around the material's meshes by the renderer, see `HullOutline`
+    pub outline: Option<HullOutline>,
+}
+
+/// An inverted-hull outline: the mesh drawn again pushed out along its normals, keeping only its
+/// back faces, so a `width` band of `color` shows around its silhouette. Needs mesh normals at
+/// location 2.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HullOutline {
+    /// In view space units
+    pub width: f32,
+    pub color: Color,
 }
 
 pub struct Camera {
@@ -28,6 +185,7 @@ pub struct Camera {
 
 impl ShaderProgram {
     pub fn with_shaders(
+        context: &GlContext,
         vertex_shader_src: &str,
         fragment_shader_src: &str,
     ) -> Result<ShaderProgram, String> {
@@ -47,117 +205,1401 @@ impl ShaderProgram {
                 .and_then(|program_id| {
                     clean_shader(vertex_shader);
                     clean_shader(fragment_shader);
-                    Ok(ShaderProgram { id: program_id })
+                    Ok(ShaderProgram {
+                        id: program_id,
+                        context: *context,
+                    })
                 })
         }
     }
 
+    /// Program whose vertex shader outputs `feedback_varyings` are captured with transform
+    /// feedback, interleaved in that order.
+    pub fn with_transform_feedback(
+        context: &GlContext,
+        vertex_shader_src: &str,
+        fragment_shader_src: &str,
+        feedback_varyings: &[&str],
+    ) -> Result<ShaderProgram, String> {
+        unsafe {
+            let vertex_shader = build_shader(vertex_shader_src, gl::VERTEX_SHADER)?;
+            let fragment_shader = build_shader(fragment_shader_src, gl::FRAGMENT_SHADER)?;
+            let program_id = link_program(&[vertex_shader, fragment_shader], feedback_varyings)?;
+            clean_shader(vertex_shader);
+            clean_shader(fragment_shader);
+            Ok(ShaderProgram {
+                id: program_id,
+                context: *context,
+            })
+        }
+    }
+
+    /// Program with a geometry shader between its vertex and fragment shaders. Geometry shaders
+    /// are core since OpenGL 3.2, but OpenGL ES 3.0 lacks them.
+    pub fn with_geometry_shader(
+        context: &GlContext,
+        vertex_shader_src: &str,
+        geometry_shader_src: &str,
+        fragment_shader_src: &str,
+    ) -> Result<ShaderProgram, String> {
+        unsafe {
+            let shaders = [
+                build_shader(vertex_shader_src, gl::VERTEX_SHADER)?,
+                build_shader(geometry_shader_src, gl::GEOMETRY_SHADER)?,
+                build_shader(fragment_shader_src, gl::FRAGMENT_SHADER)?,
+            ];
+            let program_id = link_program(&shaders, &[])?;
+            for shader in shaders.iter() {
+                clean_shader(*shader);
+            }
+            Ok(ShaderProgram {
+                id: program_id,
+                context: *context,
+            })
+        }
+    }
+
+    /// Program with tessellation control and evaluation stages between its vertex and fragment
+    /// shaders, which needs OpenGL 4.0.
+    #[cfg(feature = "gl41")]
+    pub fn with_tessellation_shaders(
+        context: &GlContext,
+        vertex_shader_src: &str,
+        control_shader_src: &str,
+        evaluation_shader_src: &str,
+        fragment_shader_src: &str,
+    ) -> Result<ShaderProgram, String> {
+        unsafe {
+            let shaders = [
+                build_shader(vertex_shader_src, gl::VERTEX_SHADER)?,
+                build_shader(control_shader_src, gl::TESS_CONTROL_SHADER)?,
+                build_shader(evaluation_shader_src, gl::TESS_EVALUATION_SHADER)?,
+                build_shader(fragment_shader_src, gl::FRAGMENT_SHADER)?,
+            ];
+            let program_id = link_program(&shaders, &[])?;
+            for shader in shaders.iter() {
+                clean_shader(*shader);
+            }
+            Ok(ShaderProgram {
+                id: program_id,
+                context: *context,
+            })
+        }
+    }
+
+    /// Program of a single compute shader, which needs OpenGL 4.3.
+    #[cfg(feature = "gl45")]
+    pub fn with_compute_shader(
+        context: &GlContext,
+        compute_shader_src: &str,
+    ) -> Result<ShaderProgram, String> {
+        unsafe {
+            let compute_shader = build_shader(compute_shader_src, gl::COMPUTE_SHADER)?;
+            let program_id = link_program(&[compute_shader], &[])?;
+            clean_shader(compute_shader);
+            Ok(ShaderProgram {
+                id: program_id,
+                context: *context,
+            })
+        }
+    }
+
     pub fn use_program(&self) {
         unsafe {
-            gl::UseProgram(self.id);
+            gl_check!(gl::UseProgram(self.id));
+        }
+    }
+
+    /// Names the program for debuggers, see `debug_label::set`. Its shaders are deleted once it
+    /// is linked, so the program's label stands for them too.
+    pub fn set_label(&self, label: &str) {
+        unsafe {
+            debug_label::set(gl::PROGRAM, self.id, label);
+        }
+    }
+
+    /// Handle to the uniform `name`. Uniforms the program lacks get a handle that sets nothing.
+    pub fn uniform(&self, name: &str) -> Uniform {
+        let c_name = CString::new(name).unwrap();
+        Uniform {
+            location: unsafe { gl_check!(gl::GetUniformLocation(self.id, c_name.as_ptr())) },
         }
     }
 
     #[allow(dead_code)]
     pub fn set_bool(&self, name: &CStr, value: bool) {
         unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value as i32);
+            gl_check!(gl::Uniform1i(
+                gl::GetUniformLocation(self.id, name.as_ptr()),
+                value as i32
+            ));
         }
     }
 
     #[allow(dead_code)]
     pub fn set_int(&self, name: &CStr, value: i32) {
         unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value);
+            gl_check!(gl::Uniform1i(
+                gl::GetUniformLocation(self.id, name.as_ptr()),
+                value
+            ));
         }
     }
 
     #[allow(dead_code)]
     pub fn set_float(&self, name: &CStr, value: f32) {
         unsafe {
-            gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr()), value);
+            gl_check!(gl::Uniform1f(
+                gl::GetUniformLocation(self.id, name.as_ptr()),
+                value
+            ));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_vec2f(&self, name: &CStr, value: [f32; 2]) {
+        unsafe {
+            gl_check!(gl::Uniform2fv(
+                gl::GetUniformLocation(self.id, name.as_ptr()),
+                1,
+                value.as_ptr(),
+            ));
         }
     }
 
     #[allow(dead_code)]
     pub fn set_vec3f(&self, name: &CStr, value: [f32; 3]) {
         unsafe {
-            gl::Uniform3fv(
+            gl_check!(gl::Uniform3fv(
                 gl::GetUniformLocation(self.id, name.as_ptr()),
                 1,
                 value.as_ptr(),
-            );
+            ));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_vec4f(&self, name: &CStr, value: [f32; 4]) {
+        unsafe {
+            gl_check!(gl::Uniform4fv(
+                gl::GetUniformLocation(self.id, name.as_ptr()),
+                1,
+                value.as_ptr(),
+            ));
         }
     }
 
+    /// Sets a vec4 uniform to the sRGB encoding of `color`, matching the non-sRGB framebuffers.
+    #[allow(dead_code)]
+    pub fn set_color(&self, name: &CStr, color: &Color) {
+        self.set_vec4f(name, color.to_srgb());
+    }
+
     #[allow(dead_code)]
     pub fn set_mat4f(&self, name: &CStr, value: &Mat4) {
         unsafe {
-            gl::UniformMatrix4fv(
+            gl_check!(gl::UniformMatrix4fv(
                 gl::GetUniformLocation(self.id, name.as_ptr()),
                 1,
                 gl::FALSE,
                 glm::value_ptr(value).as_ptr(),
-            );
+            ));
+        }
+    }
+
+    /// Lists the uniforms that can be tweaked, with their current values.
+    #[allow(dead_code)]
+    pub fn active_uniforms(&self) -> Vec<ActiveUniform> {
+        let mut uniforms = vec![];
+        unsafe {
+            let mut uniform_count = 0;
+            gl_check!(gl::GetProgramiv(
+                self.id,
+                gl::ACTIVE_UNIFORMS,
+                &mut uniform_count
+            ));
+            let mut max_name_length = 0;
+            gl_check!(gl::GetProgramiv(
+                self.id,
+                gl::ACTIVE_UNIFORM_MAX_LENGTH,
+                &mut max_name_length
+            ));
+            let mut name_buffer = vec![0_u8; max_name_length.max(1) as usize];
+            for uniform_i in 0..uniform_count as GLuint {
+                let (mut name_length, mut size, mut uniform_type) = (0, 0, 0);
+                gl_check!(gl::GetActiveUniform(
+                    self.id,
+                    uniform_i,
+                    name_buffer.len() as GLsizei,
+                    &mut name_length,
+                    &mut size,
+                    &mut uniform_type,
+                    name_buffer.as_mut_ptr() as *mut GLchar,
+                ));
+                if size != 1 {
+                    continue;
+                }
+                let name = String::from_utf8_lossy(&name_buffer[..name_length as usize]);
+                let c_name = CString::new(name.as_bytes()).unwrap();
+                // Uniforms in named uniform blocks have no location
+                let location = gl_check!(gl::GetUniformLocation(self.id, c_name.as_ptr()));
+                if location < 0 {
+                    continue;
+                }
+                let mut values = [0.0_f32; 4];
+                let value = match uniform_type {
+                    gl::FLOAT | gl::FLOAT_VEC2 | gl::FLOAT_VEC3 | gl::FLOAT_VEC4 => {
+                        gl_check!(gl::GetUniformfv(self.id, location, values.as_mut_ptr()));
+                        match uniform_type {
+                            gl::FLOAT => UniformValue::Float(values[0]),
+                            gl::FLOAT_VEC2 => UniformValue::Vec2([values[0], values[1]]),
+                            gl::FLOAT_VEC3 => UniformValue::Vec3([values[0], values[1], values[2]]),
+                            _ => UniformValue::Vec4(values),
+                        }
+                    }
+                    _ => continue,
+                };
+                uniforms.push(ActiveUniform {
+                    name: name.into_owned(),
+                    location,
+                    value,
+                });
+            }
+        }
+        uniforms
+    }
+
+    /// Lists the vertex shader inputs the linker kept.
+    pub fn active_attributes(&self) -> Vec<ActiveAttribute> {
+        let mut attributes = vec![];
+        unsafe {
+            let mut attribute_count = 0;
+            gl_check!(gl::GetProgramiv(
+                self.id,
+                gl::ACTIVE_ATTRIBUTES,
+                &mut attribute_count
+            ));
+            let mut max_name_length = 0;
+            gl_check!(gl::GetProgramiv(
+                self.id,
+                gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+                &mut max_name_length
+            ));
+            let mut name_buffer = vec![0_u8; max_name_length.max(1) as usize];
+            for attribute_i in 0..attribute_count as GLuint {
+                let (mut name_length, mut size, mut attribute_type) = (0, 0, 0);
+                gl_check!(gl::GetActiveAttrib(
+                    self.id,
+                    attribute_i,
+                    name_buffer.len() as GLsizei,
+                    &mut name_length,
+                    &mut size,
+                    &mut attribute_type,
+                    name_buffer.as_mut_ptr() as *mut GLchar,
+                ));
+                let name = String::from_utf8_lossy(&name_buffer[..name_length as usize]);
+                let c_name = CString::new(name.as_bytes()).unwrap();
+                let location = gl_check!(gl::GetAttribLocation(self.id, c_name.as_ptr()));
+                if location < 0 {
+                    continue;
+                }
+                attributes.push(ActiveAttribute {
+                    name: name.into_owned(),
+                    location: location as GLuint,
+                    attribute_type,
+                    size,
+                });
+            }
+        }
+        attributes
+    }
+
+    /// Checks that every input of the program is fed with a compatible type: from the float
+    /// attributes of a mesh laid out as `attributes`, or, at the locations listed in
+    /// `instance_attributes` with their types, from per-instance data. Rendering with a layout
+    /// that fails reads garbage, or zeros, without any GL error.
+    pub fn check_vertex_layout(
+        &self,
+        attributes: &[VertexAttribute],
+        instance_attributes: &[(GLuint, GLenum)],
+    ) -> Result<(), String> {
+        for attribute in self.active_attributes() {
+            let describe = || {
+                format!(
+                    "Attribute {} (location {}) expects {}",
+                    attribute.name,
+                    attribute.location,
+                    glsl_type_name(attribute.attribute_type)
+                )
+            };
+            if let Some(&(_, provided_type)) = instance_attributes
+                .iter()
+                .find(|(location, _)| *location == attribute.location)
+            {
+                if provided_type != attribute.attribute_type {
+                    return Err(format!(
+                        "{}, but instance data provides {}",
+                        describe(),
+                        glsl_type_name(provided_type)
+                    ));
+                }
+                continue;
+            }
+            let (columns, rows, float) = match attribute_shape(attribute.attribute_type) {
+                Some(shape) => shape,
+                None => continue,
+            };
+            if !float {
+                return Err(format!(
+                    "{}, but meshes only provide floats, which integer inputs misread",
+                    describe()
+                ));
+            }
+            // Matrices and arrays take a location per column and element
+            for location_i in 0..(columns * attribute.size.max(1)) as GLuint {
+                let location = attribute.location + location_i;
+                let components = match attributes
+                    .iter()
+                    .find(|provided| provided.location == location)
+                {
+                    Some(provided) => provided.components,
+                    None => {
+                        return Err(format!(
+                            "{}, but the mesh's vertex layout has nothing at location {}",
+                            describe(),
+                            location
+                        ))
+                    }
+                };
+                // A missing w reads as 1, which is how positions are usually fed to a vec4
+                if components != rows && (rows, components) != (4, 3) {
+                    return Err(format!(
+                        "{}, but the mesh provides {} floats at location {}",
+                        describe(),
+                        components,
+                        location
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Uploads `uniform.value`; the program is left in use.
+    #[allow(dead_code)]
+    pub fn set_uniform(&self, uniform: &ActiveUniform) {
+        self.use_program();
+        unsafe {
+            match uniform.value {
+                UniformValue::Float(value) => gl_check!(gl::Uniform1f(uniform.location, value)),
+                UniformValue::Vec2(value) => {
+                    gl_check!(gl::Uniform2fv(uniform.location, 1, value.as_ptr()))
+                }
+                UniformValue::Vec3(value) => {
+                    gl_check!(gl::Uniform3fv(uniform.location, 1, value.as_ptr()))
+                }
+                UniformValue::Vec4(value) => {
+                    gl_check!(gl::Uniform4fv(uniform.location, 1, value.as_ptr()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Program, self.id);
+    }
+}
+
+impl Uniform {
+    #[allow(dead_code)]
+    pub fn set_bool(&self, value: bool) {
+        self.set_int(value as i32);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_int(&self, value: i32) {
+        unsafe {
+            gl_check!(gl::Uniform1i(self.location, value));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_float(&self, value: f32) {
+        unsafe {
+            gl_check!(gl::Uniform1f(self.location, value));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_vec2f(&self, value: [f32; 2]) {
+        unsafe {
+            gl_check!(gl::Uniform2fv(self.location, 1, value.as_ptr()));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_vec3f(&self, value: [f32; 3]) {
+        unsafe {
+            gl_check!(gl::Uniform3fv(self.location, 1, value.as_ptr()));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_vec4f(&self, value: [f32; 4]) {
+        unsafe {
+            gl_check!(gl::Uniform4fv(self.location, 1, value.as_ptr()));
+        }
+    }
+
+    /// Sets a vec4 uniform to the sRGB encoding of `color`, like `ShaderProgram::set_color`.
+    pub fn set_color(&self, color: &Color) {
+        self.set_vec4f(color.to_srgb());
+    }
+
+    pub fn set_mat4f(&self, value: &Mat4) {
+        unsafe {
+            gl_check!(gl::UniformMatrix4fv(
+                self.location,
+                1,
+                gl::FALSE,
+                glm::value_ptr(value).as_ptr(),
+            ));
         }
     }
 }
 
+impl TextureFormat {
+    /// Picks the storage for an image's channels and bit depth: one or two channel images keep
+    /// them, spread over RGBA by swizzling when sampled, and 16-bit ones keep their precision.
+    pub fn for_channels(channels: u8, sixteen_bit: bool) -> TextureFormat {
+        let (internal_format, format) = match (channels, sixteen_bit) {
+            (1, false) => (gl::R8, gl::RED),
+            (2, false) => (gl::RG8, gl::RG),
+            (3, false) => (gl::RGB8, gl::RGB),
+            (1, true) => (gl::R16, gl::RED),
+            (2, true) => (gl::RG16, gl::RG),
+            (3, true) => (gl::RGB16, gl::RGB),
+            (_, true) => (gl::RGBA16, gl::RGBA),
+            _ => (gl::RGBA8, gl::RGBA),
+        };
+        TextureFormat {
+            internal_format,
+            format,
+            pixel_type: if sixteen_bit {
+                gl::UNSIGNED_SHORT
+            } else {
+                gl::UNSIGNED_BYTE
+            },
+            swizzle: match channels {
+                1 => Some([gl::RED, gl::RED, gl::RED, gl::ONE]),
+                2 => Some([gl::RED, gl::RED, gl::RED, gl::GREEN]),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self.format {
+            gl::RED => 1,
+            gl::RG => 2,
+            gl::RGB => 3,
+            _ => 4,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        let bytes_per_channel = if self.pixel_type == gl::UNSIGNED_SHORT {
+            2
+        } else {
+            1
+        };
+        self.channels() * bytes_per_channel
+    }
+}
+
 impl Texture {
-    pub unsafe fn from_file(file_path: &str, flip_vertically: bool) -> Result<Texture, String> {
-        Self::load_data_from_file(file_path, flip_vertically).and_then(|(width, height, data)| {
+    pub fn from_file(
+        context: &GlContext,
+        file_path: &str,
+        flip_vertically: bool,
+    ) -> Result<Texture, String> {
+        let (width, height, format, data) = Self::load_data_from_file(file_path, flip_vertically)?;
+        Self::from_pixels(context, width, height, format, data, file_path)
+    }
+
+    /// A texture to fill with `data`, tightly packed rows in `format`, by `load`. `name` labels
+    /// it for debuggers.
+    pub fn from_pixels(
+        context: &GlContext,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: Vec<u8>,
+        name: &str,
+    ) -> Result<Texture, String> {
+        unsafe {
+            capabilities::check_texture_size(width as i32, height as i32)?;
             let mut texture_obj_id: GLuint = 0;
-            gl::GenTextures(1, &mut texture_obj_id);
+            #[cfg(feature = "gl45")]
+            {
+                if capabilities::direct_state_access() {
+                    gl_check!(gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture_obj_id));
+                }
+            }
+            if texture_obj_id == 0 {
+                gl_check!(gl::GenTextures(1, &mut texture_obj_id));
+            }
+            live_objects::created(GlObject::Texture, texture_obj_id);
             Ok(Texture {
                 id: texture_obj_id,
                 width,
                 height,
+                format,
                 data,
+                file_path: name.to_string(),
+                context: *context,
             })
-        })
+        }
     }
 
-    pub unsafe fn load(&mut self) {
-        gl::BindTexture(gl::TEXTURE_2D, self.id);
+    /// Takes over a texture loaded in a context sharing objects with `context`.
+    pub fn from_shared(context: &GlContext, shared: SharedTexture) -> Texture {
+        Texture {
+            id: shared.id,
+            width: shared.width,
+            height: shared.height,
+            format: shared.format,
+            data: vec![],
+            file_path: shared.file_path,
+            context: *context,
+        }
+    }
+
+    /// Hands the loaded texture over to another thread's context, see `from_shared`.
+    pub fn into_shared(mut self) -> SharedTexture {
+        // Name 0 is never deleted, so dropping what is left frees nothing
+        SharedTexture {
+            id: mem::replace(&mut self.id, 0),
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            file_path: mem::take(&mut self.file_path),
+        }
+    }
+
+    pub fn load(&mut self, _context: &GlContext) {
+        unsafe {
+            #[cfg(feature = "gl45")]
+            {
+                if capabilities::direct_state_access() {
+                    self.load_direct();
+                    return;
+                }
+            }
+
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.id));
+
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::REPEAT as i32
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::REPEAT as i32
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as i32
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as i32
+            ));
+            if let Some(swizzle) = self.format.swizzle {
+                for (parameter, source) in SWIZZLE_PARAMETERS.iter().zip(swizzle.iter()) {
+                    gl_check!(gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        *parameter,
+                        *source as i32
+                    ));
+                }
+            }
 
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl_check!(gl::PixelStorei(
+                gl::UNPACK_ALIGNMENT,
+                unpack_alignment(self.row_bytes())
+            ));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                self.format.internal_format as i32,
+                self.width as i32,
+                self.height as i32,
+                0,
+                self.format.format,
+                self.format.pixel_type,
+                self.data.as_ptr() as *const c_void,
+            ));
+            gl_check!(gl::PixelStorei(
+                gl::UNPACK_ALIGNMENT,
+                DEFAULT_UNPACK_ALIGNMENT
+            ));
+            gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D));
+            gpu_memory::track(
+                GpuResource::Texture,
+                self.id,
+                gpu_memory::texture_bytes(
+                    self.width,
+                    self.height,
+                    1,
+                    self.format.bytes_per_pixel(),
+                    true,
+                ),
+            );
+            debug_label::set(gl::TEXTURE, self.id, &self.file_path);
+
+            self.data.clear();
+        }
+    }
 
-        gl::TexImage2D(
-            gl::TEXTURE_2D,
+    /// `load` without binding: immutable storage sized for the full mip chain, then an upload.
+    #[cfg(feature = "gl45")]
+    unsafe fn load_direct(&mut self) {
+        for (parameter, value) in [
+            (gl::TEXTURE_WRAP_S, gl::REPEAT),
+            (gl::TEXTURE_WRAP_T, gl::REPEAT),
+            (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+            (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+        ]
+        .iter()
+        {
+            gl_check!(gl::TextureParameteri(self.id, *parameter, *value as i32));
+        }
+        if let Some(swizzle) = self.format.swizzle {
+            for (parameter, source) in SWIZZLE_PARAMETERS.iter().zip(swizzle.iter()) {
+                gl_check!(gl::TextureParameteri(self.id, *parameter, *source as i32));
+            }
+        }
+
+        let levels = 32 - self.width.max(self.height).max(1).leading_zeros();
+        gl_check!(gl::TextureStorage2D(
+            self.id,
+            levels as GLsizei,
+            self.format.internal_format,
+            self.width as i32,
+            self.height as i32
+        ));
+        gl_check!(gl::PixelStorei(
+            gl::UNPACK_ALIGNMENT,
+            unpack_alignment(self.row_bytes())
+        ));
+        gl_check!(gl::TextureSubImage2D(
+            self.id,
+            0,
+            0,
             0,
-            gl::RGB as i32,
             self.width as i32,
             self.height as i32,
-            0,
-            gl::RGB,
-            gl::UNSIGNED_BYTE,
-            self.data[0].as_ptr() as *const c_void,
+            self.format.format,
+            self.format.pixel_type,
+            self.data.as_ptr() as *const c_void,
+        ));
+        gl_check!(gl::PixelStorei(
+            gl::UNPACK_ALIGNMENT,
+            DEFAULT_UNPACK_ALIGNMENT
+        ));
+        gl_check!(gl::GenerateTextureMipmap(self.id));
+        gpu_memory::track(
+            GpuResource::Texture,
+            self.id,
+            gpu_memory::texture_bytes(
+                self.width,
+                self.height,
+                1,
+                self.format.bytes_per_pixel(),
+                true,
+            ),
         );
-        gl::GenerateMipmap(gl::TEXTURE_2D);
+        debug_label::set(gl::TEXTURE, self.id, &self.file_path);
 
         self.data.clear();
     }
 
+    /// Bytes in a row of `data`, which has no padding between rows.
+    fn row_bytes(&self) -> usize {
+        self.width as usize * self.format.bytes_per_pixel()
+    }
+
+    /// Decodes an image into rows of tightly packed pixels, in the format chosen for it.
+    /// Paletted images come out of the decoders as RGB or RGBA, with any transparent palette
+    /// entries in alpha.
     fn load_data_from_file(
         file_path: &str,
         flip_vertically: bool,
-    ) -> Result<(u32, u32, Vec<[u8; 3]>), String> {
-        match image::open(Path::new(file_path)) {
-            Ok(img) => {
-                let img = if flip_vertically { img.flipv() } else { img };
-                let (width, height) = img.dimensions();
-                let data: Vec<_> = img.into_rgb().pixels().map(|p| p.0).collect();
-                Ok((width, height, data))
+    ) -> Result<(u32, u32, TextureFormat, Vec<u8>), String> {
+        let img = image::open(Path::new(file_path)).map_err(|err| err.to_string())?;
+        let img = if flip_vertically { img.flipv() } else { img };
+        let (width, height) = img.dimensions();
+        // OpenGL ES 3.0 has no 16-bit normalized formats, and BGR orders are not worth keeping
+        let es = capabilities::opengl_es();
+        let img = match img {
+            DynamicImage::ImageLuma16(_) if es => DynamicImage::ImageLuma8(img.into_luma8()),
+            DynamicImage::ImageLumaA16(_) if es => {
+                DynamicImage::ImageLumaA8(img.into_luma_alpha8())
+            }
+            DynamicImage::ImageRgb16(_) if es => DynamicImage::ImageRgb8(img.into_rgb8()),
+            DynamicImage::ImageRgba16(_) if es => DynamicImage::ImageRgba8(img.into_rgba8()),
+            DynamicImage::ImageBgr8(_) => DynamicImage::ImageRgb8(img.into_rgb8()),
+            DynamicImage::ImageBgra8(_) => DynamicImage::ImageRgba8(img.into_rgba8()),
+            img => img,
+        };
+        let color = img.color();
+        let format = TextureFormat::for_channels(
+            color.channel_count(),
+            color.bytes_per_pixel() / color.channel_count() == 2,
+        );
+        Ok((width, height, format, img.as_bytes().to_vec()))
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Texture, self.id);
+    }
+}
+
+/// The largest `GL_UNPACK_ALIGNMENT` rows of `row_bytes` bytes meet. Tightly packed RGB and
+/// gray rows are often no multiple of 4 bytes, and read with the default alignment they shear.
+fn unpack_alignment(row_bytes: usize) -> GLint {
+    [8, 4, 2]
+        .iter()
+        .copied()
+        .find(|&alignment| row_bytes.is_multiple_of(alignment))
+        .unwrap_or(1) as GLint
+}
+
+impl MeshData {
+    pub fn floats_per_vertex(&self) -> usize {
+        self.attributes.iter().map(|a| a.components as usize).sum()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.floats_per_vertex()
+    }
+
+    /// Offset in floats of the attribute bound to `location` within a vertex.
+    pub fn attribute_offset(&self, location: GLuint) -> Option<usize> {
+        let mut offset = 0_usize;
+        for attribute in &self.attributes {
+            if attribute.location == location {
+                return Some(offset);
+            }
+            offset += attribute.components as usize;
+        }
+        None
+    }
+}
+
+impl Mesh {
+    pub fn from_data(_context: &GlContext, data: &MeshData) -> Mesh {
+        unsafe { Self::build(&data.vertices, data.indices.as_deref(), &data.attributes) }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_vertices(
+        _context: &GlContext,
+        vertices: &[f32],
+        attributes: &[VertexAttribute],
+    ) -> Mesh {
+        unsafe { Self::build(vertices, None, attributes) }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_indices(
+        _context: &GlContext,
+        vertices: &[f32],
+        indices: &[u32],
+        attributes: &[VertexAttribute],
+    ) -> Mesh {
+        unsafe { Self::build(vertices, Some(indices), attributes) }
+    }
+
+    unsafe fn build(
+        vertices: &[f32],
+        indices: Option<&[u32]>,
+        attributes: &[VertexAttribute],
+    ) -> Mesh {
+        #[cfg(feature = "gl45")]
+        {
+            if capabilities::direct_state_access() {
+                return Self::build_direct(vertices, indices, attributes);
+            }
+        }
+
+        let floats_per_vertex: GLint = attributes.iter().map(|a| a.components).sum();
+        let (mut vertex_array_obj, mut vertex_buffer_obj) = (0_u32, 0_u32);
+
+        gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+        gl_check!(gl::GenBuffers(1, &mut vertex_buffer_obj));
+        live_objects::created(GlObject::VertexArray, vertex_array_obj);
+        live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+
+        // Bind VAO
+        gl_check!(gl::BindVertexArray(vertex_array_obj));
+
+        // Setup vertices data and properties
+        gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_obj));
+        gl_check!(gl::BufferData(
+            gl::ARRAY_BUFFER,
+            mem::size_of_val(vertices) as GLsizeiptr,
+            vertices.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+        ));
+        gpu_memory::track(
+            GpuResource::Buffer,
+            vertex_buffer_obj,
+            mem::size_of_val(vertices),
+        );
+
+        let element_buffer_obj = indices.map(|indices| {
+            let mut element_buffer_obj = 0_u32;
+            gl_check!(gl::GenBuffers(1, &mut element_buffer_obj));
+            live_objects::created(GlObject::Buffer, element_buffer_obj);
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, element_buffer_obj));
+            gl_check!(gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                mem::size_of_val(indices) as GLsizeiptr,
+                indices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            ));
+            gpu_memory::track(
+                GpuResource::Buffer,
+                element_buffer_obj,
+                mem::size_of_val(indices),
+            );
+            element_buffer_obj
+        });
+
+        set_attribute_pointers(attributes);
+
+        // Unbind VAO
+        gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+        gl_check!(gl::BindVertexArray(0));
+        gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+
+        Mesh {
+            vertex_array_obj,
+            objects: Rc::new(MeshObjects {
+                vertex_array_obj,
+                vertex_buffer_obj,
+                element_buffer_obj,
+            }),
+            vertex_count: vertices.len() as GLsizei / floats_per_vertex,
+            index_count: indices.map_or(0, |indices| indices.len() as GLsizei),
+            first_index: 0,
+            attributes: attributes.to_vec(),
+        }
+    }
+
+    /// `build` without binding: buffers are filled by name and the VAO reads them through a
+    /// single vertex buffer binding.
+    #[cfg(feature = "gl45")]
+    unsafe fn build_direct(
+        vertices: &[f32],
+        indices: Option<&[u32]>,
+        attributes: &[VertexAttribute],
+    ) -> Mesh {
+        let floats_per_vertex: GLint = attributes.iter().map(|a| a.components).sum();
+        let (mut vertex_array_obj, mut vertex_buffer_obj) = (0_u32, 0_u32);
+
+        gl_check!(gl::CreateVertexArrays(1, &mut vertex_array_obj));
+        gl_check!(gl::CreateBuffers(1, &mut vertex_buffer_obj));
+        live_objects::created(GlObject::VertexArray, vertex_array_obj);
+        live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+        gl_check!(gl::NamedBufferData(
+            vertex_buffer_obj,
+            mem::size_of_val(vertices) as GLsizeiptr,
+            vertices.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+        ));
+        gpu_memory::track(
+            GpuResource::Buffer,
+            vertex_buffer_obj,
+            mem::size_of_val(vertices),
+        );
+
+        let element_buffer_obj = indices.map(|indices| {
+            let mut element_buffer_obj = 0_u32;
+            gl_check!(gl::CreateBuffers(1, &mut element_buffer_obj));
+            live_objects::created(GlObject::Buffer, element_buffer_obj);
+            gl_check!(gl::NamedBufferData(
+                element_buffer_obj,
+                mem::size_of_val(indices) as GLsizeiptr,
+                indices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            ));
+            gpu_memory::track(
+                GpuResource::Buffer,
+                element_buffer_obj,
+                mem::size_of_val(indices),
+            );
+            gl_check!(gl::VertexArrayElementBuffer(
+                vertex_array_obj,
+                element_buffer_obj
+            ));
+            element_buffer_obj
+        });
+
+        let stride = floats_per_vertex * mem::size_of::<GLfloat>() as GLsizei;
+        gl_check!(gl::VertexArrayVertexBuffer(
+            vertex_array_obj,
+            0,
+            vertex_buffer_obj,
+            0,
+            stride
+        ));
+        let mut offset = 0_usize;
+        for attribute in attributes {
+            gl_check!(gl::VertexArrayAttribFormat(
+                vertex_array_obj,
+                attribute.location,
+                attribute.components,
+                gl::FLOAT,
+                gl::FALSE,
+                (offset * mem::size_of::<GLfloat>()) as GLuint,
+            ));
+            gl_check!(gl::VertexArrayAttribBinding(
+                vertex_array_obj,
+                attribute.location,
+                0
+            ));
+            gl_check!(gl::EnableVertexArrayAttrib(
+                vertex_array_obj,
+                attribute.location
+            ));
+            offset += attribute.components as usize;
+        }
+
+        Mesh {
+            vertex_array_obj,
+            objects: Rc::new(MeshObjects {
+                vertex_array_obj,
+                vertex_buffer_obj,
+                element_buffer_obj,
+            }),
+            vertex_count: vertices.len() as GLsizei / floats_per_vertex,
+            index_count: indices.map_or(0, |indices| indices.len() as GLsizei),
+            first_index: 0,
+            attributes: attributes.to_vec(),
+        }
+    }
+
+    /// Builds a vertex array in the current context over buffers uploaded beforehand, failing
+    /// and deleting them when they hold fewer vertices or indices than `buffers` counts.
+    #[allow(dead_code)]
+    pub fn from_buffers(_context: &GlContext, buffers: MeshBuffers) -> Result<Mesh, String> {
+        unsafe {
+            let floats_per_vertex: GLint = buffers.attributes.iter().map(|a| a.components).sum();
+            let vertex_bytes = buffers.vertex_count as usize
+                * floats_per_vertex as usize
+                * mem::size_of::<GLfloat>();
+            let index_bytes = buffers.index_count as usize * mem::size_of::<GLuint>();
+            let element_bytes = buffers
+                .element_buffer_obj
+                .map_or(0, |element_buffer_obj| buffer_size(element_buffer_obj));
+            if buffers.vertex_count < 0
+                || buffers.index_count < 0
+                || vertex_bytes > buffer_size(buffers.vertex_buffer_obj)
+                || index_bytes > element_bytes
+            {
+                live_objects::delete(GlObject::Buffer, buffers.vertex_buffer_obj);
+                if let Some(element_buffer_obj) = buffers.element_buffer_obj {
+                    live_objects::delete(GlObject::Buffer, element_buffer_obj);
+                }
+                return Err(format!(
+                    "Mesh buffers are too small for {} vertices and {} indices",
+                    buffers.vertex_count, buffers.index_count
+                ));
+            }
+
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, buffers.vertex_buffer_obj));
+            if let Some(element_buffer_obj) = buffers.element_buffer_obj {
+                gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, element_buffer_obj));
+            }
+            set_attribute_pointers(&buffers.attributes);
+
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+
+            Ok(Mesh {
+                vertex_array_obj,
+                objects: Rc::new(MeshObjects {
+                    vertex_array_obj,
+                    vertex_buffer_obj: buffers.vertex_buffer_obj,
+                    element_buffer_obj: buffers.element_buffer_obj,
+                }),
+                vertex_count: buffers.vertex_count,
+                index_count: buffers.index_count,
+                first_index: 0,
+                attributes: buffers.attributes,
+            })
+        }
+    }
+
+    /// Draws `index_count` indices from `first_index` on with this mesh's buffers and vertex
+    /// array, e.g. one of the meshes packed by a `MeshPool`. Only indexed meshes can be split,
+    /// and only within their own indices.
+    pub fn sub_mesh(&self, first_index: GLsizei, index_count: GLsizei) -> Result<Mesh, String> {
+        if !self.is_indexed() {
+            return Err("Only indexed meshes have sub-meshes".to_string());
+        }
+        if first_index < 0
+            || index_count < 0
+            || first_index as i64 + index_count as i64 > self.index_count as i64
+        {
+            return Err(format!(
+                "Sub-mesh of {} indices from {} on is outside the mesh's {} indices",
+                index_count, first_index, self.index_count
+            ));
+        }
+        Ok(Mesh {
+            vertex_array_obj: self.vertex_array_obj,
+            objects: Rc::clone(&self.objects),
+            vertex_count: self.vertex_count,
+            index_count,
+            first_index: self.first_index + first_index,
+            attributes: self.attributes.clone(),
+        })
+    }
+
+    pub fn vertex_array_obj(&self) -> GLuint {
+        self.vertex_array_obj
+    }
+
+    #[allow(dead_code)]
+    pub fn vertex_count(&self) -> GLsizei {
+        self.vertex_count
+    }
+
+    #[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+    pub fn index_count(&self) -> GLsizei {
+        self.index_count
+    }
+
+    /// Index draws start at, for meshes sharing their buffers with others
+    #[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+    pub fn first_index(&self) -> GLsizei {
+        self.first_index
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.objects.element_buffer_obj.is_some()
+    }
+
+    /// Names the vertex array and buffers for debuggers, see `debug_label::set`.
+    pub fn set_label(&self, label: &str) {
+        unsafe {
+            debug_label::set(gl::VERTEX_ARRAY, self.vertex_array_obj, label);
+            debug_label::set(
+                gl::BUFFER,
+                self.objects.vertex_buffer_obj,
+                &format!("{} vertices", label),
+            );
+            if let Some(element_buffer_obj) = self.objects.element_buffer_obj {
+                debug_label::set(
+                    gl::BUFFER,
+                    element_buffer_obj,
+                    &format!("{} indices", label),
+                );
+            }
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        if self.objects.element_buffer_obj.is_some() {
+            self.index_count as usize / 3
+        } else {
+            self.vertex_count as usize / 3
+        }
+    }
+
+    pub fn draw(&self, _context: &GlContext) {
+        unsafe {
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            if self.objects.element_buffer_obj.is_some() {
+                gl_check!(gl::DrawElements(
+                    gl::TRIANGLES,
+                    self.index_count,
+                    gl::UNSIGNED_INT,
+                    self.index_offset()
+                ));
+            } else {
+                gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_count));
+            }
+        }
+    }
+
+    /// Draws `instance_count` copies, with per-instance attributes set up by the caller.
+    pub fn draw_instanced(&self, _context: &GlContext, instance_count: GLsizei) {
+        unsafe {
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            if self.objects.element_buffer_obj.is_some() {
+                gl_check!(gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    self.index_count,
+                    gl::UNSIGNED_INT,
+                    self.index_offset(),
+                    instance_count
+                ));
+            } else {
+                gl_check!(gl::DrawArraysInstanced(
+                    gl::TRIANGLES,
+                    0,
+                    self.vertex_count,
+                    instance_count
+                ));
+            }
+        }
+    }
+
+    /// Byte offset of `first_index` in the element buffer, as the draw calls take it.
+    fn index_offset(&self) -> *const c_void {
+        (self.first_index as usize * mem::size_of::<GLuint>()) as *const c_void
+    }
+}
+
+impl Drop for MeshObjects {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete(GlObject::Buffer, self.vertex_buffer_obj);
+            if let Some(element_buffer_obj) = self.element_buffer_obj {
+                live_objects::delete(GlObject::Buffer, element_buffer_obj);
+            }
+        }
+    }
+}
+
+impl MeshBuffers {
+    /// Fills new buffers from `data` without touching any vertex array, so it can run in a
+    /// context other than the one that will draw.
+    pub fn upload(_context: &GlContext, data: &MeshData) -> MeshBuffers {
+        unsafe {
+            let mut vertex_buffer_obj = 0_u32;
+            gl_check!(gl::GenBuffers(1, &mut vertex_buffer_obj));
+            live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_obj));
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(data.vertices.as_slice()) as GLsizeiptr,
+                data.vertices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            ));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gpu_memory::track(
+                GpuResource::Buffer,
+                vertex_buffer_obj,
+                mem::size_of_val(data.vertices.as_slice()),
+            );
+
+            let element_buffer_obj = data.indices.as_ref().map(|indices| {
+                let mut element_buffer_obj = 0_u32;
+                gl_check!(gl::GenBuffers(1, &mut element_buffer_obj));
+                live_objects::created(GlObject::Buffer, element_buffer_obj);
+                // Without a vertex array bound, ELEMENT_ARRAY_BUFFER is not a core profile target
+                gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, element_buffer_obj));
+                gl_check!(gl::BufferData(
+                    gl::COPY_WRITE_BUFFER,
+                    mem::size_of_val(indices.as_slice()) as GLsizeiptr,
+                    indices.as_ptr() as *const c_void,
+                    gl::STATIC_DRAW,
+                ));
+                gl_check!(gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0));
+                gpu_memory::track(
+                    GpuResource::Buffer,
+                    element_buffer_obj,
+                    mem::size_of_val(indices.as_slice()),
+                );
+                element_buffer_obj
+            });
+
+            MeshBuffers {
+                vertex_buffer_obj,
+                element_buffer_obj,
+                vertex_count: data.vertex_count() as GLsizei,
+                index_count: data
+                    .indices
+                    .as_ref()
+                    .map_or(0, |indices| indices.len() as GLsizei),
+                attributes: data.attributes.clone(),
+            }
+        }
+    }
+}
+
+/// Columns, rows and whether it holds floats, of a vertex shader input type; vectors are one
+/// column. None for types meshes cannot feed, such as doubles.
+fn attribute_shape(attribute_type: GLenum) -> Option<(GLint, GLint, bool)> {
+    Some(match attribute_type {
+        gl::FLOAT => (1, 1, true),
+        gl::FLOAT_VEC2 => (1, 2, true),
+        gl::FLOAT_VEC3 => (1, 3, true),
+        gl::FLOAT_VEC4 => (1, 4, true),
+        gl::FLOAT_MAT2 => (2, 2, true),
+        gl::FLOAT_MAT3 => (3, 3, true),
+        gl::FLOAT_MAT4 => (4, 4, true),
+        gl::INT | gl::UNSIGNED_INT => (1, 1, false),
+        gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => (1, 2, false),
+        gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => (1, 3, false),
+        gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 => (1, 4, false),
+        _ => return None,
+    })
+}
+
+fn glsl_type_name(attribute_type: GLenum) -> String {
+    match attribute_type {
+        gl::FLOAT => "float".to_string(),
+        gl::FLOAT_VEC2 => "vec2".to_string(),
+        gl::FLOAT_VEC3 => "vec3".to_string(),
+        gl::FLOAT_VEC4 => "vec4".to_string(),
+        gl::FLOAT_MAT2 => "mat2".to_string(),
+        gl::FLOAT_MAT3 => "mat3".to_string(),
+        gl::FLOAT_MAT4 => "mat4".to_string(),
+        gl::INT => "int".to_string(),
+        gl::INT_VEC2 => "ivec2".to_string(),
+        gl::INT_VEC3 => "ivec3".to_string(),
+        gl::INT_VEC4 => "ivec4".to_string(),
+        gl::UNSIGNED_INT => "uint".to_string(),
+        gl::UNSIGNED_INT_VEC2 => "uvec2".to_string(),
+        gl::UNSIGNED_INT_VEC3 => "uvec3".to_string(),
+        gl::UNSIGNED_INT_VEC4 => "uvec4".to_string(),
+        _ => format!("type 0x{:X}", attribute_type),
+    }
+}
+
+/// Points the attributes of the bound vertex array at interleaved floats in the bound
+/// `ARRAY_BUFFER`.
+/// Size in bytes of `buffer`'s data store, read through the copy-read binding.
+unsafe fn buffer_size(buffer: GLuint) -> usize {
+    let mut size: GLint = 0;
+    gl_check!(gl::BindBuffer(gl::COPY_READ_BUFFER, buffer));
+    gl_check!(gl::GetBufferParameteriv(
+        gl::COPY_READ_BUFFER,
+        gl::BUFFER_SIZE,
+        &mut size
+    ));
+    gl_check!(gl::BindBuffer(gl::COPY_READ_BUFFER, 0));
+    size.max(0) as usize
+}
+
+unsafe fn set_attribute_pointers(attributes: &[VertexAttribute]) {
+    let floats_per_vertex: GLint = attributes.iter().map(|a| a.components).sum();
+    let stride = floats_per_vertex * mem::size_of::<GLfloat>() as GLsizei;
+    let mut offset = 0_usize;
+    for attribute in attributes {
+        gl_check!(gl::VertexAttribPointer(
+            attribute.location,
+            attribute.components,
+            gl::FLOAT,
+            gl::FALSE,
+            stride,
+            if offset == 0 {
+                ptr::null()
+            } else {
+                (offset * mem::size_of::<GLfloat>()) as *const c_void
+            },
+        ));
+        gl_check!(gl::EnableVertexAttribArray(attribute.location));
+        offset += attribute.components as usize;
+    }
+}
+
+impl Material {
+    pub fn new(shader_program: Rc<ShaderProgram>, textures: Vec<GLuint>) -> Material {
+        static NEXT_MATERIAL_ID: AtomicU32 = AtomicU32::new(1);
+        Material {
+            id: NEXT_MATERIAL_ID.fetch_add(1, Ordering::Relaxed),
+            shader_program,
+            textures,
+            pass: RenderPass::Opaque,
+            texture_table: None,
+            texture_set: 0,
+            outline: None,
+        }
+    }
+
+    pub fn with_outline(mut self, outline: HullOutline) -> Material {
+        self.outline = Some(outline);
+        self
+    }
+
+    /// Samples the material's textures from set `texture_set` of `table` instead of binding
+    /// them; the shader has to be prepared for the table with `texture_table::prepare_shader`.
+    pub fn with_texture_set(mut self, table: &Rc<TextureTable>, texture_set: u32) -> Material {
+        self.texture_table = Some(Rc::clone(table));
+        self.texture_set = texture_set;
+        self
+    }
+
+    /// Whether drawing with `other` after this material needs no rebinding, so one instanced
+    /// draw can cover both. Materials reading one texture table differ only in per-instance
+    /// data.
+    pub fn shares_bindings(&self, other: &Material) -> bool {
+        if self.id == other.id {
+            return true;
+        }
+        let same_table = match (&self.texture_table, &other.texture_table) {
+            (Some(table), Some(other_table)) => Rc::ptr_eq(table, other_table),
+            _ => false,
+        };
+        same_table
+            && self.shader_program.id == other.shader_program.id
+            && self.pass == other.pass
+            && self.outline == other.outline
+    }
+
+    #[allow(dead_code)]
+    pub fn with_pass(mut self, pass: RenderPass) -> Material {
+        self.pass = pass;
+        self
+    }
+
+    pub fn bind(&self) {
+        self.shader_program.use_program();
+        unsafe {
+            if let Some(texture_table) = &self.texture_table {
+                texture_table.bind();
+                return;
+            }
+            for (tex_i, tex_obj) in self.textures.iter().enumerate() {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + tex_i as u32));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, *tex_obj));
             }
-            Err(err) => Err(err.to_string()),
         }
     }
 }
 
 impl Camera {
+    /// Recomputes `front` from `yaw` and `pitch`.
+    pub fn update_front(&mut self) {
+        let mut camera_front = Vec3::default();
+        camera_front.x = self.yaw.to_radians().cos() * self.pitch.to_radians().cos();
+        camera_front.y = self.pitch.to_radians().sin();
+        camera_front.z = self.yaw.to_radians().sin() * self.pitch.to_radians().cos();
+        self.front = glm::normalize(&camera_front);
+    }
+
     pub fn view_matrix(&self) -> Mat4 {
         glm::look_at(&self.position, &(&self.position + &self.front), &self.up)
     }
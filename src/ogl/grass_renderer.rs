@@ -0,0 +1,400 @@
+use gl::types::*;
+
+use crate::math::glm;
+use crate::math::random::Rng;
+use crate::ogl::buffer::Buffer;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::renderer::Renderer;
+use crate::terrain::GrassTuft;
+use std::ffi::c_void;
+use std::mem;
+
+// Floats per tuft in the instance buffer: position, scale and rotation
+const TUFT_FLOATS: usize = 5;
+// Three crossed quads of two triangles each
+const TUFT_VERTICES: GLsizei = 18;
+// Width and height of the blade texture
+const BLADE_TEXTURE_SIZE: usize = 128;
+const BLADES_PER_TEXTURE: usize = 9;
+// Distance from the camera at which tufts start fading out, and past which they are gone
+const FADE_START: f32 = 22.0_f32;
+const FADE_END: f32 = 34.0_f32;
+// Blade texture alpha below which alpha tested fragments are discarded
+const ALPHA_CUTOFF: f32 = 0.5_f32;
+
+const GRASS_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec4 a_position_scale;
+layout (location = 1) in float a_rotation;
+
+uniform mat4 projection_from_world;
+uniform vec3 camera_position;
+uniform float time;
+uniform float fade_start;
+uniform float fade_end;
+
+out vec2 o_uv;
+out float o_fade;
+out float o_shade;
+
+const float PI = 3.14159265f;
+const float TUFT_WIDTH = 1.0f;
+const float TUFT_HEIGHT = 0.8f;
+const vec2 WIND_DIRECTION = vec2(0.8f, 0.6f);
+// Two triangles per quad, from the bottom left corner
+const vec2 CORNERS[6] = vec2[6](
+    vec2(0.0f, 0.0f), vec2(1.0f, 0.0f), vec2(1.0f, 1.0f),
+    vec2(0.0f, 0.0f), vec2(1.0f, 1.0f), vec2(0.0f, 1.0f)
+);
+
+void main() {
+    vec3 root = a_position_scale.xyz;
+    float scale = a_position_scale.w;
+    vec2 corner = CORNERS[gl_VertexID % 6];
+    // Quads crossed at 60 degrees, so the tuft looks full from any side
+    float angle = a_rotation + float(gl_VertexID / 6) * PI / 3.0f;
+    vec3 across = vec3(cos(angle), 0.0f, sin(angle));
+    vec3 position = root + (across * (corner.x - 0.5f) * TUFT_WIDTH + vec3(0.0f, corner.y * TUFT_HEIGHT, 0.0f)) * scale;
+
+    // Gusts roll across the field as waves; bending grows with the square of the height, so
+    // roots stay planted
+    float gust = sin(time * 1.6f + dot(root.xz, vec2(0.3f, 0.2f))) * 0.7f
+        + sin(time * 3.7f + root.x * 1.3f + root.z * 0.7f) * 0.3f;
+    position.xz += WIND_DIRECTION * (gust * 0.3f + 0.15f) * corner.y * corner.y * scale;
+
+    o_uv = corner;
+    o_fade = 1.0f - smoothstep(fade_start, fade_end, distance(root, camera_position));
+    // Darker toward the roots, where blades shade each other
+    o_shade = mix(0.45f, 1.0f, corner.y);
+    gl_Position = projection_from_world * vec4(position, 1.0f);
+    if (o_fade <= 0.0f) {
+        // Entirely outside the clip volume, so faded out tufts cost no fragments
+        gl_Position = vec4(2.0f, 2.0f, 2.0f, 1.0f);
+    }
+}
+"#;
+
+const GRASS_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_uv;
+in float o_fade;
+in float o_shade;
+
+uniform sampler2D blades;
+uniform bool alpha_blend;
+uniform float alpha_cutoff;
+
+out vec4 frag_color;
+
+// 4x4 ordered dither thresholds
+const float BAYER[16] = float[16](
+    0.0f, 8.0f, 2.0f, 10.0f,
+    12.0f, 4.0f, 14.0f, 6.0f,
+    3.0f, 11.0f, 1.0f, 9.0f,
+    15.0f, 7.0f, 13.0f, 5.0f
+);
+
+void main() {
+    vec4 texel = texture(blades, o_uv);
+    vec3 color = texel.rgb * o_shade;
+    if (alpha_blend) {
+        // Soft edges and a smooth fade, but only right when drawn back to front
+        float alpha = texel.a * o_fade;
+        if (alpha < 0.01f) {
+            discard;
+        }
+        frag_color = vec4(color, alpha);
+    } else {
+        // Hard edges in any order with depth writes on; the fade becomes a dither pattern where a
+        // growing share of pixels fails the test
+        ivec2 pixel = ivec2(gl_FragCoord.xy) & 3;
+        float threshold = (BAYER[pixel.y * 4 + pixel.x] + 0.5f) / 16.0f;
+        if (texel.a < alpha_cutoff || o_fade < threshold) {
+            discard;
+        }
+        frag_color = vec4(color, 1.0f);
+    }
+}
+"#;
+
+/// Draws grass tufts as instanced crossed quads swaying in the wind and fading out with
+/// distance. Tufts are either alpha tested, writing depth in any order, or alpha blended,
+/// sorted back to front every frame. Draw them after the ground they stand on.
+pub struct GrassRenderer {
+    /// Alpha blend instead of alpha testing
+    pub alpha_blend: bool,
+    tufts: Vec<GrassTuft>,
+    time: f32,
+    blade_texture: GLuint,
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    instance_buffer: Buffer,
+    instance_data: Vec<f32>,
+    /// Tuft indices, sorted back to front when alpha blending
+    draw_order: Vec<(f32, usize)>,
+    projection_from_world_uniform: Uniform,
+    camera_position_uniform: Uniform,
+    time_uniform: Uniform,
+    alpha_blend_uniform: Uniform,
+    context: GlContext,
+}
+
+impl GrassRenderer {
+    pub fn new(
+        context: &GlContext,
+        tufts: Vec<GrassTuft>,
+        seed: u64,
+    ) -> Result<GrassRenderer, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                GRASS_VERTEX_SHADER_SOURCE,
+                GRASS_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("grass");
+            let blade_texture = upload_blade_texture(&blade_texels(seed));
+            debug_label::set(gl::TEXTURE, blade_texture, "grass blades");
+            let instance_buffer = Buffer::new(context, gl::DYNAMIC_DRAW);
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, instance_buffer.id));
+            let stride = (TUFT_FLOATS * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_position_scale and a_rotation attributes
+            let mut offset = 0;
+            for (location, components) in [(0, 4), (1, 1)].iter() {
+                gl_check!(gl::VertexAttribPointer(
+                    *location,
+                    *components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (offset * mem::size_of::<GLfloat>()) as *const c_void,
+                ));
+                gl_check!(gl::EnableVertexAttribArray(*location));
+                gl_check!(gl::VertexAttribDivisor(*location, 1));
+                offset += *components as usize;
+            }
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "grass");
+            instance_buffer.set_label(context, "grass tufts");
+
+            shader_program.use_program();
+            shader_program.uniform("blades").set_int(0);
+            shader_program.uniform("fade_start").set_float(FADE_START);
+            shader_program.uniform("fade_end").set_float(FADE_END);
+            shader_program
+                .uniform("alpha_cutoff")
+                .set_float(ALPHA_CUTOFF);
+            let projection_from_world_uniform = shader_program.uniform("projection_from_world");
+            let camera_position_uniform = shader_program.uniform("camera_position");
+            let time_uniform = shader_program.uniform("time");
+            let alpha_blend_uniform = shader_program.uniform("alpha_blend");
+
+            // Alpha tested tufts draw in any order, so the first upload lasts until blending is on
+            let draw_order = (0..tufts.len()).map(|tuft_i| (0.0_f32, tuft_i)).collect();
+            let mut grass_renderer = GrassRenderer {
+                alpha_blend: false,
+                tufts,
+                time: 0.0_f32,
+                blade_texture,
+                shader_program,
+                vertex_array_obj,
+                instance_buffer,
+                instance_data: vec![],
+                draw_order,
+                projection_from_world_uniform,
+                camera_position_uniform,
+                time_uniform,
+                alpha_blend_uniform,
+                context: *context,
+            };
+            grass_renderer.upload_instances();
+            Ok(grass_renderer)
+        }
+    }
+
+    pub fn tuft_count(&self) -> usize {
+        self.tufts.len()
+    }
+
+    /// Moves the wind on by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /// Draws every tuft with one instanced draw call.
+    pub fn draw(&mut self, camera: &Camera, renderer: &mut Renderer) {
+        if self.tufts.is_empty() {
+            return;
+        }
+        if self.alpha_blend {
+            self.sort_back_to_front(camera);
+            self.upload_instances();
+        }
+        self.shader_program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(&(renderer.projection_from_view() * camera.view_matrix()));
+        self.camera_position_uniform.set_vec3f([
+            camera.position.x,
+            camera.position.y,
+            camera.position.z,
+        ]);
+        self.time_uniform.set_float(self.time);
+        self.alpha_blend_uniform.set_bool(self.alpha_blend);
+        unsafe {
+            if self.alpha_blend {
+                gl_check!(gl::Enable(gl::BLEND));
+                gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+                // Tufts in front are drawn later, so they must not hide the ones behind
+                gl_check!(gl::DepthMask(gl::FALSE));
+            }
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.blade_texture));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::DrawArraysInstanced(
+                gl::TRIANGLES,
+                0,
+                TUFT_VERTICES,
+                self.tufts.len() as GLsizei
+            ));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            if self.alpha_blend {
+                gl_check!(gl::DepthMask(gl::TRUE));
+                gl_check!(gl::Disable(gl::BLEND));
+            }
+        }
+        renderer.stats.record_draw(6 * self.tufts.len() as u64);
+    }
+
+    fn sort_back_to_front(&mut self, camera: &Camera) {
+        self.draw_order.clear();
+        self.draw_order.extend(
+            self.tufts
+                .iter()
+                .enumerate()
+                .map(|(tuft_i, tuft)| (glm::distance2(&tuft.position, &camera.position), tuft_i)),
+        );
+        self.draw_order
+            .sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+    }
+
+    /// Uploads the tufts in `draw_order`.
+    fn upload_instances(&mut self) {
+        self.instance_data.clear();
+        for &(_, tuft_i) in self.draw_order.iter() {
+            let tuft = &self.tufts[tuft_i];
+            self.instance_data.extend_from_slice(&[
+                tuft.position.x,
+                tuft.position.y,
+                tuft.position.z,
+                tuft.scale,
+                tuft.rotation_radians,
+            ]);
+        }
+        self.instance_buffer
+            .set_data(&self.context, &self.instance_data);
+    }
+}
+
+impl Drop for GrassRenderer {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::VertexArray, self.vertex_array_obj);
+        live_objects::release(&self.context, GlObject::Texture, self.blade_texture);
+    }
+}
+
+/// Blades tapering from the bottom row up, leaning this way and that, with antialiased edges.
+/// Transparent texels keep a grass color, so filtering does not darken the edges.
+fn blade_texels(seed: u64) -> Vec<[u8; 4]> {
+    let mut rng = Rng::new(seed);
+    let size = BLADE_TEXTURE_SIZE as f32;
+    let mut texels = vec![[52, 92, 30, 0]; BLADE_TEXTURE_SIZE * BLADE_TEXTURE_SIZE];
+    for _ in 0..BLADES_PER_TEXTURE {
+        let base = rng.range(0.15_f32, 0.85_f32);
+        let lean = rng.range(-0.25_f32, 0.25_f32);
+        let height = rng.range(0.6_f32, 0.98_f32);
+        let half_width = rng.range(0.025_f32, 0.045_f32);
+        // Yellower toward the tips, and from blade to blade
+        let dryness = rng.range(0.0_f32, 0.4_f32);
+        for row in 0..BLADE_TEXTURE_SIZE {
+            let t = (row as f32 + 0.5_f32) / size / height;
+            if t >= 1.0_f32 {
+                break;
+            }
+            let center = base + lean * t * t;
+            let blade_half_width = half_width * (1.0_f32 - t);
+            for column in 0..BLADE_TEXTURE_SIZE {
+                let u = (column as f32 + 0.5_f32) / size;
+                // One texel wide ramp at the edge
+                let coverage = ((blade_half_width - (u - center).abs()) * size + 0.5_f32)
+                    .clamp(0.0_f32, 1.0_f32);
+                if coverage <= 0.0_f32 {
+                    continue;
+                }
+                let yellow = (dryness + 0.5_f32 * t).min(1.0_f32);
+                let texel = &mut texels[row * BLADE_TEXTURE_SIZE + column];
+                *texel = [
+                    (60.0_f32 + 110.0_f32 * yellow) as u8,
+                    (110.0_f32 + 60.0_f32 * yellow) as u8,
+                    (35.0_f32 + 15.0_f32 * yellow) as u8,
+                    (texel[3] as f32).max(coverage * 255.0_f32) as u8,
+                ];
+            }
+        }
+    }
+    texels
+}
+
+unsafe fn upload_blade_texture(texels: &[[u8; 4]]) -> GLuint {
+    let mut texture = 0;
+    gl_check!(gl::GenTextures(1, &mut texture));
+    live_objects::created(GlObject::Texture, texture);
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+    gl_check!(gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA8 as GLint,
+        BLADE_TEXTURE_SIZE as GLsizei,
+        BLADE_TEXTURE_SIZE as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        texels.as_ptr() as *const c_void,
+    ));
+    for (parameter, value) in [
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        texture,
+        gpu_memory::texture_bytes(
+            BLADE_TEXTURE_SIZE as u32,
+            BLADE_TEXTURE_SIZE as u32,
+            1,
+            4,
+            true,
+        ),
+    );
+    texture
+}
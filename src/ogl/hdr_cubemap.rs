@@ -0,0 +1,440 @@
+use gl::types::*;
+use image::codecs::hdr::HdrDecoder;
+
+use crate::math::glm;
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::environment_capture::cubemap_face_cameras;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::ShaderProgram;
+use crate::ogl::live_objects::{self, GlObject};
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+const PANORAMA_UNIT: GLuint = 0;
+// The first twelve bytes of every KTX 1 file
+const KTX_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const KTX_ENDIANNESS: u32 = 0x0403_0201;
+// Identifier and the thirteen header fields after it
+const KTX_HEADER_SIZE: usize = 12 + 13 * 4;
+
+// One triangle covering the face: (-1, -1), (3, -1), (-1, 3)
+const CONVERT_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+out vec2 o_position;
+
+void main() {
+    o_position = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0f;
+    gl_Position = vec4(o_position, 0.0f, 1.0f);
+}
+"#;
+
+const CONVERT_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_position;
+
+uniform sampler2D panorama;
+// The face's camera: where it looks, and which directions are right and up on screen
+uniform vec3 face_front;
+uniform vec3 face_right;
+uniform vec3 face_up;
+
+out vec4 frag_color;
+
+const float PI = 3.14159265f;
+
+void main() {
+    vec3 direction = normalize(face_front + o_position.x * face_right + o_position.y * face_up);
+    // Longitude across the panorama, latitude down it from its top row
+    vec2 uv = vec2(
+        atan(direction.z, direction.x) / (2.0f * PI) + 0.5f,
+        0.5f - asin(clamp(direction.y, -1.0f, 1.0f)) / PI
+    );
+    frag_color = vec4(texture(panorama, uv).rgb, 1.0f);
+}
+"#;
+
+/// How the cubemap's texels are stored, and the KTX fields describing them.
+#[derive(Clone, Copy, PartialEq)]
+struct TexelFormat {
+    internal_format: GLenum,
+    texel_type: GLenum,
+    type_size: u32,
+    bytes_per_texel: usize,
+}
+
+// Keeps the panorama's colors brighter than white
+const HALF_FLOAT_TEXELS: TexelFormat = TexelFormat {
+    internal_format: gl::RGBA16F,
+    texel_type: gl::HALF_FLOAT,
+    type_size: 2,
+    bytes_per_texel: 8,
+};
+// Without float render targets, clipped at white
+const BYTE_TEXELS: TexelFormat = TexelFormat {
+    internal_format: gl::RGBA8,
+    texel_type: gl::UNSIGNED_BYTE,
+    type_size: 1,
+    bytes_per_texel: 4,
+};
+
+/// A cubemap made from an equirectangular `.hdr` panorama, for skyboxes and image based
+/// lighting. Converting renders each face on the GPU, sampling the panorama along the face's
+/// directions; the result can be kept on disk as a KTX file so later runs load it instead.
+pub struct HdrCubemap {
+    texture: GLuint,
+    size: i32,
+    context: GlContext,
+}
+
+impl HdrCubemap {
+    /// The panorama at `hdr_path` as a cubemap `size` pixels across each face. With a
+    /// `cache_directory`, a cached conversion at that size is loaded unless the panorama has
+    /// changed since, and fresh conversions are saved there.
+    pub fn from_equirect(
+        context: &GlContext,
+        hdr_path: &Path,
+        size: i32,
+        cache_directory: Option<&Path>,
+    ) -> Result<HdrCubemap, String> {
+        unsafe {
+            capabilities::check_texture_size(size, size)?;
+            let format = if capabilities::float_render_targets() {
+                HALF_FLOAT_TEXELS
+            } else {
+                BYTE_TEXELS
+            };
+            let cache_path = cache_directory.map(|directory| cache_path(directory, hdr_path, size));
+            if let Some(cache_path) = &cache_path {
+                if is_fresh(cache_path, hdr_path) {
+                    match load_ktx(context, cache_path, size, format) {
+                        Ok(cubemap) => return Ok(cubemap),
+                        Err(err) => eprintln!(
+                            "Converting {} again, cached {} is unusable: {}",
+                            hdr_path.display(),
+                            cache_path.display(),
+                            err
+                        ),
+                    }
+                }
+            }
+            let cubemap = convert(context, hdr_path, size, format)?;
+            // ES only promises RGBA8 readback of the faces
+            if let (Some(cache_path), false) = (&cache_path, capabilities::opengl_es()) {
+                if let Err(err) = cubemap.save_ktx(cache_path, format) {
+                    eprintln!("Failed caching {}: {}", cache_path.display(), err);
+                }
+            }
+            Ok(cubemap)
+        }
+    }
+
+    pub fn texture(&self) -> GLuint {
+        self.texture
+    }
+
+    #[allow(dead_code)]
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    /// Reads every face back and writes them to `path` as a KTX 1 cubemap.
+    unsafe fn save_ktx(&self, path: &Path, format: TexelFormat) -> Result<(), String> {
+        let face_bytes = self.size as usize * self.size as usize * format.bytes_per_texel;
+        let mut contents = Vec::with_capacity(KTX_HEADER_SIZE + 4 + 6 * face_bytes);
+        contents.extend_from_slice(&KTX_IDENTIFIER);
+        for field in [
+            KTX_ENDIANNESS,
+            format.texel_type,
+            format.type_size,
+            gl::RGBA,
+            format.internal_format,
+            gl::RGBA,
+            self.size as u32,
+            self.size as u32,
+            0,
+            0,
+            6,
+            1,
+            0,
+        ]
+        .iter()
+        {
+            contents.extend_from_slice(&field.to_le_bytes());
+        }
+        contents.extend_from_slice(&(face_bytes as u32).to_le_bytes());
+
+        let mut framebuffer = 0_u32;
+        gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+        live_objects::created(GlObject::Framebuffer, framebuffer);
+        gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer));
+        let mut pixels = vec![0_u8; face_bytes];
+        for face in 0..6 {
+            gl_check!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                self.texture,
+                0
+            ));
+            gl_check!(gl::ReadPixels(
+                0,
+                0,
+                self.size,
+                self.size,
+                gl::RGBA,
+                format.texel_type,
+                pixels.as_mut_ptr() as *mut _,
+            ));
+            // Rows and faces are already multiples of four bytes, so need no padding
+            contents.extend_from_slice(&pixels);
+        }
+        gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+        live_objects::delete(GlObject::Framebuffer, framebuffer);
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+        }
+        fs::write(path, contents).map_err(|err| err.to_string())
+    }
+}
+
+impl Drop for HdrCubemap {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Texture, self.texture);
+    }
+}
+
+/// Where the conversion of `hdr_path` at `size` is cached, named after both.
+fn cache_path(cache_directory: &Path, hdr_path: &Path, size: i32) -> PathBuf {
+    let stem = hdr_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    cache_directory.join(format!("{}_{}.ktx", stem, size))
+}
+
+/// Whether `cache_path` exists and was written after `hdr_path` last changed.
+fn is_fresh(cache_path: &Path, hdr_path: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(cache_path), modified(hdr_path)) {
+        (Ok(cached), Ok(source)) => cached >= source,
+        (Ok(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+/// An empty cubemap, or one filled from `faces`, each face's texels one after the other.
+unsafe fn new_cubemap(
+    context: &GlContext,
+    size: i32,
+    format: TexelFormat,
+    faces: Option<&[u8]>,
+) -> HdrCubemap {
+    let face_bytes = size as usize * size as usize * format.bytes_per_texel;
+    let mut texture = 0_u32;
+    gl_check!(gl::GenTextures(1, &mut texture));
+    live_objects::created(GlObject::Texture, texture);
+    gl_check!(gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture));
+    for face in 0..6 {
+        let texels = match faces {
+            Some(faces) => faces[face * face_bytes..].as_ptr() as *const _,
+            None => ptr::null(),
+        };
+        gl_check!(gl::TexImage2D(
+            gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum,
+            0,
+            format.internal_format as GLint,
+            size,
+            size,
+            0,
+            gl::RGBA,
+            format.texel_type,
+            texels,
+        ));
+    }
+    for (parameter, value) in [
+        (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    gl_check!(gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        texture,
+        gpu_memory::texture_bytes(size as u32, size as u32, 6, format.bytes_per_texel, false),
+    );
+    debug_label::set(gl::TEXTURE, texture, "hdr cubemap");
+    HdrCubemap {
+        texture,
+        size,
+        context: *context,
+    }
+}
+
+/// Uploads the panorama at `hdr_path` and renders it into each face of a new cubemap.
+unsafe fn convert(
+    context: &GlContext,
+    hdr_path: &Path,
+    size: i32,
+    format: TexelFormat,
+) -> Result<HdrCubemap, String> {
+    let file = File::open(hdr_path).map_err(|err| err.to_string())?;
+    let decoder = HdrDecoder::new(BufReader::new(file)).map_err(|err| err.to_string())?;
+    let metadata = decoder.metadata();
+    let (width, height) = (metadata.width as i32, metadata.height as i32);
+    capabilities::check_texture_size(width, height)?;
+    let texels: Vec<f32> = decoder
+        .read_image_hdr()
+        .map_err(|err| err.to_string())?
+        .iter()
+        .flat_map(|texel| texel.0.iter().copied())
+        .collect();
+
+    let program = ShaderProgram::with_shaders(
+        context,
+        CONVERT_VERTEX_SHADER_SOURCE,
+        CONVERT_FRAGMENT_SHADER_SOURCE,
+    )?;
+    program.set_label("equirect to cubemap");
+    program.use_program();
+    program.uniform("panorama").set_int(PANORAMA_UNIT as i32);
+
+    // Half floats are filterable everywhere, unlike full floats on ES; the file's top row is
+    // uploaded first, as the shader expects
+    let mut panorama = 0_u32;
+    gl_check!(gl::GenTextures(1, &mut panorama));
+    live_objects::created(GlObject::Texture, panorama);
+    gl_check!(gl::ActiveTexture(gl::TEXTURE0 + PANORAMA_UNIT));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, panorama));
+    gl_check!(gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGB16F as GLint,
+        width,
+        height,
+        0,
+        gl::RGB,
+        gl::FLOAT,
+        texels.as_ptr() as *const _,
+    ));
+    for (parameter, value) in [
+        (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+        // Longitude wraps around, latitude stops at the poles
+        (gl::TEXTURE_WRAP_S, gl::REPEAT),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    debug_label::set(gl::TEXTURE, panorama, "equirect panorama");
+
+    let cubemap = new_cubemap(context, size, format, None);
+    let mut framebuffer = 0_u32;
+    gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+    live_objects::created(GlObject::Framebuffer, framebuffer);
+    gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer));
+    debug_label::set(gl::FRAMEBUFFER, framebuffer, "equirect to cubemap");
+    let mut vertex_array_obj = 0;
+    gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+    live_objects::created(GlObject::VertexArray, vertex_array_obj);
+    gl_check!(gl::BindVertexArray(vertex_array_obj));
+    gl_check!(gl::Viewport(0, 0, size, size));
+    gl_check!(gl::Disable(gl::DEPTH_TEST));
+    let (front_uniform, right_uniform, up_uniform) = (
+        program.uniform("face_front"),
+        program.uniform("face_right"),
+        program.uniform("face_up"),
+    );
+    let mut status = gl::FRAMEBUFFER_COMPLETE;
+    for (face, camera) in cubemap_face_cameras(&glm::vec3(0.0_f32, 0.0_f32, 0.0_f32))
+        .iter()
+        .enumerate()
+    {
+        gl_check!(gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum,
+            cubemap.texture,
+            0
+        ));
+        status = gl_check!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER));
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            break;
+        }
+        // As `look_at` lays the face's camera out on screen
+        let right = glm::normalize(&glm::cross(&camera.front, &camera.up));
+        front_uniform.set_vec3f([camera.front.x, camera.front.y, camera.front.z]);
+        right_uniform.set_vec3f([right.x, right.y, right.z]);
+        up_uniform.set_vec3f([camera.up.x, camera.up.y, camera.up.z]);
+        gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+    }
+    gl_check!(gl::Enable(gl::DEPTH_TEST));
+    gl_check!(gl::BindVertexArray(0));
+    gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    live_objects::delete(GlObject::VertexArray, vertex_array_obj);
+    live_objects::delete(GlObject::Framebuffer, framebuffer);
+    live_objects::delete(GlObject::Texture, panorama);
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(format!("Cubemap face incomplete (0x{:X})", status));
+    }
+    Ok(cubemap)
+}
+
+/// A cubemap saved by `HdrCubemap::save_ktx`, when it is `size` across and stored as `format`.
+unsafe fn load_ktx(
+    context: &GlContext,
+    path: &Path,
+    size: i32,
+    format: TexelFormat,
+) -> Result<HdrCubemap, String> {
+    let contents = fs::read(path).map_err(|err| err.to_string())?;
+    if contents.len() < KTX_HEADER_SIZE + 4 || contents[..12] != KTX_IDENTIFIER {
+        return Err("not a KTX 1 file".to_string());
+    }
+    let field = |i: usize| {
+        let start = 12 + i * 4;
+        u32::from_le_bytes(contents[start..start + 4].try_into().unwrap())
+    };
+    if field(0) != KTX_ENDIANNESS {
+        return Err("big endian".to_string());
+    }
+    if (field(1), field(3), field(4)) != (format.texel_type, gl::RGBA, format.internal_format) {
+        return Err("stored in another format".to_string());
+    }
+    if (field(6), field(7), field(8), field(9)) != (size as u32, size as u32, 0, 0)
+        || field(10) != 6
+    {
+        return Err(format!("not a {0} by {0} cubemap", size));
+    }
+    let face_bytes = size as usize * size as usize * format.bytes_per_texel;
+    // The first mip level's faces follow the key/value data and their size
+    let faces_start = KTX_HEADER_SIZE + field(12) as usize + 4;
+    let faces = contents
+        .get(faces_start..faces_start + 6 * face_bytes)
+        .ok_or_else(|| "truncated".to_string())?;
+    Ok(new_cubemap(context, size, format, Some(faces)))
+}
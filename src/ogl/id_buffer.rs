@@ -0,0 +1,235 @@
+use gl::types::*;
+
+use crate::math::glm::Mat4;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Mesh, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+
+const ID_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+
+uniform mat4 world_from_object;
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+void main() {
+    gl_Position = projection_from_view * view_from_world * world_from_object * vec4(a_pos, 1.0f);
+}
+"#;
+
+const ID_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform vec4 object_id_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = object_id_color;
+}
+"#;
+
+/// Offscreen target the pickable objects are drawn into with a flat color encoding their id, so
+/// the object under a pixel can be read back exactly regardless of mesh shape. Only rendered when
+/// a pick is requested.
+pub struct IdBuffer {
+    framebuffer: GLuint,
+    color_renderbuffer: GLuint,
+    depth_renderbuffer: GLuint,
+    size: (i32, i32),
+    shader_program: ShaderProgram,
+    object_id_color_uniform: Uniform,
+    world_from_object_uniform: Uniform,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    /// For drawing the objects
+    context: GlContext,
+}
+
+impl IdBuffer {
+    pub fn new(context: &GlContext) -> Result<IdBuffer, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                ID_VERTEX_SHADER_SOURCE,
+                ID_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("id buffer");
+            let mut framebuffer = 0_u32;
+            let mut renderbuffers = [0_u32; 2];
+            gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+            live_objects::created(GlObject::Framebuffer, framebuffer);
+            gl_check!(gl::GenRenderbuffers(2, renderbuffers.as_mut_ptr()));
+            live_objects::created_all(GlObject::Renderbuffer, &renderbuffers);
+            let object_id_color_uniform = shader_program.uniform("object_id_color");
+            let world_from_object_uniform = shader_program.uniform("world_from_object");
+            let view_from_world_uniform = shader_program.uniform("view_from_world");
+            let projection_from_view_uniform = shader_program.uniform("projection_from_view");
+            Ok(IdBuffer {
+                context: *context,
+                framebuffer,
+                color_renderbuffer: renderbuffers[0],
+                depth_renderbuffer: renderbuffers[1],
+                size: (0, 0),
+                shader_program,
+                object_id_color_uniform,
+                world_from_object_uniform,
+                view_from_world_uniform,
+                projection_from_view_uniform,
+            })
+        }
+    }
+
+    /// Draws `objects` as `(id, mesh, world_from_object)` and returns the id of the one covering
+    /// `pixel`, given in framebuffer pixels from the top-left corner. Only positions at attribute
+    /// location 0 are used. Leaves the default framebuffer bound with a full `viewport_size`
+    /// viewport.
+    pub fn pick(
+        &mut self,
+        objects: &[(usize, &Mesh, Mat4)],
+        view_from_world: &Mat4,
+        projection_from_view: &Mat4,
+        viewport_size: (i32, i32),
+        pixel: (i32, i32),
+    ) -> Option<usize> {
+        unsafe {
+            let (width, height) = viewport_size;
+            if pixel.0 < 0 || pixel.1 < 0 || pixel.0 >= width || pixel.1 >= height {
+                return None;
+            }
+            if let Err(err) = self.resize(viewport_size) {
+                eprintln!("Skipping ID buffer pick: {}", err);
+                return None;
+            }
+
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer));
+            gl_check!(gl::Viewport(0, 0, width, height));
+            gl_check!(gl::ClearColor(0.0, 0.0, 0.0, 0.0));
+            gl_check!(gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT));
+
+            self.shader_program.use_program();
+            self.view_from_world_uniform.set_mat4f(view_from_world);
+            self.projection_from_view_uniform
+                .set_mat4f(projection_from_view);
+            for (id, mesh, world_from_object) in objects {
+                self.object_id_color_uniform.set_vec4f(encode_id(*id));
+                self.world_from_object_uniform.set_mat4f(world_from_object);
+                mesh.draw(&self.context);
+            }
+
+            // Window rows grow downward, framebuffer rows grow upward
+            let mut texel = [0_u8; 4];
+            gl_check!(gl::ReadPixels(
+                pixel.0,
+                height - 1 - pixel.1,
+                1,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                texel.as_mut_ptr() as *mut c_void
+            ));
+
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+            gl_check!(gl::Viewport(0, 0, width, height));
+            decode_id(texel)
+        }
+    }
+
+    unsafe fn resize(&mut self, size: (i32, i32)) -> Result<(), String> {
+        if self.size == size {
+            return Ok(());
+        }
+        let (width, height) = size;
+        gl_check!(gl::BindRenderbuffer(
+            gl::RENDERBUFFER,
+            self.color_renderbuffer
+        ));
+        gl_check!(gl::RenderbufferStorage(
+            gl::RENDERBUFFER,
+            gl::RGBA8,
+            width,
+            height
+        ));
+        gl_check!(gl::BindRenderbuffer(
+            gl::RENDERBUFFER,
+            self.depth_renderbuffer
+        ));
+        gl_check!(gl::RenderbufferStorage(
+            gl::RENDERBUFFER,
+            gl::DEPTH_COMPONENT24,
+            width,
+            height
+        ));
+        gl_check!(gl::BindRenderbuffer(gl::RENDERBUFFER, 0));
+        // Depth is padded to four bytes like the color
+        for renderbuffer in [self.color_renderbuffer, self.depth_renderbuffer].iter() {
+            gpu_memory::track(
+                GpuResource::Renderbuffer,
+                *renderbuffer,
+                gpu_memory::texture_bytes(width as u32, height as u32, 1, 4, false),
+            );
+        }
+
+        gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer));
+        gl_check!(gl::FramebufferRenderbuffer(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::RENDERBUFFER,
+            self.color_renderbuffer
+        ));
+        gl_check!(gl::FramebufferRenderbuffer(
+            gl::FRAMEBUFFER,
+            gl::DEPTH_ATTACHMENT,
+            gl::RENDERBUFFER,
+            self.depth_renderbuffer
+        ));
+        let status = gl_check!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER));
+        gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+        debug_label::set(gl::FRAMEBUFFER, self.framebuffer, "id buffer");
+        debug_label::set(gl::RENDERBUFFER, self.color_renderbuffer, "id buffer ids");
+        debug_label::set(gl::RENDERBUFFER, self.depth_renderbuffer, "id buffer depth");
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            // Force a retry on the next pick
+            self.size = (0, 0);
+            return Err(format!("framebuffer incomplete (0x{:X})", status));
+        }
+        self.size = size;
+        Ok(())
+    }
+}
+
+impl Drop for IdBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::Framebuffer, self.framebuffer);
+            live_objects::delete_all(
+                GlObject::Renderbuffer,
+                &[self.color_renderbuffer, self.depth_renderbuffer],
+            );
+        }
+    }
+}
+
+/// Packs `id + 1` into 24 bits of RGB so a cleared (zero) pixel means nothing was hit.
+fn encode_id(id: usize) -> [f32; 4] {
+    let value = id as u32 + 1;
+    [
+        (value & 0xFF) as f32 / 255.0_f32,
+        ((value >> 8) & 0xFF) as f32 / 255.0_f32,
+        ((value >> 16) & 0xFF) as f32 / 255.0_f32,
+        1.0_f32,
+    ]
+}
+
+fn decode_id(texel: [u8; 4]) -> Option<usize> {
+    let value = texel[0] as u32 | (texel[1] as u32) << 8 | (texel[2] as u32) << 16;
+    if value == 0 {
+        None
+    } else {
+        Some(value as usize - 1)
+    }
+}
@@ -0,0 +1,99 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+use std::mem;
+
+/// One draw of a `glMultiDrawElementsIndirect` call, laid out as GL reads it from the
+/// `DRAW_INDIRECT_BUFFER`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DrawElementsIndirectCommand {
+    pub count: GLuint,
+    pub instance_count: GLuint,
+    pub first_index: GLuint,
+    pub base_vertex: GLint,
+    /// Offsets where instanced attributes start reading, for this draw's instances
+    pub base_instance: GLuint,
+}
+
+/// Indirect draw commands gathered on the CPU during a view and uploaded in one go, then
+/// drawn in slices with `multi_draw`. Needs OpenGL 4.3.
+pub struct IndirectBuffer {
+    pub id: GLuint,
+    commands: Vec<DrawElementsIndirectCommand>,
+    context: GlContext,
+}
+
+impl IndirectBuffer {
+    pub fn new(context: &GlContext) -> IndirectBuffer {
+        unsafe {
+            let mut id = 0;
+            gl_check!(gl::GenBuffers(1, &mut id));
+            live_objects::created(GlObject::Buffer, id);
+            gl_check!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, id));
+            gl_check!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0));
+            debug_label::set(gl::BUFFER, id, "indirect draws");
+            IndirectBuffer {
+                id,
+                commands: vec![],
+                context: *context,
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn push(&mut self, command: DrawElementsIndirectCommand) {
+        self.commands.push(command);
+    }
+
+    pub unsafe fn upload(&self) {
+        if self.commands.is_empty() {
+            return;
+        }
+        gl_check!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.id));
+        gl_check!(gl::BufferData(
+            gl::DRAW_INDIRECT_BUFFER,
+            mem::size_of_val(self.commands.as_slice()) as GLsizeiptr,
+            self.commands.as_ptr() as *const c_void,
+            gl::STREAM_DRAW,
+        ));
+        gl_check!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0));
+        gpu_memory::track(
+            GpuResource::Buffer,
+            self.id,
+            mem::size_of_val(self.commands.as_slice()),
+        );
+    }
+
+    /// Issues `count` uploaded commands from `first` on as one draw call, with the vertex array
+    /// of the meshes they draw bound.
+    pub unsafe fn multi_draw(&self, first: usize, count: usize) {
+        let stride = mem::size_of::<DrawElementsIndirectCommand>();
+        gl_check!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.id));
+        gl_check!(gl::MultiDrawElementsIndirect(
+            gl::TRIANGLES,
+            gl::UNSIGNED_INT,
+            (first * stride) as *const c_void,
+            count as GLsizei,
+            stride as GLsizei,
+        ));
+        gl_check!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0));
+    }
+}
+
+impl Drop for IndirectBuffer {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Buffer, self.id);
+    }
+}
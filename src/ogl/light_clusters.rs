@@ -0,0 +1,530 @@
+use gl::types::*;
+
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::context::GlContext;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::scene::{Light, LightKind};
+use std::ffi::c_void;
+use std::mem;
+
+/// Screen tiles across and down, and depth slices, the view is split into.
+pub const CLUSTER_TILES_X: usize = 16;
+pub const CLUSTER_TILES_Y: usize = 9;
+pub const CLUSTER_SLICES: usize = 24;
+/// Lights beyond this many are left out, with a message.
+pub const MAX_CLUSTERED_LIGHTS: usize = 1024;
+// Texture units the light data, cluster and index textures are bound to
+const LIGHT_DATA_UNIT: GLuint = 8;
+const CLUSTERS_UNIT: GLuint = 9;
+const LIGHT_INDICES_UNIT: GLuint = 10;
+// Texels per light in the light data texture, one light per row
+const LIGHT_TEXELS: usize = 3;
+// Light indices are laid out in rows this wide
+const INDEX_TEXTURE_WIDTH: usize = 1024;
+// Cone edge, as a fraction of the way from the outer cutoff's cosine to 1, where spot lights
+// reach full strength
+const SPOT_SOFTNESS: f32 = 0.2_f32;
+
+// Lighting code for fragment shaders, pasted in after their `#version` and declarations.
+// `clustered_lighting` sums the contributions of every directional light and of the point and
+// spot lights listed in the fragment's cluster, all in view space.
+const CLUSTERED_LIGHTING_SOURCE: &str = r#"
+#ifdef GL_ES
+precision highp usampler2D;
+#endif
+// Per light a row of three texels: view position and range, color and kind (0 directional,
+// 1 point, 2 spot), and view direction and the cosine of the cone's outer cutoff
+uniform sampler2D light_data;
+// Per cluster, where its lights start in light_indices and how many there are
+uniform usampler2D light_clusters;
+uniform usampler2D light_indices;
+uniform int directional_light_count;
+uniform vec2 cluster_tile_size;
+uniform float cluster_depth_scale;
+uniform float cluster_depth_bias;
+uniform bool show_light_heatmap;
+
+vec3 light_contribution(int light_i, vec3 view_position, vec3 normal, vec3 albedo) {
+    vec4 position_range = texelFetch(light_data, ivec2(0, light_i), 0);
+    vec4 color_kind = texelFetch(light_data, ivec2(1, light_i), 0);
+    vec4 direction_cutoff = texelFetch(light_data, ivec2(2, light_i), 0);
+    vec3 to_light = -direction_cutoff.xyz;
+    float attenuation = 1.0f;
+    if (color_kind.w > 0.5f) {
+        vec3 offset = position_range.xyz - view_position;
+        float distance = length(offset);
+        to_light = offset / max(distance, 0.0001f);
+        // Inverse square, windowed to reach zero at the range
+        float ratio = distance / position_range.w;
+        float window = clamp(1.0f - ratio * ratio * ratio * ratio, 0.0f, 1.0f);
+        attenuation = window * window / (1.0f + distance * distance);
+        if (color_kind.w > 1.5f) {
+            float cos_angle = dot(-to_light, direction_cutoff.xyz);
+            float full = mix(direction_cutoff.w, 1.0f, SPOT_SOFTNESS);
+            attenuation *= smoothstep(direction_cutoff.w, full, cos_angle);
+        }
+    }
+    float diffuse = max(dot(normal, to_light), 0.0f);
+    vec3 half_vector = normalize(to_light + normalize(-view_position));
+    float specular = diffuse > 0.0f ? pow(max(dot(normal, half_vector), 0.0f), 32.0f) : 0.0f;
+    return color_kind.rgb * attenuation * (albedo * diffuse + 0.25f * specular);
+}
+
+vec3 clustered_lighting(vec3 view_position, vec3 normal, vec3 albedo) {
+    vec3 color = vec3(0.0f);
+    for (int i = 0; i < directional_light_count; ++i) {
+        color += light_contribution(i, view_position, normal, albedo);
+    }
+    ivec2 tile = min(ivec2(gl_FragCoord.xy / cluster_tile_size),
+                     ivec2(CLUSTER_TILES_X - 1, CLUSTER_TILES_Y - 1));
+    float depth = max(-view_position.z, 0.0001f);
+    int slice = clamp(int(log(depth) * cluster_depth_scale - cluster_depth_bias), 0,
+                      CLUSTER_SLICES - 1);
+    uvec2 entries = texelFetch(light_clusters, ivec2(tile.x + tile.y * CLUSTER_TILES_X, slice), 0).xy;
+    for (uint i = 0u; i < entries.y; ++i) {
+        uint entry = entries.x + i;
+        ivec2 texel = ivec2(int(entry % uint(INDEX_TEXTURE_WIDTH)), int(entry / uint(INDEX_TEXTURE_WIDTH)));
+        int light_i = int(texelFetch(light_indices, texel, 0).x);
+        color += light_contribution(light_i, view_position, normal, albedo);
+    }
+    if (show_light_heatmap) {
+        // Blue for a few lights through green to red for 16 or more
+        float load = clamp(float(entries.y) / 16.0f, 0.0f, 1.0f);
+        vec3 heat = clamp(vec3(2.0f * load - 1.0f, 1.0f - abs(2.0f * load - 1.0f), 1.0f - 2.0f * load), 0.0f, 1.0f);
+        color = mix(color, heat, 0.6f);
+    }
+    return color;
+}
+"#;
+
+/// The uniforms of a program using `clustered_lighting_source`, looked up once.
+pub struct LightClusterUniforms {
+    directional_light_count: Uniform,
+    cluster_tile_size: Uniform,
+    cluster_depth_scale: Uniform,
+    cluster_depth_bias: Uniform,
+    show_light_heatmap: Uniform,
+}
+
+/// Point and spot lights sorted into clusters, a grid of screen tiles split into depth slices,
+/// so each fragment only shades with the lights that can reach its cluster (clustered forward
+/// shading, or Forward+). The lists are rebuilt on the CPU every frame and read by the shaders
+/// from integer textures, so this works down to OpenGL 3.3 and ES 3.0, and forward shading keeps
+/// multisampling working. Depth slices grow exponentially, like perspective does.
+pub struct LightClusters {
+    /// Tints lit surfaces by how many lights their cluster lists
+    pub show_heatmap: bool,
+    light_data_texture: GLuint,
+    clusters_texture: GLuint,
+    light_indices_texture: GLuint,
+    /// View space bounds of every cluster, rebuilt when the projection or viewport changes
+    cluster_bounds: Vec<Aabb>,
+    bounds_key: Option<(Mat4, (i32, i32))>,
+    near: f32,
+    far: f32,
+    tile_size: [f32; 2],
+    directional_count: usize,
+    light_count: usize,
+    /// Most lights any cluster lists, for the debug readout
+    max_cluster_lights: usize,
+    /// Reused between frames: light rows, (cluster, light) pairs, cluster entries and indices
+    light_data: Vec<f32>,
+    pairs: Vec<(u32, u32)>,
+    cluster_entries: Vec<u32>,
+    light_indices: Vec<u32>,
+    warned_light_limit: bool,
+    context: GlContext,
+}
+
+/// `CLUSTERED_LIGHTING_SOURCE` with the cluster grid's dimensions filled in.
+pub fn clustered_lighting_source() -> String {
+    let mut source =
+        CLUSTERED_LIGHTING_SOURCE.replace("SPOT_SOFTNESS", &format!("{:?}f", SPOT_SOFTNESS));
+    for (name, value) in [
+        ("CLUSTER_TILES_X", CLUSTER_TILES_X),
+        ("CLUSTER_TILES_Y", CLUSTER_TILES_Y),
+        ("CLUSTER_SLICES", CLUSTER_SLICES),
+        ("INDEX_TEXTURE_WIDTH", INDEX_TEXTURE_WIDTH),
+    ]
+    .iter()
+    {
+        source = source.replace(name, &format!("{}", value));
+    }
+    source
+}
+
+impl LightClusterUniforms {
+    /// Looks up the uniforms of `program` and points its samplers at the cluster textures.
+    pub fn new(program: &ShaderProgram) -> LightClusterUniforms {
+        program.use_program();
+        for (name, unit) in [
+            ("light_data", LIGHT_DATA_UNIT),
+            ("light_clusters", CLUSTERS_UNIT),
+            ("light_indices", LIGHT_INDICES_UNIT),
+        ]
+        .iter()
+        {
+            program.uniform(name).set_int(*unit as i32);
+        }
+        LightClusterUniforms {
+            directional_light_count: program.uniform("directional_light_count"),
+            cluster_tile_size: program.uniform("cluster_tile_size"),
+            cluster_depth_scale: program.uniform("cluster_depth_scale"),
+            cluster_depth_bias: program.uniform("cluster_depth_bias"),
+            show_light_heatmap: program.uniform("show_light_heatmap"),
+        }
+    }
+}
+
+impl LightClusters {
+    pub fn new(context: &GlContext) -> LightClusters {
+        let mut textures = [0; 3];
+        unsafe {
+            gl_check!(gl::GenTextures(3, textures.as_mut_ptr()));
+            live_objects::created_all(GlObject::Texture, &textures);
+            for texture in textures.iter() {
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, *texture));
+                // Integer textures are incomplete with linear filtering, and all are fetched
+                for parameter in [gl::TEXTURE_MIN_FILTER, gl::TEXTURE_MAG_FILTER].iter() {
+                    gl_check!(gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        *parameter,
+                        gl::NEAREST as GLint
+                    ));
+                }
+            }
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+        }
+        LightClusters {
+            show_heatmap: false,
+            light_data_texture: textures[0],
+            clusters_texture: textures[1],
+            light_indices_texture: textures[2],
+            cluster_bounds: vec![],
+            bounds_key: None,
+            near: 0.1_f32,
+            far: 100.0_f32,
+            tile_size: [1.0_f32, 1.0_f32],
+            directional_count: 0,
+            light_count: 0,
+            max_cluster_lights: 0,
+            light_data: vec![],
+            pairs: vec![],
+            cluster_entries: vec![],
+            light_indices: vec![],
+            warned_light_limit: false,
+            context: *context,
+        }
+    }
+
+    /// Lights uploaded in the last `update`, and the most any one cluster lists.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.light_count, self.max_cluster_lights)
+    }
+
+    /// Sorts `lights` into the clusters of the view seen through `projection_from_view`, a
+    /// perspective projection, over a viewport of `viewport_size` pixels, and uploads the lists.
+    pub fn update(
+        &mut self,
+        lights: &[Light],
+        view_from_world: &Mat4,
+        projection_from_view: &Mat4,
+        viewport_size: (i32, i32),
+    ) {
+        if self.bounds_key != Some((*projection_from_view, viewport_size)) {
+            self.build_cluster_bounds(projection_from_view, viewport_size);
+        }
+        if lights.len() > MAX_CLUSTERED_LIGHTS && !self.warned_light_limit {
+            eprintln!(
+                "Shading with the first {} of {} lights",
+                MAX_CLUSTERED_LIGHTS,
+                lights.len()
+            );
+            self.warned_light_limit = true;
+        }
+        let lights = &lights[..lights.len().min(MAX_CLUSTERED_LIGHTS)];
+        // Directional lights go first, as they reach every cluster
+        let (directional, local): (Vec<&Light>, Vec<&Light>) = lights
+            .iter()
+            .partition(|light| matches!(light.kind, LightKind::Directional { .. }));
+        self.directional_count = directional.len();
+        self.light_count = lights.len();
+        self.light_data.clear();
+        self.pairs.clear();
+        let to_view = |position: &[f32; 3]| {
+            glm::xyz(&(view_from_world * glm::vec4(position[0], position[1], position[2], 1.0_f32)))
+        };
+        let direction_to_view = |direction: &[f32; 3]| {
+            glm::normalize(&glm::xyz(
+                &(view_from_world * glm::vec4(direction[0], direction[1], direction[2], 0.0_f32)),
+            ))
+        };
+        for (light_i, light) in directional.iter().chain(local.iter()).enumerate() {
+            let color = light.color.to_array();
+            let (position, range, kind, direction, cos_cutoff) = match light.kind {
+                LightKind::Directional { direction, .. } => (
+                    Vec3::default(),
+                    0.0_f32,
+                    0.0_f32,
+                    direction_to_view(&direction),
+                    -1.0_f32,
+                ),
+                LightKind::Point { position, range } => (
+                    to_view(&position),
+                    range,
+                    1.0_f32,
+                    Vec3::default(),
+                    -1.0_f32,
+                ),
+                LightKind::Spot {
+                    position,
+                    direction,
+                    range,
+                    outer_cutoff_degrees,
+                } => (
+                    to_view(&position),
+                    range,
+                    2.0_f32,
+                    direction_to_view(&direction),
+                    outer_cutoff_degrees.to_radians().cos(),
+                ),
+            };
+            self.light_data.extend_from_slice(&[
+                position.x,
+                position.y,
+                position.z,
+                range,
+                color[0],
+                color[1],
+                color[2],
+                kind,
+                direction.x,
+                direction.y,
+                direction.z,
+                cos_cutoff,
+            ]);
+            if kind > 0.0_f32 && range > 0.0_f32 {
+                self.add_to_clusters(&BoundingSphere::new(position, range), light_i as u32);
+            }
+        }
+        self.build_lists();
+        unsafe { self.upload() };
+    }
+
+    /// Binds the cluster textures and sets the per-frame uniforms of `program`, whose
+    /// `uniforms` these are.
+    pub fn bind(&self, program: &ShaderProgram, uniforms: &LightClusterUniforms) {
+        program.use_program();
+        uniforms
+            .directional_light_count
+            .set_int(self.directional_count as i32);
+        uniforms.cluster_tile_size.set_vec2f(self.tile_size);
+        // Slice = log(depth / near) / log(far / near) * slices
+        let scale = CLUSTER_SLICES as f32 / (self.far / self.near).ln();
+        uniforms.cluster_depth_scale.set_float(scale);
+        uniforms
+            .cluster_depth_bias
+            .set_float(scale * self.near.ln());
+        uniforms.show_light_heatmap.set_bool(self.show_heatmap);
+        unsafe {
+            for (unit, texture) in [
+                (LIGHT_DATA_UNIT, self.light_data_texture),
+                (CLUSTERS_UNIT, self.clusters_texture),
+                (LIGHT_INDICES_UNIT, self.light_indices_texture),
+            ]
+            .iter()
+            {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + unit));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, *texture));
+            }
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        }
+    }
+
+    /// Bounds of every cluster in view space: the box around the corners of its tile at both
+    /// ends of its depth slice.
+    fn build_cluster_bounds(&mut self, projection_from_view: &Mat4, viewport_size: (i32, i32)) {
+        // The depth row of a GL perspective matrix is (0, 0, -(far + near) / (far - near),
+        // -2 far near / (far - near))
+        let depth_row = glm::row(projection_from_view, 2);
+        self.near = depth_row.w / (depth_row.z - 1.0_f32);
+        self.far = depth_row.w / (depth_row.z + 1.0_f32);
+        self.tile_size = [
+            viewport_size.0.max(1) as f32 / CLUSTER_TILES_X as f32,
+            viewport_size.1.max(1) as f32 / CLUSTER_TILES_Y as f32,
+        ];
+        let view_from_projection = glm::inverse(projection_from_view);
+        // Where the ray through a point of the screen crosses the near plane
+        let near_point = |x: f32, y: f32| {
+            let point = view_from_projection * glm::vec4(x, y, -1.0_f32, 1.0_f32);
+            glm::xyz(&point) / point.w
+        };
+        let slice_depth = |slice: usize| {
+            self.near * (self.far / self.near).powf(slice as f32 / CLUSTER_SLICES as f32)
+        };
+        let mut cluster_bounds = Vec::with_capacity(CLUSTER_TILES_X * CLUSTER_TILES_Y);
+        for slice in 0..CLUSTER_SLICES {
+            let (depth_min, depth_max) = (slice_depth(slice), slice_depth(slice + 1));
+            for tile_y in 0..CLUSTER_TILES_Y {
+                for tile_x in 0..CLUSTER_TILES_X {
+                    let ndc =
+                        |tile: usize, tiles: usize| 2.0_f32 * tile as f32 / tiles as f32 - 1.0_f32;
+                    let (x0, x1) = (
+                        ndc(tile_x, CLUSTER_TILES_X),
+                        ndc(tile_x + 1, CLUSTER_TILES_X),
+                    );
+                    let (y0, y1) = (
+                        ndc(tile_y, CLUSTER_TILES_Y),
+                        ndc(tile_y + 1, CLUSTER_TILES_Y),
+                    );
+                    let mut corners = Vec::with_capacity(8);
+                    for point in [
+                        near_point(x0, y0),
+                        near_point(x1, y0),
+                        near_point(x0, y1),
+                        near_point(x1, y1),
+                    ]
+                    .iter()
+                    {
+                        // Points along the ray scale with their depth
+                        let near_depth = -point.z;
+                        corners.push(point * (depth_min / near_depth));
+                        corners.push(point * (depth_max / near_depth));
+                    }
+                    cluster_bounds.push(Aabb::from_points(corners.iter()).unwrap());
+                }
+            }
+        }
+        self.cluster_bounds = cluster_bounds;
+        self.bounds_key = Some((*projection_from_view, viewport_size));
+    }
+
+    /// Pairs light `light_i` with every cluster `sphere` reaches, testing only those in the
+    /// slices its depth range spans.
+    fn add_to_clusters(&mut self, sphere: &BoundingSphere, light_i: u32) {
+        let depth_min = -sphere.center.z - sphere.radius;
+        let depth_max = -sphere.center.z + sphere.radius;
+        if depth_max < self.near || depth_min > self.far {
+            return;
+        }
+        let slice_of = |depth: f32| {
+            let slice = (depth.max(self.near) / self.near).ln() / (self.far / self.near).ln()
+                * CLUSTER_SLICES as f32;
+            (slice.max(0.0_f32) as usize).min(CLUSTER_SLICES - 1)
+        };
+        let tiles = CLUSTER_TILES_X * CLUSTER_TILES_Y;
+        for slice in slice_of(depth_min)..=slice_of(depth_max) {
+            for tile in 0..tiles {
+                let cluster_i = slice * tiles + tile;
+                if self.cluster_bounds[cluster_i].intersects_sphere(sphere) {
+                    self.pairs.push((cluster_i as u32, light_i));
+                }
+            }
+        }
+    }
+
+    /// Groups the (cluster, light) pairs by cluster into `light_indices`, with each cluster's
+    /// first index and count in `cluster_entries`.
+    fn build_lists(&mut self) {
+        let cluster_count = self.cluster_bounds.len();
+        self.cluster_entries.clear();
+        self.cluster_entries.resize(2 * cluster_count, 0);
+        for (cluster_i, _) in self.pairs.iter() {
+            self.cluster_entries[2 * *cluster_i as usize + 1] += 1;
+        }
+        let mut start = 0;
+        self.max_cluster_lights = 0;
+        for cluster_i in 0..cluster_count {
+            let count = self.cluster_entries[2 * cluster_i + 1];
+            self.cluster_entries[2 * cluster_i] = start;
+            self.max_cluster_lights = self.max_cluster_lights.max(count as usize);
+            start += count;
+        }
+        // Counts are filled in again as the lights are placed
+        let mut placed = vec![0_u32; cluster_count];
+        self.light_indices.clear();
+        self.light_indices.resize(self.pairs.len(), 0);
+        for (cluster_i, light_i) in self.pairs.iter() {
+            let cluster_i = *cluster_i as usize;
+            let index = self.cluster_entries[2 * cluster_i] + placed[cluster_i];
+            self.light_indices[index as usize] = *light_i;
+            placed[cluster_i] += 1;
+        }
+    }
+
+    unsafe fn upload(&mut self) {
+        let index_rows = self
+            .light_indices
+            .len()
+            .div_ceil(INDEX_TEXTURE_WIDTH)
+            .max(1);
+        self.light_indices
+            .resize(index_rows * INDEX_TEXTURE_WIDTH, 0);
+        let light_rows = self.light_count.max(1);
+        self.light_data
+            .resize(light_rows * LIGHT_TEXELS * 4, 0.0_f32);
+        for (texture, internal_format, format, component_type, size, data, bytes) in [
+            (
+                self.light_data_texture,
+                gl::RGBA32F,
+                gl::RGBA,
+                gl::FLOAT,
+                (LIGHT_TEXELS, light_rows),
+                self.light_data.as_ptr() as *const c_void,
+                mem::size_of_val(self.light_data.as_slice()),
+            ),
+            (
+                self.clusters_texture,
+                gl::RG32UI,
+                gl::RG_INTEGER,
+                gl::UNSIGNED_INT,
+                (CLUSTER_TILES_X * CLUSTER_TILES_Y, CLUSTER_SLICES),
+                self.cluster_entries.as_ptr() as *const c_void,
+                mem::size_of_val(self.cluster_entries.as_slice()),
+            ),
+            (
+                self.light_indices_texture,
+                gl::R32UI,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                (INDEX_TEXTURE_WIDTH, index_rows),
+                self.light_indices.as_ptr() as *const c_void,
+                mem::size_of_val(self.light_indices.as_slice()),
+            ),
+        ]
+        .iter()
+        {
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, *texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                *internal_format as GLint,
+                size.0 as GLsizei,
+                size.1 as GLsizei,
+                0,
+                *format,
+                *component_type,
+                *data,
+            ));
+            gpu_memory::track(GpuResource::Texture, *texture, *bytes);
+        }
+        gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    }
+}
+
+impl Drop for LightClusters {
+    fn drop(&mut self) {
+        for texture in [
+            self.light_data_texture,
+            self.clusters_texture,
+            self.light_indices_texture,
+        ]
+        .iter()
+        {
+            live_objects::release(&self.context, GlObject::Texture, *texture);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use gl::types::*;
+
+use crate::math::glm::{self, Vec3};
+use crate::ogl::context::GlContext;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::render_target::RenderTarget;
+use crate::scene::LightProbe;
+use std::ffi::c_void;
+use std::mem;
+
+/// Probes beyond this many are left out, with a message.
+pub const MAX_LIGHT_PROBES: usize = 64;
+/// Pixels across each face a probe captures.
+pub const PROBE_CAPTURE_SIZE: i32 = 32;
+/// Spherical harmonics coefficients per color channel, up to the second band.
+pub const SH_COEFFICIENTS: usize = 9;
+// Texture unit the probe texture is bound to, after those of light_clusters
+const LIGHT_PROBES_UNIT: GLuint = 11;
+// Texels per probe: position and radius, then the coefficients
+const PROBE_TEXELS: usize = 1 + SH_COEFFICIENTS;
+
+/// Lighting code for vertex shaders, pasted in after their declarations. `probe_irradiance`
+/// blends the probes reaching a point by distance and evaluates them for a normal, returning the
+/// diffuse light over pi, so it multiplies the albedo directly, and in alpha how fully the
+/// probes cover the point.
+pub const LIGHT_PROBES_SOURCE: &str = r#"
+// Per probe a row of texels: world position and radius, then nine RGB coefficients
+uniform sampler2D light_probes;
+uniform int light_probe_count;
+
+vec4 probe_irradiance(vec3 sample_position, vec3 world_normal) {
+    vec3 n = world_normal;
+    float basis[9] = float[9](
+        0.282095f,
+        0.488603f * n.y, 0.488603f * n.z, 0.488603f * n.x,
+        1.092548f * n.x * n.y, 1.092548f * n.y * n.z, 0.315392f * (3.0f * n.z * n.z - 1.0f),
+        1.092548f * n.x * n.z, 0.546274f * (n.x * n.x - n.y * n.y));
+    vec3 irradiance = vec3(0.0f);
+    float total_weight = 0.0f;
+    for (int probe_i = 0; probe_i < light_probe_count; ++probe_i) {
+        vec4 position_radius = texelFetch(light_probes, ivec2(0, probe_i), 0);
+        float falloff = 1.0f - distance(sample_position, position_radius.xyz) / position_radius.w;
+        if (falloff > 0.0f) {
+            vec3 probe = vec3(0.0f);
+            for (int i = 0; i < 9; ++i) {
+                probe += basis[i] * texelFetch(light_probes, ivec2(i + 1, probe_i), 0).rgb;
+            }
+            float weight = falloff * falloff;
+            irradiance += weight * max(probe, 0.0f);
+            total_weight += weight;
+        }
+    }
+    if (total_weight == 0.0f) {
+        return vec4(0.0f);
+    }
+    return vec4(irradiance / total_weight, min(total_weight, 1.0f));
+}
+"#;
+
+/// The diffuse light a probe saw, as second order spherical harmonics, already convolved with
+/// the cosine lobe and divided by pi.
+pub type ProbeIrradiance = [[f32; 3]; SH_COEFFICIENTS];
+
+/// The uniforms of a program using `LIGHT_PROBES_SOURCE`, looked up once.
+pub struct LightProbeUniforms {
+    light_probe_count: Uniform,
+}
+
+/// Baked irradiance of a scene's light probes, in a float texture the vertex shaders read so
+/// objects pick up the light around them rather than one flat ambient term.
+pub struct LightProbes {
+    texture: GLuint,
+    probe_count: usize,
+    context: GlContext,
+}
+
+/// Renders the six views around `position` into `target`, a square target, through
+/// `draw_face`, which draws the scene for the camera it is given with a 90 degree projection,
+/// and projects what they saw onto spherical harmonics.
+pub fn capture(
+    context: &GlContext,
+    position: &Vec3,
+    target: &RenderTarget,
+    mut draw_face: impl FnMut(&Camera),
+) -> ProbeIrradiance {
+    let mut radiance = [Vec3::default(); SH_COEFFICIENTS];
+    let mut total_solid_angle = 0.0_f32;
+    for camera in cube_face_cameras(position).iter() {
+        target.bind(context);
+        draw_face(camera);
+        let pixels = target.read_pixels(context);
+        // The screen axes look_at builds from the camera
+        let front = &camera.front;
+        let right = glm::normalize(&glm::cross(front, &camera.up));
+        let screen_up = glm::cross(&right, front);
+        let size = target.width as usize;
+        let texel = 2.0_f32 / size as f32;
+        for (pixel_i, pixel) in pixels.chunks_exact(4).enumerate() {
+            // Rows come bottom first, like the screen's y axis
+            let u = (pixel_i % size) as f32 * texel + 0.5_f32 * texel - 1.0_f32;
+            let v = (pixel_i / size) as f32 * texel + 0.5_f32 * texel - 1.0_f32;
+            let distance_squared = 1.0_f32 + u * u + v * v;
+            let solid_angle = texel * texel / (distance_squared * distance_squared.sqrt());
+            let direction = glm::normalize(&(front + right * u + screen_up * v));
+            let color = glm::vec3(
+                pixel[0] as f32 / 255.0_f32,
+                pixel[1] as f32 / 255.0_f32,
+                pixel[2] as f32 / 255.0_f32,
+            );
+            for (coefficient, basis) in radiance.iter_mut().zip(sh_basis(&direction).iter()) {
+                *coefficient += color * (basis * solid_angle);
+            }
+            total_solid_angle += solid_angle;
+        }
+    }
+    target.unbind(context);
+    // The texels' solid angles only approximate the sphere's 4 pi; the cosine lobe scales each
+    // band by pi, 2 pi / 3 and pi / 4, and the result is divided by pi
+    let normalization = 4.0_f32 * std::f32::consts::PI / total_solid_angle;
+    let band_scales = [1.0_f32, 2.0_f32 / 3.0_f32, 0.25_f32];
+    let mut irradiance = [[0.0_f32; 3]; SH_COEFFICIENTS];
+    for (coefficient_i, coefficient) in radiance.iter().enumerate() {
+        let band = match coefficient_i {
+            0 => 0,
+            1..=3 => 1,
+            _ => 2,
+        };
+        let scaled = coefficient * (normalization * band_scales[band]);
+        irradiance[coefficient_i] = [scaled.x, scaled.y, scaled.z];
+    }
+    irradiance
+}
+
+/// Cameras at `position` looking along +X, -X, +Z, -Z, +Y and -Y, whose 90 degree views cover
+/// every direction.
+pub fn cube_face_cameras(position: &Vec3) -> [Camera; 6] {
+    let camera = |front: Vec3, up: Vec3| Camera {
+        position: *position,
+        front,
+        up,
+        yaw: 0.0_f32,
+        pitch: 0.0_f32,
+    };
+    let (x, y, z) = (
+        glm::vec3(1.0_f32, 0.0_f32, 0.0_f32),
+        glm::vec3(0.0_f32, 1.0_f32, 0.0_f32),
+        glm::vec3(0.0_f32, 0.0_f32, 1.0_f32),
+    );
+    [
+        camera(x, y),
+        camera(-x, y),
+        camera(z, y),
+        camera(-z, y),
+        camera(y, -z),
+        camera(-y, z),
+    ]
+}
+
+// Real spherical harmonics basis up to the second band, in the order the shader expects
+fn sh_basis(n: &Vec3) -> [f32; SH_COEFFICIENTS] {
+    [
+        0.282095_f32,
+        0.488603_f32 * n.y,
+        0.488603_f32 * n.z,
+        0.488603_f32 * n.x,
+        1.092548_f32 * n.x * n.y,
+        1.092548_f32 * n.y * n.z,
+        0.315392_f32 * (3.0_f32 * n.z * n.z - 1.0_f32),
+        1.092548_f32 * n.x * n.z,
+        0.546274_f32 * (n.x * n.x - n.y * n.y),
+    ]
+}
+
+impl LightProbeUniforms {
+    /// Looks up the uniforms of `program` and points its sampler at the probe texture.
+    pub fn new(program: &ShaderProgram) -> LightProbeUniforms {
+        program.use_program();
+        program
+            .uniform("light_probes")
+            .set_int(LIGHT_PROBES_UNIT as i32);
+        LightProbeUniforms {
+            light_probe_count: program.uniform("light_probe_count"),
+        }
+    }
+}
+
+impl LightProbes {
+    /// No probes until `upload`, so shaders fall back to their flat ambient term.
+    pub fn new(context: &GlContext) -> LightProbes {
+        let mut texture = 0;
+        unsafe {
+            gl_check!(gl::GenTextures(1, &mut texture));
+            live_objects::created(GlObject::Texture, texture);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+            for parameter in [gl::TEXTURE_MIN_FILTER, gl::TEXTURE_MAG_FILTER].iter() {
+                gl_check!(gl::TexParameteri(
+                    gl::TEXTURE_2D,
+                    *parameter,
+                    gl::NEAREST as GLint
+                ));
+            }
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+        }
+        LightProbes {
+            texture,
+            probe_count: 0,
+            context: *context,
+        }
+    }
+
+    pub fn probe_count(&self) -> usize {
+        self.probe_count
+    }
+
+    /// Replaces the probes with `probes` and what `capture` returned for each of them.
+    pub fn upload(&mut self, probes: &[LightProbe], irradiance: &[ProbeIrradiance]) {
+        if probes.len() > MAX_LIGHT_PROBES {
+            eprintln!(
+                "Blending the first {} of {} light probes",
+                MAX_LIGHT_PROBES,
+                probes.len()
+            );
+        }
+        self.probe_count = probes.len().min(irradiance.len()).min(MAX_LIGHT_PROBES);
+        let mut data = Vec::with_capacity(self.probe_count.max(1) * PROBE_TEXELS * 4);
+        for (probe, coefficients) in probes.iter().zip(irradiance.iter()).take(MAX_LIGHT_PROBES) {
+            data.extend_from_slice(&probe.position);
+            data.push(probe.radius);
+            for coefficient in coefficients.iter() {
+                data.extend_from_slice(coefficient);
+                data.push(0.0_f32);
+            }
+        }
+        data.resize(self.probe_count.max(1) * PROBE_TEXELS * 4, 0.0_f32);
+        unsafe {
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA32F as GLint,
+                PROBE_TEXELS as GLsizei,
+                self.probe_count.max(1) as GLsizei,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                data.as_ptr() as *const c_void,
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+        }
+        gpu_memory::track(
+            GpuResource::Texture,
+            self.texture,
+            mem::size_of_val(data.as_slice()),
+        );
+    }
+
+    /// Binds the probe texture and sets the probe count of `program`, whose `uniforms` these
+    /// are.
+    pub fn bind(&self, program: &ShaderProgram, uniforms: &LightProbeUniforms) {
+        program.use_program();
+        uniforms.light_probe_count.set_int(self.probe_count as i32);
+        unsafe {
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0 + LIGHT_PROBES_UNIT));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.texture));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        }
+    }
+}
+
+impl Drop for LightProbes {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Texture, self.texture);
+    }
+}
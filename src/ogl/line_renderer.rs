@@ -0,0 +1,269 @@
+use gl::types::*;
+
+use crate::math::color::Color;
+use crate::math::glm::{Mat4, Vec3};
+use crate::ogl::buffer::Buffer;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::stats::FrameStats;
+use std::ffi::c_void;
+use std::mem;
+
+// Floats per segment: start, end, sRGB start and end colors with alpha, then width in pixels
+const SEGMENT_FLOATS: usize = 15;
+
+const LINE_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_start;
+layout (location = 1) in vec3 a_end;
+layout (location = 2) in vec4 a_start_color;
+layout (location = 3) in vec4 a_end_color;
+layout (location = 4) in float a_width;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+uniform vec2 viewport_size;
+
+// Pixels along the segment from its start, and across it from its center
+out vec2 o_offset;
+out vec4 o_color;
+flat out float o_length;
+flat out float o_half_width;
+
+// Clip-space w below which a point counts as behind the camera
+const float NEAR_W = 1e-4f;
+
+void main() {
+    mat4 projection_from_world = projection_from_view * view_from_world;
+    vec4 clip_start = projection_from_world * vec4(a_start, 1.0f);
+    vec4 clip_end = projection_from_world * vec4(a_end, 1.0f);
+    if (clip_start.w < NEAR_W && clip_end.w < NEAR_W) {
+        // Wholly behind the camera: outside the clip volume, so nothing is drawn
+        gl_Position = vec4(2.0f, 2.0f, 2.0f, 1.0f);
+        return;
+    }
+    // Segments crossing the camera plane are cut there, so their ends project the right way
+    if (clip_start.w < NEAR_W) {
+        clip_start = mix(clip_start, clip_end, (NEAR_W - clip_start.w) / (clip_end.w - clip_start.w));
+    } else if (clip_end.w < NEAR_W) {
+        clip_end = mix(clip_end, clip_start, (NEAR_W - clip_end.w) / (clip_start.w - clip_end.w));
+    }
+    vec3 ndc_start = clip_start.xyz / clip_start.w;
+    vec3 ndc_end = clip_end.xyz / clip_end.w;
+    vec2 pixels_from_ndc = viewport_size * 0.5f;
+    vec2 delta = (ndc_end.xy - ndc_start.xy) * pixels_from_ndc;
+    float segment_length = length(delta);
+    // Points are segments of no length, expanded into squares
+    vec2 direction = segment_length > 1e-4f ? delta / segment_length : vec2(1.0f, 0.0f);
+    vec2 normal = vec2(-direction.y, direction.x);
+
+    // Four vertices per segment, drawn as a strip; the quad reaches a pixel past the line's
+    // rounded outline, for the anti-aliased edge
+    bool at_end = (gl_VertexID & 1) == 1;
+    float side = float(gl_VertexID >> 1) * 2.0f - 1.0f;
+    float half_width = a_width * 0.5f;
+    float extent = half_width + 1.0f;
+    vec2 offset = direction * (at_end ? extent : -extent) + normal * side * extent;
+    vec3 ndc = at_end ? ndc_end : ndc_start;
+    // Positions go out already divided, so offsets interpolate linearly in screen space
+    gl_Position = vec4(ndc.xy + offset / pixels_from_ndc, ndc.z, 1.0f);
+    o_offset = vec2(at_end ? segment_length + extent : -extent, side * extent);
+    o_color = at_end ? a_end_color : a_start_color;
+    o_length = segment_length;
+    o_half_width = half_width;
+}
+"#;
+
+const LINE_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_offset;
+in vec4 o_color;
+flat in float o_length;
+flat in float o_half_width;
+
+out vec4 frag_color;
+
+void main() {
+    // Distance to the segment's core line, which rounds the caps and makes points discs
+    float along = max(-o_offset.x, 0.0f) + max(o_offset.x - o_length, 0.0f);
+    float distance = length(vec2(along, o_offset.y));
+    // Lines thinner than a pixel fade out instead of breaking up
+    float coverage = clamp(max(o_half_width, 0.5f) + 0.5f - distance, 0.0f, 1.0f)
+        * min(o_half_width * 2.0f, 1.0f);
+    if (coverage <= 0.0f) {
+        discard;
+    }
+    frag_color = vec4(o_color.rgb, o_color.a * coverage);
+}
+"#;
+
+/// Whether lines are hidden behind the scene or drawn over it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthMode {
+    /// Tested against the scene's depth, without writing it
+    Tested,
+    /// Drawn on top of everything, e.g. for handles inside the object they manipulate
+    Overlay,
+}
+
+/// Batches world-space line segments and points with per-vertex colors, then draws them all at
+/// `flush`. Lines are expanded into screen-space quads, so they keep their width in pixels on
+/// any context and get smooth, rounded edges; OpenGL core profiles cap `glLineWidth` at 1.
+pub struct LineRenderer {
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    instance_buffer: Buffer,
+    /// Segments queued since the last flush, depth tested then overlaid
+    tested_segments: Vec<f32>,
+    overlay_segments: Vec<f32>,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    viewport_size_uniform: Uniform,
+    context: GlContext,
+}
+
+impl LineRenderer {
+    pub fn new(context: &GlContext) -> Result<LineRenderer, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                LINE_VERTEX_SHADER_SOURCE,
+                LINE_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("lines");
+            let instance_buffer = Buffer::new(context, gl::STREAM_DRAW);
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, instance_buffer.id));
+            let stride = (SEGMENT_FLOATS * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_start, a_end, a_start_color, a_end_color and a_width attributes
+            let mut offset = 0;
+            for (location, components) in [(0, 3), (1, 3), (2, 4), (3, 4), (4, 1)].iter() {
+                gl_check!(gl::VertexAttribPointer(
+                    *location,
+                    *components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (offset * mem::size_of::<GLfloat>()) as *const c_void,
+                ));
+                gl_check!(gl::EnableVertexAttribArray(*location));
+                gl_check!(gl::VertexAttribDivisor(*location, 1));
+                offset += *components as usize;
+            }
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "lines");
+            instance_buffer.set_label(context, "line segments");
+
+            let view_from_world_uniform = shader_program.uniform("view_from_world");
+            let projection_from_view_uniform = shader_program.uniform("projection_from_view");
+            let viewport_size_uniform = shader_program.uniform("viewport_size");
+            Ok(LineRenderer {
+                shader_program,
+                vertex_array_obj,
+                instance_buffer,
+                tested_segments: vec![],
+                overlay_segments: vec![],
+                view_from_world_uniform,
+                projection_from_view_uniform,
+                viewport_size_uniform,
+                context: *context,
+            })
+        }
+    }
+
+    /// Queues a segment `width` pixels wide in one color.
+    pub fn line(&mut self, start: &Vec3, end: &Vec3, color: Color, width: f32, depth: DepthMode) {
+        self.gradient_line(start, end, color, color, width, depth);
+    }
+
+    /// Queues a segment `width` pixels wide, its color blending from `start_color` to
+    /// `end_color`.
+    pub fn gradient_line(
+        &mut self,
+        start: &Vec3,
+        end: &Vec3,
+        start_color: Color,
+        end_color: Color,
+        width: f32,
+        depth: DepthMode,
+    ) {
+        let segments = match depth {
+            DepthMode::Tested => &mut self.tested_segments,
+            DepthMode::Overlay => &mut self.overlay_segments,
+        };
+        segments.extend_from_slice(&[start.x, start.y, start.z, end.x, end.y, end.z]);
+        segments.extend_from_slice(&start_color.to_srgb());
+        segments.extend_from_slice(&end_color.to_srgb());
+        segments.push(width);
+    }
+
+    /// Queues a round dot `size` pixels across.
+    #[allow(dead_code)]
+    pub fn point(&mut self, position: &Vec3, color: Color, size: f32, depth: DepthMode) {
+        self.gradient_line(position, position, color, color, size, depth);
+    }
+
+    /// Draws the queued lines and points into the bound viewport, depth tested ones first, and
+    /// empties the queue.
+    pub fn flush(&mut self, camera: &Camera, projection_from_view: &Mat4, stats: &mut FrameStats) {
+        if self.tested_segments.is_empty() && self.overlay_segments.is_empty() {
+            return;
+        }
+        self.shader_program.use_program();
+        self.view_from_world_uniform
+            .set_mat4f(&camera.view_matrix());
+        self.projection_from_view_uniform
+            .set_mat4f(projection_from_view);
+        unsafe {
+            let mut viewport = [0; 4];
+            gl_check!(gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()));
+            self.viewport_size_uniform
+                .set_vec2f([viewport[2] as f32, viewport[3] as f32]);
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            // Anti-aliased edges would hide what lies behind them if they wrote depth
+            gl_check!(gl::DepthMask(gl::FALSE));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            self.draw_segments(DepthMode::Tested, stats);
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            self.draw_segments(DepthMode::Overlay, stats);
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::DepthMask(gl::TRUE));
+            gl_check!(gl::Disable(gl::BLEND));
+        }
+        self.tested_segments.clear();
+        self.overlay_segments.clear();
+    }
+
+    unsafe fn draw_segments(&mut self, depth: DepthMode, stats: &mut FrameStats) {
+        let segments = match depth {
+            DepthMode::Tested => &self.tested_segments,
+            DepthMode::Overlay => &self.overlay_segments,
+        };
+        if segments.is_empty() {
+            return;
+        }
+        let segment_count = segments.len() / SEGMENT_FLOATS;
+        self.instance_buffer.set_data(&self.context, segments);
+        gl_check!(gl::DrawArraysInstanced(
+            gl::TRIANGLE_STRIP,
+            0,
+            4,
+            segment_count as GLsizei
+        ));
+        stats.record_draw(2 * segment_count as u64);
+    }
+}
+
+impl Drop for LineRenderer {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::VertexArray, self.vertex_array_obj);
+    }
+}
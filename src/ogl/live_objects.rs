@@ -0,0 +1,128 @@
+// Every GL object the wrappers create, from its creation until it is deleted. Owners delete
+// theirs when dropped; `teardown` deletes whatever is left before the context goes, reporting
+// it as leaked in debug builds.
+
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GlObject {
+    VertexArray,
+    Buffer,
+    Texture,
+    Renderbuffer,
+    Framebuffer,
+    Sampler,
+    Query,
+    Program,
+}
+
+// Live objects with the label debuggers show for them, if set. Behind a lock because the upload
+// worker creates textures and buffers from its own thread.
+static LIVE: Mutex<BTreeMap<(GlObject, GLuint), Option<String>>> = Mutex::new(BTreeMap::new());
+
+/// Records object `id`, just created in the current context or one sharing its objects.
+pub fn created(object: GlObject, id: GLuint) {
+    if id != 0 {
+        LIVE.lock().unwrap().insert((object, id), None);
+    }
+}
+
+/// Records `ids`, created together.
+pub fn created_all(object: GlObject, ids: &[GLuint]) {
+    for id in ids {
+        created(object, *id);
+    }
+}
+
+/// Keeps the label `debug_label::set` gave object `id`, for the leak report.
+pub fn set_label(identifier: GLenum, id: GLuint, label: &str) {
+    let object = match identifier {
+        gl::VERTEX_ARRAY => GlObject::VertexArray,
+        gl::BUFFER => GlObject::Buffer,
+        gl::TEXTURE => GlObject::Texture,
+        gl::RENDERBUFFER => GlObject::Renderbuffer,
+        gl::FRAMEBUFFER => GlObject::Framebuffer,
+        gl::SAMPLER => GlObject::Sampler,
+        gl::QUERY => GlObject::Query,
+        gl::PROGRAM => GlObject::Program,
+        _ => return,
+    };
+    if let Some(tracked) = LIVE.lock().unwrap().get_mut(&(object, id)) {
+        *tracked = Some(label.to_string());
+    }
+}
+
+/// Deletes object `id` and forgets it, along with the storage `gpu_memory` counts for it.
+/// Vertex arrays, framebuffers and queries are not shared, so those have to go in the context
+/// that created them.
+pub unsafe fn delete(object: GlObject, id: GLuint) {
+    if id == 0 {
+        return;
+    }
+    match object {
+        GlObject::VertexArray => gl_check!(gl::DeleteVertexArrays(1, &id)),
+        GlObject::Buffer => {
+            gl_check!(gl::DeleteBuffers(1, &id));
+            gpu_memory::untrack(GpuResource::Buffer, id);
+        }
+        GlObject::Texture => {
+            gl_check!(gl::DeleteTextures(1, &id));
+            gpu_memory::untrack(GpuResource::Texture, id);
+        }
+        GlObject::Renderbuffer => {
+            gl_check!(gl::DeleteRenderbuffers(1, &id));
+            gpu_memory::untrack(GpuResource::Renderbuffer, id);
+        }
+        GlObject::Framebuffer => gl_check!(gl::DeleteFramebuffers(1, &id)),
+        GlObject::Sampler => gl_check!(gl::DeleteSamplers(1, &id)),
+        GlObject::Query => gl_check!(gl::DeleteQueries(1, &id)),
+        GlObject::Program => gl_check!(gl::DeleteProgram(id)),
+    }
+    LIVE.lock().unwrap().remove(&(object, id));
+}
+
+/// `delete` for owners of a bare `id` that hold the context token instead of an `unsafe` block.
+pub fn release(_context: &GlContext, object: GlObject, id: GLuint) {
+    unsafe { delete(object, id) }
+}
+
+/// Deletes `ids`, see `delete`.
+pub unsafe fn delete_all(object: GlObject, ids: &[GLuint]) {
+    for id in ids {
+        delete(object, *id);
+    }
+}
+
+/// Live objects with their labels, by kind and ascending name.
+fn live() -> Vec<(GlObject, GLuint, Option<String>)> {
+    LIVE.lock()
+        .unwrap()
+        .iter()
+        .map(|(&(object, id), label)| (object, id, label.clone()))
+        .collect()
+}
+
+/// Deletes every object still alive, in the context about to be destroyed, once their owners
+/// are gone: whatever is left was leaked, and is listed first when `report_leaks` is set.
+pub fn teardown(_context: &GlContext, report_leaks: bool) {
+    unsafe {
+        let leaked = live();
+        if report_leaks && !leaked.is_empty() {
+            eprintln!("{} GL objects still alive at exit:", leaked.len());
+            for (object, id, label) in leaked.iter() {
+                match label {
+                    Some(label) => eprintln!("  {:?} {} \"{}\"", object, id, label),
+                    None => eprintln!("  {:?} {}", object, id),
+                }
+            }
+        }
+        for (object, id, _) in leaked.iter() {
+            delete(*object, *id);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{Mesh, MeshData, VertexAttribute};
+use std::rc::Rc;
+
+/// Packs meshes sharing one vertex layout into a single vertex and index buffer behind one
+/// vertex array, so the renderer can draw any number of them with one multi-draw call. Each
+/// mesh's indices are rebased onto its vertices in the shared buffer, so drawing one needs no
+/// base vertex.
+pub struct MeshPool {
+    data: MeshData,
+    /// First index and index count of every added mesh
+    ranges: Vec<(GLsizei, GLsizei)>,
+}
+
+impl MeshPool {
+    pub fn new(attributes: &[VertexAttribute]) -> MeshPool {
+        MeshPool {
+            data: MeshData {
+                vertices: vec![],
+                indices: Some(vec![]),
+                attributes: attributes.to_vec(),
+            },
+            ranges: vec![],
+        }
+    }
+
+    /// Appends `geometry`, returning where its mesh will be in the result of `build`.
+    /// Non-indexed geometry gets an index per vertex.
+    pub fn add(&mut self, geometry: &MeshData) -> Result<usize, String> {
+        if geometry.attributes != self.data.attributes {
+            return Err(format!(
+                "Pooled geometry layout {:?} does not match pool layout {:?}",
+                geometry.attributes, self.data.attributes
+            ));
+        }
+        let base_vertex = self.data.vertex_count() as u32;
+        let pool_indices = self.data.indices.get_or_insert_with(Vec::new);
+        let first_index = pool_indices.len();
+        match &geometry.indices {
+            Some(indices) => {
+                pool_indices.extend(indices.iter().map(|index| base_vertex + index));
+            }
+            None => {
+                pool_indices
+                    .extend((0..geometry.vertex_count() as u32).map(|index| base_vertex + index));
+            }
+        }
+        let index_count = pool_indices.len() - first_index;
+        self.data.vertices.extend_from_slice(&geometry.vertices);
+        self.ranges
+            .push((first_index as GLsizei, index_count as GLsizei));
+        Ok(self.ranges.len() - 1)
+    }
+
+    /// Uploads the shared buffers, returning one mesh per `add` in the same order.
+    pub fn build(self, context: &GlContext) -> Result<Vec<Rc<Mesh>>, String> {
+        let shared = Mesh::from_data(context, &self.data);
+        shared.set_label("mesh pool");
+        self.ranges
+            .iter()
+            .map(|(first_index, index_count)| {
+                shared.sub_mesh(*first_index, *index_count).map(Rc::new)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,229 @@
+use gl::types::*;
+
+use crate::math::color::Color;
+use crate::math::glm::{self, Mat4, Vec3, Vec4};
+use crate::math::plane::Plane;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::renderer::Renderer;
+use crate::ogl::stencil::StencilState;
+
+// Stencil value marking where the mirror is visible
+const MIRROR_STENCIL: GLint = 1;
+
+const MIRROR_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform mat4 projection_from_world;
+uniform vec3 center;
+uniform vec3 half_axis_u;
+uniform vec3 half_axis_v;
+
+void main() {
+    // Four vertices, drawn as a strip: (-1, -1), (1, -1), (-1, 1), (1, 1)
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1)) * 2.0f - 1.0f;
+    vec3 position = center + half_axis_u * corner.x + half_axis_v * corner.y;
+    gl_Position = projection_from_world * vec4(position, 1.0f);
+}
+"#;
+
+const MIRROR_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform vec4 color;
+// Pushes the surface to the far plane, clearing the depth behind the mirror
+uniform bool far_depth;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = color;
+    gl_FragDepth = far_depth ? 1.0f : gl_FragCoord.z;
+}
+"#;
+
+/// A flat rectangular mirror, drawn by marking where it is visible in the stencil buffer and
+/// drawing the scene again there, reflected about its plane. Needs a stencil buffer in the bound
+/// framebuffer.
+pub struct Mirror {
+    /// Faces the side the mirror reflects
+    pub plane: Plane,
+    /// Blended over the reflection; alpha is its strength
+    pub tint: Color,
+    center: Vec3,
+    half_axis_u: Vec3,
+    half_axis_v: Vec3,
+    /// The camera's projection, while a reflection replaces it
+    saved_projection: Option<Mat4>,
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    projection_from_world_uniform: Uniform,
+    center_uniform: Uniform,
+    half_axis_u_uniform: Uniform,
+    half_axis_v_uniform: Uniform,
+    color_uniform: Uniform,
+    far_depth_uniform: Uniform,
+}
+
+impl Mirror {
+    /// A `width` by `height` mirror centered on `center`, facing `normal`.
+    pub fn new(
+        context: &GlContext,
+        center: Vec3,
+        normal: Vec3,
+        width: f32,
+        height: f32,
+    ) -> Result<Mirror, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                MIRROR_VERTEX_SHADER_SOURCE,
+                MIRROR_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("mirror");
+            let normal = glm::normalize(&normal);
+            // Any direction not along the normal gives the rectangle's axes
+            let helper = if normal.y.abs() < 0.99_f32 {
+                glm::vec3(0.0_f32, 1.0_f32, 0.0_f32)
+            } else {
+                glm::vec3(0.0_f32, 0.0_f32, 1.0_f32)
+            };
+            let axis_u = glm::normalize(&glm::cross(&helper, &normal));
+            let axis_v = glm::cross(&normal, &axis_u);
+            // No attributes: the vertex shader places the corners
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "mirror");
+            Ok(Mirror {
+                plane: Plane::from_point_normal(&center, &normal),
+                tint: Color::from_srgb(0.6_f32, 0.7_f32, 0.8_f32, 0.15_f32),
+                center,
+                half_axis_u: axis_u * (width * 0.5_f32),
+                half_axis_v: axis_v * (height * 0.5_f32),
+                saved_projection: None,
+                projection_from_world_uniform: shader_program.uniform("projection_from_world"),
+                center_uniform: shader_program.uniform("center"),
+                half_axis_u_uniform: shader_program.uniform("half_axis_u"),
+                half_axis_v_uniform: shader_program.uniform("half_axis_v"),
+                color_uniform: shader_program.uniform("color"),
+                far_depth_uniform: shader_program.uniform("far_depth"),
+                shader_program,
+                vertex_array_obj,
+            })
+        }
+    }
+
+    /// Marks the mirror in the stencil buffer where the scene drawn so far leaves it visible,
+    /// clears it to the clear color, then starts a masked view of `renderer` reflected about the
+    /// mirror plane. Submit the scene and call `end_reflection`. Returns false, starting nothing,
+    /// when the camera is behind the mirror.
+    pub fn begin_reflection(&mut self, camera: &Camera, renderer: &mut Renderer) -> bool {
+        if self.plane.signed_distance(&camera.position) <= 0.0_f32 {
+            return false;
+        }
+        let view_from_world = camera.view_matrix();
+        let projection_from_view = *renderer.projection_from_view();
+        self.use_program(&(projection_from_view * view_from_world));
+        unsafe {
+            // Outlines leave marks of their own behind
+            gl_check!(gl::Clear(gl::STENCIL_BUFFER_BIT));
+            StencilState::mark(MIRROR_STENCIL).apply();
+            gl_check!(gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE));
+            gl_check!(gl::DepthMask(gl::FALSE));
+            self.draw_quad(&Color::TRANSPARENT, false);
+            gl_check!(gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE));
+            gl_check!(gl::DepthMask(gl::TRUE));
+
+            StencilState::inside(MIRROR_STENCIL).apply();
+            gl_check!(gl::DepthFunc(gl::ALWAYS));
+            self.draw_quad(&renderer.clear_color, true);
+            gl_check!(gl::DepthFunc(gl::LESS));
+        }
+        renderer.stats.record_draw(4);
+
+        // Clipping at the mirror keeps what lies behind it out of the reflection
+        let reflected_view = view_from_world * self.plane.reflection();
+        let clip_plane = self.plane.coefficients_in(&reflected_view);
+        renderer.set_projection(oblique_projection(&projection_from_view, &clip_plane));
+        self.saved_projection = Some(projection_from_view);
+        renderer.begin_masked_view(reflected_view);
+        true
+    }
+
+    /// Draws the reflected scene submitted since `begin_reflection`, then tints it and puts the
+    /// mirror surface into the depth buffer, so what is drawn later passes in front of it or
+    /// behind it correctly.
+    pub fn end_reflection(&mut self, camera: &Camera, renderer: &mut Renderer) {
+        renderer.end_view("mirror");
+        if let Some(projection_from_view) = self.saved_projection.take() {
+            renderer.set_projection(projection_from_view);
+        }
+        renderer.begin_masked_view(camera.view_matrix());
+        self.use_program(&(renderer.projection_from_view() * camera.view_matrix()));
+        unsafe {
+            StencilState::inside(MIRROR_STENCIL).apply();
+            gl_check!(gl::DepthFunc(gl::ALWAYS));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            self.draw_quad(&self.tint, false);
+            gl_check!(gl::Disable(gl::BLEND));
+            gl_check!(gl::DepthFunc(gl::LESS));
+            StencilState::disable();
+        }
+        renderer.stats.record_draw(2);
+    }
+
+    fn use_program(&self, projection_from_world: &Mat4) {
+        self.shader_program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(projection_from_world);
+        for (uniform, value) in [
+            (&self.center_uniform, &self.center),
+            (&self.half_axis_u_uniform, &self.half_axis_u),
+            (&self.half_axis_v_uniform, &self.half_axis_v),
+        ]
+        .iter()
+        {
+            uniform.set_vec3f([value.x, value.y, value.z]);
+        }
+    }
+
+    unsafe fn draw_quad(&self, color: &Color, far_depth: bool) {
+        self.color_uniform.set_color(color);
+        self.far_depth_uniform.set_bool(far_depth);
+        gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+        gl_check!(gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4));
+        gl_check!(gl::BindVertexArray(0));
+    }
+}
+
+impl Drop for Mirror {
+    fn drop(&mut self) {
+        unsafe { live_objects::delete(GlObject::VertexArray, self.vertex_array_obj) };
+    }
+}
+
+/// `projection_from_view` with its near plane moved onto `clip_plane`, given in view space with
+/// the camera on its negative side. Geometry behind the plane is clipped with no clip distances
+/// in the shaders, at the cost of some depth precision (Lengyel's oblique near-plane clipping).
+fn oblique_projection(projection_from_view: &Mat4, clip_plane: &Vec4) -> Mat4 {
+    // The corner of the view volume opposite the plane, in view space
+    let corner = glm::inverse(projection_from_view)
+        * glm::vec4(
+            clip_plane.x.signum(),
+            clip_plane.y.signum(),
+            1.0_f32,
+            1.0_f32,
+        );
+    let scaled_plane = clip_plane * (2.0_f32 / glm::dot4(clip_plane, &corner));
+    let mut oblique = *projection_from_view;
+    glm::set_row(
+        &mut oblique,
+        2,
+        &(scaled_plane - glm::row(projection_from_view, 3)),
+    );
+    oblique
+}
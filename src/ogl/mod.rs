@@ -1,2 +1,58 @@
-pub mod graphics;
+#[macro_use]
 pub mod utils;
+
+pub mod batching;
+pub mod billboard_renderer;
+#[cfg(feature = "gl45")]
+pub mod bindless;
+pub mod buffer;
+pub mod capabilities;
+pub mod context;
+pub mod debug_hud;
+pub mod debug_label;
+pub mod debug_output;
+pub mod environment_capture;
+pub mod fallback;
+pub mod font_renderer;
+pub mod frame_capture;
+#[cfg(feature = "gl45")]
+pub mod gpu_culling;
+pub mod gpu_memory;
+pub mod gpu_particles;
+pub mod gpu_timer;
+pub mod graphics;
+pub mod grass_renderer;
+pub mod hdr_cubemap;
+pub mod id_buffer;
+#[cfg(feature = "gl45")]
+pub mod indirect;
+pub mod light_clusters;
+pub mod light_probes;
+pub mod line_renderer;
+pub mod live_objects;
+pub mod mesh_pool;
+pub mod mirror;
+pub mod occlusion;
+pub mod overlay;
+pub mod particle_renderer;
+#[cfg(feature = "gl45")]
+pub mod persistent_buffer;
+pub mod post_process;
+pub mod reflection_probes;
+pub mod render_queue;
+pub mod render_target;
+pub mod renderer;
+pub mod skybox;
+pub mod sprite_batch;
+pub mod stats;
+pub mod stencil;
+pub mod terrain_renderer;
+pub mod text_renderer;
+pub mod texture_inspector;
+pub mod texture_table;
+#[cfg(feature = "ui")]
+pub mod ui_painter;
+pub mod upload_worker;
+pub mod water_renderer;
+#[cfg(target_arch = "wasm32")]
+pub mod webgl;
@@ -0,0 +1,199 @@
+use gl::types::*;
+
+use crate::math::bounds::Aabb;
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{Mesh, ShaderProgram, Uniform, VertexAttribute};
+use crate::ogl::live_objects::{self, GlObject};
+use std::collections::HashMap;
+
+// Boxes the camera is this close to are counted as visible: their front faces may be clipped
+// by the near plane, which would make a box in plain view fail its query
+const CAMERA_MARGIN: f32 = 0.2;
+
+const BOX_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+
+uniform mat4 projection_from_world;
+uniform vec3 box_min;
+uniform vec3 box_size;
+
+void main() {
+    gl_Position = projection_from_world * vec4(box_min + a_pos * box_size, 1.0);
+}
+"#;
+
+const BOX_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(1.0);
+}
+"#;
+
+struct OcclusionQuery {
+    id: GLuint,
+    /// Issued and not read back yet
+    pending: bool,
+    visible: bool,
+}
+
+/// Skips objects hidden behind others by testing their bounding boxes against the depth buffer
+/// with `ANY_SAMPLES_PASSED` queries. Results are read back a frame late rather than waited for,
+/// so an object coming out from behind an occluder can show up a frame late. Worth it for large
+/// models with many triangles, not for small ones, where the query costs as much as the draw.
+pub struct OcclusionCuller {
+    queries: HashMap<u64, OcclusionQuery>,
+    /// Boxes to test once the scene's depth is drawn, by object key
+    requests: Vec<(u64, Aabb)>,
+    box_mesh: Mesh,
+    program: ShaderProgram,
+    projection_from_world_uniform: Uniform,
+    box_min_uniform: Uniform,
+    box_size_uniform: Uniform,
+    /// For drawing the boxes
+    context: GlContext,
+}
+
+impl OcclusionCuller {
+    pub fn new(context: &GlContext) -> Result<OcclusionCuller, String> {
+        let program = ShaderProgram::with_shaders(
+            context,
+            BOX_VERTEX_SHADER_SOURCE,
+            BOX_FRAGMENT_SHADER_SOURCE,
+        )?;
+        program.set_label("occlusion boxes");
+        #[rustfmt::skip]
+        let corners = [
+            0.0_f32, 0.0_f32, 0.0_f32,
+            1.0_f32, 0.0_f32, 0.0_f32,
+            1.0_f32, 1.0_f32, 0.0_f32,
+            0.0_f32, 1.0_f32, 0.0_f32,
+            0.0_f32, 0.0_f32, 1.0_f32,
+            1.0_f32, 0.0_f32, 1.0_f32,
+            1.0_f32, 1.0_f32, 1.0_f32,
+            0.0_f32, 1.0_f32, 1.0_f32,
+        ];
+        #[rustfmt::skip]
+        let indices = [
+            0, 2, 1, 0, 3, 2,
+            4, 5, 6, 4, 6, 7,
+            0, 1, 5, 0, 5, 4,
+            3, 7, 6, 3, 6, 2,
+            0, 4, 7, 0, 7, 3,
+            1, 2, 6, 1, 6, 5,
+        ];
+        let box_mesh = Mesh::with_indices(
+            context,
+            &corners,
+            &indices,
+            &[VertexAttribute {
+                location: 0,
+                components: 3,
+            }],
+        );
+        box_mesh.set_label("occlusion box");
+        let projection_from_world_uniform = program.uniform("projection_from_world");
+        let box_min_uniform = program.uniform("box_min");
+        let box_size_uniform = program.uniform("box_size");
+        Ok(OcclusionCuller {
+            context: *context,
+            queries: HashMap::new(),
+            requests: vec![],
+            box_mesh,
+            program,
+            projection_from_world_uniform,
+            box_min_uniform,
+            box_size_uniform,
+        })
+    }
+
+    /// Reads back the results that have arrived since they were issued, without waiting for
+    /// the rest.
+    pub unsafe fn collect(&mut self) {
+        for query in self.queries.values_mut().filter(|query| query.pending) {
+            let mut available = 0;
+            gl_check!(gl::GetQueryObjectuiv(
+                query.id,
+                gl::QUERY_RESULT_AVAILABLE,
+                &mut available
+            ));
+            if available != 0 {
+                let mut samples_passed = 0;
+                gl_check!(gl::GetQueryObjectuiv(
+                    query.id,
+                    gl::QUERY_RESULT,
+                    &mut samples_passed
+                ));
+                query.visible = samples_passed != 0;
+                query.pending = false;
+            }
+        }
+    }
+
+    /// Whether the object's box was visible at its last finished query. Objects not tested yet
+    /// count as visible.
+    pub fn is_visible(&self, key: u64) -> bool {
+        self.queries.get(&key).is_none_or(|query| query.visible)
+    }
+
+    /// Queues a test of `world_bounds` for `issue`, unless the last one is still in flight.
+    pub fn request(&mut self, key: u64, world_bounds: &Aabb) {
+        if !self.queries.get(&key).is_some_and(|query| query.pending) {
+            self.requests.push((key, *world_bounds));
+        }
+    }
+
+    /// Draws the requested boxes against the current depth buffer, each inside its own query,
+    /// without writing color or depth. Boxes around the camera pass without a query.
+    pub unsafe fn issue(&mut self, projection_from_world: &Mat4, camera_position: &Vec3) {
+        if self.requests.is_empty() {
+            return;
+        }
+        self.program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(projection_from_world);
+        gl_check!(gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE));
+        gl_check!(gl::DepthMask(gl::FALSE));
+        let margin = glm::vec3(CAMERA_MARGIN, CAMERA_MARGIN, CAMERA_MARGIN);
+        for (key, bounds) in self.requests.drain(..) {
+            let query = self.queries.entry(key).or_insert_with(|| {
+                let mut id = 0;
+                gl_check!(gl::GenQueries(1, &mut id));
+                live_objects::created(GlObject::Query, id);
+                OcclusionQuery {
+                    id,
+                    pending: false,
+                    visible: true,
+                }
+            });
+            let around_camera = Aabb::new(bounds.min - margin, bounds.max + margin);
+            if around_camera.contains_point(camera_position) {
+                query.visible = true;
+                continue;
+            }
+            let size = bounds.max - bounds.min;
+            self.box_min_uniform
+                .set_vec3f([bounds.min.x, bounds.min.y, bounds.min.z]);
+            self.box_size_uniform.set_vec3f([size.x, size.y, size.z]);
+            gl_check!(gl::BeginQuery(gl::ANY_SAMPLES_PASSED, query.id));
+            self.box_mesh.draw(&self.context);
+            gl_check!(gl::EndQuery(gl::ANY_SAMPLES_PASSED));
+            query.pending = true;
+        }
+        gl_check!(gl::DepthMask(gl::TRUE));
+        gl_check!(gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE));
+    }
+}
+
+impl Drop for OcclusionCuller {
+    fn drop(&mut self) {
+        unsafe {
+            for query in self.queries.values() {
+                live_objects::delete(GlObject::Query, query.id);
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::ShaderProgram;
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::stats::FrameStats;
+use std::ffi::c_void;
+use std::{mem, ptr};
+
+const OVERLAY_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 a_pos;
+layout (location = 1) in vec3 a_color;
+
+out vec3 o_color;
+
+void main() {
+    gl_Position = vec4(a_pos, 0.0f, 1.0f);
+    o_color = a_color;
+}
+"#;
+
+const OVERLAY_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec3 o_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(o_color, 1.0f);
+}
+"#;
+
+// Graph placement in normalized device coordinates
+const GRAPH_LEFT: f32 = -0.98;
+const GRAPH_BOTTOM: f32 = -0.98;
+const GRAPH_WIDTH: f32 = 0.6;
+const GRAPH_HEIGHT: f32 = 0.3;
+const GRAPH_SPACING: f32 = 0.04;
+// Frame time mapped to the top of the graph, in seconds
+const GRAPH_MAX_FRAME_TIME: f32 = 0.05;
+// Colors of the stacked GPU pass bars, cycled by pass index
+pub const GPU_PASS_COLORS: [[f32; 3]; 4] = [
+    [0.3_f32, 0.6_f32, 1.0_f32],
+    [1.0_f32, 0.5_f32, 0.1_f32],
+    [0.7_f32, 0.3_f32, 0.9_f32],
+    [0.2_f32, 0.9_f32, 0.8_f32],
+];
+
+/// Screen-space frame time graphs: CPU frame time bars colored by the 60 and 30 FPS budgets, and
+/// next to them GPU time bars stacked per timed pass, both with reference lines at the budgets.
+pub struct StatsOverlay {
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    vertex_buffer_obj: GLuint,
+    vertices: Vec<f32>,
+}
+
+impl StatsOverlay {
+    pub fn new(context: &GlContext) -> Result<StatsOverlay, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                OVERLAY_VERTEX_SHADER_SOURCE,
+                OVERLAY_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("stats overlay");
+            let (mut vertex_array_obj, mut vertex_buffer_obj) = (0_u32, 0_u32);
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::GenBuffers(1, &mut vertex_buffer_obj));
+            live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_obj));
+            let stride = 5 * mem::size_of::<GLfloat>() as GLsizei;
+            // a_pos attribute
+            gl_check!(gl::VertexAttribPointer(
+                0,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                ptr::null()
+            ));
+            gl_check!(gl::EnableVertexAttribArray(0));
+            // a_color attribute
+            gl_check!(gl::VertexAttribPointer(
+                1,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(1));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "stats overlay");
+            debug_label::set(gl::BUFFER, vertex_buffer_obj, "stats overlay vertices");
+
+            Ok(StatsOverlay {
+                shader_program,
+                vertex_array_obj,
+                vertex_buffer_obj,
+                vertices: vec![],
+            })
+        }
+    }
+
+    pub fn draw(&mut self, stats: &FrameStats) {
+        self.vertices.clear();
+        let bar_width = GRAPH_WIDTH / stats.history() as f32;
+        for (i, frame_time) in stats.frame_times().enumerate() {
+            let height = (frame_time / GRAPH_MAX_FRAME_TIME).min(1.0_f32) * GRAPH_HEIGHT;
+            let color = if *frame_time <= 1.0_f32 / 60.0_f32 {
+                [0.2_f32, 0.8_f32, 0.2_f32]
+            } else if *frame_time <= 1.0_f32 / 30.0_f32 {
+                [0.9_f32, 0.8_f32, 0.1_f32]
+            } else {
+                [0.9_f32, 0.2_f32, 0.2_f32]
+            };
+            let left = GRAPH_LEFT + i as f32 * bar_width;
+            self.push_quad(left, GRAPH_BOTTOM, bar_width * 0.8_f32, height, color);
+        }
+
+        let gpu_graph_left = GRAPH_LEFT + GRAPH_WIDTH + GRAPH_SPACING;
+        for (i, pass_times) in stats.gpu_pass_times().enumerate() {
+            let left = gpu_graph_left + i as f32 * bar_width;
+            let mut bottom = GRAPH_BOTTOM;
+            for (pass_i, pass_time) in pass_times.iter().enumerate() {
+                let top = (bottom + pass_time / GRAPH_MAX_FRAME_TIME * GRAPH_HEIGHT)
+                    .min(GRAPH_BOTTOM + GRAPH_HEIGHT);
+                let color = GPU_PASS_COLORS[pass_i % GPU_PASS_COLORS.len()];
+                self.push_quad(left, bottom, bar_width * 0.8_f32, top - bottom, color);
+                bottom = top;
+            }
+        }
+
+        for graph_left in [GRAPH_LEFT, gpu_graph_left].iter() {
+            for budget in [1.0_f32 / 60.0_f32, 1.0_f32 / 30.0_f32].iter() {
+                let y = GRAPH_BOTTOM + budget / GRAPH_MAX_FRAME_TIME * GRAPH_HEIGHT;
+                self.push_quad(*graph_left, y, GRAPH_WIDTH, 0.003_f32, [0.8_f32; 3]);
+            }
+        }
+
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            self.shader_program.use_program();
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer_obj));
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(self.vertices.as_slice()) as GLsizeiptr,
+                self.vertices.as_ptr() as *const c_void,
+                gl::STREAM_DRAW,
+            ));
+            gpu_memory::track(
+                GpuResource::Buffer,
+                self.vertex_buffer_obj,
+                mem::size_of_val(self.vertices.as_slice()),
+            );
+            gl_check!(gl::DrawArrays(
+                gl::TRIANGLES,
+                0,
+                (self.vertices.len() / 5) as GLsizei
+            ));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+    }
+
+    fn push_quad(&mut self, left: f32, bottom: f32, width: f32, height: f32, color: [f32; 3]) {
+        let (right, top) = (left + width, bottom + height);
+        for (x, y) in [
+            (left, bottom),
+            (right, bottom),
+            (right, top),
+            (right, top),
+            (left, top),
+            (left, bottom),
+        ]
+        .iter()
+        {
+            self.vertices.extend_from_slice(&[*x, *y]);
+            self.vertices.extend_from_slice(&color);
+        }
+    }
+}
+
+impl Drop for StatsOverlay {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete(GlObject::Buffer, self.vertex_buffer_obj);
+        }
+    }
+}
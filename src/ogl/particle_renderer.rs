@@ -0,0 +1,52 @@
+use gl::types::*;
+
+use crate::math::glm::Mat4;
+use crate::ogl::billboard_renderer::{Billboard, BillboardRenderer};
+use crate::ogl::graphics::Camera;
+use crate::ogl::stats::FrameStats;
+use crate::particles::ParticleSystem;
+
+/// Draws particles as billboards, one instanced draw call per emitter. Particles test against
+/// the scene's depth without writing it, so draw them after the opaque scene.
+pub struct ParticleRenderer {
+    /// The emitter being drawn's particles
+    billboards: Vec<Billboard>,
+}
+
+impl ParticleRenderer {
+    pub fn new() -> ParticleRenderer {
+        ParticleRenderer { billboards: vec![] }
+    }
+
+    /// Draws every emitter of `system` through `billboard_renderer`, blended the emitter's way:
+    /// emitters with a flipbook play its frames from `sprite_sheet`, the rest are soft discs.
+    pub fn draw(
+        &mut self,
+        system: &ParticleSystem,
+        sprite_sheet: GLuint,
+        billboard_renderer: &mut BillboardRenderer,
+        camera: &Camera,
+        projection_from_view: &Mat4,
+        stats: &mut FrameStats,
+    ) {
+        for emitter in system.emitters.iter() {
+            self.billboards.clear();
+            self.billboards
+                .extend(emitter.particles().iter().map(|particle| Billboard {
+                    position: particle.position,
+                    size: emitter.size(particle),
+                    rotation_radians: particle.rotation_degrees.to_radians(),
+                    color: emitter.color(particle),
+                    uv_rect: emitter.uv_rect(particle),
+                }));
+            billboard_renderer.draw(
+                &self.billboards,
+                emitter.settings.flipbook.map(|_| sprite_sheet),
+                emitter.settings.blend,
+                camera,
+                projection_from_view,
+                stats,
+            );
+        }
+    }
+}
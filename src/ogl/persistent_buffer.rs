@@ -0,0 +1,151 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::live_objects::{self, GlObject};
+use std::mem;
+use std::ptr;
+
+// Regions the buffer is split into, so the CPU can fill one while the GPU still reads the others
+const REGION_COUNT: usize = 3;
+// How long to block on the GPU at a time while waiting for a region to free up
+const FENCE_WAIT_NS: GLuint64 = 1_000_000;
+
+/// Buffer that stays mapped for its whole lifetime (GL 4.4 buffer storage), for data rewritten
+/// every frame such as instance matrices. Each write goes into the next of three regions, and a
+/// fence placed after the draws reading a region keeps it from being overwritten too early, so
+/// streaming never maps, unmaps or respecifies storage.
+pub struct PersistentBuffer {
+    pub id: GLuint,
+    target: GLenum,
+    region_size: usize,
+    mapped: *mut u8,
+    fences: [Option<GLsync>; REGION_COUNT],
+    region: usize,
+}
+
+impl PersistentBuffer {
+    /// Creates a buffer bound to `target` with room for `region_size` bytes per write.
+    pub fn new(
+        _context: &GlContext,
+        target: GLenum,
+        region_size: usize,
+    ) -> Result<PersistentBuffer, String> {
+        unsafe {
+            let mut buffer = PersistentBuffer {
+                id: 0,
+                target,
+                region_size: 0,
+                mapped: ptr::null_mut(),
+                fences: [None; REGION_COUNT],
+                region: 0,
+            };
+            buffer.allocate(region_size.max(1))?;
+            Ok(buffer)
+        }
+    }
+
+    /// Copies `data` into the next free region, growing the buffer when it does not fit, and
+    /// returns the region's byte offset for the attribute or range bindings of the draws. Call
+    /// `fence` once those draws are issued.
+    pub unsafe fn write<T: Copy>(&mut self, data: &[T]) -> Result<usize, String> {
+        let size = mem::size_of_val(data);
+        if size > self.region_size {
+            self.wait_all();
+            self.release();
+            self.allocate(size.next_power_of_two())?;
+        }
+        self.region = (self.region + 1) % REGION_COUNT;
+        if let Some(fence) = self.fences[self.region].take() {
+            wait_fence(fence);
+        }
+        let offset = self.region * self.region_size;
+        // Coherent mapping makes the copy visible to draws issued afterwards without a flush
+        ptr::copy_nonoverlapping(data.as_ptr() as *const u8, self.mapped.add(offset), size);
+        Ok(offset)
+    }
+
+    /// Marks the end of the draws reading the region last written.
+    pub unsafe fn fence(&mut self) {
+        if let Some(fence) = self.fences[self.region].take() {
+            gl_check!(gl::DeleteSync(fence));
+        }
+        self.fences[self.region] =
+            Some(gl_check!(gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)));
+    }
+
+    unsafe fn allocate(&mut self, region_size: usize) -> Result<(), String> {
+        let total_size = (region_size * REGION_COUNT) as GLsizeiptr;
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        gl_check!(gl::GenBuffers(1, &mut self.id));
+        live_objects::created(GlObject::Buffer, self.id);
+        gl_check!(gl::BindBuffer(self.target, self.id));
+        gl_check!(gl::BufferStorage(
+            self.target,
+            total_size,
+            ptr::null(),
+            flags
+        ));
+        let mapped = gl_check!(gl::MapBufferRange(self.target, 0, total_size, flags));
+        gl_check!(gl::BindBuffer(self.target, 0));
+        if mapped.is_null() {
+            live_objects::delete(GlObject::Buffer, self.id);
+            self.id = 0;
+            return Err(format!(
+                "Failed mapping a {} byte persistent buffer",
+                total_size
+            ));
+        }
+        self.mapped = mapped as *mut u8;
+        self.region_size = region_size;
+        gpu_memory::track(GpuResource::Buffer, self.id, total_size as usize);
+        debug_label::set(gl::BUFFER, self.id, "persistent ring buffer");
+        Ok(())
+    }
+
+    unsafe fn release(&mut self) {
+        gl_check!(gl::BindBuffer(self.target, self.id));
+        gl_check!(gl::UnmapBuffer(self.target));
+        gl_check!(gl::BindBuffer(self.target, 0));
+        live_objects::delete(GlObject::Buffer, self.id);
+        self.id = 0;
+        self.region_size = 0;
+        self.mapped = ptr::null_mut();
+    }
+
+    unsafe fn wait_all(&mut self) {
+        for fence in self.fences.iter_mut() {
+            if let Some(fence) = fence.take() {
+                wait_fence(fence);
+            }
+        }
+    }
+}
+
+impl Drop for PersistentBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            // Draws still reading a region keep the storage alive until they finish
+            for fence in self.fences.iter_mut() {
+                if let Some(fence) = fence.take() {
+                    gl_check!(gl::DeleteSync(fence));
+                }
+            }
+            if self.id != 0 {
+                self.release();
+            }
+        }
+    }
+}
+
+/// Blocks until the GPU passes `fence`, then deletes it. The first wait flushes, in case the
+/// fence has not been submitted yet.
+unsafe fn wait_fence(fence: GLsync) {
+    let mut flags = gl::SYNC_FLUSH_COMMANDS_BIT;
+    // Anything but a timeout is either signaled or failed, where waiting again would not help
+    while gl_check!(gl::ClientWaitSync(fence, flags, FENCE_WAIT_NS)) == gl::TIMEOUT_EXPIRED {
+        flags = 0;
+    }
+    gl_check!(gl::DeleteSync(fence));
+}
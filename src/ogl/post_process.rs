@@ -0,0 +1,755 @@
+use gl::types::*;
+use serde::{Deserialize, Serialize};
+
+use crate::math::glm::{self, Mat4};
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::render_target::TextureTarget;
+use crate::ogl::renderer::Renderer;
+
+const SCENE_COLOR_UNIT: GLuint = 0;
+const SCENE_DEPTH_UNIT: GLuint = 1;
+const OCCLUSION_UNIT: GLuint = 2;
+const SUN_COLOR: [f32; 3] = [1.0_f32, 0.9_f32, 0.7_f32];
+// How far off screen, in screen widths, the sun can be before its shafts have faded out
+const SUN_FADE_DISTANCE: f32 = 0.5_f32;
+
+// One triangle covering the screen: (-1, -1), (3, -1), (-1, 3)
+const FULL_SCREEN_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+out vec2 o_uv;
+
+void main() {
+    vec2 position = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0f;
+    o_uv = position * 0.5f + 0.5f;
+    gl_Position = vec4(position, 0.0f, 1.0f);
+}
+"#;
+
+const OCCLUSION_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_uv;
+
+uniform sampler2D scene_depth;
+uniform vec2 sun_position;
+uniform float aspect_ratio;
+uniform vec3 sun_color;
+
+out vec4 frag_color;
+
+void main() {
+    // Only the sky, where nothing was drawn, lets the sun through
+    float sky = texture(scene_depth, o_uv).r > 0.99999f ? 1.0f : 0.0f;
+    float sun_distance = length((o_uv - sun_position) * vec2(aspect_ratio, 1.0f));
+    float disk = 1.0f - smoothstep(0.03f, 0.05f, sun_distance);
+    float halo = 0.35f * exp(-6.0f * sun_distance);
+    frag_color = vec4(sun_color * (disk + halo) * sky, 1.0f);
+}
+"#;
+
+const GOD_RAYS_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_uv;
+
+uniform sampler2D scene_color;
+uniform sampler2D occlusion;
+uniform vec2 sun_position;
+uniform float sun_visibility;
+uniform int samples;
+uniform float density;
+uniform float weight;
+uniform float decay;
+uniform float exposure;
+
+out vec4 frag_color;
+
+void main() {
+    // Marching towards the sun gathers the light of every unblocked pixel on the way, fading
+    // with each step, so light streams outwards from gaps between silhouettes
+    vec2 step = (o_uv - sun_position) * density / float(samples);
+    vec2 uv = o_uv;
+    float illumination = 1.0f;
+    vec3 shafts = vec3(0.0f);
+    for (int i = 0; i < samples; i++) {
+        uv -= step;
+        shafts += texture(occlusion, uv).rgb * illumination * weight;
+        illumination *= decay;
+    }
+    vec3 color = texture(scene_color, o_uv).rgb + shafts * exposure * sun_visibility;
+    frag_color = vec4(color, 1.0f);
+}
+"#;
+
+const MOTION_BLUR_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_uv;
+
+uniform sampler2D scene_color;
+uniform sampler2D scene_depth;
+uniform mat4 world_from_clip;
+uniform mat4 previous_projection_from_world;
+uniform int samples;
+uniform float shutter_scale;
+
+out vec4 frag_color;
+
+// Longest smear in screen widths, so sudden turns and jumps do not blur the whole screen
+const float MAX_BLUR = 0.05f;
+
+void main() {
+    // Where this pixel's surface was on screen last frame, from its depth and both frames'
+    // cameras; only the camera's motion blurs, objects moving on their own do not
+    float depth = texture(scene_depth, o_uv).r;
+    vec4 world_position = world_from_clip * vec4(vec3(o_uv, depth) * 2.0f - 1.0f, 1.0f);
+    world_position /= world_position.w;
+    vec4 previous_position = previous_projection_from_world * world_position;
+    vec2 previous_uv = previous_position.xy / previous_position.w * 0.5f + 0.5f;
+    vec2 velocity = (o_uv - previous_uv) * shutter_scale;
+    float speed = length(velocity);
+    if (speed > MAX_BLUR) {
+        velocity *= MAX_BLUR / speed;
+    }
+
+    // Averaged along the motion, centered on the pixel
+    vec3 color = vec3(0.0f);
+    for (int i = 0; i < samples; i++) {
+        float t = float(i) / float(max(samples - 1, 1)) - 0.5f;
+        color += texture(scene_color, o_uv + velocity * t).rgb;
+    }
+    frag_color = vec4(color / float(samples), 1.0f);
+}
+"#;
+
+// Operators are numbered as `ToneMapOperator::shader_index` does
+const OUTPUT_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_uv;
+
+uniform sampler2D scene_color;
+uniform float exposure;
+uniform float white_point;
+uniform int tone_map_operator;
+uniform int compare_operator;
+// Window x in pixels from which compare_operator is used instead, negative while not comparing
+uniform float split_x;
+
+out vec4 frag_color;
+
+// John Hable's filmic curve from Uncharted 2, and the linear color it maps to white
+const float SHOULDER_STRENGTH = 0.15f;
+const float LINEAR_STRENGTH = 0.5f;
+const float LINEAR_ANGLE = 0.1f;
+const float TOE_STRENGTH = 0.2f;
+const float TOE_NUMERATOR = 0.02f;
+const float TOE_DENOMINATOR = 0.3f;
+const float UNCHARTED2_WHITE = 11.2f;
+
+vec3 uncharted2_curve(vec3 x) {
+    vec3 numerator =
+        x * (SHOULDER_STRENGTH * x + LINEAR_ANGLE * LINEAR_STRENGTH) + TOE_STRENGTH * TOE_NUMERATOR;
+    vec3 denominator =
+        x * (SHOULDER_STRENGTH * x + LINEAR_STRENGTH) + TOE_STRENGTH * TOE_DENOMINATOR;
+    return numerator / denominator - TOE_NUMERATOR / TOE_DENOMINATOR;
+}
+
+vec3 tone_map(vec3 color, int operator_index) {
+    if (operator_index == 1) {
+        // Reinhard: never quite reaches white
+        return color / (1.0f + color);
+    } else if (operator_index == 2) {
+        // Reinhard extended: white_point and brighter map to white
+        return color * (1.0f + color / (white_point * white_point)) / (1.0f + color);
+    } else if (operator_index == 3) {
+        return uncharted2_curve(color * 2.0f) / uncharted2_curve(vec3(UNCHARTED2_WHITE));
+    } else if (operator_index == 4) {
+        // Krzysztof Narkowicz's fit of the ACES filmic curve
+        return clamp(
+            color * (2.51f * color + 0.03f) / (color * (2.43f * color + 0.59f) + 0.14f),
+            0.0f,
+            1.0f
+        );
+    }
+    // None: anything brighter than white is clipped
+    return clamp(color, 0.0f, 1.0f);
+}
+
+void main() {
+    vec3 color = max(texture(scene_color, o_uv).rgb * exposure, vec3(0.0f));
+    bool compared = split_x >= 0.0f && gl_FragCoord.x >= split_x;
+    vec3 mapped = tone_map(color, compared ? compare_operator : tone_map_operator);
+    if (split_x >= 0.0f && abs(gl_FragCoord.x - split_x) < 1.0f) {
+        mapped = vec3(1.0f);
+    }
+    frag_color = vec4(mapped, 1.0f);
+}
+"#;
+
+/// Full screen effects applied to the rendered scene before the HUD is drawn over it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PostProcessSettings {
+    pub god_rays: GodRaySettings,
+    pub motion_blur: MotionBlurSettings,
+    pub tone_mapping: ToneMappingSettings,
+}
+
+/// Light shafts streaming from the sun past whatever hides it, by radial blur in screen space.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GodRaySettings {
+    pub enabled: bool,
+    /// World space direction towards the sun
+    pub sun_direction: [f32; 3],
+    /// Steps marched from each pixel towards the sun
+    pub samples: u32,
+    /// Share of the way to the sun the steps cover
+    pub density: f32,
+    /// Brightness of each step
+    pub weight: f32,
+    /// Falloff from one step to the next
+    pub decay: f32,
+    /// Brightness of the summed shafts
+    pub exposure: f32,
+}
+
+/// Smears the scene along the camera's motion since the previous frame, as a camera shutter
+/// open for part of the frame would.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MotionBlurSettings {
+    pub enabled: bool,
+    /// Scene samples averaged along each pixel's motion
+    pub samples: u32,
+    /// Share of the motion between frames the shutter is open for; above 1 exaggerates it
+    pub shutter_scale: f32,
+}
+
+/// Brings scene colors brighter than white into the displayable range as the scene is drawn
+/// out, after scaling them by `exposure`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ToneMappingSettings {
+    pub operator: ToneMapOperator,
+    /// Scene colors are multiplied by this first
+    pub exposure: f32,
+    /// Color mapped to white by `reinhard_extended`, in multiples of white
+    pub white_point: f32,
+    /// Show `compare_operator` right of `split`, and `operator` left of it
+    pub compare: bool,
+    pub compare_operator: ToneMapOperator,
+    /// Share of the screen's width left of the split
+    pub split: f32,
+}
+
+/// A curve from scene colors to displayable ones.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapOperator {
+    /// Colors brighter than white are clipped
+    #[default]
+    None,
+    /// `c / (1 + c)`, which flattens highlights and never reaches white
+    Reinhard,
+    /// Reinhard with a white point that does reach white
+    ReinhardExtended,
+    /// John Hable's filmic curve from Uncharted 2, with a toe darkening the shadows
+    Uncharted2,
+    /// A fit of the ACES filmic curve, contrasty and saturated
+    Aces,
+}
+
+/// Runs full screen effects over the scene. While any is enabled the scene is drawn into an
+/// offscreen target from `begin` on, and `apply` draws the result into the window or whichever
+/// framebuffer is bound, ready for the HUD on top.
+pub struct PostProcess {
+    /// Created on first use and recreated when the output size changes
+    targets: Option<PostProcessTargets>,
+    /// Set when the targets could not be created, which turns post-processing off for good
+    failed: bool,
+    occlusion_program: ShaderProgram,
+    god_rays_program: ShaderProgram,
+    motion_blur_program: ShaderProgram,
+    output_program: ShaderProgram,
+    /// The camera's last frame, for motion blur
+    previous_projection_from_world: Option<Mat4>,
+    /// Empty, but drawing needs one bound
+    vertex_array_obj: GLuint,
+    occlusion_sun_position_uniform: Uniform,
+    occlusion_aspect_ratio_uniform: Uniform,
+    god_rays_sun_position_uniform: Uniform,
+    god_rays_sun_visibility_uniform: Uniform,
+    god_rays_samples_uniform: Uniform,
+    god_rays_density_uniform: Uniform,
+    god_rays_weight_uniform: Uniform,
+    god_rays_decay_uniform: Uniform,
+    god_rays_exposure_uniform: Uniform,
+    motion_blur_world_from_clip_uniform: Uniform,
+    motion_blur_previous_projection_from_world_uniform: Uniform,
+    motion_blur_samples_uniform: Uniform,
+    motion_blur_shutter_scale_uniform: Uniform,
+    output_exposure_uniform: Uniform,
+    output_white_point_uniform: Uniform,
+    output_operator_uniform: Uniform,
+    output_compare_operator_uniform: Uniform,
+    output_split_x_uniform: Uniform,
+    /// For recreating the targets
+    context: GlContext,
+}
+
+struct PostProcessTargets {
+    /// The scene's color and depth, then the effects' output in turn, switching between the two
+    color: [TextureTarget; 2],
+    /// Half resolution sun and sky, black where the scene blocks them
+    occlusion: TextureTarget,
+    width: i32,
+    height: i32,
+}
+
+impl PostProcess {
+    pub fn new(context: &GlContext) -> Result<PostProcess, String> {
+        unsafe {
+            let occlusion_program = ShaderProgram::with_shaders(
+                context,
+                FULL_SCREEN_VERTEX_SHADER_SOURCE,
+                OCCLUSION_FRAGMENT_SHADER_SOURCE,
+            )?;
+            let god_rays_program = ShaderProgram::with_shaders(
+                context,
+                FULL_SCREEN_VERTEX_SHADER_SOURCE,
+                GOD_RAYS_FRAGMENT_SHADER_SOURCE,
+            )?;
+            let motion_blur_program = ShaderProgram::with_shaders(
+                context,
+                FULL_SCREEN_VERTEX_SHADER_SOURCE,
+                MOTION_BLUR_FRAGMENT_SHADER_SOURCE,
+            )?;
+            let output_program = ShaderProgram::with_shaders(
+                context,
+                FULL_SCREEN_VERTEX_SHADER_SOURCE,
+                OUTPUT_FRAGMENT_SHADER_SOURCE,
+            )?;
+            occlusion_program.set_label("post process occlusion");
+            god_rays_program.set_label("post process god rays");
+            motion_blur_program.set_label("post process motion blur");
+            output_program.set_label("post process output");
+            let mut vertex_array_obj = 0;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "post process");
+
+            occlusion_program.use_program();
+            occlusion_program
+                .uniform("scene_depth")
+                .set_int(SCENE_DEPTH_UNIT as i32);
+            occlusion_program.uniform("sun_color").set_vec3f(SUN_COLOR);
+            god_rays_program.use_program();
+            god_rays_program
+                .uniform("scene_color")
+                .set_int(SCENE_COLOR_UNIT as i32);
+            god_rays_program
+                .uniform("occlusion")
+                .set_int(OCCLUSION_UNIT as i32);
+            motion_blur_program.use_program();
+            motion_blur_program
+                .uniform("scene_color")
+                .set_int(SCENE_COLOR_UNIT as i32);
+            motion_blur_program
+                .uniform("scene_depth")
+                .set_int(SCENE_DEPTH_UNIT as i32);
+            output_program.use_program();
+            output_program
+                .uniform("scene_color")
+                .set_int(SCENE_COLOR_UNIT as i32);
+
+            Ok(PostProcess {
+                context: *context,
+                targets: None,
+                failed: false,
+                occlusion_sun_position_uniform: occlusion_program.uniform("sun_position"),
+                occlusion_aspect_ratio_uniform: occlusion_program.uniform("aspect_ratio"),
+                god_rays_sun_position_uniform: god_rays_program.uniform("sun_position"),
+                god_rays_sun_visibility_uniform: god_rays_program.uniform("sun_visibility"),
+                god_rays_samples_uniform: god_rays_program.uniform("samples"),
+                god_rays_density_uniform: god_rays_program.uniform("density"),
+                god_rays_weight_uniform: god_rays_program.uniform("weight"),
+                god_rays_decay_uniform: god_rays_program.uniform("decay"),
+                god_rays_exposure_uniform: god_rays_program.uniform("exposure"),
+                motion_blur_world_from_clip_uniform: motion_blur_program.uniform("world_from_clip"),
+                motion_blur_previous_projection_from_world_uniform: motion_blur_program
+                    .uniform("previous_projection_from_world"),
+                motion_blur_samples_uniform: motion_blur_program.uniform("samples"),
+                motion_blur_shutter_scale_uniform: motion_blur_program.uniform("shutter_scale"),
+                output_exposure_uniform: output_program.uniform("exposure"),
+                output_white_point_uniform: output_program.uniform("white_point"),
+                output_operator_uniform: output_program.uniform("tone_map_operator"),
+                output_compare_operator_uniform: output_program.uniform("compare_operator"),
+                output_split_x_uniform: output_program.uniform("split_x"),
+                occlusion_program,
+                god_rays_program,
+                motion_blur_program,
+                output_program,
+                previous_projection_from_world: None,
+                vertex_array_obj,
+            })
+        }
+    }
+
+    /// Whether any effect is enabled, so the scene needs drawing offscreen first.
+    pub fn is_active(&self, settings: &PostProcessSettings) -> bool {
+        !self.failed
+            && (settings.god_rays.enabled
+                || settings.motion_blur.enabled
+                || settings.tone_mapping.is_active())
+    }
+
+    /// Binds the offscreen target the scene is drawn into, sized to match the final output.
+    /// Returns false, reporting why the first time, when the target cannot be created.
+    pub fn begin(&mut self, (width, height): (i32, i32)) -> bool {
+        let resized = match &self.targets {
+            Some(targets) => targets.width != width || targets.height != height,
+            None => true,
+        };
+        if resized {
+            // The old targets go first, so both sizes are never held at once
+            self.targets = None;
+            match unsafe { PostProcessTargets::new(&self.context, width, height) } {
+                Ok(targets) => self.targets = Some(targets),
+                Err(e) => {
+                    eprintln!("Post-processing disabled: {}", e);
+                    self.failed = true;
+                    return false;
+                }
+            }
+        }
+        self.scene_target().bind(&self.context);
+        true
+    }
+
+    /// The target `begin` binds, to rebind after drawing into others mid-frame.
+    pub fn scene_target(&self) -> &TextureTarget {
+        &self
+            .targets
+            .as_ref()
+            .expect("Post-processing has not begun")
+            .color[0]
+    }
+
+    /// Runs the enabled effects over the scene drawn since `begin`, seen from `camera`, and
+    /// draws the result into the framebuffer bound when called.
+    pub fn apply(
+        &mut self,
+        settings: &PostProcessSettings,
+        camera: &Camera,
+        renderer: &mut Renderer,
+    ) {
+        let projection_from_world = renderer.projection_from_view() * camera.view_matrix();
+        let previous_projection_from_world = self
+            .previous_projection_from_world
+            .replace(projection_from_world)
+            .unwrap_or(projection_from_world);
+        let targets = match &self.targets {
+            Some(targets) => targets,
+            None => return,
+        };
+        let (mut output_framebuffer, mut output_viewport) = (0, [0; 4]);
+        unsafe {
+            gl_check!(gl::GetIntegerv(
+                gl::DRAW_FRAMEBUFFER_BINDING,
+                &mut output_framebuffer
+            ));
+            gl_check!(gl::GetIntegerv(gl::VIEWPORT, output_viewport.as_mut_ptr()));
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0 + SCENE_DEPTH_UNIT));
+            gl_check!(gl::BindTexture(
+                gl::TEXTURE_2D,
+                targets.color[0].depth_texture
+            ));
+        }
+        let mut source = 0;
+        // First, while the scene's color is still in the target its depth is attached to, as
+        // sampling depth while drawing into that target would be a feedback loop
+        if settings.motion_blur.enabled {
+            self.motion_blur(
+                &settings.motion_blur,
+                targets,
+                source,
+                &projection_from_world,
+                &previous_projection_from_world,
+            );
+            renderer.stats.record_draw(1);
+            source = 1 - source;
+        }
+        if settings.god_rays.enabled {
+            if let Some((sun_position, sun_visibility)) =
+                sun_screen_position(&settings.god_rays, camera, renderer)
+            {
+                self.god_rays(
+                    &settings.god_rays,
+                    targets,
+                    source,
+                    sun_position,
+                    sun_visibility,
+                );
+                renderer.stats.record_draw(2);
+                source = 1 - source;
+            }
+        }
+        self.output_program.use_program();
+        let tone_mapping = &settings.tone_mapping;
+        self.output_exposure_uniform
+            .set_float(tone_mapping.exposure.max(0.0_f32));
+        self.output_white_point_uniform
+            .set_float(tone_mapping.white_point.max(1.0_f32));
+        self.output_operator_uniform
+            .set_int(tone_mapping.operator.shader_index());
+        self.output_compare_operator_uniform
+            .set_int(tone_mapping.compare_operator.shader_index());
+        let [x, _, width, _] = output_viewport;
+        self.output_split_x_uniform
+            .set_float(if tone_mapping.compare {
+                x as f32 + tone_mapping.split.clamp(0.0_f32, 1.0_f32) * width as f32
+            } else {
+                -1.0_f32
+            });
+        unsafe {
+            gl_check!(gl::BindFramebuffer(
+                gl::FRAMEBUFFER,
+                output_framebuffer as GLuint
+            ));
+            let [x, y, width, height] = output_viewport;
+            gl_check!(gl::Viewport(x, y, width, height));
+            bind_color(&targets.color[source]);
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+        renderer.stats.record_draw(1);
+    }
+
+    /// Blurs `targets.color[source]` along the camera's motion into the other color target.
+    fn motion_blur(
+        &self,
+        settings: &MotionBlurSettings,
+        targets: &PostProcessTargets,
+        source: usize,
+        projection_from_world: &Mat4,
+        previous_projection_from_world: &Mat4,
+    ) {
+        targets.color[1 - source].bind(&self.context);
+        self.motion_blur_program.use_program();
+        self.motion_blur_world_from_clip_uniform
+            .set_mat4f(&glm::inverse(projection_from_world));
+        self.motion_blur_previous_projection_from_world_uniform
+            .set_mat4f(previous_projection_from_world);
+        self.motion_blur_samples_uniform
+            .set_int(settings.samples.max(1) as i32);
+        self.motion_blur_shutter_scale_uniform
+            .set_float(settings.shutter_scale);
+        unsafe {
+            bind_color(&targets.color[source]);
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+        }
+    }
+
+    /// Renders the sun's occlusion at half resolution, then adds the shafts blurred out of it to
+    /// `targets.color[source]` in the other color target.
+    fn god_rays(
+        &self,
+        settings: &GodRaySettings,
+        targets: &PostProcessTargets,
+        source: usize,
+        sun_position: [f32; 2],
+        sun_visibility: f32,
+    ) {
+        targets.occlusion.bind(&self.context);
+        self.occlusion_program.use_program();
+        self.occlusion_sun_position_uniform.set_vec2f(sun_position);
+        self.occlusion_aspect_ratio_uniform
+            .set_float(targets.width as f32 / targets.height as f32);
+        unsafe {
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+        }
+
+        targets.color[1 - source].bind(&self.context);
+        self.god_rays_program.use_program();
+        self.god_rays_sun_position_uniform.set_vec2f(sun_position);
+        self.god_rays_sun_visibility_uniform
+            .set_float(sun_visibility);
+        self.god_rays_samples_uniform
+            .set_int(settings.samples.max(1) as i32);
+        self.god_rays_density_uniform.set_float(settings.density);
+        self.god_rays_weight_uniform.set_float(settings.weight);
+        self.god_rays_decay_uniform.set_float(settings.decay);
+        self.god_rays_exposure_uniform.set_float(settings.exposure);
+        unsafe {
+            bind_color(&targets.color[source]);
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0 + OCCLUSION_UNIT));
+            gl_check!(gl::BindTexture(
+                gl::TEXTURE_2D,
+                targets.occlusion.color_texture
+            ));
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+        }
+    }
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        unsafe { live_objects::delete(GlObject::VertexArray, self.vertex_array_obj) };
+    }
+}
+
+impl Default for GodRaySettings {
+    fn default() -> GodRaySettings {
+        GodRaySettings {
+            enabled: false,
+            // Low in the sky ahead of the default cameras
+            sun_direction: [0.3_f32, 0.35_f32, -1.0_f32],
+            samples: 64,
+            density: 0.9_f32,
+            weight: 0.5_f32,
+            decay: 0.96_f32,
+            exposure: 0.25_f32,
+        }
+    }
+}
+
+impl Default for ToneMappingSettings {
+    fn default() -> ToneMappingSettings {
+        ToneMappingSettings {
+            operator: ToneMapOperator::None,
+            exposure: 1.0_f32,
+            white_point: 4.0_f32,
+            compare: false,
+            compare_operator: ToneMapOperator::Aces,
+            split: 0.5_f32,
+        }
+    }
+}
+
+impl ToneMappingSettings {
+    /// Whether drawing the scene out changes it at all, rather than only clipping it.
+    pub fn is_active(&self) -> bool {
+        self.operator != ToneMapOperator::None || self.exposure != 1.0_f32 || self.compare
+    }
+}
+
+impl ToneMapOperator {
+    pub const ALL: [ToneMapOperator; 5] = [
+        ToneMapOperator::None,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::ReinhardExtended,
+        ToneMapOperator::Uncharted2,
+        ToneMapOperator::Aces,
+    ];
+
+    /// Parses what `name` returns.
+    pub fn from_name(name: &str) -> Option<ToneMapOperator> {
+        ToneMapOperator::ALL
+            .iter()
+            .copied()
+            .find(|operator| operator.name() == name)
+    }
+
+    /// The name settings and the console use, e.g. `reinhard_extended`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapOperator::None => "none",
+            ToneMapOperator::Reinhard => "reinhard",
+            ToneMapOperator::ReinhardExtended => "reinhard_extended",
+            ToneMapOperator::Uncharted2 => "uncharted2",
+            ToneMapOperator::Aces => "aces",
+        }
+    }
+
+    fn shader_index(self) -> i32 {
+        match self {
+            ToneMapOperator::None => 0,
+            ToneMapOperator::Reinhard => 1,
+            ToneMapOperator::ReinhardExtended => 2,
+            ToneMapOperator::Uncharted2 => 3,
+            ToneMapOperator::Aces => 4,
+        }
+    }
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> MotionBlurSettings {
+        MotionBlurSettings {
+            enabled: false,
+            samples: 8,
+            // A 180 degree shutter, as in film
+            shutter_scale: 0.5_f32,
+        }
+    }
+}
+
+impl PostProcessTargets {
+    unsafe fn new(
+        context: &GlContext,
+        width: i32,
+        height: i32,
+    ) -> Result<PostProcessTargets, String> {
+        // Without float targets colors are clipped at white before the effects see them
+        let format = if capabilities::float_render_targets() {
+            gl::RGBA16F
+        } else {
+            gl::RGBA8
+        };
+        let targets = PostProcessTargets {
+            color: [
+                TextureTarget::with_format(context, width, height, format)?,
+                TextureTarget::with_format(context, width, height, format)?,
+            ],
+            occlusion: TextureTarget::new(context, (width / 2).max(1), (height / 2).max(1))?,
+            width,
+            height,
+        };
+        targets.color[0].set_label(context, "post process color 0");
+        targets.color[1].set_label(context, "post process color 1");
+        targets
+            .occlusion
+            .set_label(context, "post process occlusion");
+        Ok(targets)
+    }
+}
+
+unsafe fn bind_color(target: &TextureTarget) {
+    gl_check!(gl::ActiveTexture(gl::TEXTURE0 + SCENE_COLOR_UNIT));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, target.color_texture));
+}
+
+/// Where the sun is on screen, in 0..1 texture coordinates, and how strongly its shafts show:
+/// fully while it is on screen, fading out as it moves off. None while it is behind the camera.
+fn sun_screen_position(
+    settings: &GodRaySettings,
+    camera: &Camera,
+    renderer: &Renderer,
+) -> Option<([f32; 2], f32)> {
+    let [x, y, z] = settings.sun_direction;
+    // A direction rather than a point, so the sun stays put as the camera moves
+    let clip_position =
+        renderer.projection_from_view() * camera.view_matrix() * glm::vec4(x, y, z, 0.0_f32);
+    if clip_position.w <= 0.0_f32 {
+        return None;
+    }
+    let position = [
+        clip_position.x / clip_position.w * 0.5_f32 + 0.5_f32,
+        clip_position.y / clip_position.w * 0.5_f32 + 0.5_f32,
+    ];
+    let outside = position
+        .iter()
+        .map(|coordinate| (-coordinate).max(coordinate - 1.0_f32).max(0.0_f32))
+        .fold(0.0_f32, f32::max);
+    let visibility = 1.0_f32 - (outside / SUN_FADE_DISTANCE).min(1.0_f32);
+    if visibility <= 0.0_f32 {
+        return None;
+    }
+    Some((position, visibility))
+}
@@ -0,0 +1,314 @@
+use gl::types::*;
+
+use crate::math::glm::Vec3;
+use crate::ogl::context::GlContext;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::render_target::RenderTarget;
+use crate::scene::ReflectionProbe;
+use std::ffi::c_void;
+use std::{mem, ptr};
+
+/// Probes beyond this many are left out, with a message.
+pub const MAX_REFLECTION_PROBES: usize = 16;
+/// Pixels across each face a probe captures.
+pub const REFLECTION_CAPTURE_SIZE: i32 = 128;
+// Texture units of the captured faces and the probe boxes, after light_probes'
+const FACES_UNIT: GLuint = 12;
+const PROBE_DATA_UNIT: GLuint = 13;
+// Texels per probe in the data texture: position and blend distance, box min, box max
+const PROBE_TEXELS: usize = 3;
+
+/// Reflection code for fragment shaders, pasted in after their declarations.
+/// `probe_reflection` returns what the probes around a world position see along a direction,
+/// blended by how close to each probe's box the position is, and in alpha how fully the
+/// probes cover it.
+pub const REFLECTION_PROBES_SOURCE: &str = r#"
+// Six faces per probe, in the order and orientation of light_probes::cube_face_cameras
+uniform sampler2DArray reflection_probe_faces;
+// Per probe a row of texels: capture position and blend distance, box min, box max
+uniform sampler2D reflection_probe_data;
+uniform int reflection_probe_count;
+uniform vec3 camera_position;
+
+const vec3 FACE_FRONTS[6] = vec3[6](vec3(1.0f, 0.0f, 0.0f), vec3(-1.0f, 0.0f, 0.0f),
+                                    vec3(0.0f, 0.0f, 1.0f), vec3(0.0f, 0.0f, -1.0f),
+                                    vec3(0.0f, 1.0f, 0.0f), vec3(0.0f, -1.0f, 0.0f));
+const vec3 FACE_RIGHTS[6] = vec3[6](vec3(0.0f, 0.0f, 1.0f), vec3(0.0f, 0.0f, -1.0f),
+                                    vec3(-1.0f, 0.0f, 0.0f), vec3(1.0f, 0.0f, 0.0f),
+                                    vec3(-1.0f, 0.0f, 0.0f), vec3(-1.0f, 0.0f, 0.0f));
+const vec3 FACE_UPS[6] = vec3[6](vec3(0.0f, 1.0f, 0.0f), vec3(0.0f, 1.0f, 0.0f),
+                                 vec3(0.0f, 1.0f, 0.0f), vec3(0.0f, 1.0f, 0.0f),
+                                 vec3(0.0f, 0.0f, -1.0f), vec3(0.0f, 0.0f, 1.0f));
+
+vec3 sample_reflection_probe(int probe_i, vec3 direction) {
+    vec3 a = abs(direction);
+    int face = a.x >= a.y && a.x >= a.z ? (direction.x > 0.0f ? 0 : 1)
+             : a.z >= a.y ? (direction.z > 0.0f ? 2 : 3)
+             : (direction.y > 0.0f ? 4 : 5);
+    vec2 uv = vec2(dot(direction, FACE_RIGHTS[face]), dot(direction, FACE_UPS[face]))
+        / dot(direction, FACE_FRONTS[face]);
+    return textureLod(reflection_probe_faces, vec3(uv * 0.5f + 0.5f, float(probe_i * 6 + face)), 0.0f).rgb;
+}
+
+vec4 probe_reflection(vec3 world_position, vec3 direction) {
+    vec3 reflection = vec3(0.0f);
+    float total_weight = 0.0f;
+    for (int probe_i = 0; probe_i < reflection_probe_count; ++probe_i) {
+        vec4 position_blend = texelFetch(reflection_probe_data, ivec2(0, probe_i), 0);
+        vec3 box_min = texelFetch(reflection_probe_data, ivec2(1, probe_i), 0).xyz;
+        vec3 box_max = texelFetch(reflection_probe_data, ivec2(2, probe_i), 0).xyz;
+        // Full weight inside the box, fading out over the blend distance beyond it
+        vec3 outside = max(max(box_min - world_position, world_position - box_max), 0.0f);
+        float weight = clamp(1.0f - length(outside) / position_blend.w, 0.0f, 1.0f);
+        if (weight > 0.0f) {
+            // Box projection: where the ray leaves the box, seen from the capture position
+            vec3 exits = max((box_max - world_position) / direction, (box_min - world_position) / direction);
+            float distance = min(min(exits.x, exits.y), exits.z);
+            vec3 corrected = world_position + direction * distance - position_blend.xyz;
+            reflection += weight * sample_reflection_probe(probe_i, corrected);
+            total_weight += weight;
+        }
+    }
+    if (total_weight == 0.0f) {
+        return vec4(0.0f);
+    }
+    return vec4(reflection / total_weight, min(total_weight, 1.0f));
+}
+"#;
+
+/// The uniforms of a program using `REFLECTION_PROBES_SOURCE`, looked up once.
+pub struct ReflectionProbeUniforms {
+    reflection_probe_count: Uniform,
+    camera_position: Uniform,
+}
+
+/// Baked views around a scene's reflection probes, six faces each in a texture array, which
+/// glossy surfaces inside the probes' boxes reflect. Faces are sampled by hand rather than
+/// through cubemaps so any probe can be picked in the shader, down to OpenGL 3.3 and ES 3.0.
+pub struct ReflectionProbes {
+    faces_texture: GLuint,
+    data_texture: GLuint,
+    /// Probes reflected, none while baking
+    probe_count: usize,
+    baked_count: usize,
+    /// Layers and size the faces texture has room for
+    allocated: (usize, i32),
+    context: GlContext,
+}
+
+impl ReflectionProbeUniforms {
+    /// Looks up the uniforms of `program` and points its samplers at the probe textures.
+    pub fn new(program: &ShaderProgram) -> ReflectionProbeUniforms {
+        program.use_program();
+        program
+            .uniform("reflection_probe_faces")
+            .set_int(FACES_UNIT as i32);
+        program
+            .uniform("reflection_probe_data")
+            .set_int(PROBE_DATA_UNIT as i32);
+        ReflectionProbeUniforms {
+            reflection_probe_count: program.uniform("reflection_probe_count"),
+            camera_position: program.uniform("camera_position"),
+        }
+    }
+}
+
+impl ReflectionProbes {
+    /// No probes until baked, so glossy surfaces reflect nothing.
+    pub fn new(context: &GlContext) -> ReflectionProbes {
+        let mut textures = [0; 2];
+        unsafe {
+            gl_check!(gl::GenTextures(2, textures.as_mut_ptr()));
+            live_objects::created_all(GlObject::Texture, &textures);
+            for (texture, target, filter) in [
+                (textures[0], gl::TEXTURE_2D_ARRAY, gl::LINEAR),
+                (textures[1], gl::TEXTURE_2D, gl::NEAREST),
+            ]
+            .iter()
+            {
+                gl_check!(gl::BindTexture(*target, *texture));
+                for parameter in [gl::TEXTURE_MIN_FILTER, gl::TEXTURE_MAG_FILTER].iter() {
+                    gl_check!(gl::TexParameteri(*target, *parameter, *filter as GLint));
+                }
+                // Keeps samples near a face's edge from wrapping to the opposite edge
+                for parameter in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T].iter() {
+                    gl_check!(gl::TexParameteri(
+                        *target,
+                        *parameter,
+                        gl::CLAMP_TO_EDGE as GLint
+                    ));
+                }
+                gl_check!(gl::BindTexture(*target, 0));
+            }
+        }
+        ReflectionProbes {
+            faces_texture: textures[0],
+            data_texture: textures[1],
+            probe_count: 0,
+            baked_count: 0,
+            allocated: (0, 0),
+            context: *context,
+        }
+    }
+
+    pub fn probe_count(&self) -> usize {
+        self.probe_count
+    }
+
+    /// Makes room for the faces of `probes`, which `capture_face` then fills, and uploads their
+    /// boxes. Until `end_bake` nothing is reflected, so the captures do not see stale faces.
+    /// Returns the probes that fit.
+    pub fn begin_bake<'a>(
+        &mut self,
+        probes: &'a [ReflectionProbe],
+        size: i32,
+    ) -> &'a [ReflectionProbe] {
+        if probes.len() > MAX_REFLECTION_PROBES {
+            eprintln!(
+                "Reflecting the first {} of {} reflection probes",
+                MAX_REFLECTION_PROBES,
+                probes.len()
+            );
+        }
+        let probes = &probes[..probes.len().min(MAX_REFLECTION_PROBES)];
+        self.probe_count = 0;
+        self.baked_count = probes.len();
+        let layers = probes.len().max(1) * 6;
+        unsafe {
+            if (layers, size) != self.allocated {
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.faces_texture));
+                gl_check!(gl::TexImage3D(
+                    gl::TEXTURE_2D_ARRAY,
+                    0,
+                    gl::RGBA8 as GLint,
+                    size,
+                    size,
+                    layers as GLsizei,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    ptr::null(),
+                ));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0));
+                gpu_memory::track(
+                    GpuResource::Texture,
+                    self.faces_texture,
+                    gpu_memory::texture_bytes(size as u32, size as u32, layers as u32, 4, false),
+                );
+                self.allocated = (layers, size);
+            }
+        }
+        let mut data = Vec::with_capacity(probes.len().max(1) * PROBE_TEXELS * 4);
+        for probe in probes.iter() {
+            data.extend_from_slice(&probe.position);
+            data.push(probe.blend_distance.max(0.001_f32));
+            data.extend_from_slice(&probe.box_min);
+            data.push(0.0_f32);
+            data.extend_from_slice(&probe.box_max);
+            data.push(0.0_f32);
+        }
+        data.resize(probes.len().max(1) * PROBE_TEXELS * 4, 0.0_f32);
+        unsafe {
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.data_texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA32F as GLint,
+                PROBE_TEXELS as GLsizei,
+                probes.len().max(1) as GLsizei,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                data.as_ptr() as *const c_void,
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+        }
+        gpu_memory::track(
+            GpuResource::Texture,
+            self.data_texture,
+            mem::size_of_val(data.as_slice()),
+        );
+        probes
+    }
+
+    /// Copies what was drawn into `target`, the size given to `begin_bake`, to face `face` of
+    /// probe `probe_i`, in the order of `light_probes::cube_face_cameras`.
+    pub fn capture_face(
+        &self,
+        context: &GlContext,
+        probe_i: usize,
+        face: usize,
+        target: &RenderTarget,
+    ) {
+        unsafe {
+            // Copies from the framebuffer bound for reading
+            target.bind(context);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.faces_texture));
+            gl_check!(gl::CopyTexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                0,
+                0,
+                (probe_i * 6 + face) as GLint,
+                0,
+                0,
+                target.width,
+                target.height
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0));
+        }
+        target.unbind(context);
+    }
+
+    /// Starts reflecting the probes captured since `begin_bake`.
+    pub fn end_bake(&mut self) {
+        self.probe_count = self.baked_count;
+    }
+
+    /// Stops reflecting any probes, keeping the textures for the next bake.
+    pub fn clear(&mut self) {
+        self.probe_count = 0;
+        self.baked_count = 0;
+    }
+
+    /// Binds the probe textures and sets the probe count and `camera_position`, which glossy
+    /// surfaces reflect the view from, on `program`, whose `uniforms` these are.
+    pub fn bind(
+        &self,
+        program: &ShaderProgram,
+        uniforms: &ReflectionProbeUniforms,
+        camera_position: &Vec3,
+    ) {
+        program.use_program();
+        uniforms
+            .reflection_probe_count
+            .set_int(self.probe_count as i32);
+        uniforms.camera_position.set_vec3f([
+            camera_position.x,
+            camera_position.y,
+            camera_position.z,
+        ]);
+        unsafe {
+            for (unit, target, texture) in [
+                (FACES_UNIT, gl::TEXTURE_2D_ARRAY, self.faces_texture),
+                (PROBE_DATA_UNIT, gl::TEXTURE_2D, self.data_texture),
+            ]
+            .iter()
+            {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + unit));
+                gl_check!(gl::BindTexture(*target, *texture));
+            }
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        }
+    }
+}
+
+impl Drop for ReflectionProbes {
+    fn drop(&mut self) {
+        for texture in [self.faces_texture, self.data_texture].iter() {
+            live_objects::release(&self.context, GlObject::Texture, *texture);
+        }
+    }
+}
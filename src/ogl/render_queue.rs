@@ -0,0 +1,92 @@
+use crate::math::glm::Mat4;
+use crate::ogl::graphics::{Material, Mesh};
+use std::rc::Rc;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderPass {
+    Opaque = 0,
+    Transparent = 1,
+    Overlay = 2,
+}
+
+/// Packed draw ordering key, most significant field first:
+/// | pass (4) | shader (16) | material (16) | depth (28) |
+/// Transparent draws need strict back to front ordering, so their depth is inverted and moved
+/// directly below the pass: | pass (4) | depth (28) | shader (16) | material (16) |
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey(pub u64);
+
+pub struct DrawCommand {
+    pub key: SortKey,
+    pub mesh: Rc<Mesh>,
+    pub material: Rc<Material>,
+    pub world_from_object: Mat4,
+}
+
+pub struct RenderQueue {
+    commands: Vec<DrawCommand>,
+}
+
+impl SortKey {
+    const DEPTH_BITS: u32 = 28;
+    const MATERIAL_BITS: u32 = 16;
+    const SHADER_BITS: u32 = 16;
+
+    /// `depth` is the normalized [0, 1] depth of the object. Transparent draws are ordered back to
+    /// front, everything else front to back.
+    pub fn new(pass: RenderPass, shader_id: u32, material_id: u32, depth: f32) -> SortKey {
+        let depth_max = (1_u64 << Self::DEPTH_BITS) - 1;
+        let mut depth_bits = (depth.clamp(0.0_f32, 1.0_f32) as f64 * depth_max as f64) as u64;
+        if pass == RenderPass::Transparent {
+            depth_bits = depth_max - depth_bits;
+        }
+        let material_bits = material_id as u64 & ((1_u64 << Self::MATERIAL_BITS) - 1);
+        let shader_bits = shader_id as u64 & ((1_u64 << Self::SHADER_BITS) - 1);
+
+        let pass_bits =
+            (pass as u64) << (Self::SHADER_BITS + Self::MATERIAL_BITS + Self::DEPTH_BITS);
+        if pass == RenderPass::Transparent {
+            SortKey(
+                pass_bits
+                    | depth_bits << (Self::SHADER_BITS + Self::MATERIAL_BITS)
+                    | shader_bits << Self::MATERIAL_BITS
+                    | material_bits,
+            )
+        } else {
+            SortKey(
+                pass_bits
+                    | shader_bits << (Self::MATERIAL_BITS + Self::DEPTH_BITS)
+                    | material_bits << Self::DEPTH_BITS
+                    | depth_bits,
+            )
+        }
+    }
+}
+
+impl RenderQueue {
+    pub fn new() -> RenderQueue {
+        RenderQueue { commands: vec![] }
+    }
+
+    pub fn push(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Sorts the queued commands by key and hands them out, leaving the queue empty for the next
+    /// frame while keeping its allocation.
+    pub fn drain_sorted(&mut self) -> std::vec::Drain<'_, DrawCommand> {
+        self.commands.sort_by_key(|command| command.key);
+        self.commands.drain(..)
+    }
+}
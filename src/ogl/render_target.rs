@@ -0,0 +1,387 @@
+use gl::types::*;
+
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+use std::ptr;
+
+/// Offscreen framebuffer with an RGBA8 color and a depth/stencil renderbuffer. While bound, the
+/// renderer draws into it exactly as it would into the window.
+pub struct RenderTarget {
+    framebuffer: GLuint,
+    color_renderbuffer: GLuint,
+    depth_stencil_renderbuffer: GLuint,
+    pub width: i32,
+    pub height: i32,
+    context: GlContext,
+}
+
+impl RenderTarget {
+    pub fn new(context: &GlContext, width: i32, height: i32) -> Result<RenderTarget, String> {
+        unsafe {
+            capabilities::check_texture_size(width, height)?;
+            let mut framebuffer = 0_u32;
+            let mut renderbuffers = [0_u32; 2];
+            gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+            gl_check!(gl::GenRenderbuffers(2, renderbuffers.as_mut_ptr()));
+            live_objects::created(GlObject::Framebuffer, framebuffer);
+            live_objects::created_all(GlObject::Renderbuffer, &renderbuffers);
+            let [color_renderbuffer, depth_stencil_renderbuffer] = renderbuffers;
+
+            gl_check!(gl::BindRenderbuffer(gl::RENDERBUFFER, color_renderbuffer));
+            gl_check!(gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::RGBA8,
+                width,
+                height
+            ));
+            gl_check!(gl::BindRenderbuffer(
+                gl::RENDERBUFFER,
+                depth_stencil_renderbuffer
+            ));
+            gl_check!(gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width,
+                height
+            ));
+            gl_check!(gl::BindRenderbuffer(gl::RENDERBUFFER, 0));
+            // RGBA8 and DEPTH24_STENCIL8 both take four bytes a pixel
+            for renderbuffer in renderbuffers.iter() {
+                gpu_memory::track(
+                    GpuResource::Renderbuffer,
+                    *renderbuffer,
+                    gpu_memory::texture_bytes(width as u32, height as u32, 1, 4, false),
+                );
+            }
+
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer));
+            gl_check!(gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                color_renderbuffer
+            ));
+            gl_check!(gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_stencil_renderbuffer
+            ));
+            let status = gl_check!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER));
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+            let target = RenderTarget {
+                framebuffer,
+                color_renderbuffer,
+                depth_stencil_renderbuffer,
+                width,
+                height,
+                context: *context,
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Render target incomplete (0x{:X})", status));
+            }
+            Ok(target)
+        }
+    }
+
+    pub fn bind(&self, _context: &GlContext) {
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer));
+            gl_check!(gl::Viewport(0, 0, self.width, self.height));
+        }
+    }
+
+    /// Names the framebuffer and its attachments for debuggers, see `debug_label::set`.
+    pub fn set_label(&self, _context: &GlContext, label: &str) {
+        unsafe {
+            debug_label::set(gl::FRAMEBUFFER, self.framebuffer, label);
+            debug_label::set(
+                gl::RENDERBUFFER,
+                self.color_renderbuffer,
+                &format!("{} color", label),
+            );
+            debug_label::set(
+                gl::RENDERBUFFER,
+                self.depth_stencil_renderbuffer,
+                &format!("{} depth/stencil", label),
+            );
+        }
+    }
+
+    pub fn unbind(&self, _context: &GlContext) {
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+        }
+    }
+
+    /// Reads the color attachment back as RGBA8 rows, bottom row first. Stalls until rendering
+    /// into the target has finished.
+    pub fn read_pixels(&self, _context: &GlContext) -> Vec<u8> {
+        let mut pixels = vec![0_u8; (self.width * self.height * 4) as usize];
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer));
+            gl_check!(gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void
+            ));
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0));
+        }
+        pixels
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Framebuffer, self.framebuffer);
+        for renderbuffer in [self.color_renderbuffer, self.depth_stencil_renderbuffer].iter() {
+            live_objects::release(&self.context, GlObject::Renderbuffer, *renderbuffer);
+        }
+    }
+}
+
+/// Offscreen framebuffer rendering into a color texture and a depth/stencil texture, so later
+/// passes can sample what was drawn and how far away it was.
+pub struct TextureTarget {
+    framebuffer: GLuint,
+    pub color_texture: GLuint,
+    /// Depth in the red channel when sampled, from 0 at the near plane to 1 at the far plane
+    pub depth_texture: GLuint,
+    pub width: i32,
+    pub height: i32,
+    context: GlContext,
+}
+
+impl TextureTarget {
+    pub fn new(context: &GlContext, width: i32, height: i32) -> Result<TextureTarget, String> {
+        TextureTarget::with_format(context, width, height, gl::RGBA8)
+    }
+
+    /// Like `new`, with an `RGBA8` or `RGBA16F` color texture. Half floats keep colors brighter
+    /// than white for post-processing, where `capabilities::float_render_targets` allows.
+    pub fn with_format(
+        context: &GlContext,
+        width: i32,
+        height: i32,
+        internal_format: GLenum,
+    ) -> Result<TextureTarget, String> {
+        unsafe {
+            let (pixel_type, bytes_per_pixel) = match internal_format {
+                gl::RGBA8 => (gl::UNSIGNED_BYTE, 4),
+                gl::RGBA16F => (gl::HALF_FLOAT, 8),
+                _ => {
+                    return Err(format!(
+                        "Unsupported texture target format 0x{:X}",
+                        internal_format
+                    ))
+                }
+            };
+            capabilities::check_texture_size(width, height)?;
+            let mut framebuffer = 0_u32;
+            let mut textures = [0_u32; 2];
+            gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+            gl_check!(gl::GenTextures(2, textures.as_mut_ptr()));
+            live_objects::created(GlObject::Framebuffer, framebuffer);
+            live_objects::created_all(GlObject::Texture, &textures);
+            let [color_texture, depth_texture] = textures;
+
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, color_texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as GLint,
+                width,
+                height,
+                0,
+                gl::RGBA,
+                pixel_type,
+                ptr::null(),
+            ));
+            // Clamped, as passes sample at distorted coordinates that can stray past the edges
+            set_sampling(gl::LINEAR);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, depth_texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH24_STENCIL8 as GLint,
+                width,
+                height,
+                0,
+                gl::DEPTH_STENCIL,
+                gl::UNSIGNED_INT_24_8,
+                ptr::null(),
+            ));
+            // Depth cannot be filtered on ES
+            set_sampling(gl::NEAREST);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            gpu_memory::track(
+                GpuResource::Texture,
+                color_texture,
+                gpu_memory::texture_bytes(width as u32, height as u32, 1, bytes_per_pixel, false),
+            );
+            gpu_memory::track(
+                GpuResource::Texture,
+                depth_texture,
+                gpu_memory::texture_bytes(width as u32, height as u32, 1, 4, false),
+            );
+
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer));
+            gl_check!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                color_texture,
+                0
+            ));
+            gl_check!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::TEXTURE_2D,
+                depth_texture,
+                0
+            ));
+            let status = gl_check!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER));
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+            let target = TextureTarget {
+                framebuffer,
+                color_texture,
+                depth_texture,
+                width,
+                height,
+                context: *context,
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Texture target incomplete (0x{:X})", status));
+            }
+            Ok(target)
+        }
+    }
+
+    pub fn bind(&self, _context: &GlContext) {
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer));
+            gl_check!(gl::Viewport(0, 0, self.width, self.height));
+        }
+    }
+
+    /// Names the framebuffer and its textures for debuggers, see `debug_label::set`.
+    pub fn set_label(&self, _context: &GlContext, label: &str) {
+        unsafe {
+            debug_label::set(gl::FRAMEBUFFER, self.framebuffer, label);
+            debug_label::set(gl::TEXTURE, self.color_texture, &format!("{} color", label));
+            debug_label::set(
+                gl::TEXTURE,
+                self.depth_texture,
+                &format!("{} depth/stencil", label),
+            );
+        }
+    }
+}
+
+impl Drop for TextureTarget {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::Framebuffer, self.framebuffer);
+        for texture in [self.color_texture, self.depth_texture].iter() {
+            live_objects::release(&self.context, GlObject::Texture, *texture);
+        }
+    }
+}
+
+/// Clamps the bound texture's edges and filters it with `filter`.
+unsafe fn set_sampling(filter: GLenum) {
+    for (parameter, value) in [
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_MIN_FILTER, filter),
+        (gl::TEXTURE_MAG_FILTER, filter),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            *parameter,
+            *value as GLint
+        ));
+    }
+}
+
+/// Shows a `RenderTarget` in a window whose context shares objects with the one that created the
+/// target. Framebuffers are never shared between contexts but renderbuffers are, so the target's
+/// color renderbuffer is attached to a framebuffer owned by the presenting context and blitted.
+pub struct SharedTargetPresenter {
+    framebuffer: GLuint,
+    width: i32,
+    height: i32,
+    /// The presenting context
+    context: GlContext,
+}
+
+impl SharedTargetPresenter {
+    /// Must be called, like every other method, with the presenting context current.
+    pub fn new(
+        context: &GlContext,
+        target: &RenderTarget,
+    ) -> Result<SharedTargetPresenter, String> {
+        unsafe {
+            let mut framebuffer = 0_u32;
+            gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+            live_objects::created(GlObject::Framebuffer, framebuffer);
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer));
+            gl_check!(gl::FramebufferRenderbuffer(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                target.color_renderbuffer
+            ));
+            let status = gl_check!(gl::CheckFramebufferStatus(gl::READ_FRAMEBUFFER));
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0));
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                live_objects::delete(GlObject::Framebuffer, framebuffer);
+                return Err(format!("Shared render target incomplete (0x{:X})", status));
+            }
+
+            Ok(SharedTargetPresenter {
+                framebuffer,
+                width: target.width,
+                height: target.height,
+                context: *context,
+            })
+        }
+    }
+
+    /// Stretches the target over the default framebuffer. The creating context must have flushed
+    /// its rendering into the target first.
+    pub fn present(&self, _context: &GlContext, framebuffer_width: i32, framebuffer_height: i32) {
+        unsafe {
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer));
+            gl_check!(gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0));
+            gl_check!(gl::BlitFramebuffer(
+                0,
+                0,
+                self.width,
+                self.height,
+                0,
+                0,
+                framebuffer_width,
+                framebuffer_height,
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR
+            ));
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0));
+        }
+    }
+
+    /// Deletes the presenter's framebuffer, with the presenting context current. Not done on
+    /// drop, since that could run with another context current.
+    pub fn delete(self) {
+        live_objects::release(&self.context, GlObject::Framebuffer, self.framebuffer);
+    }
+}
@@ -0,0 +1,1344 @@
+use gl::types::*;
+
+use crate::math::bounds::Aabb;
+use crate::math::color::Color;
+use crate::math::frustum::Frustum;
+use crate::math::glm::{self, Mat4, Vec3};
+use crate::ogl::buffer::Buffer;
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::gpu_timer::GpuTimer;
+use crate::ogl::graphics::{Camera, Material, Mesh, ShaderProgram, Uniform};
+#[cfg(feature = "gl45")]
+use crate::ogl::indirect::{DrawElementsIndirectCommand, IndirectBuffer};
+use crate::ogl::occlusion::OcclusionCuller;
+#[cfg(feature = "gl45")]
+use crate::ogl::persistent_buffer::PersistentBuffer;
+use crate::ogl::render_queue::{DrawCommand, RenderQueue, SortKey};
+use crate::ogl::stats::FrameStats;
+use crate::ogl::stencil::StencilState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::mem;
+use std::rc::Rc;
+
+const FRAME_STATS_HISTORY: usize = 120;
+/// First of the four attribute locations, one per column, that scene shaders read each
+/// instance's `world_from_object` matrix from.
+pub const INSTANCE_MATRIX_LOCATION: GLuint = 3;
+/// Attribute location of each instance's texture set (an unsigned int), for materials sampling
+/// from a `TextureTable`.
+pub const INSTANCE_TEXTURE_SET_LOCATION: GLuint = 7;
+// Inputs scene shaders get from the instance buffer instead of the mesh, with their GLSL types
+const INSTANCE_ATTRIBUTES: [(GLuint, GLenum); 2] = [
+    (INSTANCE_MATRIX_LOCATION, gl::FLOAT_MAT4),
+    (INSTANCE_TEXTURE_SET_LOCATION, gl::UNSIGNED_INT),
+];
+// Floats per instance: the matrix, then the texture set index stored bit for bit
+const INSTANCE_FLOATS: usize = 17;
+// Instances the persistently mapped instance buffer has room for before it first grows
+#[cfg(feature = "gl45")]
+const INITIAL_INSTANCE_CAPACITY: usize = 1024;
+// Object-space scale of the silhouette drawn around outlined objects
+const OUTLINE_SCALE: f32 = 1.08;
+
+const OUTLINE_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+
+uniform mat4 world_from_object;
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+void main() {
+    gl_Position = projection_from_view * view_from_world * world_from_object * vec4(a_pos, 1.0f);
+}
+"#;
+
+const OUTLINE_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform vec4 outline_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = outline_color;
+}
+"#;
+
+const HULL_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 2) in vec3 a_normal;
+layout (location = 3) in mat4 world_from_object;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+uniform float outline_width;
+
+out vec3 o_normal;
+out vec3 o_view_position;
+
+void main() {
+    mat4 view_from_object = view_from_world * world_from_object;
+    // Extruded in view space, so the band is as wide however the object is scaled
+    vec3 normal = normalize(mat3(transpose(inverse(view_from_object))) * a_normal);
+    vec4 view_position = view_from_object * vec4(a_pos, 1.0f) + vec4(normal * outline_width, 0.0f);
+    gl_Position = projection_from_view * view_position;
+    o_normal = normal;
+    o_view_position = view_position.xyz;
+}
+"#;
+
+const HULL_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform vec4 outline_color;
+
+in vec3 o_normal;
+in vec3 o_view_position;
+
+out vec4 frag_color;
+
+void main() {
+    // Only the far side of the hull shows, peeking out around the mesh; faces are told apart by
+    // their normals, which unlike winding every mesh gets right
+    if (dot(o_normal, -o_view_position) > 0.0f) {
+        discard;
+    }
+    frag_color = outline_color;
+}
+"#;
+
+const NORMAL_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 2) in vec3 a_normal;
+layout (location = 3) in mat4 world_from_object;
+
+uniform mat4 view_from_world;
+
+out vec3 o_normal;
+
+void main() {
+    mat4 view_from_object = view_from_world * world_from_object;
+    gl_Position = view_from_object * vec4(a_pos, 1.0f);
+    // Meshes without normals leave a_normal at zero
+    o_normal = mat3(transpose(inverse(view_from_object))) * a_normal;
+}
+"#;
+
+const NORMAL_GEOMETRY_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (triangles) in;
+layout (line_strip, max_vertices = 6) out;
+
+in vec3 o_normal[];
+
+uniform mat4 projection_from_view;
+uniform float normal_length;
+
+void main() {
+    vec3 a = gl_in[0].gl_Position.xyz;
+    vec3 b = gl_in[1].gl_Position.xyz;
+    vec3 c = gl_in[2].gl_Position.xyz;
+    vec3 face_normal = normalize(cross(b - a, c - a));
+    // One segment per vertex, along its normal or the face's when the mesh has none
+    for (int i = 0; i < 3; i++) {
+        vec3 normal = dot(o_normal[i], o_normal[i]) > 0.0f ? normalize(o_normal[i]) : face_normal;
+        vec4 start = gl_in[i].gl_Position;
+        gl_Position = projection_from_view * start;
+        EmitVertex();
+        gl_Position = projection_from_view * (start + vec4(normal * normal_length, 0.0f));
+        EmitVertex();
+        EndPrimitive();
+    }
+}
+"#;
+
+const NORMAL_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform vec4 normal_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = normal_color;
+}
+"#;
+
+const WIREFRAME_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 3) in mat4 world_from_object;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+void main() {
+    gl_Position = projection_from_view * view_from_world * world_from_object * vec4(a_pos, 1.0f);
+}
+"#;
+
+const WIREFRAME_GEOMETRY_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (triangles) in;
+layout (triangle_strip, max_vertices = 3) out;
+
+// Each corner of the triangle gets one axis, so every component falls to zero along the edge
+// opposite its corner
+noperspective out vec3 o_barycentric;
+
+void main() {
+    for (int i = 0; i < 3; i++) {
+        gl_Position = gl_in[i].gl_Position;
+        o_barycentric = vec3(0.0f);
+        o_barycentric[i] = 1.0f;
+        EmitVertex();
+    }
+    EndPrimitive();
+}
+"#;
+
+const WIREFRAME_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform float line_width;
+uniform vec4 line_color;
+
+noperspective in vec3 o_barycentric;
+
+out vec4 frag_color;
+
+void main() {
+    // Pixels to the nearest edge, from how fast each coordinate changes across the screen
+    vec3 edge_distances = o_barycentric / fwidth(o_barycentric);
+    float distance = min(min(edge_distances.x, edge_distances.y), edge_distances.z);
+    float coverage = clamp(line_width * 0.5f + 0.5f - distance, 0.0f, 1.0f);
+    if (coverage <= 0.0f) {
+        discard;
+    }
+    frag_color = vec4(line_color.rgb, line_color.a * coverage);
+}
+"#;
+
+const VISUALIZATION_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 2) in vec3 a_normal;
+layout (location = 3) in mat4 world_from_object;
+
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+out vec3 o_world_pos;
+out vec3 o_world_normal;
+
+void main() {
+    vec4 world_pos = world_from_object * vec4(a_pos, 1.0f);
+    gl_Position = projection_from_view * view_from_world * world_pos;
+    o_world_pos = world_pos.xyz;
+    // Meshes without normals leave a_normal at zero
+    o_world_normal = mat3(transpose(inverse(world_from_object))) * a_normal;
+}
+"#;
+
+const VISUALIZATION_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+// What to show, numbered like `Visualization::mode`
+uniform int mode;
+uniform mat4 view_from_world;
+uniform mat4 projection_from_view;
+
+in vec3 o_world_pos;
+in vec3 o_world_normal;
+
+out vec4 frag_color;
+
+const int DEPTH = 1;
+const int VIEW_NORMALS = 3;
+const int OVERDRAW = 4;
+// Added by every fragment, so overlapping layers go from red through yellow to white
+const vec3 OVERDRAW_STEP = vec3(0.25f, 0.125f, 0.0625f);
+
+void main() {
+    if (mode == DEPTH) {
+        // Undoes the projection of the depth buffer value, as in the Depth Testing chapter
+        float near = projection_from_view[3][2] / (projection_from_view[2][2] - 1.0f);
+        float far = projection_from_view[3][2] / (projection_from_view[2][2] + 1.0f);
+        float ndc_z = gl_FragCoord.z * 2.0f - 1.0f;
+        float view_depth = 2.0f * near * far / (far + near - ndc_z * (far - near));
+        frag_color = vec4(vec3(view_depth / far), 1.0f);
+        return;
+    }
+    if (mode == OVERDRAW) {
+        frag_color = vec4(OVERDRAW_STEP, 1.0f);
+        return;
+    }
+    // Meshes without normals show their face normals
+    vec3 normal = dot(o_world_normal, o_world_normal) > 0.0f
+        ? normalize(o_world_normal)
+        : normalize(cross(dFdx(o_world_pos), dFdy(o_world_pos)));
+    if (mode == VIEW_NORMALS) {
+        normal = mat3(view_from_world) * normal;
+    }
+    frag_color = vec4(normal * 0.5f + 0.5f, 1.0f);
+}
+"#;
+
+/// What the scene meshes show in place of their materials, for debugging.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visualization {
+    /// Their materials, as usual
+    #[default]
+    Shaded,
+    /// Distance from the camera, from black at the camera to white at the far plane
+    Depth,
+    /// Normals mapped from -1..1 to colors, in world space
+    WorldNormals,
+    /// Normals in view space, so faces turned toward the camera are blue
+    ViewNormals,
+    /// How often each pixel is drawn, the depth test aside: red, then yellow, then white
+    Overdraw,
+}
+
+impl Visualization {
+    pub fn next(self) -> Visualization {
+        match self {
+            Visualization::Shaded => Visualization::Depth,
+            Visualization::Depth => Visualization::WorldNormals,
+            Visualization::WorldNormals => Visualization::ViewNormals,
+            Visualization::ViewNormals => Visualization::Overdraw,
+            Visualization::Overdraw => Visualization::Shaded,
+        }
+    }
+
+    /// Number of the visualization in the fragment shader.
+    fn mode(self) -> i32 {
+        self as i32
+    }
+
+    /// Color the view is cleared to instead of the renderer's, matching what empty space shows.
+    fn clear_color(self) -> Option<Color> {
+        match self {
+            Visualization::Shaded => None,
+            Visualization::Depth => Some(Color::WHITE),
+            _ => Some(Color::BLACK),
+        }
+    }
+}
+
+/// Draws the scene meshes with the program showing a `Visualization`.
+struct VisualizationDisplay {
+    shader_program: ShaderProgram,
+    mode_uniform: Uniform,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+}
+
+/// Draws the edges of every scene triangle over the shaded scene, anti-aliased and a fixed
+/// number of pixels wide. A geometry shader gives each triangle barycentric coordinates, so
+/// fragments know their distance to the edges.
+struct WireframeOverlay {
+    shader_program: ShaderProgram,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    line_width_uniform: Uniform,
+    line_color_uniform: Uniform,
+}
+
+/// Draws a line along every vertex normal of the scene meshes, as the Geometry Shader chapter
+/// does. Normals come from the `a_normal` attribute at location 2; meshes without one show
+/// their face normals.
+struct NormalDisplay {
+    shader_program: ShaderProgram,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    normal_length_uniform: Uniform,
+    normal_color_uniform: Uniform,
+}
+
+/// Draws the inverted-hull outlines of materials that have one.
+struct HullOutlines {
+    shader_program: ShaderProgram,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    outline_width_uniform: Uniform,
+    outline_color_uniform: Uniform,
+}
+
+pub struct Renderer {
+    pub clear_color: Color,
+    pub stats: FrameStats,
+    pub gpu_timer: GpuTimer,
+    pub outline_color: Color,
+    /// Length in world units of the lines drawn by `set_show_normals`
+    pub normal_length: f32,
+    pub normal_color: Color,
+    /// Width in pixels of the edges drawn by `set_show_wireframe_overlay`
+    pub wireframe_width: f32,
+    pub wireframe_color: Color,
+    /// Skip objects submitted with bounds that lie outside `culling_frustum`
+    pub frustum_culling: bool,
+    /// Skip objects submitted with `submit_occlusion_culled` that their last occlusion query
+    /// found hidden
+    pub occlusion_culling: bool,
+    /// Draw consecutive queued draws of the same mesh and material with one instanced call;
+    /// when off every object gets its own draw call, for comparison
+    pub instancing: bool,
+    /// Widen instanced runs to different meshes sharing buffers (see `MeshPool`), drawn with
+    /// one indirect multi-draw call each where the context supports it
+    pub multi_draw_indirect: bool,
+    culling_frustum: Frustum,
+    culling_frozen: bool,
+    occlusion_culler: OcclusionCuller,
+    /// Whether the current view is the one occlusion is tested from, not an observer view
+    occlusion_view: bool,
+    camera_position: Vec3,
+    view_from_world: Mat4,
+    projection_from_view: Mat4,
+    wireframe: bool,
+    /// Built when normals are first shown
+    normal_display: Option<NormalDisplay>,
+    show_normals: bool,
+    /// Built when the overlay is first shown
+    wireframe_overlay: Option<WireframeOverlay>,
+    show_wireframe_overlay: bool,
+    /// Built when a visualization is first shown
+    visualization_display: Option<VisualizationDisplay>,
+    visualization: Visualization,
+    queue: RenderQueue,
+    outlines: Vec<(Rc<Mesh>, Mat4)>,
+    /// `world_from_object` and texture set of every draw in the current view, in draw order
+    instance_data: Vec<f32>,
+    instance_buffer: Buffer,
+    /// What `instance_buffer` holds, to upload only what changed
+    uploaded_instance_data: Vec<f32>,
+    /// Replaces `instance_buffer` when the context supports buffer storage
+    #[cfg(feature = "gl45")]
+    persistent_instance_buffer: Option<PersistentBuffer>,
+    #[cfg(feature = "gl45")]
+    indirect_buffer: Option<IndirectBuffer>,
+    hull_outlines: HullOutlines,
+    outline_program: ShaderProgram,
+    outline_color_uniform: Uniform,
+    world_from_object_uniform: Uniform,
+    view_from_world_uniform: Uniform,
+    projection_from_view_uniform: Uniform,
+    /// `view_from_world` and `projection_from_view` of every material program drawn with, by
+    /// program id
+    material_uniforms: HashMap<GLuint, (Uniform, Uniform)>,
+    /// Whether the layout of a vertex array suits a program, by vertex array and program id
+    vertex_layout_checks: HashMap<(GLuint, GLuint), bool>,
+    /// For drawing, and for building the debug displays when first shown
+    context: GlContext,
+}
+
+impl Renderer {
+    pub fn new(
+        context: &GlContext,
+        clear_color: Color,
+        projection_from_view: Mat4,
+    ) -> Result<Renderer, String> {
+        let outline_program = ShaderProgram::with_shaders(
+            context,
+            OUTLINE_VERTEX_SHADER_SOURCE,
+            OUTLINE_FRAGMENT_SHADER_SOURCE,
+        )?;
+        let hull_program = ShaderProgram::with_shaders(
+            context,
+            HULL_VERTEX_SHADER_SOURCE,
+            HULL_FRAGMENT_SHADER_SOURCE,
+        )?;
+        outline_program.set_label("stencil outlines");
+        hull_program.set_label("hull outlines");
+        let hull_outlines = HullOutlines {
+            view_from_world_uniform: hull_program.uniform("view_from_world"),
+            projection_from_view_uniform: hull_program.uniform("projection_from_view"),
+            outline_width_uniform: hull_program.uniform("outline_width"),
+            outline_color_uniform: hull_program.uniform("outline_color"),
+            shader_program: hull_program,
+        };
+        unsafe {
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+        let instance_buffer = Buffer::new(context, gl::DYNAMIC_DRAW);
+        instance_buffer.set_label(context, "instance data");
+        #[cfg(feature = "gl45")]
+        let persistent_instance_buffer = if capabilities::buffer_storage() {
+            let region_size =
+                INITIAL_INSTANCE_CAPACITY * INSTANCE_FLOATS * mem::size_of::<GLfloat>();
+            PersistentBuffer::new(context, gl::ARRAY_BUFFER, region_size)
+                .map_err(|e| eprintln!("Streaming instances without buffer storage: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        #[cfg(feature = "gl45")]
+        let indirect_buffer = if capabilities::multi_draw_indirect() {
+            Some(IndirectBuffer::new(context))
+        } else {
+            None
+        };
+        let outline_color_uniform = outline_program.uniform("outline_color");
+        let world_from_object_uniform = outline_program.uniform("world_from_object");
+        let view_from_world_uniform = outline_program.uniform("view_from_world");
+        let projection_from_view_uniform = outline_program.uniform("projection_from_view");
+        Ok(Renderer {
+            context: *context,
+            clear_color,
+            stats: FrameStats::new(FRAME_STATS_HISTORY),
+            gpu_timer: GpuTimer::new(context),
+            outline_color: Color::from_srgb(1.0_f32, 0.6_f32, 0.1_f32, 1.0_f32),
+            normal_length: 0.1_f32,
+            normal_color: Color::from_srgb(1.0_f32, 1.0_f32, 0.0_f32, 1.0_f32),
+            wireframe_width: 1.0_f32,
+            wireframe_color: Color::from_srgb(0.0_f32, 0.0_f32, 0.0_f32, 0.8_f32),
+            frustum_culling: true,
+            culling_frustum: Frustum::from_matrix(&projection_from_view),
+            culling_frozen: false,
+            occlusion_culling: false,
+            occlusion_culler: OcclusionCuller::new(context)?,
+            occlusion_view: false,
+            camera_position: Vec3::default(),
+            instancing: true,
+            multi_draw_indirect: true,
+            view_from_world: glm::identity(),
+            projection_from_view,
+            wireframe: false,
+            normal_display: None,
+            show_normals: false,
+            wireframe_overlay: None,
+            show_wireframe_overlay: false,
+            visualization_display: None,
+            visualization: Visualization::default(),
+            queue: RenderQueue::new(),
+            outlines: vec![],
+            instance_data: vec![],
+            instance_buffer,
+            uploaded_instance_data: vec![],
+            #[cfg(feature = "gl45")]
+            persistent_instance_buffer,
+            #[cfg(feature = "gl45")]
+            indirect_buffer,
+            hull_outlines,
+            outline_program,
+            outline_color_uniform,
+            world_from_object_uniform,
+            view_from_world_uniform,
+            projection_from_view_uniform,
+            material_uniforms: HashMap::new(),
+            vertex_layout_checks: HashMap::new(),
+        })
+    }
+
+    pub fn projection_from_view(&self) -> &Mat4 {
+        &self.projection_from_view
+    }
+
+    pub fn set_projection(&mut self, projection_from_view: Mat4) {
+        self.projection_from_view = projection_from_view;
+    }
+
+    /// Frustum the scene is culled against: the camera's, unless culling is frozen.
+    pub fn culling_frustum(&self) -> &Frustum {
+        &self.culling_frustum
+    }
+
+    pub fn culling_frozen(&self) -> bool {
+        self.culling_frozen
+    }
+
+    /// Stops (or resumes) updating the culling frustum from the camera, so the camera can fly
+    /// out and look at what the frozen view culls.
+    pub fn set_culling_frozen(&mut self, frozen: bool) {
+        self.culling_frozen = frozen;
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Draws scene meshes as outlines of their triangles. Needs desktop OpenGL; ES has no
+    /// polygon modes.
+    pub fn set_wireframe(&mut self, wireframe: bool) -> Result<(), String> {
+        if !gl::PolygonMode::is_loaded() {
+            return Err("Wireframe rendering is not supported on OpenGL ES".to_string());
+        }
+        self.wireframe = wireframe;
+        Ok(())
+    }
+
+    pub fn show_normals(&self) -> bool {
+        self.show_normals
+    }
+
+    /// Draws the scene meshes again as lines along their normals, `normal_length` long. Needs
+    /// geometry shaders, which OpenGL ES 3.0 lacks.
+    pub fn set_show_normals(&mut self, show_normals: bool) -> Result<(), String> {
+        if show_normals && self.normal_display.is_none() {
+            if capabilities::opengl_es() {
+                return Err(
+                    "Normal display needs geometry shaders, which OpenGL ES lacks".to_string(),
+                );
+            }
+            let shader_program = ShaderProgram::with_geometry_shader(
+                &self.context,
+                NORMAL_VERTEX_SHADER_SOURCE,
+                NORMAL_GEOMETRY_SHADER_SOURCE,
+                NORMAL_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("normal display");
+            self.normal_display = Some(NormalDisplay {
+                view_from_world_uniform: shader_program.uniform("view_from_world"),
+                projection_from_view_uniform: shader_program.uniform("projection_from_view"),
+                normal_length_uniform: shader_program.uniform("normal_length"),
+                normal_color_uniform: shader_program.uniform("normal_color"),
+                shader_program,
+            });
+        }
+        self.show_normals = show_normals;
+        Ok(())
+    }
+
+    pub fn show_wireframe_overlay(&self) -> bool {
+        self.show_wireframe_overlay
+    }
+
+    /// Draws the edges of the scene triangles over the shaded meshes, `wireframe_width` pixels
+    /// wide. Unlike `set_wireframe` the shading stays visible and the lines are anti-aliased.
+    /// Needs geometry shaders, which OpenGL ES 3.0 lacks.
+    pub fn set_show_wireframe_overlay(&mut self, show: bool) -> Result<(), String> {
+        if show && self.wireframe_overlay.is_none() {
+            if capabilities::opengl_es() {
+                return Err(
+                    "Wireframe overlay needs geometry shaders, which OpenGL ES lacks".to_string(),
+                );
+            }
+            let shader_program = ShaderProgram::with_geometry_shader(
+                &self.context,
+                WIREFRAME_VERTEX_SHADER_SOURCE,
+                WIREFRAME_GEOMETRY_SHADER_SOURCE,
+                WIREFRAME_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("wireframe overlay");
+            self.wireframe_overlay = Some(WireframeOverlay {
+                view_from_world_uniform: shader_program.uniform("view_from_world"),
+                projection_from_view_uniform: shader_program.uniform("projection_from_view"),
+                line_width_uniform: shader_program.uniform("line_width"),
+                line_color_uniform: shader_program.uniform("line_color"),
+                shader_program,
+            });
+        }
+        self.show_wireframe_overlay = show;
+        Ok(())
+    }
+
+    pub fn visualization(&self) -> Visualization {
+        self.visualization
+    }
+
+    /// Draws the scene meshes showing `visualization` instead of their materials. Only what is
+    /// submitted to the renderer changes; terrain, water and the like still draw shaded.
+    pub fn set_visualization(&mut self, visualization: Visualization) -> Result<(), String> {
+        if visualization != Visualization::Shaded && self.visualization_display.is_none() {
+            let shader_program = ShaderProgram::with_shaders(
+                &self.context,
+                VISUALIZATION_VERTEX_SHADER_SOURCE,
+                VISUALIZATION_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("visualization");
+            self.visualization_display = Some(VisualizationDisplay {
+                mode_uniform: shader_program.uniform("mode"),
+                view_from_world_uniform: shader_program.uniform("view_from_world"),
+                projection_from_view_uniform: shader_program.uniform("projection_from_view"),
+                shader_program,
+            });
+        }
+        self.visualization = visualization;
+        Ok(())
+    }
+
+    pub fn begin_frame(&mut self, camera: &Camera) {
+        self.gpu_timer.collect(&mut self.stats);
+        if self.occlusion_culling {
+            unsafe {
+                self.occlusion_culler.collect();
+            }
+        }
+        self.stats.begin_frame();
+        self.begin_view(camera);
+    }
+
+    /// Starts another view of the scene within the current frame, e.g. into an offscreen target.
+    /// Clears the bound framebuffer; its draws count towards the frame stats.
+    pub fn begin_view(&mut self, camera: &Camera) {
+        self.begin_observer_view(camera);
+        self.occlusion_view = true;
+        self.camera_position = camera.position;
+        if !self.culling_frozen {
+            self.culling_frustum =
+                Frustum::from_matrix(&(self.projection_from_view * self.view_from_world));
+        }
+    }
+
+    /// Like `begin_view`, but keeps culling against the frustum of the previous view, so
+    /// `camera` can watch from outside what that view skips.
+    pub fn begin_observer_view(&mut self, camera: &Camera) {
+        self.view_from_world = camera.view_matrix();
+        self.occlusion_view = false;
+        let clear_color = self.visualization.clear_color().unwrap_or(self.clear_color);
+        let [r, g, b, a] = clear_color.to_srgb();
+        unsafe {
+            gl_check!(gl::ClearColor(r, g, b, a));
+            gl_check!(gl::Clear(
+                gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT
+            ));
+        }
+    }
+
+    /// Starts a view drawn over what the bound framebuffer already holds, such as the scene
+    /// reflected in a mirror and masked by the stencil buffer. Culls against this view's frustum
+    /// unless culling is frozen, but leaves occlusion queries to the camera's view.
+    pub fn begin_masked_view(&mut self, view_from_world: Mat4) {
+        self.view_from_world = view_from_world;
+        self.occlusion_view = false;
+        if !self.culling_frozen {
+            self.culling_frustum =
+                Frustum::from_matrix(&(self.projection_from_view * self.view_from_world));
+        }
+    }
+
+    /// Queues a draw; nothing reaches GL until `end_frame` sorts and flushes the queue. Meshes
+    /// whose vertex layout does not suit the material's shader are reported once and never
+    /// drawn with it, see `check_vertex_layout`.
+    pub fn submit(&mut self, mesh: &Rc<Mesh>, material: &Rc<Material>, world_from_object: &Mat4) {
+        if !self.vertex_layout_suits(mesh, material) {
+            return;
+        }
+        let key = SortKey::new(
+            material.pass,
+            material.shader_program.id,
+            material.id,
+            self.normalized_depth(world_from_object),
+        );
+        self.queue.push(DrawCommand {
+            key,
+            mesh: Rc::clone(mesh),
+            material: Rc::clone(material),
+            world_from_object: *world_from_object,
+        });
+    }
+
+    /// Forgets what was looked up for material programs and vertex arrays, once those are
+    /// deleted: GL hands their names out again, to objects the lookups do not hold for.
+    pub fn forget_deleted_objects(&mut self) {
+        self.material_uniforms.clear();
+        self.vertex_layout_checks.clear();
+    }
+
+    /// Checks that `mesh`, with the renderer's per-instance data, feeds every input of the
+    /// shader of `material` with a compatible type.
+    pub fn check_vertex_layout(&self, mesh: &Mesh, material: &Material) -> Result<(), String> {
+        material
+            .shader_program
+            .check_vertex_layout(&mesh.attributes, &INSTANCE_ATTRIBUTES)
+    }
+
+    fn vertex_layout_suits(&mut self, mesh: &Mesh, material: &Material) -> bool {
+        let key = (mesh.vertex_array_obj(), material.shader_program.id);
+        if let Some(&suits) = self.vertex_layout_checks.get(&key) {
+            return suits;
+        }
+        let suits = match self.check_vertex_layout(mesh, material) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Not drawing a mesh with material {}: {}", material.id, e);
+                false
+            }
+        };
+        self.vertex_layout_checks.insert(key, suits);
+        suits
+    }
+
+    /// Queues a draw unless frustum culling is on and `world_bounds`, the object's bounds in
+    /// world space, lie outside the culling frustum.
+    #[allow(dead_code)]
+    pub fn submit_culled(
+        &mut self,
+        mesh: &Rc<Mesh>,
+        material: &Rc<Material>,
+        world_from_object: &Mat4,
+        world_bounds: &Aabb,
+    ) {
+        let visible = !self.frustum_culling || self.culling_frustum.is_aabb_visible(world_bounds);
+        self.stats.record_culling(visible);
+        if visible {
+            self.submit(mesh, material, world_from_object);
+        }
+    }
+
+    /// Like `submit_culled`, but with occlusion culling on also skips the object when its last
+    /// occlusion query found it hidden. `key` identifies the object across frames.
+    pub fn submit_occlusion_culled(
+        &mut self,
+        key: u64,
+        mesh: &Rc<Mesh>,
+        material: &Rc<Material>,
+        world_from_object: &Mat4,
+        world_bounds: &Aabb,
+    ) {
+        if self.frustum_culling && !self.culling_frustum.is_aabb_visible(world_bounds) {
+            self.stats.record_culling(false);
+            return;
+        }
+        if self.occlusion_culling {
+            // Hidden objects keep being tested, so they come back once they are in view
+            if self.occlusion_view {
+                self.occlusion_culler.request(key, world_bounds);
+            }
+            if !self.occlusion_culler.is_visible(key) {
+                self.stats.record_occlusion();
+                return;
+            }
+        }
+        self.stats.record_culling(true);
+        self.submit(mesh, material, world_from_object);
+    }
+
+    /// Queues a stencil outline around `mesh`. The mesh itself still has to be submitted (or be
+    /// part of a batch) to be visible; only positions at attribute location 0 are used.
+    pub fn submit_outline(&mut self, mesh: &Rc<Mesh>, world_from_object: &Mat4) {
+        self.outlines.push((Rc::clone(mesh), *world_from_object));
+    }
+
+    pub fn end_frame(&mut self) {
+        self.end_view("scene");
+    }
+
+    /// Draws everything submitted since `begin_view`, timed as the GPU pass `pass_name`.
+    pub fn end_view(&mut self, pass_name: &str) {
+        self.gpu_timer.begin_pass(pass_name);
+        if self.wireframe {
+            unsafe {
+                gl_check!(gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE));
+            }
+        }
+        let commands: Vec<DrawCommand> = self.queue.drain_sorted().collect();
+        // Every draw reads its matrices from the instance buffer, so they all go up at once
+        self.instance_data.clear();
+        for command in commands.iter() {
+            self.instance_data
+                .extend_from_slice(glm::value_ptr(&command.world_from_object));
+            self.instance_data
+                .push(f32::from_bits(command.material.texture_set));
+        }
+        let (instance_buffer, instance_offset) = unsafe { self.upload_instance_data() };
+
+        let multi_draw = self.multi_draw_active();
+        let runs = if self.visualization == Visualization::Shaded {
+            self.draw_runs(&commands, multi_draw)
+        } else {
+            // The visualization replaces every material, so no run is left to draw shaded
+            unsafe {
+                self.draw_visualization(&commands, instance_buffer, instance_offset);
+            }
+            vec![]
+        };
+        #[cfg(feature = "gl45")]
+        let indirect_slices = if multi_draw {
+            unsafe { self.upload_indirect_commands(&commands, &runs) }
+        } else {
+            vec![]
+        };
+
+        let mut bound_program: Option<GLuint> = None;
+        let mut bound_material: Option<&Material> = None;
+        #[cfg_attr(not(feature = "gl45"), allow(unused_variables))]
+        for (run_i, &(run_start, run_end)) in runs.iter().enumerate() {
+            let command = &commands[run_start];
+            let material = &command.material;
+            let shader_program = &material.shader_program;
+            if !bound_material.is_some_and(|bound| bound.shares_bindings(material)) {
+                material.bind();
+                bound_material = Some(material);
+                self.stats.record_state_change();
+            }
+            if bound_program != Some(shader_program.id) {
+                let (view_from_world, projection_from_view) = *self
+                    .material_uniforms
+                    .entry(shader_program.id)
+                    .or_insert_with(|| {
+                        (
+                            shader_program.uniform("view_from_world"),
+                            shader_program.uniform("projection_from_view"),
+                        )
+                    });
+                view_from_world.set_mat4f(&self.view_from_world);
+                projection_from_view.set_mat4f(&self.projection_from_view);
+                bound_program = Some(shader_program.id);
+            }
+            let triangle_count: usize = commands[run_start..run_end]
+                .iter()
+                .map(|command| command.mesh.triangle_count())
+                .sum();
+            #[cfg(feature = "gl45")]
+            {
+                if let (Some(Some((first, count))), Some(indirect_buffer)) =
+                    (indirect_slices.get(run_i), self.indirect_buffer.as_ref())
+                {
+                    unsafe {
+                        // Each indirect command picks its instances through its base instance
+                        self.bind_instance_data(&command.mesh, instance_buffer, instance_offset, 0);
+                        indirect_buffer.multi_draw(*first, *count);
+                    }
+                    self.stats.record_draw(triangle_count as u64);
+                    continue;
+                }
+            }
+            unsafe {
+                self.bind_instance_data(&command.mesh, instance_buffer, instance_offset, run_start);
+                command
+                    .mesh
+                    .draw_instanced(&self.context, (run_end - run_start) as GLsizei);
+            }
+            self.stats.record_draw(triangle_count as u64);
+        }
+        if self.wireframe {
+            unsafe {
+                gl_check!(gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL));
+            }
+        }
+        if commands
+            .iter()
+            .any(|command| command.material.outline.is_some())
+        {
+            // Like the normals below, the hulls read the same instance data
+            unsafe {
+                self.draw_hull_outlines(&commands, instance_buffer, instance_offset);
+            }
+        }
+        if self.show_normals {
+            // The normals read the same instance data, before it is fenced
+            unsafe {
+                self.draw_normals(&commands, instance_buffer, instance_offset);
+            }
+        }
+        if self.show_wireframe_overlay {
+            unsafe {
+                self.draw_wireframe_overlay(&commands, instance_buffer, instance_offset);
+            }
+        }
+        #[cfg(feature = "gl45")]
+        {
+            if let Some(persistent_buffer) = self.persistent_instance_buffer.as_mut() {
+                if !commands.is_empty() {
+                    unsafe {
+                        persistent_buffer.fence();
+                    }
+                }
+            }
+        }
+        if self.occlusion_culling && self.occlusion_view {
+            // The boxes are tested against the depth of everything drawn above
+            unsafe {
+                self.occlusion_culler.issue(
+                    &(self.projection_from_view * self.view_from_world),
+                    &self.camera_position,
+                );
+            }
+        }
+
+        if !self.outlines.is_empty() {
+            self.draw_outlines();
+        }
+
+        unsafe {
+            gl_check!(gl::BindVertexArray(0));
+        }
+        self.gpu_timer.end_pass();
+    }
+
+    fn multi_draw_active(&self) -> bool {
+        #[cfg(feature = "gl45")]
+        let supported = self.indirect_buffer.is_some();
+        #[cfg(not(feature = "gl45"))]
+        let supported = false;
+        self.multi_draw_indirect && self.instancing && supported
+    }
+
+    /// Splits the sorted draws into runs drawn with one call each: consecutive draws of one
+    /// material and mesh when instancing, widened to indexed meshes sharing a vertex array when
+    /// multi-drawing. Returns the start and end of every run.
+    fn draw_runs(&self, commands: &[DrawCommand], multi_draw: bool) -> Vec<(usize, usize)> {
+        let mut runs = vec![];
+        let mut run_start = 0;
+        while run_start < commands.len() {
+            let first = &commands[run_start];
+            let shares_buffers = multi_draw && first.mesh.is_indexed();
+            let mut run_end = run_start + 1;
+            while self.instancing && run_end < commands.len() {
+                let next = &commands[run_end];
+                let same_geometry = Rc::ptr_eq(&next.mesh, &first.mesh)
+                    || (shares_buffers
+                        && next.mesh.vertex_array_obj() == first.mesh.vertex_array_obj());
+                if !next.material.shares_bindings(&first.material) || !same_geometry {
+                    break;
+                }
+                run_end += 1;
+            }
+            runs.push((run_start, run_end));
+            run_start = run_end;
+        }
+        runs
+    }
+
+    /// Fills the indirect buffer with a command per stretch of one mesh within each run of
+    /// indexed meshes, returning the first command and command count of every run, or `None`
+    /// for runs drawn directly.
+    #[cfg(feature = "gl45")]
+    unsafe fn upload_indirect_commands(
+        &mut self,
+        commands: &[DrawCommand],
+        runs: &[(usize, usize)],
+    ) -> Vec<Option<(usize, usize)>> {
+        let indirect_buffer = match self.indirect_buffer.as_mut() {
+            Some(indirect_buffer) => indirect_buffer,
+            None => return vec![],
+        };
+        indirect_buffer.clear();
+        let slices = runs
+            .iter()
+            .map(|&(run_start, run_end)| {
+                if !commands[run_start].mesh.is_indexed() {
+                    return None;
+                }
+                let first_command = indirect_buffer.len();
+                let mut mesh_start = run_start;
+                while mesh_start < run_end {
+                    let mesh = &commands[mesh_start].mesh;
+                    let mut mesh_end = mesh_start + 1;
+                    while mesh_end < run_end && Rc::ptr_eq(&commands[mesh_end].mesh, mesh) {
+                        mesh_end += 1;
+                    }
+                    indirect_buffer.push(DrawElementsIndirectCommand {
+                        count: mesh.index_count() as GLuint,
+                        instance_count: (mesh_end - mesh_start) as GLuint,
+                        first_index: mesh.first_index() as GLuint,
+                        base_vertex: 0,
+                        base_instance: mesh_start as GLuint,
+                    });
+                    mesh_start = mesh_end;
+                }
+                Some((first_command, indirect_buffer.len() - first_command))
+            })
+            .collect();
+        indirect_buffer.upload();
+        slices
+    }
+
+    /// Returns the buffer the instance data went into and its byte offset in it.
+    unsafe fn upload_instance_data(&mut self) -> (GLuint, usize) {
+        if self.instance_data.is_empty() {
+            return (self.instance_buffer.id, 0);
+        }
+        #[cfg(feature = "gl45")]
+        {
+            if let Some(persistent_buffer) = self.persistent_instance_buffer.as_mut() {
+                match persistent_buffer.write(&self.instance_data) {
+                    Ok(offset) => return (persistent_buffer.id, offset),
+                    Err(e) => {
+                        eprintln!("Streaming instances without buffer storage: {}", e);
+                        self.persistent_instance_buffer = None;
+                    }
+                }
+            }
+        }
+        // Only the span between the first and last instance that changed since the last upload
+        // goes up, so a scene where few objects move uploads little. Past half the buffer,
+        // respecifying it whole is cheaper.
+        let mut changed = self
+            .instance_data
+            .iter()
+            .zip(self.uploaded_instance_data.iter())
+            .map(|(value, uploaded)| value.to_bits() != uploaded.to_bits());
+        let same_size = self.instance_data.len() == self.uploaded_instance_data.len();
+        let first_changed = changed.clone().position(|changed| changed);
+        let last_changed = changed.rposition(|changed| changed);
+        match (same_size, first_changed, last_changed) {
+            (true, None, _) => {}
+            (true, Some(first), Some(last))
+                if (last - first + 1) * 2 < self.instance_data.len() =>
+            {
+                if let Err(e) = self.instance_buffer.update(
+                    &self.context,
+                    first * mem::size_of::<GLfloat>(),
+                    &self.instance_data[first..=last],
+                ) {
+                    eprintln!("Failed updating instance data: {}", e);
+                }
+            }
+            // Respecifying the whole buffer lets the driver hand out fresh storage instead of
+            // waiting for last frame's draws to finish reading it
+            _ => self
+                .instance_buffer
+                .set_data(&self.context, &self.instance_data),
+        }
+        self.uploaded_instance_data.clear();
+        self.uploaded_instance_data
+            .extend_from_slice(&self.instance_data);
+        (self.instance_buffer.id, 0)
+    }
+
+    /// Points the instance attributes of `mesh`'s vertex array at the instances from
+    /// `first_instance` on, in `buffer` from byte `offset`. Offsetting the pointers stands in
+    /// for a base instance, which needs OpenGL 4.2.
+    unsafe fn bind_instance_data(
+        &self,
+        mesh: &Mesh,
+        buffer: GLuint,
+        offset: usize,
+        first_instance: usize,
+    ) {
+        let column_size = 4 * mem::size_of::<GLfloat>();
+        let instance_size = INSTANCE_FLOATS * mem::size_of::<GLfloat>();
+        let instance_start = offset + first_instance * instance_size;
+        gl_check!(gl::BindVertexArray(mesh.vertex_array_obj()));
+        gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, buffer));
+        for column in 0..4 {
+            let location = INSTANCE_MATRIX_LOCATION + column as GLuint;
+            gl_check!(gl::EnableVertexAttribArray(location));
+            gl_check!(gl::VertexAttribPointer(
+                location,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                instance_size as GLsizei,
+                (instance_start + column * column_size) as *const c_void,
+            ));
+            gl_check!(gl::VertexAttribDivisor(location, 1));
+        }
+        gl_check!(gl::EnableVertexAttribArray(INSTANCE_TEXTURE_SET_LOCATION));
+        gl_check!(gl::VertexAttribIPointer(
+            INSTANCE_TEXTURE_SET_LOCATION,
+            1,
+            gl::UNSIGNED_INT,
+            instance_size as GLsizei,
+            (instance_start + 4 * column_size) as *const c_void,
+        ));
+        gl_check!(gl::VertexAttribDivisor(INSTANCE_TEXTURE_SET_LOCATION, 1));
+        gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+    }
+
+    /// Redraws the `commands` whose material has a `HullOutline` with the hull program, one
+    /// instanced call per run, reading their matrices from the instance data already uploaded.
+    unsafe fn draw_hull_outlines(
+        &mut self,
+        commands: &[DrawCommand],
+        buffer: GLuint,
+        offset: usize,
+    ) {
+        let hull_outlines = &self.hull_outlines;
+        hull_outlines.shader_program.use_program();
+        hull_outlines
+            .view_from_world_uniform
+            .set_mat4f(&self.view_from_world);
+        hull_outlines
+            .projection_from_view_uniform
+            .set_mat4f(&self.projection_from_view);
+        self.stats.record_state_change();
+        // Runs never mix outlines, as materials with different ones do not share bindings
+        for (run_start, run_end) in self.draw_runs(commands, false) {
+            let command = &commands[run_start];
+            let outline = match command.material.outline {
+                Some(outline) => outline,
+                None => continue,
+            };
+            self.hull_outlines
+                .outline_width_uniform
+                .set_float(outline.width);
+            self.hull_outlines
+                .outline_color_uniform
+                .set_color(&outline.color);
+            self.bind_instance_data(&command.mesh, buffer, offset, run_start);
+            command
+                .mesh
+                .draw_instanced(&self.context, (run_end - run_start) as GLsizei);
+            let triangle_count: usize = commands[run_start..run_end]
+                .iter()
+                .map(|command| command.mesh.triangle_count())
+                .sum();
+            self.stats.record_draw(triangle_count as u64);
+        }
+    }
+
+    /// Redraws `commands` with the normal display program, one instanced call per run of the same
+    /// mesh, reading their matrices from the instance data already uploaded.
+    unsafe fn draw_normals(&mut self, commands: &[DrawCommand], buffer: GLuint, offset: usize) {
+        let normal_display = match self.normal_display.as_ref() {
+            Some(normal_display) => normal_display,
+            None => return,
+        };
+        normal_display.shader_program.use_program();
+        normal_display
+            .view_from_world_uniform
+            .set_mat4f(&self.view_from_world);
+        normal_display
+            .projection_from_view_uniform
+            .set_mat4f(&self.projection_from_view);
+        normal_display
+            .normal_length_uniform
+            .set_float(self.normal_length);
+        normal_display
+            .normal_color_uniform
+            .set_color(&self.normal_color);
+        self.stats.record_state_change();
+        // Runs sharing buffers draw different meshes, which only indirect draws can do at once
+        for (run_start, run_end) in self.draw_runs(commands, false) {
+            let mesh = &commands[run_start].mesh;
+            self.bind_instance_data(mesh, buffer, offset, run_start);
+            mesh.draw_instanced(&self.context, (run_end - run_start) as GLsizei);
+            self.stats.record_draw(0);
+        }
+    }
+
+    /// Draws `commands` with the visualization program instead of their materials, one instanced
+    /// call per run of the same mesh. Overdraw adds up every fragment, hidden ones included.
+    unsafe fn draw_visualization(
+        &mut self,
+        commands: &[DrawCommand],
+        buffer: GLuint,
+        offset: usize,
+    ) {
+        let visualization_display = match self.visualization_display.as_ref() {
+            Some(visualization_display) => visualization_display,
+            None => return,
+        };
+        visualization_display.shader_program.use_program();
+        visualization_display
+            .mode_uniform
+            .set_int(self.visualization.mode());
+        visualization_display
+            .view_from_world_uniform
+            .set_mat4f(&self.view_from_world);
+        visualization_display
+            .projection_from_view_uniform
+            .set_mat4f(&self.projection_from_view);
+        self.stats.record_state_change();
+        let overdraw = self.visualization == Visualization::Overdraw;
+        if overdraw {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::ONE, gl::ONE));
+        }
+        for (run_start, run_end) in self.draw_runs(commands, false) {
+            let mesh = &commands[run_start].mesh;
+            self.bind_instance_data(mesh, buffer, offset, run_start);
+            mesh.draw_instanced(&self.context, (run_end - run_start) as GLsizei);
+            let triangle_count: usize = commands[run_start..run_end]
+                .iter()
+                .map(|command| command.mesh.triangle_count())
+                .sum();
+            self.stats.record_draw(triangle_count as u64);
+        }
+        if overdraw {
+            gl_check!(gl::Disable(gl::BLEND));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+    }
+
+    /// Redraws `commands` with the wireframe overlay program over what they drew, pulled slightly
+    /// toward the camera so the edges win the depth test against their own triangles.
+    unsafe fn draw_wireframe_overlay(
+        &mut self,
+        commands: &[DrawCommand],
+        buffer: GLuint,
+        offset: usize,
+    ) {
+        let wireframe_overlay = match self.wireframe_overlay.as_ref() {
+            Some(wireframe_overlay) => wireframe_overlay,
+            None => return,
+        };
+        wireframe_overlay.shader_program.use_program();
+        wireframe_overlay
+            .view_from_world_uniform
+            .set_mat4f(&self.view_from_world);
+        wireframe_overlay
+            .projection_from_view_uniform
+            .set_mat4f(&self.projection_from_view);
+        wireframe_overlay
+            .line_width_uniform
+            .set_float(self.wireframe_width);
+        wireframe_overlay
+            .line_color_uniform
+            .set_color(&self.wireframe_color);
+        self.stats.record_state_change();
+        gl_check!(gl::Enable(gl::BLEND));
+        gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+        gl_check!(gl::DepthMask(gl::FALSE));
+        gl_check!(gl::DepthFunc(gl::LEQUAL));
+        gl_check!(gl::Enable(gl::POLYGON_OFFSET_FILL));
+        gl_check!(gl::PolygonOffset(-1.0_f32, -1.0_f32));
+        for (run_start, run_end) in self.draw_runs(commands, false) {
+            let mesh = &commands[run_start].mesh;
+            self.bind_instance_data(mesh, buffer, offset, run_start);
+            mesh.draw_instanced(&self.context, (run_end - run_start) as GLsizei);
+            self.stats.record_draw(0);
+        }
+        gl_check!(gl::Disable(gl::POLYGON_OFFSET_FILL));
+        gl_check!(gl::DepthFunc(gl::LESS));
+        gl_check!(gl::DepthMask(gl::TRUE));
+        gl_check!(gl::Disable(gl::BLEND));
+    }
+
+    /// Marks the silhouettes of the outlined meshes in the stencil buffer, then draws slightly
+    /// enlarged copies in a flat color wherever the stencil is not marked.
+    fn draw_outlines(&mut self) {
+        self.outline_program.use_program();
+        self.view_from_world_uniform
+            .set_mat4f(&self.view_from_world);
+        self.projection_from_view_uniform
+            .set_mat4f(&self.projection_from_view);
+        self.outline_color_uniform.set_color(&self.outline_color);
+        self.stats.record_state_change();
+
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            StencilState::mark(1).apply();
+            gl_check!(gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE));
+        }
+        for (mesh, world_from_object) in self.outlines.iter() {
+            self.world_from_object_uniform.set_mat4f(world_from_object);
+            mesh.draw(&self.context);
+            self.stats.record_draw(mesh.triangle_count() as u64);
+        }
+
+        unsafe {
+            gl_check!(gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE));
+            StencilState::outside(1).apply();
+        }
+        let outline_scale = glm::vec3(OUTLINE_SCALE, OUTLINE_SCALE, OUTLINE_SCALE);
+        for (mesh, world_from_object) in self.outlines.drain(..) {
+            self.world_from_object_uniform
+                .set_mat4f(&glm::scale(&world_from_object, &outline_scale));
+            mesh.draw(&self.context);
+            self.stats.record_draw(mesh.triangle_count() as u64);
+        }
+
+        unsafe {
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+            StencilState::disable();
+        }
+    }
+
+    fn normalized_depth(&self, world_from_object: &Mat4) -> f32 {
+        let clip_position = self.projection_from_view
+            * self.view_from_world
+            * world_from_object
+            * glm::vec4(0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32);
+        if clip_position.w <= 0.0_f32 {
+            return 0.0_f32;
+        }
+        (clip_position.z / clip_position.w) * 0.5_f32 + 0.5_f32
+    }
+}
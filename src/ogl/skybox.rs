@@ -0,0 +1,121 @@
+use gl::types::*;
+
+use crate::math::glm;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::hdr_cubemap::HdrCubemap;
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::renderer::Renderer;
+use std::path::Path;
+
+const SKYBOX_UNIT: GLuint = 0;
+
+// One triangle covering the screen on the far plane: (-1, -1), (3, -1), (-1, 3)
+const SKYBOX_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform mat4 view_from_world;
+uniform mat4 view_from_clip;
+
+out vec3 o_direction;
+
+void main() {
+    vec2 position = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0f;
+    vec4 view_position = view_from_clip * vec4(position, 1.0f, 1.0f);
+    // Only the camera's rotation, so the sky stays infinitely far away
+    o_direction = transpose(mat3(view_from_world)) * (view_position.xyz / view_position.w);
+    gl_Position = vec4(position, 1.0f, 1.0f);
+}
+"#;
+
+const SKYBOX_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec3 o_direction;
+
+uniform samplerCube skybox;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(texture(skybox, o_direction).rgb, 1.0f);
+}
+"#;
+
+/// A cubemap drawn behind everything, wherever the scene left the depth buffer clear.
+pub struct SkyboxRenderer {
+    cubemap: HdrCubemap,
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    view_from_world_uniform: Uniform,
+    view_from_clip_uniform: Uniform,
+}
+
+impl SkyboxRenderer {
+    /// The sky of the equirectangular panorama at `hdr_path`, converted as
+    /// `HdrCubemap::from_equirect` does.
+    pub fn new(
+        context: &GlContext,
+        hdr_path: &Path,
+        size: i32,
+        cache_directory: Option<&Path>,
+    ) -> Result<SkyboxRenderer, String> {
+        unsafe {
+            let cubemap = HdrCubemap::from_equirect(context, hdr_path, size, cache_directory)?;
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                SKYBOX_VERTEX_SHADER_SOURCE,
+                SKYBOX_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("skybox");
+            let mut vertex_array_obj = 0;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "skybox");
+
+            shader_program.use_program();
+            shader_program.uniform("skybox").set_int(SKYBOX_UNIT as i32);
+            Ok(SkyboxRenderer {
+                cubemap,
+                view_from_world_uniform: shader_program.uniform("view_from_world"),
+                view_from_clip_uniform: shader_program.uniform("view_from_clip"),
+                shader_program,
+                vertex_array_obj,
+            })
+        }
+    }
+
+    /// Draws the sky seen from `camera` into the bound framebuffer, behind what is already
+    /// there.
+    pub fn draw(&self, camera: &Camera, renderer: &mut Renderer) {
+        self.shader_program.use_program();
+        self.view_from_world_uniform
+            .set_mat4f(&camera.view_matrix());
+        self.view_from_clip_uniform
+            .set_mat4f(&glm::inverse(renderer.projection_from_view()));
+        unsafe {
+            // The far plane itself passes where nothing was drawn
+            gl_check!(gl::DepthFunc(gl::LEQUAL));
+            gl_check!(gl::DepthMask(gl::FALSE));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0 + SKYBOX_UNIT));
+            gl_check!(gl::BindTexture(
+                gl::TEXTURE_CUBE_MAP,
+                self.cubemap.texture()
+            ));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0));
+            gl_check!(gl::DepthMask(gl::TRUE));
+            gl_check!(gl::DepthFunc(gl::LESS));
+        }
+        renderer.stats.record_draw(1);
+    }
+}
+
+impl Drop for SkyboxRenderer {
+    fn drop(&mut self) {
+        unsafe { live_objects::delete(GlObject::VertexArray, self.vertex_array_obj) };
+    }
+}
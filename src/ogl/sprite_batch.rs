@@ -0,0 +1,364 @@
+use gl::types::*;
+
+use crate::flipbook::UvRect;
+use crate::math::color::Color;
+use crate::math::glm::{self, Mat4, Vec2};
+use crate::ogl::buffer::Buffer;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::stats::FrameStats;
+use std::ffi::c_void;
+use std::mem;
+
+const SPRITE_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+layout (location = 2) in vec4 a_color;
+
+uniform mat4 projection_from_world;
+
+out vec2 o_tex_coords;
+out vec4 o_color;
+
+void main() {
+    gl_Position = projection_from_world * vec4(a_pos, 0.0f, 1.0f);
+    o_tex_coords = a_tex_coords;
+    o_color = a_color;
+}
+"#;
+
+const SPRITE_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform sampler2D sprite;
+
+in vec2 o_tex_coords;
+in vec4 o_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = texture(sprite, o_tex_coords) * o_color;
+}
+"#;
+
+// Floats per vertex: position, texture coordinates and sRGB color with alpha
+const VERTEX_SIZE: usize = 8;
+
+/// Orthographic camera over a 2D world measured in pixels, with y pointing down like the screen.
+/// `position` is the world point at the top left of the viewport, and `zoom` the screen pixels
+/// per world pixel, so the default camera maps world coordinates straight to framebuffer pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera2d {
+    pub position: Vec2,
+    pub zoom: f32,
+}
+
+/// A rectangle, textured or flat, drawn by `SpriteBatch`.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    /// Top left corner, in world pixels
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Turn around the center, clockwise on screen
+    pub rotation_radians: f32,
+    /// Tints the texture
+    pub color: Color,
+    /// Texture object to draw, or 0 for a flat rectangle of `color`
+    pub texture: GLuint,
+    /// Part of the texture shown, such as a flipbook frame
+    pub uv_rect: UvRect,
+    /// Lower layers are drawn first, and sprites within a layer grouped by texture
+    pub layer: i32,
+}
+
+/// Collects sprites over a frame and draws them with one call per texture: `draw` sorts them by
+/// layer and then texture, keeping the order they were added in otherwise, uploads them into a
+/// single buffer and draws each run sharing a texture together. Sprites are alpha blended over
+/// whatever is in the framebuffer, without depth testing.
+pub struct SpriteBatch {
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    vertex_buffer: Buffer,
+    /// Stands in for the texture of flat sprites, so they share the textured path
+    white_texture: GLuint,
+    sprites: Vec<Sprite>,
+    vertices: Vec<f32>,
+    /// Texture and sprite count of each draw call
+    runs: Vec<(GLuint, usize)>,
+    projection_from_world_uniform: Uniform,
+    context: GlContext,
+}
+
+impl Camera2d {
+    /// World pixels straight to framebuffer pixels.
+    pub fn new() -> Camera2d {
+        Camera2d {
+            position: glm::vec2(0.0_f32, 0.0_f32),
+            zoom: 1.0_f32,
+        }
+    }
+
+    /// Shows the `size` area from the world origin as large as it fits a viewport of
+    /// `viewport_size` pixels, centered with bars along the sides left over.
+    pub fn fitting(size: Vec2, viewport_size: (i32, i32)) -> Camera2d {
+        let viewport = glm::vec2(viewport_size.0 as f32, viewport_size.1 as f32);
+        let zoom = (viewport.x / size.x)
+            .min(viewport.y / size.y)
+            .max(0.0001_f32);
+        Camera2d {
+            position: (size - viewport / zoom) * 0.5_f32,
+            zoom,
+        }
+    }
+
+    pub fn projection_from_world(&self, viewport_size: (i32, i32)) -> Mat4 {
+        let width = viewport_size.0 as f32 / self.zoom;
+        let height = viewport_size.1 as f32 / self.zoom;
+        glm::ortho(
+            self.position.x,
+            self.position.x + width,
+            self.position.y + height,
+            self.position.y,
+            -1.0_f32,
+            1.0_f32,
+        )
+    }
+
+    /// The world point under `screen_position`, in framebuffer pixels from the top left.
+    #[allow(dead_code)]
+    pub fn world_from_screen(&self, screen_position: Vec2) -> Vec2 {
+        self.position + screen_position / self.zoom
+    }
+}
+
+impl Sprite {
+    /// A white rectangle `size` pixels across with its top left corner at `position`, on layer 0.
+    pub fn new(position: Vec2, size: Vec2) -> Sprite {
+        Sprite {
+            position,
+            size,
+            rotation_radians: 0.0_f32,
+            color: Color::WHITE,
+            texture: 0,
+            uv_rect: UvRect::FULL,
+            layer: 0,
+        }
+    }
+
+    pub fn with_texture(self, texture: GLuint) -> Sprite {
+        Sprite { texture, ..self }
+    }
+
+    pub fn with_color(self, color: Color) -> Sprite {
+        Sprite { color, ..self }
+    }
+
+    pub fn with_uv_rect(self, uv_rect: UvRect) -> Sprite {
+        Sprite { uv_rect, ..self }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_rotation(self, rotation_radians: f32) -> Sprite {
+        Sprite {
+            rotation_radians,
+            ..self
+        }
+    }
+
+    pub fn with_layer(self, layer: i32) -> Sprite {
+        Sprite { layer, ..self }
+    }
+}
+
+impl SpriteBatch {
+    pub fn new(context: &GlContext) -> Result<SpriteBatch, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                SPRITE_VERTEX_SHADER_SOURCE,
+                SPRITE_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("sprites");
+            let vertex_buffer = Buffer::new(context, gl::STREAM_DRAW);
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer.id));
+            let stride = (VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_pos, a_tex_coords and a_color attributes
+            let mut offset = 0;
+            for (location, components) in [(0, 2), (1, 2), (2, 4)].iter() {
+                gl_check!(gl::VertexAttribPointer(
+                    *location,
+                    *components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (offset * mem::size_of::<GLfloat>()) as *const c_void,
+                ));
+                gl_check!(gl::EnableVertexAttribArray(*location));
+                offset += *components as usize;
+            }
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "sprites");
+            vertex_buffer.set_label(context, "sprite vertices");
+
+            let mut white_texture = 0;
+            gl_check!(gl::GenTextures(1, &mut white_texture));
+            live_objects::created(GlObject::Texture, white_texture);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, white_texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                1,
+                1,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                [255_u8; 4].as_ptr() as *const c_void,
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::NEAREST as GLint
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            gpu_memory::track(
+                GpuResource::Texture,
+                white_texture,
+                gpu_memory::texture_bytes(1, 1, 1, 4, false),
+            );
+            debug_label::set(gl::TEXTURE, white_texture, "sprite white");
+
+            shader_program.use_program();
+            shader_program.uniform("sprite").set_int(0);
+            let projection_from_world_uniform = shader_program.uniform("projection_from_world");
+            Ok(SpriteBatch {
+                shader_program,
+                vertex_array_obj,
+                vertex_buffer,
+                white_texture,
+                sprites: vec![],
+                vertices: vec![],
+                runs: vec![],
+                projection_from_world_uniform,
+                context: *context,
+            })
+        }
+    }
+
+    /// Queues `sprite` for the next `draw`.
+    pub fn add(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    /// Draws and clears the queued sprites, seen through `camera` in a viewport of
+    /// `viewport_size` pixels.
+    pub fn draw(&mut self, camera: &Camera2d, viewport_size: (i32, i32), stats: &mut FrameStats) {
+        if self.sprites.is_empty() {
+            return;
+        }
+        // Stable, so overlapping sprites sharing a layer and texture keep their order
+        self.sprites
+            .sort_by_key(|sprite| (sprite.layer, sprite.texture));
+        self.fill_vertices();
+        self.shader_program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(&camera.projection_from_world(viewport_size));
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+            self.vertex_buffer.set_data(&self.context, &self.vertices);
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            let mut first_sprite = 0;
+            for &(texture, sprite_count) in self.runs.iter() {
+                let texture = if texture == 0 {
+                    self.white_texture
+                } else {
+                    texture
+                };
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+                gl_check!(gl::DrawArrays(
+                    gl::TRIANGLES,
+                    (first_sprite * 6) as GLint,
+                    (sprite_count * 6) as GLsizei
+                ));
+                stats.record_draw(2 * sprite_count as u64);
+                first_sprite += sprite_count;
+            }
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            gl_check!(gl::Disable(gl::BLEND));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+        self.sprites.clear();
+    }
+
+    /// Fills `vertices` with the sorted sprites, two triangles each, and `runs` with the draw
+    /// calls they split into.
+    fn fill_vertices(&mut self) {
+        self.vertices.clear();
+        self.runs.clear();
+        for sprite in self.sprites.iter() {
+            match self.runs.last_mut() {
+                Some((texture, sprite_count)) if *texture == sprite.texture => *sprite_count += 1,
+                _ => self.runs.push((sprite.texture, 1)),
+            }
+            let half_size = sprite.size * 0.5_f32;
+            let center = sprite.position + half_size;
+            let (sin, cos) = sprite.rotation_radians.sin_cos();
+            let [red, green, blue, alpha] = sprite.color.to_srgb();
+            let uv = &sprite.uv_rect;
+            // Texture coordinates go up while world y goes down, so the top corners take the
+            // top of the texture
+            let corner = |x: f32, y: f32| {
+                let offset = glm::vec2(x * half_size.x, y * half_size.y);
+                let position = center
+                    + glm::vec2(
+                        cos * offset.x - sin * offset.y,
+                        sin * offset.x + cos * offset.y,
+                    );
+                [
+                    position.x,
+                    position.y,
+                    uv.offset[0] + (x + 1.0_f32) * 0.5_f32 * uv.size[0],
+                    uv.offset[1] + (1.0_f32 - y) * 0.5_f32 * uv.size[1],
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                ]
+            };
+            let (top_left, top_right) = (corner(-1.0_f32, -1.0_f32), corner(1.0_f32, -1.0_f32));
+            let (bottom_left, bottom_right) = (corner(-1.0_f32, 1.0_f32), corner(1.0_f32, 1.0_f32));
+            for vertex in [
+                top_left,
+                bottom_left,
+                bottom_right,
+                bottom_right,
+                top_right,
+                top_left,
+            ]
+            .iter()
+            {
+                self.vertices.extend_from_slice(vertex);
+            }
+        }
+    }
+}
+
+impl Drop for SpriteBatch {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::VertexArray, self.vertex_array_obj);
+        live_objects::release(&self.context, GlObject::Texture, self.white_texture);
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameCounters {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub state_changes: u32,
+    /// Objects tested against the view frustum that were drawn or skipped
+    pub visible_objects: u32,
+    pub culled_objects: u32,
+    /// Objects inside the frustum skipped because their last occlusion query found them hidden
+    pub occluded_objects: u32,
+}
+
+/// Frame times in seconds over the retained history.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameTimeSummary {
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub p99: f32,
+}
+
+pub struct FrameStats {
+    history: usize,
+    frame_times: VecDeque<f32>,
+    current: FrameCounters,
+    last_frame: FrameCounters,
+    gpu_pass_names: Vec<String>,
+    gpu_pass_times: VecDeque<Vec<f32>>,
+}
+
+impl FrameStats {
+    pub fn new(history: usize) -> FrameStats {
+        FrameStats {
+            history,
+            frame_times: VecDeque::with_capacity(history),
+            current: FrameCounters::default(),
+            last_frame: FrameCounters::default(),
+            gpu_pass_names: vec![],
+            gpu_pass_times: VecDeque::with_capacity(history),
+        }
+    }
+
+    /// Closes out the counters of the previous frame and starts counting a new one.
+    pub fn begin_frame(&mut self) {
+        self.last_frame = self.current;
+        self.current = FrameCounters::default();
+    }
+
+    pub fn record_frame_time(&mut self, frame_time: f32) {
+        if self.frame_times.len() == self.history {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// GPU time in seconds of every timed pass, in the order the passes were first seen.
+    pub fn record_gpu_pass_times(&mut self, pass_times: Vec<(String, f32)>) {
+        let mut frame_times = Vec::with_capacity(pass_times.len());
+        for (name, time) in pass_times {
+            if !self.gpu_pass_names.contains(&name) {
+                self.gpu_pass_names.push(name);
+            }
+            frame_times.push(time);
+        }
+        if self.gpu_pass_times.len() == self.history {
+            self.gpu_pass_times.pop_front();
+        }
+        self.gpu_pass_times.push_back(frame_times);
+    }
+
+    pub fn record_draw(&mut self, triangles: u64) {
+        self.current.draw_calls += 1;
+        self.current.triangles += triangles;
+    }
+
+    pub fn record_state_change(&mut self) {
+        self.current.state_changes += 1;
+    }
+
+    pub fn record_culling(&mut self, visible: bool) {
+        if visible {
+            self.current.visible_objects += 1;
+        } else {
+            self.current.culled_objects += 1;
+        }
+    }
+
+    pub fn record_occlusion(&mut self) {
+        self.current.occluded_objects += 1;
+    }
+
+    /// Counters of the last completed frame.
+    pub fn counters(&self) -> FrameCounters {
+        self.last_frame
+    }
+
+    pub fn frame_times(&self) -> impl Iterator<Item = &f32> {
+        self.frame_times.iter()
+    }
+
+    #[allow(dead_code)]
+    pub fn gpu_pass_names(&self) -> &[String] {
+        &self.gpu_pass_names
+    }
+
+    /// Per-frame GPU pass times, indexed like `gpu_pass_names`.
+    pub fn gpu_pass_times(&self) -> impl Iterator<Item = &Vec<f32>> {
+        self.gpu_pass_times.iter()
+    }
+
+    /// Latest collected GPU time of each pass.
+    pub fn last_gpu_pass_times(&self) -> Vec<(&str, f32)> {
+        match self.gpu_pass_times.back() {
+            Some(times) => self
+                .gpu_pass_names
+                .iter()
+                .map(|name| name.as_str())
+                .zip(times.iter().copied())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn history(&self) -> usize {
+        self.history
+    }
+
+    pub fn summary(&self) -> FrameTimeSummary {
+        if self.frame_times.is_empty() {
+            return FrameTimeSummary::default();
+        }
+        let mut sorted: Vec<f32> = self.frame_times.iter().copied().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let p99_i = ((sorted.len() as f32 * 0.99_f32).ceil() as usize).max(1) - 1;
+        FrameTimeSummary {
+            min: sorted[0],
+            avg: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            max: sorted[sorted.len() - 1],
+            p99: sorted[p99_i],
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use gl::types::*;
+
+/// Stencil test and update settings, applied together so a pass cannot inherit half of the
+/// previous pass's state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StencilState {
+    pub func: GLenum,
+    pub reference: GLint,
+    pub read_mask: GLuint,
+    pub write_mask: GLuint,
+    pub stencil_fail: GLenum,
+    pub depth_fail: GLenum,
+    pub pass: GLenum,
+}
+
+impl StencilState {
+    /// Writes `reference` wherever fragments pass the depth test.
+    pub fn mark(reference: GLint) -> StencilState {
+        StencilState {
+            func: gl::ALWAYS,
+            reference,
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+            stencil_fail: gl::KEEP,
+            depth_fail: gl::KEEP,
+            pass: gl::REPLACE,
+        }
+    }
+
+    /// Passes only where the stencil holds `reference`, leaving it as it is.
+    pub fn inside(reference: GLint) -> StencilState {
+        StencilState {
+            func: gl::EQUAL,
+            write_mask: 0x00,
+            pass: gl::KEEP,
+            ..StencilState::mark(reference)
+        }
+    }
+
+    /// Passes only where the stencil does not hold `reference`, leaving it as it is.
+    pub fn outside(reference: GLint) -> StencilState {
+        StencilState {
+            func: gl::NOTEQUAL,
+            ..StencilState::inside(reference)
+        }
+    }
+
+    /// Enables the stencil test with these settings.
+    pub unsafe fn apply(&self) {
+        gl_check!(gl::Enable(gl::STENCIL_TEST));
+        gl_check!(gl::StencilFunc(self.func, self.reference, self.read_mask));
+        gl_check!(gl::StencilOp(self.stencil_fail, self.depth_fail, self.pass));
+        gl_check!(gl::StencilMask(self.write_mask));
+    }
+
+    /// Disables the stencil test, leaving the stencil writable again so clears reach it.
+    pub unsafe fn disable() {
+        gl_check!(gl::StencilMask(0xFF));
+        gl_check!(gl::Disable(gl::STENCIL_TEST));
+    }
+}
@@ -0,0 +1,615 @@
+use gl::types::*;
+
+use crate::math::color::Color;
+use crate::math::frustum::Frustum;
+use crate::math::glm;
+use crate::math::noise::{Fbm, Noise};
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::renderer::Renderer;
+use crate::terrain::Terrain;
+use std::ffi::c_void;
+
+const HEIGHTMAP_UNIT: GLuint = 0;
+const NORMAL_MAP_UNIT: GLuint = 1;
+const LAYERS_UNIT: GLuint = 2;
+// Width and height of each detail layer
+const LAYER_SIZE: usize = 256;
+// World units one detail layer repeat covers
+const LAYER_TILING: f32 = 4.0_f32;
+// Tessellation levels of patch edges at the camera and from `TESSELLATION_FALLOFF` away on
+const MAX_TESSELLATION: f32 = 64.0_f32;
+const MIN_TESSELLATION: f32 = 2.0_f32;
+const TESSELLATION_FALLOFF: f32 = 80.0_f32;
+
+// Maps world x and z to heightmap and normal map coordinates, hitting texel centers at samples
+const TERRAIN_UV_SOURCE: &str = r#"
+uniform float terrain_size;
+uniform float terrain_samples;
+
+vec2 terrain_uv(vec2 xz) {
+    vec2 sample_position = (xz / terrain_size + 0.5f) * (terrain_samples - 1.0f);
+    return (sample_position + 0.5f) / terrain_samples;
+}
+"#;
+
+const TERRAIN_POSITION_SOURCE: &str = r#"
+uniform sampler2D heightmap;
+uniform vec2 patch_origin;
+uniform float patch_size;
+
+vec3 terrain_position(vec2 patch_uv) {
+    vec2 xz = patch_origin + patch_uv * patch_size;
+    return vec3(xz.x, texture(heightmap, terrain_uv(xz)).r, xz.y);
+}
+"#;
+
+// Corners are computed from the patch uniforms, so patches need no vertex data
+#[cfg(feature = "gl41")]
+const PATCH_VERTEX_SHADER_SOURCE: &str = r#"
+#version 410 core
+void main() {
+    gl_Position = vec4(0.0f);
+}
+"#;
+
+#[cfg(feature = "gl41")]
+const PATCH_CONTROL_SHADER_SOURCE: &str = r#"
+#version 410 core
+layout (vertices = 4) out;
+
+uniform vec3 camera_position;
+uniform float min_tessellation;
+uniform float max_tessellation;
+uniform float tessellation_falloff;
+"#;
+
+// Levels depend on edge midpoints alone, so neighboring patches agree on their shared edges and
+// no cracks open between them
+#[cfg(feature = "gl41")]
+const PATCH_CONTROL_SHADER_MAIN: &str = r#"
+float edge_level(vec2 midpoint) {
+    float distance_to_camera = distance(camera_position, terrain_position(midpoint));
+    float t = clamp(distance_to_camera / tessellation_falloff, 0.0f, 1.0f);
+    return mix(max_tessellation, min_tessellation, t);
+}
+
+void main() {
+    gl_out[gl_InvocationID].gl_Position = gl_in[gl_InvocationID].gl_Position;
+    if (gl_InvocationID == 0) {
+        gl_TessLevelOuter[0] = edge_level(vec2(0.0f, 0.5f));
+        gl_TessLevelOuter[1] = edge_level(vec2(0.5f, 0.0f));
+        gl_TessLevelOuter[2] = edge_level(vec2(1.0f, 0.5f));
+        gl_TessLevelOuter[3] = edge_level(vec2(0.5f, 1.0f));
+        gl_TessLevelInner[0] = max(gl_TessLevelOuter[1], gl_TessLevelOuter[3]);
+        gl_TessLevelInner[1] = max(gl_TessLevelOuter[0], gl_TessLevelOuter[2]);
+    }
+}
+"#;
+
+#[cfg(feature = "gl41")]
+const PATCH_EVALUATION_SHADER_SOURCE: &str = r#"
+#version 410 core
+layout (quads, fractional_even_spacing, ccw) in;
+
+uniform mat4 projection_from_world;
+uniform vec4 clip_plane;
+
+out vec3 o_world_position;
+"#;
+
+#[cfg(feature = "gl41")]
+const PATCH_EVALUATION_SHADER_MAIN: &str = r#"
+void main() {
+    o_world_position = terrain_position(gl_TessCoord.xy);
+    gl_Position = projection_from_world * vec4(o_world_position, 1.0f);
+    gl_ClipDistance[0] = dot(vec4(o_world_position, 1.0f), clip_plane);
+}
+"#;
+
+// Without tessellation every patch is a fixed grid, one cell per heightmap sample
+const GRID_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+#ifdef GL_ES
+precision highp sampler2D;
+#endif
+
+uniform mat4 projection_from_world;
+uniform int grid_resolution;
+uniform vec4 clip_plane;
+
+out vec3 o_world_position;
+"#;
+
+const GRID_VERTEX_SHADER_MAIN: &str = r#"
+const ivec2 corners[6] = ivec2[6](
+    ivec2(0, 0), ivec2(0, 1), ivec2(1, 0),
+    ivec2(1, 0), ivec2(0, 1), ivec2(1, 1));
+
+void main() {
+    int cell = gl_VertexID / 6;
+    ivec2 grid_position = ivec2(cell % grid_resolution, cell / grid_resolution)
+        + corners[gl_VertexID % 6];
+    o_world_position = terrain_position(vec2(grid_position) / float(grid_resolution));
+    gl_Position = projection_from_world * vec4(o_world_position, 1.0f);
+#ifndef GL_ES
+    gl_ClipDistance[0] = dot(vec4(o_world_position, 1.0f), clip_plane);
+#endif
+}
+"#;
+
+const TERRAIN_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+#ifdef GL_ES
+precision highp sampler2DArray;
+#endif
+in vec3 o_world_position;
+
+uniform sampler2D normal_map;
+// Grass, rock and snow
+uniform sampler2DArray layers;
+uniform float layer_tiling;
+uniform float max_height;
+uniform vec3 light_direction;
+uniform vec4 clip_plane;
+
+out vec4 frag_color;
+"#;
+
+const TERRAIN_FRAGMENT_SHADER_MAIN: &str = r#"
+void main() {
+#ifdef GL_ES
+    // ES has no clip distances
+    if (dot(vec4(o_world_position, 1.0f), clip_plane) < 0.0f) {
+        discard;
+    }
+#endif
+    vec3 normal = normalize(texture(normal_map, terrain_uv(o_world_position.xz)).xyz * 2.0f - 1.0f);
+    vec2 layer_uv = o_world_position.xz / layer_tiling;
+    vec3 grass = texture(layers, vec3(layer_uv, 0.0f)).rgb;
+    vec3 rock = texture(layers, vec3(layer_uv, 1.0f)).rgb;
+    vec3 snow = texture(layers, vec3(layer_uv, 2.0f)).rgb;
+
+    // Rock shows through on high ground and on slopes too steep for grass; snow only settles on
+    // high ground that is not too steep
+    float height = o_world_position.y / max_height;
+    float steepness = 1.0f - normal.y;
+    float rock_weight = max(smoothstep(0.3f, 0.5f, height), smoothstep(0.2f, 0.35f, steepness));
+    float snow_weight = smoothstep(0.65f, 0.8f, height) * (1.0f - smoothstep(0.3f, 0.45f, steepness));
+    vec3 color = mix(mix(grass, rock, rock_weight), snow, snow_weight);
+
+    float diffuse = max(dot(normal, light_direction), 0.0f);
+    frag_color = vec4(color * (0.25f + 0.75f * diffuse), 1.0f);
+}
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerrainMode {
+    /// Tessellation shaders subdivide each patch by its distance from the camera
+    #[cfg_attr(not(feature = "gl41"), allow(dead_code))]
+    Tessellated,
+    /// Every patch is drawn at the heightmap's full resolution
+    Grid,
+}
+
+/// Draws a `Terrain` patch by patch, skipping patches outside the renderer's culling frustum.
+/// Heights and normals come from textures, so neither mode keeps any vertex data.
+pub struct TerrainRenderer {
+    pub mode: TerrainMode,
+    shader_program: ShaderProgram,
+    /// Empty, but drawing needs one bound
+    vertex_array_obj: GLuint,
+    heightmap: GLuint,
+    normal_map: GLuint,
+    layers: GLuint,
+    grid_resolution: i32,
+    projection_from_world_uniform: Uniform,
+    patch_origin_uniform: Uniform,
+    camera_position_uniform: Uniform,
+    clip_plane_uniform: Uniform,
+}
+
+impl TerrainMode {
+    /// Tessellated where OpenGL 4.0 allows, the fixed grid otherwise.
+    pub fn detect() -> TerrainMode {
+        #[cfg(feature = "gl41")]
+        {
+            if capabilities::tessellation_shaders() {
+                return TerrainMode::Tessellated;
+            }
+        }
+        TerrainMode::Grid
+    }
+}
+
+impl TerrainRenderer {
+    pub fn new(
+        context: &GlContext,
+        mode: TerrainMode,
+        terrain: &Terrain,
+    ) -> Result<TerrainRenderer, String> {
+        unsafe {
+            let fragment_shader_source = [
+                TERRAIN_FRAGMENT_SHADER_SOURCE,
+                TERRAIN_UV_SOURCE,
+                TERRAIN_FRAGMENT_SHADER_MAIN,
+            ]
+            .concat();
+            let shader_program = match mode {
+                #[cfg(feature = "gl41")]
+                TerrainMode::Tessellated => ShaderProgram::with_tessellation_shaders(
+                    context,
+                    PATCH_VERTEX_SHADER_SOURCE,
+                    &[
+                        PATCH_CONTROL_SHADER_SOURCE,
+                        TERRAIN_UV_SOURCE,
+                        TERRAIN_POSITION_SOURCE,
+                        PATCH_CONTROL_SHADER_MAIN,
+                    ]
+                    .concat(),
+                    &[
+                        PATCH_EVALUATION_SHADER_SOURCE,
+                        TERRAIN_UV_SOURCE,
+                        TERRAIN_POSITION_SOURCE,
+                        PATCH_EVALUATION_SHADER_MAIN,
+                    ]
+                    .concat(),
+                    &fragment_shader_source,
+                )?,
+                #[cfg(not(feature = "gl41"))]
+                TerrainMode::Tessellated => {
+                    return Err("Tessellated terrain needs the gl41 feature".to_string())
+                }
+                TerrainMode::Grid => ShaderProgram::with_shaders(
+                    context,
+                    &[
+                        GRID_VERTEX_SHADER_SOURCE,
+                        TERRAIN_UV_SOURCE,
+                        TERRAIN_POSITION_SOURCE,
+                        GRID_VERTEX_SHADER_MAIN,
+                    ]
+                    .concat(),
+                    &fragment_shader_source,
+                )?,
+            };
+            shader_program.set_label("terrain");
+            let mut vertex_array_obj = 0;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "terrain");
+
+            let heightmap_data = &terrain.heightmap;
+            let samples = heightmap_data.samples;
+            let heightmap = upload_texture_2d(
+                gl::R32F,
+                gl::RED,
+                gl::FLOAT,
+                samples,
+                heightmap_data.heights().as_ptr() as *const c_void,
+                4,
+            );
+            let normals: Vec<[u8; 4]> = (0..samples * samples)
+                .map(|i| {
+                    let normal = heightmap_data.normal(i % samples, i / samples);
+                    let encode =
+                        |value: f32| ((value * 0.5_f32 + 0.5_f32) * 255.0_f32).round() as u8;
+                    [encode(normal.x), encode(normal.y), encode(normal.z), 255]
+                })
+                .collect();
+            let normal_map = upload_texture_2d(
+                gl::RGBA8,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                samples,
+                normals.as_ptr() as *const c_void,
+                4,
+            );
+            let layers = build_layers();
+            debug_label::set(gl::TEXTURE, heightmap, "terrain heightmap");
+            debug_label::set(gl::TEXTURE, normal_map, "terrain normal map");
+            debug_label::set(gl::TEXTURE, layers, "terrain layers");
+
+            let max_height = heightmap_data
+                .heights()
+                .iter()
+                .copied()
+                .fold(0.0_f32, f32::max);
+            let grid_resolution = ((samples - 1) / terrain.patches_per_side) as i32;
+            shader_program.use_program();
+            shader_program
+                .uniform("heightmap")
+                .set_int(HEIGHTMAP_UNIT as i32);
+            shader_program
+                .uniform("normal_map")
+                .set_int(NORMAL_MAP_UNIT as i32);
+            shader_program.uniform("layers").set_int(LAYERS_UNIT as i32);
+            shader_program
+                .uniform("layer_tiling")
+                .set_float(LAYER_TILING);
+            shader_program.uniform("max_height").set_float(max_height);
+            let light_direction = glm::normalize(&glm::vec3(0.4_f32, 1.0_f32, 0.3_f32));
+            shader_program.uniform("light_direction").set_vec3f([
+                light_direction.x,
+                light_direction.y,
+                light_direction.z,
+            ]);
+            shader_program
+                .uniform("terrain_size")
+                .set_float(heightmap_data.size);
+            shader_program
+                .uniform("terrain_samples")
+                .set_float(samples as f32);
+            shader_program
+                .uniform("patch_size")
+                .set_float(terrain.patch_size());
+            shader_program
+                .uniform("grid_resolution")
+                .set_int(grid_resolution);
+            shader_program
+                .uniform("min_tessellation")
+                .set_float(MIN_TESSELLATION);
+            shader_program
+                .uniform("max_tessellation")
+                .set_float(MAX_TESSELLATION);
+            shader_program
+                .uniform("tessellation_falloff")
+                .set_float(TESSELLATION_FALLOFF);
+
+            let projection_from_world_uniform = shader_program.uniform("projection_from_world");
+            let patch_origin_uniform = shader_program.uniform("patch_origin");
+            let camera_position_uniform = shader_program.uniform("camera_position");
+            let clip_plane_uniform = shader_program.uniform("clip_plane");
+            Ok(TerrainRenderer {
+                mode,
+                shader_program,
+                vertex_array_obj,
+                heightmap,
+                normal_map,
+                layers,
+                grid_resolution,
+                projection_from_world_uniform,
+                patch_origin_uniform,
+                camera_position_uniform,
+                clip_plane_uniform,
+            })
+        }
+    }
+
+    /// Draws the patches of `terrain` seen by `camera`, culled against its frustum (or the frozen
+    /// one) and outlined when the renderer draws wireframes, which shows where tessellation
+    /// levels change. With `clip_plane` only what lies on its positive side is drawn.
+    pub fn draw(
+        &self,
+        terrain: &Terrain,
+        camera: &Camera,
+        clip_plane: Option<[f32; 4]>,
+        renderer: &mut Renderer,
+    ) {
+        let projection_from_world = renderer.projection_from_view() * camera.view_matrix();
+        // Culled against `camera` itself, which need not be the renderer's
+        let frustum = if renderer.culling_frozen() {
+            renderer.culling_frustum().clone()
+        } else {
+            Frustum::from_matrix(&projection_from_world)
+        };
+        self.shader_program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(&projection_from_world);
+        // Without a plane everything is kept
+        self.clip_plane_uniform
+            .set_vec4f(clip_plane.unwrap_or([0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32]));
+        let clipped = clip_plane.is_some() && !capabilities::opengl_es();
+        self.camera_position_uniform.set_vec3f([
+            camera.position.x,
+            camera.position.y,
+            camera.position.z,
+        ]);
+        let wireframe = renderer.wireframe();
+        unsafe {
+            for (unit, target, texture) in [
+                (HEIGHTMAP_UNIT, gl::TEXTURE_2D, self.heightmap),
+                (NORMAL_MAP_UNIT, gl::TEXTURE_2D, self.normal_map),
+                (LAYERS_UNIT, gl::TEXTURE_2D_ARRAY, self.layers),
+            ]
+            .iter()
+            {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + unit));
+                gl_check!(gl::BindTexture(*target, *texture));
+            }
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            if clipped {
+                gl_check!(gl::Enable(gl::CLIP_DISTANCE0));
+            }
+            if wireframe {
+                gl_check!(gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE));
+            }
+            #[cfg(feature = "gl41")]
+            {
+                if self.mode == TerrainMode::Tessellated {
+                    gl_check!(gl::PatchParameteri(gl::PATCH_VERTICES, 4));
+                }
+            }
+            for patch in terrain.patches.iter() {
+                let visible = !renderer.frustum_culling || frustum.is_aabb_visible(&patch.bounds);
+                renderer.stats.record_culling(visible);
+                if !visible {
+                    continue;
+                }
+                self.patch_origin_uniform.set_vec2f(patch.origin);
+                match self.mode {
+                    #[cfg(feature = "gl41")]
+                    TerrainMode::Tessellated => {
+                        gl_check!(gl::DrawArrays(gl::PATCHES, 0, 4));
+                        // The triangles are generated on the GPU, so there are none to count
+                        renderer.stats.record_draw(0);
+                    }
+                    #[cfg(not(feature = "gl41"))]
+                    TerrainMode::Tessellated => {}
+                    TerrainMode::Grid => {
+                        let cells = self.grid_resolution * self.grid_resolution;
+                        gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 6 * cells));
+                        renderer.stats.record_draw(2 * cells as u64);
+                    }
+                }
+            }
+            if wireframe {
+                gl_check!(gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL));
+            }
+            if clipped {
+                gl_check!(gl::Disable(gl::CLIP_DISTANCE0));
+            }
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        }
+    }
+}
+
+impl Drop for TerrainRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete_all(
+                GlObject::Texture,
+                &[self.heightmap, self.normal_map, self.layers],
+            );
+        }
+    }
+}
+
+/// Square texture of `size` texels per side, linearly filtered and clamped to its edges.
+/// `R32F` is not filterable on ES, but there samples are only ever read at texel centers.
+unsafe fn upload_texture_2d(
+    internal_format: GLenum,
+    format: GLenum,
+    component_type: GLenum,
+    size: usize,
+    data: *const c_void,
+    bytes_per_pixel: usize,
+) -> GLuint {
+    let mut texture = 0;
+    gl_check!(gl::GenTextures(1, &mut texture));
+    live_objects::created(GlObject::Texture, texture);
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+    gl_check!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1));
+    gl_check!(gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        internal_format as GLint,
+        size as GLsizei,
+        size as GLsizei,
+        0,
+        format,
+        component_type,
+        data,
+    ));
+    gl_check!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4));
+    let filter = if internal_format == gl::R32F && capabilities::opengl_es() {
+        gl::NEAREST
+    } else {
+        gl::LINEAR
+    };
+    for (parameter, value) in [
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_MIN_FILTER, filter),
+        (gl::TEXTURE_MAG_FILTER, filter),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        texture,
+        gpu_memory::texture_bytes(size as u32, size as u32, 1, bytes_per_pixel, false),
+    );
+    texture
+}
+
+/// Grass, rock and snow detail textures, mottled with noise, as the layers of an array texture.
+unsafe fn build_layers() -> GLuint {
+    let palettes = [
+        (
+            Color::from_srgb(0.22, 0.38, 0.12, 1.0),
+            Color::from_srgb(0.36, 0.52, 0.2, 1.0),
+        ),
+        (
+            Color::from_srgb(0.36, 0.33, 0.3, 1.0),
+            Color::from_srgb(0.55, 0.52, 0.48, 1.0),
+        ),
+        (
+            Color::from_srgb(0.85, 0.88, 0.92, 1.0),
+            Color::from_srgb(1.0, 1.0, 1.0, 1.0),
+        ),
+    ];
+    let noise = Noise::new(0);
+    let fbm = Fbm::default();
+    // Whole noise periods per layer, so the layers tile seamlessly
+    let period = 8.0_f32;
+    let mut texels: Vec<[u8; 4]> = Vec::with_capacity(palettes.len() * LAYER_SIZE * LAYER_SIZE);
+    for (layer, (dark, light)) in palettes.iter().enumerate() {
+        for row in 0..LAYER_SIZE {
+            for column in 0..LAYER_SIZE {
+                let x = column as f32 * period / LAYER_SIZE as f32;
+                let y = row as f32 * period / LAYER_SIZE as f32;
+                let value = noise.fbm3(x, y, layer as f32 * 10.0_f32, &fbm) * 0.5_f32 + 0.5_f32;
+                texels.push(dark.lerp(light, value.clamp(0.0_f32, 1.0_f32)).to_srgba8());
+            }
+        }
+    }
+    let mut layers = 0;
+    gl_check!(gl::GenTextures(1, &mut layers));
+    live_objects::created(GlObject::Texture, layers);
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, layers));
+    gl_check!(gl::TexImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0,
+        gl::RGBA8 as GLint,
+        LAYER_SIZE as GLsizei,
+        LAYER_SIZE as GLsizei,
+        palettes.len() as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        texels.as_ptr() as *const c_void,
+    ));
+    for (parameter, value) in [
+        (gl::TEXTURE_WRAP_S, gl::REPEAT),
+        (gl::TEXTURE_WRAP_T, gl::REPEAT),
+        (gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        layers,
+        gpu_memory::texture_bytes(
+            LAYER_SIZE as u32,
+            LAYER_SIZE as u32,
+            palettes.len() as u32,
+            4,
+            true,
+        ),
+    );
+    layers
+}
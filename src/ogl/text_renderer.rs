@@ -0,0 +1,310 @@
+use gl::types::*;
+
+use crate::math::glm;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+use std::path::Path;
+use std::{mem, ptr};
+
+const TEXT_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+layout (location = 2) in vec4 a_color;
+
+uniform mat4 projection_from_screen;
+
+out vec2 o_tex_coords;
+out vec4 o_color;
+
+void main() {
+    gl_Position = projection_from_screen * vec4(a_pos, 0.0f, 1.0f);
+    o_tex_coords = a_tex_coords;
+    o_color = a_color;
+}
+"#;
+
+const TEXT_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform sampler2D font_atlas;
+
+in vec2 o_tex_coords;
+in vec4 o_color;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(o_color.rgb, o_color.a * texture(font_atlas, o_tex_coords).a);
+}
+"#;
+
+pub const DEFAULT_FONT_ATLAS: &str = "resources/fonts/dejavu_sans_mono_16x32.png";
+// Web builds have no file system, and the console and HUD can't do without the default atlas
+#[cfg(target_arch = "wasm32")]
+const DEFAULT_FONT_ATLAS_IMAGE: &[u8] =
+    include_bytes!("../../resources/fonts/dejavu_sans_mono_16x32.png");
+
+// The atlas is a grid of equally sized cells holding ASCII 32 (space) to 127, row by row
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = 6;
+const FIRST_CHAR: u8 = b' ';
+// Floats per vertex: position, texture coordinates and color
+const VERTEX_SIZE: usize = 8;
+
+/// Draws ASCII text from a monospaced bitmap font atlas as screen-space quads. Text is collected
+/// with `add_text` and drawn in one call by `draw`, over whatever is in the framebuffer.
+pub struct TextRenderer {
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    vertex_buffer_obj: GLuint,
+    atlas_texture: GLuint,
+    /// Size in pixels of one glyph cell at scale 1
+    pub cell_size: (f32, f32),
+    projection_from_screen_uniform: Uniform,
+    vertices: Vec<f32>,
+}
+
+impl TextRenderer {
+    /// Loads an atlas image whose alpha channel holds glyph coverage.
+    pub fn new(context: &GlContext, atlas_file: &str) -> Result<TextRenderer, String> {
+        unsafe {
+            let atlas = open_atlas(atlas_file)
+                .map_err(|e| format!("Failed loading font atlas {}: {}", atlas_file, e))?
+                .into_rgba();
+            let (atlas_width, atlas_height) = atlas.dimensions();
+
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                TEXT_VERTEX_SHADER_SOURCE,
+                TEXT_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label(atlas_file);
+            let (mut vertex_array_obj, mut vertex_buffer_obj) = (0_u32, 0_u32);
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::GenBuffers(1, &mut vertex_buffer_obj));
+            live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_obj));
+            let stride = (VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_pos attribute
+            gl_check!(gl::VertexAttribPointer(
+                0,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                ptr::null()
+            ));
+            gl_check!(gl::EnableVertexAttribArray(0));
+            // a_tex_coords attribute
+            gl_check!(gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(1));
+            // a_color attribute
+            gl_check!(gl::VertexAttribPointer(
+                2,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (4 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(2));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, atlas_file);
+            debug_label::set(
+                gl::BUFFER,
+                vertex_buffer_obj,
+                &format!("{} vertices", atlas_file),
+            );
+
+            let mut atlas_texture = 0_u32;
+            gl_check!(gl::GenTextures(1, &mut atlas_texture));
+            live_objects::created(GlObject::Texture, atlas_texture);
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, atlas_texture));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                atlas_width as GLsizei,
+                atlas_height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                atlas.as_ptr() as *const c_void,
+            ));
+            gpu_memory::track(
+                GpuResource::Texture,
+                atlas_texture,
+                gpu_memory::texture_bytes(atlas_width, atlas_height, 1, 4, false),
+            );
+            debug_label::set(gl::TEXTURE, atlas_texture, atlas_file);
+            // Linear filtering keeps scaled text smooth; cells are padded so neighbors do not bleed
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+
+            let projection_from_screen_uniform = shader_program.uniform("projection_from_screen");
+            Ok(TextRenderer {
+                shader_program,
+                vertex_array_obj,
+                vertex_buffer_obj,
+                atlas_texture,
+                cell_size: (
+                    (atlas_width / ATLAS_COLUMNS) as f32,
+                    (atlas_height / ATLAS_ROWS) as f32,
+                ),
+                projection_from_screen_uniform,
+                vertices: vec![],
+            })
+        }
+    }
+
+    /// Height in pixels of one line of text at `scale`.
+    pub fn line_height(&self, scale: f32) -> f32 {
+        self.cell_size.1 * scale
+    }
+
+    /// Queues `text` with its top left corner at `position`, in framebuffer pixels from the top
+    /// left. Lines break at `\n`; characters outside printable ASCII show as `?`.
+    pub fn add_text(&mut self, text: &str, position: (f32, f32), scale: f32, color: [f32; 4]) {
+        let (cell_width, cell_height) = (self.cell_size.0 * scale, self.cell_size.1 * scale);
+        let (mut x, mut y) = position;
+        for character in text.chars() {
+            if character == '\n' {
+                x = position.0;
+                y += cell_height;
+                continue;
+            }
+            let code = if character.is_ascii() && !character.is_ascii_control() {
+                character as u8
+            } else {
+                b'?'
+            };
+            if code != b' ' {
+                let cell = (code - FIRST_CHAR) as u32;
+                let (column, row) = (cell % ATLAS_COLUMNS, cell / ATLAS_COLUMNS);
+                let (u0, v0) = (
+                    column as f32 / ATLAS_COLUMNS as f32,
+                    row as f32 / ATLAS_ROWS as f32,
+                );
+                let (u1, v1) = (
+                    (column + 1) as f32 / ATLAS_COLUMNS as f32,
+                    (row + 1) as f32 / ATLAS_ROWS as f32,
+                );
+                let (x1, y1) = (x + cell_width, y + cell_height);
+                for (vertex_x, vertex_y, u, v) in [
+                    (x, y, u0, v0),
+                    (x, y1, u0, v1),
+                    (x1, y1, u1, v1),
+                    (x1, y1, u1, v1),
+                    (x1, y, u1, v0),
+                    (x, y, u0, v0),
+                ]
+                .iter()
+                {
+                    self.vertices
+                        .extend_from_slice(&[*vertex_x, *vertex_y, *u, *v]);
+                    self.vertices.extend_from_slice(&color);
+                }
+            }
+            x += cell_width;
+        }
+    }
+
+    /// Draws and clears the queued text.
+    pub fn draw(&mut self, framebuffer_size: (i32, i32)) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let (width, height) = framebuffer_size;
+        // Pixel coordinates with y pointing down, matching how text is laid out
+        let projection_from_screen = glm::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        self.shader_program.use_program();
+        self.projection_from_screen_uniform
+            .set_mat4f(&projection_from_screen);
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.atlas_texture));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer_obj));
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(self.vertices.as_slice()) as GLsizeiptr,
+                self.vertices.as_ptr() as *const c_void,
+                gl::STREAM_DRAW,
+            ));
+            gpu_memory::track(
+                GpuResource::Buffer,
+                self.vertex_buffer_obj,
+                mem::size_of_val(self.vertices.as_slice()),
+            );
+            gl_check!(gl::DrawArrays(
+                gl::TRIANGLES,
+                0,
+                (self.vertices.len() / VERTEX_SIZE) as GLsizei
+            ));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            gl_check!(gl::Disable(gl::BLEND));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+        self.vertices.clear();
+    }
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete(GlObject::Buffer, self.vertex_buffer_obj);
+            live_objects::delete(GlObject::Texture, self.atlas_texture);
+        }
+    }
+}
+
+/// The atlas image at `atlas_file`, or the compiled-in default one in web builds.
+fn open_atlas(atlas_file: &str) -> image::ImageResult<image::DynamicImage> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        if atlas_file == DEFAULT_FONT_ATLAS {
+            return image::load_from_memory(DEFAULT_FONT_ATLAS_IMAGE);
+        }
+    }
+    image::open(Path::new(atlas_file))
+}
@@ -0,0 +1,456 @@
+use gl::types::*;
+
+#[cfg(feature = "gl45")]
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+#[cfg(feature = "gl45")]
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::text_renderer::TextRenderer;
+
+const INSPECTOR_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+// Left, top, right and bottom edges in framebuffer pixels from the top left
+uniform vec4 rect;
+uniform vec2 viewport_size;
+
+out vec2 o_tex_coords;
+
+void main() {
+    // Four vertices, drawn as a strip: bottom left, bottom right, top left, top right
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1));
+    vec2 pixel = vec2(mix(rect.x, rect.z, corner.x), mix(rect.w, rect.y, corner.y));
+    gl_Position = vec4(pixel / viewport_size * vec2(2.0f, -2.0f) + vec2(-1.0f, 1.0f), 0.0f, 1.0f);
+    // Textures start at their bottom row, like render targets
+    o_tex_coords = corner;
+}
+"#;
+
+const INSPECTOR_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform sampler2D image;
+// 0 fills with color, 1 shows all channels, 2 to 5 only red, green, blue or alpha
+uniform int mode;
+uniform vec4 color;
+uniform float lod;
+
+in vec2 o_tex_coords;
+
+out vec4 frag_color;
+
+void main() {
+    if (mode == 0) {
+        frag_color = color;
+        return;
+    }
+    vec4 texel = textureLod(image, o_tex_coords, lod);
+    if (mode == 1) {
+        // Transparent texels show a checkerboard through them
+        vec2 cell = floor(gl_FragCoord.xy / 8.0f);
+        float checker = mod(cell.x + cell.y, 2.0f) * 0.2f + 0.4f;
+        frag_color = vec4(mix(vec3(checker), texel.rgb, texel.a), 1.0f);
+    } else {
+        frag_color = vec4(vec3(texel[mode - 2]), 1.0f);
+    }
+}
+"#;
+
+// Largest side of a thumbnail and the gap around it, in pixels at a content scale of 1
+const THUMBNAIL_SIZE: f32 = 160.0_f32;
+const THUMBNAIL_SPACING: f32 = 12.0_f32;
+// Lines of caption under each thumbnail
+const CAPTION_LINES: f32 = 2.0_f32;
+const BACKDROP_COLOR: [f32; 4] = [0.05_f32, 0.05_f32, 0.08_f32, 0.9_f32];
+const FRAME_COLOR: [f32; 4] = [0.3_f32, 0.3_f32, 0.35_f32, 1.0_f32];
+const TEXT_COLOR: [f32; 4] = [0.85_f32, 0.85_f32, 0.85_f32, 1.0_f32];
+const TITLE_COLOR: [f32; 4] = [1.0_f32, 0.9_f32, 0.3_f32, 1.0_f32];
+// Fragment shader modes
+const MODE_FILL: i32 = 0;
+const MODE_RGBA: i32 = 1;
+
+/// Which channels of the inspected textures are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InspectorChannel {
+    /// Blended over a checkerboard by alpha
+    #[default]
+    Rgba,
+    /// One channel as grayscale
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl InspectorChannel {
+    /// Parses what `name` returns: `rgba`, `r`, `g`, `b` or `a`.
+    pub fn from_name(name: &str) -> Option<InspectorChannel> {
+        match name {
+            "rgba" => Some(InspectorChannel::Rgba),
+            "r" => Some(InspectorChannel::Red),
+            "g" => Some(InspectorChannel::Green),
+            "b" => Some(InspectorChannel::Blue),
+            "a" => Some(InspectorChannel::Alpha),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InspectorChannel::Rgba => "rgba",
+            InspectorChannel::Red => "r",
+            InspectorChannel::Green => "g",
+            InspectorChannel::Blue => "b",
+            InspectorChannel::Alpha => "a",
+        }
+    }
+
+    fn mode(self) -> i32 {
+        match self {
+            InspectorChannel::Rgba => MODE_RGBA,
+            InspectorChannel::Red => 2,
+            InspectorChannel::Green => 3,
+            InspectorChannel::Blue => 4,
+            InspectorChannel::Alpha => 5,
+        }
+    }
+}
+
+/// A live 2D texture as the driver describes it.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+struct InspectedTexture {
+    id: GLuint,
+    label: String,
+    width: i32,
+    height: i32,
+    internal_format: GLenum,
+    levels: i32,
+}
+
+/// Debug page over the whole window with a thumbnail of every live 2D texture allocated through
+/// the wrappers, texture render target attachments included, so their contents can be checked
+/// without writing them to disk. One texture can be selected to fill the page. Textures are
+/// found through the GPU memory tracker and described with GL 4.5 queries, so the page stays
+/// empty without direct state access. Integer textures, which cannot be sampled as floats, and
+/// renderbuffers, which cannot be sampled at all, are left out.
+pub struct TextureInspector {
+    pub visible: bool,
+    pub channel: InspectorChannel,
+    /// Shown where the textures have it, and their last level where they do not
+    pub mip_level: u32,
+    /// Index into the thumbnails of the texture filling the page
+    pub selected: Option<usize>,
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    /// Samplers picking single mip levels, and for textures without mips, plain linear filtering
+    mip_sampler: GLuint,
+    linear_sampler: GLuint,
+    rect_uniform: Uniform,
+    viewport_size_uniform: Uniform,
+    mode_uniform: Uniform,
+    color_uniform: Uniform,
+    lod_uniform: Uniform,
+    text_renderer: TextRenderer,
+}
+
+impl TextureInspector {
+    pub fn new(context: &GlContext, font_atlas_file: &str) -> Result<TextureInspector, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                INSPECTOR_VERTEX_SHADER_SOURCE,
+                INSPECTOR_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("texture inspector");
+            // No attributes: the vertex shader places the corners
+            let mut vertex_array_obj = 0_u32;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "texture inspector");
+
+            // Sampler objects override the textures' own filtering, which bindless textures may not
+            // have changed
+            let mut samplers = [0_u32; 2];
+            gl_check!(gl::GenSamplers(2, samplers.as_mut_ptr()));
+            live_objects::created_all(GlObject::Sampler, &samplers);
+            let [mip_sampler, linear_sampler] = samplers;
+            for (sampler, min_filter) in [
+                (mip_sampler, gl::LINEAR_MIPMAP_NEAREST),
+                (linear_sampler, gl::LINEAR),
+            ]
+            .iter()
+            {
+                for (parameter, value) in [
+                    (gl::TEXTURE_MIN_FILTER, *min_filter),
+                    (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                    (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                    (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                ]
+                .iter()
+                {
+                    gl_check!(gl::SamplerParameteri(*sampler, *parameter, *value as GLint));
+                }
+            }
+
+            Ok(TextureInspector {
+                visible: false,
+                channel: InspectorChannel::Rgba,
+                mip_level: 0,
+                selected: None,
+                rect_uniform: shader_program.uniform("rect"),
+                viewport_size_uniform: shader_program.uniform("viewport_size"),
+                mode_uniform: shader_program.uniform("mode"),
+                color_uniform: shader_program.uniform("color"),
+                lod_uniform: shader_program.uniform("lod"),
+                shader_program,
+                vertex_array_obj,
+                mip_sampler,
+                linear_sampler,
+                text_renderer: TextRenderer::new(context, font_atlas_file)?,
+            })
+        }
+    }
+
+    /// Draws the page over the framebuffer, when visible.
+    pub fn draw(&mut self, framebuffer_size: (i32, i32), content_scale: f32) {
+        if !self.visible {
+            return;
+        }
+        let textures = unsafe { inspected_textures() };
+        if matches!(self.selected, Some(texture_i) if texture_i >= textures.len()) {
+            self.selected = None;
+        }
+        let (width, height) = (framebuffer_size.0 as f32, framebuffer_size.1 as f32);
+        let scale = 0.5_f32 * content_scale;
+        let line_height = self.text_renderer.line_height(scale);
+        let spacing = THUMBNAIL_SPACING * content_scale;
+
+        self.shader_program.use_program();
+        self.viewport_size_uniform.set_vec2f([width, height]);
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+            self.fill([0.0_f32, 0.0_f32, width, height], BACKDROP_COLOR);
+            gl_check!(gl::Disable(gl::BLEND));
+        }
+
+        let title = format!(
+            "Textures: {} shown  channel {}  mip {}  (inspect <index>|all|r|g|b|a|rgba|mip <level>)",
+            textures.len(),
+            self.channel.name(),
+            self.mip_level
+        );
+        self.text_renderer
+            .add_text(&title, (spacing, spacing), scale, TITLE_COLOR);
+        if textures.is_empty() {
+            self.text_renderer.add_text(
+                "No textures to show; finding them needs GL 4.5 direct state access",
+                (spacing, spacing + line_height),
+                scale,
+                TEXT_COLOR,
+            );
+        }
+        let top = spacing * 2.0_f32 + line_height;
+
+        match self.selected {
+            Some(texture_i) => {
+                let caption_top = height - spacing - line_height * CAPTION_LINES;
+                let texture = &textures[texture_i];
+                self.draw_texture(
+                    texture,
+                    [spacing, top, width - spacing, caption_top - spacing],
+                );
+                self.text_renderer.add_text(
+                    &caption(texture_i, texture),
+                    (spacing, caption_top),
+                    scale,
+                    TEXT_COLOR,
+                );
+            }
+            None => {
+                let size = THUMBNAIL_SIZE * content_scale;
+                let cell_width = size + spacing;
+                let cell_height = size + line_height * CAPTION_LINES + spacing;
+                let columns = (((width - spacing) / cell_width) as usize).max(1);
+                for (texture_i, texture) in textures.iter().enumerate() {
+                    let left = spacing + (texture_i % columns) as f32 * cell_width;
+                    let cell_top = top + (texture_i / columns) as f32 * cell_height;
+                    if cell_top > height {
+                        break;
+                    }
+                    self.draw_texture(texture, [left, cell_top, left + size, cell_top + size]);
+                    // Labels are cut to the thumbnail's width
+                    let characters = (size / (self.text_renderer.cell_size.0 * scale)) as usize;
+                    let text: String = caption(texture_i, texture)
+                        .lines()
+                        .map(|line| line.chars().take(characters).collect::<String>())
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    self.text_renderer
+                        .add_text(&text, (left, cell_top + size), scale, TEXT_COLOR);
+                }
+            }
+        }
+        unsafe {
+            gl_check!(gl::BindSampler(0, 0));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+        self.text_renderer.draw(framebuffer_size);
+    }
+
+    /// Draws `texture` into the largest rectangle of its aspect ratio that fits within `rect`,
+    /// framed.
+    fn draw_texture(&self, texture: &InspectedTexture, rect: [f32; 4]) {
+        let [left, top, right, bottom] = rect;
+        let fit =
+            ((right - left) / texture.width as f32).min((bottom - top) / texture.height as f32);
+        let (width, height) = (texture.width as f32 * fit, texture.height as f32 * fit);
+        let (left, top) = (
+            left + (right - left - width) * 0.5_f32,
+            top + (bottom - top - height) * 0.5_f32,
+        );
+        let lod = (self.mip_level as i32).min(texture.levels - 1);
+        unsafe {
+            self.fill(
+                [
+                    left - 1.0_f32,
+                    top - 1.0_f32,
+                    left + width + 1.0_f32,
+                    top + height + 1.0_f32,
+                ],
+                FRAME_COLOR,
+            );
+            self.mode_uniform.set_int(self.channel.mode());
+            self.lod_uniform.set_float(lod as f32);
+            self.rect_uniform
+                .set_vec4f([left, top, left + width, top + height]);
+            let sampler = if texture.levels > 1 {
+                self.mip_sampler
+            } else {
+                self.linear_sampler
+            };
+            gl_check!(gl::BindSampler(0, sampler));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture.id));
+            gl_check!(gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4));
+        }
+    }
+
+    unsafe fn fill(&self, rect: [f32; 4], color: [f32; 4]) {
+        self.mode_uniform.set_int(MODE_FILL);
+        self.color_uniform.set_vec4f(color);
+        self.rect_uniform.set_vec4f(rect);
+        gl_check!(gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4));
+    }
+}
+
+impl Drop for TextureInspector {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete_all(GlObject::Sampler, &[self.mip_sampler, self.linear_sampler]);
+        }
+    }
+}
+
+fn caption(texture_i: usize, texture: &InspectedTexture) -> String {
+    format!(
+        "{} {}\n{}x{} {}{}",
+        texture_i,
+        texture.label,
+        texture.width,
+        texture.height,
+        format_name(texture.internal_format),
+        if texture.levels > 1 {
+            format!(", {} mips", texture.levels)
+        } else {
+            String::new()
+        }
+    )
+}
+
+fn format_name(internal_format: GLenum) -> String {
+    match internal_format {
+        gl::RGBA8 => "RGBA8".to_string(),
+        gl::RGB8 => "RGB8".to_string(),
+        gl::RG8 => "RG8".to_string(),
+        gl::R8 => "R8".to_string(),
+        gl::SRGB8_ALPHA8 => "SRGB8_ALPHA8".to_string(),
+        gl::RGBA16F => "RGBA16F".to_string(),
+        gl::RGBA16 => "RGBA16".to_string(),
+        gl::RGB16 => "RGB16".to_string(),
+        gl::RG16 => "RG16".to_string(),
+        gl::R16 => "R16".to_string(),
+        gl::R32F => "R32F".to_string(),
+        gl::DEPTH24_STENCIL8 => "DEPTH24_STENCIL8".to_string(),
+        gl::DEPTH_COMPONENT24 => "DEPTH_COMPONENT24".to_string(),
+        gl::DEPTH_COMPONENT32F => "DEPTH_COMPONENT32F".to_string(),
+        _ => format!("0x{:X}", internal_format),
+    }
+}
+
+/// The live textures the page can show, by ascending name.
+#[cfg(feature = "gl45")]
+unsafe fn inspected_textures() -> Vec<InspectedTexture> {
+    if !capabilities::direct_state_access() {
+        return vec![];
+    }
+    gpu_memory::ids(GpuResource::Texture)
+        .into_iter()
+        .filter_map(|id| {
+            let mut target = 0;
+            gl_check!(gl::GetTextureParameteriv(
+                id,
+                gl::TEXTURE_TARGET,
+                &mut target
+            ));
+            if target as GLenum != gl::TEXTURE_2D {
+                return None;
+            }
+            let level_parameter = |level: GLint, parameter: GLenum| {
+                let mut value = 0;
+                gl_check!(gl::GetTextureLevelParameteriv(
+                    id, level, parameter, &mut value
+                ));
+                value
+            };
+            let red_type = level_parameter(0, gl::TEXTURE_RED_TYPE) as GLenum;
+            if red_type == gl::INT || red_type == gl::UNSIGNED_INT {
+                return None;
+            }
+            let (width, height) = (
+                level_parameter(0, gl::TEXTURE_WIDTH),
+                level_parameter(0, gl::TEXTURE_HEIGHT),
+            );
+            if width == 0 || height == 0 {
+                return None;
+            }
+            // Levels holding an image; a chain stops at the first empty one
+            let levels = (1..32)
+                .find(|&level| level_parameter(level, gl::TEXTURE_WIDTH) == 0)
+                .unwrap_or(32);
+            Some(InspectedTexture {
+                id,
+                label: debug_label::get(gl::TEXTURE, id)
+                    .unwrap_or_else(|| format!("texture {}", id)),
+                width,
+                height,
+                internal_format: level_parameter(0, gl::TEXTURE_INTERNAL_FORMAT) as GLenum,
+                levels,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "gl45"))]
+unsafe fn inspected_textures() -> Vec<InspectedTexture> {
+    vec![]
+}
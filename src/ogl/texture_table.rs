@@ -0,0 +1,307 @@
+use gl::types::*;
+
+#[cfg(feature = "gl45")]
+use crate::ogl::bindless;
+use crate::ogl::capabilities;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Texture};
+use crate::ogl::live_objects::{self, GlObject};
+#[cfg(feature = "gl45")]
+use std::ffi::c_void;
+#[cfg(feature = "gl45")]
+use std::mem;
+use std::ptr;
+
+/// Shader storage binding the bindless handles are read from; shaders declare it literally.
+pub const TEXTURE_HANDLES_BINDING: GLuint = 0;
+/// Texture unit the array fallback is bound to.
+pub const TEXTURE_ARRAY_UNIT: GLuint = 0;
+// Width and height every texture is scaled to in the array fallback
+const ARRAY_LAYER_SIZE: GLint = 512;
+
+const COPY_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+out vec2 o_uv;
+
+void main() {
+    // One triangle covering the layer
+    vec2 position = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0f;
+    o_uv = position * 0.5f + 0.5f;
+    gl_Position = vec4(position, 0.0f, 1.0f);
+}
+"#;
+
+const COPY_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_uv;
+
+uniform sampler2D source;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = texture(source, o_uv);
+}
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureTableKind {
+    /// Resident ARB_bindless_texture handles in a shader storage buffer
+    #[cfg_attr(not(feature = "gl45"), allow(dead_code))]
+    Bindless,
+    /// Layers of one 2D array texture, every texture scaled to the same size
+    Array,
+}
+
+/// Every texture of the materials drawn with one shader, reachable by index from within the
+/// shader, so switching between those materials binds no textures and instanced runs can span
+/// them. Textures come in sets of the same size, one per material; shaders pick a set by the
+/// per-instance texture set index and a texture within it by position.
+pub struct TextureTable {
+    pub kind: TextureTableKind,
+    /// The handle buffer or the array texture
+    id: GLuint,
+    context: GlContext,
+}
+
+impl TextureTableKind {
+    /// Bindless where the context supports it and it is wanted, texture arrays otherwise.
+    #[cfg_attr(not(feature = "gl45"), allow(unused_variables))]
+    pub fn detect(prefer_bindless: bool) -> TextureTableKind {
+        #[cfg(feature = "gl45")]
+        {
+            if prefer_bindless && capabilities::bindless_textures() {
+                return TextureTableKind::Bindless;
+            }
+        }
+        TextureTableKind::Array
+    }
+}
+
+impl TextureTable {
+    /// Builds a table of `kind` from `sets`, which must all hold the same number of textures.
+    /// Textures made bindless can no longer have their sampling state changed.
+    pub fn build(
+        context: &GlContext,
+        kind: TextureTableKind,
+        sets: &[Vec<&Texture>],
+    ) -> Result<TextureTable, String> {
+        let textures_per_set = sets.first().map_or(0, |set| set.len());
+        if sets.iter().any(|set| set.len() != textures_per_set) {
+            return Err(format!(
+                "Every texture set in a table needs {} textures",
+                textures_per_set
+            ));
+        }
+        let textures: Vec<&Texture> = sets.iter().flatten().copied().collect();
+        let id = match kind {
+            #[cfg(feature = "gl45")]
+            TextureTableKind::Bindless => unsafe { build_handle_buffer(&textures) },
+            #[cfg(not(feature = "gl45"))]
+            TextureTableKind::Bindless => {
+                return Err("Bindless textures need the gl45 feature".to_string())
+            }
+            TextureTableKind::Array => {
+                capabilities::check_array_texture_layers(textures.len())?;
+                unsafe { build_array(context, &textures)? }
+            }
+        };
+        Ok(TextureTable {
+            kind,
+            id,
+            context: *context,
+        })
+    }
+
+    /// Makes the table the one shaders read from.
+    pub unsafe fn bind(&self) {
+        match self.kind {
+            TextureTableKind::Bindless => {
+                gl_check!(gl::BindBufferBase(
+                    gl::SHADER_STORAGE_BUFFER,
+                    TEXTURE_HANDLES_BINDING,
+                    self.id
+                ));
+            }
+            TextureTableKind::Array => {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + TEXTURE_ARRAY_UNIT));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id));
+            }
+        }
+    }
+}
+
+impl Drop for TextureTable {
+    fn drop(&mut self) {
+        let object = match self.kind {
+            TextureTableKind::Bindless => GlObject::Buffer,
+            TextureTableKind::Array => GlObject::Texture,
+        };
+        live_objects::release(&self.context, object, self.id);
+    }
+}
+
+/// Adapts `source` to read from a table of `kind`: defines `BINDLESS_TEXTURES` (raising the
+/// GLSL version the extension needs) or `TEXTURE_ARRAY`, and `TEXTURES_PER_SET`.
+pub fn prepare_shader(source: &str, kind: TextureTableKind, textures_per_set: usize) -> String {
+    let header = match kind {
+        TextureTableKind::Bindless => {
+            "#version 450 core\n#extension GL_ARB_bindless_texture : require\n\
+             #define BINDLESS_TEXTURES"
+        }
+        // Array samplers have no default precision on ES
+        TextureTableKind::Array => {
+            "#version 330 core\n#define TEXTURE_ARRAY\nprecision highp sampler2DArray;"
+        }
+    };
+    source
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with("#version") {
+                format!("{}\n#define TEXTURES_PER_SET {}", header, textures_per_set)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(feature = "gl45")]
+unsafe fn build_handle_buffer(textures: &[&Texture]) -> GLuint {
+    let handles: Vec<GLuint64> = textures
+        .iter()
+        .map(|texture| {
+            let handle = bindless::texture_handle(texture.id);
+            bindless::make_resident(handle);
+            handle
+        })
+        .collect();
+    let mut buffer = 0;
+    gl_check!(gl::GenBuffers(1, &mut buffer));
+    live_objects::created(GlObject::Buffer, buffer);
+    gl_check!(gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, buffer));
+    gl_check!(gl::BufferData(
+        gl::SHADER_STORAGE_BUFFER,
+        mem::size_of_val(handles.as_slice()) as GLsizeiptr,
+        handles.as_ptr() as *const c_void,
+        gl::STATIC_DRAW,
+    ));
+    gl_check!(gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0));
+    gpu_memory::track(
+        GpuResource::Buffer,
+        buffer,
+        mem::size_of_val(handles.as_slice()),
+    );
+    debug_label::set(gl::BUFFER, buffer, "texture table handles");
+    buffer
+}
+
+/// Copies the textures into the layers of a new array texture by drawing them, which scales them
+/// to the layer size and applies their swizzles, so gray textures land in the layers as gray.
+unsafe fn build_array(context: &GlContext, textures: &[&Texture]) -> Result<GLuint, String> {
+    let shader_program = ShaderProgram::with_shaders(
+        context,
+        COPY_VERTEX_SHADER_SOURCE,
+        COPY_FRAGMENT_SHADER_SOURCE,
+    )?;
+    let mut array = 0;
+    gl_check!(gl::GenTextures(1, &mut array));
+    live_objects::created(GlObject::Texture, array);
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, array));
+    gl_check!(gl::TexImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0,
+        gl::RGBA8 as GLint,
+        ARRAY_LAYER_SIZE,
+        ARRAY_LAYER_SIZE,
+        textures.len().max(1) as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        ptr::null(),
+    ));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0));
+
+    let mut viewport = [0; 4];
+    gl_check!(gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()));
+    let depth_test = gl_check!(gl::IsEnabled(gl::DEPTH_TEST)) == gl::TRUE;
+    // No attributes: the vertex shader places the corners
+    let mut vertex_array_obj = 0;
+    gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+    live_objects::created(GlObject::VertexArray, vertex_array_obj);
+    let mut framebuffer = 0;
+    gl_check!(gl::GenFramebuffers(1, &mut framebuffer));
+    live_objects::created(GlObject::Framebuffer, framebuffer);
+    gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer));
+    gl_check!(gl::BindVertexArray(vertex_array_obj));
+    gl_check!(gl::Viewport(0, 0, ARRAY_LAYER_SIZE, ARRAY_LAYER_SIZE));
+    gl_check!(gl::Disable(gl::DEPTH_TEST));
+    shader_program.use_program();
+    shader_program.uniform("source").set_int(0);
+    gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+    for (layer, texture) in textures.iter().enumerate() {
+        gl_check!(gl::FramebufferTextureLayer(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            array,
+            0,
+            layer as GLint
+        ));
+        gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture.id));
+        gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+    }
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    gl_check!(gl::BindVertexArray(0));
+    gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+    live_objects::delete(GlObject::Framebuffer, framebuffer);
+    live_objects::delete(GlObject::VertexArray, vertex_array_obj);
+    gl_check!(gl::Viewport(
+        viewport[0],
+        viewport[1],
+        viewport[2],
+        viewport[3]
+    ));
+    if depth_test {
+        gl_check!(gl::Enable(gl::DEPTH_TEST));
+    }
+
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, array));
+    gl_check!(gl::TexParameteri(
+        gl::TEXTURE_2D_ARRAY,
+        gl::TEXTURE_WRAP_S,
+        gl::REPEAT as GLint
+    ));
+    gl_check!(gl::TexParameteri(
+        gl::TEXTURE_2D_ARRAY,
+        gl::TEXTURE_WRAP_T,
+        gl::REPEAT as GLint
+    ));
+    gl_check!(gl::TexParameteri(
+        gl::TEXTURE_2D_ARRAY,
+        gl::TEXTURE_MIN_FILTER,
+        gl::LINEAR_MIPMAP_LINEAR as GLint
+    ));
+    gl_check!(gl::TexParameteri(
+        gl::TEXTURE_2D_ARRAY,
+        gl::TEXTURE_MAG_FILTER,
+        gl::LINEAR as GLint
+    ));
+    gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        array,
+        gpu_memory::texture_bytes(
+            ARRAY_LAYER_SIZE as u32,
+            ARRAY_LAYER_SIZE as u32,
+            textures.len().max(1) as u32,
+            4,
+            true,
+        ),
+    );
+    debug_label::set(gl::TEXTURE, array, "texture table array");
+    Ok(array)
+}
@@ -0,0 +1,312 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use std::ffi::c_void;
+use std::{mem, ptr};
+
+const UI_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 a_pos;
+layout (location = 1) in vec2 a_tex_coords;
+layout (location = 2) in vec4 a_color;
+
+uniform vec2 screen_size;
+
+out vec2 o_tex_coords;
+out vec4 o_color;
+
+void main() {
+    gl_Position = vec4(2.0f * a_pos.x / screen_size.x - 1.0f, 1.0f - 2.0f * a_pos.y / screen_size.y, 0.0f, 1.0f);
+    o_tex_coords = a_tex_coords;
+    o_color = a_color;
+}
+"#;
+
+const UI_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec2 o_tex_coords;
+in vec4 o_color;
+
+uniform sampler2D ui_texture;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = o_color * texture(ui_texture, o_tex_coords);
+}
+"#;
+
+// Floats per vertex: position, texture coordinates and color
+const VERTEX_SIZE: usize = 8;
+
+/// Draws the triangle meshes egui tessellates its shapes into, on top of whatever is in the
+/// framebuffer. Colors are premultiplied sRGB, blended as they are without conversion.
+pub struct UiPainter {
+    shader_program: ShaderProgram,
+    vertex_array_obj: GLuint,
+    vertex_buffer_obj: GLuint,
+    element_buffer_obj: GLuint,
+    font_texture: GLuint,
+    font_texture_version: Option<u64>,
+    screen_size_uniform: Uniform,
+    vertices: Vec<f32>,
+}
+
+impl UiPainter {
+    pub fn new(context: &GlContext) -> Result<UiPainter, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                UI_VERTEX_SHADER_SOURCE,
+                UI_FRAGMENT_SHADER_SOURCE,
+            )?;
+            shader_program.set_label("ui");
+            let (mut vertex_array_obj, mut vertex_buffer_obj, mut element_buffer_obj) =
+                (0_u32, 0_u32, 0_u32);
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::GenBuffers(1, &mut vertex_buffer_obj));
+            live_objects::created(GlObject::Buffer, vertex_buffer_obj);
+            gl_check!(gl::GenBuffers(1, &mut element_buffer_obj));
+            live_objects::created(GlObject::Buffer, element_buffer_obj);
+
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer_obj));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, element_buffer_obj));
+            let stride = (VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            // a_pos attribute
+            gl_check!(gl::VertexAttribPointer(
+                0,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                ptr::null()
+            ));
+            gl_check!(gl::EnableVertexAttribArray(0));
+            // a_tex_coords attribute
+            gl_check!(gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(1));
+            // a_color attribute
+            gl_check!(gl::VertexAttribPointer(
+                2,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (4 * mem::size_of::<GLfloat>()) as *const c_void,
+            ));
+            gl_check!(gl::EnableVertexAttribArray(2));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "ui");
+            debug_label::set(gl::BUFFER, vertex_buffer_obj, "ui vertices");
+            debug_label::set(gl::BUFFER, element_buffer_obj, "ui indices");
+
+            let mut font_texture = 0_u32;
+            gl_check!(gl::GenTextures(1, &mut font_texture));
+            live_objects::created(GlObject::Texture, font_texture);
+
+            let screen_size_uniform = shader_program.uniform("screen_size");
+            Ok(UiPainter {
+                shader_program,
+                vertex_array_obj,
+                vertex_buffer_obj,
+                element_buffer_obj,
+                font_texture,
+                font_texture_version: None,
+                screen_size_uniform,
+                vertices: vec![],
+            })
+        }
+    }
+
+    /// Uploads egui's font atlas when it changed. The atlas only holds coverage, stored here as
+    /// premultiplied white.
+    pub fn update_font_texture(&mut self, texture: &egui::Texture) {
+        if self.font_texture_version == Some(texture.version) {
+            return;
+        }
+        let pixels: Vec<u8> = texture
+            .pixels
+            .iter()
+            .flat_map(|alpha| vec![*alpha; 4])
+            .collect();
+        unsafe {
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, self.font_texture));
+            gl_check!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1));
+            gl_check!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                texture.width as GLsizei,
+                texture.height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
+            ));
+            gl_check!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4));
+            gpu_memory::track(GpuResource::Texture, self.font_texture, pixels.len());
+            debug_label::set(gl::TEXTURE, self.font_texture, "ui font");
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint
+            ));
+            gl_check!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+        }
+        self.font_texture_version = Some(texture.version);
+    }
+
+    /// Draws `meshes` in points, `pixels_per_point` framebuffer pixels each. `TextureId::User`
+    /// meshes sample the GL texture of that name, so scene textures can be shown in the UI.
+    pub fn paint(
+        &mut self,
+        meshes: &[egui::ClippedMesh],
+        framebuffer_size: (i32, i32),
+        pixels_per_point: f32,
+    ) {
+        let (framebuffer_width, framebuffer_height) = framebuffer_size;
+        unsafe {
+            gl_check!(gl::Disable(gl::DEPTH_TEST));
+            gl_check!(gl::Enable(gl::BLEND));
+            gl_check!(gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA));
+            gl_check!(gl::Enable(gl::SCISSOR_TEST));
+            self.shader_program.use_program();
+            self.screen_size_uniform.set_vec2f([
+                framebuffer_width as f32 / pixels_per_point,
+                framebuffer_height as f32 / pixels_per_point,
+            ]);
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer_obj));
+            gl_check!(gl::BindBuffer(
+                gl::ELEMENT_ARRAY_BUFFER,
+                self.element_buffer_obj
+            ));
+        }
+
+        for egui::ClippedMesh(clip_rect, mesh) in meshes {
+            if mesh.indices.is_empty() {
+                continue;
+            }
+            self.vertices.clear();
+            for vertex in &mesh.vertices {
+                self.vertices.extend_from_slice(&[
+                    vertex.pos.x,
+                    vertex.pos.y,
+                    vertex.uv.x,
+                    vertex.uv.y,
+                    vertex.color.r() as f32 / 255.0,
+                    vertex.color.g() as f32 / 255.0,
+                    vertex.color.b() as f32 / 255.0,
+                    vertex.color.a() as f32 / 255.0,
+                ]);
+            }
+            let texture = match mesh.texture_id {
+                egui::TextureId::Egui => self.font_texture,
+                egui::TextureId::User(id) => id as GLuint,
+            };
+
+            // Clip rectangles are in points from the top left; scissor boxes in pixels from the
+            // bottom left
+            let clip_left = (clip_rect.min.x * pixels_per_point).round() as i32;
+            let clip_right = (clip_rect.max.x * pixels_per_point).round() as i32;
+            let clip_top = (clip_rect.min.y * pixels_per_point).round() as i32;
+            let clip_bottom = (clip_rect.max.y * pixels_per_point).round() as i32;
+            let clip_left = clip_left.max(0).min(framebuffer_width);
+            let clip_right = clip_right.max(clip_left).min(framebuffer_width);
+            let clip_top = clip_top.max(0).min(framebuffer_height);
+            let clip_bottom = clip_bottom.max(clip_top).min(framebuffer_height);
+
+            unsafe {
+                gl_check!(gl::Scissor(
+                    clip_left,
+                    framebuffer_height - clip_bottom,
+                    clip_right - clip_left,
+                    clip_bottom - clip_top,
+                ));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+                gl_check!(gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    mem::size_of_val(self.vertices.as_slice()) as GLsizeiptr,
+                    self.vertices.as_ptr() as *const c_void,
+                    gl::STREAM_DRAW,
+                ));
+                gl_check!(gl::BufferData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    mem::size_of_val(mesh.indices.as_slice()) as GLsizeiptr,
+                    mesh.indices.as_ptr() as *const c_void,
+                    gl::STREAM_DRAW,
+                ));
+                gpu_memory::track(
+                    GpuResource::Buffer,
+                    self.vertex_buffer_obj,
+                    mem::size_of_val(self.vertices.as_slice()),
+                );
+                gpu_memory::track(
+                    GpuResource::Buffer,
+                    self.element_buffer_obj,
+                    mem::size_of_val(mesh.indices.as_slice()),
+                );
+                gl_check!(gl::DrawElements(
+                    gl::TRIANGLES,
+                    mesh.indices.len() as GLsizei,
+                    gl::UNSIGNED_INT,
+                    ptr::null()
+                ));
+            }
+        }
+
+        unsafe {
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            gl_check!(gl::Disable(gl::SCISSOR_TEST));
+            gl_check!(gl::Disable(gl::BLEND));
+            gl_check!(gl::Enable(gl::DEPTH_TEST));
+        }
+    }
+}
+
+impl Drop for UiPainter {
+    fn drop(&mut self) {
+        unsafe {
+            live_objects::delete(GlObject::VertexArray, self.vertex_array_obj);
+            live_objects::delete_all(
+                GlObject::Buffer,
+                &[self.vertex_buffer_obj, self.element_buffer_obj],
+            );
+            live_objects::delete(GlObject::Texture, self.font_texture);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use gl::types::*;
+
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{MeshBuffers, MeshData, SharedTexture, Texture};
+use crate::platform::WorkerContext;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+// How long `wait` blocks on a fence before checking it again
+const FENCE_WAIT_TIMEOUT_NS: GLuint64 = 1_000_000;
+
+pub type UploadId = u64;
+
+/// A finished upload, ready to be used from the render thread.
+pub enum Upload {
+    /// Image decoded and uploaded with its full mip chain; `Texture::from_shared` takes it over
+    Texture(SharedTexture),
+    /// Buffers only; `Mesh::from_buffers` adds the vertex array
+    #[allow(dead_code)]
+    Mesh(MeshBuffers),
+}
+
+enum UploadRequest {
+    Texture {
+        id: UploadId,
+        file_path: String,
+        flip_vertically: bool,
+    },
+    Mesh {
+        id: UploadId,
+        data: MeshData,
+    },
+}
+
+struct CompletedUpload {
+    id: UploadId,
+    result: Result<Upload, String>,
+    fence: Fence,
+}
+
+/// Sync objects are shared between contexts, the pointer just is not marked `Send`.
+struct Fence(GLsync);
+
+unsafe impl Send for Fence {}
+
+/// Decodes files and uploads textures and buffers on a thread of its own, in a context sharing
+/// objects with the render thread's. Each upload is followed by a fence, and is only handed out
+/// once the fence has signaled, so the render thread never sees a half-written object.
+pub struct UploadWorker {
+    requests: Sender<UploadRequest>,
+    completed: Receiver<CompletedUpload>,
+    // Received from the worker, waiting for their fences
+    in_flight: Vec<CompletedUpload>,
+    next_id: UploadId,
+    thread: JoinHandle<()>,
+}
+
+impl UploadWorker {
+    pub fn start(mut context: Box<dyn WorkerContext>) -> UploadWorker {
+        let (request_sender, request_receiver) = channel::<UploadRequest>();
+        let (completed_sender, completed_receiver) = channel();
+        let thread = thread::spawn(move || {
+            context.make_current();
+            // The shared context is current on this thread from here on
+            let gl_context = unsafe { GlContext::assume_current() };
+            for request in request_receiver {
+                let (id, result) = match request {
+                    UploadRequest::Texture {
+                        id,
+                        file_path,
+                        flip_vertically,
+                    } => (
+                        id,
+                        Texture::from_file(&gl_context, &file_path, flip_vertically).map(
+                            |mut texture| {
+                                texture.load(&gl_context);
+                                Upload::Texture(texture.into_shared())
+                            },
+                        ),
+                    ),
+                    UploadRequest::Mesh { id, data } => (
+                        id,
+                        Ok(Upload::Mesh(MeshBuffers::upload(&gl_context, &data))),
+                    ),
+                };
+                let fence = unsafe {
+                    let fence = gl_check!(gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0));
+                    // Other contexts can only wait on a fence that has been flushed
+                    gl_check!(gl::Flush());
+                    fence
+                };
+                let completed = CompletedUpload {
+                    id,
+                    result,
+                    fence: Fence(fence),
+                };
+                if completed_sender.send(completed).is_err() {
+                    break;
+                }
+            }
+            context.release();
+        });
+
+        UploadWorker {
+            requests: request_sender,
+            completed: completed_receiver,
+            in_flight: vec![],
+            next_id: 0,
+            thread,
+        }
+    }
+
+    pub fn upload_texture(&mut self, file_path: &str, flip_vertically: bool) -> UploadId {
+        let id = self.next_id();
+        self.send(UploadRequest::Texture {
+            id,
+            file_path: file_path.to_string(),
+            flip_vertically,
+        });
+        id
+    }
+
+    #[allow(dead_code)]
+    pub fn upload_mesh(&mut self, data: MeshData) -> UploadId {
+        let id = self.next_id();
+        self.send(UploadRequest::Mesh { id, data });
+        id
+    }
+
+    /// Uploads that have finished on the GPU since the last call. Never blocks.
+    #[allow(dead_code)]
+    pub fn poll(&mut self) -> Vec<(UploadId, Result<Upload, String>)> {
+        self.receive();
+        let mut finished = vec![];
+        let mut upload_i = 0;
+        while upload_i < self.in_flight.len() {
+            if unsafe { fence_signaled(&self.in_flight[upload_i].fence, 0) } {
+                let upload = self.in_flight.remove(upload_i);
+                unsafe {
+                    gl_check!(gl::DeleteSync(upload.fence.0));
+                }
+                finished.push((upload.id, upload.result));
+            } else {
+                upload_i += 1;
+            }
+        }
+        finished
+    }
+
+    /// Blocks until upload `id` has finished on the GPU. Other uploads finishing meanwhile stay
+    /// queued for `poll`.
+    pub fn wait(&mut self, id: UploadId) -> Result<Upload, String> {
+        loop {
+            self.receive();
+            if let Some(upload_i) = self.in_flight.iter().position(|upload| upload.id == id) {
+                let upload = self.in_flight.remove(upload_i);
+                unsafe {
+                    while !fence_signaled(&upload.fence, FENCE_WAIT_TIMEOUT_NS) {}
+                    gl_check!(gl::DeleteSync(upload.fence.0));
+                }
+                return upload.result;
+            }
+            match self.completed.recv() {
+                Ok(upload) => self.in_flight.push(upload),
+                Err(_) => return Err("Upload worker stopped".to_string()),
+            }
+        }
+    }
+
+    /// Lets the worker finish queued uploads, then joins it. Uploads not yet collected are
+    /// dropped without freeing their GL objects.
+    pub fn stop(self) {
+        drop(self.requests);
+        if self.thread.join().is_err() {
+            eprintln!("Upload worker panicked");
+        }
+    }
+
+    fn next_id(&mut self) -> UploadId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn send(&self, request: UploadRequest) {
+        if self.requests.send(request).is_err() {
+            eprintln!("Upload worker stopped, dropping upload request");
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok(upload) = self.completed.try_recv() {
+            self.in_flight.push(upload);
+        }
+    }
+}
+
+/// A failed wait counts as signaled, since waiting again would not succeed either.
+unsafe fn fence_signaled(fence: &Fence, timeout_ns: GLuint64) -> bool {
+    gl_check!(gl::ClientWaitSync(fence.0, 0, timeout_ns)) != gl::TIMEOUT_EXPIRED
+}
@@ -1,42 +1,120 @@
 use gl::types::*;
+
+use crate::ogl::capabilities;
+use crate::ogl::live_objects::{self, GlObject};
 use std::ffi::CString;
 use std::ptr;
 
+/// Wraps a GL call and, in debug builds, drains `glGetError` afterwards, reporting every error
+/// along with the call-site. Expands to the bare call in release builds.
+#[cfg(debug_assertions)]
+macro_rules! gl_check {
+    ($call:expr) => {{
+        let result = $call;
+        $crate::ogl::utils::report_gl_errors(stringify!($call), file!(), line!());
+        result
+    }};
+}
+
+#[cfg(not(debug_assertions))]
+macro_rules! gl_check {
+    ($call:expr) => {
+        $call
+    };
+}
+
 pub unsafe fn build_shader(shader: &str, shader_type: GLenum) -> Result<GLuint, String> {
-    let shader = CString::new(shader.as_bytes()).unwrap();
-    let shader_id = gl::CreateShader(shader_type);
-    gl::ShaderSource(shader_id, 1, &shader.as_ptr(), ptr::null());
-    gl::CompileShader(shader_id);
+    let shader = if capabilities::opengl_es() {
+        CString::new(to_gles_source(shader)).unwrap()
+    } else {
+        CString::new(shader.as_bytes()).unwrap()
+    };
+    let shader_id = gl_check!(gl::CreateShader(shader_type));
+    gl_check!(gl::ShaderSource(
+        shader_id,
+        1,
+        &shader.as_ptr(),
+        ptr::null()
+    ));
+    gl_check!(gl::CompileShader(shader_id));
     match get_shader_compile_status(shader_id) {
         Ok(_) => Ok(shader_id),
         Err(msg) => Err(msg),
     }
 }
 
+/// Rewrites the `#version` directive of a desktop GLSL 3.30 shader for GLSL ES 3.00, which also
+/// requires a default float precision in fragment shaders, and in all stages for the sampler
+/// types ES gives no default precision, such as array and unsigned integer samplers.
+fn to_gles_source(shader: &str) -> String {
+    shader
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with("#version") {
+                "#version 300 es\nprecision highp float;\nprecision highp sampler2DArray;\n\
+                 precision highp sampler3D;\nprecision highp sampler2DShadow;\n\
+                 precision highp usampler2D;\nprecision highp isampler2D;"
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub unsafe fn clean_shader(shader_id: GLuint) {
-    gl::DeleteShader(shader_id);
+    gl_check!(gl::DeleteShader(shader_id));
 }
 
 pub unsafe fn build_program(
     vertex_shader_id: GLuint,
     fragment_shader_id: GLuint,
 ) -> Result<GLuint, String> {
-    let program_id = gl::CreateProgram();
-    gl::AttachShader(program_id, vertex_shader_id);
-    gl::AttachShader(program_id, fragment_shader_id);
-    gl::LinkProgram(program_id);
+    link_program(&[vertex_shader_id, fragment_shader_id], &[])
+}
+
+/// Links `shader_ids` into a program, capturing the vertex shader outputs `feedback_varyings`,
+/// if any, interleaved into transform feedback buffer 0.
+pub unsafe fn link_program(
+    shader_ids: &[GLuint],
+    feedback_varyings: &[&str],
+) -> Result<GLuint, String> {
+    let program_id = gl_check!(gl::CreateProgram());
+    live_objects::created(GlObject::Program, program_id);
+    for shader_id in shader_ids {
+        gl_check!(gl::AttachShader(program_id, *shader_id));
+    }
+    if !feedback_varyings.is_empty() {
+        let names: Vec<CString> = feedback_varyings
+            .iter()
+            .map(|name| CString::new(*name).unwrap())
+            .collect();
+        let name_ptrs: Vec<*const GLchar> = names.iter().map(|name| name.as_ptr()).collect();
+        gl_check!(gl::TransformFeedbackVaryings(
+            program_id,
+            name_ptrs.len() as GLsizei,
+            name_ptrs.as_ptr(),
+            gl::INTERLEAVED_ATTRIBS
+        ));
+    }
+    gl_check!(gl::LinkProgram(program_id));
 
     let mut link_success = gl::FALSE as GLint;
     let mut link_log = Vec::with_capacity(512);
     link_log.set_len(link_log.capacity() - 1);
-    gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut link_success);
+    gl_check!(gl::GetProgramiv(
+        program_id,
+        gl::LINK_STATUS,
+        &mut link_success
+    ));
     if link_success != gl::TRUE as GLint {
-        gl::GetProgramInfoLog(
+        gl_check!(gl::GetProgramInfoLog(
             program_id,
             512,
             ptr::null_mut(),
             link_log.as_mut_ptr() as *mut GLchar,
-        );
+        ));
+        live_objects::delete(GlObject::Program, program_id);
         Err(format!(
             "Program build failed: {}",
             String::from_utf8(link_log).unwrap()
@@ -51,14 +129,18 @@ unsafe fn get_shader_compile_status(shader_id: GLuint) -> Result<(), String> {
     let mut compile_log = Vec::with_capacity(1024);
     compile_log.set_len(1024 - 1);
     let mut log_length: GLsizei = 0;
-    gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut compile_success);
+    gl_check!(gl::GetShaderiv(
+        shader_id,
+        gl::COMPILE_STATUS,
+        &mut compile_success
+    ));
     if compile_success != gl::TRUE as GLint {
-        gl::GetShaderInfoLog(
+        gl_check!(gl::GetShaderInfoLog(
             shader_id,
             1024,
             &mut log_length,
             compile_log.as_mut_ptr() as *mut GLchar,
-        );
+        ));
         compile_log.set_len(log_length as usize);
         Err(format!(
             "Shader compilation failed: {}",
@@ -68,3 +150,43 @@ unsafe fn get_shader_compile_status(shader_id: GLuint) -> Result<(), String> {
         Ok(())
     }
 }
+
+// Errors drained after one call at most, since a lost context can keep reporting them forever
+#[cfg(debug_assertions)]
+const MAX_GL_ERRORS_PER_CALL: usize = 32;
+
+#[cfg(debug_assertions)]
+pub unsafe fn report_gl_errors(call: &str, file: &str, line: u32) {
+    let mut previous_error = gl::NO_ERROR;
+    for _ in 0..MAX_GL_ERRORS_PER_CALL {
+        let error = gl::GetError();
+        // A repeated error is the driver saying the same thing again, not a new one queued
+        if error == gl::NO_ERROR || error == previous_error {
+            break;
+        }
+        previous_error = error;
+        eprintln!(
+            "GL error {} (0x{:04X}) at {}:{} in `{}`",
+            gl_error_name(error),
+            error,
+            file,
+            line,
+            call
+        );
+    }
+}
+
+#[cfg(debug_assertions)]
+fn gl_error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "INVALID_ENUM",
+        gl::INVALID_VALUE => "INVALID_VALUE",
+        gl::INVALID_OPERATION => "INVALID_OPERATION",
+        gl::STACK_OVERFLOW => "STACK_OVERFLOW",
+        gl::STACK_UNDERFLOW => "STACK_UNDERFLOW",
+        gl::OUT_OF_MEMORY => "OUT_OF_MEMORY",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "INVALID_FRAMEBUFFER_OPERATION",
+        gl::CONTEXT_LOST => "CONTEXT_LOST",
+        _ => "UNKNOWN_ERROR",
+    }
+}
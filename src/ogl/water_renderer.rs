@@ -0,0 +1,381 @@
+use gl::types::*;
+
+use crate::math::glm::{self, Vec3};
+use crate::math::random::Rng;
+use crate::ogl::context::GlContext;
+use crate::ogl::debug_label;
+use crate::ogl::gpu_memory::{self, GpuResource};
+use crate::ogl::graphics::{Camera, ShaderProgram, Uniform};
+use crate::ogl::live_objects::{self, GlObject};
+use crate::ogl::render_target::TextureTarget;
+use crate::ogl::renderer::Renderer;
+use std::f32::consts::PI;
+use std::ffi::c_void;
+
+const REFLECTION_UNIT: GLuint = 0;
+const REFRACTION_UNIT: GLuint = 1;
+const DUDV_MAP_UNIT: GLuint = 2;
+const NORMAL_MAP_UNIT: GLuint = 3;
+// Width and height of the distortion and normal maps
+const WAVE_MAP_SIZE: usize = 256;
+// World units one repeat of the wave maps covers
+const WAVE_TILING: f32 = 12.0_f32;
+// Wave map repeats the ripples move per second
+const WAVE_SPEED: f32 = 0.03_f32;
+// Keeps the reflection and refraction passes from leaving a gap along the shore
+const CLIP_BIAS: f32 = 0.1_f32;
+
+const WATER_VERTEX_SHADER_SOURCE: &str = r#"
+#version 330 core
+uniform mat4 projection_from_world;
+uniform float water_height;
+uniform float water_size;
+
+out vec4 o_clip_position;
+out vec3 o_world_position;
+
+void main() {
+    // Four vertices drawn as a strip: (-1, -1), (1, -1), (-1, 1), (1, 1)
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1)) * 2.0f - 1.0f;
+    o_world_position = vec3(corner.x, 0.0f, corner.y) * water_size * 0.5f;
+    o_world_position.y = water_height;
+    o_clip_position = projection_from_world * vec4(o_world_position, 1.0f);
+    gl_Position = o_clip_position;
+}
+"#;
+
+const WATER_FRAGMENT_SHADER_SOURCE: &str = r#"
+#version 330 core
+in vec4 o_clip_position;
+in vec3 o_world_position;
+
+uniform sampler2D reflection;
+uniform sampler2D refraction;
+uniform sampler2D dudv_map;
+uniform sampler2D normal_map;
+uniform vec3 camera_position;
+uniform vec3 light_direction;
+uniform float wave_offset;
+uniform float wave_tiling;
+
+out vec4 frag_color;
+
+const float DISTORTION_STRENGTH = 0.02f;
+const vec3 WATER_TINT = vec3(0.0f, 0.25f, 0.35f);
+
+void main() {
+    // Both passes were rendered from this fragment's point of view, so they are looked up at its
+    // screen position; the reflection is upside down
+    vec2 screen_uv = o_clip_position.xy / o_clip_position.w * 0.5f + 0.5f;
+
+    // Two layers of ripples moving in different directions, so no single direction shows
+    vec2 wave_uv = o_world_position.xz / wave_tiling;
+    vec2 distorted_uv = texture(dudv_map, vec2(wave_uv.x + wave_offset, wave_uv.y)).rg * 0.1f;
+    distorted_uv = wave_uv + vec2(distorted_uv.x, distorted_uv.y + wave_offset);
+    vec2 distortion = (texture(dudv_map, distorted_uv).rg * 2.0f - 1.0f) * DISTORTION_STRENGTH;
+
+    vec2 reflection_uv = clamp(vec2(screen_uv.x, 1.0f - screen_uv.y) + distortion, 0.001f, 0.999f);
+    vec2 refraction_uv = clamp(screen_uv + distortion, 0.001f, 0.999f);
+    vec3 reflection_color = texture(reflection, reflection_uv).rgb;
+    vec3 refraction_color = texture(refraction, refraction_uv).rgb;
+
+    vec3 normal = normalize(texture(normal_map, distorted_uv).rgb * 2.0f - 1.0f);
+    vec3 to_camera = normalize(camera_position - o_world_position);
+    // Looking straight down shows what is below; at grazing angles water is a mirror
+    float fresnel = pow(clamp(dot(to_camera, normal), 0.0f, 1.0f), 0.8f);
+    vec3 color = mix(reflection_color, refraction_color, fresnel);
+    color = mix(color, WATER_TINT, 0.2f);
+
+    vec3 halfway = normalize(light_direction + to_camera);
+    float specular = pow(max(dot(normal, halfway), 0.0f), 64.0f);
+    frag_color = vec4(color + vec3(specular * 0.6f), 1.0f);
+}
+"#;
+
+/// A square of water at a fixed height, reflecting and refracting whatever is drawn into its
+/// two offscreen passes, rippled by a distortion map and lit through a normal map.
+pub struct WaterRenderer {
+    height: f32,
+    /// What is above the water, seen from below it
+    reflection: TextureTarget,
+    /// What is below the water, seen from the camera
+    refraction: TextureTarget,
+    dudv_map: GLuint,
+    normal_map: GLuint,
+    wave_offset: f32,
+    shader_program: ShaderProgram,
+    /// Empty, but drawing needs one bound
+    vertex_array_obj: GLuint,
+    projection_from_world_uniform: Uniform,
+    camera_position_uniform: Uniform,
+    wave_offset_uniform: Uniform,
+    context: GlContext,
+}
+
+impl WaterRenderer {
+    /// Water `size` units across at `height`, with both passes rendered `pass_width` by
+    /// `pass_height`.
+    pub fn new(
+        context: &GlContext,
+        height: f32,
+        size: f32,
+        (pass_width, pass_height): (i32, i32),
+        seed: u64,
+    ) -> Result<WaterRenderer, String> {
+        unsafe {
+            let shader_program = ShaderProgram::with_shaders(
+                context,
+                WATER_VERTEX_SHADER_SOURCE,
+                WATER_FRAGMENT_SHADER_SOURCE,
+            )?;
+            let reflection = TextureTarget::new(context, pass_width, pass_height)?;
+            let refraction = TextureTarget::new(context, pass_width, pass_height)?;
+            shader_program.set_label("water");
+            reflection.set_label(context, "water reflection");
+            refraction.set_label(context, "water refraction");
+            let (dudv_texels, normal_texels) = wave_maps(seed);
+            let dudv_map = upload_wave_map(&dudv_texels);
+            let normal_map = upload_wave_map(&normal_texels);
+            debug_label::set(gl::TEXTURE, dudv_map, "water du/dv map");
+            debug_label::set(gl::TEXTURE, normal_map, "water normal map");
+            let mut vertex_array_obj = 0;
+            gl_check!(gl::GenVertexArrays(1, &mut vertex_array_obj));
+            live_objects::created(GlObject::VertexArray, vertex_array_obj);
+            gl_check!(gl::BindVertexArray(vertex_array_obj));
+            gl_check!(gl::BindVertexArray(0));
+            debug_label::set(gl::VERTEX_ARRAY, vertex_array_obj, "water");
+
+            shader_program.use_program();
+            shader_program
+                .uniform("reflection")
+                .set_int(REFLECTION_UNIT as i32);
+            shader_program
+                .uniform("refraction")
+                .set_int(REFRACTION_UNIT as i32);
+            shader_program
+                .uniform("dudv_map")
+                .set_int(DUDV_MAP_UNIT as i32);
+            shader_program
+                .uniform("normal_map")
+                .set_int(NORMAL_MAP_UNIT as i32);
+            shader_program.uniform("water_height").set_float(height);
+            shader_program.uniform("water_size").set_float(size);
+            shader_program.uniform("wave_tiling").set_float(WAVE_TILING);
+            let light_direction = glm::normalize(&glm::vec3(0.4_f32, 1.0_f32, 0.3_f32));
+            shader_program.uniform("light_direction").set_vec3f([
+                light_direction.x,
+                light_direction.y,
+                light_direction.z,
+            ]);
+
+            let projection_from_world_uniform = shader_program.uniform("projection_from_world");
+            let camera_position_uniform = shader_program.uniform("camera_position");
+            let wave_offset_uniform = shader_program.uniform("wave_offset");
+            Ok(WaterRenderer {
+                height,
+                reflection,
+                refraction,
+                dudv_map,
+                normal_map,
+                wave_offset: 0.0_f32,
+                shader_program,
+                vertex_array_obj,
+                projection_from_world_uniform,
+                camera_position_uniform,
+                wave_offset_uniform,
+                context: *context,
+            })
+        }
+    }
+
+    /// Moves the ripples on by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.wave_offset = (self.wave_offset + WAVE_SPEED * dt).fract();
+    }
+
+    /// `camera` mirrored in the water surface, to render the reflection from.
+    pub fn reflection_camera(&self, camera: &Camera) -> Camera {
+        let mut position: Vec3 = camera.position;
+        position.y = 2.0_f32 * self.height - position.y;
+        let mut mirrored = Camera {
+            position,
+            front: camera.front,
+            up: camera.up,
+            yaw: camera.yaw,
+            pitch: -camera.pitch,
+        };
+        mirrored.update_front();
+        mirrored
+    }
+
+    /// Keeps what is above the water, for the reflection pass.
+    pub fn reflection_clip_plane(&self) -> [f32; 4] {
+        [0.0_f32, 1.0_f32, 0.0_f32, CLIP_BIAS - self.height]
+    }
+
+    /// Keeps what is below the water, for the refraction pass.
+    pub fn refraction_clip_plane(&self) -> [f32; 4] {
+        [0.0_f32, -1.0_f32, 0.0_f32, CLIP_BIAS + self.height]
+    }
+
+    /// Binds and clears the reflection target; draw the reflection camera's view next.
+    pub fn begin_reflection(&self, renderer: &Renderer) {
+        self.reflection.bind(&self.context);
+        unsafe {
+            clear(renderer);
+        }
+    }
+
+    /// Binds and clears the refraction target; draw the camera's view next.
+    pub fn begin_refraction(&self, renderer: &Renderer) {
+        self.refraction.bind(&self.context);
+        unsafe {
+            clear(renderer);
+        }
+    }
+
+    /// Draws the water surface from `camera` into the bound framebuffer, sampling both passes.
+    pub fn draw(&self, camera: &Camera, renderer: &mut Renderer) {
+        self.shader_program.use_program();
+        self.projection_from_world_uniform
+            .set_mat4f(&(renderer.projection_from_view() * camera.view_matrix()));
+        self.camera_position_uniform.set_vec3f([
+            camera.position.x,
+            camera.position.y,
+            camera.position.z,
+        ]);
+        self.wave_offset_uniform.set_float(self.wave_offset);
+        unsafe {
+            for (unit, texture) in [
+                (REFLECTION_UNIT, self.reflection.color_texture),
+                (REFRACTION_UNIT, self.refraction.color_texture),
+                (DUDV_MAP_UNIT, self.dudv_map),
+                (NORMAL_MAP_UNIT, self.normal_map),
+            ]
+            .iter()
+            {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + unit));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, *texture));
+            }
+            gl_check!(gl::BindVertexArray(self.vertex_array_obj));
+            gl_check!(gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4));
+            gl_check!(gl::BindVertexArray(0));
+            gl_check!(gl::ActiveTexture(gl::TEXTURE0));
+        }
+        renderer.stats.record_draw(2);
+    }
+}
+
+impl Drop for WaterRenderer {
+    fn drop(&mut self) {
+        live_objects::release(&self.context, GlObject::VertexArray, self.vertex_array_obj);
+        for texture in [self.dudv_map, self.normal_map].iter() {
+            live_objects::release(&self.context, GlObject::Texture, *texture);
+        }
+    }
+}
+
+unsafe fn clear(renderer: &Renderer) {
+    let [r, g, b, a] = renderer.clear_color.to_srgb();
+    gl_check!(gl::ClearColor(r, g, b, a));
+    gl_check!(gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT));
+}
+
+/// Distortion and normal maps of one tiling field of ripples: a sum of waves with whole numbers
+/// of periods across the map. The distortion map holds the slope, the normal map the normal,
+/// both remapped from -1..1 to 0..255.
+fn wave_maps(seed: u64) -> (Vec<[u8; 4]>, Vec<[u8; 4]>) {
+    let mut rng = Rng::new(seed);
+    let waves: Vec<([f32; 2], f32, f32)> = (0..12)
+        .map(|_| {
+            let frequency = [
+                rng.range(-6.0_f32, 6.0_f32).round(),
+                rng.range(1.0_f32, 6.0_f32).round(),
+            ];
+            let amplitude = 1.0_f32 / (frequency[0].abs() + frequency[1].abs());
+            (frequency, amplitude, rng.range(0.0_f32, 2.0_f32 * PI))
+        })
+        .collect();
+    let slopes: Vec<[f32; 2]> = (0..WAVE_MAP_SIZE * WAVE_MAP_SIZE)
+        .map(|i| {
+            let u = (i % WAVE_MAP_SIZE) as f32 / WAVE_MAP_SIZE as f32;
+            let v = (i / WAVE_MAP_SIZE) as f32 / WAVE_MAP_SIZE as f32;
+            let mut slope = [0.0_f32; 2];
+            for (frequency, amplitude, phase) in waves.iter() {
+                let angle = 2.0_f32 * PI * (frequency[0] * u + frequency[1] * v) + phase;
+                slope[0] += amplitude * frequency[0] * angle.cos();
+                slope[1] += amplitude * frequency[1] * angle.cos();
+            }
+            slope
+        })
+        .collect();
+    let steepest = slopes
+        .iter()
+        .map(|slope| slope[0].abs().max(slope[1].abs()))
+        .fold(f32::EPSILON, f32::max);
+    let encode = |value: f32| ((value * 0.5_f32 + 0.5_f32) * 255.0_f32).round() as u8;
+    let dudv = slopes
+        .iter()
+        .map(|slope| {
+            [
+                encode(slope[0] / steepest),
+                encode(slope[1] / steepest),
+                0,
+                255,
+            ]
+        })
+        .collect();
+    let normals = slopes
+        .iter()
+        .map(|slope| {
+            // Flattened, as ripples on a lake are shallow
+            let normal = glm::normalize(&glm::vec3(
+                -0.3_f32 * slope[0] / steepest,
+                1.0_f32,
+                -0.3_f32 * slope[1] / steepest,
+            ));
+            [encode(normal.x), encode(normal.y), encode(normal.z), 255]
+        })
+        .collect();
+    (dudv, normals)
+}
+
+unsafe fn upload_wave_map(texels: &[[u8; 4]]) -> GLuint {
+    let mut texture = 0;
+    gl_check!(gl::GenTextures(1, &mut texture));
+    live_objects::created(GlObject::Texture, texture);
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+    gl_check!(gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA8 as GLint,
+        WAVE_MAP_SIZE as GLsizei,
+        WAVE_MAP_SIZE as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        texels.as_ptr() as *const c_void,
+    ));
+    for (parameter, value) in [
+        (gl::TEXTURE_WRAP_S, gl::REPEAT),
+        (gl::TEXTURE_WRAP_T, gl::REPEAT),
+        (gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+    ]
+    .iter()
+    {
+        gl_check!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            *parameter,
+            *value as GLint
+        ));
+    }
+    gl_check!(gl::GenerateMipmap(gl::TEXTURE_2D));
+    gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    gpu_memory::track(
+        GpuResource::Texture,
+        texture,
+        gpu_memory::texture_bytes(WAVE_MAP_SIZE as u32, WAVE_MAP_SIZE as u32, 1, 4, true),
+    );
+    texture
+}
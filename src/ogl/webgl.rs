@@ -0,0 +1,1697 @@
+// Loader for the `gl` entry points in web builds. Browsers have no OpenGL library to load from,
+// so every entry point the renderer calls is a shim forwarding to the canvas's WebGL2 context,
+// which is OpenGL ES 3.0 behind a JavaScript API. GL names stand for WebGL objects kept in tables
+// here, pointers into wasm memory become typed array views, and the context reports itself as
+// ES 3.0 so `GlCapabilities` takes the same paths it takes on an ES driver. Entry points WebGL
+// lacks are left unloaded, as an ES driver leaves them, except buffer mapping, which copies.
+
+use gl::types::*;
+use js_sys::{
+    Array, Float32Array, Int16Array, Int32Array, Int8Array, Object, Uint16Array, Uint32Array,
+    Uint8Array, WebAssembly,
+};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    WebGl2RenderingContext, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlQuery,
+    WebGlRenderbuffer, WebGlSampler, WebGlShader, WebGlSync, WebGlTexture, WebGlUniformLocation,
+    WebGlVertexArrayObject,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::{mem, ptr, slice};
+
+// WebGL extensions the renderer has uses for, turned on as the context is loaded
+const WANTED_EXTENSIONS: [&str; 3] = [
+    "EXT_color_buffer_float",
+    "EXT_texture_filter_anisotropic",
+    "OES_texture_float_linear",
+];
+
+// Capabilities that are always on in WebGL and cannot be toggled
+const ALWAYS_ENABLED: [GLenum; 2] = [gl::MULTISAMPLE, gl::PROGRAM_POINT_SIZE];
+
+thread_local! {
+    static STATE: RefCell<Option<WebGlState>> = RefCell::new(None);
+}
+
+/// Makes `context` the one the `gl` entry points forward to on this thread, and loads them.
+pub fn load(context: WebGl2RenderingContext) {
+    let extensions = WANTED_EXTENSIONS
+        .iter()
+        .filter(|name| matches!(context.get_extension(name), Ok(Some(_))))
+        .map(|name| format!("GL_{}", name))
+        .collect();
+    STATE.with(|state| {
+        *state.borrow_mut() = Some(WebGlState {
+            context,
+            buffers: Names::new(),
+            textures: Names::new(),
+            framebuffers: Names::new(),
+            renderbuffers: Names::new(),
+            vertex_arrays: Names::new(),
+            queries: Names::new(),
+            samplers: Names::new(),
+            syncs: Names::new(),
+            shaders: Names::new(),
+            programs: Names::new(),
+            uniform_locations: vec![],
+            uniform_names: HashMap::new(),
+            mapped_ranges: HashMap::new(),
+            strings: HashMap::new(),
+            extensions,
+            pack_alignment: 4,
+            unpack_alignment: 4,
+            pixel_pack_buffer: false,
+            error: gl::NO_ERROR,
+        })
+    });
+    gl::load_with(proc_address);
+}
+
+/// WebGL objects of one kind, named the way GL names them: name `n` is slot `n - 1`, and 0 is
+/// never an object.
+struct Names<T> {
+    objects: Vec<Option<T>>,
+}
+
+impl<T: PartialEq> Names<T> {
+    fn new() -> Names<T> {
+        Names { objects: vec![] }
+    }
+
+    /// Names `object`; a lost context creates no objects and gets 0, as GL hands out no name.
+    fn insert(&mut self, object: Option<T>) -> GLuint {
+        match object {
+            Some(object) => {
+                self.objects.push(Some(object));
+                self.objects.len() as GLuint
+            }
+            None => 0,
+        }
+    }
+
+    fn get(&self, name: GLuint) -> Option<&T> {
+        let slot = (name as usize).checked_sub(1)?;
+        self.objects.get(slot)?.as_ref()
+    }
+
+    fn remove(&mut self, name: GLuint) -> Option<T> {
+        let slot = (name as usize).checked_sub(1)?;
+        self.objects.get_mut(slot)?.take()
+    }
+
+    fn name_of(&self, object: &T) -> GLuint {
+        self.objects
+            .iter()
+            .position(|slot| slot.as_ref() == Some(object))
+            .map_or(0, |slot| slot as GLuint + 1)
+    }
+}
+
+/// A buffer range mapped into a copy in wasm memory, written back to the buffer as it is flushed
+/// or unmapped.
+struct MappedRange {
+    offset: GLintptr,
+    access: GLbitfield,
+    data: Vec<u8>,
+}
+
+struct WebGlState {
+    context: WebGl2RenderingContext,
+    buffers: Names<WebGlBuffer>,
+    textures: Names<WebGlTexture>,
+    framebuffers: Names<WebGlFramebuffer>,
+    renderbuffers: Names<WebGlRenderbuffer>,
+    vertex_arrays: Names<WebGlVertexArrayObject>,
+    queries: Names<WebGlQuery>,
+    samplers: Names<WebGlSampler>,
+    syncs: Names<WebGlSync>,
+    shaders: Names<WebGlShader>,
+    programs: Names<WebGlProgram>,
+    /// GL numbers uniform locations, WebGL hands out objects; a location is an index in here
+    uniform_locations: Vec<Option<WebGlUniformLocation>>,
+    uniform_names: HashMap<(GLuint, String), GLint>,
+    mapped_ranges: HashMap<GLenum, MappedRange>,
+    /// Returned by `glGetString` and `glGetStringi`, which hand out pointers that stay valid
+    strings: HashMap<(GLenum, GLuint), CString>,
+    extensions: Vec<String>,
+    pack_alignment: usize,
+    unpack_alignment: usize,
+    /// `glReadPixels` writes into the bound pixel pack buffer instead of client memory
+    pixel_pack_buffer: bool,
+    /// Raised by a shim itself, reported by `glGetError` ahead of WebGL's own errors
+    error: GLenum,
+}
+
+impl WebGlState {
+    fn uniform_location(&self, location: GLint) -> Option<&WebGlUniformLocation> {
+        if location < 0 {
+            return None;
+        }
+        self.uniform_locations.get(location as usize)?.as_ref()
+    }
+
+    /// Drops the locations looked up in `program`, which linking again or deleting invalidates.
+    fn forget_uniforms(&mut self, program: GLuint) {
+        let WebGlState {
+            ref mut uniform_names,
+            ref mut uniform_locations,
+            ..
+        } = *self;
+        uniform_names.retain(|(location_program, _), location| {
+            if *location_program == program {
+                uniform_locations[*location as usize] = None;
+            }
+            *location_program != program
+        });
+    }
+
+    fn object_name(&self, value: &JsValue) -> GLuint {
+        if let Some(buffer) = value.dyn_ref::<WebGlBuffer>() {
+            self.buffers.name_of(buffer)
+        } else if let Some(texture) = value.dyn_ref::<WebGlTexture>() {
+            self.textures.name_of(texture)
+        } else if let Some(framebuffer) = value.dyn_ref::<WebGlFramebuffer>() {
+            self.framebuffers.name_of(framebuffer)
+        } else if let Some(renderbuffer) = value.dyn_ref::<WebGlRenderbuffer>() {
+            self.renderbuffers.name_of(renderbuffer)
+        } else if let Some(vertex_array) = value.dyn_ref::<WebGlVertexArrayObject>() {
+            self.vertex_arrays.name_of(vertex_array)
+        } else if let Some(program) = value.dyn_ref::<WebGlProgram>() {
+            self.programs.name_of(program)
+        } else if let Some(sampler) = value.dyn_ref::<WebGlSampler>() {
+            self.samplers.name_of(sampler)
+        } else if let Some(query) = value.dyn_ref::<WebGlQuery>() {
+            self.queries.name_of(query)
+        } else {
+            0
+        }
+    }
+
+    /// A `glGet` result as GL integers: numbers and booleans as they are, arrays element by
+    /// element, and WebGL objects, or null for none, as their GL names.
+    fn integers(&self, value: &JsValue) -> Vec<GLint> {
+        if let Some(number) = value.as_f64() {
+            // Through i64, so unsigned masks wrap like GL's do
+            vec![number as i64 as GLint]
+        } else if let Some(flag) = value.as_bool() {
+            vec![GLint::from(flag)]
+        } else if let Some(array) = value.dyn_ref::<Int32Array>() {
+            array.to_vec()
+        } else if let Some(array) = value.dyn_ref::<Uint32Array>() {
+            array
+                .to_vec()
+                .into_iter()
+                .map(|value| value as GLint)
+                .collect()
+        } else if let Some(array) = value.dyn_ref::<Float32Array>() {
+            array
+                .to_vec()
+                .into_iter()
+                .map(|value| value as GLint)
+                .collect()
+        } else if let Some(array) = value.dyn_ref::<Array>() {
+            array
+                .iter()
+                .map(|element| match element.as_bool() {
+                    Some(flag) => GLint::from(flag),
+                    None => element.as_f64().unwrap_or(0.0) as GLint,
+                })
+                .collect()
+        } else {
+            vec![self.object_name(value) as GLint]
+        }
+    }
+}
+
+/// A `glGet` result as GL floats.
+fn floats(value: &JsValue) -> Vec<GLfloat> {
+    if let Some(number) = value.as_f64() {
+        vec![number as GLfloat]
+    } else if let Some(flag) = value.as_bool() {
+        vec![if flag { 1.0 } else { 0.0 }]
+    } else if let Some(array) = value.dyn_ref::<Float32Array>() {
+        array.to_vec()
+    } else if let Some(array) = value.dyn_ref::<Int32Array>() {
+        array
+            .to_vec()
+            .into_iter()
+            .map(|value| value as GLfloat)
+            .collect()
+    } else if let Some(array) = value.dyn_ref::<Uint32Array>() {
+        array
+            .to_vec()
+            .into_iter()
+            .map(|value| value as GLfloat)
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+fn with_state<R>(f: impl FnOnce(&mut WebGlState) -> R) -> R {
+    STATE.with(|state| {
+        f(state
+            .borrow_mut()
+            .as_mut()
+            .expect("GL called before webgl::load"))
+    })
+}
+
+/// Keeps the first error raised since the last `glGetError`, as GL does.
+fn raise(error: &mut GLenum, code: GLenum) {
+    if *error == gl::NO_ERROR {
+        *error = code;
+    }
+}
+
+/// The object `name` stands for; an unknown name is GL's `INVALID_VALUE`.
+fn lookup<'a, T: PartialEq>(
+    error: &mut GLenum,
+    names: &'a Names<T>,
+    name: GLuint,
+) -> Option<&'a T> {
+    let object = names.get(name);
+    if object.is_none() {
+        raise(error, gl::INVALID_VALUE);
+    }
+    object
+}
+
+/// WebGL reports invalid arguments mostly through its error flag, but throws for some, such as
+/// too short an array; those become `INVALID_OPERATION`.
+fn check_thrown(error: &mut GLenum, result: Result<(), JsValue>) {
+    if result.is_err() {
+        raise(error, gl::INVALID_OPERATION);
+    }
+}
+
+unsafe fn c_string(string: *const GLchar) -> String {
+    CStr::from_ptr(string).to_string_lossy().into_owned()
+}
+
+/// Copies `text` into a `buffer_size` byte buffer the way GL returns strings: truncated,
+/// terminated, and with the length written without the terminator.
+unsafe fn write_string(
+    text: &str,
+    buffer_size: GLsizei,
+    length: *mut GLsizei,
+    buffer: *mut GLchar,
+) {
+    let count = text.len().min((buffer_size - 1).max(0) as usize);
+    if buffer_size > 0 && !buffer.is_null() {
+        ptr::copy_nonoverlapping(text.as_ptr() as *const GLchar, buffer, count);
+        *buffer.add(count) = 0;
+    }
+    if !length.is_null() {
+        *length = count as GLsizei;
+    }
+}
+
+unsafe fn write_values<T: Copy>(values: &[T], destination: *mut T) {
+    if !destination.is_null() {
+        ptr::copy_nonoverlapping(values.as_ptr(), destination, values.len());
+    }
+}
+
+unsafe fn slice_of<'a, T>(pointer: *const T, length: usize) -> &'a [T] {
+    if pointer.is_null() || length == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(pointer, length)
+    }
+}
+
+/// `glGetShaderiv` and `glGetProgramiv` count the terminator of a non-empty log.
+fn info_log_length(log: Option<String>) -> GLint {
+    log.filter(|log| !log.is_empty())
+        .map_or(0, |log| log.len() as GLint + 1)
+}
+
+/// Bytes of the pixels of `type_` in `format` a `width` by `height` by `depth` image reads from
+/// or writes to memory, with each row but the last padded to `alignment`.
+fn image_size(
+    (width, height, depth): (GLsizei, GLsizei, GLsizei),
+    format: GLenum,
+    type_: GLenum,
+    alignment: usize,
+) -> usize {
+    let rows = height.max(0) as usize * depth.max(0) as usize;
+    if width <= 0 || rows == 0 {
+        return 0;
+    }
+    let row_size = width as usize * pixel_size(format, type_);
+    let stride = (row_size + alignment - 1) / alignment * alignment;
+    stride * (rows - 1) + row_size
+}
+
+fn pixel_size(format: GLenum, type_: GLenum) -> usize {
+    match type_ {
+        gl::UNSIGNED_SHORT_5_6_5 | gl::UNSIGNED_SHORT_4_4_4_4 | gl::UNSIGNED_SHORT_5_5_5_1 => 2,
+        gl::UNSIGNED_INT_24_8
+        | gl::UNSIGNED_INT_2_10_10_10_REV
+        | gl::UNSIGNED_INT_10F_11F_11F_REV
+        | gl::UNSIGNED_INT_5_9_9_9_REV => 4,
+        gl::FLOAT_32_UNSIGNED_INT_24_8_REV => 8,
+        _ => {
+            let components = match format {
+                gl::RG | gl::RG_INTEGER => 2,
+                gl::RGB | gl::RGB_INTEGER => 3,
+                gl::RGBA | gl::RGBA_INTEGER => 4,
+                _ => 1,
+            };
+            let component_size = match type_ {
+                gl::UNSIGNED_SHORT | gl::SHORT | gl::HALF_FLOAT => 2,
+                gl::UNSIGNED_INT | gl::INT | gl::FLOAT => 4,
+                _ => 1,
+            };
+            components * component_size
+        }
+    }
+}
+
+/// A view of `size` bytes of wasm memory at `pointer`, of the typed array kind WebGL requires
+/// for pixels of `type_`. Views are made right before the call using them, since growing the
+/// memory detaches them.
+unsafe fn pixel_view(pointer: *const c_void, size: usize, type_: GLenum) -> Option<Object> {
+    if pointer.is_null() {
+        return None;
+    }
+    let memory = wasm_bindgen::memory()
+        .unchecked_into::<WebAssembly::Memory>()
+        .buffer();
+    let offset = pointer as u32;
+    let view: Object = match type_ {
+        gl::FLOAT => {
+            Float32Array::new_with_byte_offset_and_length(&memory, offset, size as u32 / 4).into()
+        }
+        gl::INT => {
+            Int32Array::new_with_byte_offset_and_length(&memory, offset, size as u32 / 4).into()
+        }
+        gl::UNSIGNED_INT
+        | gl::UNSIGNED_INT_24_8
+        | gl::UNSIGNED_INT_2_10_10_10_REV
+        | gl::UNSIGNED_INT_10F_11F_11F_REV
+        | gl::UNSIGNED_INT_5_9_9_9_REV => {
+            Uint32Array::new_with_byte_offset_and_length(&memory, offset, size as u32 / 4).into()
+        }
+        gl::SHORT => {
+            Int16Array::new_with_byte_offset_and_length(&memory, offset, size as u32 / 2).into()
+        }
+        gl::UNSIGNED_SHORT
+        | gl::HALF_FLOAT
+        | gl::UNSIGNED_SHORT_5_6_5
+        | gl::UNSIGNED_SHORT_4_4_4_4
+        | gl::UNSIGNED_SHORT_5_5_5_1 => {
+            Uint16Array::new_with_byte_offset_and_length(&memory, offset, size as u32 / 2).into()
+        }
+        gl::BYTE => Int8Array::new_with_byte_offset_and_length(&memory, offset, size as u32).into(),
+        _ => Uint8Array::new_with_byte_offset_and_length(&memory, offset, size as u32).into(),
+    };
+    Some(view)
+}
+
+fn parameter_string(context: &WebGl2RenderingContext, name: GLenum) -> String {
+    context
+        .get_parameter(name)
+        .ok()
+        .and_then(|value| value.as_string())
+        .unwrap_or_default()
+}
+
+/// The shim standing in for GL entry point `name`; null for those WebGL has no counterpart to.
+fn proc_address(name: &str) -> *const c_void {
+    match name {
+        "glActiveTexture" => active_texture as *const c_void,
+        "glAttachShader" => attach_shader as *const c_void,
+        "glBeginQuery" => begin_query as *const c_void,
+        "glBeginTransformFeedback" => begin_transform_feedback as *const c_void,
+        "glBindBuffer" => bind_buffer as *const c_void,
+        "glBindBufferBase" => bind_buffer_base as *const c_void,
+        "glBindFramebuffer" => bind_framebuffer as *const c_void,
+        "glBindRenderbuffer" => bind_renderbuffer as *const c_void,
+        "glBindSampler" => bind_sampler as *const c_void,
+        "glBindTexture" => bind_texture as *const c_void,
+        "glBindVertexArray" => bind_vertex_array as *const c_void,
+        "glBlendFunc" => blend_func as *const c_void,
+        "glBlitFramebuffer" => blit_framebuffer as *const c_void,
+        "glBufferData" => buffer_data as *const c_void,
+        "glBufferSubData" => buffer_sub_data as *const c_void,
+        "glCheckFramebufferStatus" => check_framebuffer_status as *const c_void,
+        "glClear" => clear as *const c_void,
+        "glClearColor" => clear_color as *const c_void,
+        "glClientWaitSync" => client_wait_sync as *const c_void,
+        "glColorMask" => color_mask as *const c_void,
+        "glCompileShader" => compile_shader as *const c_void,
+        "glCopyTexSubImage3D" => copy_tex_sub_image_3d as *const c_void,
+        "glCreateProgram" => create_program as *const c_void,
+        "glCreateShader" => create_shader as *const c_void,
+        "glDeleteBuffers" => delete_buffers as *const c_void,
+        "glDeleteFramebuffers" => delete_framebuffers as *const c_void,
+        "glDeleteProgram" => delete_program as *const c_void,
+        "glDeleteQueries" => delete_queries as *const c_void,
+        "glDeleteRenderbuffers" => delete_renderbuffers as *const c_void,
+        "glDeleteSamplers" => delete_samplers as *const c_void,
+        "glDeleteShader" => delete_shader as *const c_void,
+        "glDeleteSync" => delete_sync as *const c_void,
+        "glDeleteTextures" => delete_textures as *const c_void,
+        "glDeleteVertexArrays" => delete_vertex_arrays as *const c_void,
+        "glDepthFunc" => depth_func as *const c_void,
+        "glDepthMask" => depth_mask as *const c_void,
+        "glDisable" => disable as *const c_void,
+        "glDrawArrays" => draw_arrays as *const c_void,
+        "glDrawArraysInstanced" => draw_arrays_instanced as *const c_void,
+        "glDrawElements" => draw_elements as *const c_void,
+        "glDrawElementsInstanced" => draw_elements_instanced as *const c_void,
+        "glEnable" => enable as *const c_void,
+        "glEnableVertexAttribArray" => enable_vertex_attrib_array as *const c_void,
+        "glEndQuery" => end_query as *const c_void,
+        "glEndTransformFeedback" => end_transform_feedback as *const c_void,
+        "glFenceSync" => fence_sync as *const c_void,
+        "glFlush" => flush as *const c_void,
+        "glFlushMappedBufferRange" => flush_mapped_buffer_range as *const c_void,
+        "glFramebufferRenderbuffer" => framebuffer_renderbuffer as *const c_void,
+        "glFramebufferTexture2D" => framebuffer_texture_2d as *const c_void,
+        "glFramebufferTextureLayer" => framebuffer_texture_layer as *const c_void,
+        "glGenBuffers" => gen_buffers as *const c_void,
+        "glGenFramebuffers" => gen_framebuffers as *const c_void,
+        "glGenQueries" => gen_queries as *const c_void,
+        "glGenRenderbuffers" => gen_renderbuffers as *const c_void,
+        "glGenSamplers" => gen_samplers as *const c_void,
+        "glGenTextures" => gen_textures as *const c_void,
+        "glGenVertexArrays" => gen_vertex_arrays as *const c_void,
+        "glGenerateMipmap" => generate_mipmap as *const c_void,
+        "glGetActiveAttrib" => get_active_attrib as *const c_void,
+        "glGetActiveUniform" => get_active_uniform as *const c_void,
+        "glGetAttribLocation" => get_attrib_location as *const c_void,
+        "glGetBufferParameteriv" => get_buffer_parameteriv as *const c_void,
+        "glGetError" => get_error as *const c_void,
+        "glGetFloatv" => get_floatv as *const c_void,
+        "glGetIntegerv" => get_integerv as *const c_void,
+        "glGetProgramInfoLog" => get_program_info_log as *const c_void,
+        "glGetProgramiv" => get_programiv as *const c_void,
+        "glGetQueryObjectuiv" => get_query_objectuiv as *const c_void,
+        "glGetShaderInfoLog" => get_shader_info_log as *const c_void,
+        "glGetShaderiv" => get_shaderiv as *const c_void,
+        "glGetString" => get_string as *const c_void,
+        "glGetStringi" => get_stringi as *const c_void,
+        "glGetUniformLocation" => get_uniform_location as *const c_void,
+        "glGetUniformfv" => get_uniformfv as *const c_void,
+        "glIsEnabled" => is_enabled as *const c_void,
+        "glLinkProgram" => link_program as *const c_void,
+        "glMapBufferRange" => map_buffer_range as *const c_void,
+        "glPixelStorei" => pixel_storei as *const c_void,
+        "glPolygonOffset" => polygon_offset as *const c_void,
+        "glReadPixels" => read_pixels as *const c_void,
+        "glRenderbufferStorage" => renderbuffer_storage as *const c_void,
+        "glSamplerParameteri" => sampler_parameteri as *const c_void,
+        "glScissor" => scissor as *const c_void,
+        "glShaderSource" => shader_source as *const c_void,
+        "glStencilFunc" => stencil_func as *const c_void,
+        "glStencilMask" => stencil_mask as *const c_void,
+        "glStencilOp" => stencil_op as *const c_void,
+        "glTexImage2D" => tex_image_2d as *const c_void,
+        "glTexImage3D" => tex_image_3d as *const c_void,
+        "glTexParameteri" => tex_parameteri as *const c_void,
+        "glTransformFeedbackVaryings" => transform_feedback_varyings as *const c_void,
+        "glUniform1f" => uniform_1f as *const c_void,
+        "glUniform1i" => uniform_1i as *const c_void,
+        "glUniform2fv" => uniform_2fv as *const c_void,
+        "glUniform3fv" => uniform_3fv as *const c_void,
+        "glUniform4fv" => uniform_4fv as *const c_void,
+        "glUniformMatrix4fv" => uniform_matrix_4fv as *const c_void,
+        "glUnmapBuffer" => unmap_buffer as *const c_void,
+        "glUseProgram" => use_program as *const c_void,
+        "glVertexAttribDivisor" => vertex_attrib_divisor as *const c_void,
+        "glVertexAttribIPointer" => vertex_attrib_i_pointer as *const c_void,
+        "glVertexAttribPointer" => vertex_attrib_pointer as *const c_void,
+        "glViewport" => viewport as *const c_void,
+        _ => ptr::null(),
+    }
+}
+
+// Generating and deleting names of the object kinds WebGL creates one at a time
+macro_rules! object_names {
+    ($gen:ident, $delete:ident, $names:ident, $create:ident, $destroy:ident) => {
+        extern "system" fn $gen(count: GLsizei, names: *mut GLuint) {
+            with_state(|state| {
+                for name_i in 0..count.max(0) as usize {
+                    let name = state.$names.insert(state.context.$create());
+                    unsafe { *names.add(name_i) = name };
+                }
+            });
+        }
+
+        extern "system" fn $delete(count: GLsizei, names: *const GLuint) {
+            with_state(|state| {
+                for name_i in 0..count.max(0) as usize {
+                    let object = state.$names.remove(unsafe { *names.add(name_i) });
+                    state.context.$destroy(object.as_ref());
+                }
+            });
+        }
+    };
+}
+
+object_names!(
+    gen_buffers,
+    delete_buffers,
+    buffers,
+    create_buffer,
+    delete_buffer
+);
+object_names!(
+    gen_textures,
+    delete_textures,
+    textures,
+    create_texture,
+    delete_texture
+);
+object_names!(
+    gen_framebuffers,
+    delete_framebuffers,
+    framebuffers,
+    create_framebuffer,
+    delete_framebuffer
+);
+object_names!(
+    gen_renderbuffers,
+    delete_renderbuffers,
+    renderbuffers,
+    create_renderbuffer,
+    delete_renderbuffer
+);
+object_names!(
+    gen_vertex_arrays,
+    delete_vertex_arrays,
+    vertex_arrays,
+    create_vertex_array,
+    delete_vertex_array
+);
+object_names!(
+    gen_queries,
+    delete_queries,
+    queries,
+    create_query,
+    delete_query
+);
+object_names!(
+    gen_samplers,
+    delete_samplers,
+    samplers,
+    create_sampler,
+    delete_sampler
+);
+
+extern "system" fn get_error() -> GLenum {
+    with_state(|state| {
+        if state.error != gl::NO_ERROR {
+            return mem::replace(&mut state.error, gl::NO_ERROR);
+        }
+        state.context.get_error()
+    })
+}
+
+extern "system" fn get_integerv(pname: GLenum, data: *mut GLint) {
+    with_state(|state| {
+        let values = match pname {
+            gl::MAJOR_VERSION => vec![3],
+            gl::MINOR_VERSION => vec![0],
+            gl::NUM_EXTENSIONS => vec![state.extensions.len() as GLint],
+            // WebGL contexts have no debug or robustness flags
+            gl::CONTEXT_FLAGS => vec![0],
+            _ => match state.context.get_parameter(pname) {
+                Ok(value) => state.integers(&value),
+                Err(_) => return raise(&mut state.error, gl::INVALID_ENUM),
+            },
+        };
+        unsafe { write_values(&values, data) };
+    });
+}
+
+extern "system" fn get_floatv(pname: GLenum, data: *mut GLfloat) {
+    with_state(|state| match state.context.get_parameter(pname) {
+        Ok(value) => unsafe { write_values(&floats(&value), data) },
+        Err(_) => raise(&mut state.error, gl::INVALID_ENUM),
+    });
+}
+
+extern "system" fn get_string(name: GLenum) -> *const GLubyte {
+    with_state(|state| {
+        let string = match name {
+            // Reads "WebGL 2.0 (...)"; the ES prefix is what marks an ES context to GL code
+            gl::VERSION => format!(
+                "OpenGL ES 3.0 {}",
+                parameter_string(&state.context, gl::VERSION)
+            ),
+            gl::EXTENSIONS => state.extensions.join(" "),
+            _ => parameter_string(&state.context, name),
+        };
+        state
+            .strings
+            .entry((name, 0))
+            .or_insert_with(|| CString::new(string).unwrap_or_default())
+            .as_ptr() as *const GLubyte
+    })
+}
+
+extern "system" fn get_stringi(name: GLenum, index: GLuint) -> *const GLubyte {
+    with_state(|state| {
+        let extension = match (name, state.extensions.get(index as usize)) {
+            (gl::EXTENSIONS, Some(extension)) => extension.clone(),
+            _ => {
+                raise(&mut state.error, gl::INVALID_VALUE);
+                return ptr::null();
+            }
+        };
+        state
+            .strings
+            .entry((name, index))
+            .or_insert_with(|| CString::new(extension).unwrap_or_default())
+            .as_ptr() as *const GLubyte
+    })
+}
+
+extern "system" fn enable(capability: GLenum) {
+    if !ALWAYS_ENABLED.contains(&capability) {
+        with_state(|state| state.context.enable(capability));
+    }
+}
+
+extern "system" fn disable(capability: GLenum) {
+    if !ALWAYS_ENABLED.contains(&capability) {
+        with_state(|state| state.context.disable(capability));
+    }
+}
+
+extern "system" fn is_enabled(capability: GLenum) -> GLboolean {
+    let enabled = ALWAYS_ENABLED.contains(&capability)
+        || with_state(|state| state.context.is_enabled(capability));
+    if enabled {
+        gl::TRUE
+    } else {
+        gl::FALSE
+    }
+}
+
+extern "system" fn viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+    with_state(|state| state.context.viewport(x, y, width, height));
+}
+
+extern "system" fn scissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+    with_state(|state| state.context.scissor(x, y, width, height));
+}
+
+extern "system" fn clear(mask: GLbitfield) {
+    with_state(|state| state.context.clear(mask));
+}
+
+extern "system" fn clear_color(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+    with_state(|state| state.context.clear_color(red, green, blue, alpha));
+}
+
+extern "system" fn flush() {
+    with_state(|state| state.context.flush());
+}
+
+extern "system" fn blend_func(source: GLenum, destination: GLenum) {
+    with_state(|state| state.context.blend_func(source, destination));
+}
+
+extern "system" fn depth_func(func: GLenum) {
+    with_state(|state| state.context.depth_func(func));
+}
+
+extern "system" fn depth_mask(flag: GLboolean) {
+    with_state(|state| state.context.depth_mask(flag != gl::FALSE));
+}
+
+extern "system" fn color_mask(red: GLboolean, green: GLboolean, blue: GLboolean, alpha: GLboolean) {
+    with_state(|state| {
+        state.context.color_mask(
+            red != gl::FALSE,
+            green != gl::FALSE,
+            blue != gl::FALSE,
+            alpha != gl::FALSE,
+        )
+    });
+}
+
+extern "system" fn stencil_func(func: GLenum, reference: GLint, mask: GLuint) {
+    with_state(|state| state.context.stencil_func(func, reference, mask));
+}
+
+extern "system" fn stencil_mask(mask: GLuint) {
+    with_state(|state| state.context.stencil_mask(mask));
+}
+
+extern "system" fn stencil_op(stencil_fail: GLenum, depth_fail: GLenum, depth_pass: GLenum) {
+    with_state(|state| {
+        state
+            .context
+            .stencil_op(stencil_fail, depth_fail, depth_pass)
+    });
+}
+
+extern "system" fn polygon_offset(factor: GLfloat, units: GLfloat) {
+    with_state(|state| state.context.polygon_offset(factor, units));
+}
+
+extern "system" fn pixel_storei(pname: GLenum, param: GLint) {
+    with_state(|state| {
+        match pname {
+            gl::PACK_ALIGNMENT => state.pack_alignment = param.max(1) as usize,
+            gl::UNPACK_ALIGNMENT => state.unpack_alignment = param.max(1) as usize,
+            _ => {}
+        }
+        state.context.pixel_storei(pname, param);
+    });
+}
+
+extern "system" fn bind_buffer(target: GLenum, buffer: GLuint) {
+    with_state(|state| {
+        if target == gl::PIXEL_PACK_BUFFER {
+            state.pixel_pack_buffer = buffer != 0;
+        }
+        state.context.bind_buffer(target, state.buffers.get(buffer));
+    });
+}
+
+extern "system" fn bind_buffer_base(target: GLenum, index: GLuint, buffer: GLuint) {
+    with_state(|state| {
+        state
+            .context
+            .bind_buffer_base(target, index, state.buffers.get(buffer))
+    });
+}
+
+extern "system" fn buffer_data(
+    target: GLenum,
+    size: GLsizeiptr,
+    data: *const c_void,
+    usage: GLenum,
+) {
+    with_state(|state| {
+        if data.is_null() {
+            state
+                .context
+                .buffer_data_with_i32(target, size as i32, usage);
+        } else {
+            let data = unsafe { slice_of(data as *const u8, size as usize) };
+            state.context.buffer_data_with_u8_array(target, data, usage);
+        }
+    });
+}
+
+extern "system" fn buffer_sub_data(
+    target: GLenum,
+    offset: GLintptr,
+    size: GLsizeiptr,
+    data: *const c_void,
+) {
+    let data = unsafe { slice_of(data as *const u8, size as usize) };
+    with_state(|state| {
+        state
+            .context
+            .buffer_sub_data_with_i32_and_u8_array(target, offset as i32, data)
+    });
+}
+
+extern "system" fn get_buffer_parameteriv(target: GLenum, pname: GLenum, params: *mut GLint) {
+    with_state(|state| {
+        let values = match pname {
+            gl::BUFFER_MAPPED => vec![GLint::from(state.mapped_ranges.contains_key(&target))],
+            _ => state
+                .integers(&state.context.get_buffer_parameter(target, pname))
+                .into_iter()
+                .take(1)
+                .collect(),
+        };
+        unsafe { write_values(&values, params) };
+    });
+}
+
+/// WebGL cannot map buffers, so the range is copied into wasm memory, first out of the buffer
+/// when mapped for reading, and back into it when flushed or unmapped after writing.
+extern "system" fn map_buffer_range(
+    target: GLenum,
+    offset: GLintptr,
+    length: GLsizeiptr,
+    access: GLbitfield,
+) -> *mut c_void {
+    with_state(|state| {
+        if state.mapped_ranges.contains_key(&target) {
+            raise(&mut state.error, gl::INVALID_OPERATION);
+            return ptr::null_mut();
+        }
+        let mut data = vec![0_u8; length.max(0) as usize];
+        if access & gl::MAP_READ_BIT != 0 {
+            let view = unsafe {
+                pixel_view(
+                    data.as_ptr() as *const c_void,
+                    data.len(),
+                    gl::UNSIGNED_BYTE,
+                )
+            };
+            if let Some(view) = view {
+                state
+                    .context
+                    .get_buffer_sub_data_with_i32_and_array_buffer_view(
+                        target,
+                        offset as i32,
+                        &view,
+                    );
+            }
+        }
+        let pointer = data.as_mut_ptr() as *mut c_void;
+        state.mapped_ranges.insert(
+            target,
+            MappedRange {
+                offset,
+                access,
+                data,
+            },
+        );
+        pointer
+    })
+}
+
+extern "system" fn flush_mapped_buffer_range(target: GLenum, offset: GLintptr, length: GLsizeiptr) {
+    with_state(|state| {
+        let range = match state.mapped_ranges.get(&target) {
+            Some(range) => range,
+            None => return raise(&mut state.error, gl::INVALID_OPERATION),
+        };
+        let start = (offset.max(0) as usize).min(range.data.len());
+        let end = (start + length.max(0) as usize).min(range.data.len());
+        state.context.buffer_sub_data_with_i32_and_u8_array(
+            target,
+            (range.offset as usize + start) as i32,
+            &range.data[start..end],
+        );
+    });
+}
+
+extern "system" fn unmap_buffer(target: GLenum) -> GLboolean {
+    with_state(|state| match state.mapped_ranges.remove(&target) {
+        Some(range) => {
+            let flushed_on_unmap = range.access & gl::MAP_WRITE_BIT != 0
+                && range.access & gl::MAP_FLUSH_EXPLICIT_BIT == 0;
+            if flushed_on_unmap {
+                state.context.buffer_sub_data_with_i32_and_u8_array(
+                    target,
+                    range.offset as i32,
+                    &range.data,
+                );
+            }
+            gl::TRUE
+        }
+        None => {
+            raise(&mut state.error, gl::INVALID_OPERATION);
+            gl::FALSE
+        }
+    })
+}
+
+extern "system" fn bind_vertex_array(vertex_array: GLuint) {
+    with_state(|state| {
+        state
+            .context
+            .bind_vertex_array(state.vertex_arrays.get(vertex_array))
+    });
+}
+
+extern "system" fn enable_vertex_attrib_array(index: GLuint) {
+    with_state(|state| state.context.enable_vertex_attrib_array(index));
+}
+
+// Client-side vertex arrays do not exist in WebGL, so `pointer` is always a buffer offset
+extern "system" fn vertex_attrib_pointer(
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    normalized: GLboolean,
+    stride: GLsizei,
+    pointer: *const c_void,
+) {
+    with_state(|state| {
+        state.context.vertex_attrib_pointer_with_i32(
+            index,
+            size,
+            type_,
+            normalized != gl::FALSE,
+            stride,
+            pointer as i32,
+        )
+    });
+}
+
+extern "system" fn vertex_attrib_i_pointer(
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    stride: GLsizei,
+    pointer: *const c_void,
+) {
+    with_state(|state| {
+        state
+            .context
+            .vertex_attrib_i_pointer_with_i32(index, size, type_, stride, pointer as i32)
+    });
+}
+
+extern "system" fn vertex_attrib_divisor(index: GLuint, divisor: GLuint) {
+    with_state(|state| state.context.vertex_attrib_divisor(index, divisor));
+}
+
+extern "system" fn draw_arrays(mode: GLenum, first: GLint, count: GLsizei) {
+    with_state(|state| state.context.draw_arrays(mode, first, count));
+}
+
+extern "system" fn draw_arrays_instanced(
+    mode: GLenum,
+    first: GLint,
+    count: GLsizei,
+    instance_count: GLsizei,
+) {
+    with_state(|state| {
+        state
+            .context
+            .draw_arrays_instanced(mode, first, count, instance_count)
+    });
+}
+
+// Indices always come from the bound element array buffer, so `indices` is an offset into it
+extern "system" fn draw_elements(
+    mode: GLenum,
+    count: GLsizei,
+    type_: GLenum,
+    indices: *const c_void,
+) {
+    with_state(|state| {
+        state
+            .context
+            .draw_elements_with_i32(mode, count, type_, indices as i32)
+    });
+}
+
+extern "system" fn draw_elements_instanced(
+    mode: GLenum,
+    count: GLsizei,
+    type_: GLenum,
+    indices: *const c_void,
+    instance_count: GLsizei,
+) {
+    with_state(|state| {
+        state.context.draw_elements_instanced_with_i32(
+            mode,
+            count,
+            type_,
+            indices as i32,
+            instance_count,
+        )
+    });
+}
+
+extern "system" fn begin_transform_feedback(primitive_mode: GLenum) {
+    with_state(|state| state.context.begin_transform_feedback(primitive_mode));
+}
+
+extern "system" fn end_transform_feedback() {
+    with_state(|state| state.context.end_transform_feedback());
+}
+
+extern "system" fn active_texture(texture: GLenum) {
+    with_state(|state| state.context.active_texture(texture));
+}
+
+extern "system" fn bind_texture(target: GLenum, texture: GLuint) {
+    with_state(|state| {
+        state
+            .context
+            .bind_texture(target, state.textures.get(texture))
+    });
+}
+
+extern "system" fn tex_parameteri(target: GLenum, pname: GLenum, param: GLint) {
+    with_state(|state| state.context.tex_parameteri(target, pname, param));
+}
+
+extern "system" fn generate_mipmap(target: GLenum) {
+    with_state(|state| state.context.generate_mipmap(target));
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_image_2d(
+    target: GLenum,
+    level: GLint,
+    internal_format: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    border: GLint,
+    format: GLenum,
+    type_: GLenum,
+    pixels: *const c_void,
+) {
+    with_state(|state| {
+        let size = image_size((width, height, 1), format, type_, state.unpack_alignment);
+        let pixels = unsafe { pixel_view(pixels, size, type_) };
+        let result = state
+            .context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+                target,
+                level,
+                internal_format,
+                width,
+                height,
+                border,
+                format,
+                type_,
+                pixels.as_ref(),
+            );
+        check_thrown(&mut state.error, result);
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_image_3d(
+    target: GLenum,
+    level: GLint,
+    internal_format: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    depth: GLsizei,
+    border: GLint,
+    format: GLenum,
+    type_: GLenum,
+    pixels: *const c_void,
+) {
+    with_state(|state| {
+        let size = image_size(
+            (width, height, depth),
+            format,
+            type_,
+            state.unpack_alignment,
+        );
+        let pixels = unsafe { pixel_view(pixels, size, type_) };
+        let result = state.context.tex_image_3d_with_opt_array_buffer_view(
+            target,
+            level,
+            internal_format,
+            width,
+            height,
+            depth,
+            border,
+            format,
+            type_,
+            pixels.as_ref(),
+        );
+        check_thrown(&mut state.error, result);
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn copy_tex_sub_image_3d(
+    target: GLenum,
+    level: GLint,
+    x_offset: GLint,
+    y_offset: GLint,
+    z_offset: GLint,
+    x: GLint,
+    y: GLint,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    with_state(|state| {
+        state.context.copy_tex_sub_image_3d(
+            target, level, x_offset, y_offset, z_offset, x, y, width, height,
+        )
+    });
+}
+
+extern "system" fn read_pixels(
+    x: GLint,
+    y: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    type_: GLenum,
+    pixels: *mut c_void,
+) {
+    with_state(|state| {
+        let result = if state.pixel_pack_buffer {
+            state
+                .context
+                .read_pixels_with_i32(x, y, width, height, format, type_, pixels as i32)
+        } else {
+            let size = image_size((width, height, 1), format, type_, state.pack_alignment);
+            let pixels = unsafe { pixel_view(pixels, size, type_) };
+            state.context.read_pixels_with_opt_array_buffer_view(
+                x,
+                y,
+                width,
+                height,
+                format,
+                type_,
+                pixels.as_ref(),
+            )
+        };
+        check_thrown(&mut state.error, result);
+    });
+}
+
+extern "system" fn bind_sampler(unit: GLuint, sampler: GLuint) {
+    with_state(|state| {
+        state
+            .context
+            .bind_sampler(unit, state.samplers.get(sampler))
+    });
+}
+
+extern "system" fn sampler_parameteri(sampler: GLuint, pname: GLenum, param: GLint) {
+    with_state(|state| {
+        if let Some(sampler) = lookup(&mut state.error, &state.samplers, sampler) {
+            state.context.sampler_parameteri(sampler, pname, param);
+        }
+    });
+}
+
+extern "system" fn bind_framebuffer(target: GLenum, framebuffer: GLuint) {
+    with_state(|state| {
+        state
+            .context
+            .bind_framebuffer(target, state.framebuffers.get(framebuffer))
+    });
+}
+
+extern "system" fn bind_renderbuffer(target: GLenum, renderbuffer: GLuint) {
+    with_state(|state| {
+        state
+            .context
+            .bind_renderbuffer(target, state.renderbuffers.get(renderbuffer))
+    });
+}
+
+extern "system" fn renderbuffer_storage(
+    target: GLenum,
+    internal_format: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    with_state(|state| {
+        state
+            .context
+            .renderbuffer_storage(target, internal_format, width, height)
+    });
+}
+
+extern "system" fn framebuffer_texture_2d(
+    target: GLenum,
+    attachment: GLenum,
+    texture_target: GLenum,
+    texture: GLuint,
+    level: GLint,
+) {
+    with_state(|state| {
+        state.context.framebuffer_texture_2d(
+            target,
+            attachment,
+            texture_target,
+            state.textures.get(texture),
+            level,
+        )
+    });
+}
+
+extern "system" fn framebuffer_texture_layer(
+    target: GLenum,
+    attachment: GLenum,
+    texture: GLuint,
+    level: GLint,
+    layer: GLint,
+) {
+    with_state(|state| {
+        state.context.framebuffer_texture_layer(
+            target,
+            attachment,
+            state.textures.get(texture),
+            level,
+            layer,
+        )
+    });
+}
+
+extern "system" fn framebuffer_renderbuffer(
+    target: GLenum,
+    attachment: GLenum,
+    renderbuffer_target: GLenum,
+    renderbuffer: GLuint,
+) {
+    with_state(|state| {
+        state.context.framebuffer_renderbuffer(
+            target,
+            attachment,
+            renderbuffer_target,
+            state.renderbuffers.get(renderbuffer),
+        )
+    });
+}
+
+extern "system" fn check_framebuffer_status(target: GLenum) -> GLenum {
+    with_state(|state| state.context.check_framebuffer_status(target))
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn blit_framebuffer(
+    source_x0: GLint,
+    source_y0: GLint,
+    source_x1: GLint,
+    source_y1: GLint,
+    destination_x0: GLint,
+    destination_y0: GLint,
+    destination_x1: GLint,
+    destination_y1: GLint,
+    mask: GLbitfield,
+    filter: GLenum,
+) {
+    with_state(|state| {
+        state.context.blit_framebuffer(
+            source_x0,
+            source_y0,
+            source_x1,
+            source_y1,
+            destination_x0,
+            destination_y0,
+            destination_x1,
+            destination_y1,
+            mask,
+            filter,
+        )
+    });
+}
+
+extern "system" fn begin_query(target: GLenum, id: GLuint) {
+    with_state(|state| {
+        if let Some(query) = lookup(&mut state.error, &state.queries, id) {
+            state.context.begin_query(target, query);
+        }
+    });
+}
+
+extern "system" fn end_query(target: GLenum) {
+    with_state(|state| state.context.end_query(target));
+}
+
+// Results only become available once the browser has regained control, after the frame
+extern "system" fn get_query_objectuiv(id: GLuint, pname: GLenum, params: *mut GLuint) {
+    with_state(|state| {
+        if let Some(query) = lookup(&mut state.error, &state.queries, id) {
+            let value = state.context.get_query_parameter(query, pname);
+            let values: Vec<GLuint> = state
+                .integers(&value)
+                .into_iter()
+                .take(1)
+                .map(|value| value as GLuint)
+                .collect();
+            unsafe { write_values(&values, params) };
+        }
+    });
+}
+
+extern "system" fn fence_sync(condition: GLenum, flags: GLbitfield) -> GLsync {
+    with_state(|state| {
+        let name = state
+            .syncs
+            .insert(state.context.fence_sync(condition, flags));
+        name as usize as GLsync
+    })
+}
+
+// WebGL does not let the page block on the GPU, so waits only poll: the timeout is ignored
+extern "system" fn client_wait_sync(sync: GLsync, flags: GLbitfield, _timeout: GLuint64) -> GLenum {
+    with_state(
+        |state| match lookup(&mut state.error, &state.syncs, sync as usize as GLuint) {
+            Some(sync) => state.context.client_wait_sync_with_u32(sync, flags, 0),
+            None => gl::WAIT_FAILED,
+        },
+    )
+}
+
+extern "system" fn delete_sync(sync: GLsync) {
+    with_state(|state| {
+        let sync = state.syncs.remove(sync as usize as GLuint);
+        state.context.delete_sync(sync.as_ref());
+    });
+}
+
+extern "system" fn create_shader(shader_type: GLenum) -> GLuint {
+    with_state(|state| {
+        state
+            .shaders
+            .insert(state.context.create_shader(shader_type))
+    })
+}
+
+extern "system" fn delete_shader(shader: GLuint) {
+    with_state(|state| {
+        let shader = state.shaders.remove(shader);
+        state.context.delete_shader(shader.as_ref());
+    });
+}
+
+extern "system" fn shader_source(
+    shader: GLuint,
+    count: GLsizei,
+    strings: *const *const GLchar,
+    lengths: *const GLint,
+) {
+    let mut source = String::new();
+    for string_i in 0..count.max(0) as usize {
+        unsafe {
+            let string = *strings.add(string_i);
+            let length = if lengths.is_null() {
+                -1
+            } else {
+                *lengths.add(string_i)
+            };
+            if length < 0 {
+                source.push_str(&c_string(string));
+            } else {
+                let bytes = slice_of(string as *const u8, length as usize);
+                source.push_str(&String::from_utf8_lossy(bytes));
+            }
+        }
+    }
+    with_state(|state| {
+        if let Some(shader) = lookup(&mut state.error, &state.shaders, shader) {
+            state.context.shader_source(shader, &source);
+        }
+    });
+}
+
+extern "system" fn compile_shader(shader: GLuint) {
+    with_state(|state| {
+        if let Some(shader) = lookup(&mut state.error, &state.shaders, shader) {
+            state.context.compile_shader(shader);
+        }
+    });
+}
+
+extern "system" fn get_shaderiv(shader: GLuint, pname: GLenum, params: *mut GLint) {
+    with_state(|state| {
+        let shader = match lookup(&mut state.error, &state.shaders, shader) {
+            Some(shader) => shader,
+            None => return,
+        };
+        let value = match pname {
+            gl::INFO_LOG_LENGTH => info_log_length(state.context.get_shader_info_log(shader)),
+            _ => state
+                .integers(&state.context.get_shader_parameter(shader, pname))
+                .first()
+                .copied()
+                .unwrap_or(0),
+        };
+        unsafe { write_values(&[value], params) };
+    });
+}
+
+extern "system" fn get_shader_info_log(
+    shader: GLuint,
+    buffer_size: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    with_state(|state| {
+        if let Some(shader) = lookup(&mut state.error, &state.shaders, shader) {
+            let log = state
+                .context
+                .get_shader_info_log(shader)
+                .unwrap_or_default();
+            unsafe { write_string(&log, buffer_size, length, info_log) };
+        }
+    });
+}
+
+extern "system" fn create_program() -> GLuint {
+    with_state(|state| state.programs.insert(state.context.create_program()))
+}
+
+extern "system" fn delete_program(program: GLuint) {
+    with_state(|state| {
+        state.forget_uniforms(program);
+        let program = state.programs.remove(program);
+        state.context.delete_program(program.as_ref());
+    });
+}
+
+extern "system" fn attach_shader(program: GLuint, shader: GLuint) {
+    with_state(|state| {
+        let program = lookup(&mut state.error, &state.programs, program);
+        let shader = lookup(&mut state.error, &state.shaders, shader);
+        if let (Some(program), Some(shader)) = (program, shader) {
+            state.context.attach_shader(program, shader);
+        }
+    });
+}
+
+extern "system" fn transform_feedback_varyings(
+    program: GLuint,
+    count: GLsizei,
+    varyings: *const *const GLchar,
+    buffer_mode: GLenum,
+) {
+    let names: Array = (0..count.max(0) as usize)
+        .map(|varying_i| JsValue::from_str(&unsafe { c_string(*varyings.add(varying_i)) }))
+        .collect();
+    with_state(|state| {
+        if let Some(program) = lookup(&mut state.error, &state.programs, program) {
+            state
+                .context
+                .transform_feedback_varyings(program, &names, buffer_mode);
+        }
+    });
+}
+
+extern "system" fn link_program(program: GLuint) {
+    with_state(|state| {
+        state.forget_uniforms(program);
+        if let Some(program) = lookup(&mut state.error, &state.programs, program) {
+            state.context.link_program(program);
+        }
+    });
+}
+
+extern "system" fn use_program(program: GLuint) {
+    with_state(|state| state.context.use_program(state.programs.get(program)));
+}
+
+extern "system" fn get_programiv(program: GLuint, pname: GLenum, params: *mut GLint) {
+    with_state(|state| {
+        let program = match lookup(&mut state.error, &state.programs, program) {
+            Some(program) => program,
+            None => return,
+        };
+        let context = &state.context;
+        // WebGL has no maximum name lengths, since its names are JavaScript strings
+        let longest_name = |count: GLenum, info: &dyn Fn(u32) -> Option<String>| {
+            let count = context.get_program_parameter(program, count).as_f64();
+            (0..count.unwrap_or(0.0) as u32)
+                .filter_map(info)
+                .map(|name| name.len() as GLint + 1)
+                .max()
+                .unwrap_or(0)
+        };
+        let value = match pname {
+            gl::INFO_LOG_LENGTH => info_log_length(context.get_program_info_log(program)),
+            gl::ACTIVE_UNIFORM_MAX_LENGTH => longest_name(gl::ACTIVE_UNIFORMS, &|index| {
+                context
+                    .get_active_uniform(program, index)
+                    .map(|info| info.name())
+            }),
+            gl::ACTIVE_ATTRIBUTE_MAX_LENGTH => longest_name(gl::ACTIVE_ATTRIBUTES, &|index| {
+                context
+                    .get_active_attrib(program, index)
+                    .map(|info| info.name())
+            }),
+            _ => state
+                .integers(&context.get_program_parameter(program, pname))
+                .first()
+                .copied()
+                .unwrap_or(0),
+        };
+        unsafe { write_values(&[value], params) };
+    });
+}
+
+extern "system" fn get_program_info_log(
+    program: GLuint,
+    buffer_size: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    with_state(|state| {
+        if let Some(program) = lookup(&mut state.error, &state.programs, program) {
+            let log = state
+                .context
+                .get_program_info_log(program)
+                .unwrap_or_default();
+            unsafe { write_string(&log, buffer_size, length, info_log) };
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn get_active_uniform(
+    program: GLuint,
+    index: GLuint,
+    buffer_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    with_state(|state| {
+        let info = match lookup(&mut state.error, &state.programs, program) {
+            Some(program) => state.context.get_active_uniform(program, index),
+            None => return,
+        };
+        match info {
+            Some(info) => unsafe {
+                write_values(&[info.size()], size);
+                write_values(&[info.type_()], type_);
+                write_string(&info.name(), buffer_size, length, name);
+            },
+            None => raise(&mut state.error, gl::INVALID_VALUE),
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn get_active_attrib(
+    program: GLuint,
+    index: GLuint,
+    buffer_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    with_state(|state| {
+        let info = match lookup(&mut state.error, &state.programs, program) {
+            Some(program) => state.context.get_active_attrib(program, index),
+            None => return,
+        };
+        match info {
+            Some(info) => unsafe {
+                write_values(&[info.size()], size);
+                write_values(&[info.type_()], type_);
+                write_string(&info.name(), buffer_size, length, name);
+            },
+            None => raise(&mut state.error, gl::INVALID_VALUE),
+        }
+    });
+}
+
+extern "system" fn get_attrib_location(program: GLuint, name: *const GLchar) -> GLint {
+    let name = unsafe { c_string(name) };
+    with_state(
+        |state| match lookup(&mut state.error, &state.programs, program) {
+            Some(program) => state.context.get_attrib_location(program, &name),
+            None => -1,
+        },
+    )
+}
+
+extern "system" fn get_uniform_location(program: GLuint, name: *const GLchar) -> GLint {
+    let name = unsafe { c_string(name) };
+    with_state(|state| {
+        let key = (program, name);
+        if let Some(location) = state.uniform_names.get(&key) {
+            return *location;
+        }
+        let location = match lookup(&mut state.error, &state.programs, program) {
+            Some(program) => state.context.get_uniform_location(program, &key.1),
+            None => return -1,
+        };
+        match location {
+            Some(location) => {
+                state.uniform_locations.push(Some(location));
+                let location = state.uniform_locations.len() as GLint - 1;
+                state.uniform_names.insert(key, location);
+                location
+            }
+            None => -1,
+        }
+    })
+}
+
+extern "system" fn get_uniformfv(program: GLuint, location: GLint, params: *mut GLfloat) {
+    with_state(|state| {
+        let program = lookup(&mut state.error, &state.programs, program);
+        if let (Some(program), Some(location)) = (program, state.uniform_location(location)) {
+            let values = floats(&state.context.get_uniform(program, location));
+            unsafe { write_values(&values, params) };
+        }
+    });
+}
+
+extern "system" fn uniform_1i(location: GLint, value: GLint) {
+    with_state(|state| {
+        state
+            .context
+            .uniform1i(state.uniform_location(location), value)
+    });
+}
+
+extern "system" fn uniform_1f(location: GLint, value: GLfloat) {
+    with_state(|state| {
+        state
+            .context
+            .uniform1f(state.uniform_location(location), value)
+    });
+}
+
+extern "system" fn uniform_2fv(location: GLint, count: GLsizei, value: *const GLfloat) {
+    let values = unsafe { slice_of(value, count.max(0) as usize * 2) };
+    with_state(|state| {
+        state
+            .context
+            .uniform2fv_with_f32_array(state.uniform_location(location), values)
+    });
+}
+
+extern "system" fn uniform_3fv(location: GLint, count: GLsizei, value: *const GLfloat) {
+    let values = unsafe { slice_of(value, count.max(0) as usize * 3) };
+    with_state(|state| {
+        state
+            .context
+            .uniform3fv_with_f32_array(state.uniform_location(location), values)
+    });
+}
+
+extern "system" fn uniform_4fv(location: GLint, count: GLsizei, value: *const GLfloat) {
+    let values = unsafe { slice_of(value, count.max(0) as usize * 4) };
+    with_state(|state| {
+        state
+            .context
+            .uniform4fv_with_f32_array(state.uniform_location(location), values)
+    });
+}
+
+extern "system" fn uniform_matrix_4fv(
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: *const GLfloat,
+) {
+    let values = unsafe { slice_of(value, count.max(0) as usize * 16) };
+    with_state(|state| {
+        state.context.uniform_matrix4fv_with_f32_array(
+            state.uniform_location(location),
+            transpose != gl::FALSE,
+            values,
+        )
+    });
+}
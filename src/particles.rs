@@ -0,0 +1,265 @@
+use crate::flipbook::{Flipbook, UvRect};
+use crate::math::color::Color;
+use crate::math::easing::lerp;
+use crate::math::glm::{self, Vec3};
+use crate::math::random::{random_point_in_sphere, Rng};
+use crate::ogl::billboard_renderer::{PUFF_SHEET_COLUMNS, PUFF_SHEET_ROWS};
+
+/// How particles combine with what is behind them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    /// Adds light, order independent; for fire and sparks
+    Additive,
+    /// Covers what is behind, drawn back to front; for smoke and water
+    Alpha,
+}
+
+/// A value at the start and the end of a particle's life, blended linearly in between.
+#[derive(Clone, Copy, Debug)]
+pub struct OverLife<T> {
+    pub start: T,
+    pub end: T,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmitterSettings {
+    /// Particles spawned per second
+    pub rate: f32,
+    /// Seconds a particle lives, plus or minus `lifetime_variance`
+    pub lifetime: f32,
+    pub lifetime_variance: f32,
+    /// Particles spawn within this distance of the emitter
+    pub spawn_radius: f32,
+    /// Initial velocity, plus a random offset up to `velocity_spread` long
+    pub velocity: Vec3,
+    pub velocity_spread: f32,
+    /// Gravity, buoyancy and wind, applied every step
+    pub acceleration: Vec3,
+    /// Fraction of velocity lost per second
+    pub drag: f32,
+    /// Largest spin in degrees per second either way
+    pub spin: f32,
+    pub color: OverLife<Color>,
+    pub size: OverLife<f32>,
+    pub blend: BlendMode,
+    /// Frames of the renderer's sprite sheet each particle plays from its spawn, or none for
+    /// soft discs
+    pub flipbook: Option<Flipbook>,
+    /// Spawning pauses while this many particles are alive
+    pub max_particles: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub rotation_degrees: f32,
+    pub spin_degrees_per_second: f32,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+/// Spawns and simulates particles on the CPU.
+pub struct Emitter {
+    pub position: Vec3,
+    pub settings: EmitterSettings,
+    particles: Vec<Particle>,
+    /// Fractional particles owed from previous steps, so low rates still spawn
+    spawn_debt: f32,
+    rng: Rng,
+}
+
+/// The emitters of a scene, stepped together with the rest of the simulation.
+pub struct ParticleSystem {
+    pub emitters: Vec<Emitter>,
+}
+
+impl Particle {
+    /// How far through its life the particle is, 0 at spawn and 1 at death.
+    pub fn life_fraction(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0_f32)
+    }
+}
+
+impl Emitter {
+    pub fn new(position: Vec3, settings: EmitterSettings, seed: u64) -> Emitter {
+        Emitter {
+            position,
+            particles: Vec::with_capacity(settings.max_particles),
+            settings,
+            spawn_debt: 0.0_f32,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Ages, moves and retires particles, then spawns the ones due over `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        let settings = &self.settings;
+        let damping = (1.0_f32 - settings.drag * dt).max(0.0_f32);
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+            particle.velocity = (particle.velocity + settings.acceleration * dt) * damping;
+            particle.position += particle.velocity * dt;
+            particle.rotation_degrees += particle.spin_degrees_per_second * dt;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        self.spawn_debt += settings.rate * dt;
+        while self.spawn_debt >= 1.0_f32 {
+            self.spawn_debt -= 1.0_f32;
+            if self.particles.len() < self.settings.max_particles {
+                let particle = self.spawn();
+                self.particles.push(particle);
+            }
+        }
+    }
+
+    pub fn color(&self, particle: &Particle) -> Color {
+        let color = &self.settings.color;
+        color.start.lerp(&color.end, particle.life_fraction())
+    }
+
+    pub fn size(&self, particle: &Particle) -> f32 {
+        let size = &self.settings.size;
+        lerp(size.start, size.end, particle.life_fraction())
+    }
+
+    /// The part of the sprite sheet `particle` shows, when the emitter has a flipbook.
+    pub fn uv_rect(&self, particle: &Particle) -> UvRect {
+        self.settings
+            .flipbook
+            .map_or(UvRect::FULL, |flipbook| flipbook.uv_rect_at(particle.age))
+    }
+
+    fn spawn(&mut self) -> Particle {
+        let settings = &self.settings;
+        let rng = &mut self.rng;
+        let lifetime =
+            settings.lifetime + rng.range(-settings.lifetime_variance, settings.lifetime_variance);
+        Particle {
+            position: self.position + random_point_in_sphere(rng, settings.spawn_radius),
+            velocity: settings.velocity + random_point_in_sphere(rng, settings.velocity_spread),
+            rotation_degrees: rng.range(0.0_f32, 360.0_f32),
+            spin_degrees_per_second: rng.range(-settings.spin, settings.spin),
+            age: 0.0_f32,
+            lifetime: lifetime.max(0.01_f32),
+        }
+    }
+}
+
+impl EmitterSettings {
+    /// Water thrown up and falling back under gravity.
+    pub fn fountain() -> EmitterSettings {
+        EmitterSettings {
+            rate: 600.0_f32,
+            lifetime: 2.2_f32,
+            lifetime_variance: 0.4_f32,
+            spawn_radius: 0.05_f32,
+            velocity: glm::vec3(0.0_f32, 6.5_f32, 0.0_f32),
+            velocity_spread: 1.2_f32,
+            acceleration: glm::vec3(0.0_f32, -9.8_f32, 0.0_f32),
+            drag: 0.1_f32,
+            spin: 0.0_f32,
+            color: OverLife {
+                start: Color::from_srgb(0.75, 0.9, 1.0, 0.9),
+                end: Color::from_srgb(0.2, 0.45, 0.9, 0.0),
+            },
+            size: OverLife {
+                start: 0.12_f32,
+                end: 0.06_f32,
+            },
+            blend: BlendMode::Alpha,
+            flipbook: None,
+            max_particles: 2000,
+        }
+    }
+
+    /// Slow, growing puffs drifting up and off with the wind.
+    pub fn smoke() -> EmitterSettings {
+        EmitterSettings {
+            rate: 30.0_f32,
+            lifetime: 6.0_f32,
+            lifetime_variance: 1.5_f32,
+            spawn_radius: 0.3_f32,
+            velocity: glm::vec3(0.0_f32, 0.8_f32, 0.0_f32),
+            velocity_spread: 0.25_f32,
+            acceleration: glm::vec3(0.25_f32, 0.2_f32, 0.0_f32),
+            drag: 0.3_f32,
+            spin: 30.0_f32,
+            color: OverLife {
+                start: Color::from_srgb(0.45, 0.45, 0.45, 0.6),
+                end: Color::from_srgb(0.7, 0.7, 0.7, 0.0),
+            },
+            size: OverLife {
+                start: 0.5_f32,
+                end: 2.5_f32,
+            },
+            blend: BlendMode::Alpha,
+            // Billows out over about a lifetime
+            flipbook: Some(Flipbook::new(PUFF_SHEET_COLUMNS, PUFF_SHEET_ROWS, 2.5_f32).once()),
+            max_particles: 300,
+        }
+    }
+
+    /// Short-lived flames rising and cooling from yellow to dark red.
+    pub fn fire() -> EmitterSettings {
+        EmitterSettings {
+            rate: 300.0_f32,
+            lifetime: 0.9_f32,
+            lifetime_variance: 0.3_f32,
+            spawn_radius: 0.35_f32,
+            velocity: glm::vec3(0.0_f32, 1.2_f32, 0.0_f32),
+            velocity_spread: 0.4_f32,
+            acceleration: glm::vec3(0.0_f32, 2.0_f32, 0.0_f32),
+            drag: 0.5_f32,
+            spin: 90.0_f32,
+            color: OverLife {
+                start: Color::from_srgb(1.0, 0.85, 0.4, 0.9),
+                end: Color::from_srgb(0.7, 0.08, 0.0, 0.0),
+            },
+            size: OverLife {
+                start: 0.55_f32,
+                end: 0.15_f32,
+            },
+            blend: BlendMode::Additive,
+            flipbook: Some(Flipbook::new(PUFF_SHEET_COLUMNS, PUFF_SHEET_ROWS, 18.0_f32).once()),
+            max_particles: 1000,
+        }
+    }
+}
+
+impl ParticleSystem {
+    /// A fountain, a smoke column and a fire side by side along the x axis.
+    pub fn presets(seed: u64) -> ParticleSystem {
+        ParticleSystem {
+            emitters: vec![
+                Emitter::new(
+                    glm::vec3(-4.0_f32, 0.0_f32, 0.0_f32),
+                    EmitterSettings::fountain(),
+                    seed,
+                ),
+                Emitter::new(
+                    glm::vec3(0.0_f32, 0.0_f32, 0.0_f32),
+                    EmitterSettings::smoke(),
+                    seed + 1,
+                ),
+                Emitter::new(
+                    glm::vec3(4.0_f32, 0.0_f32, 0.0_f32),
+                    EmitterSettings::fire(),
+                    seed + 2,
+                ),
+            ],
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for emitter in self.emitters.iter_mut() {
+            emitter.update(dt);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::glm;
+use crate::math::ray::Ray;
+use crate::scene::{Scene, WorldMatrices};
+
+/// Object-space bounds of the built-in meshes.
+pub fn mesh_bounds(mesh: &str) -> Option<Aabb> {
+    match mesh {
+        "cube" => Some(Aabb::new(
+            glm::vec3(-0.5_f32, -0.5_f32, -0.5_f32),
+            glm::vec3(0.5_f32, 0.5_f32, 0.5_f32),
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the index of the closest scene object hit by `ray`, placing objects with
+/// `world_matrices` so they are picked where the last frame drew them. Each object is first
+/// tested against a world-space bounding sphere, then against its bounds in object space so
+/// rotation and non-uniform scale are accounted for exactly.
+pub fn pick_scene_object(
+    ray: &Ray,
+    scene: &Scene,
+    world_matrices: &WorldMatrices,
+) -> Option<usize> {
+    let mut closest: Option<(usize, f32)> = None;
+    for (object_i, object) in scene.objects.iter().enumerate() {
+        let bounds = match mesh_bounds(&object.mesh) {
+            Some(bounds) => bounds,
+            None => {
+                eprintln!(
+                    "Cannot pick scene object {}: no bounds for mesh {}",
+                    object.name, object.mesh
+                );
+                continue;
+            }
+        };
+        let world_from_object = *world_matrices.get(object_i);
+
+        let sphere = BoundingSphere::from_aabb(&bounds).transformed(&world_from_object);
+        if ray.intersect_sphere(&sphere).is_none() {
+            continue;
+        }
+
+        // The object-space direction is left unnormalized so hit distances stay comparable
+        let object_ray = ray.transformed(&glm::inverse(&world_from_object));
+        if let Some(hit) = object_ray.intersect_aabb(&bounds) {
+            let t = hit.distance;
+            match closest {
+                Some((_, closest_t)) if closest_t <= t => {}
+                _ => closest = Some((object_i, t)),
+            }
+        }
+    }
+    closest.map(|(object_i, _)| object_i)
+}
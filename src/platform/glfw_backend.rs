@@ -0,0 +1,439 @@
+use glfw::{
+    Context, CursorMode, GamepadAxis, Glfw, JoystickId, SwapInterval, Window, WindowEvent,
+    WindowHint,
+};
+
+use crate::config::{FullscreenMode, Settings, WindowSettings};
+#[cfg(feature = "gl45")]
+use crate::ogl::bindless;
+use crate::ogl::capabilities::GlProfile;
+use crate::platform::{
+    Action, Event, GamepadState, Key, MouseButton, VsyncMode, WindowBackend, WorkerContext,
+};
+use std::sync::mpsc::Receiver;
+
+pub struct GlfwBackend {
+    glfw_obj: Glfw,
+    window: Window,
+    events: Receiver<(f64, WindowEvent)>,
+    // Position and size to restore when leaving fullscreen
+    windowed_rect: Option<(i32, i32, i32, i32)>,
+}
+
+impl WindowBackend for GlfwBackend {
+    fn create(settings: &Settings, visible: bool) -> Result<GlfwBackend, String> {
+        let mut glfw_obj = glfw::init(glfw::LOG_ERRORS)
+            .map_err(|e| format!("GLFW initialization failed with error: {}", e))?;
+        glfw_obj.window_hint(WindowHint::DoubleBuffer(settings.window.double_buffer));
+        // Keeps the window the same apparent size on HiDPI monitors where sizes are in pixels
+        glfw_obj.window_hint(WindowHint::ScaleToMonitor(true));
+        if settings.window.msaa_samples > 0 {
+            glfw_obj.window_hint(WindowHint::Samples(Some(settings.window.msaa_samples)));
+        }
+        if !visible {
+            glfw_obj.window_hint(WindowHint::Visible(false));
+        }
+        glfw_obj.window_hint(WindowHint::OpenGlDebugContext(settings.window.gl_debug));
+
+        let (width, height) = (settings.window.width, settings.window.height);
+        let mut created = None;
+        // Newer profiles are unavailable on some platforms (macOS stops at 4.1), so fall back
+        for profile in GlProfile::candidates(settings.window.api, settings.window.gl_version) {
+            let [major, minor] = profile.version();
+            if profile == GlProfile::Gles30 {
+                glfw_obj.window_hint(WindowHint::ClientApi(glfw::ClientApiHint::OpenGlEs));
+                glfw_obj.window_hint(WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Any));
+            } else {
+                glfw_obj.window_hint(WindowHint::ClientApi(glfw::ClientApiHint::OpenGl));
+                glfw_obj.window_hint(WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
+                #[cfg(target_os = "macos")]
+                glfw_obj.window_hint(WindowHint::OpenGlForwardCompat(true));
+            }
+            glfw_obj.window_hint(WindowHint::ContextVersion(major, minor));
+            match glfw_obj.create_window(width, height, "Learn OpenGL", glfw::WindowMode::Windowed)
+            {
+                Some(window) => {
+                    created = Some(window);
+                    break;
+                }
+                None => eprintln!("Could not create an OpenGL {}.{} context", major, minor),
+            }
+        }
+        let (mut window, events) = created.ok_or("GLFW window creation failed")?;
+
+        window.make_current();
+        window.set_key_polling(true);
+        window.set_char_polling(true);
+        window.set_framebuffer_size_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_content_scale_polling(true);
+        gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+        #[cfg(feature = "gl45")]
+        bindless::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+        let mut backend = GlfwBackend {
+            glfw_obj,
+            window,
+            events,
+            windowed_rect: None,
+        };
+        // Goes through the windowed size first, so Alt+Enter has a window to return to
+        if settings.window.fullscreen {
+            backend.set_fullscreen(true, &settings.window);
+        }
+        Ok(backend)
+    }
+
+    fn time(&self) -> f64 {
+        self.glfw_obj.get_time()
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        self.glfw_obj.poll_events();
+        glfw::flush_messages(&self.events)
+            .filter_map(|(_, event)| match event {
+                WindowEvent::FramebufferSize(width, height) => {
+                    Some(Event::FramebufferSize(width, height))
+                }
+                WindowEvent::Key(key, _, action, _) => {
+                    Some(Event::Key(from_glfw_key(key)?, from_glfw_action(action)))
+                }
+                WindowEvent::MouseButton(button, action, _) => Some(Event::MouseButton(
+                    from_glfw_mouse_button(button)?,
+                    from_glfw_action(action),
+                )),
+                WindowEvent::CursorPos(x, y) => Some(Event::CursorPos(x, y)),
+                WindowEvent::ContentScale(x_scale, _) => Some(Event::ContentScale(x_scale)),
+                WindowEvent::Char(character) => Some(Event::Char(character)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn should_close(&self) -> bool {
+        self.window.should_close()
+    }
+
+    fn is_minimized(&self) -> bool {
+        self.window.is_iconified()
+    }
+
+    fn is_focused(&self) -> bool {
+        self.window.is_focused()
+    }
+
+    fn set_should_close(&mut self, should_close: bool) {
+        self.window.set_should_close(should_close);
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.window.get_key(to_glfw_key(key)) == glfw::Action::Press
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.window.get_size()
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        self.window.get_framebuffer_size()
+    }
+
+    fn content_scale(&self) -> f32 {
+        self.window.get_content_scale().0
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.window.get_cursor_pos()
+    }
+
+    fn is_cursor_captured(&self) -> bool {
+        self.window.get_cursor_mode() == CursorMode::Disabled
+    }
+
+    // Raw motion skips the OS pointer acceleration; GLFW only applies it to a disabled cursor
+    fn set_cursor_captured(&mut self, captured: bool) {
+        self.window.set_cursor_mode(if captured {
+            CursorMode::Disabled
+        } else {
+            CursorMode::Normal
+        });
+        if self.glfw_obj.supports_raw_motion() {
+            self.window.set_raw_mouse_motion(captured);
+        }
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn set_icon(&mut self, width: u32, height: u32, rgba: &[u8]) {
+        // GLFW reads the pixels back as bytes, so keep them in memory order
+        let pixels = rgba
+            .chunks_exact(4)
+            .map(|pixel| u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]))
+            .collect();
+        self.window.set_icon_from_pixels(vec![glfw::PixelImage {
+            width,
+            height,
+            pixels,
+        }]);
+    }
+
+    fn swap_buffers(&mut self) {
+        self.window.swap_buffers();
+    }
+
+    fn set_vsync(&mut self, mode: VsyncMode) -> VsyncMode {
+        let adaptive_supported = self
+            .glfw_obj
+            .extension_supported("WGL_EXT_swap_control_tear")
+            || self
+                .glfw_obj
+                .extension_supported("GLX_EXT_swap_control_tear");
+        let (interval, mode) = match mode {
+            VsyncMode::Off => (SwapInterval::None, VsyncMode::Off),
+            VsyncMode::Adaptive if adaptive_supported => {
+                (SwapInterval::Adaptive, VsyncMode::Adaptive)
+            }
+            VsyncMode::On | VsyncMode::Adaptive => (SwapInterval::Sync(1), VsyncMode::On),
+        };
+        self.glfw_obj.set_swap_interval(interval);
+        mode
+    }
+
+    fn monitors(&mut self) -> Vec<String> {
+        self.glfw_obj.with_connected_monitors(|_, monitors| {
+            monitors
+                .iter()
+                .map(|monitor| {
+                    let name = monitor.get_name().unwrap_or_else(|| "Unknown".to_string());
+                    match monitor.get_video_mode() {
+                        Some(mode) => format!(
+                            "{} ({}x{} @ {} Hz)",
+                            name, mode.width, mode.height, mode.refresh_rate
+                        ),
+                        None => name,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.window.with_window_mode(|mode| match mode {
+            glfw::WindowMode::FullScreen(_) => true,
+            glfw::WindowMode::Windowed => false,
+        })
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool, settings: &WindowSettings) {
+        if fullscreen == self.is_fullscreen() {
+            return;
+        }
+        if !fullscreen {
+            let (x, y, width, height) = self.windowed_rect.take().unwrap_or((
+                100,
+                100,
+                settings.width as i32,
+                settings.height as i32,
+            ));
+            self.window.set_monitor(
+                glfw::WindowMode::Windowed,
+                x,
+                y,
+                width as u32,
+                height as u32,
+                None,
+            );
+            return;
+        }
+
+        let (x, y) = self.window.get_pos();
+        let (width, height) = self.window.get_size();
+        self.windowed_rect = Some((x, y, width, height));
+        let window = &mut self.window;
+        self.glfw_obj.with_connected_monitors(|_, monitors| {
+            let monitor = monitors.get(settings.monitor).or_else(|| monitors.first());
+            match (
+                monitor,
+                monitor.and_then(|monitor| monitor.get_video_mode()),
+            ) {
+                (Some(monitor), Some(current_mode)) => {
+                    let (width, height, refresh_rate) = match settings.fullscreen_mode {
+                        FullscreenMode::Exclusive => (settings.width, settings.height, None),
+                        // GLFW's "windowed full screen": the monitor keeps its current mode
+                        FullscreenMode::Borderless => (
+                            current_mode.width,
+                            current_mode.height,
+                            Some(current_mode.refresh_rate),
+                        ),
+                    };
+                    window.set_monitor(
+                        glfw::WindowMode::FullScreen(monitor),
+                        0,
+                        0,
+                        width,
+                        height,
+                        refresh_rate,
+                    );
+                }
+                _ => eprintln!("No monitor to go fullscreen on"),
+            }
+        });
+    }
+
+    fn create_shared(&self, title: &str, width: u32, height: u32) -> Result<GlfwBackend, String> {
+        // Uses the hints of the main window, so the context gets the same version and profile
+        let (mut window, events) = self
+            .window
+            .create_shared(width, height, title, glfw::WindowMode::Windowed)
+            .ok_or("GLFW shared window creation failed")?;
+        let mut glfw_obj = self.glfw_obj.clone();
+
+        window.make_current();
+        window.set_key_polling(true);
+        window.set_framebuffer_size_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_mouse_button_polling(true);
+        glfw_obj.set_swap_interval(SwapInterval::None);
+
+        Ok(GlfwBackend {
+            glfw_obj,
+            window,
+            events,
+            windowed_rect: None,
+        })
+    }
+
+    fn create_worker_context(&self) -> Result<(GlfwBackend, Box<dyn WorkerContext>), String> {
+        let mut glfw_obj = self.glfw_obj.clone();
+        glfw_obj.window_hint(WindowHint::Visible(false));
+        let created = self.window.create_shared(
+            1,
+            1,
+            "Learn OpenGL upload worker",
+            glfw::WindowMode::Windowed,
+        );
+        glfw_obj.window_hint(WindowHint::Visible(self.window.is_visible()));
+        let (mut window, events) = created.ok_or("GLFW worker window creation failed")?;
+        let context = window.render_context();
+
+        Ok((
+            GlfwBackend {
+                glfw_obj,
+                window,
+                events,
+                windowed_rect: None,
+            },
+            Box::new(context),
+        ))
+    }
+
+    fn make_current(&mut self) {
+        self.window.make_current();
+    }
+
+    // GLFW maps known controllers to a common layout; plain joysticks are skipped
+    fn gamepad(&self) -> Option<GamepadState> {
+        let state = (0..=glfw::ffi::JOYSTICK_LAST)
+            .filter_map(JoystickId::from_i32)
+            .map(|id| self.glfw_obj.get_joystick(id))
+            .filter(|joystick| joystick.is_gamepad())
+            .find_map(|joystick| joystick.get_gamepad_state())?;
+        // Triggers rest at -1
+        let trigger = |axis: GamepadAxis| (state.get_axis(axis) + 1.0) * 0.5;
+        Some(GamepadState {
+            left_stick: (
+                state.get_axis(GamepadAxis::AxisLeftX),
+                state.get_axis(GamepadAxis::AxisLeftY),
+            ),
+            right_stick: (
+                state.get_axis(GamepadAxis::AxisRightX),
+                state.get_axis(GamepadAxis::AxisRightY),
+            ),
+            left_trigger: trigger(GamepadAxis::AxisLeftTrigger),
+            right_trigger: trigger(GamepadAxis::AxisRightTrigger),
+        })
+    }
+}
+
+impl WorkerContext for glfw::RenderContext {
+    fn make_current(&mut self) {
+        Context::make_current(self);
+    }
+
+    fn release(&mut self) {
+        glfw::make_context_current(None);
+    }
+}
+
+const KEY_MAP: [(Key, glfw::Key); 35] = [
+    (Key::W, glfw::Key::W),
+    (Key::A, glfw::Key::A),
+    (Key::S, glfw::Key::S),
+    (Key::D, glfw::Key::D),
+    (Key::P, glfw::Key::P),
+    (Key::Num1, glfw::Key::Num1),
+    (Key::Num2, glfw::Key::Num2),
+    (Key::Num3, glfw::Key::Num3),
+    (Key::Period, glfw::Key::Period),
+    (Key::LeftBracket, glfw::Key::LeftBracket),
+    (Key::RightBracket, glfw::Key::RightBracket),
+    (Key::Escape, glfw::Key::Escape),
+    (Key::F3, glfw::Key::F3),
+    (Key::F4, glfw::Key::F4),
+    (Key::F5, glfw::Key::F5),
+    (Key::F6, glfw::Key::F6),
+    (Key::F9, glfw::Key::F9),
+    (Key::F12, glfw::Key::F12),
+    (Key::V, glfw::Key::V),
+    (Key::Enter, glfw::Key::Enter),
+    (Key::LeftAlt, glfw::Key::LeftAlt),
+    (Key::RightAlt, glfw::Key::RightAlt),
+    (Key::Tab, glfw::Key::Tab),
+    (Key::R, glfw::Key::R),
+    (Key::LeftShift, glfw::Key::LeftShift),
+    (Key::RightShift, glfw::Key::RightShift),
+    (Key::LeftControl, glfw::Key::LeftControl),
+    (Key::RightControl, glfw::Key::RightControl),
+    (Key::GraveAccent, glfw::Key::GraveAccent),
+    (Key::Backspace, glfw::Key::Backspace),
+    (Key::Up, glfw::Key::Up),
+    (Key::Down, glfw::Key::Down),
+    (Key::Left, glfw::Key::Left),
+    (Key::Right, glfw::Key::Right),
+    (Key::Space, glfw::Key::Space),
+];
+
+fn from_glfw_key(glfw_key: glfw::Key) -> Option<Key> {
+    KEY_MAP
+        .iter()
+        .find(|(_, mapped)| *mapped == glfw_key)
+        .map(|(key, _)| *key)
+}
+
+fn to_glfw_key(key: Key) -> glfw::Key {
+    KEY_MAP
+        .iter()
+        .find(|(mapped, _)| *mapped == key)
+        .map(|(_, glfw_key)| *glfw_key)
+        .unwrap()
+}
+
+fn from_glfw_action(action: glfw::Action) -> Action {
+    match action {
+        glfw::Action::Press => Action::Press,
+        glfw::Action::Release => Action::Release,
+        glfw::Action::Repeat => Action::Repeat,
+    }
+}
+
+fn from_glfw_mouse_button(button: glfw::MouseButton) -> Option<MouseButton> {
+    match button {
+        glfw::MouseButton::Button1 => Some(MouseButton::Left),
+        glfw::MouseButton::Button2 => Some(MouseButton::Right),
+        glfw::MouseButton::Button3 => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
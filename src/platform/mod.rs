@@ -0,0 +1,259 @@
+use crate::config::{Settings, WindowSettings};
+use crate::ogl::context::GlContext;
+
+#[cfg(all(feature = "glfw-backend", not(target_arch = "wasm32")))]
+pub mod glfw_backend;
+#[cfg(all(
+    feature = "sdl2-backend",
+    not(any(feature = "glfw-backend", target_arch = "wasm32"))
+))]
+pub mod sdl2_backend;
+#[cfg(target_arch = "wasm32")]
+pub mod web_backend;
+#[cfg(all(
+    feature = "winit-backend",
+    not(any(
+        feature = "glfw-backend",
+        feature = "sdl2-backend",
+        target_arch = "wasm32"
+    ))
+))]
+pub mod winit_backend;
+
+/// The backend picked by cargo features. When several are enabled glfw wins, since it is on by
+/// default, then SDL2, then winit. Web builds always draw into a canvas, whatever the features.
+#[cfg(all(feature = "glfw-backend", not(target_arch = "wasm32")))]
+pub type DefaultBackend = glfw_backend::GlfwBackend;
+#[cfg(all(
+    feature = "sdl2-backend",
+    not(any(feature = "glfw-backend", target_arch = "wasm32"))
+))]
+pub type DefaultBackend = sdl2_backend::SdlBackend;
+#[cfg(all(
+    feature = "winit-backend",
+    not(any(
+        feature = "glfw-backend",
+        feature = "sdl2-backend",
+        target_arch = "wasm32"
+    ))
+))]
+pub type DefaultBackend = winit_backend::WinitBackend;
+#[cfg(target_arch = "wasm32")]
+pub type DefaultBackend = web_backend::WebBackend;
+
+#[cfg(not(any(
+    feature = "glfw-backend",
+    feature = "sdl2-backend",
+    feature = "winit-backend",
+    target_arch = "wasm32"
+)))]
+compile_error!(
+    "Enable a windowing backend: the `glfw-backend`, `sdl2-backend` or `winit-backend` feature"
+);
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    W,
+    A,
+    S,
+    D,
+    P,
+    Num1,
+    Num2,
+    Num3,
+    Period,
+    LeftBracket,
+    RightBracket,
+    Escape,
+    F3,
+    F4,
+    F5,
+    F6,
+    F9,
+    F12,
+    V,
+    Enter,
+    LeftAlt,
+    RightAlt,
+    Tab,
+    R,
+    LeftShift,
+    RightShift,
+    LeftControl,
+    RightControl,
+    GraveAccent,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+}
+
+/// Modifier keys held down; the left and right keys count the same.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    Press,
+    Release,
+    Repeat,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VsyncMode {
+    Off,
+    On,
+    /// Syncs like `On`, but swaps immediately when a frame is late
+    Adaptive,
+}
+
+/// Window events the app reacts to, translated from the backend's own event types. Keys and
+/// buttons the app has no use for are dropped by the backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    FramebufferSize(i32, i32),
+    Key(Key, Action),
+    MouseButton(MouseButton, Action),
+    /// Cursor position in window coordinates. While the cursor is captured it keeps moving
+    /// freely past the window edges, so differences between positions give mouse motion.
+    CursorPos(f64, f64),
+    /// The window moved to a monitor with a different content scale, e.g. 2.0 on Retina
+    ContentScale(f32),
+    /// Text typed, after the keyboard layout and dead keys are applied
+    Char(char),
+}
+
+/// Analog controls of a connected gamepad. Sticks range over -1..1 with +y pointing down, the
+/// triggers over 0..1. No dead zone is applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GamepadState {
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+/// A GL context handed to another thread, which makes it current there.
+pub trait WorkerContext: Send {
+    fn make_current(&mut self);
+
+    /// Detaches the context from the calling thread, so its window can be destroyed.
+    fn release(&mut self);
+}
+
+/// Window creation, event polling and GL context management. Implementations leave the window's
+/// GL context current and the `gl` entry points loaded once `create` returns.
+pub trait WindowBackend: Sized {
+    /// Tries the GL profiles allowed by `settings`, newest first.
+    fn create(settings: &Settings, visible: bool) -> Result<Self, String>;
+
+    /// Whether a window can be created again after the previous one is dropped, e.g. to change
+    /// settings fixed at context creation.
+    fn can_recreate() -> bool {
+        true
+    }
+
+    /// Seconds since the backend was created.
+    fn time(&self) -> f64;
+
+    /// Processes pending window system events and returns those the app handles.
+    fn poll_events(&mut self) -> Vec<Event>;
+
+    fn should_close(&self) -> bool;
+
+    /// Iconified, or otherwise shrunk to nothing: there is no framebuffer to render into.
+    fn is_minimized(&self) -> bool;
+
+    fn is_focused(&self) -> bool;
+
+    fn set_should_close(&mut self, should_close: bool);
+
+    fn is_key_down(&self, key: Key) -> bool;
+
+    fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift: self.is_key_down(Key::LeftShift) || self.is_key_down(Key::RightShift),
+            control: self.is_key_down(Key::LeftControl) || self.is_key_down(Key::RightControl),
+            alt: self.is_key_down(Key::LeftAlt) || self.is_key_down(Key::RightAlt),
+        }
+    }
+
+    /// Window size in the same units as cursor positions. On HiDPI displays these may be logical
+    /// units smaller than framebuffer pixels; viewports and projections use `framebuffer_size`.
+    fn size(&self) -> (i32, i32);
+
+    fn framebuffer_size(&self) -> (i32, i32);
+
+    /// Ratio between the monitor's DPI and the platform's default DPI, for scaling pixel-sized UI.
+    fn content_scale(&self) -> f32;
+
+    fn cursor_pos(&self) -> (f64, f64);
+
+    fn is_cursor_captured(&self) -> bool;
+
+    /// Hides the cursor and locks it to the window for mouse look.
+    fn set_cursor_captured(&mut self, captured: bool);
+
+    fn set_title(&mut self, title: &str);
+
+    /// Sets the window icon from tightly packed RGBA8 rows, top row first. Ignored on macOS,
+    /// where the icon comes from the application bundle.
+    fn set_icon(&mut self, width: u32, height: u32, rgba: &[u8]);
+
+    /// Presents the back buffer. Does nothing for a single buffered window.
+    fn swap_buffers(&mut self);
+
+    /// Sets the swap interval of this window's context, which must be current, and returns the
+    /// mode actually in effect: adaptive vsync falls back to `On` where unsupported.
+    fn set_vsync(&mut self, mode: VsyncMode) -> VsyncMode;
+
+    /// Names of the connected monitors, indexed as `WindowSettings::monitor`.
+    fn monitors(&mut self) -> Vec<String>;
+
+    fn is_fullscreen(&self) -> bool;
+
+    /// Switches between a window and fullscreen on the monitor and in the mode `settings` pick.
+    /// The window gets its previous size and position back when leaving fullscreen.
+    fn set_fullscreen(&mut self, fullscreen: bool, settings: &WindowSettings);
+
+    /// Opens another window whose context shares buffers, textures, renderbuffers and shaders
+    /// with this one; vertex arrays and framebuffers stay per context. The new window's context
+    /// is left current and never waits for vsync, so swapping it does not stall the main window.
+    fn create_shared(&self, _title: &str, _width: u32, _height: u32) -> Result<Self, String> {
+        Err("This windowing backend does not support shared windows".to_string())
+    }
+
+    /// Opens a hidden window sharing objects with this one and returns its context for use on a
+    /// worker thread. The window must outlive the worker's use of the context.
+    fn create_worker_context(&self) -> Result<(Self, Box<dyn WorkerContext>), String> {
+        Err("This windowing backend does not support worker contexts".to_string())
+    }
+
+    /// Token for creating GL resources with the window's context; `create` has loaded GL and
+    /// left the context current.
+    fn gl_context(&self) -> GlContext {
+        unsafe { GlContext::assume_current() }
+    }
+
+    /// Makes this window's context current. Backends without `create_shared` only ever have one
+    /// context, which stays current.
+    fn make_current(&mut self) {}
+
+    /// The first connected gamepad, on backends that support them.
+    fn gamepad(&self) -> Option<GamepadState> {
+        None
+    }
+}
@@ -0,0 +1,424 @@
+use sdl2::controller::{Axis, GameController};
+use sdl2::event::WindowEvent;
+use sdl2::keyboard::Scancode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::surface::Surface;
+use sdl2::video::{FullscreenType, GLContext, GLProfile, SwapInterval, Window, WindowPos};
+use sdl2::{EventPump, GameControllerSubsystem, Sdl};
+
+use crate::config::{FullscreenMode, Settings, WindowSettings};
+#[cfg(feature = "gl45")]
+use crate::ogl::bindless;
+use crate::ogl::capabilities::GlProfile;
+use crate::platform::{Action, Event, GamepadState, Key, MouseButton, VsyncMode, WindowBackend};
+use std::time::Instant;
+
+/// SDL2 window with a GL context. Game controllers are opened as they are plugged in, and the
+/// first one connected is reported by `gamepad`.
+pub struct SdlBackend {
+    sdl: Sdl,
+    // Declared before `window` so the context is dropped first
+    _gl_context: GLContext,
+    window: Window,
+    event_pump: EventPump,
+    controller_subsystem: GameControllerSubsystem,
+    controllers: Vec<GameController>,
+    start: Instant,
+    should_close: bool,
+    cursor_pos: (f64, f64),
+    // Last content scale reported, to notice moves between monitors
+    content_scale: f32,
+}
+
+impl WindowBackend for SdlBackend {
+    fn create(settings: &Settings, visible: bool) -> Result<SdlBackend, String> {
+        let sdl = sdl2::init()?;
+        let video = sdl.video()?;
+        let gl_attr = video.gl_attr();
+        gl_attr.set_double_buffer(settings.window.double_buffer);
+        if settings.window.msaa_samples > 0 {
+            gl_attr.set_multisample_buffers(1);
+            gl_attr.set_multisample_samples(settings.window.msaa_samples as u8);
+        }
+
+        let mut created = None;
+        // Newer profiles are unavailable on some platforms (macOS stops at 4.1), so fall back
+        for profile in GlProfile::candidates(settings.window.api, settings.window.gl_version) {
+            let [major, minor] = profile.version();
+            let mut context_flags = gl_attr.set_context_flags();
+            if settings.window.gl_debug {
+                context_flags.debug();
+            }
+            if profile == GlProfile::Gles30 {
+                gl_attr.set_context_profile(GLProfile::GLES);
+            } else {
+                gl_attr.set_context_profile(GLProfile::Core);
+                #[cfg(target_os = "macos")]
+                context_flags.forward_compatible();
+            }
+            context_flags.set();
+            gl_attr.set_context_version(major as u8, minor as u8);
+
+            let mut window_builder = video.window(
+                "Learn OpenGL",
+                settings.window.width,
+                settings.window.height,
+            );
+            // Without it, macOS renders at the logical size and upscales
+            window_builder.opengl().resizable().allow_highdpi();
+            if !visible {
+                window_builder.hidden();
+            }
+            let window = window_builder.build().map_err(|e| e.to_string())?;
+            // SDL only reports an unsupported version once the context is created
+            match window.gl_create_context() {
+                Ok(gl_context) => {
+                    created = Some((window, gl_context));
+                    break;
+                }
+                Err(e) => eprintln!(
+                    "Could not create an OpenGL {}.{} context: {}",
+                    major, minor, e
+                ),
+            }
+        }
+        let (window, gl_context) = created.ok_or("SDL2 window creation failed")?;
+
+        window.gl_make_current(&gl_context)?;
+        gl::load_with(|symbol| video.gl_get_proc_address(symbol) as *const _);
+        #[cfg(feature = "gl45")]
+        bindless::load_with(|symbol| video.gl_get_proc_address(symbol) as *const _);
+
+        let content_scale = window_content_scale(&window);
+        let mut backend = SdlBackend {
+            event_pump: sdl.event_pump()?,
+            controller_subsystem: sdl.game_controller()?,
+            sdl,
+            _gl_context: gl_context,
+            window,
+            controllers: vec![],
+            start: Instant::now(),
+            should_close: false,
+            cursor_pos: (0.0, 0.0),
+            content_scale,
+        };
+        if settings.window.fullscreen {
+            backend.set_fullscreen(true, &settings.window);
+        }
+        Ok(backend)
+    }
+
+    fn time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        for event in self.event_pump.poll_iter() {
+            match event {
+                sdl2::event::Event::Quit { .. } => self.should_close = true,
+                sdl2::event::Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    let (width, height) = self.window.drawable_size();
+                    events.push(Event::FramebufferSize(width as i32, height as i32));
+                    let content_scale = window_content_scale(&self.window);
+                    if content_scale != self.content_scale {
+                        self.content_scale = content_scale;
+                        events.push(Event::ContentScale(content_scale));
+                    }
+                }
+                sdl2::event::Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat,
+                    ..
+                } => {
+                    if let Some(key) = from_sdl_scancode(scancode) {
+                        let action = if repeat {
+                            Action::Repeat
+                        } else {
+                            Action::Press
+                        };
+                        events.push(Event::Key(key, action));
+                    }
+                }
+                sdl2::event::Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(key) = from_sdl_scancode(scancode) {
+                        events.push(Event::Key(key, Action::Release));
+                    }
+                }
+                sdl2::event::Event::TextInput { text, .. } => {
+                    events.extend(text.chars().map(Event::Char));
+                }
+                sdl2::event::Event::MouseButtonDown { mouse_btn, .. } => {
+                    if let Some(button) = from_sdl_mouse_button(mouse_btn) {
+                        events.push(Event::MouseButton(button, Action::Press));
+                    }
+                }
+                sdl2::event::Event::MouseButtonUp { mouse_btn, .. } => {
+                    if let Some(button) = from_sdl_mouse_button(mouse_btn) {
+                        events.push(Event::MouseButton(button, Action::Release));
+                    }
+                }
+                sdl2::event::Event::MouseMotion {
+                    x, y, xrel, yrel, ..
+                } => {
+                    // In relative mode the reported position stays put; accumulate the motion
+                    self.cursor_pos = if self.sdl.mouse().relative_mouse_mode() {
+                        (
+                            self.cursor_pos.0 + xrel as f64,
+                            self.cursor_pos.1 + yrel as f64,
+                        )
+                    } else {
+                        (x as f64, y as f64)
+                    };
+                    events.push(Event::CursorPos(self.cursor_pos.0, self.cursor_pos.1));
+                }
+                sdl2::event::Event::ControllerDeviceAdded { which, .. } => {
+                    match self.controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            println!("Gamepad connected: {}", controller.name());
+                            self.controllers.push(controller);
+                        }
+                        Err(e) => eprintln!("Could not open gamepad {}: {}", which, e),
+                    }
+                }
+                sdl2::event::Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers
+                        .retain(|controller| controller.instance_id() != which);
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn should_close(&self) -> bool {
+        self.should_close
+    }
+
+    fn is_minimized(&self) -> bool {
+        let minimized = sdl2::sys::SDL_WindowFlags::SDL_WINDOW_MINIMIZED as u32;
+        self.window.window_flags() & minimized != 0
+    }
+
+    fn is_focused(&self) -> bool {
+        let input_focus = sdl2::sys::SDL_WindowFlags::SDL_WINDOW_INPUT_FOCUS as u32;
+        self.window.window_flags() & input_focus != 0
+    }
+
+    fn set_should_close(&mut self, should_close: bool) {
+        self.should_close = should_close;
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.event_pump
+            .keyboard_state()
+            .is_scancode_pressed(to_sdl_scancode(key))
+    }
+
+    fn size(&self) -> (i32, i32) {
+        let (width, height) = self.window.size();
+        (width as i32, height as i32)
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        let (width, height) = self.window.drawable_size();
+        (width as i32, height as i32)
+    }
+
+    fn content_scale(&self) -> f32 {
+        window_content_scale(&self.window)
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.cursor_pos
+    }
+
+    fn is_cursor_captured(&self) -> bool {
+        self.sdl.mouse().relative_mouse_mode()
+    }
+
+    fn set_cursor_captured(&mut self, captured: bool) {
+        self.sdl.mouse().set_relative_mouse_mode(captured);
+    }
+
+    fn swap_buffers(&mut self) {
+        self.window.gl_swap_window();
+    }
+
+    fn set_title(&mut self, title: &str) {
+        if let Err(e) = self.window.set_title(title) {
+            eprintln!("Invalid window title: {}", e);
+        }
+    }
+
+    fn set_icon(&mut self, width: u32, height: u32, rgba: &[u8]) {
+        let mut pixels = rgba.to_vec();
+        match Surface::from_data(
+            &mut pixels,
+            width,
+            height,
+            width * 4,
+            PixelFormatEnum::RGBA32,
+        ) {
+            Ok(surface) => self.window.set_icon(surface),
+            Err(e) => eprintln!("Invalid window icon: {}", e),
+        };
+    }
+
+    fn set_vsync(&mut self, mode: VsyncMode) -> VsyncMode {
+        let video = self.window.subsystem();
+        // SDL reports unsupported late swap tearing as an error
+        if mode == VsyncMode::Adaptive
+            && video
+                .gl_set_swap_interval(SwapInterval::LateSwapTearing)
+                .is_ok()
+        {
+            return VsyncMode::Adaptive;
+        }
+        let (interval, mode) = match mode {
+            VsyncMode::Off => (SwapInterval::Immediate, VsyncMode::Off),
+            VsyncMode::On | VsyncMode::Adaptive => (SwapInterval::VSync, VsyncMode::On),
+        };
+        if let Err(e) = video.gl_set_swap_interval(interval) {
+            eprintln!("Could not set the swap interval: {}", e);
+        }
+        mode
+    }
+
+    fn monitors(&mut self) -> Vec<String> {
+        let video = self.window.subsystem();
+        let display_count = video.num_video_displays().unwrap_or(0);
+        (0..display_count)
+            .map(|display_i| {
+                let name = video
+                    .display_name(display_i)
+                    .unwrap_or_else(|_| "Unknown".to_string());
+                match video.current_display_mode(display_i) {
+                    Ok(mode) => format!(
+                        "{} ({}x{} @ {} Hz)",
+                        name, mode.w, mode.h, mode.refresh_rate
+                    ),
+                    Err(_) => name,
+                }
+            })
+            .collect()
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.window.fullscreen_state() != FullscreenType::Off
+    }
+
+    // SDL restores the windowed size and position by itself
+    fn set_fullscreen(&mut self, fullscreen: bool, settings: &WindowSettings) {
+        let fullscreen_type = match (fullscreen, settings.fullscreen_mode) {
+            (false, _) => FullscreenType::Off,
+            // Switches to the display mode closest to the window size
+            (true, FullscreenMode::Exclusive) => FullscreenType::True,
+            (true, FullscreenMode::Borderless) => FullscreenType::Desktop,
+        };
+        if fullscreen {
+            // Fullscreen happens on the display the window is on, so move it there first
+            let display_bounds = self
+                .window
+                .subsystem()
+                .display_bounds(settings.monitor as i32)
+                .or_else(|_| self.window.subsystem().display_bounds(0));
+            if let Ok(bounds) = display_bounds {
+                self.window.set_position(
+                    WindowPos::Positioned(bounds.x()),
+                    WindowPos::Positioned(bounds.y()),
+                );
+            }
+        }
+        if let Err(e) = self.window.set_fullscreen(fullscreen_type) {
+            eprintln!("Could not change fullscreen mode: {}", e);
+        }
+    }
+
+    fn gamepad(&self) -> Option<GamepadState> {
+        let controller = self.controllers.first()?;
+        let axis = |axis: Axis| controller.axis(axis) as f32 / i16::MAX as f32;
+        Some(GamepadState {
+            left_stick: (axis(Axis::LeftX), axis(Axis::LeftY)),
+            right_stick: (axis(Axis::RightX), axis(Axis::RightY)),
+            left_trigger: axis(Axis::TriggerLeft),
+            right_trigger: axis(Axis::TriggerRight),
+        })
+    }
+}
+
+// Scancodes name physical key positions, so WASD stays put on non-QWERTY layouts
+const KEY_MAP: [(Key, Scancode); 35] = [
+    (Key::W, Scancode::W),
+    (Key::A, Scancode::A),
+    (Key::S, Scancode::S),
+    (Key::D, Scancode::D),
+    (Key::P, Scancode::P),
+    (Key::Num1, Scancode::Num1),
+    (Key::Num2, Scancode::Num2),
+    (Key::Num3, Scancode::Num3),
+    (Key::Period, Scancode::Period),
+    (Key::LeftBracket, Scancode::LeftBracket),
+    (Key::RightBracket, Scancode::RightBracket),
+    (Key::Escape, Scancode::Escape),
+    (Key::F3, Scancode::F3),
+    (Key::F4, Scancode::F4),
+    (Key::F5, Scancode::F5),
+    (Key::F6, Scancode::F6),
+    (Key::F9, Scancode::F9),
+    (Key::F12, Scancode::F12),
+    (Key::V, Scancode::V),
+    (Key::Enter, Scancode::Return),
+    (Key::LeftAlt, Scancode::LAlt),
+    (Key::RightAlt, Scancode::RAlt),
+    (Key::Tab, Scancode::Tab),
+    (Key::R, Scancode::R),
+    (Key::LeftShift, Scancode::LShift),
+    (Key::RightShift, Scancode::RShift),
+    (Key::LeftControl, Scancode::LCtrl),
+    (Key::RightControl, Scancode::RCtrl),
+    (Key::GraveAccent, Scancode::Grave),
+    (Key::Backspace, Scancode::Backspace),
+    (Key::Up, Scancode::Up),
+    (Key::Down, Scancode::Down),
+    (Key::Left, Scancode::Left),
+    (Key::Right, Scancode::Right),
+    (Key::Space, Scancode::Space),
+];
+
+fn from_sdl_scancode(scancode: Scancode) -> Option<Key> {
+    KEY_MAP
+        .iter()
+        .find(|(_, mapped)| *mapped == scancode)
+        .map(|(key, _)| *key)
+}
+
+fn to_sdl_scancode(key: Key) -> Scancode {
+    KEY_MAP
+        .iter()
+        .find(|(mapped, _)| *mapped == key)
+        .map(|(_, scancode)| *scancode)
+        .unwrap()
+}
+
+// SDL has no content scale query before 2.0.22; use the drawable to window size ratio
+fn window_content_scale(window: &Window) -> f32 {
+    let (width, _) = window.size();
+    let (drawable_width, _) = window.drawable_size();
+    drawable_width as f32 / width.max(1) as f32
+}
+
+fn from_sdl_mouse_button(button: sdl2::mouse::MouseButton) -> Option<MouseButton> {
+    match button {
+        sdl2::mouse::MouseButton::Left => Some(MouseButton::Left),
+        sdl2::mouse::MouseButton::Right => Some(MouseButton::Right),
+        sdl2::mouse::MouseButton::Middle => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
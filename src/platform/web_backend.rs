@@ -0,0 +1,487 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    Document, Element, EventTarget, HtmlCanvasElement, KeyboardEvent, MouseEvent, Performance,
+    WebGl2RenderingContext, Window,
+};
+
+use crate::config::{Settings, WindowSettings};
+use crate::ogl::webgl;
+use crate::platform::{Action, Event, Key, MouseButton, VsyncMode, WindowBackend};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::panic;
+use std::rc::Rc;
+
+/// Id of the canvas element on the page that the lessons draw into.
+pub const CANVAS_ID: &str = "learn-opengl";
+
+/// A WebGL2 context on the page's canvas. DOM event listeners queue input until the next
+/// `poll_events`, which also resizes the canvas to its CSS size in device pixels.
+pub struct WebBackend {
+    window: Window,
+    document: Document,
+    canvas: HtmlCanvasElement,
+    performance: Performance,
+    input: Rc<RefCell<Input>>,
+    // Removed again on drop, so the closures they call are not left behind on the page
+    listeners: Vec<Listener>,
+    start: f64,
+    should_close: bool,
+    // Last device pixel ratio reported, to notice moves between monitors and zooming
+    content_scale: f32,
+}
+
+/// What the event listeners have seen since the last `poll_events`.
+#[derive(Default)]
+struct Input {
+    events: Vec<Event>,
+    keys_down: HashSet<Key>,
+    cursor_pos: (f64, f64),
+    /// Mouse look was asked for. Browsers only lock the pointer from an input handler, so the
+    /// lock is requested on the next click in the canvas.
+    wants_capture: bool,
+}
+
+struct Listener {
+    target: EventTarget,
+    event_type: &'static str,
+    callback: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl WindowBackend for WebBackend {
+    /// Settings fixed at context creation come from `settings`; the size only applies to a
+    /// canvas the page leaves unsized. Pages are always visible.
+    fn create(settings: &Settings, _visible: bool) -> Result<WebBackend, String> {
+        let window = web_sys::window().ok_or("No browser window")?;
+        let document = window.document().ok_or("No document")?;
+        let performance = window.performance().ok_or("No performance timer")?;
+        let canvas = document
+            .get_element_by_id(CANVAS_ID)
+            .ok_or_else(|| format!("No element with id {}", CANVAS_ID))?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| format!("Element {} is not a canvas", CANVAS_ID))?;
+        let style = canvas.style();
+        if style
+            .get_property_value("width")
+            .unwrap_or_default()
+            .is_empty()
+        {
+            style
+                .set_property("width", &format!("{}px", settings.window.width))
+                .map_err(js_error)?;
+            style
+                .set_property("height", &format!("{}px", settings.window.height))
+                .map_err(js_error)?;
+        }
+
+        let attributes = js_sys::Object::new();
+        for (attribute, value) in [
+            ("antialias", settings.window.msaa_samples > 0),
+            ("alpha", false),
+            ("stencil", true),
+            // Keeps the last frame on the canvas, which a single buffered window would show
+            ("preserveDrawingBuffer", !settings.window.double_buffer),
+        ]
+        .iter()
+        {
+            js_sys::Reflect::set(&attributes, &(*attribute).into(), &(*value).into())
+                .map_err(js_error)?;
+        }
+        let context = canvas
+            .get_context_with_context_options("webgl2", &attributes)
+            .map_err(js_error)?
+            .ok_or("The browser does not support WebGL2")?
+            .dyn_into::<WebGl2RenderingContext>()
+            .map_err(|_| "The webgl2 context is not a WebGL2RenderingContext")?;
+        webgl::load(context);
+
+        let input = Rc::new(RefCell::new(Input::default()));
+        let listeners = add_listeners(&window, &document, &canvas, &input)?;
+        let mut backend = WebBackend {
+            start: performance.now(),
+            window,
+            document,
+            canvas,
+            performance,
+            input,
+            listeners,
+            should_close: false,
+            content_scale: 0.0,
+        };
+        backend.resize_canvas();
+        Ok(backend)
+    }
+
+    /// The page has the one canvas, whose context keeps the attributes it was created with.
+    fn can_recreate() -> bool {
+        false
+    }
+
+    fn time(&self) -> f64 {
+        (self.performance.now() - self.start) / 1000.0
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        let mut events = self.resize_canvas();
+        events.append(&mut self.input.borrow_mut().events);
+        events
+    }
+
+    fn should_close(&self) -> bool {
+        self.should_close
+    }
+
+    // Browsers stop running animation frames for hidden pages, so a frame never finds one
+    fn is_minimized(&self) -> bool {
+        false
+    }
+
+    fn is_focused(&self) -> bool {
+        self.document.has_focus().unwrap_or(true)
+    }
+
+    fn set_should_close(&mut self, should_close: bool) {
+        self.should_close = should_close;
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.input.borrow().keys_down.contains(&key)
+    }
+
+    fn size(&self) -> (i32, i32) {
+        (self.canvas.client_width(), self.canvas.client_height())
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        (self.canvas.width() as i32, self.canvas.height() as i32)
+    }
+
+    fn content_scale(&self) -> f32 {
+        self.content_scale
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.input.borrow().cursor_pos
+    }
+
+    fn is_cursor_captured(&self) -> bool {
+        is_pointer_locked(&self.document, &self.canvas)
+    }
+
+    fn set_cursor_captured(&mut self, captured: bool) {
+        self.input.borrow_mut().wants_capture = captured;
+        if captured {
+            self.canvas.request_pointer_lock();
+        } else {
+            self.document.exit_pointer_lock();
+        }
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.document.set_title(title);
+    }
+
+    // Pages take their icon from a link element in the HTML
+    fn set_icon(&mut self, _width: u32, _height: u32, _rgba: &[u8]) {}
+
+    // The browser presents the canvas once the animation frame callback returns
+    fn swap_buffers(&mut self) {}
+
+    // Animation frames always run at the display's refresh rate
+    fn set_vsync(&mut self, _mode: VsyncMode) -> VsyncMode {
+        VsyncMode::On
+    }
+
+    fn monitors(&mut self) -> Vec<String> {
+        match self.window.screen() {
+            Ok(screen) => vec![format!(
+                "Screen ({}x{})",
+                screen.width().unwrap_or(0),
+                screen.height().unwrap_or(0)
+            )],
+            Err(_) => vec![],
+        }
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.document.fullscreen_element().is_some()
+    }
+
+    // The canvas fills the screen it is on; browsers pick the screen and mode themselves, and
+    // may refuse requests not made from an input handler
+    fn set_fullscreen(&mut self, fullscreen: bool, _settings: &WindowSettings) {
+        if !fullscreen {
+            self.document.exit_fullscreen();
+        } else if let Err(e) = self.canvas.request_fullscreen() {
+            log_error(&format!("Could not enter fullscreen: {}", js_error(e)));
+        }
+    }
+}
+
+impl WebBackend {
+    /// Matches the canvas's drawing buffer to its CSS size in device pixels, and returns the
+    /// events for any change.
+    fn resize_canvas(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        let content_scale = self.window.device_pixel_ratio() as f32;
+        let (width, height) = self.size();
+        let width = (width as f32 * content_scale).round().max(1.0) as u32;
+        let height = (height as f32 * content_scale).round().max(1.0) as u32;
+        if (width, height) != (self.canvas.width(), self.canvas.height()) {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+            events.push(Event::FramebufferSize(width as i32, height as i32));
+        }
+        if content_scale != self.content_scale {
+            self.content_scale = content_scale;
+            events.push(Event::ContentScale(content_scale));
+        }
+        events
+    }
+}
+
+impl Drop for WebBackend {
+    fn drop(&mut self) {
+        for listener in &self.listeners {
+            let _ = listener.target.remove_event_listener_with_callback(
+                listener.event_type,
+                listener.callback.as_ref().unchecked_ref(),
+            );
+        }
+    }
+}
+
+fn add_listeners(
+    window: &Window,
+    document: &Document,
+    canvas: &HtmlCanvasElement,
+    input: &Rc<RefCell<Input>>,
+) -> Result<Vec<Listener>, String> {
+    let mut listeners = vec![];
+    let key_input = input.clone();
+    listeners.push(listen(window, "keydown", move |event| {
+        let event: KeyboardEvent = event.unchecked_into();
+        let mut input = key_input.borrow_mut();
+        if let Some(key) = from_dom_code(&event.code()) {
+            // Keeps Tab, Space and the arrows from moving focus or scrolling the page
+            event.prevent_default();
+            let action = if event.repeat() {
+                Action::Repeat
+            } else {
+                Action::Press
+            };
+            input.keys_down.insert(key);
+            input.events.push(Event::Key(key, action));
+        }
+        // Printable keys are named by the one character they type
+        let mut chars = event.key().chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if !event.ctrl_key() && !event.meta_key() {
+                input.events.push(Event::Char(c));
+            }
+        }
+    })?);
+    let key_input = input.clone();
+    listeners.push(listen(window, "keyup", move |event| {
+        let event: KeyboardEvent = event.unchecked_into();
+        if let Some(key) = from_dom_code(&event.code()) {
+            let mut input = key_input.borrow_mut();
+            input.keys_down.remove(&key);
+            input.events.push(Event::Key(key, Action::Release));
+        }
+    })?);
+    // Keys let go of while the page is in the background never report a keyup
+    let blur_input = input.clone();
+    listeners.push(listen(window, "blur", move |_| {
+        blur_input.borrow_mut().keys_down.clear();
+    })?);
+
+    let button_input = input.clone();
+    let (lock_document, lock_canvas) = (document.clone(), canvas.clone());
+    listeners.push(listen(canvas, "mousedown", move |event| {
+        let event: MouseEvent = event.unchecked_into();
+        let mut input = button_input.borrow_mut();
+        if let Some(button) = from_dom_button(event.button()) {
+            input.events.push(Event::MouseButton(button, Action::Press));
+        }
+        if input.wants_capture && !is_pointer_locked(&lock_document, &lock_canvas) {
+            lock_canvas.request_pointer_lock();
+        }
+    })?);
+    // On the window, so buttons released outside the canvas are seen
+    let button_input = input.clone();
+    listeners.push(listen(window, "mouseup", move |event| {
+        let event: MouseEvent = event.unchecked_into();
+        if let Some(button) = from_dom_button(event.button()) {
+            let mut input = button_input.borrow_mut();
+            input
+                .events
+                .push(Event::MouseButton(button, Action::Release));
+        }
+    })?);
+    let cursor_input = input.clone();
+    let (cursor_document, cursor_canvas) = (document.clone(), canvas.clone());
+    listeners.push(listen(window, "mousemove", move |event| {
+        let event: MouseEvent = event.unchecked_into();
+        let mut input = cursor_input.borrow_mut();
+        let (x, y) = input.cursor_pos;
+        // A locked pointer stays put and only reports how far it moved
+        input.cursor_pos = if is_pointer_locked(&cursor_document, &cursor_canvas) {
+            (x + event.movement_x() as f64, y + event.movement_y() as f64)
+        } else {
+            let bounds = cursor_canvas.get_bounding_client_rect();
+            (
+                event.client_x() as f64 - bounds.left(),
+                event.client_y() as f64 - bounds.top(),
+            )
+        };
+        let (x, y) = input.cursor_pos;
+        input.events.push(Event::CursorPos(x, y));
+    })?);
+    // Right clicks are for the lessons, not the browser's menu
+    listeners.push(listen(canvas, "contextmenu", |event| {
+        event.prevent_default();
+    })?);
+    Ok(listeners)
+}
+
+fn listen(
+    target: &EventTarget,
+    event_type: &'static str,
+    handler: impl FnMut(web_sys::Event) + 'static,
+) -> Result<Listener, String> {
+    let callback = Closure::wrap(Box::new(handler) as Box<dyn FnMut(web_sys::Event)>);
+    target
+        .add_event_listener_with_callback(event_type, callback.as_ref().unchecked_ref())
+        .map_err(js_error)?;
+    Ok(Listener {
+        target: target.clone(),
+        event_type,
+        callback,
+    })
+}
+
+fn is_pointer_locked(document: &Document, canvas: &HtmlCanvasElement) -> bool {
+    let canvas: &Element = canvas.as_ref();
+    document
+        .pointer_lock_element()
+        .map_or(false, |element| element == *canvas)
+}
+
+/// Calls `frame` on every animation frame until it returns false. The browser runs animation
+/// frames once per display refresh, while the page is visible.
+pub fn run_animation_frames(mut frame: impl FnMut() -> bool + 'static) {
+    // The callback requests the next frame with itself, so it has to be reachable from inside
+    let callback: Rc<RefCell<Option<Closure<dyn FnMut()>>>> = Rc::new(RefCell::new(None));
+    let next_callback = callback.clone();
+    *callback.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+        if frame() {
+            request_animation_frame(&next_callback);
+        } else {
+            // Drops the callback, which holds the only other reference to itself
+            let _ = next_callback.borrow_mut().take();
+        }
+    }) as Box<dyn FnMut()>));
+    request_animation_frame(&callback);
+}
+
+fn request_animation_frame(callback: &Rc<RefCell<Option<Closure<dyn FnMut()>>>>) {
+    let window = web_sys::window().expect("No browser window");
+    if let Some(callback) = callback.borrow().as_ref() {
+        if let Err(e) = window.request_animation_frame(callback.as_ref().unchecked_ref()) {
+            log_error(&format!(
+                "Could not request an animation frame: {}",
+                js_error(e)
+            ));
+        }
+    }
+}
+
+/// The page's query string as command line arguments: `?lesson=terrain&no-vsync` reads as
+/// `--lesson=terrain --no-vsync`.
+pub fn query_args() -> Vec<String> {
+    let search = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            let arg = js_sys::decode_uri_component(&arg.replace('+', " "))
+                .map(String::from)
+                .unwrap_or_else(|_| arg.to_string());
+            format!("--{}", arg)
+        })
+        .collect()
+}
+
+/// Sends panic messages to the browser console; standard error goes nowhere on the web.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| log_error(&info.to_string())));
+}
+
+/// Prints `message` as an error in the browser console.
+pub fn log_error(message: &str) {
+    web_sys::console::error_1(&JsValue::from_str(message));
+}
+
+fn js_error(error: JsValue) -> String {
+    error.as_string().unwrap_or_else(|| format!("{:?}", error))
+}
+
+// `KeyboardEvent.code` names physical key positions, so WASD stays put on non-QWERTY layouts
+const KEY_MAP: [(Key, &str); 35] = [
+    (Key::W, "KeyW"),
+    (Key::A, "KeyA"),
+    (Key::S, "KeyS"),
+    (Key::D, "KeyD"),
+    (Key::P, "KeyP"),
+    (Key::Num1, "Digit1"),
+    (Key::Num2, "Digit2"),
+    (Key::Num3, "Digit3"),
+    (Key::Period, "Period"),
+    (Key::LeftBracket, "BracketLeft"),
+    (Key::RightBracket, "BracketRight"),
+    (Key::Escape, "Escape"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F9, "F9"),
+    (Key::F12, "F12"),
+    (Key::V, "KeyV"),
+    (Key::Enter, "Enter"),
+    (Key::LeftAlt, "AltLeft"),
+    (Key::RightAlt, "AltRight"),
+    (Key::Tab, "Tab"),
+    (Key::R, "KeyR"),
+    (Key::LeftShift, "ShiftLeft"),
+    (Key::RightShift, "ShiftRight"),
+    (Key::LeftControl, "ControlLeft"),
+    (Key::RightControl, "ControlRight"),
+    (Key::GraveAccent, "Backquote"),
+    (Key::Backspace, "Backspace"),
+    (Key::Up, "ArrowUp"),
+    (Key::Down, "ArrowDown"),
+    (Key::Left, "ArrowLeft"),
+    (Key::Right, "ArrowRight"),
+    (Key::Space, "Space"),
+];
+
+fn from_dom_code(code: &str) -> Option<Key> {
+    KEY_MAP
+        .iter()
+        .find(|(_, mapped)| *mapped == code)
+        .map(|(key, _)| *key)
+}
+
+fn from_dom_button(button: i16) -> Option<MouseButton> {
+    match button {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None,
+    }
+}
@@ -0,0 +1,357 @@
+use glutin::dpi::{LogicalSize, PhysicalSize};
+use glutin::event::{DeviceEvent, ElementState, VirtualKeyCode, WindowEvent};
+use glutin::event_loop::{ControlFlow, EventLoop};
+use glutin::platform::run_return::EventLoopExtRunReturn;
+use glutin::window::{Fullscreen, Icon, Window, WindowBuilder};
+use glutin::{Api, ContextBuilder, ContextWrapper, GlRequest, PossiblyCurrent};
+
+use crate::config::{FullscreenMode, Settings, WindowSettings};
+#[cfg(feature = "gl45")]
+use crate::ogl::bindless;
+use crate::ogl::capabilities::GlProfile;
+use crate::platform::{Action, Event, Key, MouseButton, VsyncMode, WindowBackend};
+use std::collections::HashSet;
+use std::time::Instant;
+
+/// winit window with a glutin context. Events are pumped with `run_return` once per frame, so
+/// the app keeps owning its frame loop as it does with glfw. Positions and sizes are in physical
+/// pixels.
+pub struct WinitBackend {
+    event_loop: EventLoop<()>,
+    context: ContextWrapper<PossiblyCurrent, Window>,
+    start: Instant,
+    should_close: bool,
+    keys_down: HashSet<Key>,
+    cursor_pos: (f64, f64),
+    cursor_captured: bool,
+    vsync: bool,
+    focused: bool,
+}
+
+impl WindowBackend for WinitBackend {
+    fn create(settings: &Settings, visible: bool) -> Result<WinitBackend, String> {
+        let event_loop = EventLoop::new();
+        let mut context = None;
+        // Newer profiles are unavailable on some platforms (macOS stops at 4.1), so fall back
+        for profile in GlProfile::candidates(settings.window.api, settings.window.gl_version) {
+            let [major, minor] = profile.version();
+            let window_builder = WindowBuilder::new()
+                .with_title("Learn OpenGL")
+                .with_inner_size(LogicalSize::new(
+                    settings.window.width,
+                    settings.window.height,
+                ))
+                .with_visible(visible);
+            let api = if profile == GlProfile::Gles30 {
+                Api::OpenGlEs
+            } else {
+                Api::OpenGl
+            };
+            let mut context_builder = ContextBuilder::new()
+                .with_gl(GlRequest::Specific(api, (major as u8, minor as u8)))
+                .with_double_buffer(Some(settings.window.double_buffer))
+                .with_vsync(settings.window.vsync)
+                .with_multisampling(settings.window.msaa_samples as u16)
+                .with_gl_debug_flag(settings.window.gl_debug);
+            if api == Api::OpenGl {
+                context_builder = context_builder.with_gl_profile(glutin::GlProfile::Core);
+            }
+            match context_builder.build_windowed(window_builder, &event_loop) {
+                Ok(created) => {
+                    context = Some(created);
+                    break;
+                }
+                Err(e) => eprintln!(
+                    "Could not create an OpenGL {}.{} context: {}",
+                    major, minor, e
+                ),
+            }
+        }
+        let context = context.ok_or("winit window creation failed")?;
+        let context = unsafe { context.make_current() }.map_err(|(_, e)| e.to_string())?;
+        gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+        #[cfg(feature = "gl45")]
+        bindless::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+
+        let mut backend = WinitBackend {
+            event_loop,
+            context,
+            start: Instant::now(),
+            should_close: false,
+            keys_down: HashSet::new(),
+            cursor_pos: (0.0, 0.0),
+            cursor_captured: false,
+            vsync: settings.window.vsync,
+            focused: true,
+        };
+        if settings.window.fullscreen {
+            backend.set_fullscreen(true, &settings.window);
+        }
+        Ok(backend)
+    }
+
+    // winit allows a single event loop per process, and it goes with the first window
+    fn can_recreate() -> bool {
+        false
+    }
+
+    fn time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        let context = &self.context;
+        let should_close = &mut self.should_close;
+        let keys_down = &mut self.keys_down;
+        let cursor_pos = &mut self.cursor_pos;
+        let cursor_captured = self.cursor_captured;
+        let focused = &mut self.focused;
+        self.event_loop.run_return(|event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+            match event {
+                glutin::event::Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => *should_close = true,
+                    WindowEvent::Focused(is_focused) => *focused = is_focused,
+                    WindowEvent::Resized(size) => {
+                        context.resize(size);
+                        events.push(Event::FramebufferSize(
+                            size.width as i32,
+                            size.height as i32,
+                        ));
+                    }
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    } => {
+                        context.resize(*new_inner_size);
+                        events.push(Event::ContentScale(scale_factor as f32));
+                        events.push(Event::FramebufferSize(
+                            new_inner_size.width as i32,
+                            new_inner_size.height as i32,
+                        ));
+                    }
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        if let Some(key) = input.virtual_keycode.and_then(from_winit_key) {
+                            let action = match input.state {
+                                ElementState::Pressed if keys_down.insert(key) => Action::Press,
+                                ElementState::Pressed => Action::Repeat,
+                                ElementState::Released => {
+                                    keys_down.remove(&key);
+                                    Action::Release
+                                }
+                            };
+                            events.push(Event::Key(key, action));
+                        }
+                    }
+                    WindowEvent::ReceivedCharacter(character) => {
+                        events.push(Event::Char(character));
+                    }
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        if let Some(button) = from_winit_mouse_button(button) {
+                            let action = match state {
+                                ElementState::Pressed => Action::Press,
+                                ElementState::Released => Action::Release,
+                            };
+                            events.push(Event::MouseButton(button, action));
+                        }
+                    }
+                    // A grabbed cursor stops at the window edges; raw motion drives it instead
+                    WindowEvent::CursorMoved { position, .. } if !cursor_captured => {
+                        *cursor_pos = (position.x, position.y);
+                        events.push(Event::CursorPos(position.x, position.y));
+                    }
+                    _ => {}
+                },
+                glutin::event::Event::DeviceEvent {
+                    event: DeviceEvent::MouseMotion { delta },
+                    ..
+                } if cursor_captured => {
+                    cursor_pos.0 += delta.0;
+                    cursor_pos.1 += delta.1;
+                    events.push(Event::CursorPos(cursor_pos.0, cursor_pos.1));
+                }
+                glutin::event::Event::MainEventsCleared => *control_flow = ControlFlow::Exit,
+                _ => {}
+            }
+        });
+        events
+    }
+
+    fn should_close(&self) -> bool {
+        self.should_close
+    }
+
+    // winit has no minimized query; minimizing resizes the window to zero on every platform
+    fn is_minimized(&self) -> bool {
+        let (width, height) = self.framebuffer_size();
+        width == 0 || height == 0
+    }
+
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn set_should_close(&mut self, should_close: bool) {
+        self.should_close = should_close;
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.framebuffer_size()
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        let size = self.context.window().inner_size();
+        (size.width as i32, size.height as i32)
+    }
+
+    fn content_scale(&self) -> f32 {
+        self.context.window().scale_factor() as f32
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.cursor_pos
+    }
+
+    fn is_cursor_captured(&self) -> bool {
+        self.cursor_captured
+    }
+
+    fn set_cursor_captured(&mut self, captured: bool) {
+        let window = self.context.window();
+        if let Err(e) = window.set_cursor_grab(captured) {
+            eprintln!("Could not change the cursor grab: {}", e);
+        }
+        window.set_cursor_visible(!captured);
+        self.cursor_captured = captured;
+    }
+
+    fn swap_buffers(&mut self) {
+        if let Err(e) = self.context.swap_buffers() {
+            eprintln!("Swapping buffers failed: {}", e);
+        }
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.context.window().set_title(title);
+    }
+
+    fn set_icon(&mut self, width: u32, height: u32, rgba: &[u8]) {
+        match Icon::from_rgba(rgba.to_vec(), width, height) {
+            Ok(icon) => self.context.window().set_window_icon(Some(icon)),
+            Err(e) => eprintln!("Invalid window icon: {}", e),
+        }
+    }
+
+    // glutin fixes the swap interval when the context is created
+    fn set_vsync(&mut self, mode: VsyncMode) -> VsyncMode {
+        let current = if self.vsync {
+            VsyncMode::On
+        } else {
+            VsyncMode::Off
+        };
+        if mode != current {
+            eprintln!("The winit backend cannot change vsync at runtime");
+        }
+        current
+    }
+
+    fn monitors(&mut self) -> Vec<String> {
+        self.context
+            .window()
+            .available_monitors()
+            .map(|monitor| {
+                let size = monitor.size();
+                format!(
+                    "{} ({}x{})",
+                    monitor.name().unwrap_or_else(|| "Unknown".to_string()),
+                    size.width,
+                    size.height
+                )
+            })
+            .collect()
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.context.window().fullscreen().is_some()
+    }
+
+    // winit restores the windowed size and position by itself
+    fn set_fullscreen(&mut self, fullscreen: bool, settings: &WindowSettings) {
+        let window = self.context.window();
+        if !fullscreen {
+            window.set_fullscreen(None);
+            return;
+        }
+        let monitor = window
+            .available_monitors()
+            .nth(settings.monitor)
+            .or_else(|| window.primary_monitor());
+        let exclusive = match (settings.fullscreen_mode, &monitor) {
+            (FullscreenMode::Exclusive, Some(monitor)) => monitor
+                .video_modes()
+                .filter(|mode| mode.size() == PhysicalSize::new(settings.width, settings.height))
+                .max_by_key(|mode| mode.refresh_rate())
+                .map(Fullscreen::Exclusive),
+            _ => None,
+        };
+        // Without a video mode matching the window size, exclusive falls back to borderless
+        window.set_fullscreen(Some(
+            exclusive.unwrap_or_else(|| Fullscreen::Borderless(monitor)),
+        ));
+    }
+}
+
+fn from_winit_key(keycode: VirtualKeyCode) -> Option<Key> {
+    match keycode {
+        VirtualKeyCode::W => Some(Key::W),
+        VirtualKeyCode::A => Some(Key::A),
+        VirtualKeyCode::S => Some(Key::S),
+        VirtualKeyCode::D => Some(Key::D),
+        VirtualKeyCode::P => Some(Key::P),
+        VirtualKeyCode::Key1 => Some(Key::Num1),
+        VirtualKeyCode::Key2 => Some(Key::Num2),
+        VirtualKeyCode::Key3 => Some(Key::Num3),
+        VirtualKeyCode::Period => Some(Key::Period),
+        VirtualKeyCode::LBracket => Some(Key::LeftBracket),
+        VirtualKeyCode::RBracket => Some(Key::RightBracket),
+        VirtualKeyCode::Escape => Some(Key::Escape),
+        VirtualKeyCode::F3 => Some(Key::F3),
+        VirtualKeyCode::F4 => Some(Key::F4),
+        VirtualKeyCode::F5 => Some(Key::F5),
+        VirtualKeyCode::F6 => Some(Key::F6),
+        VirtualKeyCode::F9 => Some(Key::F9),
+        VirtualKeyCode::F12 => Some(Key::F12),
+        VirtualKeyCode::V => Some(Key::V),
+        VirtualKeyCode::Return => Some(Key::Enter),
+        VirtualKeyCode::LAlt => Some(Key::LeftAlt),
+        VirtualKeyCode::RAlt => Some(Key::RightAlt),
+        VirtualKeyCode::Tab => Some(Key::Tab),
+        VirtualKeyCode::R => Some(Key::R),
+        VirtualKeyCode::LShift => Some(Key::LeftShift),
+        VirtualKeyCode::RShift => Some(Key::RightShift),
+        VirtualKeyCode::LControl => Some(Key::LeftControl),
+        VirtualKeyCode::RControl => Some(Key::RightControl),
+        VirtualKeyCode::Grave => Some(Key::GraveAccent),
+        VirtualKeyCode::Back => Some(Key::Backspace),
+        VirtualKeyCode::Up => Some(Key::Up),
+        VirtualKeyCode::Down => Some(Key::Down),
+        VirtualKeyCode::Left => Some(Key::Left),
+        VirtualKeyCode::Right => Some(Key::Right),
+        VirtualKeyCode::Space => Some(Key::Space),
+        _ => None,
+    }
+}
+
+fn from_winit_mouse_button(button: glutin::event::MouseButton) -> Option<MouseButton> {
+    match button {
+        glutin::event::MouseButton::Left => Some(MouseButton::Left),
+        glutin::event::MouseButton::Right => Some(MouseButton::Right),
+        glutin::event::MouseButton::Middle => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
@@ -0,0 +1,221 @@
+// Reusable object definitions for scene files. An object naming a prefab starts from the
+// prefab's mesh, shading, transform, spin and script, and whatever the object sets itself
+// overrides those one by one. Prefabs are looked up in the scene file's `prefabs` first, then in
+// `resources/prefabs/<name>.ron`, so they can be shared between scenes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::scene::{SceneObject, Shading, Transform};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Where prefabs a scene does not define itself are looked up, by name.
+pub const PREFAB_DIRECTORY: &str = "resources/prefabs";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Prefab {
+    pub mesh: String,
+    #[serde(default)]
+    pub shading: Shading,
+    /// Instances override its parts one by one, typically just the position
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default)]
+    pub spin_degrees_per_second: f32,
+    #[serde(default)]
+    pub script: Option<String>,
+}
+
+/// Prefabs by name, loaded from `PREFAB_DIRECTORY` the first time they are asked for unless the
+/// scene defines them.
+pub struct PrefabLibrary {
+    prefabs: BTreeMap<String, Result<Prefab, String>>,
+}
+
+/// A scene object as written in a scene file: its prefab, if any, and what it sets itself.
+/// Objects without a prefab set everything, with a mesh at least.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ObjectEntry {
+    pub name: String,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub prefab: Option<String>,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub mesh: Option<String>,
+    #[serde(default, skip_serializing_if = "TransformEntry::is_empty")]
+    pub transform: TransformEntry,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub spin_degrees_per_second: Option<f32>,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub shading: Option<Shading>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+}
+
+/// The parts of a transform an object overrides.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TransformEntry {
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub rotation_axis: Option<[f32; 3]>,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub rotation_degrees: Option<f32>,
+    #[serde(default, with = "bare_option", skip_serializing_if = "Option::is_none")]
+    pub scale: Option<[f32; 3]>,
+}
+
+impl Prefab {
+    pub fn load(file_path: &Path) -> Result<Prefab, String> {
+        let contents = fs::read_to_string(file_path).map_err(|err| err.to_string())?;
+        ron::de::from_str(&contents).map_err(|err| err.to_string())
+    }
+}
+
+impl PrefabLibrary {
+    /// A library starting with the prefabs a scene defines itself.
+    pub fn new(scene_prefabs: &BTreeMap<String, Prefab>) -> PrefabLibrary {
+        PrefabLibrary {
+            prefabs: scene_prefabs
+                .iter()
+                .map(|(name, prefab)| (name.clone(), Ok(prefab.clone())))
+                .collect(),
+        }
+    }
+
+    pub fn get(&mut self, name: &str) -> Result<&Prefab, String> {
+        self.prefabs
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let file_path = Path::new(PREFAB_DIRECTORY).join(format!("{}.ron", name));
+                Prefab::load(&file_path)
+                    .map_err(|e| format!("Failed loading prefab {}: {}", file_path.display(), e))
+            })
+            .as_ref()
+            .map_err(|e| e.clone())
+    }
+}
+
+impl ObjectEntry {
+    /// The object with its prefab's values filled in where it does not set its own.
+    pub fn resolve(self, library: &mut PrefabLibrary) -> Result<SceneObject, String> {
+        let prefab = match &self.prefab {
+            Some(prefab_name) => Some(library.get(prefab_name)?.clone()),
+            None => None,
+        };
+        let mesh = match (self.mesh, &prefab) {
+            (Some(mesh), _) => mesh,
+            (None, Some(prefab)) => prefab.mesh.clone(),
+            (None, None) => return Err(format!("Object {} has no mesh and no prefab", self.name)),
+        };
+        let base = prefab
+            .as_ref()
+            .map(|prefab| prefab.transform.clone())
+            .unwrap_or_default();
+        let transform = Transform::new(
+            self.transform.position.unwrap_or(base.position),
+            self.transform
+                .rotation_axis
+                .unwrap_or_else(|| base.rotation_axis()),
+            self.transform
+                .rotation_degrees
+                .unwrap_or_else(|| base.rotation_degrees()),
+            self.transform.scale.unwrap_or(base.scale),
+        );
+        Ok(SceneObject {
+            name: self.name,
+            mesh,
+            transform,
+            spin_degrees_per_second: self
+                .spin_degrees_per_second
+                .or_else(|| prefab.as_ref().map(|prefab| prefab.spin_degrees_per_second))
+                .unwrap_or(0.0_f32),
+            shading: self
+                .shading
+                .or_else(|| prefab.as_ref().map(|prefab| prefab.shading))
+                .unwrap_or_default(),
+            script: self
+                .script
+                .or_else(|| prefab.as_ref().and_then(|prefab| prefab.script.clone())),
+            prefab: self.prefab,
+        })
+    }
+
+    /// What to write for `object`: only where it differs from `prefab`, or everything without
+    /// one.
+    pub fn from_object(object: &SceneObject, prefab: Option<&Prefab>) -> ObjectEntry {
+        fn differing<T: Clone + PartialEq>(value: &T, prefab_value: Option<&T>) -> Option<T> {
+            if prefab_value == Some(value) {
+                None
+            } else {
+                Some(value.clone())
+            }
+        }
+        let transform = &object.transform;
+        let prefab_transform = prefab.map(|prefab| &prefab.transform);
+        ObjectEntry {
+            name: object.name.clone(),
+            prefab: object.prefab.clone(),
+            mesh: differing(&object.mesh, prefab.map(|prefab| &prefab.mesh)),
+            transform: TransformEntry {
+                position: differing(
+                    &transform.position,
+                    prefab_transform.map(|base| &base.position),
+                ),
+                rotation_axis: differing(
+                    &transform.rotation_axis(),
+                    prefab_transform.map(|base| base.rotation_axis()).as_ref(),
+                ),
+                rotation_degrees: differing(
+                    &transform.rotation_degrees(),
+                    prefab_transform
+                        .map(|base| base.rotation_degrees())
+                        .as_ref(),
+                ),
+                scale: differing(&transform.scale, prefab_transform.map(|base| &base.scale)),
+            },
+            spin_degrees_per_second: differing(
+                &object.spin_degrees_per_second,
+                prefab.map(|prefab| &prefab.spin_degrees_per_second),
+            ),
+            shading: differing(&object.shading, prefab.map(|prefab| &prefab.shading)),
+            script: match prefab {
+                Some(prefab) if prefab.script == object.script => None,
+                _ => object.script.clone(),
+            },
+        }
+    }
+}
+
+impl TransformEntry {
+    fn is_empty(&self) -> bool {
+        self.position.is_none()
+            && self.rotation_axis.is_none()
+            && self.rotation_degrees.is_none()
+            && self.scale.is_none()
+    }
+}
+
+// Overrides are written as bare values rather than `Some(...)`; an absent field is `None`
+mod bare_option {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::WindowSettings;
+use crate::platform::{VsyncMode, WindowBackend};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often a minimized window checks for events
+const MINIMIZED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Ends each frame: swaps (or flushes a single buffered window), then holds the frame rate to
+/// `max_fps`, or `background_fps` while the window is unfocused, by sleeping. Owns the window's
+/// vsync mode so it can be changed at runtime.
+pub struct Presenter {
+    pub vsync: VsyncMode,
+    double_buffer: bool,
+    frame_duration: Option<Duration>,
+    background_frame_duration: Option<Duration>,
+    next_frame: Option<Instant>,
+}
+
+impl Presenter {
+    /// Applies the vsync mode from `settings` to `window`, whose context must be current.
+    pub fn new(window: &mut impl WindowBackend, settings: &WindowSettings) -> Presenter {
+        let requested = match (settings.vsync, settings.adaptive_vsync) {
+            (false, _) => VsyncMode::Off,
+            (true, false) => VsyncMode::On,
+            (true, true) => VsyncMode::Adaptive,
+        };
+        let vsync = window.set_vsync(requested);
+        if vsync != requested {
+            println!("Vsync {:?} unsupported, using {:?}", requested, vsync);
+        }
+        Presenter {
+            vsync,
+            double_buffer: settings.double_buffer,
+            frame_duration: frame_duration(settings.max_fps),
+            background_frame_duration: frame_duration(settings.background_fps),
+            next_frame: None,
+        }
+    }
+
+    /// Steps through off, on and adaptive vsync; modes the driver lacks are skipped.
+    pub fn cycle_vsync(&mut self, window: &mut impl WindowBackend) {
+        let next = match self.vsync {
+            VsyncMode::Off => VsyncMode::On,
+            VsyncMode::On => VsyncMode::Adaptive,
+            VsyncMode::Adaptive => VsyncMode::Off,
+        };
+        self.vsync = match window.set_vsync(next) {
+            // Adaptive unsupported: skip ahead instead of staying on `On`
+            VsyncMode::On if next == VsyncMode::Adaptive => window.set_vsync(VsyncMode::Off),
+            applied => applied,
+        };
+        println!("Vsync {:?}", self.vsync);
+    }
+
+    pub fn present(&mut self, window: &mut impl WindowBackend) {
+        if self.double_buffer {
+            window.swap_buffers();
+        } else {
+            window.gl_context().flush();
+        }
+
+        let frame_duration = if window.is_focused() {
+            self.frame_duration
+        } else {
+            // Whichever cap is lower wins
+            self.frame_duration.max(self.background_frame_duration)
+        };
+        if let Some(frame_duration) = frame_duration {
+            let now = Instant::now();
+            let next_frame = self.next_frame.unwrap_or(now);
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            }
+            // Deadlines advance by whole frames, but never fall behind after a slow frame
+            self.next_frame = Some(next_frame.max(now) + frame_duration);
+        }
+    }
+
+    /// Stands in for a frame while the window is minimized, so events keep being polled without
+    /// spinning.
+    pub fn idle(&mut self) {
+        thread::sleep(MINIMIZED_POLL_INTERVAL);
+        self.next_frame = None;
+    }
+}
+
+fn frame_duration(fps: u32) -> Option<Duration> {
+    if fps > 0 {
+        Some(Duration::from_secs_f64(1.0 / fps as f64))
+    } else {
+        None
+    }
+}
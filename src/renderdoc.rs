@@ -0,0 +1,185 @@
+// RenderDoc's in-application API: frame captures triggered from inside the app, so debugging a
+// frame needs no launching through the RenderDoc UI. See renderdoc_app.h for the API.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+// Captures are written here, named after the lesson
+const CAPTURE_DIRECTORY: &str = "renderdoc_captures";
+// eRENDERDOC_API_Version_1_2_0, the first version with capture file comments
+const API_VERSION_1_2_0: c_int = 10200;
+
+type GetApiFn = unsafe extern "C" fn(version: c_int, api: *mut *mut c_void) -> c_int;
+
+/// RENDERDOC_API_1_2_0: a table of function pointers. Entries this app does not call are kept
+/// as untyped slots, so the ones it does call sit at the right offsets.
+#[repr(C)]
+struct RenderDocApi {
+    get_api_version: unsafe extern "C" fn(major: *mut c_int, minor: *mut c_int, patch: *mut c_int),
+    _capture_options_and_focus_keys: [*const c_void; 5],
+    set_capture_keys: unsafe extern "C" fn(keys: *const c_int, count: c_int),
+    _overlay_hooks_and_crash_handler: [*const c_void; 4],
+    set_capture_file_path_template: unsafe extern "C" fn(path_template: *const c_char),
+    _get_capture_file_path_template: *const c_void,
+    get_num_captures: unsafe extern "C" fn() -> u32,
+    get_capture: unsafe extern "C" fn(
+        capture_i: u32,
+        file_path: *mut c_char,
+        path_length: *mut u32,
+        timestamp: *mut u64,
+    ) -> u32,
+    _trigger_capture_to_set_active_window: [*const c_void; 4],
+    start_frame_capture: unsafe extern "C" fn(device: *mut c_void, window: *mut c_void),
+    _is_frame_capturing: *const c_void,
+    end_frame_capture: unsafe extern "C" fn(device: *mut c_void, window: *mut c_void) -> u32,
+    _trigger_multi_frame_capture: *const c_void,
+    set_capture_file_comments:
+        unsafe extern "C" fn(file_path: *const c_char, comments: *const c_char),
+}
+
+/// A connection to RenderDoc, injected into the process either by launching through RenderDoc
+/// or by `connect` loading its library.
+pub struct RenderDoc {
+    api: &'static RenderDocApi,
+    capturing: bool,
+    /// Stored with every capture, e.g. the lesson it shows
+    comments: CString,
+}
+
+impl RenderDoc {
+    /// Connects to RenderDoc if it is already loaded into the process, or else, with `load`,
+    /// loads its library (librenderdoc.so or renderdoc.dll, found on the library search path).
+    /// Has to run before the OpenGL context is created for RenderDoc to hook it. Captures are
+    /// named after `capture_name` and only taken through `start_frame_capture`, as RenderDoc's
+    /// own capture keys are turned off.
+    pub fn connect(load: bool, capture_name: &str) -> Result<RenderDoc, String> {
+        let get_api = unsafe { find_get_api(load) }
+            .ok_or_else(|| "RenderDoc is not loaded into the process".to_string())?;
+        let mut api: *mut c_void = ptr::null_mut();
+        if unsafe { get_api(API_VERSION_1_2_0, &mut api) } != 1 || api.is_null() {
+            return Err("RenderDoc does not provide API version 1.2".to_string());
+        }
+        let api = unsafe { &*(api as *const RenderDocApi) };
+        let path_template = CString::new(format!("{}/{}", CAPTURE_DIRECTORY, capture_name))
+            .map_err(|e| e.to_string())?;
+        unsafe {
+            (api.set_capture_keys)(ptr::null(), 0);
+            (api.set_capture_file_path_template)(path_template.as_ptr());
+        }
+        Ok(RenderDoc {
+            api,
+            capturing: false,
+            comments: CString::new(format!("Lesson: {}", capture_name))
+                .map_err(|e| e.to_string())?,
+        })
+    }
+
+    /// Major, minor and patch version of the RenderDoc library.
+    pub fn version(&self) -> [i32; 3] {
+        let mut version = [0; 3];
+        unsafe {
+            (self.api.get_api_version)(&mut version[0], &mut version[1], &mut version[2]);
+        }
+        version
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    /// Records every OpenGL call from now until `end_frame_capture`.
+    pub fn start_frame_capture(&mut self) {
+        unsafe {
+            (self.api.start_frame_capture)(ptr::null_mut(), ptr::null_mut());
+        }
+        self.capturing = true;
+    }
+
+    /// Finishes the capture begun by `start_frame_capture`, stores the comments with it and
+    /// returns the file it was saved to.
+    pub fn end_frame_capture(&mut self) -> Result<String, String> {
+        self.capturing = false;
+        unsafe {
+            if (self.api.end_frame_capture)(ptr::null_mut(), ptr::null_mut()) != 1 {
+                return Err("RenderDoc failed to capture the frame".to_string());
+            }
+            // A null path stands for the latest capture
+            (self.api.set_capture_file_comments)(ptr::null(), self.comments.as_ptr());
+            let capture_i = (self.api.get_num_captures)()
+                .checked_sub(1)
+                .ok_or_else(|| "RenderDoc saved no capture".to_string())?;
+            let mut path_length = 0_u32;
+            (self.api.get_capture)(
+                capture_i,
+                ptr::null_mut(),
+                &mut path_length,
+                ptr::null_mut(),
+            );
+            let mut file_path = vec![0 as c_char; path_length as usize + 1];
+            (self.api.get_capture)(
+                capture_i,
+                file_path.as_mut_ptr(),
+                &mut path_length,
+                ptr::null_mut(),
+            );
+            Ok(CStr::from_ptr(file_path.as_ptr())
+                .to_string_lossy()
+                .into_owned())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn find_get_api(load: bool) -> Option<GetApiFn> {
+    extern "C" {
+        fn dlopen(file_name: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+    const RTLD_NOW: c_int = 2;
+    const RTLD_NOLOAD: c_int = 4;
+    let library = b"librenderdoc.so\0".as_ptr() as *const c_char;
+    let mut handle = dlopen(library, RTLD_NOW | RTLD_NOLOAD);
+    if handle.is_null() && load {
+        handle = dlopen(library, RTLD_NOW);
+    }
+    if handle.is_null() {
+        return None;
+    }
+    let symbol = dlsym(handle, b"RENDERDOC_GetAPI\0".as_ptr() as *const c_char);
+    if symbol.is_null() {
+        None
+    } else {
+        Some(std::mem::transmute::<*mut c_void, GetApiFn>(symbol))
+    }
+}
+
+#[cfg(windows)]
+unsafe fn find_get_api(load: bool) -> Option<GetApiFn> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleA(module_name: *const c_char) -> *mut c_void;
+        fn LoadLibraryA(file_name: *const c_char) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+    let library = b"renderdoc.dll\0".as_ptr() as *const c_char;
+    let mut module = GetModuleHandleA(library);
+    if module.is_null() && load {
+        module = LoadLibraryA(library);
+    }
+    if module.is_null() {
+        return None;
+    }
+    let symbol = GetProcAddress(module, b"RENDERDOC_GetAPI\0".as_ptr() as *const c_char);
+    if symbol.is_null() {
+        None
+    } else {
+        Some(std::mem::transmute::<*mut c_void, GetApiFn>(symbol))
+    }
+}
+
+// RenderDoc supports no other platforms
+#[cfg(not(any(target_os = "linux", windows)))]
+unsafe fn find_get_api(_load: bool) -> Option<GetApiFn> {
+    None
+}
@@ -0,0 +1,1134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{Looping, Property, Track, Tween, TweenValue};
+use crate::math::color::Color;
+use crate::math::easing::{slerp, Easing};
+use crate::math::glm::{self, Mat4, Quat, Vec3};
+use crate::math::random::{random_point_in_ring, random_unit_vector, Rng};
+use crate::ogl::graphics::Camera;
+use crate::prefab::{ObjectEntry, Prefab, PrefabLibrary};
+use ron::ser::PrettyConfig;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_SCENE_FILE: &str = "resources/scenes/cubes.ron";
+/// Where the stress lesson saves to, so it never overwrites the cubes scene.
+pub const STRESS_SCENE_FILE: &str = "resources/scenes/stress.ron";
+pub const PARTICLES_SCENE_FILE: &str = "resources/scenes/particles.ron";
+pub const TERRAIN_SCENE_FILE: &str = "resources/scenes/terrain.ron";
+pub const GPU_CULLING_SCENE_FILE: &str = "resources/scenes/gpu_culling.ron";
+pub const TOON_SCENE_FILE: &str = "resources/scenes/toon.ron";
+pub const LIGHTS_SCENE_FILE: &str = "resources/scenes/lights.ron";
+pub const REFLECTIONS_SCENE_FILE: &str = "resources/scenes/reflections.ron";
+pub const BREAKOUT_SCENE_FILE: &str = "resources/scenes/breakout.ron";
+pub const HDR_SCENE_FILE: &str = "resources/scenes/hdr.ron";
+/// Where scene files given by name alone are looked up.
+pub const SCENE_DIRECTORY: &str = "resources/scenes";
+
+// How fast every third of the default cubes turns
+const CUBE_SPIN_DEGREES_PER_SECOND: f32 = 25.0_f32;
+// Half the width of the lit slab of `Scene::many_lights`, and the spacing of its cubes
+const LIGHTS_FLOOR_HALF_SIZE: f32 = 20.0_f32;
+const LIGHTS_CUBE_SPACING: f32 = 4.0_f32;
+const LIGHTS_PROBE_SPACING: f32 = 10.0_f32;
+// Each of the two rooms of `Scene::reflection_rooms` spans this much in x, y and z
+const ROOM_SIZE: [f32; 3] = [8.0_f32, 4.0_f32, 10.0_f32];
+const WALL_THICKNESS: f32 = 0.2_f32;
+// Width, height and length of the tunnel of `Scene::hdr_tunnel`, as in LearnOpenGL's HDR chapter
+const TUNNEL_SIZE: [f32; 3] = [2.5_f32, 2.5_f32, 27.5_f32];
+
+/// Written to scene and prefab files as a `TransformFields`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "TransformFields", into = "TransformFields")]
+pub struct Transform {
+    pub position: [f32; 3],
+    /// Kept a turn about `rotation_axis`, which spins, scripts and rotation tracks turn about
+    rotation: Quat,
+    rotation_axis: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+/// A transform as scene and prefab files spell it, its rotation as an angle about an axis.
+/// Fields left out are those of the identity transform.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+struct TransformFields {
+    position: [f32; 3],
+    rotation_axis: [f32; 3],
+    rotation_degrees: f32,
+    scale: [f32; 3],
+}
+
+/// Written to scene files as an `ObjectEntry`.
+#[derive(Clone, Debug)]
+pub struct SceneObject {
+    pub name: String,
+    pub mesh: String,
+    pub transform: Transform,
+    pub spin_degrees_per_second: f32,
+    pub shading: Shading,
+    /// Rhai script file run against the object, with the `scripting` feature
+    pub script: Option<String>,
+    /// Kept so saving writes only what the object overrides
+    pub prefab: Option<String>,
+}
+
+/// Which material a scene object is drawn with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shading {
+    /// The chapter's two blended textures, unlit
+    #[default]
+    Textured,
+    /// Cel shaded: banded diffuse light, a rim light and an outline
+    Toon,
+    /// The textures lit by every light of the scene, through clustered light lists
+    Lit,
+    /// Lit, and reflecting the scene's reflection probes
+    Glossy,
+    /// A perfect mirror of everything around it, moving objects included, captured into a
+    /// cubemap at runtime
+    Mirror,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Light {
+    pub name: String,
+    pub color: Color,
+    pub kind: LightKind,
+    /// Rhai script file run against the light, with the `scripting` feature
+    #[serde(default)]
+    pub script: Option<String>,
+}
+
+/// How a light shines, following the Light Casters chapter. Directions point the way the light
+/// travels.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum LightKind {
+    /// Parallel rays from far away, like sunlight. `position` only places it in debug views.
+    Directional {
+        position: [f32; 3],
+        direction: [f32; 3],
+    },
+    /// Shines every way, fading out to nothing `range` away
+    Point { position: [f32; 3], range: f32 },
+    /// A point light limited to a cone around `direction`, `outer_cutoff_degrees` off its axis
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        range: f32,
+        outer_cutoff_degrees: f32,
+    },
+}
+
+/// A point the diffuse light around is baked at, for lit objects within `radius` to blend in
+/// by distance.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LightProbe {
+    pub name: String,
+    pub position: [f32; 3],
+    pub radius: f32,
+}
+
+/// A point the scene around is baked at for glossy objects to reflect. Reflections are
+/// projected onto the box from `box_min` to `box_max`, usually the room the probe is in, and
+/// fade out over `blend_distance` beyond it, blending into the probes next door.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReflectionProbe {
+    pub name: String,
+    pub position: [f32; 3],
+    pub box_min: [f32; 3],
+    pub box_max: [f32; 3],
+    pub blend_distance: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CameraPose {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub camera: CameraPose,
+    pub objects: Vec<SceneObject>,
+    pub lights: Vec<Light>,
+    pub probes: Vec<LightProbe>,
+    pub reflection_probes: Vec<ReflectionProbe>,
+    /// Played back by `Animator`
+    pub animations: Vec<Track>,
+    /// Defined in the scene file itself, rather than shared from `PREFAB_DIRECTORY`
+    pub prefabs: BTreeMap<String, Prefab>,
+}
+
+/// A scene as written to disk, with objects made from prefabs only listing their overrides.
+#[derive(Deserialize, Serialize)]
+struct SceneFile {
+    camera: CameraPose,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    prefabs: BTreeMap<String, Prefab>,
+    objects: Vec<ObjectEntry>,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    probes: Vec<LightProbe>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    reflection_probes: Vec<ReflectionProbe>,
+    #[serde(default)]
+    animations: Vec<Track>,
+}
+
+/// `world_from_object` of every scene object at the render time, kept between frames. Static
+/// objects are only recomputed once marked dirty; animated ones every `update`.
+pub struct WorldMatrices {
+    matrices: Vec<Mat4>,
+    dirty: Vec<bool>,
+    static_objects: Vec<bool>,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform {
+            position: [0.0_f32; 3],
+            rotation: glm::quat_identity(),
+            rotation_axis: [0.0_f32, 1.0_f32, 0.0_f32],
+            scale: [1.0_f32; 3],
+        }
+    }
+}
+
+impl Default for TransformFields {
+    fn default() -> TransformFields {
+        TransformFields::from(Transform::default())
+    }
+}
+
+impl From<TransformFields> for Transform {
+    fn from(fields: TransformFields) -> Transform {
+        Transform::new(
+            fields.position,
+            fields.rotation_axis,
+            fields.rotation_degrees,
+            fields.scale,
+        )
+    }
+}
+
+impl From<Transform> for TransformFields {
+    fn from(transform: Transform) -> TransformFields {
+        TransformFields {
+            position: transform.position,
+            rotation_axis: transform.rotation_axis,
+            rotation_degrees: transform.rotation_degrees(),
+            scale: transform.scale,
+        }
+    }
+}
+
+impl Transform {
+    /// Turned `rotation_degrees` about `rotation_axis`, which need not be of unit length. A zero
+    /// axis stands for the y axis.
+    pub fn new(
+        position: [f32; 3],
+        rotation_axis: [f32; 3],
+        rotation_degrees: f32,
+        scale: [f32; 3],
+    ) -> Transform {
+        let mut transform = Transform {
+            position,
+            scale,
+            ..Transform::default()
+        };
+        transform.set_rotation(rotation_axis, rotation_degrees);
+        transform
+    }
+
+    pub fn world_from_object(&self) -> Mat4 {
+        let world_from_object = glm::translate(&glm::identity(), &glm::make_vec3(&self.position))
+            * glm::quat_to_mat4(&self.rotation);
+        glm::scale(&world_from_object, &glm::make_vec3(&self.scale))
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    /// Axis the rotation turns about, as last set. It outlives a zero turn, so spins keep their
+    /// direction through it.
+    pub fn rotation_axis(&self) -> [f32; 3] {
+        self.rotation_axis
+    }
+
+    /// How far the rotation turns about `rotation_axis`, in 0..360.
+    pub fn rotation_degrees(&self) -> f32 {
+        let degrees = glm::quat_angle(&self.rotation).to_degrees();
+        // The angle comes back in 0..180, about whichever way round the axis gives that
+        if glm::dot(
+            &glm::quat_axis(&self.rotation),
+            &glm::make_vec3(&self.rotation_axis),
+        ) < 0.0_f32
+        {
+            (360.0_f32 - degrees).rem_euclid(360.0_f32)
+        } else {
+            degrees
+        }
+    }
+
+    /// Turns `degrees` about `axis` from no rotation. A zero axis keeps the current one.
+    pub fn set_rotation(&mut self, axis: [f32; 3], degrees: f32) {
+        if glm::length(&glm::make_vec3(&axis)) > f32::EPSILON {
+            self.rotation_axis = axis;
+        }
+        self.rotation = glm::quat_angle_axis(
+            degrees.rem_euclid(360.0_f32).to_radians(),
+            &glm::make_vec3(&self.rotation_axis),
+        );
+    }
+
+    /// Replaces the rotation with `rotation`, taking its axis unless it is no rotation at all.
+    pub fn set_rotation_quat(&mut self, rotation: &Quat) {
+        let axis = glm::quat_axis(rotation);
+        if glm::length(&axis) > f32::EPSILON {
+            self.rotation_axis = [axis.x, axis.y, axis.z];
+        }
+        self.rotation = glm::quat_normalize(rotation);
+    }
+
+    /// Turns a further `degrees` about `rotation_axis`.
+    pub fn turn(&mut self, degrees: f32) {
+        let turn = glm::quat_angle_axis(degrees.to_radians(), &glm::make_vec3(&self.rotation_axis));
+        self.rotation = glm::quat_normalize(&(turn * self.rotation));
+    }
+
+    /// Blends towards `other`, the rotation along the shortest arc between the two.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        let lerp3 = |a: &[f32; 3], b: &[f32; 3]| {
+            let v = glm::lerp(&glm::make_vec3(a), &glm::make_vec3(b), t);
+            [v.x, v.y, v.z]
+        };
+        let mut transform = Transform {
+            position: lerp3(&self.position, &other.position),
+            rotation_axis: other.rotation_axis,
+            scale: lerp3(&self.scale, &other.scale),
+            ..Transform::default()
+        };
+        transform.set_rotation_quat(&slerp(&self.rotation, &other.rotation, t));
+        transform
+    }
+}
+
+impl SceneObject {
+    pub fn is_static(&self) -> bool {
+        self.spin_degrees_per_second == 0.0_f32 && self.script.is_none()
+    }
+}
+
+impl LightKind {
+    /// Where the light is, or for directional lights where debug views show it.
+    pub fn position(&self) -> Vec3 {
+        match self {
+            LightKind::Directional { position, .. }
+            | LightKind::Point { position, .. }
+            | LightKind::Spot { position, .. } => glm::make_vec3(position),
+        }
+    }
+}
+
+impl CameraPose {
+    pub fn from_camera(camera: &Camera) -> CameraPose {
+        CameraPose {
+            position: [camera.position.x, camera.position.y, camera.position.z],
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+        }
+    }
+
+    pub fn to_camera(&self) -> Camera {
+        let mut camera = Camera {
+            position: glm::make_vec3(&self.position),
+            front: Vec3::default(),
+            up: glm::vec3(0.0_f32, 1.0_f32, 0.0_f32),
+            yaw: self.yaw,
+            pitch: self.pitch,
+        };
+        camera.update_front();
+        camera
+    }
+}
+
+impl Scene {
+    pub fn load(file_path: &str) -> Result<Scene, String> {
+        let contents = fs::read_to_string(file_path).map_err(|err| err.to_string())?;
+        let file: SceneFile = ron::de::from_str(&contents).map_err(|err| err.to_string())?;
+        let mut library = PrefabLibrary::new(&file.prefabs);
+        let objects = file
+            .objects
+            .into_iter()
+            .map(|entry| entry.resolve(&mut library))
+            .collect::<Result<Vec<SceneObject>, String>>()?;
+        Ok(Scene {
+            camera: file.camera,
+            objects,
+            lights: file.lights,
+            probes: file.probes,
+            reflection_probes: file.reflection_probes,
+            animations: file.animations,
+            prefabs: file.prefabs,
+        })
+    }
+
+    /// Whether each object keeps its transform, neither spinning nor animated by a track, so it
+    /// can be batched and its matrix kept.
+    pub fn static_objects(&self) -> Vec<bool> {
+        self.objects
+            .iter()
+            .map(|object| {
+                object.is_static()
+                    && !self
+                        .animations
+                        .iter()
+                        .any(|track| track.property.object_name() == Some(object.name.as_str()))
+            })
+            .collect()
+    }
+
+    /// Advances every spinning object by one simulation step of `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        for object in self.objects.iter_mut() {
+            let transform = &mut object.transform;
+            transform.turn(object.spin_degrees_per_second * dt);
+        }
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), String> {
+        let mut library = PrefabLibrary::new(&self.prefabs);
+        let objects = self
+            .objects
+            .iter()
+            .map(|object| {
+                // A prefab gone missing since loading gets the object written out in full
+                let prefab = object
+                    .prefab
+                    .as_ref()
+                    .and_then(|prefab_name| library.get(prefab_name).ok());
+                ObjectEntry::from_object(object, prefab)
+            })
+            .collect();
+        let file = SceneFile {
+            camera: self.camera.clone(),
+            prefabs: self.prefabs.clone(),
+            objects,
+            lights: self.lights.clone(),
+            probes: self.probes.clone(),
+            reflection_probes: self.reflection_probes.clone(),
+            animations: self.animations.clone(),
+        };
+        let contents = ron::ser::to_string_pretty(&file, PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        if let Some(parent) = Path::new(file_path).parent() {
+            fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        fs::write(file_path, contents).map_err(|err| err.to_string())
+    }
+
+    /// The ten tilted cubes of the "Camera" chapter, with every third one spinning and a few
+    /// more animated.
+    pub fn default_cubes() -> Scene {
+        #[rustfmt::skip]
+        let cube_centers: [[f32; 3]; 10] = [
+            [ 0.0_f32,   0.0_f32,   0.0_f32],
+            [ 2.0_f32,   5.0_f32, -15.0_f32],
+            [-1.5_f32,  -2.2_f32,  -2.0_f32],
+            [-3.8_f32,  -2.0_f32, -12.3_f32],
+            [ 2.4_f32,  -0.4_f32,  -3.5_f32],
+            [-1.7_f32,   3.0_f32,  -7.5_f32],
+            [ 1.3_f32,  -2.0_f32,  -2.5_f32],
+            [ 1.5_f32,   2.0_f32,  -2.5_f32],
+            [ 1.5_f32,   0.2_f32,  -1.5_f32],
+            [-1.3_f32,   1.0_f32,  -1.5_f32],
+        ];
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 0.0_f32, 3.0_f32],
+                yaw: -90.0_f32,
+                pitch: 0.0_f32,
+            },
+            objects: cube_centers
+                .iter()
+                .enumerate()
+                .map(|(i, center)| SceneObject {
+                    name: format!("cube_{}", i),
+                    mesh: "cube".to_string(),
+                    transform: Transform::new(
+                        *center,
+                        [1.0_f32, 0.3_f32, 0.5_f32],
+                        20.0_f32 * i as f32,
+                        [1.0_f32; 3],
+                    ),
+                    spin_degrees_per_second: 0.0_f32,
+                    shading: Shading::Textured,
+                    script: None,
+                    prefab: None,
+                })
+                .collect(),
+            lights: Scene::default_lights(),
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: Scene::default_animations(&cube_centers),
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// Every third cube spinning, one bobbing up and down, one pulsing, and the lamp and spot
+    /// light changing, as a showcase of the tracks.
+    fn default_animations(cube_centers: &[[f32; 3]]) -> Vec<Track> {
+        let mut animations: Vec<Track> = (0..cube_centers.len())
+            .step_by(3)
+            .map(|i| {
+                let degrees = 20.0_f32 * i as f32;
+                Track {
+                    property: Property::RotationDegrees(format!("cube_{}", i)),
+                    from: TweenValue::Float(degrees),
+                    tweens: vec![Tween {
+                        to: TweenValue::Float(degrees + 360.0_f32),
+                        duration: 360.0_f32 / CUBE_SPIN_DEGREES_PER_SECOND,
+                        easing: Easing::Linear,
+                    }],
+                    looping: Looping::Repeat,
+                    delay: 0.0_f32,
+                }
+            })
+            .collect();
+        let [x, y, z] = cube_centers[5];
+        animations.push(Track {
+            property: Property::Position("cube_5".to_string()),
+            from: TweenValue::Vec3([x, y, z]),
+            tweens: vec![Tween {
+                to: TweenValue::Vec3([x, y + 0.75_f32, z]),
+                duration: 1.5_f32,
+                easing: Easing::Smoothstep,
+            }],
+            looping: Looping::PingPong,
+            delay: 0.0_f32,
+        });
+        animations.push(Track {
+            property: Property::Scale("cube_8".to_string()),
+            from: TweenValue::Vec3([1.0_f32; 3]),
+            tweens: vec![
+                Tween {
+                    to: TweenValue::Vec3([1.25_f32; 3]),
+                    duration: 0.6_f32,
+                    easing: Easing::ElasticOut,
+                },
+                Tween {
+                    to: TweenValue::Vec3([1.0_f32; 3]),
+                    duration: 0.9_f32,
+                    easing: Easing::CubicInOut,
+                },
+                // Rests before pulsing again
+                Tween {
+                    to: TweenValue::Vec3([1.0_f32; 3]),
+                    duration: 1.5_f32,
+                    easing: Easing::Linear,
+                },
+            ],
+            looping: Looping::Repeat,
+            delay: 1.0_f32,
+        });
+        animations.push(Track {
+            property: Property::LightColor("lamp".to_string()),
+            from: TweenValue::Color(Color::rgb(1.0_f32, 0.6_f32, 0.3_f32)),
+            tweens: vec![Tween {
+                to: TweenValue::Color(Color::rgb(0.3_f32, 0.5_f32, 1.0_f32)),
+                duration: 3.0_f32,
+                easing: Easing::Smoothstep,
+            }],
+            looping: Looping::PingPong,
+            delay: 0.0_f32,
+        });
+        animations.push(Track {
+            property: Property::LightCutoffDegrees("spot".to_string()),
+            from: TweenValue::Float(17.5_f32),
+            tweens: vec![Tween {
+                to: TweenValue::Float(26.0_f32),
+                duration: 2.5_f32,
+                easing: Easing::CubicInOut,
+            }],
+            looping: Looping::PingPong,
+            delay: 0.0_f32,
+        });
+        animations
+    }
+
+    /// A sun, a lamp among the cubes and a spot light on the first cube, one of each kind.
+    fn default_lights() -> Vec<Light> {
+        vec![
+            Light {
+                name: "sun".to_string(),
+                color: Color::rgb(1.0_f32, 0.95_f32, 0.85_f32),
+                kind: LightKind::Directional {
+                    position: [0.0_f32, 4.0_f32, -4.0_f32],
+                    direction: [-0.4_f32, -0.8_f32, -0.45_f32],
+                },
+                script: None,
+            },
+            Light {
+                name: "lamp".to_string(),
+                color: Color::rgb(1.0_f32, 0.6_f32, 0.3_f32),
+                kind: LightKind::Point {
+                    position: [-2.5_f32, 1.0_f32, -5.0_f32],
+                    range: 3.0_f32,
+                },
+                script: None,
+            },
+            Light {
+                name: "spot".to_string(),
+                color: Color::rgb(0.5_f32, 0.7_f32, 1.0_f32),
+                kind: LightKind::Spot {
+                    position: [0.0_f32, 3.0_f32, 1.0_f32],
+                    direction: [0.0_f32, -1.0_f32, -0.35_f32],
+                    range: 4.0_f32,
+                    outer_cutoff_degrees: 17.5_f32,
+                },
+                script: None,
+            },
+        ]
+    }
+
+    /// The default cubes with every other one cel shaded, to compare both materials side by
+    /// side.
+    pub fn toon_cubes() -> Scene {
+        let mut scene = Scene::default_cubes();
+        for object in scene.objects.iter_mut().skip(1).step_by(2) {
+            object.shading = Shading::Toon;
+        }
+        scene
+    }
+
+    /// A slab set with a grid of cubes, all lit and every fifth one spinning, `light_count` point
+    /// lights of random colors scattered low over it, and a grid of light probes.
+    pub fn many_lights(light_count: usize, seed: u64) -> Scene {
+        let mut rng = Rng::new(seed);
+        let lit_object = |name: String, position: [f32; 3], scale: [f32; 3]| SceneObject {
+            name,
+            mesh: "cube".to_string(),
+            transform: Transform {
+                position,
+                scale,
+                ..Transform::default()
+            },
+            spin_degrees_per_second: 0.0_f32,
+            shading: Shading::Lit,
+            script: None,
+            prefab: None,
+        };
+        let mut objects = vec![lit_object(
+            "floor".to_string(),
+            [0.0_f32, -0.1_f32, 0.0_f32],
+            [
+                2.0_f32 * LIGHTS_FLOOR_HALF_SIZE,
+                0.2_f32,
+                2.0_f32 * LIGHTS_FLOOR_HALF_SIZE,
+            ],
+        )];
+        let cubes_per_side = (2.0_f32 * LIGHTS_FLOOR_HALF_SIZE / LIGHTS_CUBE_SPACING) as i32;
+        for row in 0..cubes_per_side {
+            for column in 0..cubes_per_side {
+                let offset =
+                    |i: i32| (i as f32 + 0.5_f32) * LIGHTS_CUBE_SPACING - LIGHTS_FLOOR_HALF_SIZE;
+                let mut cube = lit_object(
+                    format!("cube_{}_{}", row, column),
+                    [offset(column), 0.5_f32, offset(row)],
+                    [1.0_f32; 3],
+                );
+                if (row * cubes_per_side + column) % 5 == 0 {
+                    cube.spin_degrees_per_second = CUBE_SPIN_DEGREES_PER_SECOND;
+                }
+                objects.push(cube);
+            }
+        }
+        let lights = (0..light_count)
+            .map(|i| Light {
+                name: format!("light_{}", i),
+                color: Color::from_hsv(rng.range(0.0_f32, 360.0_f32), 0.7_f32, 1.0_f32),
+                kind: LightKind::Point {
+                    position: [
+                        rng.range(-LIGHTS_FLOOR_HALF_SIZE, LIGHTS_FLOOR_HALF_SIZE),
+                        rng.range(0.3_f32, 2.0_f32),
+                        rng.range(-LIGHTS_FLOOR_HALF_SIZE, LIGHTS_FLOOR_HALF_SIZE),
+                    ],
+                    range: rng.range(3.0_f32, 6.0_f32),
+                },
+                script: None,
+            })
+            .collect();
+        // A grid of probes just above the cubes, each reaching past its neighbors
+        let probes_per_side = (2.0_f32 * LIGHTS_FLOOR_HALF_SIZE / LIGHTS_PROBE_SPACING) as i32;
+        let probe_offset =
+            |i: i32| (i as f32 + 0.5_f32) * LIGHTS_PROBE_SPACING - LIGHTS_FLOOR_HALF_SIZE;
+        let probes = (0..probes_per_side * probes_per_side)
+            .map(|i| LightProbe {
+                name: format!("probe_{}", i),
+                position: [
+                    probe_offset(i % probes_per_side),
+                    1.5_f32,
+                    probe_offset(i / probes_per_side),
+                ],
+                radius: 1.5_f32 * LIGHTS_PROBE_SPACING,
+            })
+            .collect();
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 12.0_f32, 30.0_f32],
+                yaw: -90.0_f32,
+                pitch: -25.0_f32,
+            },
+            objects,
+            lights,
+            probes,
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// Two rooms side by side, joined by a doorway, with a glossy floor, a few cubes, a light and
+    /// a light probe in each, and a reflection probe boxed to each room. A mirror cube in the
+    /// left room reflects a cube sliding to and fro beside it.
+    pub fn reflection_rooms() -> Scene {
+        let [width, height, depth] = ROOM_SIZE;
+        let block =
+            |name: &str, position: [f32; 3], scale: [f32; 3], shading: Shading| SceneObject {
+                name: name.to_string(),
+                mesh: "cube".to_string(),
+                transform: Transform {
+                    position,
+                    scale,
+                    ..Transform::default()
+                },
+                spin_degrees_per_second: 0.0_f32,
+                shading,
+                script: None,
+                prefab: None,
+            };
+        let half_wall = 0.5_f32 * WALL_THICKNESS;
+        let doorway_width = 3.0_f32;
+        let divider_length = 0.5_f32 * (depth - doorway_width);
+        let divider_z = 0.5_f32 * (depth - divider_length);
+        let mut objects = vec![
+            block(
+                "floor",
+                [0.0_f32, -half_wall, 0.0_f32],
+                [2.0_f32 * width, WALL_THICKNESS, depth],
+                Shading::Glossy,
+            ),
+            block(
+                "ceiling",
+                [0.0_f32, height + half_wall, 0.0_f32],
+                [2.0_f32 * width, WALL_THICKNESS, depth],
+                Shading::Lit,
+            ),
+            block(
+                "back_wall",
+                [0.0_f32, 0.5_f32 * height, -0.5_f32 * depth - half_wall],
+                [2.0_f32 * width, height, WALL_THICKNESS],
+                Shading::Lit,
+            ),
+            block(
+                "front_wall",
+                [0.0_f32, 0.5_f32 * height, 0.5_f32 * depth + half_wall],
+                [2.0_f32 * width, height, WALL_THICKNESS],
+                Shading::Lit,
+            ),
+            block(
+                "left_wall",
+                [-width - half_wall, 0.5_f32 * height, 0.0_f32],
+                [WALL_THICKNESS, height, depth],
+                Shading::Lit,
+            ),
+            block(
+                "right_wall",
+                [width + half_wall, 0.5_f32 * height, 0.0_f32],
+                [WALL_THICKNESS, height, depth],
+                Shading::Lit,
+            ),
+            block(
+                "divider_back",
+                [0.0_f32, 0.5_f32 * height, -divider_z],
+                [WALL_THICKNESS, height, divider_length],
+                Shading::Lit,
+            ),
+            block(
+                "divider_front",
+                [0.0_f32, 0.5_f32 * height, divider_z],
+                [WALL_THICKNESS, height, divider_length],
+                Shading::Lit,
+            ),
+            block(
+                "crate_low",
+                [-5.5_f32, 0.5_f32, -3.0_f32],
+                [1.0_f32; 3],
+                Shading::Lit,
+            ),
+            block(
+                "crate_high",
+                [-5.5_f32, 1.5_f32, -3.0_f32],
+                [1.0_f32; 3],
+                Shading::Lit,
+            ),
+            block(
+                "pillar",
+                [4.0_f32, 0.5_f32 * height, -2.0_f32],
+                [0.6_f32, height, 0.6_f32],
+                Shading::Lit,
+            ),
+            block(
+                "glossy_crate",
+                [5.0_f32, 0.5_f32, 2.5_f32],
+                [1.0_f32; 3],
+                Shading::Glossy,
+            ),
+        ];
+        let mut spinner = block(
+            "spinner",
+            [-3.0_f32, 1.0_f32, 1.5_f32],
+            [0.8_f32; 3],
+            Shading::Lit,
+        );
+        spinner.spin_degrees_per_second = CUBE_SPIN_DEGREES_PER_SECOND;
+        objects.push(spinner);
+        // A mirror with something moving beside it, which the baked probes could not show
+        objects.push(block(
+            "mirror_cube",
+            [-5.0_f32, 1.0_f32, 1.5_f32],
+            [1.2_f32; 3],
+            Shading::Mirror,
+        ));
+        let shuttle_from = [-6.5_f32, 0.35_f32, -0.5_f32];
+        objects.push(block("shuttle", shuttle_from, [0.7_f32; 3], Shading::Lit));
+        let animations = vec![Track {
+            property: Property::Position("shuttle".to_string()),
+            from: TweenValue::Vec3(shuttle_from),
+            tweens: vec![Tween {
+                to: TweenValue::Vec3([-6.5_f32, 0.35_f32, 3.5_f32]),
+                duration: 3.0_f32,
+                easing: Easing::Smoothstep,
+            }],
+            looping: Looping::PingPong,
+            delay: 0.0_f32,
+        }];
+        // Everything per room, with the left room at negative x
+        let rooms = [("left", -0.5_f32 * width), ("right", 0.5_f32 * width)];
+        let lights = rooms
+            .iter()
+            .zip(
+                [
+                    Color::rgb(1.0_f32, 0.75_f32, 0.45_f32),
+                    Color::rgb(0.5_f32, 0.7_f32, 1.0_f32),
+                ]
+                .iter(),
+            )
+            .map(|((room, center_x), color)| Light {
+                name: format!("{}_lamp", room),
+                color: *color,
+                kind: LightKind::Point {
+                    position: [*center_x, height - 0.8_f32, 0.0_f32],
+                    range: 9.0_f32,
+                },
+                script: None,
+            })
+            .collect();
+        let probes = rooms
+            .iter()
+            .map(|(room, center_x)| LightProbe {
+                name: format!("{}_probe", room),
+                position: [*center_x, 1.5_f32, 0.0_f32],
+                radius: 0.75_f32 * depth,
+            })
+            .collect();
+        let reflection_probes = rooms
+            .iter()
+            .map(|(room, center_x)| ReflectionProbe {
+                name: format!("{}_reflection", room),
+                position: [*center_x, 1.5_f32, 0.0_f32],
+                box_min: [center_x - 0.5_f32 * width, 0.0_f32, -0.5_f32 * depth],
+                box_max: [center_x + 0.5_f32 * width, height, 0.5_f32 * depth],
+                blend_distance: 0.5_f32,
+            })
+            .collect();
+        Scene {
+            camera: CameraPose {
+                position: [-7.0_f32, 2.2_f32, 4.0_f32],
+                yaw: -30.0_f32,
+                pitch: -12.0_f32,
+            },
+            objects,
+            lights,
+            probes,
+            reflection_probes,
+            animations,
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// A long closed tunnel, seen from one end, lit by a light hundreds of times brighter than
+    /// white at the far end and three dim colored ones near the camera, for tone mapping.
+    pub fn hdr_tunnel() -> Scene {
+        let [width, height, length] = TUNNEL_SIZE;
+        let half_wall = 0.5_f32 * WALL_THICKNESS;
+        let center_z = 1.0_f32 - 0.5_f32 * length;
+        let block = |name: &str, position: [f32; 3], scale: [f32; 3]| SceneObject {
+            name: name.to_string(),
+            mesh: "cube".to_string(),
+            transform: Transform {
+                position,
+                scale,
+                ..Transform::default()
+            },
+            spin_degrees_per_second: 0.0_f32,
+            shading: Shading::Lit,
+            script: None,
+            prefab: None,
+        };
+        let objects = vec![
+            block(
+                "floor",
+                [0.0_f32, -half_wall, center_z],
+                [width, WALL_THICKNESS, length],
+            ),
+            block(
+                "ceiling",
+                [0.0_f32, height + half_wall, center_z],
+                [width, WALL_THICKNESS, length],
+            ),
+            block(
+                "left_wall",
+                [-0.5_f32 * width - half_wall, 0.5_f32 * height, center_z],
+                [WALL_THICKNESS, height, length],
+            ),
+            block(
+                "right_wall",
+                [0.5_f32 * width + half_wall, 0.5_f32 * height, center_z],
+                [WALL_THICKNESS, height, length],
+            ),
+            block(
+                "far_wall",
+                [0.0_f32, 0.5_f32 * height, 1.0_f32 - length - half_wall],
+                [width, height, WALL_THICKNESS],
+            ),
+            block(
+                "near_wall",
+                [0.0_f32, 0.5_f32 * height, 1.0_f32 + half_wall],
+                [width, height, WALL_THICKNESS],
+            ),
+        ];
+        let point = |name: &str, color: Color, position: [f32; 3], range: f32| Light {
+            name: name.to_string(),
+            color,
+            kind: LightKind::Point { position, range },
+            script: None,
+        };
+        let lights = vec![
+            point(
+                "far_light",
+                Color::rgb(200.0_f32, 200.0_f32, 200.0_f32),
+                [0.0_f32, 0.5_f32 * height, 1.5_f32 - length],
+                length,
+            ),
+            point(
+                "red_light",
+                Color::rgb(0.1_f32, 0.0_f32, 0.0_f32),
+                [-0.9_f32, 0.3_f32, -8.0_f32],
+                4.0_f32,
+            ),
+            point(
+                "blue_light",
+                Color::rgb(0.0_f32, 0.0_f32, 0.2_f32),
+                [0.0_f32, 0.4_f32, -3.0_f32],
+                4.0_f32,
+            ),
+            point(
+                "green_light",
+                Color::rgb(0.0_f32, 0.1_f32, 0.0_f32),
+                [0.8_f32, 0.5_f32, -5.0_f32],
+                4.0_f32,
+            ),
+        ];
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 0.5_f32 * height, 0.0_f32],
+                yaw: -90.0_f32,
+                pitch: 0.0_f32,
+            },
+            objects,
+            lights,
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// A flat slab for the particle emitters to stand on, seen from a little above.
+    pub fn particles() -> Scene {
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 2.5_f32, 10.0_f32],
+                yaw: -90.0_f32,
+                pitch: -8.0_f32,
+            },
+            objects: vec![SceneObject {
+                name: "ground".to_string(),
+                mesh: "cube".to_string(),
+                transform: Transform {
+                    position: [0.0_f32, -0.1_f32, 0.0_f32],
+                    scale: [14.0_f32, 0.2_f32, 6.0_f32],
+                    ..Transform::default()
+                },
+                spin_degrees_per_second: 0.0_f32,
+                shading: Shading::Textured,
+                script: None,
+                prefab: None,
+            }],
+            lights: vec![],
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// Nothing but the camera, looking over the terrain from above one edge.
+    pub fn terrain() -> Scene {
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 28.0_f32, 56.0_f32],
+                yaw: -90.0_f32,
+                pitch: -25.0_f32,
+            },
+            objects: vec![],
+            lights: vec![],
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// Nothing but a camera, for lessons that draw everything themselves.
+    pub fn empty() -> Scene {
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 0.0_f32, 3.0_f32],
+                yaw: -90.0_f32,
+                pitch: 0.0_f32,
+            },
+            objects: vec![],
+            lights: vec![],
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// Nothing but the camera, in the middle of the GPU culled shapes.
+    pub fn gpu_culling() -> Scene {
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 0.0_f32, 0.0_f32],
+                yaw: -90.0_f32,
+                pitch: 0.0_f32,
+            },
+            objects: vec![],
+            lights: vec![],
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+
+    /// `count` small cubes spinning in a wide ring around the origin, like an asteroid belt,
+    /// for measuring how the renderer holds up with many objects.
+    pub fn stress(count: usize, seed: u64) -> Scene {
+        let mut rng = Rng::new(seed);
+        Scene {
+            camera: CameraPose {
+                position: [0.0_f32, 20.0_f32, 48.0_f32],
+                yaw: -90.0_f32,
+                pitch: -20.0_f32,
+            },
+            objects: (0..count)
+                .map(|i| {
+                    let mut position = random_point_in_ring(&mut rng, 8.0_f32, 40.0_f32);
+                    position.y = rng.range(-4.0_f32, 4.0_f32);
+                    let axis = random_unit_vector(&mut rng);
+                    let scale = rng.range(0.15_f32, 0.35_f32);
+                    SceneObject {
+                        name: format!("cube_{}", i),
+                        mesh: "cube".to_string(),
+                        transform: Transform::new(
+                            [position.x, position.y, position.z],
+                            [axis.x, axis.y, axis.z],
+                            rng.range(0.0_f32, 360.0_f32),
+                            [scale; 3],
+                        ),
+                        spin_degrees_per_second: rng.range(10.0_f32, 90.0_f32),
+                        shading: Shading::Textured,
+                        script: None,
+                        prefab: None,
+                    }
+                })
+                .collect(),
+            lights: vec![],
+            probes: vec![],
+            reflection_probes: vec![],
+            animations: vec![],
+            prefabs: BTreeMap::new(),
+        }
+    }
+}
+
+impl WorldMatrices {
+    pub fn new(scene: &Scene) -> WorldMatrices {
+        WorldMatrices {
+            matrices: scene
+                .objects
+                .iter()
+                .map(|object| object.transform.world_from_object())
+                .collect(),
+            dirty: vec![false; scene.objects.len()],
+            static_objects: scene.static_objects(),
+        }
+    }
+
+    /// Recomputes the matrix of `object_i` on the next `update`, after its transform was edited.
+    pub fn mark_dirty(&mut self, object_i: usize) {
+        self.dirty[object_i] = true;
+    }
+
+    /// Brings the matrices up to date with `scene`, blending animated objects from
+    /// `previous_transforms` by `alpha`.
+    pub fn update(&mut self, scene: &Scene, previous_transforms: &[Transform], alpha: f32) {
+        for (object_i, object) in scene.objects.iter().enumerate() {
+            if self.static_objects[object_i] {
+                if !self.dirty[object_i] {
+                    continue;
+                }
+                self.matrices[object_i] = object.transform.world_from_object();
+            } else {
+                self.matrices[object_i] = previous_transforms[object_i]
+                    .lerp(&object.transform, alpha)
+                    .world_from_object();
+            }
+            self.dirty[object_i] = false;
+        }
+    }
+
+    pub fn get(&self, object_i: usize) -> &Mat4 {
+        &self.matrices[object_i]
+    }
+}
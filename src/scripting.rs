@@ -0,0 +1,334 @@
+// Rhai scripts attached to scene objects and lights through their `script` field. A script may
+// define `setup()`, called once when the scene is loaded, and `update(dt)`, called every
+// simulation step; both see the object or light as `this`, a map of its properties that is
+// written back into the scene after the call. Fields the script adds to `this` are kept between
+// calls, for its own state. Script files are recompiled when they change on disk.
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use crate::math::color::Color;
+use crate::math::glm::{self, Vec3};
+use crate::math::spline::Spline;
+use crate::scene::{LightKind, Scene};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScriptTarget {
+    Object(usize),
+    Light(usize),
+}
+
+struct AttachedScript {
+    file_path: String,
+    target: ScriptTarget,
+    /// None until the file first compiles
+    ast: Option<AST>,
+    /// Of the file as last compiled
+    modified: Option<SystemTime>,
+    this: Map,
+    /// Set after a runtime error, so it is reported once; cleared when the file changes
+    failed: bool,
+}
+
+/// Runs the scripts of a scene's objects and lights.
+pub struct ScriptRunner {
+    engine: Engine,
+    scripts: Vec<AttachedScript>,
+    /// Seconds of simulation since the scene was loaded, `this.time` in scripts
+    elapsed: f32,
+}
+
+impl ScriptRunner {
+    /// Compiles the scripts `scene` refers to and runs their `setup`. Scripts that fail to
+    /// compile are reported and retried when their file changes.
+    pub fn new(scene: &mut Scene) -> ScriptRunner {
+        let object_scripts = scene
+            .objects
+            .iter()
+            .enumerate()
+            .filter_map(|(object_i, object)| {
+                let file_path = object.script.clone()?;
+                Some((file_path, ScriptTarget::Object(object_i)))
+            });
+        let light_scripts = scene
+            .lights
+            .iter()
+            .enumerate()
+            .filter_map(|(light_i, light)| {
+                let file_path = light.script.clone()?;
+                Some((file_path, ScriptTarget::Light(light_i)))
+            });
+        let mut runner = ScriptRunner {
+            engine: create_engine(),
+            scripts: object_scripts
+                .chain(light_scripts)
+                .map(|(file_path, target)| AttachedScript {
+                    file_path,
+                    target,
+                    ast: None,
+                    modified: None,
+                    this: Map::new(),
+                    failed: false,
+                })
+                .collect(),
+            elapsed: 0.0_f32,
+        };
+        for script_i in 0..runner.scripts.len() {
+            runner.compile(script_i);
+            runner.call(scene, script_i, "setup", None);
+        }
+        runner
+    }
+
+    /// Calls every script's `update` for a simulation step of `dt` seconds.
+    pub fn update(&mut self, scene: &mut Scene, dt: f32) {
+        self.elapsed += dt;
+        for script_i in 0..self.scripts.len() {
+            self.call(scene, script_i, "update", Some(dt));
+        }
+    }
+
+    /// Recompiles the scripts whose files changed since they were last compiled. `setup` is not
+    /// run again; reloading the scene does that.
+    pub fn reload_changed(&mut self) {
+        for script_i in 0..self.scripts.len() {
+            let script = &self.scripts[script_i];
+            let modified = fs::metadata(&script.file_path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            if modified.is_some() && modified != script.modified && self.compile(script_i) {
+                println!("Reloaded script {}", self.scripts[script_i].file_path);
+            }
+        }
+    }
+
+    /// Compiles script `script_i` from its file, keeping the previous version on failure.
+    fn compile(&mut self, script_i: usize) -> bool {
+        let script = &mut self.scripts[script_i];
+        script.modified = fs::metadata(&script.file_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        match self.engine.compile_file(PathBuf::from(&script.file_path)) {
+            Ok(ast) => {
+                script.ast = Some(ast);
+                script.failed = false;
+                true
+            }
+            Err(e) => {
+                eprintln!("Failed compiling script {}: {}", script.file_path, e);
+                false
+            }
+        }
+    }
+
+    /// Calls `function` of script `script_i` with its target as `this`, if the script defines
+    /// it, then copies the properties back into `scene`.
+    fn call(&mut self, scene: &mut Scene, script_i: usize, function: &str, dt: Option<f32>) {
+        let script = &mut self.scripts[script_i];
+        let ast = match (&script.ast, script.failed) {
+            (Some(ast), false) => ast,
+            _ => return,
+        };
+        let param_count = if dt.is_some() { 1 } else { 0 };
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == param_count)
+        {
+            return;
+        }
+        write_properties(scene, script.target, self.elapsed, &mut script.this);
+        let mut this = Dynamic::from_map(script.this.clone());
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut this);
+        let mut scope = Scope::new();
+        let result = match dt {
+            Some(dt) => self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut scope,
+                ast,
+                function,
+                (dt,),
+            ),
+            None => {
+                self.engine
+                    .call_fn_with_options::<Dynamic>(options, &mut scope, ast, function, ())
+            }
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "Script {} failed in {}: {}; stopped until the file changes",
+                script.file_path, function, e
+            );
+            script.failed = true;
+            return;
+        }
+        match this.try_cast::<Map>() {
+            Some(map) => {
+                read_properties(&map, script.target, scene);
+                script.this = map;
+            }
+            None => {
+                eprintln!(
+                    "Script {} replaced `this` in {}; stopped until the file changes",
+                    script.file_path, function
+                );
+                script.failed = true;
+            }
+        }
+    }
+}
+
+/// The engine with the math scripts get on top of Rhai's own.
+fn create_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .register_type_with_name::<Spline>("Spline")
+        .register_fn(
+            "spline",
+            |points: Array| -> Result<Spline, Box<EvalAltResult>> {
+                let points = points
+                    .iter()
+                    .map(|point| {
+                        to_vec3(point)
+                            .ok_or_else(|| "Spline points are [x, y, z] arrays".to_string())
+                    })
+                    .collect::<Result<Vec<Vec3>, String>>()?;
+                Ok(Spline::catmull_rom(points)?)
+            },
+        )
+        .register_fn("point", |spline: &mut Spline, t: f32| {
+            from_vec3(&spline.point(t.clamp(0.0_f32, 1.0_f32)))
+        })
+        .register_fn("point_at_distance", |spline: &mut Spline, distance: f32| {
+            from_vec3(&spline.point_at_distance(distance))
+        })
+        .register_get("length", |spline: &mut Spline| spline.length());
+    engine
+}
+
+/// Fills `this` with the properties of `target` in `scene`.
+fn write_properties(scene: &Scene, target: ScriptTarget, elapsed: f32, this: &mut Map) {
+    this.insert("time".into(), Dynamic::from_float(elapsed));
+    match target {
+        ScriptTarget::Object(object_i) => {
+            let object = &scene.objects[object_i];
+            let transform = &object.transform;
+            this.insert("name".into(), object.name.clone().into());
+            this.insert("position".into(), from_array3(&transform.position));
+            this.insert(
+                "rotation_axis".into(),
+                from_array3(&transform.rotation_axis()),
+            );
+            this.insert(
+                "rotation_degrees".into(),
+                Dynamic::from_float(transform.rotation_degrees()),
+            );
+            this.insert("scale".into(), from_array3(&transform.scale));
+        }
+        ScriptTarget::Light(light_i) => {
+            let light = &scene.lights[light_i];
+            this.insert("name".into(), light.name.clone().into());
+            this.insert("position".into(), from_vec3(&light.kind.position()));
+            let color = light.color.to_array();
+            this.insert(
+                "color".into(),
+                Dynamic::from_array(color.iter().map(|c| Dynamic::from_float(*c)).collect()),
+            );
+            if let LightKind::Point { range, .. } | LightKind::Spot { range, .. } = light.kind {
+                this.insert("range".into(), Dynamic::from_float(range));
+            }
+        }
+    }
+}
+
+/// Copies the properties a script may change from `this` back into `scene`. Missing or
+/// mistyped ones are left alone.
+fn read_properties(this: &Map, target: ScriptTarget, scene: &mut Scene) {
+    let vec3 = |name: &str| this.get(name).and_then(to_vec3);
+    let float = |name: &str| this.get(name).and_then(to_float);
+    match target {
+        ScriptTarget::Object(object_i) => {
+            let transform = &mut scene.objects[object_i].transform;
+            if let Some(position) = vec3("position") {
+                transform.position = [position.x, position.y, position.z];
+            }
+            let axis = vec3("rotation_axis")
+                .filter(|axis| glm::length(axis) > 0.0_f32)
+                .map(|axis| [axis.x, axis.y, axis.z]);
+            let degrees = float("rotation_degrees");
+            if axis.is_some() || degrees.is_some() {
+                transform.set_rotation(
+                    axis.unwrap_or_else(|| transform.rotation_axis()),
+                    degrees.unwrap_or_else(|| transform.rotation_degrees()),
+                );
+            }
+            if let Some(scale) = vec3("scale") {
+                transform.scale = [scale.x, scale.y, scale.z];
+            }
+        }
+        ScriptTarget::Light(light_i) => {
+            let light = &mut scene.lights[light_i];
+            if let Some(new_position) = vec3("position") {
+                match &mut light.kind {
+                    LightKind::Directional { position, .. }
+                    | LightKind::Point { position, .. }
+                    | LightKind::Spot { position, .. } => {
+                        *position = [new_position.x, new_position.y, new_position.z];
+                    }
+                }
+            }
+            if let Some(color) = this.get("color").and_then(to_color) {
+                light.color = color;
+            }
+            if let (
+                Some(new_range),
+                LightKind::Point { range, .. } | LightKind::Spot { range, .. },
+            ) = (float("range"), &mut light.kind)
+            {
+                *range = new_range.max(0.0_f32);
+            }
+        }
+    }
+}
+
+/// Numbers may be written as integers in scripts.
+fn to_float(value: &Dynamic) -> Option<f32> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|value| value as f32))
+}
+
+fn to_vec3(value: &Dynamic) -> Option<Vec3> {
+    let array = value.as_array_ref().ok()?;
+    match array.as_slice() {
+        [x, y, z] => Some(glm::vec3(to_float(x)?, to_float(y)?, to_float(z)?)),
+        _ => None,
+    }
+}
+
+/// `[r, g, b]` or `[r, g, b, a]`, linear.
+fn to_color(value: &Dynamic) -> Option<Color> {
+    let array = value.as_array_ref().ok()?;
+    match array.as_slice() {
+        [r, g, b] => Some(Color::rgb(to_float(r)?, to_float(g)?, to_float(b)?)),
+        [r, g, b, a] => Some(Color::new(
+            to_float(r)?,
+            to_float(g)?,
+            to_float(b)?,
+            to_float(a)?,
+        )),
+        _ => None,
+    }
+}
+
+fn from_vec3(v: &Vec3) -> Dynamic {
+    from_array3(&[v.x, v.y, v.z])
+}
+
+fn from_array3(v: &[f32; 3]) -> Dynamic {
+    Dynamic::from_array(v.iter().map(|c| Dynamic::from_float(*c)).collect())
+}
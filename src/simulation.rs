@@ -0,0 +1,87 @@
+/// Accumulates frame time and hands out whole simulation steps of a fixed length, so updates
+/// behave the same at any frame rate. Rendering blends the last two states by `alpha`.
+pub struct FixedTimestep {
+    pub step: f32,
+    accumulator: f32,
+    max_steps_per_frame: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(steps_per_second: f32) -> FixedTimestep {
+        FixedTimestep {
+            step: 1.0_f32 / steps_per_second,
+            accumulator: 0.0_f32,
+            max_steps_per_frame: 8,
+        }
+    }
+
+    /// Returns the number of steps to simulate for a frame that took `frame_time` seconds. Long
+    /// stalls are clamped so the simulation cannot spiral trying to catch up.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        let max_accumulated = self.step * self.max_steps_per_frame as f32;
+        self.accumulator = (self.accumulator + frame_time).min(max_accumulated);
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// How far, in [0, 1), rendering is between the previous and the current simulation state.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}
+
+/// Global simulation clock controls. Rendering and camera input keep running at real time while
+/// the simulation is paused, slowed down or stepped a frame at a time.
+pub struct Time {
+    pub paused: bool,
+    pub time_scale: f32,
+    step_requested: bool,
+}
+
+impl Time {
+    pub const MIN_TIME_SCALE: f32 = 1.0 / 16.0;
+    pub const MAX_TIME_SCALE: f32 = 4.0;
+
+    pub fn new() -> Time {
+        Time {
+            paused: false,
+            time_scale: 1.0_f32,
+            step_requested: false,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Multiplies the time scale by `factor`, clamped to the supported range.
+    pub fn scale_by(&mut self, factor: f32) {
+        self.time_scale =
+            (self.time_scale * factor).clamp(Self::MIN_TIME_SCALE, Self::MAX_TIME_SCALE);
+    }
+
+    /// Requests a single simulation step; only meaningful while paused.
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+
+    /// Converts a real frame time into the simulation time to advance by. `step` is the fixed
+    /// simulation step, used as the duration of a requested single step.
+    pub fn simulation_delta(&mut self, frame_time: f32, step: f32) -> f32 {
+        if self.paused {
+            if self.step_requested {
+                self.step_requested = false;
+                step
+            } else {
+                0.0_f32
+            }
+        } else {
+            self.step_requested = false;
+            frame_time * self.time_scale
+        }
+    }
+}
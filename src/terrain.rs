@@ -0,0 +1,199 @@
+// Heightmap terrain: heights sampled on a regular grid centered on the origin, split into square
+// patches that are culled and tessellated one by one.
+
+use crate::math::bounds::Aabb;
+use crate::math::easing::lerp;
+use crate::math::glm::{self, Vec3};
+use crate::math::noise::{Fbm, Noise};
+use crate::math::random::{poisson_disk_samples, Rng};
+
+/// Heights in world units over a square `size` units wide, `samples` per side.
+pub struct Heightmap {
+    pub samples: usize,
+    pub size: f32,
+    heights: Vec<f32>,
+}
+
+/// A square piece of the terrain, drawn with one call.
+#[derive(Clone, Debug)]
+pub struct TerrainPatch {
+    /// Corner with the smallest x and z
+    pub origin: [f32; 2],
+    /// Bounds of the heights within, for culling
+    pub bounds: Aabb,
+}
+
+/// A clump of grass blades standing on the terrain.
+#[derive(Clone, Copy, Debug)]
+pub struct GrassTuft {
+    pub position: Vec3,
+    pub scale: f32,
+    pub rotation_radians: f32,
+}
+
+pub struct Terrain {
+    pub heightmap: Heightmap,
+    pub patches_per_side: usize,
+    pub patches: Vec<TerrainPatch>,
+}
+
+impl Heightmap {
+    /// Rolling hills from fractal noise, reaching up to `height`. Valleys are flattened by
+    /// squaring, so the peaks stand out.
+    pub fn generate(samples: usize, size: f32, height: f32, seed: u64) -> Heightmap {
+        let noise = Noise::new(seed);
+        let fbm = Fbm::default();
+        // Features about a quarter of the terrain across
+        let frequency = 4.0_f32 / size;
+        let mut heights = Vec::with_capacity(samples * samples);
+        for row in 0..samples {
+            for column in 0..samples {
+                let x = column as f32 * frequency * size / (samples - 1) as f32;
+                let z = row as f32 * frequency * size / (samples - 1) as f32;
+                let value = (noise.fbm2(x, z, &fbm) * 0.5_f32 + 0.5_f32).clamp(0.0_f32, 1.0_f32);
+                heights.push(value * value * height);
+            }
+        }
+        Heightmap {
+            samples,
+            size,
+            heights,
+        }
+    }
+
+    /// Distance between neighboring samples.
+    pub fn spacing(&self) -> f32 {
+        self.size / (self.samples - 1) as f32
+    }
+
+    /// Rows of heights, from the smallest z on.
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    pub fn sample(&self, column: usize, row: usize) -> f32 {
+        let last = self.samples - 1;
+        self.heights[row.min(last) * self.samples + column.min(last)]
+    }
+
+    /// Height at world position `x`, `z`, interpolated between samples and clamped to the edges.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let last = (self.samples - 1) as f32;
+        let column = ((x + self.size * 0.5_f32) / self.spacing()).clamp(0.0_f32, last);
+        let row = ((z + self.size * 0.5_f32) / self.spacing()).clamp(0.0_f32, last);
+        let (column_0, row_0) = (column.floor() as usize, row.floor() as usize);
+        let (u, v) = (column.fract(), row.fract());
+        lerp(
+            lerp(
+                self.sample(column_0, row_0),
+                self.sample(column_0 + 1, row_0),
+                u,
+            ),
+            lerp(
+                self.sample(column_0, row_0 + 1),
+                self.sample(column_0 + 1, row_0 + 1),
+                u,
+            ),
+            v,
+        )
+    }
+
+    /// Surface normal at world position `x`, `z`, from central differences of `height_at`.
+    pub fn normal_at(&self, x: f32, z: f32) -> Vec3 {
+        let step = self.spacing();
+        glm::normalize(&glm::vec3(
+            self.height_at(x - step, z) - self.height_at(x + step, z),
+            2.0_f32 * step,
+            self.height_at(x, z - step) - self.height_at(x, z + step),
+        ))
+    }
+
+    /// Surface normal at a sample, from central differences of its neighbors.
+    pub fn normal(&self, column: usize, row: usize) -> Vec3 {
+        let left = self.sample(column.saturating_sub(1), row);
+        let right = self.sample(column + 1, row);
+        let back = self.sample(column, row.saturating_sub(1));
+        let front = self.sample(column, row + 1);
+        glm::normalize(&glm::vec3(
+            left - right,
+            2.0_f32 * self.spacing(),
+            back - front,
+        ))
+    }
+}
+
+impl Terrain {
+    /// Splits `heightmap` into `patches_per_side` squared patches.
+    pub fn new(heightmap: Heightmap, patches_per_side: usize) -> Terrain {
+        let patch_size = heightmap.size / patches_per_side as f32;
+        let samples_per_patch = (heightmap.samples - 1) / patches_per_side;
+        let half_size = heightmap.size * 0.5_f32;
+        let mut patches = Vec::with_capacity(patches_per_side * patches_per_side);
+        for patch_row in 0..patches_per_side {
+            for patch_column in 0..patches_per_side {
+                let (mut min_height, mut max_height) = (f32::MAX, f32::MIN);
+                for row in 0..=samples_per_patch {
+                    for column in 0..=samples_per_patch {
+                        let height = heightmap.sample(
+                            patch_column * samples_per_patch + column,
+                            patch_row * samples_per_patch + row,
+                        );
+                        min_height = min_height.min(height);
+                        max_height = max_height.max(height);
+                    }
+                }
+                let origin = [
+                    patch_column as f32 * patch_size - half_size,
+                    patch_row as f32 * patch_size - half_size,
+                ];
+                patches.push(TerrainPatch {
+                    origin,
+                    bounds: Aabb::new(
+                        glm::vec3(origin[0], min_height, origin[1]),
+                        glm::vec3(origin[0] + patch_size, max_height, origin[1] + patch_size),
+                    ),
+                });
+            }
+        }
+        Terrain {
+            heightmap,
+            patches_per_side,
+            patches,
+        }
+    }
+
+    pub fn patch_size(&self) -> f32 {
+        self.heightmap.size / self.patches_per_side as f32
+    }
+}
+
+/// Tufts of grass scattered by Poisson disk sampling at least `min_distance` apart, wherever the
+/// ground is below `max_height` and flat enough for grass to grow; rock and snow stay bare.
+pub fn scatter_grass(
+    heightmap: &Heightmap,
+    min_distance: f32,
+    max_height: f32,
+    seed: u64,
+) -> Vec<GrassTuft> {
+    let mut rng = Rng::new(seed);
+    let half_size = heightmap.size * 0.5_f32;
+    let points = poisson_disk_samples(
+        &mut rng,
+        glm::vec2(heightmap.size, heightmap.size),
+        min_distance,
+    );
+    let mut tufts = Vec::with_capacity(points.len());
+    for point in points {
+        let (x, z) = (point.x - half_size, point.y - half_size);
+        let height = heightmap.height_at(x, z);
+        if height > max_height || heightmap.normal_at(x, z).y < 0.85_f32 {
+            continue;
+        }
+        tufts.push(GrassTuft {
+            position: glm::vec3(x, height, z),
+            scale: rng.range(0.7_f32, 1.3_f32),
+            rotation_radians: rng.range(0.0_f32, std::f32::consts::PI),
+        });
+    }
+    tufts
+}
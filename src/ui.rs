@@ -0,0 +1,185 @@
+use crate::ogl::context::GlContext;
+use crate::ogl::graphics::{ShaderProgram, UniformValue};
+use crate::ogl::ui_painter::UiPainter;
+use crate::platform::{Action, Event, Key, MouseButton, WindowBackend};
+
+/// Immediate mode UI drawn over the scene with egui. Window events pass through `handle_events`
+/// first, which keeps the pointer and keyboard events the UI is using from reaching the app, so
+/// clicking a button does not also pick an object. While the cursor is captured for mouse look the
+/// UI is shown but cannot be interacted with.
+pub struct Ui {
+    ctx: egui::CtxRef,
+    painter: UiPainter,
+    events: Vec<egui::Event>,
+    pointer_pos: egui::Pos2,
+    pointer_captured: bool,
+}
+
+impl Ui {
+    pub fn new(context: &GlContext) -> Result<Ui, String> {
+        Ok(Ui {
+            ctx: egui::CtxRef::default(),
+            painter: UiPainter::new(context)?,
+            events: vec![],
+            pointer_pos: egui::Pos2::ZERO,
+            pointer_captured: false,
+        })
+    }
+
+    /// Queues `events` for the next `run` and returns the ones the UI did not take.
+    pub fn handle_events(&mut self, window: &impl WindowBackend, events: Vec<Event>) -> Vec<Event> {
+        let pointer_captured = window.is_cursor_captured();
+        if pointer_captured && !self.pointer_captured {
+            self.events.push(egui::Event::PointerGone);
+        }
+        self.pointer_captured = pointer_captured;
+        let modifiers = window.modifiers();
+        let modifiers = egui::Modifiers {
+            alt: modifiers.alt,
+            ctrl: modifiers.control,
+            shift: modifiers.shift,
+            mac_cmd: false,
+            command: modifiers.control,
+        };
+        // Both reflect the previous frame's layout, which is what the user was looking at
+        let wants_pointer = !pointer_captured
+            && (self.ctx.wants_pointer_input() || self.ctx.is_pointer_over_area());
+        let wants_keyboard = self.ctx.wants_keyboard_input();
+
+        let mut unused = vec![];
+        for event in events {
+            let used = match event {
+                Event::CursorPos(x, y) if !pointer_captured => {
+                    self.pointer_pos = egui::pos2(x as f32, y as f32);
+                    self.events
+                        .push(egui::Event::PointerMoved(self.pointer_pos));
+                    wants_pointer
+                }
+                Event::MouseButton(button, action) if !pointer_captured => {
+                    let button = match button {
+                        MouseButton::Left => egui::PointerButton::Primary,
+                        MouseButton::Right => egui::PointerButton::Secondary,
+                        MouseButton::Middle => egui::PointerButton::Middle,
+                    };
+                    self.events.push(egui::Event::PointerButton {
+                        pos: self.pointer_pos,
+                        button,
+                        pressed: action == Action::Press,
+                        modifiers,
+                    });
+                    wants_pointer
+                }
+                Event::Key(key, action) => {
+                    if let Some(key) = to_egui_key(key) {
+                        self.events.push(egui::Event::Key {
+                            key,
+                            pressed: action != Action::Release,
+                            modifiers,
+                        });
+                    }
+                    wants_keyboard
+                }
+                Event::Char(character) if !character.is_control() => {
+                    self.events.push(egui::Event::Text(character.to_string()));
+                    wants_keyboard
+                }
+                _ => false,
+            };
+            if !used {
+                unused.push(event);
+            }
+        }
+        unused
+    }
+
+    /// Lays out the UI with `build` and draws it into the current framebuffer.
+    pub fn run(&mut self, window: &impl WindowBackend, build: impl FnOnce(&egui::CtxRef)) {
+        let (width, height) = window.size();
+        let framebuffer_size = window.framebuffer_size();
+        // Points are the window units cursor positions come in
+        let pixels_per_point = if width > 0 {
+            framebuffer_size.0 as f32 / width as f32
+        } else {
+            1.0
+        };
+        let raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(width as f32, height as f32),
+            )),
+            pixels_per_point: Some(pixels_per_point),
+            time: Some(window.time()),
+            events: self.events.drain(..).collect(),
+            ..Default::default()
+        };
+        self.ctx.begin_frame(raw_input);
+        build(&self.ctx);
+        let (_output, shapes) = self.ctx.end_frame();
+        let meshes = self.ctx.tessellate(shapes);
+        self.painter.update_font_texture(&self.ctx.texture());
+        self.painter
+            .paint(&meshes, framebuffer_size, pixels_per_point);
+    }
+}
+
+/// Editors for the float uniforms of `program`, applying edits as they are made. Vectors named
+/// like colors get a color picker. Values are read back from the program every frame, so they
+/// also show changes made elsewhere.
+pub fn uniform_panel(ui: &mut egui::Ui, program: &ShaderProgram) {
+    for mut uniform in program.active_uniforms() {
+        let is_color = uniform.name.contains("color") || uniform.name.contains("tint");
+        let changed = ui
+            .horizontal(|ui| {
+                ui.label(&uniform.name);
+                match &mut uniform.value {
+                    UniformValue::Float(value) => drag_values(ui, std::slice::from_mut(value)),
+                    UniformValue::Vec2(values) => drag_values(ui, values),
+                    UniformValue::Vec3(values) if is_color => {
+                        ui.color_edit_button_rgb(values).changed()
+                    }
+                    UniformValue::Vec3(values) => drag_values(ui, values),
+                    UniformValue::Vec4(values) if is_color => {
+                        ui.color_edit_button_rgba_unmultiplied(values).changed()
+                    }
+                    UniformValue::Vec4(values) => drag_values(ui, values),
+                }
+            })
+            .inner;
+        if changed {
+            program.set_uniform(&uniform);
+        }
+    }
+}
+
+fn drag_values(ui: &mut egui::Ui, values: &mut [f32]) -> bool {
+    let mut changed = false;
+    for value in values {
+        changed |= ui.add(egui::DragValue::new(value).speed(0.01)).changed();
+    }
+    changed
+}
+
+fn to_egui_key(key: Key) -> Option<egui::Key> {
+    match key {
+        Key::Enter => Some(egui::Key::Enter),
+        Key::Escape => Some(egui::Key::Escape),
+        Key::Tab => Some(egui::Key::Tab),
+        Key::Backspace => Some(egui::Key::Backspace),
+        Key::Up => Some(egui::Key::ArrowUp),
+        Key::Down => Some(egui::Key::ArrowDown),
+        Key::Left => Some(egui::Key::ArrowLeft),
+        Key::Right => Some(egui::Key::ArrowRight),
+        Key::Space => Some(egui::Key::Space),
+        Key::A => Some(egui::Key::A),
+        Key::S => Some(egui::Key::S),
+        Key::D => Some(egui::Key::D),
+        Key::W => Some(egui::Key::W),
+        Key::P => Some(egui::Key::P),
+        Key::R => Some(egui::Key::R),
+        Key::V => Some(egui::Key::V),
+        Key::Num1 => Some(egui::Key::Num1),
+        Key::Num2 => Some(egui::Key::Num2),
+        Key::Num3 => Some(egui::Key::Num3),
+        _ => None,
+    }
+}
@@ -0,0 +1,50 @@
+// How often the title's stats are refreshed; faster would only make them unreadable
+const UPDATE_INTERVAL: f32 = 1.0;
+
+/// Fills in a window title template with stats averaged over the last second. `{fps}`,
+/// `{frame_time}` (milliseconds) and `{resolution}` are replaced, other text is kept as is.
+pub struct WindowTitle {
+    template: String,
+    frames: u32,
+    elapsed: f32,
+}
+
+impl WindowTitle {
+    pub fn new(template: &str) -> WindowTitle {
+        WindowTitle {
+            template: template.to_string(),
+            frames: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    /// The title before any frame has been timed, with placeholders for the stats.
+    pub fn initial(&self, framebuffer_size: (i32, i32)) -> String {
+        self.format("-", "-", framebuffer_size)
+    }
+
+    /// Counts a frame and returns a new title once a second has passed since the last one.
+    pub fn update(&mut self, delta_time: f32, framebuffer_size: (i32, i32)) -> Option<String> {
+        self.frames += 1;
+        self.elapsed += delta_time;
+        if self.elapsed < UPDATE_INTERVAL {
+            return None;
+        }
+        let fps = self.frames as f32 / self.elapsed;
+        let frame_time_ms = self.elapsed * 1000.0 / self.frames as f32;
+        self.frames = 0;
+        self.elapsed = 0.0;
+        Some(self.format(
+            &format!("{:.0}", fps),
+            &format!("{:.2}", frame_time_ms),
+            framebuffer_size,
+        ))
+    }
+
+    fn format(&self, fps: &str, frame_time: &str, (width, height): (i32, i32)) -> String {
+        self.template
+            .replace("{fps}", fps)
+            .replace("{frame_time}", frame_time)
+            .replace("{resolution}", &format!("{}x{}", width, height))
+    }
+}